@@ -0,0 +1,553 @@
+//! A run set up in Rust instead of a scene file: every setting of the
+//! library's `Config` as a constant below, each documented with an example,
+//! including those scene files do not cover yet.  Edit the constants and
+//! `cargo run --release --example scene` to simulate something else.  As
+//! they stand they describe the scene the `fdtd_3d` binary runs without -c
+//! (scenes/default.toml).
+
+use std::io;
+use std::process::ExitCode;
+
+use fdtd_3d::*;
+
+use adapters::PowerPreference;
+use bands::BandDiagram;
+use config::{Boundary, OutputFormat, Verbosity};
+use conformal::ConformalPec;
+use cosim::CircuitPort;
+use dft::DftMonitor;
+use divergence::DivergenceCheck;
+use flux::{FluxBox, FluxMonitor};
+use gpu::MAX_CELLS;
+use geometry::Object;
+use grid::Grid;
+use harminv::HarmonicInversion;
+use kspace::KSpaceMonitor;
+use lumped::LumpedElement;
+use materials::CoefficientStorage;
+use meshing::MeshSpec;
+use modes::ModeMonitor;
+use modulation::ModulatedRegion;
+use monitors::Monitor;
+use moving_window::MovingWindow;
+use pattern::PatternCuts;
+use phantom::Phantom;
+use ports::FeedPort;
+use precision::Precision;
+use probes::{Location, Probe, ProbeOutput, Quantity};
+use purcell::PurcellDipole;
+use random_media::RandomRegion;
+use rcs::Rcs;
+use reduced::Mode;
+use reflectance::ReflectionTransmission;
+use rough_surface::RoughSurface;
+use sar::Sar;
+use sheets::{ConductiveSheet, ThinLayer};
+use shielding::Shielding;
+use sibc::SibcObject;
+use slices::SliceImages;
+use smoothing::Smoothing;
+use snapshots::Snapshots;
+use sources::Waveform;
+use spectra::ProbeSpectra;
+use stability::Scheme;
+use subgrid::Subgrid;
+use tdr::Tdr;
+use thermal::Thermal;
+use touchstone::Touchstone;
+use unit_cell::UnitCell;
+use wires::ThinWire;
+
+// Used by the examples in the comments below.
+#[allow(unused_imports)]
+use materials::Material;
+
+// ── simulation parameters ────────────────────────────────────────────
+
+const NX: u32 = 64;
+const NY: u32 = 64;
+const NZ: u32 = 64;
+const MAX_TIME: u32 = 300;
+
+// Grid spacing  (uniform cubic cells)
+const DX: f64 = 1e-3; // 1 mm
+const DY: f64 = DX;
+const DZ: f64 = DX;
+
+// Time step (Courant condition: Sc = c·Δt/Δ ≤ 1/√3 for 3D)
+const SC: f64 = 0.5; // Courant number
+const DT: f64 = SC * DX / C0;
+
+// Source (Gaussian pulse at grid centre)
+const SRC_I: u32 = NX / 2;
+const SRC_J: u32 = NY / 2;
+const SRC_K: u32 = NZ / 2;
+const PULSE_WIDTH: f64 = 20.0;
+const PULSE_DELAY: f64 = 40.0;
+const SOURCE_WAVEFORM: Waveform = Waveform::Gaussian { width: PULSE_WIDTH, delay: PULSE_DELAY };
+
+// Probe location (slightly offset from source)
+const PROBE_I: u32 = NX / 2 + 10;
+const PROBE_J: u32 = NY / 2;
+const PROBE_K: u32 = NZ / 2;
+
+// Point probes (name, a cell or a physical point interpolated between the
+// Yee samples, quantity: a component or |E| / |H|).  Only the first, on a
+// cell, is followed by the reduced modes (in their own field) and by non-f32
+// precisions (as Ez); add entries to sample more points, e.g.
+//   Probe { name: "off_grid", at: Location::Point([0.0123, 0.0101, 0.0098]),
+//           quantity: Quantity::AbsH },
+const PROBES: &[Probe] = &[Probe {
+    name: "probe",
+    at: Location::Cell([PROBE_I, PROBE_J, PROBE_K]),
+    quantity: Quantity::Component(grid::Field::E(grid::Axis::Z)),
+}];
+
+// Steps between probe readbacks; samples are gathered on the GPU every step
+// and copied back a batch at a time.
+const PROBE_BATCH: u32 = 1;
+
+// Probe traces on disk (CSV or JSON lines, one file per probe, flushed every
+// step); the console shows a progress bar instead, and the per-step values
+// only with -v.  None keeps the traces for the analyses alone.
+const PROBE_OUTPUT: Option<ProbeOutput> = Some(ProbeOutput {
+    dir: "probes",
+    format: probes::ProbeFormat::Csv,
+});
+
+// Steps between reductions of the total field energy U = Σ(ε|E|² + μ|H|²)dV/2,
+// reported on the probe line of the step (and as an `energy` trace next to
+// the probe files), e.g. Some(10).  A steady rise flags an instability.
+const ENERGY_EVERY: Option<u32> = None;
+
+// Check of the fields every `every` steps for NaN, Inf, a component over
+// `max_field` (V/m) or tenfold growth at five checks in a row, stopping the
+// run with the step, cell, material and Δt margin (divergence.rs); `snapshot`
+// also writes the fields to MONITOR_DIR/diverged.  None to leave it out.
+const DIVERGENCE: Option<DivergenceCheck> =
+    Some(DivergenceCheck { every: 100, max_field: 1e12, snapshot: false });
+
+// Resonances of the ringing probe signals by harmonic inversion: frequency,
+// Q, amplitude and phase of every decaying mode in the band, written to
+// MONITOR_DIR/<probe>_resonances.csv, e.g. for a cavity's modes up to 10 GHz:
+//   Some(HarmonicInversion { f_min: 1e9, f_max: 10e9, tolerance: 1e-4 })
+const HARMINV: Option<HarmonicInversion> = None;
+
+// FFT spectra of the whole probe traces, next to the probe files (or in
+// MONITOR_DIR without PROBE_OUTPUT) as <probe>_spectrum.csv, e.g. padded to
+// at least 4× the record, Hann-windowed, in dB and cut at 10 GHz:
+//   Some(ProbeSpectra { padding: 4, window: spectra::Window::Hann, segments: 1,
+//                       db: true, f_max: Some(10e9) })
+const PROBE_SPECTRA: Option<ProbeSpectra> = None;
+
+// Graded cell widths per axis (None = uniform).  DX/DY/DZ should then be the
+// finest width so DT stays stable, e.g. 0.25 mm cells on x ∈ cells 28..36
+// grading ×1.2 out to 1 mm (with DX = 0.25e-3):
+//   const X_WIDTHS: [f64; NX as usize] = grid::graded_widths(1e-3, 0.25e-3, 28, 36, 1.2);
+//   const GRADED: [Option<&[f64]>; 3] = [Some(&X_WIDTHS), None, None];
+const GRADED: [Option<&[f64]>; 3] = [None, None, None];
+
+const GRID: Grid = Grid {
+    nx: NX, ny: NY, nz: NZ, dx: DX, dy: DY, dz: DZ, dt: DT, graded: GRADED, origin: [0.0; 3],
+};
+
+// Outer boundaries along x, y and z: PEC walls, or periodic so that the
+// fields wrap around (uniform f32 Yee update only), e.g. a slab infinite in
+// x and y:
+//   const BOUNDARIES: [Boundary; 3] = [Boundary::Periodic, Boundary::Periodic, Boundary::Pec];
+const BOUNDARIES: [Boundary; 3] = [Boundary::Pec; 3];
+
+// Homogeneous objects, painted in order over the vacuum background.
+const OBJECTS: &[Object] = &[];
+
+// Interface treatment for OBJECTS (None = staircase by cell centre).
+const SMOOTHING: Smoothing = Smoothing::None;
+
+// Labeled voxel phantoms (label → material or tissue; other labels keep the
+// background), e.g. a 2 mm NIfTI head model evaluated at 900 MHz:
+//   Phantom { source: phantom::VoxelSource::File("head.nii"), origin: [0.0; 3],
+//             materials: &[],
+//             tissues: &[(1, tissues::Tissue::SkinDry), (2, tissues::Tissue::BoneCortical),
+//                        (3, tissues::Tissue::BrainGreyMatter)],
+//             tissue_model: tissues::TissueModel::SingleFrequency(900e6) }
+const PHANTOMS: &[Phantom] = &[];
+
+// SAR in the PHANTOMS cells of a box: local σ|E|²/2ρ, 1 g and 10 g cube
+// averages as volumes and peak values (MONITOR_DIR/<name>_sar*, see sar.rs),
+// the fields normalised by the spectrum of the excitation waveform, e.g. the
+// head above at 900 MHz:
+//   Sar { name: "head", lo: [8, 8, 8], hi: [NX - 8, NY - 8, NZ - 8], frequencies: &[900e6],
+//         waveform: SOURCE_WAVEFORM, densities: &[] }
+const SAR: &[Sar] = &[];
+
+// Thermal stage after the EM run: the dissipation of SAR box `sar` at its
+// frequency `frequency` heats the phantom by the Pennes bioheat equation or
+// plain diffusion (MONITOR_DIR/<name>_thermal.*, see thermal.rs), e.g. six
+// minutes of exposure with the fields scaled to 10× the power:
+//   Some(Thermal { name: "head", sar: 0, frequency: 0, scale: 10.0,
+//                  model: thermal::ThermalModel::Pennes, materials: &[],
+//                  duration: 360.0, record_every: 10.0 })
+const THERMAL: Option<Thermal> = None;
+
+// Random media, e.g. a Gaussian-correlated slab (ε_r = 4 ± 0.5, ℓ = 3 mm):
+//   RandomRegion {
+//       shape: geometry::Shape::Box { min: [0.040, 0.0, 0.0], max: [0.056, 0.064, 0.064] },
+//       medium: random_media::RandomMedium::GaussianCorrelated {
+//           mean: Material { eps_r: 4.0, ..Material::VACUUM }, eps_std: 0.5, corr_len: 3e-3 },
+//       seed: 1 }
+const RANDOM_REGIONS: &[RandomRegion] = &[];
+
+// Rough two-material interfaces, e.g. a sea surface (ε_r = 80, σ = 4 S/m)
+// at z = 16 mm with 1 mm RMS height and 8 mm correlation length:
+//   RoughSurface { normal: grid::Axis::Z, mean_height: 0.016, rms: 1e-3, corr_len: 8e-3,
+//                  spectrum: rough_surface::Spectrum::Gaussian,
+//                  below: Material { eps_r: 80.0, sigma: 4.0, ..Material::VACUUM },
+//                  above: Material::VACUUM, seed: 7 }
+const ROUGH_SURFACES: &[RoughSurface] = &[];
+
+// Materials with time-varying ε/σ, e.g. a photoconductive switch that turns
+// conducting (σ = 100 S/m) between steps 80 and 200:
+//   ModulatedRegion {
+//       shape: geometry::Shape::Box { min: [0.040, 0.0, 0.0], max: [0.042, 0.064, 0.064] },
+//       base: Material::VACUUM,
+//       modulation: modulation::Modulation::Switch {
+//           to: Material { sigma: 100.0, ..Material::VACUUM }, on: 80, off: 200 } }
+const MODULATED: &[ModulatedRegion] = &[];
+
+// Thin conductive sheets (e.g. a graphene layer 8 cells behind the probe):
+//   ConductiveSheet { normal: grid::Axis::X, index: PROBE_I + 8, u: (0, NY), v: (0, NZ),
+//                     model: sheets::SheetModel::graphene(0.5, 1e-12, 300.0) }
+const SHEETS: &[ConductiveSheet] = &[];
+
+// Sub-cell dielectric/conductive layers (e.g. a 0.2 mm ε_r = 4 coating):
+//   ThinLayer { normal: grid::Axis::Z, center: 0.040, thickness: 2e-4, u: (0, NX), v: (0, NY),
+//               material: Material { eps_r: 4.0, ..Material::VACUUM } }
+const THIN_LAYERS: &[ThinLayer] = &[];
+
+// Good conductors with skin-effect losses via the SIBC, e.g. a copper block:
+//   SibcObject { shape: geometry::Shape::Box { min: [0.044, 0.020, 0.020],
+//                max: [0.050, 0.044, 0.044] }, sigma: 5.8e7, mu_r: 1.0 }
+const SIBC_OBJECTS: &[SibcObject] = &[];
+
+// Curved PEC scatterers with the Dey–Mittra conformal scheme, e.g. a sphere
+// of radius 10 mm (faces less than 5 % open are frozen for stability):
+//   ConformalPec { shape: geometry::Shape::Sphere { center: [0.048, 0.032, 0.032],
+//                  radius: 0.010 }, min_fraction: 0.05 }
+const CONFORMAL_PEC: &[ConformalPec] = &[];
+
+// Thin PEC wires with sub-cell radius, e.g. a 40-cell dipole along z with a
+// 0.1 mm radius (leave the feed gap edge to a source):
+//   ThinWire { axis: grid::Axis::Z, at: (SRC_I, SRC_J), span: (SRC_K - 20, SRC_K), radius: 1e-4 }
+const WIRES: &[ThinWire] = &[];
+
+// Lumped R/L/C elements and resistive voltage sources on single edges, e.g.
+// a 50 Ω feed in the gap of the dipole above:
+//   LumpedElement { axis: grid::Axis::Z, cell: [SRC_I, SRC_J, SRC_K],
+//                   kind: lumped::LumpedKind::VoltageSource {
+//                       r: 50.0, v: 1.0, waveform: SOURCE_WAVEFORM } }
+const LUMPED: &[LumpedElement] = &[];
+
+// Feed ports reporting Z_in(f) and S11(f) (CSV in MONITOR_DIR, see ports.rs)
+// on a lumped source edge, e.g. the 50 Ω dipole feed above up to 3 GHz:
+//   FeedPort { name: "feed", axis: grid::Axis::Z, cell: [SRC_I, SRC_J, SRC_K],
+//              z0: 50.0, f_max: 3e9 },
+const PORTS: &[FeedPort] = &[];
+
+// Touchstone copy of the complete S-matrix of PORTS (MONITOR_DIR/sparams.sNp),
+// e.g. in GHz as real/imaginary pairs referred to 50 Ω:
+//   Some(Touchstone { unit: touchstone::FrequencyUnit::GHz,
+//                     format: touchstone::DataFormat::RealImaginary, reference: 50.0 })
+const TOUCHSTONE: Option<Touchstone> = None;
+
+// Time-domain reflectometry: a matched step source on the edge of PORTS[port]
+// replaces the point source, and the reflected wave gives the impedance along
+// the line (MONITOR_DIR/<name>_tdr.csv, see tdr.rs), e.g. a 1 V step rising
+// over 20 steps into a microstrip of ε_eff = 3.3:
+//   Some(Tdr { name: "trace", port: 0, amplitude: 1.0, rise: 20.0, eps_eff: 3.3 })
+const TDR: Option<Tdr> = None;
+
+// Circuits co-simulated across single edges, exchanging V and I every step
+// (MONITOR_DIR/<name>_circuit.csv, see cosim.rs and circuit.rs), e.g. a diode
+// detector with an RC load in the dipole gap:
+//   CircuitPort { name: "detector", axis: grid::Axis::Z, cell: [SRC_I, SRC_J, SRC_K],
+//                 netlist: "D1 in out\nC1 out 0 10p\nR1 out 0 10k\nR2 in 0 1k",
+//                 node: "in" },
+const CIRCUITS: &[CircuitPort] = &[];
+
+// Locally refined regions (objects only), e.g. a 2:1 child grid over parent
+// cells 40..56 × 24..40 × 24..40 around a small scatterer:
+//   Subgrid { lo: [40, 24, 24], hi: [56, 40, 40], ratio: 2 }
+const SUBGRIDS: &[Subgrid] = &[];
+
+// Window following the pulse along +x (objects only), e.g. moving at c once
+// the pulse has crossed most of the grid:
+//   Some(MovingWindow { start: 100, velocity: C0 })
+const MOVING_WINDOW: Option<MovingWindow> = None;
+
+// Wavelength-driven mesh for OBJECTS, reported before the run (the grid
+// above stays as configured), e.g. 15 cells per wavelength up to 30 GHz:
+//   Some(MeshSpec { f_max: 30e9, cells_per_wavelength: 15.0, padding: 0.5,
+//                   courant: SC, duration: 1e-9 })
+const AUTO_MESH: Option<MeshSpec> = None;
+
+// Update scheme of the 3D run: Yee (second order), Yee24 (fourth order in
+// space on uniform meshes, Courant limit × 6/7), Hie { axis } (implicit along
+// a thin layer's normal, limit from the other two axes; set DT_SAFETY to use
+// it; objects only) or Adi { multiple } (implicit, unconditionally stable,
+// Δt = multiple × the Yee limit; objects only).
+const SCHEME: Scheme = Scheme::Yee;
+
+// Field precision of the 3D update: F32, F16 (half-precision storage, f32
+// arithmetic), F64 (native SHADER_F64, falling back to emulated
+// double-single on adapters without it) or DoubleSingle; the non-f32 paths
+// run the Yee scheme on plain objects.
+const PRECISION: Precision = Precision::F32;
+
+// Full-volume snapshots of selected components to disk (layout in
+// snapshots.rs), raw, as VTK files for ParaView, as a Zarr store
+// (SnapshotFormat::Zarr { chunk: [32, 32, 32] }) or as CF NetCDF, e.g. Ez
+// and Hy every 50 steps:
+//   Some(Snapshots { every: 50, dir: "snapshots", format: snapshots::SnapshotFormat::Vtk,
+//                    fields: &[grid::Field::E(grid::Axis::Z), grid::Field::H(grid::Axis::Y)] })
+const SNAPSHOTS: Option<Snapshots> = None;
+
+// Line and plane monitors recording every cell of one component over time
+// (layout and images in monitors.rs), e.g. Ez along x through the source
+// every step and an Hz movie of the mid z plane every 5 steps:
+//   Monitor { name: "ez_line", field: grid::Field::E(grid::Axis::Z),
+//             span: monitors::Span::Line { axis: grid::Axis::X, through: [0, SRC_J, SRC_K] },
+//             every: 1, batch: 50 },
+//   Monitor { name: "hz_plane", field: grid::Field::H(grid::Axis::Z),
+//             span: monitors::Span::Plane { normal: grid::Axis::Z, index: NZ / 2 },
+//             every: 5, batch: 4 },
+const MONITORS: &[Monitor] = &[];
+const MONITOR_DIR: &str = "monitors";
+
+// Probe traces, snapshots and DFT spectra in one MATLAB v7.3 file (variable
+// names in sinks.rs), e.g. Some("monitors/results.mat")
+const MAT_FILE: Option<&str> = None;
+
+// Spatial power spectra of planes, transformed and accumulated on the GPU
+// (MONITOR_DIR/<name>_kspace.csv and .png, see kspace.rs), e.g. the angular
+// spectrum of Ez on a z plane above the source every 2 steps:
+//   KSpaceMonitor { name: "ez_k", field: grid::Field::E(grid::Axis::Z),
+//                   normal: grid::Axis::Z, index: SRC_K + 10, every: 2 },
+const KSPACE_MONITORS: &[KSpaceMonitor] = &[];
+
+// Running-DFT frequency monitors over a point, plane or box of cells,
+// written to MONITOR_DIR at the end of the run (layout in dft.rs), e.g. the
+// complex Ez on the mid z plane at 1, 2 and 3 GHz:
+//   DftMonitor { name: "ez_mid", fields: &[grid::Field::E(grid::Axis::Z)],
+//                region: dft::Region::Plane { normal: grid::Axis::Z, index: NZ / 2 },
+//                frequencies: &[1e9, 2e9, 3e9] },
+const DFT_MONITORS: &[DftMonitor] = &[];
+
+// Poynting flux through axis-aligned rectangles, positive along +normal:
+// time-domain power every step to MONITOR_DIR/<name>.csv and, with
+// frequencies, the spectrum to <name>_spectrum.csv.  E.g. the power through
+// a z plane 20 cells above the source:
+//   FluxMonitor { name: "top", normal: grid::Axis::Z, index: SRC_K + 20,
+//                 u: (10, NX - 10), v: (10, NY - 10), frequencies: &[1e9, 2e9] },
+const FLUX_MONITORS: &[FluxMonitor] = &[];
+
+// Closed flux boxes reporting the net outgoing power, e.g. the radiated
+// power of the source over 1–3 GHz (files as for FLUX_MONITORS):
+//   FluxBox { name: "radiated", lo: [SRC_I - 8, SRC_J - 8, SRC_K - 8],
+//             hi: [SRC_I + 8, SRC_J + 8, SRC_K + 8], frequencies: &[1e9, 2e9, 3e9] },
+const FLUX_BOXES: &[FluxBox] = &[];
+
+// Radar cross sections from the near-to-far-field transform of a box around
+// the scatterer, monostatic and (with `step` > 0) bistatic, written to
+// MONITOR_DIR.  The scatterer must be lit by a plane wave whose E at the box
+// centre follows `incident`, between absorbing walls (see rcs.rs; none exist
+// yet, so validation rejects RCS runs), e.g. a wave arriving from +x:
+//   Rcs { name: "sphere", lo: [20, 20, 20], hi: [44, 44, 44], frequencies: &[2e9, 3e9],
+//         incidence: (90.0, 0.0), incident: SOURCE_WAVEFORM, step: 5.0 },
+const RCS: &[Rcs] = &[];
+
+// E- and H-plane gain cuts of an antenna (dBi vs angle from boresight, CSV
+// and polar PNG per frequency in MONITOR_DIR, see pattern.rs) from a box
+// around it, e.g. a z-polarised antenna radiating along +x:
+//   PatternCuts { name: "antenna", lo: [20, 20, 20], hi: [44, 44, 44],
+//                 frequencies: &[2.4e9], boresight: grid::Axis::X,
+//                 polarization: grid::Axis::Z },
+const PATTERNS: &[PatternCuts] = &[];
+
+// Reflectance / transmittance of the structure between two flux planes,
+// normalised by an automatic reference run without it (R, T and A = 1 − R − T
+// in MONITOR_DIR/<name>_rt.csv, see reflectance.rs), e.g. a slab between
+// z planes 16 and 48 lit from below:
+//   ReflectionTransmission { name: "slab", normal: grid::Axis::Z, front: 16, back: 48,
+//                            u: (1, NX - 1), v: (1, NY - 1), frequencies: &[5e9, 10e9] },
+const REFLECTANCE: &[ReflectionTransmission] = &[];
+
+// Waveguide mode-overlap monitors: forward/backward amplitudes and powers of
+// each mode through a rectangle (MONITOR_DIR/<name>_modes.csv, see modes.rs),
+// the TE/TM modes of a metal guide walled by the rectangle's edges or profiles
+// stored by an earlier run, e.g. TE10 and TE20 of an air-filled guide:
+//   ModeMonitor { name: "out", normal: grid::Axis::X, index: 48, u: (16, 48),
+//                 v: (24, 40), frequencies: &[6e9, 8e9], eps_r: 1.0,
+//                 modes: &[modes::ModeProfile::Te { m: 1, n: 0 },
+//                         modes::ModeProfile::Te { m: 2, n: 0 }] },
+const MODE_MONITORS: &[ModeMonitor] = &[];
+
+// Metasurface unit cell: the axes across `normal` become periodic, a current
+// sheet replaces the point source with a normally incident plane wave, and an
+// automatic reference run normalises the complex t(f) and r(f) written to
+// MONITOR_DIR/<name>_unit_cell.csv (see unit_cell.rs), e.g. a cell with the
+// surface at z = 40 lit by an x-polarised pulse:
+//   Some(UnitCell { name: "cell", normal: grid::Axis::Z, source: 8, front: 30, surface: 40,
+//                   back: 50, polarization: grid::Axis::X, amplitude: 1.0,
+//                   waveform: SOURCE_WAVEFORM, frequencies: &[8e9, 10e9, 12e9] })
+const UNIT_CELL: Option<UnitCell> = None;
+
+// Shielding effectiveness: a current sheet replaces the point source with a
+// plane wave, and an automatic reference run without the enclosure gives the
+// SE_E(f) and SE_H(f) in dB at the interior points (MONITOR_DIR/<name>_shielding.csv,
+// see shielding.rs), e.g. a box lit from below by an x-polarised pulse:
+//   Some(Shielding { name: "box", normal: grid::Axis::Z, source: 8,
+//                    polarization: grid::Axis::X, amplitude: 1.0, waveform: SOURCE_WAVEFORM,
+//                    points: &[[32, 32, 32], [32, 32, 40]], frequencies: &[1e9, 2e9, 3e9] })
+const SHIELDING: Option<Shielding> = None;
+
+// Purcell factor: a current dipole replaces the point source, and the power it
+// delivers (from E at the dipole) and the power leaving a flux box `margin`
+// cells around it are normalised by the analytic vacuum dipole
+// (MONITOR_DIR/<name>_purcell.csv, see purcell.rs), e.g. a z dipole of 1 nA·m:
+//   Some(PurcellDipole { name: "emitter", axis: grid::Axis::Z, cell: [SRC_I, SRC_J, SRC_K],
+//                        moment: 1e-9, waveform: SOURCE_WAVEFORM, margin: 4,
+//                        frequencies: &[2e9, 4e9, 6e9] })
+const PURCELL: Option<PurcellDipole> = None;
+
+// Photonic band diagram: the grid is one unit cell with Bloch-periodic axes,
+// pulsed at random points for each k along the path (units of 2π/L per axis)
+// and the ringing decomposed into ω(k) (MONITOR_DIR/<name>_bands.csv and .png,
+// see bands.rs) instead of the normal run, e.g. a square lattice in xy, Γ–X–M–Γ:
+//   Some(BandDiagram { name: "lattice", periodic: [true, true, false],
+//                      path: &[[0.0, 0.0, 0.0], [0.5, 0.0, 0.0], [0.5, 0.5, 0.0],
+//                              [0.0, 0.0, 0.0]],
+//                      points_per_segment: 8, steps: 4000, seed: 1,
+//                      analysis: HarmonicInversion { f_min: 1e9, f_max: 12e9,
+//                                                    tolerance: 1e-4 } })
+const BANDS: Option<BandDiagram> = None;
+
+// Colour-mapped PNG of one component on an axis-aligned plane, e.g. Ez on
+// the mid z plane every 10 steps, each image scaled to its own peak:
+//   Some(SliceImages { every: 10, field: grid::Field::E(grid::Axis::Z),
+//                      normal: grid::Axis::Z, index: NZ / 2, scale: None, dir: "slices" })
+const SLICE_IMAGES: Option<SliceImages> = None;
+
+// Also run the f32 update next to a non-f32 PRECISION and report the probe
+// difference (keeps the f32 fields allocated).
+const COMPARE_F32: bool = false;
+
+// Δt as this fraction of the material-aware stability limit, replacing
+// DT = SC·DX/C0 (e.g. Some(0.95)); None keeps DT and only checks it.
+const DT_SAFETY: Option<f64> = None;
+
+// Coefficient layout on the GPU: Indexed stores a material index per cell
+// plus a lookup table (not combined with MODULATED or SUBGRIDS, which need
+// dense maps).
+const COEFFICIENT_STORAGE: CoefficientStorage = CoefficientStorage::Dense;
+
+// Dimensionality: OneD (Ez/Hy along x) and TMz/TEz (xy plane) run on the
+// line or plane through the source with OBJECTS only; TEz drives Hz.
+// Bor { m } solves azimuthal order m in (r, z) about the z line through the
+// grid centre (for m ≥ 1 move SRC_I off the axis, where Ez vanishes).
+const MODE: Mode = Mode::ThreeD;
+
+const _: () = assert!(
+    NX as usize <= MAX_CELLS && NY as usize <= MAX_CELLS && NZ as usize <= MAX_CELLS
+);
+
+/// The constants above as the library's configuration.
+fn config() -> Config {
+    Config {
+        grid: GRID,
+        boundaries: BOUNDARIES,
+        steps: MAX_TIME,
+        source: [SRC_I, SRC_J, SRC_K],
+        waveform: SOURCE_WAVEFORM,
+        probes: PROBES.to_vec(),
+        probe_batch: PROBE_BATCH,
+        steps_per_submit: 1,
+        probe_output: PROBE_OUTPUT,
+        energy_every: ENERGY_EVERY,
+        divergence: DIVERGENCE,
+        harminv: HARMINV,
+        probe_spectra: PROBE_SPECTRA,
+        material_map: None,
+        objects: OBJECTS.to_vec(),
+        smoothing: SMOOTHING,
+        phantoms: PHANTOMS.to_vec(),
+        sar: SAR.to_vec(),
+        thermal: THERMAL,
+        random_regions: RANDOM_REGIONS.to_vec(),
+        rough_surfaces: ROUGH_SURFACES.to_vec(),
+        modulated: MODULATED.to_vec(),
+        sheets: SHEETS.to_vec(),
+        thin_layers: THIN_LAYERS.to_vec(),
+        sibc_objects: SIBC_OBJECTS.to_vec(),
+        conformal_pec: CONFORMAL_PEC.to_vec(),
+        wires: WIRES.to_vec(),
+        lumped: LUMPED.to_vec(),
+        ports: PORTS.to_vec(),
+        touchstone: TOUCHSTONE,
+        tdr: TDR,
+        circuits: CIRCUITS.to_vec(),
+        subgrids: SUBGRIDS.to_vec(),
+        moving_window: MOVING_WINDOW,
+        auto_mesh: AUTO_MESH,
+        scheme: SCHEME,
+        precision: PRECISION,
+        compare_f32: COMPARE_F32,
+        dt_safety: DT_SAFETY,
+        coefficient_storage: COEFFICIENT_STORAGE,
+        mode: MODE,
+        adapter: None,
+        backend: None,
+        power_preference: PowerPreference::HighPerformance,
+        cpu_threads: None,
+        cluster: None,
+        time_kernels: false,
+        verbosity: Verbosity::Normal,
+        output_format: OutputFormat::Text,
+        resume: None,
+        time_budget: None,
+        checkpoint_every: None,
+        initial_fields: None,
+        final_fields: false,
+        snapshots: SNAPSHOTS,
+        monitors: MONITORS.to_vec(),
+        monitor_dir: MONITOR_DIR,
+        mat_file: MAT_FILE,
+        kspace_monitors: KSPACE_MONITORS.to_vec(),
+        dft_monitors: DFT_MONITORS.to_vec(),
+        flux_monitors: FLUX_MONITORS.to_vec(),
+        flux_boxes: FLUX_BOXES.to_vec(),
+        rcs: RCS.to_vec(),
+        patterns: PATTERNS.to_vec(),
+        reflectance: REFLECTANCE.to_vec(),
+        mode_monitors: MODE_MONITORS.to_vec(),
+        unit_cell: UNIT_CELL,
+        shielding: SHIELDING,
+        purcell: PURCELL,
+        bands: BANDS,
+        slice_images: SLICE_IMAGES,
+    }
+}
+
+fn main() -> ExitCode {
+    tracing_subscriber::fmt()
+        .with_writer(io::stderr)
+        .without_time()
+        .with_target(false)
+        .init();
+    match run(&config()) {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,191 @@
+/* C interface of the fdtd_3d solver; see src/ffi.rs. */
+
+#ifndef FDTD_3D_H
+#define FDTD_3D_H
+
+/* Generated by cbindgen from src/ffi.rs; do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/*
+ Field components as `fdtd_read_field` takes them, passed as their
+ `uint32_t` value so that any other number is rejected instead of read
+ as an enum.
+ */
+typedef enum FdtdField {
+  FDTD_FIELD_EX = 0,
+  FDTD_FIELD_EY = 1,
+  FDTD_FIELD_EZ = 2,
+  FDTD_FIELD_HX = 3,
+  FDTD_FIELD_HY = 4,
+  FDTD_FIELD_HZ = 5,
+} FdtdField;
+
+/*
+ Result of every call.
+ */
+typedef enum FdtdStatus {
+  FDTD_STATUS_OK = 0,
+  /*
+   A handle, array or string argument was null.
+   */
+  FDTD_STATUS_NULL_POINTER = 1,
+  /*
+   An index, size or string was out of range or not UTF-8.
+   */
+  FDTD_STATUS_INVALID_ARGUMENT = 2,
+  /*
+   The configuration or scene file was rejected.
+   */
+  FDTD_STATUS_INVALID_CONFIGURATION = 3,
+  /*
+   The run has started and cannot be configured any more.
+   */
+  FDTD_STATUS_RUNNING = 4,
+  /*
+   The solver panicked (a broken invariant).
+   */
+  FDTD_STATUS_PANIC = 5,
+  /*
+   No GPU adapter, or a device short of the solver's limits.
+   */
+  FDTD_STATUS_NO_DEVICE = 6,
+  /*
+   The grid's buffers exceed what the device allows.
+   */
+  FDTD_STATUS_TOO_LARGE = 7,
+  /*
+   A copy back from the GPU failed.
+   */
+  FDTD_STATUS_READBACK_FAILED = 8,
+  /*
+   Writing an output file failed.
+   */
+  FDTD_STATUS_IO = 9,
+  /*
+   The run was interrupted (see the `interrupt` module).
+   */
+  FDTD_STATUS_INTERRUPTED = 10,
+  /*
+   The estimated wall time is over the configured budget.
+   */
+  FDTD_STATUS_OVER_BUDGET = 11,
+  /*
+   The fields diverged (see the `divergence` module).
+   */
+  FDTD_STATUS_DIVERGED = 12,
+  /*
+   The device rejected a call (a validation or out-of-memory error).
+   */
+  FDTD_STATUS_GPU_ERROR = 13,
+  /*
+   The device was lost.
+   */
+  FDTD_STATUS_DEVICE_LOST = 14,
+} FdtdStatus;
+
+/*
+ Opaque run handle.
+ */
+typedef struct FdtdSim FdtdSim;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/*
+ The message of the last failed call on this thread, or "".
+ */
+const char *fdtd_last_error(void);
+
+/*
+ A vacuum run on `nx`×`ny`×`nz` cubic cells of `spacing` (m) with time
+ step `dt` (s), pulsed at the centre.
+ */
+enum FdtdStatus fdtd_create(uint32_t nx,
+                            uint32_t ny,
+                            uint32_t nz,
+                            double spacing,
+                            double dt,
+                            struct FdtdSim **out);
+
+/*
+ A run described by a TOML or JSON scene file.
+ */
+enum FdtdStatus fdtd_create_from_scene(const char *path, struct FdtdSim **out);
+
+/*
+ Replace the source by a Gaussian pulse of `width` and `delay` steps at
+ cell (i, j, k).
+ */
+enum FdtdStatus fdtd_set_source(struct FdtdSim *sim,
+                                uint32_t i,
+                                uint32_t j,
+                                uint32_t k,
+                                double width,
+                                double delay);
+
+/*
+ Make `axis` (0 = x, 1 = y, 2 = z) periodic.
+ */
+enum FdtdStatus fdtd_set_periodic(struct FdtdSim *sim, uint32_t axis);
+
+/*
+ Paint the box between corners `min` and `max` (m) with ε_r and σ (S/m).
+ */
+enum FdtdStatus fdtd_add_box(struct FdtdSim *sim,
+                             const double *min,
+                             const double *max,
+                             double eps_r,
+                             double sigma);
+
+/*
+ Paint the sphere around `center` (m) with ε_r and σ (S/m).
+ */
+enum FdtdStatus fdtd_add_sphere(struct FdtdSim *sim,
+                                const double *center,
+                                double radius,
+                                double eps_r,
+                                double sigma);
+
+/*
+ Record Ez at cell (i, j, k) under `name` in the probe outputs.
+ */
+enum FdtdStatus fdtd_add_probe(struct FdtdSim *sim,
+                               const char *name,
+                               uint32_t i,
+                               uint32_t j,
+                               uint32_t k);
+
+/*
+ Take `steps` steps, setting the run up on the first call.
+ */
+enum FdtdStatus fdtd_run(struct FdtdSim *sim, uint32_t steps);
+
+/*
+ Steps taken so far, or 0 for a null handle.
+ */
+uint32_t fdtd_steps_taken(const struct FdtdSim *sim);
+
+/*
+ Copy `field`, an [`FdtdField`], into `out`, which holds `len` =
+ nx·ny·nz values, x fastest.
+ */
+enum FdtdStatus fdtd_read_field(const struct FdtdSim *sim,
+                                uint32_t field,
+                                float *out,
+                                uintptr_t len);
+
+/*
+ Free a handle; null is ignored.
+ */
+void fdtd_destroy(struct FdtdSim *sim);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* FDTD_3D_H */
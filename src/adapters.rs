@@ -0,0 +1,98 @@
+//! Choosing the GPU a run takes.
+//!
+//! Left alone, a run takes the adapter wgpu prefers for high performance on
+//! any backend, which on a machine with several GPUs may not be the one
+//! wanted.  [`Config::adapter`](crate::Config::adapter) picks one by its
+//! index in [`list`] or by (part of) its name,
+//! [`Config::backend`](crate::Config::backend) looks on one graphics API
+//! only and [`Config::power_preference`](crate::Config::power_preference)
+//! asks for the integrated GPU instead; `fdtd_3d --list-adapters` prints
+//! the list.  [`Backend::Cpu`] runs on the CPU instead (see
+//! [`crate::cpu`]).  The browser picks the adapter itself: only the power
+//! preference applies there.
+
+use std::fmt;
+
+use serde::Serialize;
+
+/// A GPU adapter asked for by the user.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum AdapterChoice {
+    /// Index in [`list`] of the configured backend.
+    Index(usize),
+    /// Name, or part of it in any case.
+    Name(String),
+}
+
+impl fmt::Display for AdapterChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AdapterChoice::Index(index) => write!(f, "#{index}"),
+            AdapterChoice::Name(name) => f.write_str(name),
+        }
+    }
+}
+
+/// Graphics API to look for adapters on, or none.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum Backend {
+    Vulkan,
+    Metal,
+    Dx12,
+    Gl,
+    /// The multithreaded CPU reference of the Yee core, probes only, no
+    /// adapter.
+    Cpu,
+}
+
+impl Backend {
+    fn backends(self) -> wgpu::Backends {
+        match self {
+            Backend::Vulkan => wgpu::Backends::VULKAN,
+            Backend::Metal => wgpu::Backends::METAL,
+            Backend::Dx12 => wgpu::Backends::DX12,
+            Backend::Gl => wgpu::Backends::GL,
+            Backend::Cpu => wgpu::Backends::empty(),
+        }
+    }
+}
+
+/// Which GPU wgpu prefers when no adapter is named.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub enum PowerPreference {
+    /// The discrete GPU, if any.
+    #[default]
+    HighPerformance,
+    /// The integrated GPU, if any.
+    LowPower,
+}
+
+impl PowerPreference {
+    pub(crate) fn wgpu(self) -> wgpu::PowerPreference {
+        match self {
+            PowerPreference::HighPerformance => wgpu::PowerPreference::HighPerformance,
+            PowerPreference::LowPower => wgpu::PowerPreference::LowPower,
+        }
+    }
+}
+
+/// The backends searched: `backend`, or all of them.
+pub(crate) fn backends(backend: Option<Backend>) -> wgpu::Backends {
+    backend.map_or(wgpu::Backends::all(), Backend::backends)
+}
+
+/// A wgpu instance looking for adapters on `backend` only, if given.
+pub(crate) fn instance(backend: Option<Backend>) -> wgpu::Instance {
+    wgpu::Instance::new(&wgpu::InstanceDescriptor {
+        backends: backends(backend),
+        ..Default::default()
+    })
+}
+
+/// The adapters on `backend` (all backends when None), in the order
+/// [`AdapterChoice::Index`] counts them.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn list(backend: Option<Backend>) -> Vec<wgpu::AdapterInfo> {
+    let adapters = instance(backend).enumerate_adapters(backends(backend));
+    adapters.iter().map(wgpu::Adapter::get_info).collect()
+}
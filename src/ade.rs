@@ -0,0 +1,240 @@
+//! Sparse auxiliary-differential-equation (ADE) currents on single E edges.
+//!
+//! Dispersive sub-cell models (Drude sheets, lumped inductors, Debye tissue
+//! poles, …) register
+//! one [`AdeEdge`] per affected edge and fold the instantaneous part of their
+//! response into that edge's CA/CB.  The `ade_edges.wgsl` pass then applies
+//! the auxiliary current after every E-update.  An edge may also be driven
+//! by a per-step scalar ("drive" slot) for lumped voltage sources.
+
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+
+use crate::gpu::{bg_entry, bgl_storage_entry, compute_pipeline, groups_1d, Uploads};
+use crate::grid::Axis;
+use crate::materials::{Coefficients, EPS0};
+
+/// One auxiliary current (must match WGSL `AdeEdge`).
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct AdeEdge {
+    pub cell: u32,
+    pub comp: u32,
+    pub kj: f32,
+    pub bj: f32,
+    pub cj: f32,
+    /// Index into the drive buffer, or [`NO_DRIVE`].
+    pub src: u32,
+    /// Scale of the drive value added to E.
+    pub dj: f32,
+    /// Weight of the previous E in the J update (equals BJ for Drude).
+    pub bp: f32,
+}
+
+/// `AdeEdge::src` value for edges without a drive.
+pub const NO_DRIVE: u32 = u32::MAX;
+
+impl AdeEdge {
+    /// Drude current `dJ/dt + γJ = γσ₀E` on edge (`id`, `axis`).
+    ///
+    /// Trapezoidal discretisation (Taflove & Hagness §9.4):
+    ///   KJ = (1 − γΔt/2)/(1 + γΔt/2),  BJ = (γσ₀Δt/2)/(1 + γΔt/2).
+    /// BJ acts as an extra conductivity in CA/CB, which is added to `coeffs`
+    /// here; CJ = CB·(1 + KJ)/2 uses the resulting CB.
+    pub fn drude(
+        coeffs: &mut Coefficients,
+        id: usize,
+        axis: Axis,
+        sigma0: f64,
+        gamma: f64,
+        dt: f64,
+    ) -> AdeEdge {
+        let half = gamma * dt / 2.0;
+        let kj = (1.0 - half) / (1.0 + half);
+        let bj = sigma0 * half / (1.0 + half);
+        AdeEdge::trapezoidal(coeffs, id, axis, kj, bj, bj, dt)
+    }
+
+    /// Debye polarisation current `τ dJ/dt + J = ε₀Δε dE/dt`.
+    ///
+    /// Trapezoidal in J, central in E:
+    ///   KJ = (1 − Δt/2τ)/(1 + Δt/2τ),  BJ = −BP = (ε₀Δε/τ)/(1 + Δt/2τ),
+    /// so BJ·Δt/2 appears as extra permittivity in CA/CB.
+    pub fn debye(
+        coeffs: &mut Coefficients,
+        id: usize,
+        axis: Axis,
+        delta_eps: f64,
+        tau: f64,
+        dt: f64,
+    ) -> AdeEdge {
+        let half = dt / (2.0 * tau);
+        let kj = (1.0 - half) / (1.0 + half);
+        let bj = EPS0 * delta_eps / tau / (1.0 + half);
+        AdeEdge::trapezoidal(coeffs, id, axis, kj, bj, -bj, dt)
+    }
+
+    /// Integrating current `dJ/dt = k·E` (a lumped inductor), the γ → 0
+    /// limit of the Drude form with γσ₀ = k.
+    pub fn integrator(
+        coeffs: &mut Coefficients,
+        id: usize,
+        axis: Axis,
+        k: f64,
+        dt: f64,
+    ) -> AdeEdge {
+        let bj = k * dt / 2.0;
+        AdeEdge::trapezoidal(coeffs, id, axis, 1.0, bj, bj, dt)
+    }
+
+    /// Edge without auxiliary current, driven by `dj · drive[src]`.
+    pub fn driven(id: usize, axis: Axis, src: u32, dj: f64) -> AdeEdge {
+        AdeEdge {
+            cell: id as u32,
+            comp: axis.lane() as u32,
+            kj: 0.0,
+            bj: 0.0,
+            cj: 0.0,
+            src,
+            dj: dj as f32,
+            bp: 0.0,
+        }
+    }
+
+    /// `J' = KJ·J + BJ·E^{n+1} + BP·E^n`.  The instantaneous part splits into
+    /// a conductivity (BJ + BP)/2 and a permittivity (BJ − BP)·Δt/4, both
+    /// folded into CA/CB.
+    fn trapezoidal(
+        coeffs: &mut Coefficients,
+        id: usize,
+        axis: Axis,
+        kj: f64,
+        bj: f64,
+        bp: f64,
+        dt: f64,
+    ) -> AdeEdge {
+        if bj != bp {
+            coeffs.add_e_permittivity(id, axis, (bj - bp) * dt / 4.0, dt);
+        }
+        coeffs.add_e_conductivity(id, axis, (bj + bp) / 2.0, dt);
+        let cb = coeffs.cb[id][axis.lane()] as f64;
+        AdeEdge {
+            cell: id as u32,
+            comp: axis.lane() as u32,
+            kj: kj as f32,
+            bj: bj as f32,
+            cj: (cb * (1.0 + kj) / 2.0) as f32,
+            src: NO_DRIVE,
+            dj: 0.0,
+            bp: bp as f32,
+        }
+    }
+}
+
+/// GPU resources for the ADE correction pass.
+pub struct AdePass {
+    pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    /// Polarisation currents of the edges, kept across steps.
+    buf_state: wgpu::Buffer,
+    buf_drive: wgpu::Buffer,
+    count: u32,
+}
+
+impl AdePass {
+    /// Upload `edges` and bind them to the E-field buffers; `drives` is the
+    /// number of drive slots referenced by the edges.
+    /// Returns `None` when there is nothing to correct.
+    pub fn new(
+        device: &wgpu::Device,
+        edges: &[AdeEdge],
+        drives: usize,
+        e_fields: [&wgpu::Buffer; 3],
+    ) -> Option<Self> {
+        if edges.is_empty() {
+            return None;
+        }
+
+        let buf_edges = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("ade_edges"),
+            contents: bytemuck::cast_slice(edges),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let buf_state = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("ade_state"),
+            contents: bytemuck::cast_slice(&vec![[0.0_f32; 2]; edges.len()]),
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
+        });
+        let buf_drive = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("ade_drive"),
+            size: (drives.max(1) * 4) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("ade_bgl"),
+            entries: &[
+                bgl_storage_entry(0, true),
+                bgl_storage_entry(1, false),
+                bgl_storage_entry(2, false),
+                bgl_storage_entry(3, false),
+                bgl_storage_entry(4, false),
+                bgl_storage_entry(5, true),
+            ],
+        });
+        let pipeline = compute_pipeline(
+            device,
+            "ade_edges",
+            include_str!("shaders/ade_edges.wgsl"),
+            &bgl,
+        );
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("bg_ade"),
+            layout: &bgl,
+            entries: &[
+                bg_entry(0, buf_edges.as_entire_binding()),
+                bg_entry(1, buf_state.as_entire_binding()),
+                bg_entry(2, e_fields[0].as_entire_binding()),
+                bg_entry(3, e_fields[1].as_entire_binding()),
+                bg_entry(4, e_fields[2].as_entire_binding()),
+                bg_entry(5, buf_drive.as_entire_binding()),
+            ],
+        });
+
+        Some(AdePass {
+            pipeline,
+            bind_group,
+            buf_state,
+            buf_drive,
+            count: edges.len() as u32,
+        })
+    }
+
+    /// The buffer of the edges' state, for checkpoints.
+    pub fn state(&self) -> &wgpu::Buffer {
+        &self.buf_state
+    }
+
+    /// Upload this step's drive values (one per slot).
+    pub fn set_drives(
+        &self,
+        uploads: &mut Uploads,
+        encoder: &mut wgpu::CommandEncoder,
+        values: &[f32],
+    ) {
+        uploads.write(encoder, &self.buf_drive, 0, bytemuck::cast_slice(values));
+    }
+
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder) {
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("ADE currents"),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.dispatch_workgroups(groups_1d(self.count, 64), 1, 1);
+    }
+}
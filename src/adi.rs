@@ -0,0 +1,241 @@
+//! Alternating-direction-implicit (ADI) FDTD.
+//!
+//! Each step is split into two half steps that treat one of the two curl
+//! terms of every component implicitly (Zheng, Chen & Zhang, 2000).  The
+//! implicit terms couple each E component along one axis only, so a half
+//! step is three tridiagonal solves — one per E component, one GPU thread
+//! per grid line — followed by an explicit H update.  The scheme is
+//! unconditionally stable: Δt is set by the accuracy needed for the fields
+//! of interest instead of by the smallest cell, at the price of a splitting
+//! error that grows with Δt.
+//!
+//! The pass replaces the explicit H/E kernels and works on dense
+//! coefficients built for Δt/2 (see [`half_step_grid`]); sub-cell passes,
+//! which assume the explicit leapfrog, are not combined with it.  The line
+//! solver is shared with the hybrid update of [`crate::hie`].
+
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+
+use crate::gpu::{bg_entry, bgl_storage_entry, bgl_uniform_entry, compute_pipeline_entry};
+use crate::grid::Grid;
+use crate::materials::Coefficients;
+
+/// Line-solve parameters (must match WGSL `AdiParams`).
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct AdiParams {
+    nx: u32,
+    ny: u32,
+    nz: u32,
+    theta: f32,
+    c: u32,
+    d: u32,
+    p: u32,
+    s: f32,
+}
+
+/// Implicit axis d of E component `c` in half step `half_step`: d = next(c)
+/// first, d = prev(c) second.
+fn implicit_axis(half_step: usize, c: usize) -> usize {
+    if half_step == 0 {
+        (c + 1) % 3
+    } else {
+        (c + 2) % 3
+    }
+}
+
+/// The grid with Δt halved, on which the ADI coefficients are built.
+pub fn half_step_grid(grid: &Grid) -> Grid {
+    Grid {
+        dt: grid.dt / 2.0,
+        ..*grid
+    }
+}
+
+/// Tridiagonal solves of E components along one axis each, followed by
+/// the update of their partner H; shared by ADI and HIE.
+pub(crate) struct LineSolver {
+    solve: wgpu::ComputePipeline,
+    update_h: wgpu::ComputePipeline,
+    bgl: wgpu::BindGroupLayout,
+    /// Packed coefficients, four vec4 per cell (CA, CB, CP, CQ).
+    pub(crate) coef: wgpu::Buffer,
+    sweep: wgpu::Buffer,
+    e: [wgpu::Buffer; 3],
+    e_new: [wgpu::Buffer; 3],
+    dims: [u32; 3],
+}
+
+impl LineSolver {
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        grid: &Grid,
+        coeffs: &Coefficients,
+        e: [&wgpu::Buffer; 3],
+    ) -> Self {
+        let packed: Vec<[f32; 4]> = (0..coeffs.ca.len())
+            .flat_map(|id| [coeffs.ca[id], coeffs.cb[id], coeffs.cp[id], coeffs.cq[id]])
+            .collect();
+        let coef = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("adi_coeffs"),
+            contents: bytemuck::cast_slice(&packed),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let scratch_usage = wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC;
+        let scratch = |label: &str| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size: grid.total() as u64 * 4,
+                usage: scratch_usage,
+                mapped_at_creation: false,
+            })
+        };
+        let e_new = ["adi_ex", "adi_ey", "adi_ez"].map(scratch);
+        let sweep = scratch("adi_sweep");
+
+        let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("adi_bgl"),
+            entries: &[
+                bgl_uniform_entry(0),
+                bgl_storage_entry(1, true),
+                bgl_storage_entry(2, true),
+                bgl_storage_entry(3, false),
+                bgl_storage_entry(4, true),
+                bgl_storage_entry(5, false),
+                bgl_storage_entry(6, false),
+                bgl_storage_entry(7, true),
+                bgl_uniform_entry(9),
+            ],
+        });
+        let source = include_str!("shaders/adi.wgsl");
+        let solve = compute_pipeline_entry(device, "adi_solve", source, &bgl, "solve");
+        let update_h = compute_pipeline_entry(device, "adi_update_h", source, &bgl, "update_h");
+
+        LineSolver {
+            solve,
+            update_h,
+            bgl,
+            coef,
+            sweep,
+            e: e.map(|b| b.clone()),
+            e_new,
+            dims: [grid.nx, grid.ny, grid.nz],
+        }
+    }
+
+    /// Bind group solving E component `c` along axis `d` with implicit
+    /// weight `theta`.
+    pub(crate) fn bind_group(
+        &self,
+        device: &wgpu::Device,
+        (c, d): (usize, usize),
+        theta: f32,
+        h: [&wgpu::Buffer; 3],
+        spacing: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        let p = 3 - c - d;
+        let [nx, ny, nz] = self.dims;
+        let params = AdiParams {
+            nx,
+            ny,
+            nz,
+            theta,
+            c: c as u32,
+            d: d as u32,
+            p: p as u32,
+            s: if d == (c + 1) % 3 { 1.0 } else { -1.0 },
+        };
+        let params = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("adi_params"),
+            contents: bytemuck::bytes_of(&params),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("bg_adi"),
+            layout: &self.bgl,
+            entries: &[
+                bg_entry(0, params.as_entire_binding()),
+                bg_entry(1, self.e[c].as_entire_binding()),
+                bg_entry(2, self.e[d].as_entire_binding()),
+                bg_entry(3, h[p].as_entire_binding()),
+                bg_entry(4, h[d].as_entire_binding()),
+                bg_entry(5, self.e_new[c].as_entire_binding()),
+                bg_entry(6, self.sweep.as_entire_binding()),
+                bg_entry(7, self.coef.as_entire_binding()),
+                bg_entry(9, spacing.as_entire_binding()),
+            ],
+        })
+    }
+
+    /// Solve every `(c, d, group)` line set, update the partner H fields and
+    /// copy the new E components over the old ones.
+    pub(crate) fn encode(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        lines: &[(usize, usize, &wgpu::BindGroup)],
+    ) {
+        let [cx, cy, cz] = self.dims.map(|n| n.div_ceil(4));
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("line solve"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.solve);
+            for &(c, d, group) in lines {
+                // Lines run along d; threads cover the (c, p) plane.
+                let [gc, gp] = [c, 3 - c - d].map(|a| self.dims[a].div_ceil(8));
+                pass.set_bind_group(0, group, &[]);
+                pass.dispatch_workgroups(gc, gp, 1);
+            }
+            pass.set_pipeline(&self.update_h);
+            for &(_, _, group) in lines {
+                pass.set_bind_group(0, group, &[]);
+                pass.dispatch_workgroups(cx, cy, cz);
+            }
+        }
+        for &(c, _, _) in lines {
+            let e = &self.e[c];
+            encoder.copy_buffer_to_buffer(&self.e_new[c], 0, e, 0, e.size());
+        }
+    }
+}
+
+/// GPU resources of the ADI update.
+pub struct AdiPass {
+    lines: LineSolver,
+    /// Bind groups per half step, one per E component.
+    groups: [[wgpu::BindGroup; 3]; 2],
+}
+
+impl AdiPass {
+    /// `half` holds the scene's coefficients on [`half_step_grid`].
+    pub fn new(
+        device: &wgpu::Device,
+        grid: &Grid,
+        half: &Coefficients,
+        e: [&wgpu::Buffer; 3],
+        h: [&wgpu::Buffer; 3],
+        spacing: &wgpu::Buffer,
+    ) -> Self {
+        let lines = LineSolver::new(device, grid, half, e);
+        let groups = [0, 1].map(|half_step| {
+            [0, 1, 2].map(|c| {
+                let d = implicit_axis(half_step, c);
+                lines.bind_group(device, (c, d), 1.0, h, spacing)
+            })
+        });
+        AdiPass { lines, groups }
+    }
+
+    /// One full ADI step: two half steps of three solves, the H update and
+    /// the copy of the new E.
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder) {
+        for (half_step, groups) in self.groups.iter().enumerate() {
+            let lines: Vec<_> = (0..3)
+                .map(|c| (c, implicit_axis(half_step, c), &groups[c]))
+                .collect();
+            self.lines.encode(encoder, &lines);
+        }
+    }
+}
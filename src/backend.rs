@@ -0,0 +1,403 @@
+//! What the orchestration of a 3D run asks of the solver stepping it.
+//!
+//! [`drive`] times the first steps against the budget, writes the periodic
+//! checkpoints, sets the run up again after a lost device and saves the
+//! state on an interrupt, whatever does the stepping: it only goes through
+//! [`ComputeBackend`], which the wgpu run and the CPU's
+//! [`CpuRun`](crate::cpu::CpuRun) implement, and
+//! [`Simulation`](crate::Simulation) through whichever of them it holds.
+//! Another backend (CUDA, native Metal, a remote machine) implements the
+//! trait, refuses in [`Config::validate`](crate::Config::validate) what it
+//! does not cover, as the CPU does, and is picked in
+//! [`Simulation::new`](crate::Simulation::new) from
+//! [`Config::backend`](crate::Config::backend).
+//!
+//! The trait is also what a caller steps and reads a run through: single
+//! steps, hooks and sinks, the point source, the field readbacks and the
+//! checkpoints.  A backend implements the few operations that touch its
+//! hardware; slices, single values, source changes and resuming are
+//! provided on top of them, the same for every backend.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+
+use ndarray::{Array3, ShapeBuilder};
+use tracing::{info, warn};
+
+use crate::checkpoint::{self, Checkpoint};
+use crate::config::{Config, ConfigError, Scene};
+use crate::dft::Spectrum;
+use crate::error::FdtdError;
+use crate::grid::{Axis, Field, Grid};
+use crate::hooks::{StepCallback, StepState};
+use crate::interrupt;
+use crate::manifest::RunInfo;
+use crate::notebook::Slice;
+use crate::progress::format_duration;
+use crate::sinks::OutputSink;
+use crate::slices;
+use crate::sources::Waveform;
+
+/// Steps timed at the start of a run to estimate its wall time.
+const CALIBRATION_STEPS: u32 = 10;
+
+/// Times a run goes on after losing its device before it fails.
+const DEVICE_RECOVERIES: u32 = 3;
+
+/// The point source of a run, to change from the next step on.
+pub struct SourceDrive<'a> {
+    /// The cell whose Ez the waveform drives.
+    pub cell: &'a mut [u32; 3],
+    pub waveform: &'a mut Waveform,
+    /// Factor on the waveform.
+    pub amplitude: &'a mut f64,
+}
+
+/// A 3D run set up on some hardware: the update, the probes and monitors
+/// and their outputs, the readbacks and the checkpoints.
+///
+/// An implementation provides the step, the hooks and sinks, the source,
+/// the field readback and the checkpoints; the rest (runs of several
+/// steps aside, which batch what the hardware allows) is written once
+/// over those.
+pub trait ComputeBackend: Sized {
+    /// The configuration being run.
+    fn config(&self) -> &Config;
+
+    /// The scene being run.
+    fn scene(&self) -> Scene;
+
+    /// The grid stepped, with the Δt actually used.
+    fn grid(&self) -> &Grid;
+
+    /// Steps taken so far.
+    fn steps_taken(&self) -> u32;
+
+    /// Whether an interrupt (see [`crate::interrupt`]) or a hook asked to
+    /// stop.
+    fn stopped(&self) -> bool;
+
+    /// Advance the fields by one Δt: the sources, the update, the probes
+    /// and monitors and the hooks due.  Fails when a readback or an output
+    /// write does.
+    fn step(&mut self) -> Result<(), FdtdError>;
+
+    /// Take `steps` steps, fewer once stopped, recording the probes and
+    /// monitors, and wait for the last.  Fails with
+    /// [`FdtdError::DeviceLost`] when the hardware went away under it.
+    fn run(&mut self, steps: u32) -> Result<(), FdtdError>;
+
+    /// Register the boxed hook of [`on_step`](Self::on_step), `every` at
+    /// least 1.
+    fn add_hook(&mut self, every: u32, hook: StepCallback) -> Result<(), FdtdError>;
+
+    /// Register the boxed sink of [`add_sink`](Self::add_sink).
+    fn add_boxed_sink(&mut self, sink: Box<dyn OutputSink>);
+
+    /// The point source, whose changes apply from the next step on.
+    fn source_mut(&mut self) -> SourceDrive<'_>;
+
+    /// A copy of one f32 field component, x fastest.
+    fn field(&self, field: Field) -> Result<Vec<f32>, FdtdError>;
+
+    /// Overwrite one f32 field component, x fastest, from the next step
+    /// on.
+    fn set_field(&mut self, field: Field, values: &[f32]) -> Result<(), FdtdError>;
+
+    /// Write the state after the steps taken to `path` (see
+    /// [`crate::checkpoint`]).
+    fn save_checkpoint(&self, path: &Path) -> Result<(), FdtdError>;
+
+    /// The state and steps of `checkpoint`, written by a run of the same
+    /// configuration and scene.
+    fn restore(&mut self, checkpoint: &Checkpoint) -> Result<(), FdtdError>;
+
+    /// The same run set up afresh, on a new device, from the checkpoint at
+    /// `resume` or from the start; the stepping time so far carries over.
+    fn reopen(self, resume: Option<PathBuf>) -> Result<Self, FdtdError>;
+
+    /// Write the analyses of the steps taken; returns the planes and points
+    /// of the normalised spectra and the run's summary.
+    fn finish(self) -> Result<(Vec<Spectrum>, RunInfo), FdtdError>;
+
+    /// Time of the E fields, n·Δt after n steps.
+    fn time(&self) -> f64 {
+        self.steps_taken() as f64 * self.grid().dt
+    }
+
+    /// Call `hook` after every `every`-th step (see [`crate::hooks`]).
+    /// Fails when `every` is 0 and on a backend that cannot hand the hook
+    /// the whole grid.
+    fn on_step(
+        &mut self,
+        every: u32,
+        hook: impl FnMut(&mut StepState) + Send + 'static,
+    ) -> Result<(), FdtdError> {
+        if every == 0 {
+            return Err(ConfigError("hooks run at least every step".into()).into());
+        }
+        self.add_hook(every, Box::new(hook))
+    }
+
+    /// Hand probe samples, snapshots and DFT spectra to `sink` as well (see
+    /// [`crate::sinks`]).
+    fn add_sink(&mut self, sink: impl OutputSink + 'static) {
+        self.add_boxed_sink(Box::new(sink));
+    }
+
+    /// The mean time per step of each update kernel (see
+    /// [`crate::bench::KERNELS`]) over the last steps; empty on hardware
+    /// that does not time them.
+    fn kernel_times(&self) -> Result<Vec<(&'static str, Duration)>, FdtdError> {
+        Ok(Vec::new())
+    }
+
+    /// [`run`](Self::run) returning the run, so a notebook cell can go on
+    /// to look at it (see [`crate::notebook`]).
+    fn advance(&mut self, steps: u32) -> Result<&mut Self, FdtdError> {
+        self.run(steps)?;
+        Ok(self)
+    }
+
+    /// Drive the source with `waveform` from the next step on, evaluated at
+    /// the steps taken so far as before.  Spectra normalised by the source
+    /// use the new waveform throughout.
+    fn set_waveform(&mut self, waveform: Waveform) {
+        *self.source_mut().waveform = waveform;
+    }
+
+    /// Scale the source's waveform by `amplitude` from the next step on.
+    fn set_source_amplitude(&mut self, amplitude: f64) {
+        *self.source_mut().amplitude = amplitude;
+    }
+
+    /// Move the source to `cell` from the next step on.
+    fn set_source(&mut self, cell: [u32; 3]) -> Result<(), FdtdError> {
+        self.grid().cell_idx(cell)?;
+        *self.source_mut().cell = cell;
+        Ok(())
+    }
+
+    /// One f32 field component at one cell.
+    fn value(&self, field: Field, cell: [u32; 3]) -> Result<f32, FdtdError> {
+        let id = self.grid().cell_idx(cell)?;
+        Ok(self.field(field)?[id])
+    }
+
+    /// [`field`](Self::field) as an array indexed `[i, j, k]`.
+    fn read_field(&self, field: Field) -> Result<Array3<f32>, FdtdError> {
+        let g = self.grid();
+        let shape = (g.nx as usize, g.ny as usize, g.nz as usize);
+        let values = self.field(field)?;
+        let found = values.len();
+        Array3::from_shape_vec(shape.f(), values).map_err(|_| FdtdError::FieldLength {
+            expected: g.total(),
+            found,
+        })
+    }
+
+    /// Plane `index` along `normal` of one f32 field component.
+    fn slice(&self, field: Field, normal: Axis, index: u32) -> Result<Slice, FdtdError> {
+        let grid = self.grid();
+        if index >= grid.cells(normal) {
+            return Err(FdtdError::OutsideGrid(format!(
+                "plane {index} along {normal:?}"
+            )));
+        }
+        let (u, v) = normal.tangential();
+        Ok(Slice {
+            field,
+            normal,
+            index,
+            steps: self.steps_taken(),
+            width: grid.cells(u),
+            height: grid.cells(v),
+            values: slices::plane(grid, &self.field(field)?, normal, index),
+        })
+    }
+
+    /// Go on from the checkpoint at `path`, written by a run of the same
+    /// configuration and scene: the fields, the sub-cell and DFT state, the
+    /// traces and the steps taken are restored, and [`run`](Self::run)
+    /// continues from there, past `config.steps` if asked to.
+    ///
+    /// The probe, flux and energy files opened by this run start afresh;
+    /// set [`Config::resume`] instead to have the setup append to them.
+    /// Sinks only see the steps after the checkpoint.  Sub-grids, the
+    /// moving window, circuit ports and line, plane and k-space monitors
+    /// keep state a checkpoint does not hold, and are refused.
+    fn resume(&mut self, path: impl AsRef<Path>) -> Result<(), FdtdError> {
+        let path = path.as_ref();
+        let checkpoint = checkpoint::read(path)?;
+        let scene = format!("{:?}", self.scene());
+        if checkpoint.section("scene") != Some(scene.as_bytes()) {
+            let message = format!("{}: not a checkpoint of the {scene} run", path.display());
+            return Err(ConfigError(message).into());
+        }
+        self.restore(&checkpoint)
+    }
+}
+
+/// Step `backend` to the end of its configured steps and finish it: the
+/// wall time is estimated after a few steps (the structure run after a
+/// reference one included) and checked against `time_budget`, and an
+/// interrupt writes `monitor_dir/checkpoint.bin` and fails with
+/// [`FdtdError::Interrupted`] once the analyses of the steps taken are
+/// written.  A structure run starts from `initial_fields` and leaves
+/// `monitor_dir/fields.bin` with `final_fields`.
+pub fn drive<B: ComputeBackend>(mut backend: B) -> Result<(Vec<Spectrum>, RunInfo), FdtdError> {
+    let config = backend.config().clone();
+    let remaining = config.steps.saturating_sub(backend.steps_taken());
+    let mut recovery = Recovery::default();
+    let structure = backend.scene() == Scene::Structure;
+    if let Some(path) = config.initial_fields.as_deref().filter(|_| structure) {
+        if backend.steps_taken() == 0 {
+            warm_start(&mut backend, path)?;
+        }
+    }
+
+    let burst = remaining.min(CALIBRATION_STEPS);
+    let start = Instant::now();
+    backend = run_recovering(backend, &config, burst, &mut recovery)?;
+    let following = if backend.scene() == Scene::Reference {
+        config.steps
+    } else {
+        0
+    };
+    let rest = remaining - burst + following;
+    if burst > 0 && rest > 0 && !backend.stopped() {
+        let estimate = start.elapsed() / burst * rest;
+        info!(
+            estimate_s = estimate.as_secs_f64(),
+            steps = rest,
+            "about {} left",
+            format_duration(estimate)
+        );
+        if let Some(budget) = config.time_budget.filter(|&budget| estimate > budget) {
+            return Err(FdtdError::OverBudget { estimate, budget });
+        }
+    }
+    backend = run_recovering(backend, &config, remaining - burst, &mut recovery)?;
+    if interrupt::requested() {
+        let path = Path::new(config.monitor_dir).join(checkpoint::FILE_NAME);
+        backend.save_checkpoint(&path)?;
+        let steps = backend.steps_taken();
+        backend.finish()?;
+        return Err(FdtdError::Interrupted {
+            steps,
+            checkpoint: path,
+        });
+    }
+    if config.final_fields && structure {
+        let path = Path::new(config.monitor_dir).join(checkpoint::FIELDS_FILE);
+        save_fields(&backend, &path)?;
+    }
+    backend.finish()
+}
+
+/// Write the fields of `backend` to `path`: a checkpoint (see
+/// [`crate::checkpoint`]) of the `scene` and `Ex` … `Hz` sections only, for
+/// another run to start from with
+/// [`Config::initial_fields`](crate::Config::initial_fields).
+pub fn save_fields<B: ComputeBackend>(backend: &B, path: &Path) -> Result<(), FdtdError> {
+    let scene = format!("{:?}", backend.scene());
+    let fields: Vec<Vec<f32>> = Field::ALL
+        .iter()
+        .map(|&field| backend.field(field))
+        .collect::<Result<_, _>>()?;
+    let mut sections: Vec<(&str, &[u8])> = vec![("scene", scene.as_bytes())];
+    for (field, values) in Field::ALL.iter().zip(&fields) {
+        sections.push((field.name(), bytemuck::cast_slice(values)));
+    }
+    let steps = backend.steps_taken();
+    checkpoint::write(path, backend.grid(), steps, &sections)?;
+    info!(path = %path.display(), steps, "wrote the fields");
+    Ok(())
+}
+
+/// Set the fields of `backend` to those of the checkpoint at `path`, of a
+/// run on a grid of the same size; the steps go on being counted from
+/// where they were.
+fn warm_start<B: ComputeBackend>(backend: &mut B, path: &Path) -> Result<(), FdtdError> {
+    let fields = checkpoint::read(path)?;
+    let g = *backend.grid();
+    if fields.size != [g.nx, g.ny, g.nz] {
+        let message = format!(
+            "{}: fields of a {:?} grid, not this run's",
+            path.display(),
+            fields.size
+        );
+        return Err(ConfigError(message).into());
+    }
+    for field in Field::ALL {
+        let values: Vec<f32> = fields
+            .section(field.name())
+            .filter(|bytes| bytes.len() == 4 * g.total())
+            .ok_or_else(|| {
+                let message = format!("{}: no full {} field", path.display(), field.name());
+                FdtdError::from(ConfigError(message))
+            })?
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+            .collect();
+        backend.set_field(field, &values)?;
+    }
+    info!(path = %path.display(), "started from the fields of another run");
+    Ok(())
+}
+
+/// The device losses a run has gone on after, and the checkpoint it would
+/// go back to.
+#[derive(Default)]
+struct Recovery {
+    losses: u32,
+    checkpoint: Option<PathBuf>,
+}
+
+/// Take `steps` more steps of `backend`, a run of `config`, writing a
+/// checkpoint every `config.checkpoint_every` steps.  When the device is
+/// lost the run is set up again from the last checkpoint, or from the
+/// start (or `config.resume`) without one, up to [`DEVICE_RECOVERIES`]
+/// times; the probe rows since the checkpoint are then written twice.
+fn run_recovering<B: ComputeBackend>(
+    mut backend: B,
+    config: &Config,
+    steps: u32,
+    recovery: &mut Recovery,
+) -> Result<B, FdtdError> {
+    let end = backend.steps_taken() + steps;
+    while backend.steps_taken() < end && !backend.stopped() {
+        let n = backend.steps_taken();
+        let chunk = config
+            .checkpoint_every
+            .map_or(end - n, |every| every - n % every);
+        match backend.run(chunk.min(end - n)) {
+            Ok(()) => {
+                let due = config.checkpoint_every;
+                if due.is_some_and(|every| backend.steps_taken().is_multiple_of(every)) {
+                    let path = Path::new(config.monitor_dir).join(checkpoint::FILE_NAME);
+                    backend.save_checkpoint(&path)?;
+                    recovery.checkpoint = Some(path);
+                }
+            }
+            Err(FdtdError::DeviceLost(reason)) if recovery.losses < DEVICE_RECOVERIES => {
+                recovery.losses += 1;
+                let resume = recovery.checkpoint.clone().or(config.resume.clone());
+                warn!(
+                    %reason,
+                    steps = backend.steps_taken(),
+                    ?resume,
+                    "the GPU device was lost, reconnecting"
+                );
+                backend = backend.reopen(resume)?;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(backend)
+}
@@ -0,0 +1,381 @@
+//! Photonic band diagrams.
+//!
+//! The grid is one unit cell of a crystal whose lattice vectors run along
+//! the periodic axes, with Bloch-periodic boundaries F(r + L) = e^{jkL} F(r)
+//! on those axes (PEC walls on the others, e.g. around a slab).  For every
+//! Bloch vector k on a piecewise-linear path through the Brillouin zone,
+//! complex fields are stepped from rest (`bloch.wgsl`), a few E components
+//! at random points are kicked by a Gaussian pulse covering the analysis
+//! band, and the ringing at other random points is decomposed by harmonic
+//! inversion ([`crate::harminv`]) into the eigenfrequencies ω_n(k).
+//!
+//! `<name>_bands.csv` lists every mode (k index, k in units of 2π/L per axis,
+//! frequency, Q) and `<name>_bands.png` plots the frequencies against the k index,
+//! with the path corners marked.  k components are fractions of the
+//! reciprocal lattice vector 2π/L, L the length of the grid along the axis,
+//! so Γ = (0, 0, 0), X = (½, 0, 0) and M = (½, ½, 0) for a square lattice.
+
+use std::f64::consts::PI;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use bytemuck::{Pod, Zeroable};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+use wgpu::util::DeviceExt;
+
+use crate::config::ConfigError;
+use crate::error::FdtdError;
+use crate::gpu::{self, bg_entry, bgl_storage_entry, bgl_uniform_entry, compute_pipeline_entry};
+use crate::grid::Grid;
+use crate::harminv::{self, HarmonicInversion, Resonance};
+use crate::materials::Coefficients;
+use crate::png;
+use crate::sources::Waveform;
+
+/// Number of pulsed source points and of probe points.
+const POINTS: usize = 4;
+
+/// Plot size (pixels).
+const WIDTH: u32 = 640;
+const HEIGHT: u32 = 400;
+
+/// A band diagram along a k-path.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct BandDiagram {
+    pub name: &'static str,
+    /// Axes with Bloch boundaries; the others keep PEC walls.
+    pub periodic: [bool; 3],
+    /// Corners of the k-path in units of 2π/L per axis.
+    pub path: &'static [[f64; 3]],
+    /// k-points per path segment (the segment's end point excluded).
+    pub points_per_segment: u32,
+    /// Band searched for modes, and harmonic-inversion threshold.
+    pub analysis: HarmonicInversion,
+    /// Time steps per k-point.
+    pub steps: u32,
+    /// Seed of the source and probe positions.
+    pub seed: u64,
+}
+
+impl BandDiagram {
+    /// The k-points of the path, the last corner included.
+    pub fn k_points(&self) -> Vec<[f64; 3]> {
+        let n = self.points_per_segment.max(1);
+        let mut points = Vec::new();
+        for pair in self.path.windows(2) {
+            for s in 0..n {
+                let t = s as f64 / n as f64;
+                points.push([0, 1, 2].map(|a| pair[0][a] + t * (pair[1][a] - pair[0][a])));
+            }
+        }
+        points.extend(self.path.last());
+        points
+    }
+
+    /// The excitation: a Gaussian-modulated pulse whose spectrum covers the
+    /// band down to about 1/e at its edges.
+    fn pulse(&self, dt: f64) -> Waveform {
+        let band = self.analysis.f_max - self.analysis.f_min;
+        let width = 2.0 / (PI * band * dt);
+        Waveform::ModulatedGaussian {
+            freq: 0.5 * (self.analysis.f_min + self.analysis.f_max),
+            width,
+            delay: 3.0 * width,
+        }
+    }
+}
+
+/// Grid, phase and source parameters (must match WGSL `Params`).
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct BlochParams {
+    size: [u32; 4],
+    inv_d: [f32; 4],
+    phase_cos: [f32; 4],
+    phase_sin: [f32; 4],
+    sources: [[u32; 4]; POINTS],
+}
+
+/// GPU state of the complex Bloch-periodic solver.
+pub struct BlochSolver {
+    update_h: wgpu::ComputePipeline,
+    update_e: wgpu::ComputePipeline,
+    inject: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    params: wgpu::Buffer,
+    /// E real and imaginary, then H.
+    fields: [wgpu::Buffer; 4],
+    base: BlochParams,
+    /// Cells whose E is summed into the trace.
+    probes: Vec<u32>,
+    groups: [u32; 3],
+    dt: f64,
+}
+
+impl BlochSolver {
+    /// Soft `sources` are (cell, E component) pairs, at most four.
+    pub fn new(
+        device: &wgpu::Device,
+        grid: &Grid,
+        coeffs: &Coefficients,
+        periodic: [bool; 3],
+        sources: &[(u32, u32)],
+        probes: &[u32],
+    ) -> Self {
+        let storage = |label: &str, contents: &[u8]| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents,
+                usage: wgpu::BufferUsages::STORAGE
+                    | wgpu::BufferUsages::COPY_DST
+                    | wgpu::BufferUsages::COPY_SRC,
+            })
+        };
+        let zeros = vec![[0.0_f32; 4]; grid.total()];
+        let fields = ["bloch_e_re", "bloch_e_im", "bloch_h_re", "bloch_h_im"]
+            .map(|label| storage(label, bytemuck::cast_slice(&zeros)));
+        let [ca, cb, cp, cq] = [&coeffs.ca, &coeffs.cb, &coeffs.cp, &coeffs.cq]
+            .map(|c| storage("bloch_coeffs", bytemuck::cast_slice(c)));
+        let mask = (0..3).filter(|&a| periodic[a]).fold(0, |m, a| m | 1 << a);
+        let mut base = BlochParams {
+            size: [grid.nx, grid.ny, grid.nz, mask],
+            inv_d: [1.0 / grid.dx, 1.0 / grid.dy, 1.0 / grid.dz, 0.0].map(|d| d as f32),
+            phase_cos: [1.0; 4],
+            phase_sin: [0.0; 4],
+            sources: [[0; 4]; POINTS],
+        };
+        for (slot, &(cell, component)) in base.sources.iter_mut().zip(sources) {
+            *slot = [cell, component, 1, 0];
+        }
+        let params = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("bloch_params"),
+            contents: bytemuck::bytes_of(&base),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let mut entries = vec![bgl_uniform_entry(0)];
+        entries.extend((1..=4).map(|b| bgl_storage_entry(b, false)));
+        entries.extend((5..=8).map(|b| bgl_storage_entry(b, true)));
+        let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("bloch_bgl"),
+            entries: &entries,
+        });
+        let buffers = [
+            &params, &fields[0], &fields[1], &fields[2], &fields[3], &ca, &cb, &cp, &cq,
+        ];
+        let bind_entries: Vec<_> = buffers
+            .iter()
+            .enumerate()
+            .map(|(b, buffer)| bg_entry(b as u32, buffer.as_entire_binding()))
+            .collect();
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("bg_bloch"),
+            layout: &bgl,
+            entries: &bind_entries,
+        });
+
+        let source = include_str!("shaders/bloch.wgsl");
+        let pipeline = |entry: &str| compute_pipeline_entry(device, "bloch", source, &bgl, entry);
+        BlochSolver {
+            update_h: pipeline("update_h"),
+            update_e: pipeline("update_e"),
+            inject: pipeline("inject"),
+            bind_group,
+            params,
+            fields,
+            base,
+            probes: probes.to_vec(),
+            groups: [grid.nx, grid.ny, grid.nz].map(|n| n.div_ceil(4)),
+            dt: grid.dt,
+        }
+    }
+
+    /// Run `steps` steps from rest at Bloch vector `k` (units of 2π/L) with
+    /// the soft sources driven by `waveform`, and return Re(Ex + Ey + Ez)
+    /// summed over the probe cells after every step.
+    pub fn run(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        k: [f64; 3],
+        waveform: &Waveform,
+        steps: u32,
+    ) -> Result<Vec<f64>, FdtdError> {
+        let mut params = self.base;
+        for (axis, k) in k.iter().enumerate() {
+            let phase = 2.0 * PI * k;
+            params.phase_cos[axis] = phase.cos() as f32;
+            params.phase_sin[axis] = phase.sin() as f32;
+        }
+        queue.write_buffer(&self.params, 0, bytemuck::bytes_of(&params));
+        let mut encoder = device.create_command_encoder(&Default::default());
+        for field in &self.fields {
+            encoder.clear_buffer(field, 0, None);
+        }
+        queue.submit(Some(encoder.finish()));
+
+        let probes = &self.probes;
+        let stride = (probes.len() * 16) as u64;
+        let trace = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("bloch_trace"),
+            size: steps as u64 * stride,
+            usage: wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        // The drive value is the w lane of `inv_d`
+        let drive_offset = 16 + 12;
+        for n in 0..steps {
+            let value = waveform.value(n as f64, self.dt) as f32;
+            queue.write_buffer(&self.params, drive_offset, bytemuck::bytes_of(&value));
+
+            let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("bloch_step"),
+            });
+            {
+                let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some("bloch update"),
+                    timestamp_writes: None,
+                });
+                pass.set_bind_group(0, &self.bind_group, &[]);
+                let [gx, gy, gz] = self.groups;
+                pass.set_pipeline(&self.update_h);
+                pass.dispatch_workgroups(gx, gy, gz);
+                pass.set_pipeline(&self.update_e);
+                pass.dispatch_workgroups(gx, gy, gz);
+                pass.set_pipeline(&self.inject);
+                pass.dispatch_workgroups(1, 1, 1);
+            }
+            for (p, &cell) in probes.iter().enumerate() {
+                let to = n as u64 * stride + p as u64 * 16;
+                encoder.copy_buffer_to_buffer(&self.fields[0], cell as u64 * 16, &trace, to, 16);
+            }
+            queue.submit(Some(encoder.finish()));
+        }
+
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("bloch_readback"),
+            size: steps as u64 * stride,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&Default::default());
+        encoder.copy_buffer_to_buffer(&trace, 0, &readback, 0, steps as u64 * stride);
+        queue.submit(Some(encoder.finish()));
+
+        let slice = readback.slice(..);
+        gpu::map_read(device, &[slice])?;
+        let values: Vec<[f32; 4]> = bytemuck::cast_slice(&slice.get_mapped_range()).to_vec();
+        readback.unmap();
+        Ok(values
+            .chunks(probes.len())
+            .map(|step| step.iter().map(|e| (e[0] + e[1] + e[2]) as f64).sum())
+            .collect())
+    }
+}
+
+/// The modes at every k-point of `spec`'s path.
+pub fn compute(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    grid: &Grid,
+    coeffs: &Coefficients,
+    spec: &BandDiagram,
+) -> Result<Vec<Vec<Resonance>>, FdtdError> {
+    // Random cells, off the PEC walls of non-periodic axes
+    let mut rng = StdRng::seed_from_u64(spec.seed);
+    let n = [grid.nx, grid.ny, grid.nz];
+    let random_cell = |rng: &mut StdRng| {
+        let c = [0, 1, 2].map(|a| {
+            let lo = if spec.periodic[a] { 0 } else { 1 };
+            rng.gen_range(lo..n[a].max(lo + 1))
+        });
+        grid.idx(c[0], c[1], c[2]) as u32
+    };
+    let sources: Vec<(u32, u32)> = (0..POINTS)
+        .map(|_| (random_cell(&mut rng), rng.gen_range(0..3)))
+        .collect();
+    let probes: Vec<u32> = (0..POINTS).map(|_| random_cell(&mut rng)).collect();
+    let solver = BlochSolver::new(device, grid, coeffs, spec.periodic, &sources, &probes);
+
+    let pulse = spec.pulse(grid.dt);
+    // Analyse the free ringing after the pulse
+    let start = match pulse {
+        Waveform::ModulatedGaussian { width, delay, .. } => (delay + 3.0 * width) as usize,
+        _ => 0,
+    };
+    if start >= spec.steps as usize / 2 {
+        return Err(ConfigError(format!(
+            "band diagram {}: {} steps leave too little ringing after the {start}-step pulse",
+            spec.name, spec.steps
+        ))
+        .into());
+    }
+    spec.k_points()
+        .iter()
+        .map(|&k| {
+            let trace = solver.run(device, queue, k, &pulse, spec.steps)?;
+            Ok(harminv::analyze(&trace[start..], grid.dt, &spec.analysis))
+        })
+        .collect()
+}
+
+/// Write `<name>_bands.csv` and `<name>_bands.png` for the modes of every k-point.
+pub fn write_results(dir: &Path, spec: &BandDiagram, bands: &[Vec<Resonance>]) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let points = spec.k_points();
+    let mut file = BufWriter::new(fs::File::create(
+        dir.join(format!("{}_bands.csv", spec.name)),
+    )?);
+    writeln!(file, "k_index,kx,ky,kz,frequency,q")?;
+    for (i, (k, modes)) in points.iter().zip(bands).enumerate() {
+        for m in modes {
+            writeln!(
+                file,
+                "{i},{:.6},{:.6},{:.6},{:e},{:.1}",
+                k[0], k[1], k[2], m.frequency, m.q
+            )?;
+        }
+    }
+    file.flush()?;
+
+    // Frequency (up to f_max) against k index, corners as grey lines
+    let mut rgb = vec![255u8; (WIDTH * HEIGHT * 3) as usize];
+    let margin = 10.0;
+    let last = (points.len().max(2) - 1) as f64;
+    let x_of = |i: f64| margin + i / last * (WIDTH as f64 - 2.0 * margin);
+    let y_of =
+        |f: f64| HEIGHT as f64 - margin - f / spec.analysis.f_max * (HEIGHT as f64 - 2.0 * margin);
+    let mut dot = |x: f64, y: f64, colour: [u8; 3]| {
+        let (x, y) = (x.round() as i64, y.round() as i64);
+        if x >= 0 && y >= 0 && x < WIDTH as i64 && y < HEIGHT as i64 {
+            let i = 3 * (y as usize * WIDTH as usize + x as usize);
+            rgb[i..i + 3].copy_from_slice(&colour);
+        }
+    };
+    for corner in 0..spec.path.len() {
+        let x = x_of((corner as u32 * spec.points_per_segment.max(1)) as f64);
+        for y in 0..HEIGHT {
+            dot(x, y as f64, [200, 200, 200]);
+        }
+    }
+    for x in 0..WIDTH {
+        dot(x as f64, y_of(0.0), [120, 120, 120]);
+    }
+    for (i, modes) in bands.iter().enumerate() {
+        for m in modes {
+            let (x, y) = (x_of(i as f64), y_of(m.frequency));
+            for (dx, dy) in [(0, 0), (1, 0), (-1, 0), (0, 1), (0, -1)] {
+                dot(x + dx as f64, y + dy as f64, [30, 30, 220]);
+            }
+        }
+    }
+    png::write_rgb(
+        &dir.join(format!("{}_bands.png", spec.name)),
+        WIDTH,
+        HEIGHT,
+        &rgb,
+    )
+}
@@ -0,0 +1,276 @@
+//! Throughput benchmark of the update: `fdtd_3d bench`.
+//!
+//! [`bench()`] steps an empty grid with no probes or outputs for a number of
+//! steps or a wall time, after a few untimed ones that build the pipelines,
+//! and reports the cell updates per second, the mean time per step of the
+//! H and E update kernels from GPU timestamps (with
+//! [`Config::time_kernels`](crate::Config::time_kernels), on adapters that
+//! have them) and the memory bandwidth this takes at least: each kernel
+//! reads the three components it differentiates, reads and writes the three
+//! it updates and reads a dense pair of coefficients per cell, which is
+//! [`BYTES_PER_CELL`].  Caches make the real traffic no lower, so the
+//! figure is a floor; against the peak bandwidth of the GPU, given by the
+//! user as wgpu cannot tell it, it says how far the update is from being
+//! bound by memory.
+
+use std::fmt;
+use std::time::Duration;
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+
+use serde_json::{json, Value};
+
+use crate::backend::ComputeBackend;
+use crate::config::{Config, Scene};
+use crate::error::FdtdError;
+use crate::gpu;
+use crate::interrupt;
+use crate::manifest::RunInfo;
+use crate::simulation::Simulation;
+
+/// The kernels timed, in the order of their timestamps.
+pub const KERNELS: [&str; 2] = ["H update", "E update"];
+
+/// Bytes each update kernel moves per cell at least: six f32 components
+/// read, three written and two `vec4` coefficients read.
+pub const BYTES_PER_CELL: u64 = 9 * 4 + 2 * 16;
+
+/// Untimed steps before the measurement.
+pub const WARMUP_STEPS: u32 = 10;
+
+/// Wall time of one batch of steps of a timed benchmark, between which the
+/// clock is checked.
+const BATCH_TIME: Duration = Duration::from_millis(100);
+
+/// Steps whose kernel timestamps are kept, the last ones.
+const TIMED_STEPS: u32 = 256;
+
+/// Bytes between the timestamps of consecutive steps in the resolve buffer.
+const SLOT: u64 = wgpu::QUERY_RESOLVE_BUFFER_ALIGNMENT;
+
+/// How long a benchmark steps.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BenchLength {
+    Steps(u32),
+    /// As many steps as fit in this wall time.
+    Wall(Duration),
+}
+
+/// What a benchmark measured.
+#[derive(Clone, Debug)]
+pub struct BenchReport {
+    /// The run, its steps and stepping time those measured.
+    pub info: RunInfo,
+    /// Mean time per step of each of [`KERNELS`] over the last steps, empty
+    /// without GPU timestamps.
+    pub kernels: Vec<(&'static str, Duration)>,
+    /// Bandwidth of the device in bytes per second, to compare with.
+    pub peak_bandwidth: Option<f64>,
+}
+
+impl BenchReport {
+    fn cells(&self) -> f64 {
+        self.info.size.iter().map(|&n| n as f64).product()
+    }
+
+    /// Bytes per second the update moves at least over the whole steps.
+    pub fn bandwidth(&self) -> f64 {
+        let bytes = (KERNELS.len() as u64 * BYTES_PER_CELL) as f64 * self.cells();
+        bytes * self.info.steps as f64 / self.info.stepping.as_secs_f64()
+    }
+
+    /// Bytes per second one kernel taking `time` per step moves at least.
+    pub fn kernel_bandwidth(&self, time: Duration) -> f64 {
+        BYTES_PER_CELL as f64 * self.cells() / time.as_secs_f64()
+    }
+
+    /// The report as one JSON object.
+    pub fn json(&self) -> Value {
+        let kernels: Vec<Value> = self
+            .kernels
+            .iter()
+            .map(|&(name, time)| {
+                json!({
+                    "name": name,
+                    "seconds_per_step": time.as_secs_f64(),
+                    "bytes_per_second": self.kernel_bandwidth(time),
+                })
+            })
+            .collect();
+        json!({
+            "run": self.info.json(),
+            "bytes_per_second": self.bandwidth(),
+            "kernels": kernels,
+            "peak_bytes_per_second": self.peak_bandwidth,
+        })
+    }
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let info = &self.info;
+        let [nx, ny, nz] = info.size;
+        write!(
+            f,
+            "{} on {}: {:.1} MCells/s ({} steps of {nx}×{ny}×{nz} in {:.3} s)",
+            info.precision,
+            info.adapter,
+            info.throughput() / 1e6,
+            info.steps,
+            info.stepping.as_secs_f64()
+        )?;
+        let utilisation = |bandwidth: f64| match self.peak_bandwidth {
+            Some(peak) => format!(
+                " ({:.0} % of {:.0} GB/s)",
+                100.0 * bandwidth / peak,
+                peak / 1e9
+            ),
+            None => String::new(),
+        };
+        let bandwidth = self.bandwidth();
+        write!(
+            f,
+            "\n  memory traffic  {:.1} GB/s at least{}",
+            bandwidth / 1e9,
+            utilisation(bandwidth)
+        )?;
+        for &(name, time) in &self.kernels {
+            let bandwidth = self.kernel_bandwidth(time);
+            write!(
+                f,
+                "\n  {name:<14}  {:.3} ms/step, {:.1} GB/s{}",
+                time.as_secs_f64() * 1e3,
+                bandwidth / 1e9,
+                utilisation(bandwidth)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Step the structure of `config`, on the GPU or the CPU as it says, for
+/// `length` after [`WARMUP_STEPS`] steps and report the throughput of the
+/// measured ones; `config` is expected to have no probes or outputs.
+pub fn bench(mut config: Config, length: BenchLength) -> Result<BenchReport, FdtdError> {
+    let measured = match length {
+        BenchLength::Steps(steps) => steps,
+        BenchLength::Wall(_) => 0,
+    };
+    config.steps = WARMUP_STEPS + measured;
+    config.time_kernels = true;
+    let mut simulation = Simulation::new(config, Scene::Structure)?;
+    simulation.run(WARMUP_STEPS)?;
+    let start = Instant::now();
+    let steps = match length {
+        BenchLength::Steps(steps) => {
+            simulation.run(steps)?;
+            steps
+        }
+        BenchLength::Wall(duration) => {
+            let (mut steps, mut batch) = (0, 1);
+            while start.elapsed() < duration && !interrupt::requested() {
+                let batch_start = Instant::now();
+                simulation.run(batch)?;
+                steps += batch;
+                let per_step = batch_start.elapsed().as_secs_f64() / batch as f64;
+                let left = duration.saturating_sub(start.elapsed()).min(BATCH_TIME);
+                batch = ((left.as_secs_f64() / per_step) as u32).max(1);
+            }
+            steps
+        }
+    };
+    let stepping = start.elapsed();
+    let kernels = simulation.kernel_times()?;
+    let (_, mut info) = simulation.finish()?;
+    (info.steps, info.stepping) = (steps, stepping);
+    Ok(BenchReport {
+        info,
+        kernels,
+        peak_bandwidth: None,
+    })
+}
+
+/// GPU timestamps at the start and end of the passes of [`KERNELS`], kept
+/// for the last [`TIMED_STEPS`] steps.
+pub(crate) struct KernelTimer {
+    queries: wgpu::QuerySet,
+    /// The timestamps of step n at [`SLOT`] × (n mod [`TIMED_STEPS`]).
+    resolved: wgpu::Buffer,
+    /// Nanoseconds per timestamp tick.
+    period: f64,
+    steps: u32,
+}
+
+impl KernelTimer {
+    /// None when `device` was opened without timestamp queries.
+    pub(crate) fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Option<Self> {
+        if !device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+        let queries = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("kernel_timestamps"),
+            ty: wgpu::QueryType::Timestamp,
+            count: 2 * KERNELS.len() as u32,
+        });
+        let resolved = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("kernel_timestamps"),
+            size: TIMED_STEPS as u64 * SLOT,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        Some(KernelTimer {
+            queries,
+            resolved,
+            period: queue.get_timestamp_period() as f64,
+            steps: 0,
+        })
+    }
+
+    /// The timestamps of the pass of kernel `kernel`.
+    pub(crate) fn writes(&self, kernel: usize) -> wgpu::ComputePassTimestampWrites<'_> {
+        let begin = 2 * kernel as u32;
+        wgpu::ComputePassTimestampWrites {
+            query_set: &self.queries,
+            beginning_of_pass_write_index: Some(begin),
+            end_of_pass_write_index: Some(begin + 1),
+        }
+    }
+
+    /// Keep the timestamps of this step, after its passes in `encoder`.
+    pub(crate) fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        let slot = (self.steps % TIMED_STEPS) as u64 * SLOT;
+        let queries = 0..2 * KERNELS.len() as u32;
+        encoder.resolve_query_set(&self.queries, queries, &self.resolved, slot);
+        self.steps += 1;
+    }
+
+    /// The mean time per step of each kernel over the steps kept.
+    pub(crate) fn times(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<Vec<(&'static str, Duration)>, FdtdError> {
+        let kept = self.steps.min(TIMED_STEPS);
+        if kept == 0 {
+            return Ok(Vec::new());
+        }
+        let size = kept as u64 * SLOT;
+        let values = gpu::read_f32(device, queue, &self.resolved, 0, size)?;
+        let words: &[u32] = bytemuck::cast_slice(&values);
+        let ticks = |slot: usize, query: usize| {
+            let at = slot * SLOT as usize / 4 + 2 * query;
+            words[at] as u64 | (words[at + 1] as u64) << 32
+        };
+        let times = KERNELS.iter().enumerate().map(|(k, &name)| {
+            let total: u64 = (0..kept as usize)
+                .map(|slot| ticks(slot, 2 * k + 1).saturating_sub(ticks(slot, 2 * k)))
+                .sum();
+            let nanos = total as f64 * self.period / kept as f64;
+            (name, Duration::from_nanos(nanos as u64))
+        });
+        Ok(times.collect())
+    }
+}
@@ -0,0 +1,206 @@
+//! Fluent setup of a [`Simulation`].
+//!
+//! ```ignore
+//! let mut sim = Simulation::builder()
+//!     .grid(Grid::uniform([64, 64, 64], 1e-3, 1e-12))
+//!     .steps(300)
+//!     .material(
+//!         Shape::Sphere { center: [0.032; 3], radius: 0.01 },
+//!         Material { eps_r: 4.0, ..Material::VACUUM },
+//!     )
+//!     .source([20, 32, 32], Waveform::Gaussian { width: 20.0, delay: 40.0 })
+//!     .boundary(Axis::X, Boundary::Periodic)
+//!     .probe(probe)
+//!     .build()?;
+//! sim.run(300)?;
+//! ```
+//!
+//! Only the grid and the number of steps are required; everything else
+//! starts from [`Config::new`].  [`SimulationBuilder::build`] validates the
+//! configuration before the device is opened, so a mistake is reported as
+//! [`FdtdError::InvalidConfig`] instead of a failure half-way through the GPU
+//! setup.
+//! Settings without a method of their own go through
+//! [`SimulationBuilder::configure`].
+
+use crate::config::{Boundary, Config, ConfigError, Scene};
+use crate::dft::DftMonitor;
+use crate::error::FdtdError;
+use crate::flux::{FluxBox, FluxMonitor};
+use crate::geometry::{Object, Shape};
+use crate::grid::{Axis, Grid};
+use crate::kspace::KSpaceMonitor;
+use crate::lumped::LumpedElement;
+use crate::materials::{Material, MaterialMap};
+use crate::monitors::Monitor;
+use crate::ports::FeedPort;
+use crate::precision::Precision;
+use crate::probes::Probe;
+use crate::reduced::Mode;
+use crate::simulation::Simulation;
+use crate::sources::Waveform;
+use crate::stability::Scheme;
+
+/// A [`Config`] under construction; see the module documentation.
+#[derive(Clone, Debug)]
+pub struct SimulationBuilder {
+    grid: Option<Grid>,
+    steps: Option<u32>,
+    /// Source cell and waveform, placed once the grid is known.
+    source: Option<([u32; 3], Waveform)>,
+    /// Everything else, on a placeholder grid.
+    config: Config,
+}
+
+impl Default for SimulationBuilder {
+    fn default() -> Self {
+        SimulationBuilder::new()
+    }
+}
+
+impl SimulationBuilder {
+    pub fn new() -> SimulationBuilder {
+        SimulationBuilder {
+            grid: None,
+            steps: None,
+            source: None,
+            config: Config::new(Grid::uniform([1; 3], 1.0, 1.0), 0),
+        }
+    }
+
+    /// Cells, spacings and Δt (required).
+    pub fn grid(mut self, grid: Grid) -> Self {
+        self.grid = Some(grid);
+        self
+    }
+
+    /// Time steps of the run (required).
+    pub fn steps(mut self, steps: u32) -> Self {
+        self.steps = Some(steps);
+        self
+    }
+
+    /// Hard Ez point source; by default a Gaussian at the centre.
+    pub fn source(mut self, cell: [u32; 3], waveform: Waveform) -> Self {
+        self.source = Some((cell, waveform));
+        self
+    }
+
+    /// Paint `material` inside `shape`, over the objects added before.
+    pub fn material(self, shape: Shape, material: Material) -> Self {
+        self.object(Object { shape, material })
+    }
+
+    /// Materials of every cell, under the objects; a `[i, j, k]` array of
+    /// ε_r or of [`Material`]s with the grid's shape.
+    pub fn material_map(mut self, map: impl Into<MaterialMap>) -> Self {
+        self.config.material_map = Some(map.into());
+        self
+    }
+
+    pub fn object(mut self, object: Object) -> Self {
+        self.config.objects.push(object);
+        self
+    }
+
+    /// Boundary along `axis`; PEC unless set.
+    pub fn boundary(mut self, axis: Axis, boundary: Boundary) -> Self {
+        self.config.boundaries[axis.lane()] = boundary;
+        self
+    }
+
+    pub fn probe(mut self, probe: Probe) -> Self {
+        self.config.probes.push(probe);
+        self
+    }
+
+    pub fn monitor(mut self, monitor: Monitor) -> Self {
+        self.config.monitors.push(monitor);
+        self
+    }
+
+    pub fn dft_monitor(mut self, monitor: DftMonitor) -> Self {
+        self.config.dft_monitors.push(monitor);
+        self
+    }
+
+    pub fn flux_monitor(mut self, monitor: FluxMonitor) -> Self {
+        self.config.flux_monitors.push(monitor);
+        self
+    }
+
+    pub fn flux_box(mut self, flux_box: FluxBox) -> Self {
+        self.config.flux_boxes.push(flux_box);
+        self
+    }
+
+    pub fn kspace_monitor(mut self, monitor: KSpaceMonitor) -> Self {
+        self.config.kspace_monitors.push(monitor);
+        self
+    }
+
+    pub fn lumped(mut self, element: LumpedElement) -> Self {
+        self.config.lumped.push(element);
+        self
+    }
+
+    pub fn port(mut self, port: FeedPort) -> Self {
+        self.config.ports.push(port);
+        self
+    }
+
+    pub fn scheme(mut self, scheme: Scheme) -> Self {
+        self.config.scheme = scheme;
+        self
+    }
+
+    pub fn precision(mut self, precision: Precision) -> Self {
+        self.config.precision = precision;
+        self
+    }
+
+    /// Directory of the monitor, analysis and summary files.
+    pub fn output_dir(mut self, dir: &'static str) -> Self {
+        self.config.monitor_dir = dir;
+        self
+    }
+
+    /// Set anything else on the configuration directly; the grid, step
+    /// count and source cell it sees are placeholders.
+    pub fn configure(mut self, f: impl FnOnce(&mut Config)) -> Self {
+        f(&mut self.config);
+        self
+    }
+
+    /// The validated configuration, without opening a device.
+    pub fn build_config(self) -> Result<Config, ConfigError> {
+        let grid = self
+            .grid
+            .ok_or_else(|| ConfigError("no grid given".into()))?;
+        let steps = self
+            .steps
+            .ok_or_else(|| ConfigError("no step count given".into()))?;
+        let centre = [grid.nx / 2, grid.ny / 2, grid.nz / 2];
+        let (source, waveform) = self.source.unwrap_or((centre, self.config.waveform));
+        let config = Config {
+            grid,
+            steps,
+            source,
+            waveform,
+            ..self.config
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Validate and set up the GPU passes of the 3D run.
+    pub fn build(self) -> Result<Simulation, FdtdError> {
+        let config = self.build_config()?;
+        if config.mode != Mode::ThreeD || config.bands.is_some() {
+            return Err(
+                ConfigError("reduced modes and band diagrams go through run_scene".into()).into(),
+            );
+        }
+        Simulation::new(config, Scene::Structure)
+    }
+}
@@ -0,0 +1,127 @@
+//! Checkpoint files of a run's state.
+//!
+//! A checkpoint is the 32-byte header `FDTDCKP1`, nx, ny, nz and the steps
+//! taken (u32 each) and Δt (f64), all little-endian, followed by named
+//! sections: an 8-byte zero-padded name, the byte count (u64) and the
+//! bytes.
+//! [`ComputeBackend::save_checkpoint`](crate::ComputeBackend::save_checkpoint)
+//! writes
+//!
+//! - `scene`: the scene run, `Structure` or `Reference`;
+//! - `Ex` … `Hz`: the f32 field components, x fastest (a single cell while
+//!   another precision runs alone), and `pEx` … `pHz` those of the other
+//!   precision in its own format;
+//! - `ade`, `sibc`: the state of the dispersive, lumped and surface-impedance
+//!   edges;
+//! - `dft0`, `dft1`, …: the DFT accumulators, component by component of
+//!   each monitor (the flux, RCS, pattern, mode and SAR spectra included);
+//! - `probe0`, …, `port0`, …: the probe and port traces so far (f64);
+//! - `counters`: the probe rows and flux steps reported (u32 each) and the
+//!   largest f32 difference and reference of the first probe (f64 each).
+//!
+//! [`ComputeBackend::resume`](crate::ComputeBackend::resume) reads it back
+//! into a run of the same configuration, which can then go on past the
+//! steps it was set up for.  The `fdtd_3d` binary writes `checkpoint.bin`
+//! in the monitor directory on Ctrl-C (see [`crate::interrupt`]) and every
+//! `--checkpoint-every` steps, goes back to the last one when the GPU
+//! device is lost, and resumes from one with `--resume`.
+//!
+//! A file of the `scene` and field sections alone carries the fields of one
+//! run over to the next case of a warm-started sweep (see
+//! [`crate::sweep`]), whose setup and steps are its own.
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::grid::Grid;
+
+/// File name of the checkpoint written on an interrupt.
+pub const FILE_NAME: &str = "checkpoint.bin";
+
+/// File name of the fields a run leaves for the next case of a
+/// warm-started sweep (see [`crate::backend::save_fields`]).
+pub const FIELDS_FILE: &str = "fields.bin";
+
+const MAGIC: &[u8; 8] = b"FDTDCKP1";
+
+/// The contents of a checkpoint file.
+#[derive(Clone, Debug)]
+pub struct Checkpoint {
+    /// Cells per axis.
+    pub size: [u32; 3],
+    pub steps: u32,
+    pub dt: f64,
+    pub sections: Vec<(String, Vec<u8>)>,
+}
+
+impl Checkpoint {
+    /// The bytes of section `name`.
+    pub fn section(&self, name: &str) -> Option<&[u8]> {
+        self.sections
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, bytes)| &bytes[..])
+    }
+}
+
+/// Write the header of `grid` after `steps` steps and `sections` to `path`.
+pub fn write(path: &Path, grid: &Grid, steps: u32, sections: &[(&str, &[u8])]) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut out = BufWriter::new(File::create(path)?);
+    out.write_all(MAGIC)?;
+    for v in [grid.nx, grid.ny, grid.nz, steps] {
+        out.write_all(&v.to_le_bytes())?;
+    }
+    out.write_all(&grid.dt.to_le_bytes())?;
+    for (name, bytes) in sections {
+        let mut padded = [0u8; 8];
+        padded[..name.len()].copy_from_slice(name.as_bytes());
+        out.write_all(&padded)?;
+        out.write_all(&(bytes.len() as u64).to_le_bytes())?;
+        out.write_all(bytes)?;
+    }
+    out.flush()
+}
+
+/// Read the checkpoint at `path`.
+pub fn read(path: &Path) -> io::Result<Checkpoint> {
+    let in_file = |e: io::Error| io::Error::new(e.kind(), format!("{}: {e}", path.display()));
+    let bytes = fs::read(path).map_err(in_file)?;
+    parse(&bytes).map_err(in_file)
+}
+
+fn parse(bytes: &[u8]) -> io::Result<Checkpoint> {
+    if bytes.len() < 32 || &bytes[..8] != MAGIC {
+        return Err(invalid("not a checkpoint file".into()));
+    }
+    let u32_at = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+    let mut sections = Vec::new();
+    let mut at = 32;
+    while at < bytes.len() {
+        let header = bytes
+            .get(at..at + 16)
+            .ok_or_else(|| invalid(format!("truncated section header at byte {at}")))?;
+        let name = String::from_utf8_lossy(&header[..8])
+            .trim_end_matches('\0')
+            .to_string();
+        let len = u64::from_le_bytes(header[8..].try_into().unwrap()) as usize;
+        let data = bytes
+            .get(at + 16..at + 16 + len)
+            .ok_or_else(|| invalid(format!("section {name} is truncated")))?;
+        sections.push((name, data.to_vec()));
+        at += 16 + len;
+    }
+    Ok(Checkpoint {
+        size: [u32_at(8), u32_at(12), u32_at(16)],
+        steps: u32_at(20),
+        dt: f64::from_le_bytes(bytes[24..32].try_into().unwrap()),
+        sections,
+    })
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
@@ -0,0 +1,582 @@
+//! Built-in circuit solver for co-simulation (modified nodal analysis).
+//!
+//! Netlists use a SPICE subset, one element per line:
+//!
+//!   R<name> a b value            resistor (Ω)
+//!   C<name> a b value            capacitor (F)
+//!   L<name> a b value            inductor (H)
+//!   V<name> a b [DC] value       voltage source, + terminal a
+//!   V<name> a b SIN(vo va f [td])
+//!   V<name> a b PULSE(v1 v2 td tr tf pw [per])
+//!   I<name> a b ...              current source from a to b through it,
+//!                                same values as V
+//!   D<name> a b [IS=..] [N=..]   diode, anode a (IS = 1e-14 A, N = 1)
+//!
+//! Node `0` (or `gnd`) is ground; values take the SPICE suffixes f, p, n,
+//! u, m, k, meg, g, t followed by any unit letters (`10pF`, `1.5k`).
+//! Lines starting with `*` and control lines starting with `.` are
+//! ignored, as are `;` comments; there is no title line, `.model` or
+//! subcircuit support.
+//!
+//! The circuit is integrated with the trapezoidal rule at a fixed step
+//! (companion models for C and L) and Newton iteration for diodes, with
+//! the junction voltage step limited to keep the exponential in range.
+//! One node, the port, is driven by a voltage from outside; see
+//! [`crate::cosim`].
+
+use std::f64::consts::PI;
+
+/// Thermal voltage kT/q at 300 K (V).
+const VT: f64 = 0.025852;
+
+/// Time dependence of an independent source.
+#[derive(Copy, Clone, Debug)]
+enum Source {
+    Dc(f64),
+    Sin {
+        offset: f64,
+        amplitude: f64,
+        freq: f64,
+        delay: f64,
+    },
+    Pulse {
+        v1: f64,
+        v2: f64,
+        delay: f64,
+        rise: f64,
+        fall: f64,
+        width: f64,
+        period: f64,
+    },
+}
+
+impl Source {
+    fn value(&self, t: f64) -> f64 {
+        match *self {
+            Source::Dc(v) => v,
+            Source::Sin {
+                offset,
+                amplitude,
+                freq,
+                delay,
+            } => {
+                if t < delay {
+                    offset
+                } else {
+                    offset + amplitude * (2.0 * PI * freq * (t - delay)).sin()
+                }
+            }
+            Source::Pulse {
+                v1,
+                v2,
+                delay,
+                rise,
+                fall,
+                width,
+                period,
+            } => {
+                if t < delay {
+                    return v1;
+                }
+                let mut t = t - delay;
+                if period > 0.0 {
+                    t %= period;
+                }
+                if t < rise {
+                    v1 + (v2 - v1) * t / rise.max(f64::MIN_POSITIVE)
+                } else if t < rise + width {
+                    v2
+                } else if t < rise + width + fall {
+                    v2 + (v1 - v2) * (t - rise - width) / fall
+                } else {
+                    v1
+                }
+            }
+        }
+    }
+}
+
+/// One element between nodes `a` and `b` (`None` is ground).
+#[derive(Copy, Clone, Debug)]
+enum Element {
+    Resistor { g: f64 },
+    Capacitor { c: f64 },
+    Inductor { l: f64 },
+    VoltageSource(Source),
+    CurrentSource(Source),
+    Diode { is: f64, n: f64 },
+}
+
+#[derive(Clone, Debug)]
+struct Branch {
+    element: Element,
+    a: Option<usize>,
+    b: Option<usize>,
+}
+
+/// A parsed netlist.
+#[derive(Clone, Debug)]
+pub struct Netlist {
+    /// Names of the non-ground nodes, in order of appearance.
+    pub nodes: Vec<String>,
+    branches: Vec<Branch>,
+}
+
+/// Number with an optional SPICE scale suffix and unit letters.
+fn parse_value(token: &str) -> Result<f64, String> {
+    let lower = token.to_ascii_lowercase();
+    let end = lower
+        .char_indices()
+        .find(|&(i, c)| {
+            !(c.is_ascii_digit()
+                || c == '.'
+                || c == '+'
+                || c == '-'
+                || (c == 'e'
+                    && lower[i + 1..]
+                        .chars()
+                        .next()
+                        .is_some_and(|n| n.is_ascii_digit() || n == '-' || n == '+')))
+        })
+        .map_or(lower.len(), |(i, _)| i);
+    let number: f64 = lower[..end]
+        .parse()
+        .map_err(|_| format!("bad value {token:?}"))?;
+    let suffix = &lower[end..];
+    let scale = if suffix.starts_with("meg") {
+        1e6
+    } else {
+        match suffix.chars().next() {
+            Some('f') => 1e-15,
+            Some('p') => 1e-12,
+            Some('n') => 1e-9,
+            Some('u') => 1e-6,
+            Some('m') => 1e-3,
+            Some('k') => 1e3,
+            Some('g') => 1e9,
+            Some('t') => 1e12,
+            _ => 1.0,
+        }
+    };
+    Ok(number * scale)
+}
+
+/// Source specification from the tokens after the nodes.
+fn parse_source(tokens: &[&str]) -> Result<Source, String> {
+    let text = tokens.join(" ");
+    let upper = text.to_ascii_uppercase();
+    let arguments = |name: &str| -> Result<Vec<f64>, String> {
+        let inner = upper[name.len()..]
+            .trim()
+            .trim_start_matches('(')
+            .trim_end_matches(')');
+        inner
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|t| !t.is_empty())
+            .map(parse_value)
+            .collect()
+    };
+    if upper.starts_with("SIN") {
+        let a = arguments("SIN")?;
+        if a.len() < 3 {
+            return Err(format!("SIN needs vo va f: {text:?}"));
+        }
+        Ok(Source::Sin {
+            offset: a[0],
+            amplitude: a[1],
+            freq: a[2],
+            delay: a.get(3).copied().unwrap_or(0.0),
+        })
+    } else if upper.starts_with("PULSE") {
+        let a = arguments("PULSE")?;
+        if a.len() < 6 {
+            return Err(format!("PULSE needs v1 v2 td tr tf pw: {text:?}"));
+        }
+        Ok(Source::Pulse {
+            v1: a[0],
+            v2: a[1],
+            delay: a[2],
+            rise: a[3],
+            fall: a[4],
+            width: a[5],
+            period: a.get(6).copied().unwrap_or(0.0),
+        })
+    } else {
+        let value = match tokens {
+            [dc, v, ..] if dc.eq_ignore_ascii_case("dc") => v,
+            [v, ..] => v,
+            [] => return Err("source without a value".to_string()),
+        };
+        Ok(Source::Dc(parse_value(value)?))
+    }
+}
+
+impl Netlist {
+    pub fn parse(text: &str) -> Result<Netlist, String> {
+        let mut netlist = Netlist {
+            nodes: Vec::new(),
+            branches: Vec::new(),
+        };
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split(';').next().unwrap().trim();
+            if line.is_empty() || line.starts_with('*') || line.starts_with('.') {
+                continue;
+            }
+            let error = |e: String| format!("line {}: {e}", number + 1);
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            if tokens.len() < 3 {
+                return Err(error(format!(
+                    "expected an element and two nodes: {line:?}"
+                )));
+            }
+            let a = netlist.node(tokens[1]);
+            let b = netlist.node(tokens[2]);
+            let rest = &tokens[3..];
+            let value = || -> Result<f64, String> {
+                let v = rest
+                    .first()
+                    .ok_or_else(|| format!("missing value: {line:?}"))?;
+                parse_value(v)
+            };
+            let element = match tokens[0].chars().next().unwrap().to_ascii_uppercase() {
+                'R' => Element::Resistor {
+                    g: 1.0 / value().map_err(error)?,
+                },
+                'C' => Element::Capacitor {
+                    c: value().map_err(error)?,
+                },
+                'L' => Element::Inductor {
+                    l: value().map_err(error)?,
+                },
+                'V' => Element::VoltageSource(parse_source(rest).map_err(error)?),
+                'I' => Element::CurrentSource(parse_source(rest).map_err(error)?),
+                'D' => {
+                    let (mut is, mut n) = (1e-14, 1.0);
+                    for parameter in rest {
+                        let upper = parameter.to_ascii_uppercase();
+                        if let Some(v) = upper.strip_prefix("IS=") {
+                            is = parse_value(v).map_err(error)?;
+                        } else if let Some(v) = upper.strip_prefix("N=") {
+                            n = parse_value(v).map_err(error)?;
+                        }
+                    }
+                    Element::Diode { is, n }
+                }
+                _ => return Err(error(format!("unsupported element {:?}", tokens[0]))),
+            };
+            netlist.branches.push(Branch { element, a, b });
+        }
+        Ok(netlist)
+    }
+
+    /// Index of node `name`, added on first use; `None` for ground.
+    fn node(&mut self, name: &str) -> Option<usize> {
+        if name == "0" || name.eq_ignore_ascii_case("gnd") {
+            return None;
+        }
+        match self.nodes.iter().position(|n| n == name) {
+            Some(i) => Some(i),
+            None => {
+                self.nodes.push(name.to_string());
+                Some(self.nodes.len() - 1)
+            }
+        }
+    }
+}
+
+/// A netlist in time, its `port` node driven by an external voltage.
+#[derive(Clone, Debug)]
+pub struct Circuit {
+    netlist: Netlist,
+    port: usize,
+    /// Time step (s) and time of the last committed solution.
+    h: f64,
+    time: f64,
+    /// Last solution: node voltages, then the branch currents of voltage
+    /// sources, inductors and the port source, in branch order.
+    solution: Vec<f64>,
+    /// Current of each capacitor branch at the last solution.
+    cap_current: Vec<f64>,
+}
+
+impl Circuit {
+    /// The circuit at rest at t = 0; `port` names the driven node.
+    pub fn new(netlist: Netlist, port: &str, h: f64) -> Result<Circuit, String> {
+        let port = netlist
+            .nodes
+            .iter()
+            .position(|n| n == port)
+            .ok_or_else(|| format!("port node {port:?} is not in the netlist"))?;
+        let size = netlist.nodes.len() + Self::extra(&netlist) + 1;
+        let caps = netlist.branches.len();
+        Ok(Circuit {
+            netlist,
+            port,
+            h,
+            time: 0.0,
+            solution: vec![0.0; size],
+            cap_current: vec![0.0; caps],
+        })
+    }
+
+    /// Branch unknowns besides the port source.
+    fn extra(netlist: &Netlist) -> usize {
+        netlist
+            .branches
+            .iter()
+            .filter(|b| {
+                matches!(
+                    b.element,
+                    Element::VoltageSource(_) | Element::Inductor { .. }
+                )
+            })
+            .count()
+    }
+
+    pub fn node_names(&self) -> &[String] {
+        &self.netlist.nodes
+    }
+
+    /// Node voltages of the last solution.
+    pub fn voltages(&self) -> &[f64] {
+        &self.solution[..self.netlist.nodes.len()]
+    }
+
+    /// Solution at `time` with the port at `v`, from the committed state.
+    fn solve(&self, time: f64, v: f64) -> Vec<f64> {
+        let nodes = self.netlist.nodes.len();
+        let size = self.solution.len();
+        let mut x = self.solution.clone();
+        let h = self.h;
+        for _ in 0..100 {
+            let mut m = vec![vec![0.0; size]; size];
+            let mut rhs = vec![0.0; size];
+            let mut row = nodes;
+            let voltage = |x: &[f64], n: Option<usize>| n.map_or(0.0, |n| x[n]);
+            let stamp_g = |m: &mut Vec<Vec<f64>>, a: Option<usize>, b: Option<usize>, g: f64| {
+                for (p, q, s) in [(a, a, g), (b, b, g), (a, b, -g), (b, a, -g)] {
+                    if let (Some(p), Some(q)) = (p, q) {
+                        m[p][q] += s;
+                    }
+                }
+            };
+            // Current `i` leaving node a and entering node b
+            let stamp_i = |rhs: &mut Vec<f64>, a: Option<usize>, b: Option<usize>, i: f64| {
+                if let Some(a) = a {
+                    rhs[a] -= i;
+                }
+                if let Some(b) = b {
+                    rhs[b] += i;
+                }
+            };
+            for (k, branch) in self.netlist.branches.iter().enumerate() {
+                let (a, b) = (branch.a, branch.b);
+                match branch.element {
+                    Element::Resistor { g } => stamp_g(&mut m, a, b, g),
+                    Element::Capacitor { c } => {
+                        // i(t+h) = 2C/h·(v(t+h) − v(t)) − i(t)
+                        let g = 2.0 * c / h;
+                        let v0 = voltage(&self.solution, a) - voltage(&self.solution, b);
+                        stamp_g(&mut m, a, b, g);
+                        stamp_i(&mut rhs, a, b, -g * v0 - self.cap_current[k]);
+                    }
+                    Element::Inductor { l } => {
+                        // v(t+h) + v(t) = 2L/h·(i(t+h) − i(t))
+                        let v0 = voltage(&self.solution, a) - voltage(&self.solution, b);
+                        let i0 = self.solution[row];
+                        for (n, s) in [(a, 1.0), (b, -1.0)] {
+                            if let Some(n) = n {
+                                m[n][row] += s;
+                                m[row][n] += s;
+                            }
+                        }
+                        m[row][row] -= 2.0 * l / h;
+                        rhs[row] = -v0 - 2.0 * l / h * i0;
+                        row += 1;
+                    }
+                    Element::VoltageSource(source) => {
+                        for (n, s) in [(a, 1.0), (b, -1.0)] {
+                            if let Some(n) = n {
+                                m[n][row] += s;
+                                m[row][n] += s;
+                            }
+                        }
+                        rhs[row] = source.value(time);
+                        row += 1;
+                    }
+                    Element::CurrentSource(source) => stamp_i(&mut rhs, a, b, source.value(time)),
+                    Element::Diode { is, n } => {
+                        let nvt = n * VT;
+                        let vd = voltage(&x, a) - voltage(&x, b);
+                        // Linear continuation past 40 thermal voltages
+                        let arg = (vd / nvt).min(40.0);
+                        let e = arg.exp();
+                        let g = is * e / nvt;
+                        let i = is * (e - 1.0) + g * (vd - arg * nvt);
+                        stamp_g(&mut m, a, b, g);
+                        stamp_i(&mut rhs, a, b, i - g * vd);
+                    }
+                }
+            }
+            // The port source, + terminal at the port node
+            m[self.port][row] += 1.0;
+            m[row][self.port] += 1.0;
+            rhs[row] = v;
+
+            let next = solve_dense(m, rhs);
+            let change = next
+                .iter()
+                .zip(&x)
+                .map(|(a, b)| (a - b).abs())
+                .fold(0.0, f64::max);
+            let scale = next.iter().map(|a| a.abs()).fold(1e-6, f64::max);
+            x = next;
+            let nonlinear = self
+                .netlist
+                .branches
+                .iter()
+                .any(|b| matches!(b.element, Element::Diode { .. }));
+            if !nonlinear || change <= 1e-9 * scale {
+                break;
+            }
+        }
+        x
+    }
+
+    /// Current drawn by the circuit from the port node at `time`.
+    fn port_current(x: &[f64]) -> f64 {
+        -x[x.len() - 1]
+    }
+
+    /// Port current one step ahead with the port at `v`, not committed.
+    pub fn predict(&self, v: f64) -> f64 {
+        Self::port_current(&self.solve(self.time + self.h, v))
+    }
+
+    /// Advance one step with the port at `v`; returns the port current.
+    pub fn step(&mut self, v: f64) -> f64 {
+        let time = self.time + self.h;
+        let x = self.solve(time, v);
+        for (k, branch) in self.netlist.branches.iter().enumerate() {
+            if let Element::Capacitor { c } = branch.element {
+                let voltage = |x: &[f64], n: Option<usize>| n.map_or(0.0, |n| x[n]);
+                let v0 = voltage(&self.solution, branch.a) - voltage(&self.solution, branch.b);
+                let v1 = voltage(&x, branch.a) - voltage(&x, branch.b);
+                self.cap_current[k] = 2.0 * c / self.h * (v1 - v0) - self.cap_current[k];
+            }
+        }
+        self.solution = x;
+        self.time = time;
+        Self::port_current(&self.solution)
+    }
+
+    /// Small-signal conductance seen by the port over one step from the
+    /// current state.
+    pub fn conductance(&self) -> f64 {
+        let dv = 1e-3;
+        (self.predict(dv) - self.predict(0.0)) / dv
+    }
+}
+
+/// Solve `m·x = rhs` by Gaussian elimination with partial pivoting.
+fn solve_dense(mut m: Vec<Vec<f64>>, mut rhs: Vec<f64>) -> Vec<f64> {
+    let n = rhs.len();
+    for col in 0..n {
+        let pivot = (col..n)
+            .max_by(|&a, &b| m[a][col].abs().total_cmp(&m[b][col].abs()))
+            .unwrap();
+        m.swap(col, pivot);
+        rhs.swap(col, pivot);
+        let d = m[col][col];
+        if d == 0.0 {
+            // Floating node: leave it at zero
+            continue;
+        }
+        let (upper, lower) = m.split_at_mut(col + 1);
+        let pivot_row = &upper[col];
+        for (r, row) in lower.iter_mut().enumerate() {
+            let f = row[col] / d;
+            if f != 0.0 {
+                for (x, p) in row[col..].iter_mut().zip(&pivot_row[col..]) {
+                    *x -= f * p;
+                }
+                rhs[col + 1 + r] -= f * rhs[col];
+            }
+        }
+    }
+    let mut x = vec![0.0; n];
+    for r in (0..n).rev() {
+        let s: f64 = (r + 1..n).map(|c| m[r][c] * x[c]).sum();
+        x[r] = if m[r][r] == 0.0 {
+            0.0
+        } else {
+            (rhs[r] - s) / m[r][r]
+        };
+    }
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn circuit(netlist: &str, port: &str, h: f64) -> Circuit {
+        Circuit::new(Netlist::parse(netlist).unwrap(), port, h).unwrap()
+    }
+
+    #[test]
+    fn values_take_spice_suffixes() {
+        let cases = [
+            ("10pF", 10e-12),
+            ("1.5k", 1.5e3),
+            ("2meg", 2e6),
+            ("3mA", 3e-3),
+            ("1e-3", 1e-3),
+            ("4.7u", 4.7e-6),
+        ];
+        for (token, value) in cases {
+            let parsed = parse_value(token).unwrap();
+            assert!((parsed / value - 1.0).abs() < 1e-12, "{token}: {parsed}");
+        }
+        assert!(parse_value("k10").is_err());
+    }
+
+    #[test]
+    fn a_resistive_divider_solves_by_nodal_analysis() {
+        // 9 V through 2 kΩ into the port node, 1 kΩ from it to ground
+        let netlist = "* divider\nV1 in 0 DC 9\nR1 in p 2k ; upper leg\nR2 p gnd 1k\n";
+        let mut divider = circuit(netlist, "p", 1e-9);
+        assert_eq!(divider.node_names(), ["in", "p"]);
+        // At its open-circuit voltage the port carries no current
+        assert!(divider.predict(3.0).abs() < 1e-12);
+        // Shorted, the source drives 4.5 mA out of the circuit
+        assert!((divider.step(0.0) + 4.5e-3).abs() < 1e-12);
+        assert_eq!(divider.voltages(), [9.0, 0.0]);
+        // The Thevenin resistance is 2 kΩ ∥ 1 kΩ
+        assert!((divider.conductance() - 1.5e-3).abs() < 1e-9);
+    }
+
+    #[test]
+    fn an_rc_charges_with_its_time_constant() {
+        let (r, c, h) = (1e3, 1e-6, 1e-6);
+        let mut rc = circuit("R1 p cap 1k\nC1 cap 0 1u\n", "p", h);
+        for _ in 0..1000 {
+            rc.step(1.0);
+        }
+        // The trapezoidal rule averages the step over the first step, so it
+        // starts half a step late
+        let expected = 1.0 - (-999.5 * h / (r * c)).exp();
+        let v = rc.voltages()[1];
+        assert!((v - expected).abs() < 1e-6, "{v} V, {expected} expected");
+    }
+
+    #[test]
+    fn unknown_elements_and_ports_are_rejected() {
+        assert!(Netlist::parse("Q1 c b e")
+            .unwrap_err()
+            .starts_with("line 1"));
+        assert!(Netlist::parse("R1 a").is_err());
+        let netlist = Netlist::parse("R1 a 0 50").unwrap();
+        assert!(Circuit::new(netlist, "b", 1e-9).is_err());
+    }
+}
@@ -0,0 +1,787 @@
+//! Comparison of run outputs against reference data.
+//!
+//! Cross-validation against another solver (Meep, gprMax, openEMS) or an
+//! analytic solution comes down to two arrays of numbers: [`load`] reads
+//! one from a file and [`Comparison::of`] measures how far apart two are.
+//! `fdtd_3d compare RUN REFERENCE` does both and fails above a tolerance.
+//!
+//! [`load`] takes `path` or `path:name` and reads by extension:
+//!
+//! - `.csv` / `.txt` / `.dat`: column `name` (a header name or a 1-based
+//!   index, the last column by default).  Lines starting with `#` are
+//!   skipped, a first row that is not all numbers is the header, and a
+//!   leading field ending in `:` is dropped, which reads Meep's
+//!   `flux1:, f, P` lines as they are printed.
+//! - `.h5` / `.hdf5` / `.mat`: dataset `name` (`/`-separated, the only
+//!   dataset of the root group by default) of an HDF5 file, which covers
+//!   Meep's `output-*` files, h5py dumps and MATLAB v7.3 files including
+//!   the ones [`crate::mat`] writes.  The reader knows the classic layout
+//!   every HDF5 library writes by default (superblock 0 or 1, symbol-table
+//!   groups, version 1 object headers) and contiguous or compact integer
+//!   and floating-point datasets; chunked datasets are refused (`h5repack
+//!   -l CONTI` rewrites them contiguous).  Complex `{real, imag}` datasets
+//!   are read as magnitudes.
+//! - `.bin`: a raw snapshot of [`crate::snapshots`].
+//!
+//! Arrays are kept in file order, last index fastest.  Snapshots and MATLAB
+//! files put x fastest (shape `[nz, ny, nx]`) while Meep writes x slowest
+//! (`[nx, ny, nz]`); [`Data::transposed`] turns one into the other.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// An array read by [`load`], last index fastest.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Data {
+    pub dims: Vec<usize>,
+    pub values: Vec<f64>,
+}
+
+impl Data {
+    /// A vector of `values`.
+    pub fn vector(values: Vec<f64>) -> Self {
+        Data {
+            dims: vec![values.len()],
+            values,
+        }
+    }
+
+    /// The same array with the axis order reversed, first index fastest.
+    pub fn transposed(&self) -> Self {
+        let dims: Vec<usize> = self.dims.iter().rev().copied().collect();
+        let mut values = vec![0.0; self.values.len()];
+        let mut index = vec![0; dims.len()];
+        for &v in &self.values {
+            // `index` walks self in file order; its reverse addresses the
+            // transposed array with the same last-fastest rule.
+            let at = index
+                .iter()
+                .zip(&self.dims)
+                .rev()
+                .fold(0, |acc, (&i, &n)| acc * n + i);
+            values[at] = v;
+            for axis in (0..index.len()).rev() {
+                index[axis] += 1;
+                if index[axis] < self.dims[axis] {
+                    break;
+                }
+                index[axis] = 0;
+            }
+        }
+        Data { dims, values }
+    }
+
+    /// The sizes other than 1, so a column `[n, 1]` matches a vector `[n]`.
+    pub fn shape(&self) -> Vec<usize> {
+        self.dims.iter().copied().filter(|&n| n != 1).collect()
+    }
+}
+
+/// Distance between a run and its reference.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Comparison {
+    /// Values compared.
+    pub points: usize,
+    /// ‖run − reference‖₂ / ‖reference‖₂.
+    pub l2: f64,
+    /// Largest |run − reference|.
+    pub max_error: f64,
+    /// Index of `max_error`.
+    pub max_index: usize,
+    /// `max_error` over the largest |reference|.
+    pub max_relative: f64,
+}
+
+impl Comparison {
+    /// Compare `values` with `reference`, value by value.
+    ///
+    /// # Panics
+    /// If the lengths differ.
+    pub fn of(values: &[f64], reference: &[f64]) -> Self {
+        assert_eq!(
+            values.len(),
+            reference.len(),
+            "compared arrays differ in length"
+        );
+        let (mut error2, mut reference2, mut peak) = (0.0, 0.0, 0.0f64);
+        let (mut max_error, mut max_index) = (0.0, 0);
+        for (i, (&v, &r)) in values.iter().zip(reference).enumerate() {
+            let e = (v - r).abs();
+            error2 += e * e;
+            reference2 += r * r;
+            peak = peak.max(r.abs());
+            if e > max_error {
+                (max_error, max_index) = (e, i);
+            }
+        }
+        Comparison {
+            points: values.len(),
+            l2: ratio(error2.sqrt(), reference2.sqrt()),
+            max_error,
+            max_index,
+            max_relative: ratio(max_error, peak),
+        }
+    }
+}
+
+/// `a / b`, infinite when only `b` is zero and zero when both are.
+fn ratio(a: f64, b: f64) -> f64 {
+    if b > 0.0 {
+        a / b
+    } else if a > 0.0 {
+        f64::INFINITY
+    } else {
+        0.0
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} points: relative L2 error {:.3e}, max error {:.3e} at index {} \
+             ({:.3e} of the reference peak)",
+            self.points, self.l2, self.max_error, self.max_index, self.max_relative
+        )
+    }
+}
+
+/// `values` linearly resampled to `n` points over the same span, e.g. a
+/// reference time series at another step.
+pub fn resample(values: &[f64], n: usize) -> Vec<f64> {
+    match (values.len(), n) {
+        (_, 0) | (0, _) => Vec::new(),
+        (1, _) => vec![values[0]; n],
+        (_, 1) => vec![values[0]],
+        (len, _) => (0..n)
+            .map(|i| {
+                let x = i as f64 * (len - 1) as f64 / (n - 1) as f64;
+                let lo = (x.floor() as usize).min(len - 2);
+                let t = x - lo as f64;
+                values[lo] * (1.0 - t) + values[lo + 1] * t
+            })
+            .collect(),
+    }
+}
+
+/// Read the array named by `spec`, `path` or `path:name` (see the module
+/// documentation for the formats).
+pub fn load(spec: &str) -> io::Result<Data> {
+    let (path, name) = match spec.rsplit_once(':') {
+        Some((path, name)) if !Path::new(spec).exists() => (path, Some(name)),
+        _ => (spec, None),
+    };
+    let path = Path::new(path);
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let in_file = |e: io::Error| io::Error::new(e.kind(), format!("{}: {e}", path.display()));
+    match extension.as_str() {
+        "csv" | "txt" | "dat" => fs::read_to_string(path).and_then(|t| read_csv(&t, name)),
+        "h5" | "hdf5" | "mat" => fs::read(path).and_then(|b| read_hdf5(&b, name)),
+        "bin" => fs::read(path).and_then(|b| read_snapshot(&b)),
+        _ => Err(invalid(
+            "unknown format (.csv, .h5, .mat or .bin)".to_string(),
+        )),
+    }
+    .map_err(in_file)
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Column `name` of a CSV text.
+fn read_csv(text: &str, name: Option<&str>) -> io::Result<Data> {
+    let mut header: Option<Vec<String>> = None;
+    let mut rows: Vec<Vec<f64>> = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        if fields.len() > 1 && fields[0].ends_with(':') {
+            fields.remove(0);
+        }
+        let numbers: Result<Vec<f64>, _> = fields.iter().map(|f| f.parse()).collect();
+        match numbers {
+            Ok(row) => rows.push(row),
+            Err(_) if header.is_none() && rows.is_empty() => {
+                header = Some(fields.iter().map(|f| f.to_string()).collect());
+            }
+            Err(_) => return Err(invalid(format!("line {}: not all numbers", n + 1))),
+        }
+    }
+    let width = rows.first().map_or(0, Vec::len);
+    let column = match name {
+        None => width.checked_sub(1),
+        Some(name) => header
+            .as_ref()
+            .and_then(|h| h.iter().position(|c| c == name))
+            .or_else(|| name.parse::<usize>().ok().and_then(|i| i.checked_sub(1))),
+    };
+    let column = column.filter(|&c| c < width).ok_or_else(|| {
+        invalid(match name {
+            Some(name) => format!("no column {name:?}"),
+            None => "no rows of numbers".to_string(),
+        })
+    })?;
+    let values = rows
+        .iter()
+        .map(|row| row.get(column).copied())
+        .collect::<Option<Vec<f64>>>()
+        .ok_or_else(|| invalid("rows of different widths".to_string()))?;
+    Ok(Data::vector(values))
+}
+
+/// A raw `FDTDSNP1` snapshot, shape `[nz, ny, nx]`.
+fn read_snapshot(bytes: &[u8]) -> io::Result<Data> {
+    if !bytes.starts_with(b"FDTDSNP1") || bytes.len() < 40 {
+        return Err(invalid("not a raw snapshot".to_string()));
+    }
+    let word = |i: usize| u32::from_le_bytes(bytes[8 + 4 * i..12 + 4 * i].try_into().unwrap());
+    let [nx, ny, nz] = [0, 1, 2].map(|i| word(i) as usize);
+    let data = &bytes[40..];
+    if data.len() != 4 * nx * ny * nz {
+        return Err(invalid(format!("truncated {nx}×{ny}×{nz} snapshot")));
+    }
+    let values = data
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes(b.try_into().unwrap()) as f64)
+        .collect();
+    Ok(Data {
+        dims: vec![nz, ny, nx],
+        values,
+    })
+}
+
+// ── HDF5 ─────────────────────────────────────────────────────────────
+
+const SIGNATURE: &[u8; 8] = b"\x89HDF\r\n\x1a\n";
+const UNDEFINED: u64 = u64::MAX;
+
+const MSG_DATASPACE: u16 = 0x0001;
+const MSG_DATATYPE: u16 = 0x0003;
+const MSG_LAYOUT: u16 = 0x0008;
+const MSG_CONTINUATION: u16 = 0x0010;
+const MSG_SYMBOL_TABLE: u16 = 0x0011;
+
+/// Little-endian reads from a byte slice, failing past its end.
+struct Cursor<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, n: usize) -> io::Result<&'a [u8]> {
+        let b = self
+            .bytes
+            .get(self.pos..self.pos + n)
+            .ok_or_else(|| invalid("truncated HDF5 file".to_string()))?;
+        self.pos += n;
+        Ok(b)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn skip(&mut self, n: usize) -> io::Result<()> {
+        self.take(n).map(|_| ())
+    }
+
+    fn expect(&mut self, signature: &[u8]) -> io::Result<()> {
+        if self.take(signature.len())? != signature {
+            return Err(invalid(format!(
+                "corrupt HDF5 file: no {} where expected",
+                String::from_utf8_lossy(signature)
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// An HDF5 file opened at its superblock.
+struct Hdf5<'a> {
+    bytes: &'a [u8],
+    /// File offset of address 0, the end of the user block.
+    base: usize,
+}
+
+/// Element type of a dataset.
+#[derive(Copy, Clone, Debug)]
+enum Element {
+    Float {
+        size: usize,
+        big_endian: bool,
+    },
+    Integer {
+        size: usize,
+        big_endian: bool,
+        signed: bool,
+    },
+    /// `{real, imag}` floats of `size` bytes each, read as magnitudes.
+    Complex {
+        size: usize,
+        big_endian: bool,
+        imag: usize,
+    },
+}
+
+/// The messages of a dataset this reader needs.
+#[derive(Default)]
+struct Dataset {
+    dims: Option<Vec<usize>>,
+    element: Option<Element>,
+    data: Option<Vec<u8>>,
+}
+
+impl<'a> Hdf5<'a> {
+    fn open(bytes: &'a [u8]) -> io::Result<(Self, u64)> {
+        let base =
+            std::iter::successors(Some(0usize), |&at| Some(if at == 0 { 512 } else { 2 * at }))
+                .take_while(|&at| at + 8 <= bytes.len())
+                .find(|&at| &bytes[at..at + 8] == SIGNATURE)
+                .ok_or_else(|| invalid("not an HDF5 file".to_string()))?;
+        let mut c = Cursor {
+            bytes,
+            pos: base + 8,
+        };
+        let version = c.u8()?;
+        if version > 1 {
+            return Err(invalid(format!(
+                "HDF5 superblock version {version}: only the classic layout (libver \
+                 'earliest') is read"
+            )));
+        }
+        c.skip(4)?;
+        let (offsets, lengths) = (c.u8()?, c.u8()?);
+        if offsets != 8 || lengths != 8 {
+            return Err(invalid(
+                "HDF5 offsets or lengths other than 8 bytes".to_string(),
+            ));
+        }
+        c.skip(1 + 2 + 2 + 4 + if version == 1 { 4 } else { 0 })?;
+        let file = Hdf5 {
+            bytes,
+            base: c.u64()? as usize,
+        };
+        c.skip(24)?;
+        // root symbol table entry: the header address follows the name
+        c.skip(8)?;
+        let root = c.u64()?;
+        Ok((file, root))
+    }
+
+    fn at(&self, address: u64) -> io::Result<Cursor<'a>> {
+        let pos = usize::try_from(address)
+            .ok()
+            .and_then(|a| a.checked_add(self.base))
+            .filter(|&p| p <= self.bytes.len())
+            .ok_or_else(|| invalid("corrupt HDF5 file: address out of range".to_string()))?;
+        Ok(Cursor {
+            bytes: self.bytes,
+            pos,
+        })
+    }
+
+    /// The messages of the version 1 object header at `address`, following
+    /// continuation blocks.
+    fn messages(&self, address: u64) -> io::Result<Vec<(u16, &'a [u8])>> {
+        let mut c = self.at(address)?;
+        if c.u8()? != 1 {
+            return Err(invalid(
+                "HDF5 object header other than version 1".to_string(),
+            ));
+        }
+        c.skip(1)?;
+        let count = c.u16()? as usize;
+        c.skip(4)?;
+        let size = c.u32()? as usize;
+        c.skip(4)?;
+        let mut blocks = vec![(c.pos, size)];
+        let mut messages = Vec::new();
+        while let Some((start, size)) = blocks.pop() {
+            let mut c = Cursor {
+                bytes: self.bytes,
+                pos: start,
+            };
+            while c.pos + 8 <= start + size && messages.len() < count {
+                let kind = c.u16()?;
+                let length = c.u16()? as usize;
+                let flags = c.u8()?;
+                c.skip(3)?;
+                let body = c.take(length)?;
+                if flags & 0x02 != 0 {
+                    return Err(invalid("shared HDF5 messages are not read".to_string()));
+                }
+                if kind == MSG_CONTINUATION {
+                    let mut b = Cursor {
+                        bytes: body,
+                        pos: 0,
+                    };
+                    let at = self.at(b.u64()?)?.pos;
+                    blocks.push((at, b.u64()? as usize));
+                }
+                messages.push((kind, body));
+            }
+        }
+        Ok(messages)
+    }
+
+    /// Names and header addresses of the group at `address`.
+    fn group(&self, address: u64) -> io::Result<Vec<(String, u64)>> {
+        let table = self
+            .messages(address)?
+            .into_iter()
+            .find(|&(kind, _)| kind == MSG_SYMBOL_TABLE)
+            .ok_or_else(|| invalid("not a group".to_string()))?
+            .1;
+        let mut t = Cursor {
+            bytes: table,
+            pos: 0,
+        };
+        let (tree, heap) = (t.u64()?, t.u64()?);
+        let mut h = self.at(heap)?;
+        h.expect(b"HEAP")?;
+        h.skip(4 + 16)?;
+        let names = self.at(h.u64()?)?.pos;
+        let mut entries = Vec::new();
+        self.tree(tree, names, &mut entries)?;
+        Ok(entries)
+    }
+
+    /// Walk the group B-tree at `address` down to its symbol-table nodes.
+    fn tree(&self, address: u64, names: usize, out: &mut Vec<(String, u64)>) -> io::Result<()> {
+        let mut c = self.at(address)?;
+        c.expect(b"TREE")?;
+        if c.u8()? != 0 {
+            return Err(invalid("corrupt HDF5 file: not a group B-tree".to_string()));
+        }
+        let level = c.u8()?;
+        let used = c.u16()? as usize;
+        c.skip(16)?;
+        for _ in 0..used {
+            c.skip(8)?; // key
+            let child = c.u64()?;
+            if level > 0 {
+                self.tree(child, names, out)?;
+                continue;
+            }
+            let mut s = self.at(child)?;
+            s.expect(b"SNOD")?;
+            s.skip(2)?;
+            for _ in 0..s.u16()? {
+                let name = s.u64()? as usize;
+                let header = s.u64()?;
+                s.skip(24)?;
+                let text = &self.bytes[(names + name).min(self.bytes.len())..];
+                let end = text.iter().position(|&b| b == 0).unwrap_or(text.len());
+                out.push((String::from_utf8_lossy(&text[..end]).into_owned(), header));
+            }
+        }
+        Ok(())
+    }
+
+    fn dataset(&self, address: u64) -> io::Result<Dataset> {
+        let mut dataset = Dataset::default();
+        for (kind, body) in self.messages(address)? {
+            let mut c = Cursor {
+                bytes: body,
+                pos: 0,
+            };
+            match kind {
+                MSG_DATASPACE => {
+                    let version = c.u8()?;
+                    let rank = c.u8()? as usize;
+                    c.skip(if version == 1 { 6 } else { 2 })?;
+                    dataset.dims = Some(
+                        (0..rank)
+                            .map(|_| c.u64().map(|n| n as usize))
+                            .collect::<io::Result<_>>()?,
+                    );
+                }
+                MSG_DATATYPE => dataset.element = Some(element(&mut c)?),
+                MSG_LAYOUT => {
+                    if c.u8()? != 3 {
+                        return Err(invalid("HDF5 layout message other than version 3".into()));
+                    }
+                    dataset.data = Some(match c.u8()? {
+                        0 => {
+                            let size = c.u16()? as usize;
+                            c.take(size)?.to_vec()
+                        }
+                        1 => {
+                            let (address, size) = (c.u64()?, c.u64()? as usize);
+                            if address == UNDEFINED {
+                                vec![0; size]
+                            } else {
+                                self.at(address)?.take(size)?.to_vec()
+                            }
+                        }
+                        _ => {
+                            return Err(invalid(
+                                "chunked HDF5 dataset: rewrite it contiguous with h5repack -l \
+                                 CONTI"
+                                    .to_string(),
+                            ))
+                        }
+                    });
+                }
+                _ => {}
+            }
+        }
+        Ok(dataset)
+    }
+}
+
+/// Parse a datatype message.
+fn element(c: &mut Cursor) -> io::Result<Element> {
+    let class = c.u8()?;
+    let bits = c.u8()?;
+    c.skip(2)?;
+    let size = c.u32()? as usize;
+    let big_endian = bits & 1 != 0;
+    match (class & 0x0F, class >> 4) {
+        (0, _) if [1, 2, 4, 8].contains(&size) => {
+            c.skip(4)?; // bit offset and precision
+            Ok(Element::Integer {
+                size,
+                big_endian,
+                signed: bits & 0x08 != 0,
+            })
+        }
+        (1, _) if size == 4 || size == 8 => {
+            c.skip(12)?; // bit layout, taken to be IEEE
+            Ok(Element::Float { size, big_endian })
+        }
+        (6, version) => {
+            let mut parts = Vec::new();
+            for _ in 0..2 {
+                let name_start = c.pos;
+                while c.u8()? != 0 {}
+                let name = String::from_utf8_lossy(&c.bytes[name_start..c.pos - 1]).into_owned();
+                let offset = if version == 3 {
+                    let n = c.take(if size < 256 {
+                        1
+                    } else if size < 65536 {
+                        2
+                    } else {
+                        4
+                    })?;
+                    n.iter().rev().fold(0, |acc, &b| acc << 8 | b as usize)
+                } else {
+                    // the name padded to eight bytes from its start
+                    c.pos = name_start + (c.pos - name_start).div_ceil(8) * 8;
+                    let offset = c.u32()? as usize;
+                    c.skip(28)?;
+                    offset
+                };
+                parts.push((name, offset, element(c)?));
+            }
+            match parts.as_slice() {
+                [(
+                    re,
+                    0,
+                    Element::Float {
+                        size: s,
+                        big_endian,
+                    },
+                ), (im, imag, Element::Float { .. })]
+                    if re == "real" && im == "imag" =>
+                {
+                    Ok(Element::Complex {
+                        size: *s,
+                        big_endian: *big_endian,
+                        imag: *imag,
+                    })
+                }
+                _ => Err(invalid(
+                    "compound HDF5 datatype other than {real, imag}".to_string(),
+                )),
+            }
+        }
+        _ => Err(invalid(format!(
+            "HDF5 datatype class {} of {size} bytes is not numeric",
+            class & 0x0F
+        ))),
+    }
+}
+
+impl Element {
+    fn size(self) -> usize {
+        match self {
+            Element::Float { size, .. } | Element::Integer { size, .. } => size,
+            Element::Complex { size, imag, .. } => imag + size,
+        }
+    }
+
+    fn value(self, b: &[u8]) -> f64 {
+        let word = |b: &[u8], big_endian: bool| {
+            let mut w = [0u8; 8];
+            if big_endian {
+                w[8 - b.len()..].copy_from_slice(b);
+                u64::from_be_bytes(w)
+            } else {
+                w[..b.len()].copy_from_slice(b);
+                u64::from_le_bytes(w)
+            }
+        };
+        let float = |b: &[u8], big_endian: bool| match b.len() {
+            4 => f32::from_bits(word(b, big_endian) as u32) as f64,
+            _ => f64::from_bits(word(b, big_endian)),
+        };
+        match self {
+            Element::Float { big_endian, .. } => float(b, big_endian),
+            Element::Integer {
+                size,
+                big_endian,
+                signed,
+            } => {
+                let w = word(b, big_endian);
+                if signed {
+                    let shift = 64 - 8 * size as u32;
+                    ((w << shift) as i64 >> shift) as f64
+                } else {
+                    w as f64
+                }
+            }
+            Element::Complex {
+                size,
+                big_endian,
+                imag,
+            } => float(&b[..size], big_endian).hypot(float(&b[imag..imag + size], big_endian)),
+        }
+    }
+}
+
+/// Dataset `name` of an HDF5 file.
+fn read_hdf5(bytes: &[u8], name: Option<&str>) -> io::Result<Data> {
+    let (file, root) = Hdf5::open(bytes)?;
+    let address = match name {
+        Some(name) => {
+            name.split('/')
+                .filter(|part| !part.is_empty())
+                .try_fold(root, |group, part| {
+                    file.group(group)?
+                        .into_iter()
+                        .find(|(n, _)| n == part)
+                        .map(|(_, address)| address)
+                        .ok_or_else(|| invalid(format!("no dataset {name:?}")))
+                })?
+        }
+        None => match file.group(root)?.as_slice() {
+            [(_, address)] => *address,
+            entries => {
+                let names: Vec<&str> = entries.iter().map(|(n, _)| n.as_str()).collect();
+                return Err(invalid(format!(
+                    "name one of the datasets as FILE:NAME: {}",
+                    names.join(", ")
+                )));
+            }
+        },
+    };
+    let dataset = file.dataset(address)?;
+    let (Some(dims), Some(element), Some(data)) = (dataset.dims, dataset.element, dataset.data)
+    else {
+        return Err(invalid("not a dataset".to_string()));
+    };
+    let count: usize = dims.iter().product();
+    if data.len() < count * element.size() {
+        return Err(invalid("dataset shorter than its shape".to_string()));
+    }
+    let values = data
+        .chunks_exact(element.size())
+        .take(count)
+        .map(|b| element.value(b))
+        .collect();
+    Ok(Data { dims, values })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::{Axis, Field, Grid};
+    use crate::mat::MatFile;
+    use crate::snapshots::raw_header;
+
+    #[test]
+    fn comparisons_measure_l2_and_peak_errors() {
+        let c = Comparison::of(&[1.0, 2.0, 3.0], &[1.0, 2.0, 4.0]);
+        assert_eq!((c.points, c.max_error, c.max_index), (3, 1.0, 2));
+        assert!((c.l2 - 1.0 / 21f64.sqrt()).abs() < 1e-15);
+        assert_eq!(c.max_relative, 0.25);
+        let zero = Comparison::of(&[0.0, 1.0], &[0.0, 0.0]);
+        assert_eq!((zero.l2, zero.max_relative), (f64::INFINITY, f64::INFINITY));
+        assert_eq!(Comparison::of(&[0.0], &[0.0]).l2, 0.0);
+    }
+
+    #[test]
+    fn resampling_interpolates_over_the_same_span() {
+        assert_eq!(resample(&[0.0, 1.0, 2.0], 5), [0.0, 0.5, 1.0, 1.5, 2.0]);
+        assert_eq!(resample(&[0.0, 4.0, 8.0], 2), [0.0, 8.0]);
+        assert_eq!(resample(&[3.0], 3), [3.0; 3]);
+    }
+
+    #[test]
+    fn csv_columns_by_header_index_or_last() {
+        let text = "# Meep flux\nflux1:, freq, P\nflux1:, 1.0, 2.5\nflux1:, 2.0, 3.5\n";
+        assert_eq!(read_csv(text, None).unwrap().values, [2.5, 3.5]);
+        assert_eq!(read_csv(text, Some("freq")).unwrap().values, [1.0, 2.0]);
+        assert_eq!(read_csv(text, Some("2")).unwrap().values, [2.5, 3.5]);
+        assert!(read_csv(text, Some("Q")).is_err());
+        assert!(read_csv("1, 2\n3\n", None).is_err());
+        assert!(read_csv("1, 2\nx, y\n", None).is_err());
+    }
+
+    #[test]
+    fn snapshots_and_mat_files_load_in_file_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let grid = Grid::uniform([3, 2, 1], 1e-3, 1e-12);
+        let values: Vec<f32> = (0..6).map(|n| n as f32).collect();
+        let snapshot = dir.path().join("Ez_000010.bin");
+        let mut bytes = raw_header(&grid, Field::E(Axis::Z), 10);
+        bytes.extend(bytemuck::cast_slice(&values));
+        fs::write(&snapshot, bytes).unwrap();
+        let data = load(snapshot.to_str().unwrap()).unwrap();
+        assert_eq!(data.dims, [1, 2, 3]);
+        assert_eq!(data.shape(), [2, 3]);
+        assert_eq!(data.values, [0.0, 1.0, 2.0, 3.0, 4.0, 5.0]);
+        let transposed = data.transposed();
+        assert_eq!(transposed.dims, [3, 2, 1]);
+        assert_eq!(transposed.values, [0.0, 3.0, 1.0, 4.0, 2.0, 5.0]);
+
+        let mat = dir.path().join("run.mat");
+        let mut file = MatFile::new();
+        file.add_f64("ez", &[3, 2], &[0.0, 1.0, 2.0, 3.0, 4.0, 5.0]);
+        file.add_complex32("s11", &[1, 1], &[[3.0, 4.0]]);
+        file.write(&mat).unwrap();
+        let ez = load(&format!("{}:ez", mat.display())).unwrap();
+        assert_eq!(ez.dims, [2, 3]);
+        assert_eq!(ez.values, data.values);
+        let s11 = load(&format!("{}:s11", mat.display())).unwrap();
+        assert_eq!(s11.values, [5.0]);
+        assert!(load(&format!("{}:hz", mat.display())).is_err());
+        assert!(
+            load(mat.to_str().unwrap()).is_err(),
+            "two datasets, none named"
+        );
+    }
+}
@@ -0,0 +1,1089 @@
+//! Resolved configuration of a run.
+//!
+//! Everything the solver takes from its user in one value: the grid and the
+//! number of steps, the scene (objects, phantoms, sub-cell models, lumped
+//! elements and circuits), the source, the analyses and the outputs.  The
+//! binary fills it from its constants; programs embedding the solver build
+//! it directly, starting from [`Config::new`] or through
+//! [`SimulationBuilder`](crate::builder::SimulationBuilder).  The lists are
+//! owned so that a scene can be assembled at run time.
+//!
+//! [`Config::validate`] rejects the combinations the solver cannot run
+//! before any GPU work, with the messages the setup would otherwise panic
+//! with.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Serialize, Serializer};
+
+use crate::adapters::{AdapterChoice, Backend, PowerPreference};
+use crate::bands::BandDiagram;
+use crate::conformal::ConformalPec;
+use crate::cosim::CircuitPort;
+use crate::dft::DftMonitor;
+use crate::distributed::Cluster;
+use crate::divergence::DivergenceCheck;
+use crate::flux::{FluxBox, FluxMonitor};
+use crate::geometry::Object;
+use crate::gpu::MAX_CELLS;
+use crate::grid::{Axis, Field, Grid};
+use crate::harminv::HarmonicInversion;
+use crate::kspace::KSpaceMonitor;
+use crate::lumped::LumpedElement;
+use crate::materials::{CoefficientStorage, MaterialMap};
+use crate::meshing::MeshSpec;
+use crate::modes::ModeMonitor;
+use crate::modulation::ModulatedRegion;
+use crate::monitors::Monitor;
+use crate::moving_window::MovingWindow;
+use crate::pattern::PatternCuts;
+use crate::phantom::Phantom;
+use crate::ports::FeedPort;
+use crate::precision::Precision;
+use crate::probes::{Location, Probe, ProbeOutput, Quantity};
+use crate::purcell::PurcellDipole;
+use crate::random_media::RandomRegion;
+use crate::rcs::Rcs;
+use crate::reduced::Mode;
+use crate::reflectance::ReflectionTransmission;
+use crate::rough_surface::RoughSurface;
+use crate::sar::Sar;
+use crate::sheets::{ConductiveSheet, ThinLayer};
+use crate::shielding::Shielding;
+use crate::sibc::SibcObject;
+use crate::slices::SliceImages;
+use crate::smoothing::Smoothing;
+use crate::snapshots::Snapshots;
+use crate::sources::Waveform;
+use crate::spectra::ProbeSpectra;
+use crate::stability::Scheme;
+use crate::subgrid::Subgrid;
+use crate::tdr::Tdr;
+use crate::thermal::Thermal;
+use crate::touchstone::Touchstone;
+use crate::unit_cell::UnitCell;
+use crate::wires::ThinWire;
+
+/// What a run simulates: the configured scene, or the reference run of
+/// REFLECTANCE, UNIT_CELL and SHIELDING with only the sources in vacuum.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Scene {
+    Structure,
+    Reference,
+}
+
+/// Outer boundary of the grid along one axis.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum Boundary {
+    /// Perfectly conducting walls (tangential E = 0).
+    Pec,
+    /// The fields wrap around from the last cell to the first.
+    Periodic,
+}
+
+impl Boundary {
+    /// Whether outgoing waves leave the grid through the wall.
+    pub fn absorbs(self) -> bool {
+        match self {
+            Boundary::Pec | Boundary::Periodic => false,
+        }
+    }
+}
+
+/// How much a run prints to the console.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum Verbosity {
+    /// The setup header and the results only.
+    Quiet,
+    /// Also a progress bar of the time loop (see [`crate::progress`]).
+    Normal,
+    /// Also a line per step with the probe values (the adapter limits,
+    /// buffer sizes and timings are `debug` events of the log).
+    Verbose,
+}
+
+/// How a run prints its results on stdout.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum OutputFormat {
+    /// Lines for people to read.
+    Text,
+    /// One JSON object per step, probe sample and result instead (see
+    /// [`crate::sinks::JsonLinesSink`]), for wrapper scripts and job
+    /// schedulers.
+    Json,
+}
+
+/// A configuration the solver cannot run.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConfigError(pub String);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid configuration: {}", self.0)
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// A complete run; the fields follow the constants of examples/scene.rs,
+/// where each is documented with an example.
+#[derive(Clone, Debug, Serialize)]
+pub struct Config {
+    /// Cells, spacings and the configured Δt.
+    pub grid: Grid,
+    /// Boundaries along x, y and z.
+    pub boundaries: [Boundary; 3],
+    /// Time steps of the run.
+    pub steps: u32,
+
+    /// Cell of the hard Ez point source and its waveform.
+    pub source: [u32; 3],
+    pub waveform: Waveform,
+
+    pub probes: Vec<Probe>,
+    /// Steps between probe and flux readbacks, rounded up to a multiple of
+    /// `steps_per_submit` (see [`Config::readback_batch`]).
+    pub probe_batch: u32,
+    /// Steps encoded into one GPU submission at most, waited for together
+    /// (see [`Simulation::run`](crate::ComputeBackend::run)).
+    pub steps_per_submit: u32,
+    pub probe_output: Option<ProbeOutput>,
+    /// Steps between total-energy reductions.
+    pub energy_every: Option<u32>,
+    /// Check of the f32 fields for NaN and runaway growth, on by default
+    /// (see [`crate::divergence`]).
+    pub divergence: Option<DivergenceCheck>,
+    pub harminv: Option<HarmonicInversion>,
+    pub probe_spectra: Option<ProbeSpectra>,
+
+    /// Per-cell materials under the objects.
+    pub material_map: Option<MaterialMap>,
+    pub objects: Vec<Object>,
+    pub smoothing: Smoothing,
+    pub phantoms: Vec<Phantom>,
+    pub sar: Vec<Sar>,
+    pub thermal: Option<Thermal>,
+    pub random_regions: Vec<RandomRegion>,
+    pub rough_surfaces: Vec<RoughSurface>,
+    pub modulated: Vec<ModulatedRegion>,
+    pub sheets: Vec<ConductiveSheet>,
+    pub thin_layers: Vec<ThinLayer>,
+    pub sibc_objects: Vec<SibcObject>,
+    pub conformal_pec: Vec<ConformalPec>,
+    pub wires: Vec<ThinWire>,
+    pub lumped: Vec<LumpedElement>,
+    pub ports: Vec<FeedPort>,
+    pub touchstone: Option<Touchstone>,
+    pub tdr: Option<Tdr>,
+    pub circuits: Vec<CircuitPort>,
+    pub subgrids: Vec<Subgrid>,
+    pub moving_window: Option<MovingWindow>,
+    pub auto_mesh: Option<MeshSpec>,
+
+    pub scheme: Scheme,
+    pub precision: Precision,
+    /// Also run the f32 update next to a non-f32 precision.
+    pub compare_f32: bool,
+    /// Δt as this fraction of the stability limit instead of `grid.dt`.
+    pub dt_safety: Option<f64>,
+    pub coefficient_storage: CoefficientStorage,
+    pub mode: Mode,
+    /// GPU adapter to run on (see [`crate::adapters`]); the one wgpu
+    /// prefers for `power_preference` on `backend` when None.
+    pub adapter: Option<AdapterChoice>,
+    /// Graphics API to look for adapters on, all of them when None.
+    pub backend: Option<Backend>,
+    pub power_preference: PowerPreference,
+    /// Worker threads of the CPU backend, one per core when None.
+    pub cpu_threads: Option<usize>,
+    /// This process's rank and the hosts of a run split over several
+    /// (see [`crate::distributed`]); a single process when None.
+    pub cluster: Option<Cluster>,
+    /// Time the H and E update kernels with GPU timestamps, where the
+    /// adapter has them (see [`crate::bench`]).
+    pub time_kernels: bool,
+    pub verbosity: Verbosity,
+    pub output_format: OutputFormat,
+    /// Checkpoint to go on from (see [`crate::checkpoint`]): the run of its
+    /// scene restores it and appends to the probe files, the other scene
+    /// starts afresh.
+    pub resume: Option<PathBuf>,
+    /// Wall time the run may take: when the estimate after the first steps
+    /// (of the reference run, if any, and the structure run after it) is
+    /// longer, the run stops there with [`crate::FdtdError::OverBudget`].
+    #[serde(serialize_with = "seconds")]
+    pub time_budget: Option<Duration>,
+    /// Steps between the checkpoints [`crate::run_scene`] writes to
+    /// `<monitor_dir>/checkpoint.bin`, and goes back to when the GPU device
+    /// is lost; a multiple of the
+    /// [`readback_batch`](Config::readback_batch).  Without them a lost
+    /// device restarts the run.
+    pub checkpoint_every: Option<u32>,
+    /// Fields (see [`crate::backend::save_fields`]) the structure run
+    /// starts from instead of zero, of a run on a grid of this size; the
+    /// rest of its state starts afresh.
+    pub initial_fields: Option<PathBuf>,
+    /// Write the fields at the end of the structure run to
+    /// `<monitor_dir>/fields.bin`, for another to start from.
+    pub final_fields: bool,
+
+    pub snapshots: Option<Snapshots>,
+    pub monitors: Vec<Monitor>,
+    /// Directory of the monitor, analysis and summary files.
+    pub monitor_dir: &'static str,
+    /// MATLAB v7.3 file of the probe traces, snapshots and DFT spectra of
+    /// the structure run (see [`crate::sinks::MatSink`]).
+    pub mat_file: Option<&'static str>,
+    pub kspace_monitors: Vec<KSpaceMonitor>,
+    pub dft_monitors: Vec<DftMonitor>,
+    pub flux_monitors: Vec<FluxMonitor>,
+    pub flux_boxes: Vec<FluxBox>,
+    pub rcs: Vec<Rcs>,
+    pub patterns: Vec<PatternCuts>,
+    pub reflectance: Vec<ReflectionTransmission>,
+    pub mode_monitors: Vec<ModeMonitor>,
+    pub unit_cell: Option<UnitCell>,
+    pub shielding: Option<Shielding>,
+    pub purcell: Option<PurcellDipole>,
+    pub bands: Option<BandDiagram>,
+    pub slice_images: Option<SliceImages>,
+}
+
+/// A duration as seconds.
+fn seconds<S: Serializer>(time: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error> {
+    time.map(|t| t.as_secs_f64()).serialize(serializer)
+}
+
+impl Config {
+    /// `steps` steps on `grid` in vacuum, pulsed at the centre by a
+    /// Gaussian (20 steps wide, 40 steps late), with nothing recorded.
+    pub fn new(grid: Grid, steps: u32) -> Config {
+        Config {
+            grid,
+            boundaries: [Boundary::Pec; 3],
+            steps,
+            source: [grid.nx / 2, grid.ny / 2, grid.nz / 2],
+            waveform: Waveform::Gaussian {
+                width: 20.0,
+                delay: 40.0,
+            },
+            probes: Vec::new(),
+            probe_batch: 1,
+            steps_per_submit: 1,
+            probe_output: None,
+            energy_every: None,
+            divergence: Some(DivergenceCheck::default()),
+            harminv: None,
+            probe_spectra: None,
+            material_map: None,
+            objects: Vec::new(),
+            smoothing: Smoothing::None,
+            phantoms: Vec::new(),
+            sar: Vec::new(),
+            thermal: None,
+            random_regions: Vec::new(),
+            rough_surfaces: Vec::new(),
+            modulated: Vec::new(),
+            sheets: Vec::new(),
+            thin_layers: Vec::new(),
+            sibc_objects: Vec::new(),
+            conformal_pec: Vec::new(),
+            wires: Vec::new(),
+            lumped: Vec::new(),
+            ports: Vec::new(),
+            touchstone: None,
+            tdr: None,
+            circuits: Vec::new(),
+            subgrids: Vec::new(),
+            moving_window: None,
+            auto_mesh: None,
+            scheme: Scheme::Yee,
+            precision: Precision::F32,
+            compare_f32: false,
+            dt_safety: None,
+            coefficient_storage: CoefficientStorage::Dense,
+            mode: Mode::ThreeD,
+            adapter: None,
+            backend: None,
+            power_preference: PowerPreference::HighPerformance,
+            cpu_threads: None,
+            cluster: None,
+            time_kernels: false,
+            verbosity: Verbosity::Normal,
+            output_format: OutputFormat::Text,
+            resume: None,
+            time_budget: None,
+            checkpoint_every: None,
+            initial_fields: None,
+            final_fields: false,
+            snapshots: None,
+            monitors: Vec::new(),
+            monitor_dir: "monitors",
+            mat_file: None,
+            kspace_monitors: Vec::new(),
+            dft_monitors: Vec::new(),
+            flux_monitors: Vec::new(),
+            flux_boxes: Vec::new(),
+            rcs: Vec::new(),
+            patterns: Vec::new(),
+            reflectance: Vec::new(),
+            mode_monitors: Vec::new(),
+            unit_cell: None,
+            shielding: None,
+            purcell: None,
+            bands: None,
+            slice_images: None,
+        }
+    }
+
+    /// Whether reflectance, unit-cell or shielding spectra need a reference
+    /// run first.
+    pub fn normalised(&self) -> bool {
+        !self.reflectance.is_empty() || self.unit_cell.is_some() || self.shielding.is_some()
+    }
+
+    /// Whether a plane wave, a Purcell dipole or a TDR step replaces the
+    /// point source.
+    pub fn source_replaced(&self) -> bool {
+        self.unit_cell.is_some()
+            || self.shielding.is_some()
+            || self.purcell.is_some()
+            || self.tdr.is_some()
+    }
+
+    /// Axes with periodic boundaries, the unit cell's included.
+    pub fn periodic_axes(&self) -> Vec<Axis> {
+        let mut axes: Vec<Axis> = [Axis::X, Axis::Y, Axis::Z]
+            .into_iter()
+            .filter(|axis| self.boundaries[axis.lane()] == Boundary::Periodic)
+            .collect();
+        for axis in self.unit_cell.iter().flat_map(UnitCell::periodic) {
+            if !axes.contains(&axis) {
+                axes.push(axis);
+            }
+        }
+        axes
+    }
+
+    /// Whether sub-cell models add sparse edges (ADE currents, SIBC faces
+    /// or H corrections) to the plain update.
+    fn has_subcell_models(&self) -> bool {
+        !self.sheets.is_empty()
+            || !self.lumped.is_empty()
+            || !self.circuits.is_empty()
+            || !self.sibc_objects.is_empty()
+            || !self.conformal_pec.is_empty()
+            || !self.wires.is_empty()
+            || self.tdr.is_some()
+            || self.source_replaced()
+    }
+
+    /// What keeps state a checkpoint does not hold, if anything.
+    pub(crate) fn unresumable(&self) -> Option<&'static str> {
+        let kept = [
+            ("sub-grids", !self.subgrids.is_empty()),
+            ("a moving window", self.moving_window.is_some()),
+            ("circuit ports", !self.circuits.is_empty()),
+            ("line and plane monitors", !self.monitors.is_empty()),
+            ("k-space monitors", !self.kspace_monitors.is_empty()),
+        ];
+        kept.iter()
+            .find(|(_, present)| *present)
+            .map(|(name, _)| *name)
+    }
+
+    /// Whether any output reads the f32 fields.
+    fn reads_f32_fields(&self) -> bool {
+        !self.ports.is_empty()
+            || !self.circuits.is_empty()
+            || !self.monitors.is_empty()
+            || !self.kspace_monitors.is_empty()
+            || !self.dft_monitors.is_empty()
+            || !self.flux_monitors.is_empty()
+            || !self.flux_boxes.is_empty()
+            || !self.rcs.is_empty()
+            || !self.patterns.is_empty()
+            || !self.mode_monitors.is_empty()
+            || !self.sar.is_empty()
+            || self.normalised()
+            || self.purcell.is_some()
+            || self.energy_every.is_some()
+            || self.snapshots.is_some()
+            || self.slice_images.is_some()
+    }
+
+    /// Steps of the probe and flux batches read back together:
+    /// `probe_batch` rounded up to whole submissions, so that a submission
+    /// of `steps_per_submit` steps ends on a readback rather than being cut
+    /// short by one.
+    pub fn readback_batch(&self) -> u32 {
+        let steps = self.steps_per_submit.max(1);
+        self.probe_batch.max(1).div_ceil(steps) * steps
+    }
+
+    /// Check everything that can be checked without the GPU: sizes and
+    /// indices, and the combinations of scheme, precision and models the
+    /// solver supports.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let fail = |message: String| Err(ConfigError(message));
+        let g = &self.grid;
+        let cells = [g.nx, g.ny, g.nz];
+        if cells.iter().any(|&n| n == 0 || n as usize > MAX_CELLS) {
+            return fail(format!(
+                "the grid needs 1 to {MAX_CELLS} cells per axis, not {cells:?}"
+            ));
+        }
+        for ((axis, widths), n) in ["x", "y", "z"].iter().zip(g.graded).zip(cells) {
+            if widths.is_some_and(|w| w.len() != n as usize) {
+                return fail(format!("{n} cells along {axis} need {n} graded widths"));
+            }
+        }
+        if !(g.dx > 0.0 && g.dy > 0.0 && g.dz > 0.0 && g.dt > 0.0) {
+            return fail("the cell sizes and Δt must be positive".into());
+        }
+        if self.steps == 0 {
+            return fail("the run needs at least one step".into());
+        }
+        if self.probe_batch == 0 {
+            return fail("the probe batch must be at least one step".into());
+        }
+        if self.steps_per_submit == 0 {
+            return fail("a submission needs at least one step".into());
+        }
+        if self.cpu_threads == Some(0) {
+            return fail("the CPU backend needs at least one thread".into());
+        }
+        if self.backend == Some(Backend::Cpu) {
+            let cpu = self.mode == Mode::ThreeD
+                && self.bands.is_none()
+                && self.scheme == Scheme::Yee
+                && self.precision == Precision::F32
+                && self.circuits.is_empty()
+                && self.sibc_objects.is_empty()
+                && self.modulated.is_empty()
+                && self.subgrids.is_empty()
+                && self.moving_window.is_none()
+                && !self.reads_f32_fields();
+            if !cpu {
+                return fail(
+                    "the CPU backend is a reference for the 3D f32 Yee update, with probes only; \
+                     circuits, surface impedances, modulation, sub-grids, the moving window, \
+                     ports, monitors, DFT, flux, far-field and SAR analyses, energies, snapshots \
+                     and images need the GPU"
+                        .into(),
+                );
+            }
+        }
+        if let Some(cluster) = &self.cluster {
+            cluster.check(&self.grid)?;
+            let split = self.backend == Some(Backend::Cpu)
+                && !self.periodic_axes().contains(&Axis::Z)
+                && self.wires.is_empty()
+                && self.conformal_pec.is_empty()
+                && self.time_budget.is_none()
+                && !self.final_fields;
+            if !split {
+                return fail(
+                    "a distributed run steps z slabs on the CPU backend; periodic z, thin wires, \
+                     conformal PEC, a time budget and the final fields need a single process"
+                        .into(),
+                );
+            }
+        }
+        if let Some(every) = self.checkpoint_every {
+            if every == 0 || !every.is_multiple_of(self.readback_batch()) {
+                return fail(format!(
+                    "checkpoints need a multiple of the probe batch of {}, not every {every} steps",
+                    self.readback_batch()
+                ));
+            }
+            if let Some(name) = self.unresumable() {
+                return fail(format!("a run with {name} cannot write checkpoints"));
+            }
+        }
+        if self.initial_fields.is_some() || self.final_fields {
+            let f32_fields = self.precision == Precision::F32 || self.compare_f32;
+            if self.mode != Mode::ThreeD || self.bands.is_some() || !f32_fields {
+                return fail("warm starts carry the f32 fields of 3D runs over".into());
+            }
+            if let Some(name) = self.unresumable().filter(|_| self.initial_fields.is_some()) {
+                return fail(format!("a run with {name} cannot start from other fields"));
+            }
+        }
+        if let Some(check) = self.divergence {
+            if !(check.every > 0 && check.max_field > 0.0) {
+                return fail(format!(
+                    "the divergence check needs every ≥ 1 and max_field > 0, not {check:?}"
+                ));
+            }
+        }
+        if let Some(safety) = self.dt_safety {
+            if !(safety > 0.0 && safety <= 1.0) {
+                return fail(format!("dt_safety must lie in (0, 1], not {safety}"));
+            }
+        }
+        let inside = |cell: [u32; 3]| cell.iter().zip(cells).all(|(&i, n)| i < n);
+        if !self.source_replaced() && !inside(self.source) {
+            return fail(format!("source cell {:?} outside the grid", self.source));
+        }
+        // Probes, monitors and ports inside the grid
+        for probe in &self.probes {
+            probe.check(g)?;
+        }
+        for monitor in &self.monitors {
+            monitor.check(g)?;
+        }
+        for monitor in &self.kspace_monitors {
+            monitor.check(g)?;
+        }
+        let derived = self.unit_cell.iter().flat_map(UnitCell::dft_monitors);
+        let derived = derived.chain(self.shielding.iter().flat_map(Shielding::dft_monitors));
+        for monitor in self.dft_monitors.iter().cloned().chain(derived) {
+            monitor.check(g)?;
+        }
+        for monitor in &self.mode_monitors {
+            monitor.check(g)?;
+        }
+        let planes = self
+            .reflectance
+            .iter()
+            .flat_map(ReflectionTransmission::planes);
+        for monitor in self.flux_monitors.iter().copied().chain(planes) {
+            monitor.check(g)?;
+        }
+        for surface in &self.flux_boxes {
+            surface.check(g)?;
+        }
+        let absorbing = self.boundaries.iter().all(|b| b.absorbs());
+        let lit = self.unit_cell.is_some() || self.shielding.is_some();
+        for rcs in &self.rcs {
+            rcs.check(g, absorbing, lit)?;
+        }
+        for pattern in &self.patterns {
+            pattern.check(g)?;
+        }
+        if let Some(dipole) = &self.purcell {
+            dipole.check(g)?;
+        }
+        for port in &self.ports {
+            port.check(g)?;
+        }
+        for port in &self.circuits {
+            port.check(g)?;
+        }
+        for wire in &self.wires {
+            wire.check(g)?;
+        }
+        for subgrid in &self.subgrids {
+            subgrid.check(g)?;
+        }
+        if let Some(unit_cell) = &self.unit_cell {
+            unit_cell.check()?;
+        }
+        if let Some(shielding) = &self.shielding {
+            shielding.check(g)?;
+        }
+        if let Some(images) = &self.slice_images {
+            if images.index >= g.cells(images.normal) {
+                return fail(format!(
+                    "slice images: plane {} along {:?} outside the grid",
+                    images.index, images.normal
+                ));
+            }
+        }
+        // One lumped element or circuit per edge, and a port on each of
+        // them or on the TDR's step source
+        let mut edges: Vec<(Axis, [u32; 3])> = Vec::new();
+        let elements = self.lumped.iter().map(|e| (e.axis, e.cell));
+        for edge in elements.chain(self.circuits.iter().map(|c| (c.axis, c.cell))) {
+            if !inside(edge.1) {
+                return fail(format!("lumped element at {:?} outside the grid", edge.1));
+            }
+            if edges.contains(&edge) {
+                return fail(format!(
+                    "two lumped elements or circuits on the {:?} edge of cell {:?}",
+                    edge.0, edge.1
+                ));
+            }
+            edges.push(edge);
+        }
+        for (n, port) in self.ports.iter().enumerate() {
+            let edge = (port.axis, port.cell);
+            if self.ports[..n].iter().any(|p| (p.axis, p.cell) == edge) {
+                return fail(format!("port {}: on the edge of another port", port.name));
+            }
+            if !edges.contains(&edge) && self.tdr.is_none_or(|tdr| tdr.port != n) {
+                return fail(format!(
+                    "port {}: no lumped element, circuit or TDR on its edge",
+                    port.name
+                ));
+            }
+        }
+        if let Some(map) = &self.material_map {
+            let shape = cells.map(|n| n as usize);
+            if map.shape() != shape {
+                return fail(format!(
+                    "a material map of shape {:?} on a grid of {shape:?} cells",
+                    map.shape()
+                ));
+            }
+            let reduced = self.mode != Mode::ThreeD || self.bands.is_some();
+            if reduced || !self.subgrids.is_empty() || self.auto_mesh.is_some() {
+                return fail("material maps need the 3D run on the configured grid".into());
+            }
+        }
+        if let Some(tdr) = &self.tdr {
+            if tdr.port >= self.ports.len() {
+                return fail(format!("TDR {}: no port {}", tdr.name, tdr.port));
+            }
+        }
+        if let Some(thermal) = &self.thermal {
+            let frequencies = self.sar.get(thermal.sar).map(|s| s.frequencies.len());
+            if frequencies.is_none_or(|n| thermal.frequency >= n) {
+                return fail(format!(
+                    "thermal {}: no SAR box {} with frequency {}",
+                    thermal.name, thermal.sar, thermal.frequency
+                ));
+            }
+        }
+
+        let first_on_cell = matches!(
+            self.probes.first(),
+            Some(Probe {
+                at: Location::Cell(_),
+                ..
+            })
+        );
+        let plain =
+            !self.has_subcell_models() && self.modulated.is_empty() && self.subgrids.is_empty();
+        if self.mode != Mode::ThreeD || self.bands.is_some() {
+            if self.scheme != Scheme::Yee {
+                return fail("reduced modes and band diagrams use the Yee scheme".into());
+            }
+            if self.normalised() {
+                return fail("normalised spectra need the normal 3D run".into());
+            }
+            if self.mode != Mode::ThreeD && !first_on_cell {
+                return fail("reduced modes follow a first probe on a cell".into());
+            }
+            if g.graded.iter().any(Option::is_some) {
+                return fail("reduced modes and band diagrams need uniform spacing".into());
+            }
+            if let (Mode::Bor { .. }, Some(Location::Cell(probe))) =
+                (self.mode, self.probes.first().map(|p| p.at))
+            {
+                if self.source[0].min(probe[0]) < g.nx / 2 {
+                    return fail(format!(
+                        "BOR runs put the axis at x = {}, and the source and first probe lie below it",
+                        g.nx / 2
+                    ));
+                }
+            }
+        }
+        if self.thermal.is_some() && g.graded.iter().any(Option::is_some) {
+            return fail("the thermal stage needs uniform spacing".into());
+        }
+        if self.scheme == Scheme::Yee24 && g.graded.iter().any(Option::is_some) {
+            return fail("the (2,4) stencil needs a uniform mesh".into());
+        }
+        let periodic = self.periodic_axes();
+        let wraps = self.scheme == Scheme::Yee
+            && self.precision == Precision::F32
+            && self.subgrids.is_empty()
+            && self.moving_window.is_none()
+            && periodic.iter().all(|axis| g.graded[axis.lane()].is_none());
+        if !periodic.is_empty() && !wraps {
+            return fail("periodic boundaries need the uniform f32 Yee update".into());
+        }
+        if self.moving_window.is_some() && !plain {
+            return fail("the moving window supports plain objects only".into());
+        }
+        if self.moving_window.is_some() && g.graded[0].is_some() {
+            return fail("the moving window needs uniform x spacing".into());
+        }
+        if matches!(self.scheme, Scheme::Adi { .. } | Scheme::Hie { .. })
+            && !(plain && self.moving_window.is_none())
+        {
+            return fail(format!("{:?} supports plain objects only", self.scheme));
+        }
+        if self.precision != Precision::F32 {
+            if !(self.scheme == Scheme::Yee && plain && self.moving_window.is_none()) {
+                return fail(
+                    "non-f32 precisions support the Yee scheme on plain objects only".into(),
+                );
+            }
+            let ez = Quantity::Component(Field::E(Axis::Z));
+            if !(first_on_cell && self.probes.len() == 1 && self.probes[0].quantity == ez) {
+                return fail("non-f32 precisions support a single Ez probe on a cell".into());
+            }
+            if !self.compare_f32 && self.reads_f32_fields() {
+                return fail(
+                    "ports, monitors and field outputs read the f32 fields (set compare_f32)"
+                        .into(),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Directories the run writes into.
+    pub fn output_dirs(&self) -> Vec<&'static str> {
+        let mut dirs = vec![self.monitor_dir];
+        dirs.extend(self.probe_output.map(|spec| spec.dir));
+        dirs.extend(self.snapshots.map(|spec| spec.dir));
+        dirs.extend(self.slice_images.map(|spec| spec.dir));
+        dirs
+    }
+
+    /// Move the outputs, those of [`Config::output_dirs`] and the MATLAB
+    /// file, under `dir` when they are relative.  The new paths are leaked,
+    /// as a scene file's are.
+    pub fn output_under(&mut self, dir: &Path) {
+        let under = |path: &str| -> &'static str {
+            Box::leak(dir.join(path).display().to_string().into_boxed_str())
+        };
+        self.monitor_dir = under(self.monitor_dir);
+        self.mat_file = self.mat_file.map(under);
+        if let Some(spec) = &mut self.probe_output {
+            spec.dir = under(spec.dir);
+        }
+        if let Some(spec) = &mut self.snapshots {
+            spec.dir = under(spec.dir);
+        }
+        if let Some(spec) = &mut self.slice_images {
+            spec.dir = under(spec.dir);
+        }
+    }
+
+    /// The configuration for results.json, every field as serde writes
+    /// it.
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::to_value(self).expect("a configuration serialises")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dft::Region;
+    use crate::lumped::LumpedKind;
+    use crate::modes::ModeProfile;
+
+    fn config() -> Config {
+        Config::new(Grid::uniform([16; 3], 1e-3, 1e-12), 10)
+    }
+
+    fn problem(config: &Config) -> String {
+        config.validate().expect_err("the config is invalid").0
+    }
+
+    fn flux(index: u32) -> FluxMonitor {
+        FluxMonitor {
+            name: "top",
+            normal: Axis::Z,
+            index,
+            u: (2, 14),
+            v: (2, 14),
+            frequencies: &[],
+        }
+    }
+
+    fn port(cell: [u32; 3]) -> FeedPort {
+        FeedPort {
+            name: "feed",
+            axis: Axis::Z,
+            cell,
+            z0: 50.0,
+            f_max: 1e10,
+        }
+    }
+
+    fn resistor(cell: [u32; 3]) -> LumpedElement {
+        LumpedElement {
+            axis: Axis::Z,
+            cell,
+            kind: LumpedKind::Resistor { r: 50.0 },
+        }
+    }
+
+    #[test]
+    fn vacuum_run_is_valid() {
+        assert!(config().validate().is_ok());
+    }
+
+    #[test]
+    fn empty_runs_and_grids_fail() {
+        let mut c = config();
+        c.steps = 0;
+        assert!(problem(&c).contains("at least one step"));
+        let mut c = config();
+        c.grid.ny = 0;
+        assert!(problem(&c).contains("cells per axis"));
+        let mut c = config();
+        c.source = [16, 8, 8];
+        assert!(problem(&c).contains("source cell"));
+    }
+
+    #[test]
+    fn flux_planes_lie_inside_the_grid() {
+        let mut c = config();
+        c.flux_monitors.push(flux(8));
+        assert!(c.validate().is_ok());
+        c.flux_monitors[0] = flux(40);
+        assert!(problem(&c).starts_with("flux monitor top: plane 40"));
+        c.flux_monitors[0] = flux(0);
+        assert!(problem(&c).contains("outside 1..16"));
+        c.flux_monitors[0] = FluxMonitor {
+            u: (4, 17),
+            ..flux(8)
+        };
+        assert!(problem(&c).contains("(4, 17)"));
+    }
+
+    #[test]
+    fn boxes_keep_off_the_lower_faces() {
+        let mut c = config();
+        c.flux_boxes.push(FluxBox {
+            name: "box",
+            lo: [0, 4, 4],
+            hi: [12, 12, 12],
+            frequencies: &[],
+        });
+        assert!(problem(&c).starts_with("box box"));
+        c.flux_boxes[0].lo[0] = 4;
+        assert!(c.validate().is_ok());
+        c.flux_boxes[0].hi[2] = 16;
+        assert!(c.validate().is_err());
+    }
+
+    #[test]
+    fn monitors_and_probes_lie_inside_the_grid() {
+        let mut c = config();
+        c.dft_monitors.push(DftMonitor {
+            name: "centre",
+            fields: &[Field::E(Axis::Z)],
+            region: Region::Box {
+                lo: [4, 4, 4],
+                hi: [8, 8, 20],
+            },
+            frequencies: &[1e10],
+        });
+        assert!(problem(&c).starts_with("DFT monitor centre"));
+        c.dft_monitors[0].region = Region::Point([8, 8, 8]);
+        assert!(c.validate().is_ok());
+        c.dft_monitors[0].frequencies = &[];
+        assert!(problem(&c).contains("positive frequencies"));
+
+        let mut c = config();
+        c.probes.push(Probe {
+            name: "far",
+            at: Location::Point([0.008, 0.008, 0.02]),
+            quantity: Quantity::AbsE,
+        });
+        assert!(problem(&c).starts_with("probe far"));
+    }
+
+    #[test]
+    fn ports_sit_on_one_driven_edge_each() {
+        let mut c = config();
+        c.ports.push(port([4, 4, 4]));
+        assert!(problem(&c).contains("no lumped element"));
+        c.lumped.push(resistor([4, 4, 4]));
+        assert!(c.validate().is_ok());
+        c.ports.push(port([4, 4, 4]));
+        assert!(problem(&c).contains("edge of another port"));
+        c.ports.pop();
+        c.lumped.push(resistor([4, 4, 4]));
+        assert!(problem(&c).contains("two lumped elements"));
+        let mut c = config();
+        c.ports.push(port([0, 4, 4]));
+        assert!(problem(&c).contains("lower faces"));
+    }
+
+    #[test]
+    fn setup_problems_fail_validation() {
+        let mut c = config();
+        c.mode_monitors.push(ModeMonitor {
+            name: "guide",
+            normal: Axis::Z,
+            index: 8,
+            u: (2, 14),
+            v: (2, 10),
+            frequencies: &[1e10],
+            eps_r: 1.0,
+            modes: &[ModeProfile::Te { m: 0, n: 0 }],
+        });
+        assert!(problem(&c).contains("no TE00 mode"));
+        c.mode_monitors[0].modes = &[ModeProfile::Te { m: 1, n: 0 }];
+        assert!(c.validate().is_ok());
+
+        let mut c = config();
+        c.circuits.push(CircuitPort {
+            name: "diode",
+            axis: Axis::Z,
+            cell: [4, 4, 4],
+            netlist: "R1 in 0 50",
+            node: "out",
+        });
+        assert!(problem(&c).starts_with("circuit port diode"));
+
+        let mut c = config();
+        c.patterns.push(PatternCuts {
+            name: "horn",
+            lo: [4, 4, 4],
+            hi: [12, 12, 12],
+            frequencies: &[1e10],
+            boresight: Axis::X,
+            polarization: Axis::X,
+        });
+        assert!(problem(&c).contains("across the boresight"));
+
+        let mut c = config();
+        (c.mode, c.scheme) = (Mode::OneD, Scheme::Yee24);
+        assert!(problem(&c).contains("Yee scheme"));
+    }
+
+    #[test]
+    fn sub_cell_models_and_refinements_fit_their_cells() {
+        let mut c = config();
+        c.wires.push(ThinWire {
+            axis: Axis::Z,
+            at: (8, 8),
+            span: (2, 14),
+            radius: 2e-3,
+        });
+        assert!(problem(&c).starts_with("thin wire along Z: the radius"));
+        c.wires[0].radius = 1e-4;
+        assert!(c.validate().is_ok());
+        c.wires[0].at = (8, 16);
+        assert!(problem(&c).contains("outside the grid"));
+
+        let mut c = config();
+        c.subgrids.push(Subgrid {
+            lo: [4, 4, 4],
+            hi: [8, 8, 8],
+            ratio: 4,
+        });
+        assert!(problem(&c).contains("ratio must be 2 or 3"));
+        c.subgrids[0] = Subgrid {
+            lo: [0, 4, 4],
+            ratio: 2,
+            ..c.subgrids[0]
+        };
+        assert!(problem(&c).contains("inside the grid"));
+
+        let mut c = config();
+        c.grid.graded[0] = Some(&[1e-3; 16]);
+        c.moving_window = Some(MovingWindow {
+            start: 0,
+            velocity: 3e8,
+        });
+        assert!(problem(&c).contains("uniform x spacing"));
+        c.moving_window = None;
+        c.mode = Mode::TMz;
+        c.probes.push(Probe {
+            name: "p",
+            at: Location::Cell([8, 8, 8]),
+            quantity: Quantity::Component(Field::E(Axis::Z)),
+        });
+        assert!(problem(&c).contains("uniform spacing"));
+        c.grid.graded[0] = None;
+        assert!(c.validate().is_ok());
+        c.mode = Mode::Bor { m: 0 };
+        c.probes[0].at = Location::Cell([4, 8, 8]);
+        assert!(problem(&c).contains("axis at x = 8"));
+    }
+
+    #[test]
+    fn rcs_needs_absorbing_walls_and_a_plane_wave() {
+        let mut c = config();
+        c.rcs.push(Rcs {
+            name: "sphere",
+            lo: [4, 4, 4],
+            hi: [12, 12, 12],
+            frequencies: &[1e10],
+            incidence: (90.0, 0.0),
+            incident: c.waveform,
+            step: 0.0,
+        });
+        assert_eq!(
+            problem(&c),
+            "RCS sphere: needs absorbing boundaries, and the walls are PEC or periodic"
+        );
+        c.boundaries = [Boundary::Periodic; 3];
+        assert!(problem(&c).contains("absorbing boundaries"));
+        c.rcs[0].hi = [12, 12, 16];
+        assert!(problem(&c).starts_with("box sphere"));
+    }
+
+    #[test]
+    fn results_json_holds_every_field() {
+        let mut c = config();
+        c.flux_monitors.push(flux(8));
+        c.time_budget = Some(Duration::from_secs(90));
+        let json = c.to_json();
+        assert_eq!(json["grid"]["nx"], 16);
+        assert_eq!(json["waveform"]["Gaussian"]["width"], 20.0);
+        assert_eq!(json["flux_monitors"][0]["name"], "top");
+        assert_eq!(json["flux_monitors"][0]["normal"], "Z");
+        assert_eq!(json["time_budget"], 90.0);
+        assert!(json["material_map"].is_null());
+        let keys: Vec<&String> = json.as_object().unwrap().keys().take(3).collect();
+        assert_eq!(keys, ["grid", "boundaries", "steps"]);
+    }
+
+    #[test]
+    fn readback_batches_round_up_to_whole_submissions() {
+        let mut c = config();
+        (c.probe_batch, c.steps_per_submit) = (3, 8);
+        assert_eq!(c.readback_batch(), 8);
+        (c.probe_batch, c.steps_per_submit) = (10, 4);
+        assert_eq!(c.readback_batch(), 12);
+        c.checkpoint_every = Some(10);
+        assert!(problem(&c).contains("probe batch of 12"));
+        c.checkpoint_every = Some(24);
+        assert!(c.validate().is_ok());
+    }
+
+    #[test]
+    fn the_cpu_backend_runs_probes_only() {
+        let mut c = config();
+        c.backend = Some(Backend::Cpu);
+        assert!(c.validate().is_ok());
+        c.flux_monitors.push(flux(8));
+        assert!(problem(&c).contains("with probes only"));
+        c.flux_monitors.clear();
+        c.energy_every = Some(5);
+        assert!(problem(&c).contains("with probes only"));
+    }
+
+    #[test]
+    fn distributed_runs_split_z_on_the_cpu() {
+        let mut c = config();
+        c.backend = Some(Backend::Cpu);
+        c.cluster = Some(Cluster {
+            rank: 0,
+            hosts: vec!["localhost:7000".into()],
+        });
+        assert!(problem(&c).contains("at least two hosts"));
+        let hosts: Vec<String> = (0..3).map(|r| format!("localhost:{}", 7000 + r)).collect();
+        c.cluster = Some(Cluster { rank: 3, hosts });
+        assert!(problem(&c).contains("rank 3"));
+        c.cluster.as_mut().unwrap().rank = 2;
+        assert!(c.validate().is_ok());
+        c.cluster
+            .as_mut()
+            .unwrap()
+            .hosts
+            .resize(17, "localhost:7100".into());
+        assert!(problem(&c).contains("16 z planes"));
+        c.cluster.as_mut().unwrap().hosts.truncate(3);
+        c.final_fields = true;
+        assert!(problem(&c).contains("single process"));
+        c.final_fields = false;
+        c.backend = None;
+        assert!(problem(&c).contains("CPU backend"));
+    }
+}
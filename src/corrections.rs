@@ -0,0 +1,138 @@
+//! Sparse additive corrections to the H-update.
+//!
+//! Sub-cell models whose Faraday loop differs from a regular Yee face
+//! register extra `coef · E` terms per H edge here; the collected entries
+//! are applied by `shaders/h_correct.wgsl` right after the H-update.
+
+use std::collections::BTreeMap;
+
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+
+use crate::gpu::{bg_entry, bgl_storage_entry, compute_pipeline, groups_1d};
+use crate::grid::Axis;
+
+/// Maximum number of E terms per corrected H edge (matches WGSL).
+pub const MAX_TERMS: usize = 4;
+
+/// Correction of one H edge (must match WGSL `HCorrection`).
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct HCorrection {
+    pub h_cell: u32,
+    pub h_comp: u32,
+    pub _pad: [u32; 2],
+    pub e_cell: [u32; MAX_TERMS],
+    pub e_comp: [u32; MAX_TERMS],
+    pub coef: [f32; MAX_TERMS],
+}
+
+/// Accumulates correction terms, merging those that hit the same H edge.
+#[derive(Default)]
+pub struct HCorrections {
+    entries: BTreeMap<(u32, u32), HCorrection>,
+}
+
+impl HCorrections {
+    /// Add `coef · E_{e_axis}[e_id]` to the update of `H_{h_axis}[h_id]`.
+    ///
+    /// The models only correct the four E edges of the H edge's Faraday
+    /// loop, so with terms on the same E edge merged the slots never run
+    /// out, however many models overlap.
+    pub fn add(&mut self, h_id: usize, h_axis: Axis, e_id: usize, e_axis: Axis, coef: f64) {
+        let key = (h_id as u32, h_axis.lane() as u32);
+        let entry = self.entries.entry(key).or_insert_with(|| HCorrection {
+            h_cell: key.0,
+            h_comp: key.1,
+            ..Zeroable::zeroed()
+        });
+        let same =
+            |t: &usize| entry.e_cell[*t] == e_id as u32 && entry.e_comp[*t] == e_axis.lane() as u32;
+        let slot = (0..MAX_TERMS)
+            .find(same)
+            .or_else(|| (0..MAX_TERMS).find(|&t| entry.coef[t] == 0.0))
+            .expect("an H edge's loop has four E edges");
+        entry.e_cell[slot] = e_id as u32;
+        entry.e_comp[slot] = e_axis.lane() as u32;
+        entry.coef[slot] += coef as f32;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn to_vec(&self) -> Vec<HCorrection> {
+        self.entries.values().copied().collect()
+    }
+}
+
+/// GPU resources for the H-correction pass.
+pub struct HCorrectionPass {
+    pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    count: u32,
+}
+
+impl HCorrectionPass {
+    /// Returns `None` when there is nothing to correct.
+    pub fn new(
+        device: &wgpu::Device,
+        corrections: &HCorrections,
+        e_fields: [&wgpu::Buffer; 3],
+        h_fields: [&wgpu::Buffer; 3],
+    ) -> Option<Self> {
+        if corrections.is_empty() {
+            return None;
+        }
+        let entries = corrections.to_vec();
+
+        let buf_entries = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("h_corrections"),
+            contents: bytemuck::cast_slice(&entries),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
+        let mut layout = vec![bgl_storage_entry(0, true)];
+        layout.extend((1..4).map(|b| bgl_storage_entry(b, true)));
+        layout.extend((4..7).map(|b| bgl_storage_entry(b, false)));
+        let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("h_correct_bgl"),
+            entries: &layout,
+        });
+        let pipeline = compute_pipeline(
+            device,
+            "h_correct",
+            include_str!("shaders/h_correct.wgsl"),
+            &bgl,
+        );
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("bg_h_correct"),
+            layout: &bgl,
+            entries: &[
+                bg_entry(0, buf_entries.as_entire_binding()),
+                bg_entry(1, e_fields[0].as_entire_binding()),
+                bg_entry(2, e_fields[1].as_entire_binding()),
+                bg_entry(3, e_fields[2].as_entire_binding()),
+                bg_entry(4, h_fields[0].as_entire_binding()),
+                bg_entry(5, h_fields[1].as_entire_binding()),
+                bg_entry(6, h_fields[2].as_entire_binding()),
+            ],
+        });
+
+        Some(HCorrectionPass {
+            pipeline,
+            bind_group,
+            count: entries.len() as u32,
+        })
+    }
+
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder) {
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("H corrections"),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.dispatch_workgroups(groups_1d(self.count, 64), 1, 1);
+    }
+}
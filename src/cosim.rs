@@ -0,0 +1,232 @@
+//! Lumped-port co-simulation with circuit netlists.
+//!
+//! A circuit port connects the `port` node of a netlist ([`crate::circuit`])
+//! across one E edge, ground at the lower end: the circuit sees the gap
+//! voltage V = −E_a·Δa (as [`crate::ports`]) and draws the current I from
+//! the upper node, which enters the edge as J = −I/A along +a.  Every step
+//! the two exchange V and I:
+//!
+//! 1. before the E update the circuit predicts its Norton equivalent one
+//!    step ahead, I(t + Δt) ≈ G₀·V + I_h, from the last gap voltage;
+//! 2. the grid takes G₀ implicitly, folded into the edge as a conductance
+//!    (semi-implicit like a lumped resistor), and the rest I_h, averaged
+//!    with the previous step's, as an explicit drive;
+//! 3. the new E is read back and the circuit advances with the new V.
+//!
+//! G₀ is the circuit's small-signal conductance over one step at rest
+//! (2C/Δt for a capacitor at the port, 1/R for a resistor), so linear
+//! circuits are coupled without lag and only the nonlinear remainder of
+//! diodes is explicit.  Nonlinear terminations that swing far from their
+//! rest conductance may need a parallel resistor to stay stable.  The read
+//! back stalls the GPU once per step, which makes the run latency-bound.
+//!
+//! `<name>_circuit.csv` records the gap voltage, port current and every
+//! node voltage per step.  Only the built-in solver is available; an
+//! external SPICE engine is not linked.
+
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::ade::AdeEdge;
+use crate::circuit::{Circuit, Netlist};
+use crate::config::ConfigError;
+use crate::error::FdtdError;
+use crate::gpu;
+use crate::grid::{Axis, Grid};
+use crate::materials::Coefficients;
+use crate::sources::Waveform;
+
+/// A netlist connected across the `axis`-directed E edge of `cell`.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct CircuitPort {
+    pub name: &'static str,
+    pub axis: Axis,
+    pub cell: [u32; 3],
+    /// SPICE-subset netlist and the node connected to the upper end of
+    /// the edge.
+    pub netlist: &'static str,
+    pub node: &'static str,
+}
+
+impl CircuitPort {
+    /// Fails unless the edge is inside `grid` and the netlist makes a
+    /// circuit with the port node.
+    pub fn check(&self, grid: &Grid) -> Result<(), ConfigError> {
+        let [i, j, k] = self.cell;
+        if !(i < grid.nx && j < grid.ny && k < grid.nz) {
+            let message = format!(
+                "circuit port {}: {:?} outside the grid",
+                self.name, self.cell
+            );
+            return Err(ConfigError(message));
+        }
+        self.circuit(grid.dt).map(drop)
+    }
+
+    /// The circuit at rest with time step `dt`.
+    pub fn circuit(&self, dt: f64) -> Result<Circuit, ConfigError> {
+        Netlist::parse(self.netlist)
+            .and_then(|netlist| Circuit::new(netlist, self.node, dt))
+            .map_err(|e| ConfigError(format!("circuit port {}: {e}", self.name)))
+    }
+
+    /// Length and cross-section of the edge.
+    fn edge(&self, grid: &Grid) -> (f64, f64) {
+        let a = self.axis;
+        let (b, c) = a.tangential();
+        let cell = |axis: Axis| self.cell[axis.lane()];
+        let area = grid.dual_width(b, cell(b)) * grid.dual_width(c, cell(c));
+        (grid.width(a, cell(a)), area)
+    }
+
+    /// Fold G₀ into the edge and register its drive slot, whose value the
+    /// [`CosimPass`] sets every step.
+    pub fn apply(
+        &self,
+        grid: &Grid,
+        coeffs: &mut Coefficients,
+        drives: &mut Vec<(f64, Waveform)>,
+    ) -> Result<AdeEdge, ConfigError> {
+        let (len, area) = self.edge(grid);
+        let id = grid.idx(self.cell[0], self.cell[1], self.cell[2]);
+        let g0 = self.circuit(grid.dt)?.conductance();
+        coeffs.add_e_conductivity(id, self.axis, g0 * len / area, grid.dt);
+        let cb = coeffs.cb[id][self.axis.lane()] as f64;
+        // Unit amplitude; the value comes from the circuit
+        drives.push((
+            1.0,
+            Waveform::Sine {
+                freq: 0.0,
+                ramp: 0.0,
+            },
+        ));
+        let slot = (drives.len() - 1) as u32;
+        Ok(AdeEdge::driven(id, self.axis, slot, cb / area))
+    }
+}
+
+/// State of one coupled port.
+struct Coupled {
+    port: CircuitPort,
+    circuit: Circuit,
+    /// Drive slot, cell index and edge length.
+    slot: usize,
+    id: usize,
+    len: f64,
+    g0: f64,
+    /// Gap voltage and I − G₀·V at the last exchange.
+    v: f64,
+    history: f64,
+    /// (V, I, node voltages) per step.
+    trace: Vec<(f64, f64, Vec<f64>)>,
+}
+
+/// Per-step V/I exchange of every circuit port.
+pub struct CosimPass {
+    ports: Vec<Coupled>,
+    readback: wgpu::Buffer,
+}
+
+impl CosimPass {
+    /// `slots` are the drive slots the ports' [`CircuitPort::apply`]
+    /// registered, in order.
+    pub fn new(
+        device: &wgpu::Device,
+        grid: &Grid,
+        ports: &[CircuitPort],
+        slots: &[u32],
+    ) -> Result<Self, ConfigError> {
+        let ports: Vec<Coupled> = ports
+            .iter()
+            .zip(slots)
+            .map(|(port, &slot)| {
+                let circuit = port.circuit(grid.dt)?;
+                Ok(Coupled {
+                    port: *port,
+                    g0: circuit.conductance(),
+                    circuit,
+                    slot: slot as usize,
+                    id: grid.idx(port.cell[0], port.cell[1], port.cell[2]),
+                    len: port.edge(grid).0,
+                    v: 0.0,
+                    history: 0.0,
+                    trace: Vec::new(),
+                })
+            })
+            .collect::<Result<_, ConfigError>>()?;
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("cosim_readback"),
+            size: (ports.len().max(1) * 4) as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Ok(CosimPass { ports, readback })
+    }
+
+    /// Put this step's explicit currents into `drives` (one per slot).
+    pub fn set_drives(&self, drives: &mut [f32]) {
+        for p in &self.ports {
+            let predicted = p.circuit.predict(p.v) - p.g0 * p.v;
+            drives[p.slot] = (0.5 * (p.history + predicted)) as f32;
+        }
+    }
+
+    /// Copy the new E of every port edge for [`exchange`](Self::exchange),
+    /// `e` being the Ex, Ey and Ez buffers.
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder, e: [&wgpu::Buffer; 3]) {
+        for (k, p) in self.ports.iter().enumerate() {
+            let source = e[p.port.axis.lane()];
+            encoder.copy_buffer_to_buffer(source, p.id as u64 * 4, &self.readback, k as u64 * 4, 4);
+        }
+    }
+
+    /// Read the gap voltages back and advance the circuits.
+    pub fn exchange(&mut self, device: &wgpu::Device) -> Result<(), FdtdError> {
+        let slice = self.readback.slice(..);
+        gpu::map_read(device, &[slice])?;
+        let e: Vec<f32> = bytemuck::cast_slice(&slice.get_mapped_range()).to_vec();
+        self.readback.unmap();
+        for (p, e) in self.ports.iter_mut().zip(e) {
+            p.v = -(e as f64) * p.len;
+            let i = p.circuit.step(p.v);
+            p.history = i - p.g0 * p.v;
+            p.trace.push((p.v, i, p.circuit.voltages().to_vec()));
+        }
+        Ok(())
+    }
+
+    /// Write `<name>_circuit.csv` for every port; returns the peak |V| and
+    /// |I| of each.
+    pub fn write_results(&self, dir: &Path, dt: f64) -> io::Result<Vec<(f64, f64)>> {
+        fs::create_dir_all(dir)?;
+        for p in &self.ports {
+            let path = dir.join(format!("{}_circuit.csv", p.port.name));
+            let mut file = BufWriter::new(fs::File::create(path)?);
+            write!(file, "time,v_port,i_port")?;
+            for node in p.circuit.node_names() {
+                write!(file, ",v({node})")?;
+            }
+            writeln!(file)?;
+            for (n, (v, i, nodes)) in p.trace.iter().enumerate() {
+                write!(file, "{:e},{v:e},{i:e}", (n + 1) as f64 * dt)?;
+                for v in nodes {
+                    write!(file, ",{v:e}")?;
+                }
+                writeln!(file)?;
+            }
+            file.flush()?;
+        }
+        Ok(self
+            .ports
+            .iter()
+            .map(|p| {
+                p.trace.iter().fold((0.0, 0.0), |(v, i), t| {
+                    (f64::max(v, t.0.abs()), f64::max(i, t.1.abs()))
+                })
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,912 @@
+//! The 3D Yee update on the CPU.
+//!
+//! [`CpuSolver`] steps the same f32 fields, coefficient maps and cell
+//! widths as `update_h.wgsl` and `update_e.wgsl`, in slabs of z planes on
+//! every core, as a reference for the Yee core of the GPU kernels: the
+//! probe files of a run with [`Backend::Cpu`] go straight into
+//! `fdtd_3d compare` against those of the GPU run.  Its only outputs are
+//! the probes, so it stands in for a GPU only in runs that ask for
+//! nothing else.  [`CpuRun`] drives it with the sources, the probes and
+//! the hooks as a [`ComputeBackend`]; [`Simulation::new`](crate::Simulation::new)
+//! sets one up for a configuration with [`Backend::Cpu`], and
+//! [`crate::run_scene`] budgets, checkpoints and interrupts it as it does a
+//! GPU run.  Its checkpoints hold the fields, the ADE currents and the
+//! steps taken, in the sections of a GPU checkpoint.
+//!
+//! It covers what the coefficient maps hold (objects, material maps and
+//! smoothing, graded meshes, thin layers), the sparse edges of the sub-cell
+//! models (dispersive, sheet and lumped currents with their drives, thin
+//! wires and conformal PEC) and periodic boundaries, with the point source
+//! and the probes; circuits, surface impedances, modulated materials,
+//! sub-grids, the other schemes and precisions, the DFT, flux, port,
+//! far-field and SAR analyses, the monitors and the field outputs need the
+//! GPU, and [`Config::validate`](crate::Config::validate) refuses them.  The half
+//! steps go over the z planes on a rayon pool kept for the whole run.
+
+use std::io;
+use std::num::NonZeroUsize;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use tracing::info;
+
+use crate::adapters::Backend;
+use crate::ade::{AdeEdge, NO_DRIVE};
+use crate::backend::{ComputeBackend, SourceDrive};
+use crate::checkpoint::{self, Checkpoint};
+use crate::config::{Config, ConfigError, OutputFormat, Scene, Verbosity};
+use crate::corrections::{HCorrection, MAX_TERMS};
+use crate::dft::Spectrum;
+use crate::error::FdtdError;
+use crate::grid::{Axis, Field, Grid};
+use crate::hooks::{StepCallback, StepFields, StepState};
+use crate::interrupt;
+use crate::manifest::RunInfo;
+use crate::materials::Coefficients;
+use crate::precision::Precision;
+use crate::probes::{self, ProbeOutput, ProbeWriter};
+use crate::progress::{self, Progress};
+use crate::simulation::{build_coefficients, console_sinks, select_dt};
+use crate::sinks::{OutputEvent, OutputSink};
+use crate::sources::Waveform;
+use crate::C0;
+
+/// Worker threads of a CPU run, one per core, unless
+/// [`Config::cpu_threads`](crate::Config::cpu_threads) sets them.
+pub fn threads() -> usize {
+    thread::available_parallelism().map_or(1, NonZeroUsize::get)
+}
+
+/// Fields and coefficients of a grid stepped on the CPU.
+pub struct CpuSolver {
+    size: [usize; 3],
+    /// Ex, Ey, Ez, Hx, Hy, Hz, x fastest.
+    fields: [Vec<f32>; 6],
+    coeffs: Coefficients,
+    /// Inverse primary (for differences of E) and dual (of H) cell widths
+    /// per index along each axis.
+    inv_primary: [Vec<f32>; 3],
+    inv_dual: [Vec<f32>; 3],
+    periodic: [bool; 3],
+    /// Planes along z whose H and whose E are updated.
+    h_planes: Range<usize>,
+    e_planes: Range<usize>,
+    /// Auxiliary currents after the E update, with their (J, E) state as in
+    /// `ade_edges.wgsl`.
+    ade_edges: Vec<AdeEdge>,
+    ade_state: Vec<[f32; 2]>,
+    /// Sub-cell terms added to H after its update, as in `h_correct.wgsl`.
+    h_corrections: Vec<HCorrection>,
+    pool: ThreadPool,
+}
+
+impl CpuSolver {
+    /// Zero fields on `grid` with the coefficient maps `coeffs`, wrapping
+    /// around on the `periodic` axes, stepped by `threads` workers.
+    pub fn new(
+        grid: &Grid,
+        coeffs: Coefficients,
+        periodic: &[Axis],
+        threads: usize,
+    ) -> io::Result<Self> {
+        let axes = [Axis::X, Axis::Y, Axis::Z];
+        let widths = |width: fn(&Grid, Axis, u32) -> f64| {
+            axes.map(|axis| {
+                (0..grid.cells(axis))
+                    .map(|i| (1.0 / width(grid, axis, i)) as f32)
+                    .collect()
+            })
+        };
+        let nz = grid.nz as usize;
+        let periodic_z = periodic.contains(&Axis::Z);
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|t| format!("fdtd-cpu-{t}"))
+            .build()
+            .map_err(io::Error::other)?;
+        Ok(CpuSolver {
+            size: [grid.nx, grid.ny, grid.nz].map(|n| n as usize),
+            fields: std::array::from_fn(|_| vec![0.0; grid.total()]),
+            coeffs,
+            inv_primary: widths(Grid::width),
+            inv_dual: widths(Grid::dual_width),
+            periodic: axes.map(|axis| periodic.contains(&axis)),
+            h_planes: if periodic_z { 0..nz } else { 0..nz - 1 },
+            e_planes: if periodic_z { 0..nz } else { 1..nz },
+            ade_edges: Vec::new(),
+            ade_state: Vec::new(),
+            h_corrections: Vec::new(),
+            pool,
+        })
+    }
+
+    /// The z planes `planes` of `grid`, whose coefficient maps are
+    /// `coeffs`, and a plane of halo below and above them where the grid
+    /// goes on, for one rank of a distributed run (see
+    /// [`crate::distributed`]): the lower halo plane takes H from the rank
+    /// below, which the E update of the first plane reads, and the upper one
+    /// E from the rank above.  Only `planes` are updated; z must not be
+    /// periodic.
+    pub fn slab(
+        grid: &Grid,
+        coeffs: &Coefficients,
+        periodic: &[Axis],
+        threads: usize,
+        planes: Range<u32>,
+    ) -> io::Result<Self> {
+        let (lo, hi) = (
+            planes.start.saturating_sub(1),
+            (planes.end + 1).min(grid.nz),
+        );
+        let [gx, gy, gz] = grid.graded;
+        let local = Grid {
+            nz: hi - lo,
+            graded: [gx, gy, gz.map(|w| &w[lo as usize..hi as usize])],
+            ..*grid
+        };
+        let mut solver = CpuSolver::new(&local, coeffs.planes(grid, lo..hi), periodic, threads)?;
+        let local = |k: u32| (k - lo) as usize;
+        solver.h_planes = local(planes.start)..local(planes.end.min(grid.nz - 1));
+        solver.e_planes = local(planes.start.max(1))..local(planes.end);
+        Ok(solver)
+    }
+
+    /// Step the ADE currents of `ade_edges` after E and add `h_corrections`
+    /// to H, the sparse edges the setup lists for the sub-cell models.  The
+    /// currents start at zero.
+    pub fn with_edges(mut self, ade_edges: Vec<AdeEdge>, h_corrections: Vec<HCorrection>) -> Self {
+        self.ade_state = vec![[0.0; 2]; ade_edges.len()];
+        self.ade_edges = ade_edges;
+        self.h_corrections = h_corrections;
+        self
+    }
+
+    pub fn threads(&self) -> usize {
+        self.pool.current_num_threads()
+    }
+
+    /// The six components in (Ex, …, Hz) order.
+    pub fn fields(&self) -> [&[f32]; 6] {
+        self.fields.each_ref().map(Vec::as_slice)
+    }
+
+    /// One component, x fastest.
+    pub fn field(&self, field: Field) -> &[f32] {
+        &self.fields[field.index()]
+    }
+
+    /// One component to overwrite, x fastest.
+    pub fn field_mut(&mut self, field: Field) -> &mut [f32] {
+        &mut self.fields[field.index()]
+    }
+
+    /// Overwrite `field` at cell `id`, as a hard source does.
+    pub fn set(&mut self, field: Field, id: usize, value: f32) {
+        self.fields[field.index()][id] = value;
+    }
+
+    /// Plane `k` of one component, x fastest.
+    pub fn plane(&self, field: Field, k: usize) -> &[f32] {
+        let plane = self.size[0] * self.size[1];
+        &self.fields[field.index()][k * plane..(k + 1) * plane]
+    }
+
+    /// Plane `k` of one component to overwrite, x fastest.
+    pub fn plane_mut(&mut self, field: Field, k: usize) -> &mut [f32] {
+        let plane = self.size[0] * self.size[1];
+        &mut self.fields[field.index()][k * plane..(k + 1) * plane]
+    }
+
+    /// The (J, E) state of the ADE currents, one pair per edge.
+    pub fn ade_state(&self) -> &[[f32; 2]] {
+        &self.ade_state
+    }
+
+    /// The (J, E) state to go on from, one pair per edge.
+    pub fn ade_state_mut(&mut self) -> &mut [[f32; 2]] {
+        &mut self.ade_state
+    }
+
+    /// The `Ex` … `Hz` and `ade` sections of a checkpoint of the planes
+    /// stepped: the fields from the first to the last plane updated, halos
+    /// left out.
+    pub fn sections(&self) -> Vec<(&'static str, &[u8])> {
+        let plane = self.size[0] * self.size[1];
+        let planes = self.stepped();
+        let cells = planes.start * plane..planes.end * plane;
+        let mut sections: Vec<(&str, &[u8])> = Field::ALL
+            .iter()
+            .zip(&self.fields)
+            .map(|(field, values)| (field.name(), bytemuck::cast_slice(&values[cells.clone()])))
+            .collect();
+        if !self.ade_state.is_empty() {
+            sections.push(("ade", bytemuck::cast_slice(&self.ade_state)));
+        }
+        sections
+    }
+
+    /// The fields and ADE currents of the [`sections`](Self::sections) of
+    /// `checkpoint`.
+    pub fn restore(&mut self, checkpoint: &Checkpoint) -> Result<(), FdtdError> {
+        let plane = self.size[0] * self.size[1];
+        let planes = self.stepped();
+        let cells = planes.start * plane..planes.end * plane;
+        let section = |name: &str, bytes: usize| {
+            checkpoint
+                .section(name)
+                .filter(|section| section.len() == bytes)
+                .ok_or_else(|| {
+                    let message = format!("the checkpoint has no {name} section for this run");
+                    FdtdError::from(ConfigError(message))
+                })
+        };
+        for field in Field::ALL {
+            let bytes = section(field.name(), 4 * cells.len())?;
+            let values = bytes
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes(b.try_into().unwrap()));
+            let out = &mut self.fields[field.index()][cells.clone()];
+            for (out, value) in out.iter_mut().zip(values) {
+                *out = value;
+            }
+        }
+        if !self.ade_state.is_empty() {
+            let bytes = section("ade", 8 * self.ade_state.len())?;
+            bytemuck::cast_slice_mut::<_, u8>(&mut self.ade_state).copy_from_slice(bytes);
+        }
+        Ok(())
+    }
+
+    /// The planes either half step updates, all of them on a whole grid.
+    fn stepped(&self) -> Range<usize> {
+        let (h, e) = (&self.h_planes, &self.e_planes);
+        match (h.is_empty(), e.is_empty()) {
+            (true, true) => 0..self.size[2],
+            _ => h.start.min(e.start)..h.end.max(e.end),
+        }
+    }
+
+    /// One step: H from the differences of E and its sub-cell terms, then E
+    /// from those of H and the ADE currents, `drives` holding the value of
+    /// each drive slot at the E update.
+    pub fn step(&mut self, drives: &[f32]) {
+        self.step_h();
+        self.step_e(drives);
+    }
+
+    /// The H half of [`step`](Self::step).
+    pub fn step_h(&mut self) {
+        self.update_h();
+        self.correct_h();
+    }
+
+    /// The E half of [`step`](Self::step).
+    pub fn step_e(&mut self, drives: &[f32]) {
+        self.update_e();
+        self.update_ade(drives);
+    }
+
+    /// H ← CP·H + CQ·curl E, staying one cell inside the upper boundary of
+    /// each non-periodic axis (and inside the planes of a slab).
+    pub fn update_h(&mut self) {
+        let [nx, ny, nz] = self.size;
+        let [ex, ey, ez, hx, hy, hz] = &mut self.fields;
+        let (ex, ey, ez) = (&ex[..], &ey[..], &ez[..]);
+        let (cp, cq) = (&self.coeffs.cp, &self.coeffs.cq);
+        let [inv_x, inv_y, inv_z] = &self.inv_primary;
+        let (periodic, updated) = (self.periodic, &self.h_planes);
+        let idx = |i: usize, j: usize, k: usize| i + nx * (j + ny * k);
+        let skip = |i: usize, n: usize, lane: usize| i == n - 1 && !periodic[lane];
+        let up = |i: usize, n: usize| if i == n - 1 { 0 } else { i + 1 };
+        planes(&self.pool, nx * ny, [hx, hy, hz], |k, [hx, hy, hz]| {
+            if !updated.contains(&k) {
+                return;
+            }
+            let kp = up(k, nz);
+            for j in (0..ny).filter(|&j| !skip(j, ny, 1)) {
+                let jp = up(j, ny);
+                for i in (0..nx).filter(|&i| !skip(i, nx, 0)) {
+                    let ip = up(i, nx);
+                    let id = idx(i, j, k);
+                    let local = i + nx * j;
+                    let d_ey_dz = (ey[idx(i, j, kp)] - ey[id]) * inv_z[k];
+                    let d_ez_dy = (ez[idx(i, jp, k)] - ez[id]) * inv_y[j];
+                    let d_ez_dx = (ez[idx(ip, j, k)] - ez[id]) * inv_x[i];
+                    let d_ex_dz = (ex[idx(i, j, kp)] - ex[id]) * inv_z[k];
+                    let d_ex_dy = (ex[idx(i, jp, k)] - ex[id]) * inv_y[j];
+                    let d_ey_dx = (ey[idx(ip, j, k)] - ey[id]) * inv_x[i];
+                    let (p, q) = (cp[id], cq[id]);
+                    hx[local] = p[0] * hx[local] + q[0] * (d_ey_dz - d_ez_dy);
+                    hy[local] = p[1] * hy[local] + q[1] * (d_ez_dx - d_ex_dz);
+                    hz[local] = p[2] * hz[local] + q[2] * (d_ex_dy - d_ey_dx);
+                }
+            }
+        });
+    }
+
+    /// H[h] += Σ coef·E of the thin-wire and conformal corrections.
+    fn correct_h(&mut self) {
+        let (e, h) = self.fields.split_at_mut(3);
+        for c in &self.h_corrections {
+            let dh: f32 = (0..MAX_TERMS)
+                .filter(|&t| c.coef[t] != 0.0)
+                .map(|t| c.coef[t] * e[c.e_comp[t] as usize][c.e_cell[t] as usize])
+                .sum();
+            h[c.h_comp as usize][c.h_cell as usize] += dh;
+        }
+    }
+
+    /// E ← CA·E + CB·curl H, skipping index 0 of each non-periodic axis
+    /// (and the planes outside a slab).
+    pub fn update_e(&mut self) {
+        let [nx, ny, nz] = self.size;
+        let [ex, ey, ez, hx, hy, hz] = &mut self.fields;
+        let (hx, hy, hz) = (&hx[..], &hy[..], &hz[..]);
+        let (ca, cb) = (&self.coeffs.ca, &self.coeffs.cb);
+        let [inv_x, inv_y, inv_z] = &self.inv_dual;
+        let (periodic, updated) = (self.periodic, &self.e_planes);
+        let idx = |i: usize, j: usize, k: usize| i + nx * (j + ny * k);
+        let skip = |i: usize, lane: usize| i == 0 && !periodic[lane];
+        let down = |i: usize, n: usize| if i == 0 { n - 1 } else { i - 1 };
+        planes(&self.pool, nx * ny, [ex, ey, ez], |k, [ex, ey, ez]| {
+            if !updated.contains(&k) {
+                return;
+            }
+            let km = down(k, nz);
+            for j in (0..ny).filter(|&j| !skip(j, 1)) {
+                let jm = down(j, ny);
+                for i in (0..nx).filter(|&i| !skip(i, 0)) {
+                    let im = down(i, nx);
+                    let id = idx(i, j, k);
+                    let local = i + nx * j;
+                    let d_hz_dy = (hz[id] - hz[idx(i, jm, k)]) * inv_y[j];
+                    let d_hy_dz = (hy[id] - hy[idx(i, j, km)]) * inv_z[k];
+                    let d_hx_dz = (hx[id] - hx[idx(i, j, km)]) * inv_z[k];
+                    let d_hz_dx = (hz[id] - hz[idx(im, j, k)]) * inv_x[i];
+                    let d_hy_dx = (hy[id] - hy[idx(im, j, k)]) * inv_x[i];
+                    let d_hx_dy = (hx[id] - hx[idx(i, jm, k)]) * inv_y[j];
+                    let (a, b) = (ca[id], cb[id]);
+                    ex[local] = a[0] * ex[local] + b[0] * (d_hz_dy - d_hy_dz);
+                    ey[local] = a[1] * ey[local] + b[1] * (d_hx_dz - d_hz_dx);
+                    ez[local] = a[2] * ez[local] + b[2] * (d_hy_dx - d_hx_dy);
+                }
+            }
+        });
+    }
+
+    /// E ← E − CJ·J + DJ·drive, then J ← KJ·J + BJ·E + BP·E_old on each ADE
+    /// edge.
+    fn update_ade(&mut self, drives: &[f32]) {
+        for (edge, state) in self.ade_edges.iter().zip(&mut self.ade_state) {
+            let e = &mut self.fields[edge.comp as usize][edge.cell as usize];
+            let [j, e_old] = *state;
+            let mut e_new = *e - edge.cj * j;
+            if edge.src != NO_DRIVE {
+                e_new += edge.dj * drives[edge.src as usize];
+            }
+            *state = [edge.kj * j + edge.bj * e_new + edge.bp * e_old, e_new];
+            *e = e_new;
+        }
+    }
+}
+
+/// A run of a 3D scene on the CPU: the solver driven by the point source
+/// and the ADE drives, and the probes written and handed to the sinks as
+/// the GPU run does.
+pub struct CpuRun {
+    config: Config,
+    scene: Scene,
+    grid: Grid,
+    solver: CpuSolver,
+    /// Factor on the point source's waveform.
+    source_amplitude: f64,
+    /// (amplitude, waveform) of each ADE drive slot.
+    drives: Vec<(f64, Waveform)>,
+    outputs: HostOutputs,
+    hooks: Vec<(u32, StepCallback)>,
+    n: u32,
+    info: RunInfo,
+    stopped: bool,
+}
+
+impl CpuRun {
+    /// Set up `scene` of `config`, going on from `config.resume` when it
+    /// is a checkpoint of that scene; `clock` started with the setup.
+    pub fn new(config: Config, scene: Scene, clock: Instant) -> Result<Self, FdtdError> {
+        let mut grid = config.grid;
+        let (mut coeffs, mut sub) = build_coefficients(&config, &grid, Scene::Structure)?;
+        if select_dt(&config, &mut grid, &coeffs)? || scene == Scene::Reference {
+            (coeffs, sub) = build_coefficients(&config, &grid, scene)?;
+        }
+        let threads = config.cpu_threads.unwrap_or_else(threads);
+        let solver = CpuSolver::new(&grid, coeffs, &config.periodic_axes(), threads)?
+            .with_edges(sub.ade_edges, sub.h_corrections.to_vec());
+        info!(
+            ade_edges = solver.ade_edges.len(),
+            threads = solver.threads(),
+            nx = grid.nx,
+            ny = grid.ny,
+            nz = grid.nz,
+            cells = grid.total(),
+            steps = config.steps,
+            courant = C0 * grid.dt / grid.dx,
+            "CPU run"
+        );
+        let info = RunInfo {
+            scene: format!("{scene:?}"),
+            adapter: format!("CPU, {} threads", solver.threads()),
+            backend: format!("{:?}", Backend::Cpu),
+            precision: format!("{:?}", Precision::F32),
+            size: [grid.nx, grid.ny, grid.nz],
+            dt: grid.dt,
+            steps: 0,
+            setup: clock.elapsed(),
+            stepping: Duration::ZERO,
+        };
+
+        // The probe files of a resumed run are appended to
+        let resumed = match &config.resume {
+            Some(path) => Some(checkpoint::read(path)?)
+                .filter(|c| c.section("scene") == Some(format!("{scene:?}").as_bytes())),
+            None => None,
+        };
+        let outputs = HostOutputs::new(&config, &grid, resumed.is_some())?;
+        let mut run = CpuRun {
+            config,
+            scene,
+            grid,
+            solver,
+            source_amplitude: 1.0,
+            drives: sub.drives,
+            outputs,
+            hooks: Vec::new(),
+            n: 0,
+            info,
+            stopped: false,
+        };
+        if let Some(checkpoint) = resumed {
+            run.restore(&checkpoint)?;
+        }
+        Ok(run)
+    }
+
+    /// The solver stepped.
+    pub fn solver(&self) -> &CpuSolver {
+        &self.solver
+    }
+
+    /// The solver, to overwrite its fields.
+    pub fn solver_mut(&mut self) -> &mut CpuSolver {
+        &mut self.solver
+    }
+}
+
+impl ComputeBackend for CpuRun {
+    fn config(&self) -> &Config {
+        &self.config
+    }
+
+    fn scene(&self) -> Scene {
+        self.scene
+    }
+
+    fn grid(&self) -> &Grid {
+        &self.grid
+    }
+
+    fn steps_taken(&self) -> u32 {
+        self.n
+    }
+
+    fn stopped(&self) -> bool {
+        self.stopped
+    }
+
+    fn step(&mut self) -> Result<(), FdtdError> {
+        let (config, grid, n) = (&self.config, &self.grid, self.n);
+        if !config.source_replaced() {
+            let [si, sj, sk] = config.source;
+            let value = self.source_amplitude * config.waveform.value(n as f64, grid.dt);
+            self.solver
+                .set(Field::E(Axis::Z), grid.idx(si, sj, sk), value as f32);
+        }
+        // Lumped voltage sources, evaluated at the E-update midpoint n + ½
+        let drives: Vec<f32> = self
+            .drives
+            .iter()
+            .map(|(v, w)| (v * w.value(n as f64 + 0.5, grid.dt)) as f32)
+            .collect();
+        self.solver.step(&drives);
+
+        let values: Vec<f64> = config
+            .probes
+            .iter()
+            .map(|probe| probes::sample(grid, probe, self.solver.fields()))
+            .collect();
+        self.n += 1;
+        self.outputs.record(config, grid, n, &values)?;
+        for (every, hook) in &mut self.hooks {
+            if self.n.is_multiple_of(*every) {
+                let mut state = StepState {
+                    fields: StepFields::Host(&mut self.solver),
+                    grid: &self.grid,
+                    steps: self.n,
+                    stop: false,
+                };
+                hook(&mut state);
+                self.stopped |= state.stop;
+            }
+        }
+        Ok(())
+    }
+
+    /// An interrupt stops the run before the next step.
+    fn run(&mut self, steps: u32) -> Result<(), FdtdError> {
+        let start = Instant::now();
+        for _ in 0..steps {
+            self.stopped |= interrupt::requested();
+            if self.stopped {
+                break;
+            }
+            self.step()?;
+        }
+        self.info.stepping += start.elapsed();
+        Ok(())
+    }
+
+    fn add_hook(&mut self, every: u32, hook: StepCallback) -> Result<(), FdtdError> {
+        self.hooks.push((every, hook));
+        Ok(())
+    }
+
+    /// The probe samples only.
+    fn add_boxed_sink(&mut self, sink: Box<dyn OutputSink>) {
+        self.outputs.sinks.push(sink);
+    }
+
+    fn source_mut(&mut self) -> SourceDrive<'_> {
+        SourceDrive {
+            cell: &mut self.config.source,
+            waveform: &mut self.config.waveform,
+            amplitude: &mut self.source_amplitude,
+        }
+    }
+
+    fn field(&self, field: Field) -> Result<Vec<f32>, FdtdError> {
+        Ok(self.solver.field(field).to_vec())
+    }
+
+    fn set_field(&mut self, field: Field, values: &[f32]) -> Result<(), FdtdError> {
+        self.grid.check_field(values)?;
+        self.solver.field_mut(field).copy_from_slice(values);
+        Ok(())
+    }
+
+    fn value(&self, field: Field, cell: [u32; 3]) -> Result<f32, FdtdError> {
+        Ok(self.solver.field(field)[self.grid.cell_idx(cell)?])
+    }
+
+    /// The `scene`, `Ex` … `Hz`, `ade` and `counters` sections of a GPU
+    /// checkpoint, the counters but the probe rows reported zero.
+    fn save_checkpoint(&self, path: &Path) -> Result<(), FdtdError> {
+        let scene = format!("{:?}", self.scene);
+        let mut counters = [self.n, 0].map(u32::to_le_bytes).concat();
+        counters.extend([0.0_f64; 2].map(f64::to_le_bytes).concat());
+        let mut sections: Vec<(&str, &[u8])> = vec![("scene", scene.as_bytes())];
+        sections.extend(self.solver.sections());
+        sections.push(("counters", &counters));
+        checkpoint::write(path, &self.grid, self.n, &sections)?;
+        info!(path = %path.display(), steps = self.n, "wrote a checkpoint");
+        Ok(())
+    }
+
+    fn restore(&mut self, checkpoint: &Checkpoint) -> Result<(), FdtdError> {
+        let g = &self.grid;
+        let same_dt = (checkpoint.dt - g.dt).abs() <= 1e-9 * g.dt;
+        if checkpoint.size != [g.nx, g.ny, g.nz] || !same_dt {
+            let message = "the checkpoint is of another grid or Δt than this run's";
+            return Err(ConfigError(message.into()).into());
+        }
+        self.solver.restore(checkpoint)?;
+        self.n = checkpoint.steps;
+        self.outputs.progress(self.n);
+        info!(steps = self.n, "resumed from a checkpoint");
+        Ok(())
+    }
+
+    /// The CPU has no device to lose: this only sets the run up again.
+    fn reopen(self, resume: Option<PathBuf>) -> Result<Self, FdtdError> {
+        let (mut config, scene) = (self.config.clone(), self.scene);
+        let stepping = self.info.stepping;
+        config.resume = resume;
+        drop(self);
+        let mut run = CpuRun::new(config, scene, Instant::now())?;
+        run.info.stepping += stepping;
+        Ok(run)
+    }
+
+    fn finish(mut self) -> Result<(Vec<Spectrum>, RunInfo), FdtdError> {
+        self.outputs.finish(&self.config)?;
+        self.info.steps = self.n;
+        Ok((Vec::new(), self.info))
+    }
+}
+
+/// The probe files, sinks and progress bar of a run stepped on the host,
+/// fed the probe values of each step.
+pub(crate) struct HostOutputs {
+    probe_writer: Option<ProbeWriter>,
+    pub(crate) sinks: Vec<Box<dyn OutputSink>>,
+    progress: Option<Progress>,
+    /// Whether the run prints to the console.
+    console: bool,
+}
+
+impl HostOutputs {
+    /// The outputs `config` asks for of a run on `grid`, appending to the
+    /// probe files when it is `resumed` from a checkpoint.
+    pub(crate) fn new(config: &Config, grid: &Grid, resumed: bool) -> io::Result<Self> {
+        let open_writer: fn(ProbeOutput, &[&str]) -> io::Result<ProbeWriter> = match resumed {
+            true => ProbeWriter::append,
+            false => ProbeWriter::new,
+        };
+        let names: Vec<&str> = config.probes.iter().map(|p| p.name).collect();
+        let probe_writer = config
+            .probe_output
+            .map(|spec| open_writer(spec, &names))
+            .transpose()?;
+        Ok(HostOutputs {
+            probe_writer,
+            sinks: console_sinks(config),
+            progress: Progress::new(config.steps, grid.total(), grid.dt, config.verbosity),
+            console: true,
+        })
+    }
+
+    /// No outputs, for a process whose outputs another writes.
+    pub(crate) fn none() -> Self {
+        HostOutputs {
+            probe_writer: None,
+            sinks: Vec::new(),
+            progress: None,
+            console: false,
+        }
+    }
+
+    /// Move the progress bar to `steps` taken.
+    pub(crate) fn progress(&mut self, steps: u32) {
+        if let Some(progress) = &mut self.progress {
+            progress.update(steps);
+        }
+    }
+
+    /// Write the probe `values` of step `n` of a run of `config` on `grid`
+    /// and hand them and the step to the sinks.
+    pub(crate) fn record(
+        &mut self,
+        config: &Config,
+        grid: &Grid,
+        n: u32,
+        values: &[f64],
+    ) -> Result<(), FdtdError> {
+        let t = (n + 1) as f64 * grid.dt;
+        let probes = &config.probes;
+        if let Some(writer) = &mut self.probe_writer {
+            for (p, &value) in values.iter().enumerate() {
+                writer.record(p, n, t, value)?;
+            }
+        }
+        for (probe, &value) in probes.iter().zip(values) {
+            let event = OutputEvent::Probe {
+                name: probe.name,
+                quantity: probe.quantity,
+                step: n,
+                time: t,
+                value,
+            };
+            for sink in &mut self.sinks {
+                sink.event(&event)?;
+            }
+        }
+        let verbose =
+            config.verbosity == Verbosity::Verbose && config.output_format == OutputFormat::Text;
+        if self.console && verbose {
+            let mut line = format!("t={:4}", n);
+            for (probe, value) in probes.iter().zip(values) {
+                line += &format!(
+                    "  {}[{}] = {:.6e}",
+                    probe.quantity.label(),
+                    probe.name,
+                    value
+                );
+            }
+            match &self.progress {
+                Some(progress) => progress.println(&line),
+                None => println!("{line}"),
+            }
+        }
+
+        self.progress(n + 1);
+        let event = OutputEvent::Step {
+            step: n,
+            steps: config.steps,
+            time: t,
+        };
+        for sink in &mut self.sinks {
+            sink.event(&event)?;
+        }
+        Ok(())
+    }
+
+    /// Close the progress bar and the sinks.
+    pub(crate) fn finish(&mut self, config: &Config) -> Result<(), FdtdError> {
+        if let Some(progress) = &self.progress {
+            progress.finish();
+        }
+        for sink in &mut self.sinks {
+            sink.finish()?;
+        }
+        if self.console && config.output_format == OutputFormat::Text {
+            progress::suspend(|| println!("\nSimulation complete."));
+        }
+        Ok(())
+    }
+}
+
+/// Run `update(k, out)` over the z planes `k` of the three `out`
+/// components on `pool`, `out` cut into planes of `plane` values.
+fn planes(
+    pool: &ThreadPool,
+    plane: usize,
+    out: [&mut Vec<f32>; 3],
+    update: impl Fn(usize, [&mut [f32]; 3]) + Send + Sync,
+) {
+    let [a, b, c] = out;
+    pool.install(|| {
+        a.par_chunks_mut(plane)
+            .zip(b.par_chunks_mut(plane))
+            .zip(c.par_chunks_mut(plane))
+            .enumerate()
+            .for_each(|(k, ((a, b), c))| update(k, [a, b, c]));
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lumped::{LumpedElement, LumpedKind};
+    use crate::Simulation;
+
+    /// A 16³ PEC cavity of 1 mm cells stepped on the CPU, with a 1 V step
+    /// source of 50 Ω on an Ez edge off the centre and a 1 nH inductor.
+    fn cavity(threads: usize, steps: u32) -> Config {
+        let mut c = Config::new(Grid::uniform([16; 3], 1e-3, 1e-12), steps);
+        c.backend = Some(Backend::Cpu);
+        c.cpu_threads = Some(threads);
+        c.verbosity = Verbosity::Quiet;
+        c.lumped = vec![
+            LumpedElement {
+                axis: Axis::Z,
+                cell: [5, 8, 8],
+                kind: LumpedKind::VoltageSource {
+                    r: 50.0,
+                    v: 1.0,
+                    waveform: Waveform::Step {
+                        rise: 50.0,
+                        delay: 0.0,
+                    },
+                },
+            },
+            LumpedElement {
+                axis: Axis::X,
+                cell: [11, 4, 4],
+                kind: LumpedKind::Inductor { l: 1e-9 },
+            },
+        ];
+        c
+    }
+
+    fn fields(simulation: &Simulation) -> Vec<Vec<f32>> {
+        Field::ALL
+            .iter()
+            .map(|&field| simulation.field(field).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn the_threads_step_the_same_fields() {
+        let mut one = Simulation::new(cavity(1, 40), Scene::Structure).unwrap();
+        let mut four = Simulation::new(cavity(4, 40), Scene::Structure).unwrap();
+        one.run(40).unwrap();
+        four.run(40).unwrap();
+        assert_eq!(fields(&one), fields(&four));
+        assert!(fields(&one)[2].iter().any(|&ez| ez != 0.0));
+    }
+
+    #[test]
+    fn an_unloaded_voltage_source_settles_at_its_voltage() {
+        let mut simulation = Simulation::new(cavity(2, 3000), Scene::Structure).unwrap();
+        simulation.set_source_amplitude(0.0);
+        simulation.run(3000).unwrap();
+        let grid = *simulation.grid();
+        let ez = simulation.value(Field::E(Axis::Z), [5, 8, 8]).unwrap();
+        let volts = ez as f64 * grid.dz;
+        assert!((volts - 1.0).abs() < 1e-2, "{volts} V across the source");
+    }
+
+    #[test]
+    fn a_checkpoint_carries_the_currents_over() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run.ckpt");
+        let mut straight = Simulation::new(cavity(2, 60), Scene::Structure).unwrap();
+        straight.run(60).unwrap();
+
+        let mut first = Simulation::new(cavity(2, 60), Scene::Structure).unwrap();
+        first.run(35).unwrap();
+        first.save_checkpoint(&path).unwrap();
+        let mut resumed = Simulation::new(cavity(2, 60), Scene::Structure).unwrap();
+        resumed.resume(&path).unwrap();
+        assert_eq!(resumed.steps_taken(), 35);
+        resumed.run(25).unwrap();
+        assert_eq!(fields(&straight), fields(&resumed));
+    }
+
+    #[test]
+    fn a_hook_stops_the_run() {
+        let mut simulation = Simulation::new(cavity(2, 60), Scene::Structure).unwrap();
+        simulation
+            .on_step(1, |state| {
+                let ez = state.value(Field::E(Axis::Z), [5, 8, 8]).unwrap();
+                if state.steps() == 12 {
+                    assert!(ez != 0.0);
+                    state.stop();
+                }
+            })
+            .unwrap();
+        simulation.run(60).unwrap();
+        assert!(simulation.stopped());
+        assert_eq!(simulation.steps_taken(), 12);
+    }
+
+    #[test]
+    fn cells_planes_and_fields_off_the_grid_are_errors() {
+        let mut simulation = Simulation::new(cavity(1, 10), Scene::Structure).unwrap();
+        let ez = Field::E(Axis::Z);
+        assert!(matches!(
+            simulation.set_field(ez, &[0.0; 15]),
+            Err(FdtdError::FieldLength {
+                expected: 4096,
+                found: 15
+            })
+        ));
+        assert!(matches!(
+            simulation.slice(ez, Axis::Y, 16),
+            Err(FdtdError::OutsideGrid(_))
+        ));
+        assert!(matches!(
+            simulation.set_source([0, 16, 0]),
+            Err(FdtdError::OutsideGrid(_))
+        ));
+        simulation
+            .on_step(1, |state| {
+                let ez = Field::E(Axis::Z);
+                assert!(state.set(ez, [16, 0, 0], 1.0).is_err());
+                assert!(state.set_field(ez, &[]).is_err());
+                state.set(ez, [15, 0, 0], 1.0).unwrap();
+            })
+            .unwrap();
+        assert!(simulation.on_step(0, |_| {}).is_err());
+        simulation.run(1).unwrap();
+        assert_eq!(simulation.value(ez, [15, 0, 0]).unwrap(), 1.0);
+        assert!(simulation.value(ez, [15, 0, 16]).is_err());
+    }
+}
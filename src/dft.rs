@@ -0,0 +1,434 @@
+//! Running-DFT frequency monitors.
+//!
+//! A monitor accumulates Σ Δt·F(t)·exp(−jωt) of chosen components on the
+//! GPU every step, for a list of frequencies over a point, a plane or a box
+//! of cells, so steady-state complex fields and spectra come out of one run
+//! without storing the time history.  E is sampled at (n + 1)·Δt after step
+//! n and H half a step earlier, so E and H spectra share one time origin.
+//! Accumulation is in f32; the per-step factors are computed in f64 on the
+//! host.
+//!
+//! At the end of a run every component is written to
+//! `<name>_<component>.bin` (little-endian: magic `FDTDDFT1`, `u32` sx, sy,
+//! sz and nf, nf `f64` frequencies in Hz, then per frequency sx·sy·sz
+//! complex values as (re, im) `f32` pairs, x fastest) and point monitors
+//! also get the spectrum `<name>.csv`.
+
+use std::f64::consts::PI;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use bytemuck::{Pod, Zeroable};
+use serde::Serialize;
+use wgpu::util::DeviceExt;
+
+use crate::config::ConfigError;
+use crate::error::FdtdError;
+use crate::gpu::{
+    self, bg_entry, bgl_storage_entry, bgl_uniform_entry, compute_pipeline, groups_1d, Uploads,
+};
+use crate::grid::{Axis, Field, Grid};
+
+/// Cells covered by a frequency monitor.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize)]
+pub enum Region {
+    /// The single cell (i, j, k).
+    Point([u32; 3]),
+    /// Every cell of the plane `index` normal to `normal`.
+    Plane { normal: Axis, index: u32 },
+    /// The cells `lo..hi` on each axis.
+    Box { lo: [u32; 3], hi: [u32; 3] },
+}
+
+impl Region {
+    /// Whether the region is non-empty and inside `grid`.
+    pub fn inside(self, grid: &Grid) -> bool {
+        let cells = [grid.nx, grid.ny, grid.nz];
+        match self {
+            Region::Point(cell) => (0..3).all(|a| cell[a] < cells[a]),
+            Region::Plane { normal, index } => index < grid.cells(normal),
+            Region::Box { lo, hi } => (0..3).all(|a| lo[a] < hi[a] && hi[a] <= cells[a]),
+        }
+    }
+
+    /// Lower cell and size on each axis.
+    pub fn bounds(self, grid: &Grid) -> ([u32; 3], [u32; 3]) {
+        let (lo, hi) = match self {
+            Region::Point(cell) => (cell, cell.map(|c| c + 1)),
+            Region::Plane { normal, index } => {
+                let mut lo = [0; 3];
+                let mut hi = [grid.nx, grid.ny, grid.nz];
+                lo[normal.lane()] = index;
+                hi[normal.lane()] = index + 1;
+                (lo, hi)
+            }
+            Region::Box { lo, hi } => (lo, hi),
+        };
+        (lo, [0, 1, 2].map(|a| hi[a] - lo[a]))
+    }
+}
+
+/// A named frequency monitor.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct DftMonitor {
+    pub name: &'static str,
+    pub fields: &'static [Field],
+    pub region: Region,
+    /// Frequencies in Hz.
+    pub frequencies: &'static [f64],
+}
+
+impl DftMonitor {
+    /// Fails unless the region is inside `grid` and some components are
+    /// transformed at positive frequencies.
+    pub fn check(&self, grid: &Grid) -> Result<(), ConfigError> {
+        let problem = if !self.region.inside(grid) {
+            format!("{:?} is empty or outside the grid", self.region)
+        } else if self.fields.is_empty() {
+            "no field components".to_string()
+        } else if self.frequencies.is_empty() || self.frequencies.iter().any(|&f| f <= 0.0) {
+            "positive frequencies needed".to_string()
+        } else {
+            return Ok(());
+        };
+        Err(ConfigError(format!("DFT monitor {}: {problem}", self.name)))
+    }
+}
+
+/// Accumulated spectra of one monitor, read back from the GPU.
+pub struct Spectrum {
+    pub name: &'static str,
+    pub frequencies: Vec<f64>,
+    /// Lower cell and size of the region.
+    pub lo: [u32; 3],
+    pub size: [u32; 3],
+    /// Per component, frequency-major (re, im) values, x fastest.
+    pub fields: Vec<(Field, Vec<[f32; 2]>)>,
+}
+
+impl Spectrum {
+    pub fn cells(&self) -> usize {
+        self.size.iter().product::<u32>() as usize
+    }
+
+    /// Complex value of component `field` at frequency `f` and the region's
+    /// cell `cell`, or `None` if the component is not monitored.
+    pub fn value(&self, field: Field, f: usize, cell: usize) -> Option<[f32; 2]> {
+        let (_, values) = self.fields.iter().find(|(c, _)| *c == field)?;
+        Some(values[f * self.cells() + cell])
+    }
+}
+
+/// Accumulation parameters (must match WGSL `DftParams`).
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct DftParams {
+    count: u32,
+    nf: u32,
+    base: u32,
+    sx: u32,
+    sy: u32,
+    nx: u32,
+    ny: u32,
+    phase: u32,
+}
+
+/// GPU state of one monitor.
+struct Accumulator {
+    spec: DftMonitor,
+    lo: [u32; 3],
+    size: [u32; 3],
+    count: u32,
+    /// Per-step E then H factors.
+    factors: wgpu::Buffer,
+    /// Per component: accumulator and bind group.
+    acc: Vec<(wgpu::Buffer, wgpu::BindGroup)>,
+}
+
+/// All frequency monitors of a run.
+pub struct DftPass {
+    pipeline: wgpu::ComputePipeline,
+    monitors: Vec<Accumulator>,
+    dt: f64,
+}
+
+impl DftPass {
+    /// `fields` are the six field buffers in (Ex, Ey, Ez, Hx, Hy, Hz) order.
+    pub fn new(
+        device: &wgpu::Device,
+        grid: &Grid,
+        monitors: &[DftMonitor],
+        fields: [&wgpu::Buffer; 6],
+    ) -> Self {
+        let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("dft_bgl"),
+            entries: &[
+                bgl_uniform_entry(0),
+                bgl_storage_entry(1, true),
+                bgl_storage_entry(2, true),
+                bgl_storage_entry(3, false),
+            ],
+        });
+        let pipeline = compute_pipeline(device, "dft", include_str!("shaders/dft.wgsl"), &bgl);
+        let monitors = monitors
+            .iter()
+            .map(|&spec| {
+                let (lo, size) = spec.region.bounds(grid);
+                let count = size.iter().product::<u32>();
+                let nf = spec.frequencies.len() as u32;
+                let factors = device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("dft_factors"),
+                    size: 2 * nf as u64 * 8,
+                    usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                });
+                let acc = spec
+                    .fields
+                    .iter()
+                    .map(|&field| {
+                        let params = DftParams {
+                            count,
+                            nf,
+                            base: grid.idx(lo[0], lo[1], lo[2]) as u32,
+                            sx: size[0],
+                            sy: size[1],
+                            nx: grid.nx,
+                            ny: grid.ny,
+                            phase: if let Field::H(_) = field { nf } else { 0 },
+                        };
+                        let params = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                            label: Some("dft_params"),
+                            contents: bytemuck::bytes_of(&params),
+                            usage: wgpu::BufferUsages::UNIFORM,
+                        });
+                        // Zero-initialised at creation
+                        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                            label: Some("dft_acc"),
+                            size: (count * nf) as u64 * 8,
+                            usage: wgpu::BufferUsages::STORAGE
+                                | wgpu::BufferUsages::COPY_SRC
+                                | wgpu::BufferUsages::COPY_DST,
+                            mapped_at_creation: false,
+                        });
+                        let group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                            label: Some("bg_dft"),
+                            layout: &bgl,
+                            entries: &[
+                                bg_entry(0, params.as_entire_binding()),
+                                bg_entry(1, fields[field.index()].as_entire_binding()),
+                                bg_entry(2, factors.as_entire_binding()),
+                                bg_entry(3, buffer.as_entire_binding()),
+                            ],
+                        });
+                        (buffer, group)
+                    })
+                    .collect();
+                Accumulator {
+                    spec,
+                    lo,
+                    size,
+                    count,
+                    factors,
+                    acc,
+                }
+            })
+            .collect();
+        DftPass {
+            pipeline,
+            monitors,
+            dt: grid.dt,
+        }
+    }
+
+    /// Add the fields after step `n` to every accumulator.
+    pub fn encode(&self, uploads: &mut Uploads, encoder: &mut wgpu::CommandEncoder, n: u32) {
+        let (t_e, t_h) = ((n as f64 + 1.0) * self.dt, (n as f64 + 0.5) * self.dt);
+        for m in &self.monitors {
+            let factor = |f: f64, t: f64| {
+                let phase = -2.0 * PI * f * t;
+                [
+                    (self.dt * phase.cos()) as f32,
+                    (self.dt * phase.sin()) as f32,
+                ]
+            };
+            let factors: Vec<[f32; 2]> = [t_e, t_h]
+                .iter()
+                .flat_map(|&t| m.spec.frequencies.iter().map(move |&f| factor(f, t)))
+                .collect();
+            uploads.write(encoder, &m.factors, 0, bytemuck::cast_slice(&factors));
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("dft"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            for (_, group) in &m.acc {
+                pass.set_bind_group(0, group, &[]);
+                pass.dispatch_workgroups(groups_1d(m.count, 64), 1, 1);
+            }
+        }
+    }
+
+    /// The accumulators, monitor by monitor, for checkpoints.
+    pub fn state(&self) -> impl Iterator<Item = &wgpu::Buffer> {
+        self.monitors
+            .iter()
+            .flat_map(|m| m.acc.iter().map(|(buffer, _)| buffer))
+    }
+
+    /// Copy every accumulator back to the host.
+    pub fn read(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<Vec<Spectrum>, FdtdError> {
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("dft_read"),
+        });
+        let staging: Vec<Vec<wgpu::Buffer>> = self
+            .monitors
+            .iter()
+            .map(|m| {
+                m.acc
+                    .iter()
+                    .map(|(buffer, _)| {
+                        let staging = device.create_buffer(&wgpu::BufferDescriptor {
+                            label: Some("dft_staging"),
+                            size: buffer.size(),
+                            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                            mapped_at_creation: false,
+                        });
+                        encoder.copy_buffer_to_buffer(buffer, 0, &staging, 0, buffer.size());
+                        staging
+                    })
+                    .collect()
+            })
+            .collect();
+        queue.submit(Some(encoder.finish()));
+        let slices: Vec<_> = staging.iter().flatten().map(|b| b.slice(..)).collect();
+        gpu::map_read(device, &slices)?;
+
+        Ok(self
+            .monitors
+            .iter()
+            .zip(&staging)
+            .map(|(m, staging)| Spectrum {
+                name: m.spec.name,
+                frequencies: m.spec.frequencies.to_vec(),
+                lo: m.lo,
+                size: m.size,
+                fields: m
+                    .spec
+                    .fields
+                    .iter()
+                    .zip(staging)
+                    .map(|(&field, buffer)| {
+                        let values =
+                            bytemuck::cast_slice(&buffer.slice(..).get_mapped_range()).to_vec();
+                        buffer.unmap();
+                        (field, values)
+                    })
+                    .collect(),
+            })
+            .collect())
+    }
+}
+
+/// Read the spectra of `monitor` stored as `<dir>/<name>_<component>.bin`
+/// by an earlier run, checking they cover the monitor's region and
+/// frequencies.
+pub fn read_spectrum(
+    dir: &Path,
+    name: &'static str,
+    monitor: &DftMonitor,
+    grid: &Grid,
+) -> io::Result<Spectrum> {
+    let (lo, size) = monitor.region.bounds(grid);
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+    let mut fields = Vec::new();
+    for &field in monitor.fields {
+        let path = dir.join(format!("{name}_{}.bin", field.name()));
+        let bytes = fs::read(&path)?;
+        if !bytes.starts_with(b"FDTDDFT1") || bytes.len() < 24 {
+            return Err(invalid(format!("{}: not a DFT spectrum", path.display())));
+        }
+        let word = |i: usize| u32::from_le_bytes(bytes[8 + 4 * i..12 + 4 * i].try_into().unwrap());
+        let [sx, sy, sz, nf] = [0, 1, 2, 3].map(word);
+        let frequencies: Vec<f64> = (0..nf as usize)
+            .filter_map(|f| bytes.get(24 + 8 * f..32 + 8 * f))
+            .map(|b| f64::from_le_bytes(b.try_into().unwrap()))
+            .collect();
+        let same_frequencies = frequencies.len() == monitor.frequencies.len()
+            && frequencies
+                .iter()
+                .zip(monitor.frequencies)
+                .all(|(a, b)| (a - b).abs() <= 1e-9 * b.abs());
+        if [sx, sy, sz] != size || !same_frequencies {
+            return Err(invalid(format!(
+                "{}: region or frequencies differ from monitor {}",
+                path.display(),
+                monitor.name
+            )));
+        }
+        let data = &bytes[24 + 8 * nf as usize..];
+        let count = (sx * sy * sz * nf) as usize;
+        if data.len() != 8 * count {
+            return Err(invalid(format!("{}: truncated", path.display())));
+        }
+        let values = data
+            .chunks_exact(8)
+            .map(|b| {
+                let re = f32::from_le_bytes(b[..4].try_into().unwrap());
+                let im = f32::from_le_bytes(b[4..].try_into().unwrap());
+                [re, im]
+            })
+            .collect();
+        fields.push((field, values));
+    }
+    Ok(Spectrum {
+        name,
+        frequencies: monitor.frequencies.to_vec(),
+        lo,
+        size,
+        fields,
+    })
+}
+
+/// Write the files described in the module docs for every spectrum.
+pub fn write_spectra(dir: &Path, spectra: &[Spectrum]) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    for s in spectra {
+        for (field, values) in &s.fields {
+            let path = dir.join(format!("{}_{}.bin", s.name, field.name()));
+            let mut file = BufWriter::new(fs::File::create(path)?);
+            file.write_all(b"FDTDDFT1")?;
+            for v in [s.size[0], s.size[1], s.size[2], s.frequencies.len() as u32] {
+                file.write_all(&v.to_le_bytes())?;
+            }
+            for f in &s.frequencies {
+                file.write_all(&f.to_le_bytes())?;
+            }
+            file.write_all(bytemuck::cast_slice(values))?;
+            file.flush()?;
+        }
+        if s.cells() == 1 {
+            let path = dir.join(format!("{}.csv", s.name));
+            let mut file = BufWriter::new(fs::File::create(path)?);
+            write!(file, "frequency")?;
+            for (field, _) in &s.fields {
+                write!(file, ",{0}_re,{0}_im", field.name())?;
+            }
+            writeln!(file)?;
+            for (f, frequency) in s.frequencies.iter().enumerate() {
+                write!(file, "{frequency:e}")?;
+                for (_, values) in &s.fields {
+                    let [re, im] = values[f];
+                    write!(file, ",{re:e},{im:e}")?;
+                }
+                writeln!(file)?;
+            }
+            file.flush()?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,805 @@
+//! A CPU run split over several processes in slabs of z planes.
+//!
+//! Each rank is a process of its own, on this machine or another, started
+//! with the same scene and options and `--rank R --hosts HOST:PORT,…`, the
+//! address each rank listens on in rank order.  The grid is cut into
+//! [`slabs`] of whole z planes, one per rank, which steps its own on the
+//! CPU (see [`CpuSolver::slab`]) with a plane of halo on either side: after
+//! the H update a rank sends its top plane of Hx and Hy to the rank above,
+//! and after the E update its bottom plane of Ex and Ey to the rank below,
+//! over TCP.  Rank 0 sums the probe samples of every slab, writes the probe
+//! files, results.json and the console output, and decides before each
+//! step whether to go on (an interrupt stops every rank); the others log.
+//!
+//! Every rank builds the coefficient maps of the whole scene and keeps its
+//! slab, so the setup still takes the memory of the whole grid for a
+//! moment.  The objects, material maps, sheets, lumped elements and
+//! dispersive media, the point source and the probes work across the
+//! slabs; z must not be periodic, and thin wires and conformal PEC, whose
+//! H corrections reach across planes, the time budget and the final fields
+//! are refused by [`Config::validate`](crate::Config::validate).  Each rank
+//! writes its checkpoints next to those of a single run, `checkpoint.bin.R`
+//! for `checkpoint.bin`, and goes on from its own.  The ranks exchange f32
+//! values in little-endian byte order.
+
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+
+use serde::Serialize;
+use tracing::info;
+
+use crate::adapters::Backend;
+use crate::ade::AdeEdge;
+use crate::backend::{ComputeBackend, SourceDrive};
+use crate::checkpoint::{self, Checkpoint};
+use crate::config::{Config, ConfigError, Scene};
+use crate::cpu::{self, CpuSolver, HostOutputs};
+use crate::dft::Spectrum;
+use crate::error::FdtdError;
+use crate::grid::{Axis, Field, Grid};
+use crate::hooks::StepCallback;
+use crate::interrupt;
+use crate::manifest::RunInfo;
+use crate::precision::Precision;
+use crate::probes;
+use crate::simulation::{build_coefficients, select_dt};
+use crate::sinks::OutputSink;
+use crate::sources::Waveform;
+
+/// How long a rank keeps trying to reach another that is not up yet.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// What a connection is for, sent with the rank that opens it.
+const NEIGHBOUR: u8 = 0;
+const GATHER: u8 = 1;
+
+/// The rank of this process in a distributed run and where every rank
+/// listens.
+#[derive(Clone, Debug, Serialize)]
+pub struct Cluster {
+    pub rank: usize,
+    /// host:port of each rank, in rank order.
+    pub hosts: Vec<String>,
+}
+
+impl Cluster {
+    pub fn ranks(&self) -> usize {
+        self.hosts.len()
+    }
+
+    /// Fails unless there are two ranks or more, this one among them, and a
+    /// z plane of `grid` for each.
+    pub fn check(&self, grid: &Grid) -> Result<(), ConfigError> {
+        let ranks = self.ranks();
+        if ranks < 2 {
+            return Err(ConfigError(
+                "a distributed run needs at least two hosts".into(),
+            ));
+        }
+        if self.rank >= ranks {
+            let message = format!("rank {} is not one of the {ranks} hosts", self.rank);
+            return Err(ConfigError(message));
+        }
+        if (grid.nz as usize) < ranks {
+            let message = format!("{} z planes do not go round {ranks} ranks", grid.nz);
+            return Err(ConfigError(message));
+        }
+        Ok(())
+    }
+}
+
+/// The z planes of each of `ranks` slabs of `nz` planes, the lower slabs a
+/// plane thicker where they do not divide evenly.
+pub fn slabs(nz: u32, ranks: usize) -> Vec<Range<u32>> {
+    let ranks = ranks as u32;
+    let (size, rest) = (nz / ranks, nz % ranks);
+    let mut start = 0;
+    (0..ranks)
+        .map(|r| {
+            let end = start + size + u32::from(r < rest);
+            let slab = start..end;
+            start = end;
+            slab
+        })
+        .collect()
+}
+
+/// The checkpoint of rank `rank` for `path`, that of a single run.
+pub fn rank_path(path: &Path, rank: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{rank}"));
+    PathBuf::from(name)
+}
+
+/// The connections of one rank.
+struct Links {
+    /// To the ranks below and above, where there are.
+    below: Option<TcpStream>,
+    above: Option<TcpStream>,
+    /// On rank 0 to ranks 1, 2, … in order, on the others to rank 0 (the
+    /// same socket as `below` on rank 1, and as `above` on rank 0 for rank
+    /// 1).
+    gather: Vec<TcpStream>,
+}
+
+impl Links {
+    /// Reach the ranks below this one and rank 0, and take the connections
+    /// of those above on `listener`.
+    fn connect(cluster: &Cluster, listener: &TcpListener) -> io::Result<Self> {
+        let (rank, ranks) = (cluster.rank, cluster.ranks());
+        let mut gather: Vec<Option<TcpStream>> = (0..ranks).map(|_| None).collect();
+        let mut below = None;
+        if rank > 0 {
+            let stream = dial(&cluster.hosts[rank - 1], rank, NEIGHBOUR)?;
+            if rank == 1 {
+                gather[0] = Some(stream.try_clone()?);
+            }
+            below = Some(stream);
+        }
+        if rank > 1 {
+            gather[0] = Some(dial(&cluster.hosts[0], rank, GATHER)?);
+        }
+
+        let mut above = None;
+        let expected = usize::from(rank + 1 < ranks) + if rank == 0 { ranks - 2 } else { 0 };
+        for _ in 0..expected {
+            let (mut stream, _) = listener.accept()?;
+            stream.set_nodelay(true)?;
+            let mut hello = [0; 5];
+            stream.read_exact(&mut hello)?;
+            let peer = u32::from_le_bytes(hello[..4].try_into().unwrap()) as usize;
+            match hello[4] {
+                NEIGHBOUR if peer == rank + 1 => {
+                    if rank == 0 {
+                        gather[1] = Some(stream.try_clone()?);
+                    }
+                    above = Some(stream);
+                }
+                GATHER if rank == 0 && (2..ranks).contains(&peer) => gather[peer] = Some(stream),
+                _ => {
+                    let message = format!("rank {rank}: an unexpected connection from rank {peer}");
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+                }
+            }
+        }
+        let gather = gather.into_iter().flatten().collect();
+        Ok(Links {
+            below,
+            above,
+            gather,
+        })
+    }
+}
+
+/// Connect to `host`, trying again while it is not up yet, and tell it
+/// this is rank `rank` connecting for `purpose`.
+fn dial(host: &str, rank: usize, purpose: u8) -> io::Result<TcpStream> {
+    let start = Instant::now();
+    let mut stream = loop {
+        match TcpStream::connect(host) {
+            Ok(stream) => break stream,
+            Err(_) if start.elapsed() < CONNECT_TIMEOUT => {
+                thread::sleep(Duration::from_millis(100))
+            }
+            Err(e) => {
+                let message = format!("rank {rank}: cannot reach {host}: {e}");
+                return Err(io::Error::new(e.kind(), message));
+            }
+        }
+    };
+    stream.set_nodelay(true)?;
+    stream.write_all(&(rank as u32).to_le_bytes())?;
+    stream.write_all(&[purpose])?;
+    Ok(stream)
+}
+
+fn send(mut stream: &TcpStream, values: &[f32]) -> io::Result<()> {
+    let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+    stream.write_all(&bytes)
+}
+
+fn receive(mut stream: &TcpStream, out: &mut [f32]) -> io::Result<()> {
+    let mut bytes = vec![0; 4 * out.len()];
+    stream.read_exact(&mut bytes)?;
+    for (out, b) in out.iter_mut().zip(bytes.chunks_exact(4)) {
+        *out = f32::from_le_bytes(b.try_into().unwrap());
+    }
+    Ok(())
+}
+
+/// The components a half step on the other side of a z boundary reads.
+const TANGENTIAL: [Axis; 2] = [Axis::X, Axis::Y];
+
+/// One rank of a distributed run as a [`ComputeBackend`], driven by
+/// [`crate::run_scene`] on every rank alike.
+pub struct DistributedRun {
+    config: Config,
+    scene: Scene,
+    grid: Grid,
+    cluster: Cluster,
+    /// The z planes of every rank.
+    slabs: Vec<Range<u32>>,
+    /// The first plane of the solver's, a halo plane but on rank 0.
+    base: u32,
+    solver: CpuSolver,
+    links: Links,
+    /// Factor on the point source's waveform.
+    source_amplitude: f64,
+    /// (amplitude, waveform) of each ADE drive slot.
+    drives: Vec<(f64, Waveform)>,
+    /// Rank 0's outputs; none on the others.
+    outputs: HostOutputs,
+    n: u32,
+    info: RunInfo,
+    stopped: bool,
+}
+
+impl DistributedRun {
+    /// Set up the slab of `config.cluster`'s rank of `scene`, once every
+    /// rank listens, going on from its checkpoint of `config.resume`;
+    /// `clock` started with the setup.
+    pub fn new(config: Config, scene: Scene, clock: Instant) -> Result<Self, FdtdError> {
+        let cluster = cluster(&config)?;
+        let host = &cluster.hosts[cluster.rank];
+        let listener = TcpListener::bind(host).map_err(|e| {
+            let message = format!("rank {}: cannot listen on {host}: {e}", cluster.rank);
+            io::Error::new(e.kind(), message)
+        })?;
+        DistributedRun::with_listener(config, scene, clock, &listener)
+    }
+
+    /// [`new`](Self::new), listening on `listener`.
+    pub fn with_listener(
+        config: Config,
+        scene: Scene,
+        clock: Instant,
+        listener: &TcpListener,
+    ) -> Result<Self, FdtdError> {
+        let cluster = cluster(&config)?;
+        let mut grid = config.grid;
+        let (mut coeffs, mut sub) = build_coefficients(&config, &grid, Scene::Structure)?;
+        if select_dt(&config, &mut grid, &coeffs)? || scene == Scene::Reference {
+            (coeffs, sub) = build_coefficients(&config, &grid, scene)?;
+        }
+        let slabs = slabs(grid.nz, cluster.ranks());
+        let planes = slabs[cluster.rank].clone();
+        let base = planes.start.saturating_sub(1);
+        let plane = grid.nx * grid.ny;
+        let ade_edges: Vec<AdeEdge> = sub
+            .ade_edges
+            .into_iter()
+            .filter(|edge| planes.contains(&(edge.cell / plane)))
+            .map(|edge| AdeEdge {
+                cell: edge.cell - base * plane,
+                ..edge
+            })
+            .collect();
+        let threads = config.cpu_threads.unwrap_or_else(cpu::threads);
+        let periodic = config.periodic_axes();
+        let solver = CpuSolver::slab(&grid, &coeffs, &periodic, threads, planes.clone())?
+            .with_edges(ade_edges, Vec::new());
+        drop(coeffs);
+
+        let links = Links::connect(&cluster, listener)?;
+        info!(
+            rank = cluster.rank,
+            ranks = cluster.ranks(),
+            planes = ?planes,
+            threads = solver.threads(),
+            nx = grid.nx,
+            ny = grid.ny,
+            nz = grid.nz,
+            steps = config.steps,
+            "distributed run"
+        );
+        let info = RunInfo {
+            scene: format!("{scene:?}"),
+            adapter: format!(
+                "CPU, {} threads, rank {} of {}",
+                solver.threads(),
+                cluster.rank,
+                cluster.ranks()
+            ),
+            backend: format!("{:?}", Backend::Cpu),
+            precision: format!("{:?}", Precision::F32),
+            size: [grid.nx, grid.ny, grid.nz],
+            dt: grid.dt,
+            steps: 0,
+            setup: clock.elapsed(),
+            stepping: Duration::ZERO,
+        };
+
+        // Every rank reads its own checkpoint; rank 0 appends to the probe files
+        let resumed = match &config.resume {
+            Some(path) => Some(checkpoint::read(&rank_path(path, cluster.rank))?)
+                .filter(|c| c.section("scene") == Some(format!("{scene:?}").as_bytes())),
+            None => None,
+        };
+        let outputs = match cluster.rank {
+            0 => HostOutputs::new(&config, &grid, resumed.is_some())?,
+            _ => HostOutputs::none(),
+        };
+        let mut run = DistributedRun {
+            config,
+            scene,
+            grid,
+            cluster,
+            slabs,
+            base,
+            solver,
+            links,
+            source_amplitude: 1.0,
+            drives: sub.drives,
+            outputs,
+            n: 0,
+            info,
+            stopped: false,
+        };
+        if let Some(checkpoint) = resumed {
+            run.restore(&checkpoint)?;
+        }
+        Ok(run)
+    }
+
+    pub fn cluster(&self) -> &Cluster {
+        &self.cluster
+    }
+
+    /// The z planes this rank steps.
+    pub fn planes(&self) -> Range<u32> {
+        self.slabs[self.cluster.rank].clone()
+    }
+
+    /// The id in the whole grid of the solver's first cell.
+    fn offset(&self) -> usize {
+        self.base as usize * (self.grid.nx * self.grid.ny) as usize
+    }
+
+    /// The cells of planes `planes` in the solver's fields.
+    fn local(&self, planes: Range<u32>) -> Range<usize> {
+        let plane = (self.grid.nx * self.grid.ny) as usize;
+        planes.start as usize * plane - self.offset()..planes.end as usize * plane - self.offset()
+    }
+
+    /// Whether to stop before the next step: rank 0's `requested`, which it
+    /// hands to the others.
+    fn agree_to_stop(&self, requested: bool) -> io::Result<bool> {
+        if self.cluster.rank == 0 {
+            for mut stream in &self.links.gather {
+                stream.write_all(&[u8::from(requested)])?;
+            }
+            return Ok(requested);
+        }
+        let mut flag = [0];
+        (&self.links.gather[0]).read_exact(&mut flag)?;
+        Ok(flag[0] != 0)
+    }
+
+    /// Hand the top plane of H up and take the halo below from the rank
+    /// below.
+    fn exchange_h(&mut self) -> io::Result<()> {
+        let top = (self.planes().end - 1 - self.base) as usize;
+        if let Some(above) = &self.links.above {
+            for axis in TANGENTIAL {
+                send(above, self.solver.plane(Field::H(axis), top))?;
+            }
+        }
+        if let Some(below) = &self.links.below {
+            for axis in TANGENTIAL {
+                receive(below, self.solver.plane_mut(Field::H(axis), 0))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Hand the bottom plane of E down and take the halo above from the
+    /// rank above.
+    fn exchange_e(&mut self) -> io::Result<()> {
+        let planes = self.planes();
+        let (bottom, halo) = (
+            (planes.start - self.base) as usize,
+            (planes.end - self.base) as usize,
+        );
+        if let Some(below) = &self.links.below {
+            for axis in TANGENTIAL {
+                send(below, self.solver.plane(Field::E(axis), bottom))?;
+            }
+        }
+        if let Some(above) = &self.links.above {
+            for axis in TANGENTIAL {
+                receive(above, self.solver.plane_mut(Field::E(axis), halo))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The probe values on rank 0, added up from what every rank samples in
+    /// its planes; nothing on the others.
+    fn gather_probes(&self) -> io::Result<Vec<f64>> {
+        let (grid, offset) = (&self.grid, self.offset());
+        let fields = self.solver.fields();
+        let parts: Vec<Vec<f32>> = self
+            .config
+            .probes
+            .iter()
+            .map(|probe| {
+                probes::components(grid, probe, self.planes(), |field, id| {
+                    fields[field.index()][id - offset]
+                })
+            })
+            .collect();
+        let mut sums = parts.concat();
+        if self.cluster.rank != 0 {
+            send(&self.links.gather[0], &sums)?;
+            return Ok(Vec::new());
+        }
+        let mut theirs = vec![0.0; sums.len()];
+        for stream in &self.links.gather {
+            receive(stream, &mut theirs)?;
+            for (sum, value) in sums.iter_mut().zip(&theirs) {
+                *sum += value;
+            }
+        }
+        let mut at = 0;
+        Ok(self
+            .config
+            .probes
+            .iter()
+            .zip(&parts)
+            .map(|(probe, part)| {
+                at += part.len();
+                probes::combine(probe, &sums[at - part.len()..at])
+            })
+            .collect())
+    }
+}
+
+/// The cluster of a distributed `config`.
+fn cluster(config: &Config) -> Result<Cluster, ConfigError> {
+    config
+        .cluster
+        .clone()
+        .ok_or_else(|| ConfigError("the run has no --rank and --hosts".into()))
+}
+
+impl ComputeBackend for DistributedRun {
+    fn config(&self) -> &Config {
+        &self.config
+    }
+
+    fn scene(&self) -> Scene {
+        self.scene
+    }
+
+    fn grid(&self) -> &Grid {
+        &self.grid
+    }
+
+    fn steps_taken(&self) -> u32 {
+        self.n
+    }
+
+    fn stopped(&self) -> bool {
+        self.stopped
+    }
+
+    /// One step of every rank: the sources, H and its halo, E and its halo,
+    /// and the probes on rank 0.
+    fn step(&mut self) -> Result<(), FdtdError> {
+        let (config, grid, n) = (&self.config, &self.grid, self.n);
+        let [si, sj, sk] = config.source;
+        if !config.source_replaced() && self.planes().contains(&sk) {
+            let value = self.source_amplitude * config.waveform.value(n as f64, grid.dt);
+            let id = grid.idx(si, sj, sk) - self.offset();
+            self.solver.set(Field::E(Axis::Z), id, value as f32);
+        }
+        // Lumped voltage sources, evaluated at the E-update midpoint n + ½
+        let drives: Vec<f32> = self
+            .drives
+            .iter()
+            .map(|(v, w)| (v * w.value(n as f64 + 0.5, grid.dt)) as f32)
+            .collect();
+        self.solver.step_h();
+        self.exchange_h()?;
+        self.solver.step_e(&drives);
+        self.exchange_e()?;
+
+        let values = self.gather_probes()?;
+        self.n += 1;
+        self.outputs.record(&self.config, &self.grid, n, &values)
+    }
+
+    /// An interrupt of rank 0 stops every rank before the next step, as
+    /// though each were interrupted.
+    fn run(&mut self, steps: u32) -> Result<(), FdtdError> {
+        let start = Instant::now();
+        for _ in 0..steps {
+            self.stopped |= self.agree_to_stop(interrupt::requested())?;
+            if self.stopped {
+                // So that every rank writes its checkpoint
+                interrupt::request();
+                break;
+            }
+            self.step()?;
+        }
+        self.info.stepping += start.elapsed();
+        Ok(())
+    }
+
+    /// Refused: a hook sees the whole grid, and a rank holds its slab.
+    fn add_hook(&mut self, _every: u32, _hook: StepCallback) -> Result<(), FdtdError> {
+        let message = "a rank of a distributed run holds a slab, not the whole grid a hook sees";
+        Err(ConfigError(message.into()).into())
+    }
+
+    /// The probe samples, which rank 0 alone records.
+    fn add_boxed_sink(&mut self, sink: Box<dyn OutputSink>) {
+        self.outputs.sinks.push(sink);
+    }
+
+    fn source_mut(&mut self) -> SourceDrive<'_> {
+        SourceDrive {
+            cell: &mut self.config.source,
+            waveform: &mut self.config.waveform,
+            amplitude: &mut self.source_amplitude,
+        }
+    }
+
+    /// The whole component on rank 0, gathered from every rank, which all
+    /// call this together; the other ranks get their planes and zeros
+    /// elsewhere.
+    fn field(&self, field: Field) -> Result<Vec<f32>, FdtdError> {
+        let plane = (self.grid.nx * self.grid.ny) as usize;
+        let cells =
+            |planes: &Range<u32>| planes.start as usize * plane..planes.end as usize * plane;
+        let planes = self.planes();
+        let mut whole = vec![0.0; self.grid.total()];
+        whole[cells(&planes)]
+            .copy_from_slice(&self.solver.field(field)[self.local(planes.clone())]);
+        if self.cluster.rank != 0 {
+            send(&self.links.gather[0], &whole[cells(&planes)])?;
+            return Ok(whole);
+        }
+        for (stream, slab) in self.links.gather.iter().zip(&self.slabs[1..]) {
+            receive(stream, &mut whole[cells(slab)])?;
+        }
+        Ok(whole)
+    }
+
+    /// Takes this rank's planes and halos of the whole component `values`.
+    fn set_field(&mut self, field: Field, values: &[f32]) -> Result<(), FdtdError> {
+        self.grid.check_field(values)?;
+        let offset = self.offset();
+        let out = self.solver.field_mut(field);
+        out.copy_from_slice(&values[offset..offset + out.len()]);
+        Ok(())
+    }
+
+    /// This rank's checkpoint, at `path` with the rank appended (see
+    /// [`rank_path`]), with the `scene`, `planes`, `Ex` … `Hz` and `ade`
+    /// sections of its slab.
+    fn save_checkpoint(&self, path: &Path) -> Result<(), FdtdError> {
+        let path = rank_path(path, self.cluster.rank);
+        let scene = format!("{:?}", self.scene);
+        let planes = self.planes();
+        let slab = [planes.start, planes.end].map(u32::to_le_bytes).concat();
+        let mut sections: Vec<(&str, &[u8])> = vec![("scene", scene.as_bytes()), ("planes", &slab)];
+        sections.extend(self.solver.sections());
+        checkpoint::write(&path, &self.grid, self.n, &sections)?;
+        info!(path = %path.display(), steps = self.n, "wrote a checkpoint");
+        Ok(())
+    }
+
+    /// The fields and steps of this rank's `checkpoint`, then the halos
+    /// from the neighbours, which the checkpoint leaves out.
+    fn restore(&mut self, checkpoint: &Checkpoint) -> Result<(), FdtdError> {
+        let g = &self.grid;
+        let same_dt = (checkpoint.dt - g.dt).abs() <= 1e-9 * g.dt;
+        let planes = self.planes();
+        let slab = [planes.start, planes.end].map(u32::to_le_bytes).concat();
+        if checkpoint.size != [g.nx, g.ny, g.nz] || !same_dt {
+            let message = "the checkpoint is of another grid or Δt than this run's";
+            return Err(ConfigError(message.into()).into());
+        }
+        if checkpoint.section("planes") != Some(&slab) {
+            let message = format!(
+                "the checkpoint is not of rank {}'s planes",
+                self.cluster.rank
+            );
+            return Err(ConfigError(message).into());
+        }
+        self.solver.restore(checkpoint)?;
+        self.exchange_e()?;
+        self.n = checkpoint.steps;
+        self.outputs.progress(self.n);
+        info!(steps = self.n, "resumed from a checkpoint");
+        Ok(())
+    }
+
+    /// This rank's checkpoint of `path` (see [`rank_path`]), which every
+    /// rank resumes from together.
+    fn resume(&mut self, path: impl AsRef<Path>) -> Result<(), FdtdError> {
+        let path = rank_path(path.as_ref(), self.cluster.rank);
+        let checkpoint = checkpoint::read(&path)?;
+        let scene = format!("{:?}", self.scene);
+        if checkpoint.section("scene") != Some(scene.as_bytes()) {
+            let message = format!("{}: not a checkpoint of the {scene} run", path.display());
+            return Err(ConfigError(message).into());
+        }
+        self.restore(&checkpoint)
+    }
+
+    /// There is no device to lose, and the links cannot be set up again
+    /// without every other rank.
+    fn reopen(self, _resume: Option<PathBuf>) -> Result<Self, FdtdError> {
+        let message = "a rank of a distributed run cannot be set up again on its own";
+        Err(ConfigError(message.into()).into())
+    }
+
+    fn finish(mut self) -> Result<(Vec<Spectrum>, RunInfo), FdtdError> {
+        self.outputs.finish(&self.config)?;
+        self.info.steps = self.n;
+        Ok((Vec::new(), self.info))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Verbosity;
+    use crate::cpu::CpuRun;
+    use crate::lumped::{LumpedElement, LumpedKind};
+    use crate::probes::{Location, Probe, Quantity};
+
+    fn cavity(cluster: Option<Cluster>) -> Config {
+        let mut c = Config::new(Grid::uniform([16; 3], 1e-3, 1e-12), 40);
+        c.backend = Some(Backend::Cpu);
+        c.cpu_threads = Some(2);
+        c.verbosity = Verbosity::Quiet;
+        c.cluster = cluster;
+        c.lumped = vec![
+            LumpedElement {
+                axis: Axis::Z,
+                cell: [5, 8, 5],
+                kind: LumpedKind::VoltageSource {
+                    r: 50.0,
+                    v: 1.0,
+                    waveform: Waveform::Step {
+                        rise: 50.0,
+                        delay: 0.0,
+                    },
+                },
+            },
+            LumpedElement {
+                axis: Axis::X,
+                cell: [11, 4, 11],
+                kind: LumpedKind::Inductor { l: 1e-9 },
+            },
+        ];
+        // The second straddles the planes of ranks 0 and 1
+        c.probes = vec![
+            Probe {
+                name: "ez",
+                at: Location::Cell([5, 8, 5]),
+                quantity: Quantity::Component(Field::E(Axis::Z)),
+            },
+            Probe {
+                name: "e",
+                at: Location::Point([8.3e-3, 7.6e-3, 5.5e-3]),
+                quantity: Quantity::AbsE,
+            },
+        ];
+        c
+    }
+
+    /// `work` on every rank of a run of `config` split three ways, on a
+    /// thread each.
+    fn ranks<T: Send + 'static>(
+        config: impl Fn(Cluster) -> Config,
+        work: impl Fn(&mut DistributedRun) -> T + Send + Clone + 'static,
+    ) -> Vec<T> {
+        let listeners: Vec<TcpListener> = (0..3)
+            .map(|_| TcpListener::bind("127.0.0.1:0").unwrap())
+            .collect();
+        let hosts: Vec<String> = listeners
+            .iter()
+            .map(|l| l.local_addr().unwrap().to_string())
+            .collect();
+        let threads: Vec<_> = listeners
+            .into_iter()
+            .enumerate()
+            .map(|(rank, listener)| {
+                let hosts = hosts.clone();
+                let config = config(Cluster { rank, hosts });
+                let work = work.clone();
+                thread::spawn(move || {
+                    let mut run = DistributedRun::with_listener(
+                        config,
+                        Scene::Structure,
+                        Instant::now(),
+                        &listener,
+                    )
+                    .unwrap();
+                    work(&mut run)
+                })
+            })
+            .collect();
+        threads.into_iter().map(|t| t.join().unwrap()).collect()
+    }
+
+    fn fields(run: &impl ComputeBackend) -> Vec<Vec<f32>> {
+        Field::ALL
+            .iter()
+            .map(|&field| run.field(field).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn slabs_cover_the_planes() {
+        assert_eq!(slabs(16, 3), [0..6, 6..11, 11..16]);
+        assert_eq!(slabs(4, 4), [0..1, 1..2, 2..3, 3..4]);
+    }
+
+    #[test]
+    fn the_ranks_step_the_fields_of_a_single_run() {
+        let mut single = CpuRun::new(cavity(None), Scene::Structure, Instant::now()).unwrap();
+        single.run(40).unwrap();
+        let results = ranks(
+            |cluster| cavity(Some(cluster)),
+            |run| {
+                run.run(40).unwrap();
+                (run.gather_probes().unwrap(), fields(run))
+            },
+        );
+        let (probes, gathered) = &results[0];
+        assert_eq!(*gathered, fields(&single));
+        assert!(gathered[2].iter().any(|&ez| ez != 0.0));
+        for (probe, &value) in single.config().probes.iter().zip(probes) {
+            let expected = probes::sample(single.grid(), probe, single.solver().fields());
+            assert!(
+                (value - expected).abs() <= 1e-6 * expected.abs(),
+                "{}",
+                probe.name
+            );
+        }
+        assert!(results[1].0.is_empty());
+    }
+
+    #[test]
+    fn every_rank_resumes_from_its_checkpoint() {
+        let mut single = CpuRun::new(cavity(None), Scene::Structure, Instant::now()).unwrap();
+        single.run(40).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(checkpoint::FILE_NAME);
+        let saved = path.clone();
+        ranks(
+            |cluster| cavity(Some(cluster)),
+            move |run| {
+                run.run(25).unwrap();
+                run.save_checkpoint(&saved).unwrap();
+            },
+        );
+        assert!(rank_path(&path, 2).exists());
+        let results = ranks(
+            |cluster| Config {
+                resume: Some(path.clone()),
+                ..cavity(Some(cluster))
+            },
+            |run| {
+                assert_eq!(run.steps_taken(), 25);
+                run.run(15).unwrap();
+                fields(run)
+            },
+        );
+        assert_eq!(results[0], fields(&single));
+    }
+}
@@ -0,0 +1,309 @@
+//! Stopping a run whose fields have blown up.
+//!
+//! Every [`DivergenceCheck::every`] steps a GPU reduction finds the largest
+//! |Ex|, |Ey| or |Ez| of the f32 fields and its edge; only one maximum per
+//! workgroup is copied back.  The run fails with
+//! [`FdtdError::Diverged`](crate::FdtdError::Diverged) when that value is
+//! NaN or infinite, over [`DivergenceCheck::max_field`], or ten times the
+//! previous check's at each of the last five checks: the exponential growth
+//! of an unstable mode rather than the build-up behind a source.  Left to
+//! itself an instability only shows as NaN probe values and spectra at the
+//! end of the run.
+//!
+//! The [`Divergence`] it fails with names the step, the edge, its material
+//! and sub-cell model, and Δt against the stability limit, which is usually
+//! enough to tell a Courant violation from a misbehaving dispersive, lumped
+//! or surface-impedance model.  With [`DivergenceCheck::snapshot`] the six
+//! field components are also written as raw snapshots (see
+//! [`crate::snapshots`]) under `<monitor_dir>/diverged`.
+//!
+//! The check reads the f32 fields, so it is skipped while another
+//! precision runs alone.
+
+use std::fmt;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use bytemuck::{Pod, Zeroable};
+use serde::Serialize;
+use wgpu::util::DeviceExt;
+
+use crate::error::FdtdError;
+use crate::gpu::{
+    self, bg_entry, bgl_storage_entry, bgl_uniform_entry, compute_pipeline, groups_1d,
+};
+use crate::grid::{Field, Grid};
+use crate::snapshots;
+
+/// Growth from one check to the next that counts towards
+/// [`Cause::Growing`].
+const GROWTH: f32 = 10.0;
+
+/// Checks in a row growing by [`GROWTH`] that make a divergence.
+const GROWING_CHECKS: u32 = 5;
+
+/// When to check the fields and what to keep of a diverged run.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize)]
+pub struct DivergenceCheck {
+    /// Steps between checks.
+    pub every: u32,
+    /// Largest |E| component (V/m) the run may reach.
+    pub max_field: f64,
+    /// Write the fields of the step that diverged.
+    pub snapshot: bool,
+}
+
+impl Default for DivergenceCheck {
+    /// Every 100 steps, up to 10¹² V/m, far above what sources of a few
+    /// volts drive, without a snapshot.
+    fn default() -> Self {
+        DivergenceCheck {
+            every: 100,
+            max_field: 1e12,
+            snapshot: false,
+        }
+    }
+}
+
+/// What gave a divergence away.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Cause {
+    /// A NaN or infinite field.
+    NonFinite,
+    /// A field over this [`DivergenceCheck::max_field`].
+    OverLimit(f64),
+    /// Fields ten times larger at each of the last five checks.
+    Growing,
+}
+
+/// Where and how a run diverged.
+#[derive(Clone, Debug)]
+pub struct Divergence {
+    /// Steps taken.
+    pub steps: u32,
+    pub cause: Cause,
+    /// The largest |E| component (V/m), and its edge.
+    pub value: f32,
+    pub field: Field,
+    pub cell: [u32; 3],
+    /// ε_r and σ (S/m) of the edge, `None` for PEC.
+    pub material: Option<(f64, f64)>,
+    /// Sub-cell model on the edge, if any.
+    pub model: Option<&'static str>,
+    /// c·Δt/Δx.
+    pub courant: f64,
+    /// Δt over its stability limit, `None` for unconditionally stable
+    /// schemes.
+    pub stability: Option<f64>,
+    /// Directory of the snapshot written, if any.
+    pub snapshot: Option<PathBuf>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [i, j, k] = self.cell;
+        let name = self.field.name();
+        write!(f, "the fields diverged after {} steps: ", self.steps)?;
+        match self.cause {
+            Cause::NonFinite => write!(f, "|{name}| is {}", self.value)?,
+            Cause::OverLimit(limit) => write!(
+                f,
+                "|{name}| = {:.3e} V/m, over the {limit:.1e} V/m limit",
+                self.value
+            )?,
+            Cause::Growing => write!(
+                f,
+                "|{name}| = {:.3e} V/m after growing {GROWTH}-fold at each of the last \
+                 {GROWING_CHECKS} checks",
+                self.value
+            )?,
+        }
+        write!(f, " at [{i}, {j}, {k}] (")?;
+        match self.material {
+            Some((eps_r, sigma)) => write!(f, "ε_r {eps_r:.3}, σ {sigma:.3e} S/m")?,
+            None => write!(f, "PEC")?,
+        }
+        if let Some(model) = self.model {
+            write!(f, ", {model} edge")?;
+        }
+        write!(f, "); Courant number {:.4}", self.courant)?;
+        if let Some(ratio) = self.stability {
+            write!(f, ", Δt at {:.1} % of the stability limit", 100.0 * ratio)?;
+        }
+        if let Some(dir) = &self.snapshot {
+            write!(f, "; fields written to {}", dir.display())?;
+        }
+        Ok(())
+    }
+}
+
+/// Reduction parameters (must match WGSL `DivergenceParams`).
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct DivergenceParams {
+    count: u32,
+    _pad: [u32; 3],
+}
+
+/// GPU reduction of the largest |E| component, and the checks on it.
+pub struct DivergencePass {
+    spec: DivergenceCheck,
+    pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    partials: wgpu::Buffer,
+    staging: wgpu::Buffer,
+    groups: [u32; 2],
+    /// The value of the last check, and the checks in a row it grew by
+    /// [`GROWTH`].
+    last: f32,
+    growing: u32,
+}
+
+impl DivergencePass {
+    /// `fields` are the six field buffers in (Ex, Ey, Ez, Hx, Hy, Hz) order.
+    pub fn new(
+        device: &wgpu::Device,
+        grid: &Grid,
+        spec: DivergenceCheck,
+        fields: [&wgpu::Buffer; 6],
+    ) -> Self {
+        let count = grid.total() as u32;
+        let params = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("divergence_params"),
+            contents: bytemuck::bytes_of(&DivergenceParams {
+                count,
+                _pad: [0; 3],
+            }),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let total = groups_1d(count, 64);
+        let groups = [total.min(65535), total.div_ceil(65535)];
+        let size = partials_size(grid.total());
+        let partials = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("divergence_partials"),
+            size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let staging = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("divergence_staging"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("divergence_bgl"),
+            entries: &[
+                bgl_uniform_entry(0),
+                bgl_storage_entry(1, true),
+                bgl_storage_entry(2, true),
+                bgl_storage_entry(3, true),
+                bgl_storage_entry(4, false),
+            ],
+        });
+        let pipeline = compute_pipeline(
+            device,
+            "divergence",
+            include_str!("shaders/divergence.wgsl"),
+            &bgl,
+        );
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("bg_divergence"),
+            layout: &bgl,
+            entries: &[
+                bg_entry(0, params.as_entire_binding()),
+                bg_entry(1, fields[0].as_entire_binding()),
+                bg_entry(2, fields[1].as_entire_binding()),
+                bg_entry(3, fields[2].as_entire_binding()),
+                bg_entry(4, partials.as_entire_binding()),
+            ],
+        });
+        DivergencePass {
+            spec,
+            pipeline,
+            bind_group,
+            partials,
+            staging,
+            groups,
+            last: 0.0,
+            growing: 0,
+        }
+    }
+
+    /// Whether step `n` (from 0) ends with a check.
+    pub fn due(&self, n: u32) -> bool {
+        (n + 1).is_multiple_of(self.spec.every)
+    }
+
+    /// Reduce the current E fields into the partial maxima and copy them to
+    /// the staging buffer.
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder) {
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("divergence"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &self.bind_group, &[]);
+            pass.dispatch_workgroups(self.groups[0], self.groups[1], 1);
+        }
+        let size = self.partials.size();
+        encoder.copy_buffer_to_buffer(&self.partials, 0, &self.staging, 0, size);
+    }
+
+    /// Check the largest |E| component reduced by the last submitted
+    /// [`encode`](Self::encode): when the run has diverged, why, the value
+    /// and its edge, 3·cell + lane.
+    pub fn read(
+        &mut self,
+        device: &wgpu::Device,
+    ) -> Result<Option<(Cause, f32, usize)>, FdtdError> {
+        let slice = self.staging.slice(..);
+        gpu::map_read(device, &[slice])?;
+        let [bits, edge] = bytemuck::cast_slice::<u8, [u32; 2]>(&slice.get_mapped_range())
+            .iter()
+            .fold([0, 0], |peak, &p| if p[0] > peak[0] { p } else { peak });
+        self.staging.unmap();
+
+        let value = f32::from_bits(bits);
+        let cause = if !value.is_finite() {
+            Some(Cause::NonFinite)
+        } else if value as f64 > self.spec.max_field {
+            Some(Cause::OverLimit(self.spec.max_field))
+        } else {
+            let grew = self.last > 0.0 && value > GROWTH * self.last;
+            self.growing = if grew { self.growing + 1 } else { 0 };
+            (self.growing >= GROWING_CHECKS).then_some(Cause::Growing)
+        };
+        self.last = value;
+        Ok(cause.map(|cause| (cause, value, edge as usize)))
+    }
+}
+
+/// Bytes of the partial maxima of a grid of `cells`, as many again for
+/// their staging copy.
+pub fn partials_size(cells: usize) -> u64 {
+    let groups = groups_1d(cells as u32, 64) as u64;
+    groups.div_ceil(65535) * groups.min(65535) * 8
+}
+
+/// Write `fields` after step `n` to `dir` as raw snapshot files,
+/// `<Ex>_<n:06>.bin`.
+pub fn write_snapshot(
+    dir: &Path,
+    grid: &Grid,
+    n: u32,
+    fields: &[(Field, Vec<f32>)],
+) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    for (field, values) in fields {
+        let path = dir.join(format!("{}_{n:06}.bin", field.name()));
+        let mut file = BufWriter::new(fs::File::create(path)?);
+        file.write_all(&snapshots::raw_header(grid, *field, n))?;
+        file.write_all(bytemuck::cast_slice(values))?;
+        file.flush()?;
+    }
+    Ok(())
+}
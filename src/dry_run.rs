@@ -0,0 +1,500 @@
+//! Dry runs: the setup of a run without its steps (`fdtd_3d validate`).
+//!
+//! [`dry_run`] goes through what [`Simulation::new`](crate::Simulation::new)
+//! does before the first GPU buffer: [`Config::validate`], the geometry and
+//! coefficient maps, Δt against the stability limit, the buffer sizes and
+//! the adapter's limits.  Nothing is allocated on the GPU; the adapter is
+//! only queried.  The [`SetupReport`] lists the cells of each material and
+//! the sources and monitors, so a misplaced port or an unstable Δt shows
+//! up in seconds instead of after a queued job.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use tracing::warn;
+
+use crate::adapters::Backend;
+use crate::config::{Config, Scene};
+use crate::cpu;
+use crate::dft::Region;
+use crate::error::FdtdError;
+use crate::grid::Axis;
+use crate::materials::{Coefficients, Material, EPS0, MU0};
+use crate::memory::MemoryEstimate;
+use crate::monitors::Span;
+use crate::precision::Precision;
+use crate::probes::Location;
+use crate::simulation::{
+    build_coefficients, check_limits, estimate_memory, index_coefficients, request_adapter,
+    required_limits, select_dt,
+};
+use crate::stability::{Scheme, Stability};
+use crate::C0;
+
+/// Materials listed one by one; the rest are summed up.
+const LISTED_MATERIALS: usize = 12;
+
+/// What a run of a configuration would set up.
+#[derive(Clone, Debug)]
+pub struct SetupReport {
+    /// Cells per axis.
+    pub size: [u32; 3],
+    pub steps: u32,
+    /// Δt after `dt_safety` or the ADI multiple (s).
+    pub dt: f64,
+    /// Stability limit of Δt, infinite for unconditionally stable schemes.
+    pub dt_max: f64,
+    /// c·Δt/Δx.
+    pub courant: f64,
+    /// The precision run, on the adapter found.
+    pub precision: Precision,
+    /// Distinct materials, by the coefficients of a cell's Ex and Hx edges,
+    /// most cells first.  `None` is PEC.
+    pub materials: Vec<(Option<Material>, u64)>,
+    /// Edges of dispersive, lumped and sheet models, and of surface
+    /// impedances.
+    pub ade_edges: usize,
+    pub sibc_edges: usize,
+    /// The GPU buffers, coefficient maps indexed or not.
+    pub memory: MemoryEstimate,
+    /// The adapter checked against, if one was found.
+    pub adapter: Option<String>,
+    pub sources: Vec<Entry>,
+    pub monitors: Vec<Entry>,
+    /// Why the run would fail.
+    pub problems: Vec<String>,
+}
+
+/// A row of the source or monitor table.
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub kind: &'static str,
+    pub name: String,
+    /// Cell, plane or box.
+    pub at: String,
+    pub detail: String,
+}
+
+impl SetupReport {
+    /// Whether the run would start.
+    pub fn ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Set `config` up as a run of the structure would, short of the GPU
+/// buffers.  Failures a run would meet after reading its inputs — an
+/// unstable Δt, an oversized buffer, a short adapter — are collected in
+/// [`SetupReport::problems`]; an invalid configuration or an unreadable
+/// input fails at once.
+pub fn dry_run(config: &Config) -> Result<SetupReport, FdtdError> {
+    config.validate()?;
+    let mut problems = Vec::new();
+
+    let mut grid = config.grid;
+    let (mut coeffs, mut sub) = build_coefficients(config, &grid, Scene::Structure)?;
+    match select_dt(config, &mut grid, &coeffs) {
+        Ok(true) => (coeffs, sub) = build_coefficients(config, &grid, Scene::Structure)?,
+        Ok(false) => {}
+        Err(e) => problems.push(e.to_string()),
+    }
+    let dt_max = match config.scheme {
+        Scheme::Adi { .. } => f64::INFINITY,
+        scheme => Stability::analyze(&grid, config.mode, scheme, &coeffs).dt_max,
+    };
+
+    let cpu = config.backend == Some(Backend::Cpu);
+    // The CPU backend has no adapter to check
+    let adapter = if cpu {
+        None
+    } else {
+        match pollster::block_on(request_adapter(config)) {
+            Ok(adapter) => Some(adapter),
+            Err(FdtdError::NoAdapter {
+                requested: None, ..
+            }) => {
+                warn!("no GPU adapter found: the device limits are not checked");
+                None
+            }
+            Err(e) => {
+                problems.push(e.to_string());
+                None
+            }
+        }
+    };
+    let precision = match &adapter {
+        Some(adapter) => config.precision.resolve(adapter.features()),
+        None => config.precision,
+    };
+
+    let indexed = index_coefficients(config, &coeffs);
+    let memory = estimate_memory(config, &grid, precision, indexed.as_ref(), &sub);
+    if let Some(adapter) = &adapter {
+        // The device is asked for the largest buffer once it fits
+        let limits = required_limits(adapter, Some(&memory));
+        let check = memory
+            .check(&adapter.limits())
+            .and_then(|()| check_limits(&limits, adapter));
+        problems.extend(check.err().map(|e| e.to_string()));
+    }
+
+    Ok(SetupReport {
+        size: [grid.nx, grid.ny, grid.nz],
+        steps: config.steps,
+        dt: grid.dt,
+        dt_max,
+        courant: C0 * grid.dt / grid.dx,
+        precision,
+        materials: materials(&coeffs, grid.dt),
+        ade_edges: sub.ade_edges.len(),
+        sibc_edges: sub.sibc_edges.len(),
+        memory,
+        adapter: match cpu {
+            true => {
+                let threads = config.cpu_threads.unwrap_or_else(cpu::threads);
+                Some(format!("CPU, {threads} threads"))
+            }
+            false => adapter.map(|a| a.get_info().name),
+        },
+        sources: sources(config),
+        monitors: monitors(config),
+        problems,
+    })
+}
+
+/// Cells per distinct (CA, CB, CP, CQ) of the Ex and Hx edges.
+fn materials(coeffs: &Coefficients, dt: f64) -> Vec<(Option<Material>, u64)> {
+    let mut counts: HashMap<[u32; 4], (usize, u64)> = HashMap::new();
+    for id in 0..coeffs.ca.len() {
+        let key =
+            [coeffs.ca[id], coeffs.cb[id], coeffs.cp[id], coeffs.cq[id]].map(|c| c[0].to_bits());
+        counts.entry(key).or_insert((id, 0)).1 += 1;
+    }
+    let mut counts: Vec<(usize, u64)> = counts.into_values().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    counts
+        .into_iter()
+        .map(|(id, cells)| {
+            let material = coeffs.e_material(id, Axis::X, dt).map(|(eps, sigma)| {
+                let (mu, sigma_m) = coeffs.h_material(id, Axis::X, dt).unwrap_or((MU0, 0.0));
+                Material {
+                    eps_r: eps / EPS0,
+                    sigma,
+                    mu_r: mu / MU0,
+                    sigma_m,
+                }
+            });
+            (material, cells)
+        })
+        .collect()
+}
+
+fn sources(config: &Config) -> Vec<Entry> {
+    let mut rows = Vec::new();
+    let mut row = |kind, name: &str, at: String, detail: String| {
+        rows.push(Entry {
+            kind,
+            name: name.to_string(),
+            at,
+            detail,
+        })
+    };
+    if !config.source_replaced() {
+        let detail = format!("Ez, {:?}", config.waveform);
+        row("source", "", cell(config.source), detail);
+    }
+    for port in &config.ports {
+        let detail = format!("Z0 = {} Ω, up to {:.3e} Hz", port.z0, port.f_max);
+        row("port", port.name, edge(port.cell, port.axis), detail);
+    }
+    for element in &config.lumped {
+        let detail = format!("{:?}", element.kind);
+        row("lumped", "", edge(element.cell, element.axis), detail);
+    }
+    for port in &config.circuits {
+        let detail = format!("node {} of {}", port.node, port.netlist);
+        row("circuit", port.name, edge(port.cell, port.axis), detail);
+    }
+    if let Some(tdr) = config.tdr {
+        let at = format!("port {}", config.ports[tdr.port].name);
+        let detail = format!("{} V step, {:.3e} s rise", tdr.amplitude, tdr.rise);
+        row("TDR", tdr.name, at, detail);
+    }
+    if let Some(dipole) = config.purcell {
+        let detail = format!("{:?}", dipole.waveform);
+        row(
+            "dipole",
+            dipole.name,
+            edge(dipole.cell, dipole.axis),
+            detail,
+        );
+    }
+    if let Some(cell) = config.unit_cell {
+        let at = plane(cell.normal, cell.source);
+        let detail = format!("{}-polarized, {:?}", axis(cell.polarization), cell.waveform);
+        row("plane wave", cell.name, at, detail);
+    }
+    if let Some(spec) = config.shielding {
+        let at = plane(spec.normal, spec.source);
+        let detail = format!("{}-polarized, {:?}", axis(spec.polarization), spec.waveform);
+        row("plane wave", spec.name, at, detail);
+    }
+    rows
+}
+
+fn monitors(config: &Config) -> Vec<Entry> {
+    let mut rows = Vec::new();
+    let mut row = |kind, name: &str, at: String, detail: String| {
+        rows.push(Entry {
+            kind,
+            name: name.to_string(),
+            at,
+            detail,
+        })
+    };
+    let frequencies = |f: &[f64]| format!("{} frequencies", f.len());
+    for probe in &config.probes {
+        let at = match probe.at {
+            Location::Cell(c) => cell(c),
+            Location::Point(p) => format!("({:.3e}, {:.3e}, {:.3e}) m", p[0], p[1], p[2]),
+        };
+        row("probe", probe.name, at, probe.quantity.label().to_string());
+    }
+    for monitor in &config.monitors {
+        let at = match monitor.span {
+            Span::Line { axis: a, through } => {
+                format!("{}-line through {}", axis(a), cell(through))
+            }
+            Span::Plane { normal, index } => plane(normal, index),
+        };
+        let detail = format!("{} every {} steps", monitor.field.name(), monitor.every);
+        row("monitor", monitor.name, at, detail);
+    }
+    for monitor in &config.dft_monitors {
+        let at = match monitor.region {
+            Region::Point(c) => cell(c),
+            Region::Plane { normal, index } => plane(normal, index),
+            Region::Box { lo, hi } => cuboid(lo, hi),
+        };
+        let fields: Vec<&str> = monitor.fields.iter().map(|f| f.name()).collect();
+        let detail = format!("{}, {}", fields.join(" "), frequencies(monitor.frequencies));
+        row("DFT", monitor.name, at, detail);
+    }
+    for monitor in &config.flux_monitors {
+        let at = rectangle(monitor.normal, monitor.index, monitor.u, monitor.v);
+        row("flux", monitor.name, at, frequencies(monitor.frequencies));
+    }
+    for flux_box in &config.flux_boxes {
+        let at = cuboid(flux_box.lo, flux_box.hi);
+        row(
+            "flux box",
+            flux_box.name,
+            at,
+            frequencies(flux_box.frequencies),
+        );
+    }
+    for monitor in &config.mode_monitors {
+        let at = rectangle(monitor.normal, monitor.index, monitor.u, monitor.v);
+        let detail = format!(
+            "{} modes, {}",
+            monitor.modes.len(),
+            frequencies(monitor.frequencies)
+        );
+        row("modes", monitor.name, at, detail);
+    }
+    for monitor in &config.reflectance {
+        let at = format!(
+            "{} and {}",
+            rectangle(monitor.normal, monitor.front, monitor.u, monitor.v),
+            plane(monitor.normal, monitor.back)
+        );
+        row("R/T", monitor.name, at, frequencies(monitor.frequencies));
+    }
+    for monitor in &config.kspace_monitors {
+        let at = plane(monitor.normal, monitor.index);
+        let detail = format!("{} every {} steps", monitor.field.name(), monitor.every);
+        row("k-space", monitor.name, at, detail);
+    }
+    for rcs in &config.rcs {
+        row(
+            "RCS",
+            rcs.name,
+            cuboid(rcs.lo, rcs.hi),
+            frequencies(rcs.frequencies),
+        );
+    }
+    for cuts in &config.patterns {
+        row(
+            "pattern",
+            cuts.name,
+            cuboid(cuts.lo, cuts.hi),
+            frequencies(cuts.frequencies),
+        );
+    }
+    for sar in &config.sar {
+        row(
+            "SAR",
+            sar.name,
+            cuboid(sar.lo, sar.hi),
+            frequencies(sar.frequencies),
+        );
+    }
+    if let Some(spec) = &config.snapshots {
+        let fields: Vec<&str> = spec.fields.iter().map(|f| f.name()).collect();
+        let detail = format!("{} every {} steps", fields.join(" "), spec.every);
+        row("snapshots", spec.dir, "whole grid".to_string(), detail);
+    }
+    if let Some(spec) = &config.slice_images {
+        let detail = format!("{} every {} steps", spec.field.name(), spec.every);
+        row("images", spec.dir, plane(spec.normal, spec.index), detail);
+    }
+    rows
+}
+
+fn axis(axis: Axis) -> char {
+    match axis {
+        Axis::X => 'x',
+        Axis::Y => 'y',
+        Axis::Z => 'z',
+    }
+}
+
+fn cell([i, j, k]: [u32; 3]) -> String {
+    format!("[{i}, {j}, {k}]")
+}
+
+fn edge(at: [u32; 3], along: Axis) -> String {
+    format!("{} along {}", cell(at), axis(along))
+}
+
+fn plane(normal: Axis, index: u32) -> String {
+    format!("{} = {index}", axis(normal))
+}
+
+fn rectangle(normal: Axis, index: u32, u: (u32, u32), v: (u32, u32)) -> String {
+    format!(
+        "{}, {}..{} × {}..{}",
+        plane(normal, index),
+        u.0,
+        u.1,
+        v.0,
+        v.1
+    )
+}
+
+fn cuboid(lo: [u32; 3], hi: [u32; 3]) -> String {
+    format!("{} – {}", cell(lo), cell(hi))
+}
+
+/// Description of a material recovered from its coefficients at `dt`.  The
+/// f32 coefficients of a good conductor hardly depend on ε, so its ε_r is
+/// shown only when it stands out of the rounding.
+fn material(material: &Option<Material>, dt: f64) -> String {
+    let Some(m) = material else {
+        return "PEC".to_string();
+    };
+    let loss = m.sigma * dt / (2.0 * m.eps_r * EPS0);
+    let mut parts = Vec::new();
+    if (m.eps_r - 1.0).abs() > 1e-5 * loss.max(1.0) {
+        parts.push(format!("ε_r {:.4}", m.eps_r));
+    }
+    if m.sigma > 0.0 {
+        parts.push(format!("σ {:.3e} S/m", m.sigma));
+    }
+    if (m.mu_r - 1.0).abs() > 1e-5 {
+        parts.push(format!("μ_r {:.4}", m.mu_r));
+    }
+    if m.sigma_m > 0.0 {
+        parts.push(format!("σ_m {:.3e} Ω/m", m.sigma_m));
+    }
+    if parts.is_empty() {
+        "vacuum".to_string()
+    } else {
+        parts.join(", ")
+    }
+}
+
+impl fmt::Display for SetupReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [nx, ny, nz] = self.size;
+        let cells = nx as u64 * ny as u64 * nz as u64;
+        writeln!(
+            f,
+            "grid       {nx}×{ny}×{nz} = {cells} cells, {} steps",
+            self.steps
+        )?;
+        if self.dt_max.is_finite() {
+            writeln!(
+                f,
+                "time step  Δt = {:.4e} s (Courant {:.4}), {:.1} % of the stability limit",
+                self.dt,
+                self.courant,
+                100.0 * self.dt / self.dt_max
+            )?;
+        } else {
+            writeln!(
+                f,
+                "time step  Δt = {:.4e} s (Courant {:.4}), unconditionally stable",
+                self.dt, self.courant
+            )?;
+        }
+        writeln!(
+            f,
+            "memory     {:.1} MiB on the GPU, {:?}",
+            self.memory.total() as f64 / (1 << 20) as f64,
+            self.precision
+        )?;
+        match &self.adapter {
+            Some(name) => writeln!(f, "adapter    {name}")?,
+            None => writeln!(f, "adapter    none found, limits not checked")?,
+        }
+        writeln!(
+            f,
+            "sub-cell   {} ADE edges, {} SIBC edges",
+            self.ade_edges, self.sibc_edges
+        )?;
+
+        writeln!(f, "\nmaterials{:>33}", "cells")?;
+        for (m, n) in self.materials.iter().take(LISTED_MATERIALS) {
+            let share = 100.0 * *n as f64 / cells as f64;
+            writeln!(f, "  {:<30} {n:>10} {share:>6.2} %", material(m, self.dt))?;
+        }
+        if self.materials.len() > LISTED_MATERIALS {
+            let rest = &self.materials[LISTED_MATERIALS..];
+            let n: u64 = rest.iter().map(|(_, n)| n).sum();
+            let share = 100.0 * n as f64 / cells as f64;
+            let label = format!("{} others", rest.len());
+            writeln!(f, "  {label:<30} {n:>10} {share:>6.2} %")?;
+        }
+
+        writeln!(f, "\n{}", self.memory)?;
+
+        for (title, entries) in [("sources", &self.sources), ("monitors", &self.monitors)] {
+            writeln!(f, "\n{title}")?;
+            if entries.is_empty() {
+                writeln!(f, "  none")?;
+            }
+            for e in entries {
+                writeln!(
+                    f,
+                    "  {:<10} {:<12} {:<28} {}",
+                    e.kind, e.name, e.at, e.detail
+                )?;
+            }
+        }
+
+        if self.ok() {
+            write!(
+                f,
+                "\nready to run ({:.4e} s simulated)",
+                self.steps as f64 * self.dt
+            )
+        } else {
+            write!(f, "\nthe run would fail:")?;
+            for problem in &self.problems {
+                write!(f, "\n  {problem}")?;
+            }
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,200 @@
+//! Total electromagnetic energy.
+//!
+//! U = Σ (ε·|E|² + μ·|H|²)·dV / 2 over every Yee sample, summed on the GPU
+//! by a workgroup reduction and finished on the host in f64.  Each sample
+//! is weighted by its own edge's ε or μ and volume (primary width along
+//! the component, dual widths across it for E, the other way round for H);
+//! PEC edges weigh nothing.  The weights are taken from the coefficients at
+//! start-up, so energy stored in dispersive auxiliary currents and changes
+//! of modulated materials are not included.  E and H are half a step apart,
+//! so U oscillates slightly about the true energy even in a lossless
+//! cavity; a steady decay gives the loss rate and growth an instability.
+//!
+//! The partial sums of each reduction go to their own slot of the staging
+//! buffer, so that the reductions of a submission of several steps are
+//! read back together after it.
+
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+
+use crate::error::FdtdError;
+use crate::gpu::{
+    self, bg_entry, bgl_storage_entry, bgl_uniform_entry, compute_pipeline, groups_1d,
+};
+use crate::grid::{Axis, Grid};
+use crate::materials::Coefficients;
+
+/// Reduction parameters (must match WGSL `EnergyParams`).
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct EnergyParams {
+    count: u32,
+    _pad: [u32; 3],
+}
+
+/// GPU reduction of the total field energy.
+pub struct EnergyPass {
+    pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    partials: wgpu::Buffer,
+    staging: wgpu::Buffer,
+    groups: [u32; 2],
+    slots: usize,
+    /// Steps of the reductions in the filled slots.
+    pending: Vec<u32>,
+}
+
+impl EnergyPass {
+    /// `fields` are the six field buffers in (Ex, Ey, Ez, Hx, Hy, Hz) order;
+    /// `slots` reductions can be encoded before a [`read`](Self::read).
+    pub fn new(
+        device: &wgpu::Device,
+        grid: &Grid,
+        coeffs: &Coefficients,
+        fields: [&wgpu::Buffer; 6],
+        slots: usize,
+    ) -> Self {
+        let mut weights = vec![[0.0_f32; 4]; 2 * grid.total()];
+        for k in 0..grid.nz {
+            for j in 0..grid.ny {
+                for i in 0..grid.nx {
+                    let id = grid.idx(i, j, k);
+                    for axis in [Axis::X, Axis::Y, Axis::Z] {
+                        // Primary width along `axis` for E, dual across it
+                        let (mut primary, mut dual) = (1.0, 1.0);
+                        for (a, index) in [(Axis::X, i), (Axis::Y, j), (Axis::Z, k)] {
+                            if a == axis {
+                                primary *= grid.width(a, index);
+                                dual *= grid.dual_width(a, index);
+                            } else {
+                                primary *= grid.dual_width(a, index);
+                                dual *= grid.width(a, index);
+                            }
+                        }
+                        let l = axis.lane();
+                        if let Some((eps, _)) = coeffs.e_material(id, axis, grid.dt) {
+                            weights[2 * id][l] = (0.5 * eps * primary) as f32;
+                        }
+                        if let Some((mu, _)) = coeffs.h_material(id, axis, grid.dt) {
+                            weights[2 * id + 1][l] = (0.5 * mu * dual) as f32;
+                        }
+                    }
+                }
+            }
+        }
+        let weights = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("energy_weights"),
+            contents: bytemuck::cast_slice(&weights),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let count = grid.total() as u32;
+        let params = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("energy_params"),
+            contents: bytemuck::bytes_of(&EnergyParams {
+                count,
+                _pad: [0; 3],
+            }),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let total = groups_1d(count, 64);
+        let groups = [total.min(65535), total.div_ceil(65535)];
+        let size = (groups[0] * groups[1]) as u64 * 4;
+        let partials = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("energy_partials"),
+            size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let staging = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("energy_staging"),
+            size: size * slots as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("energy_bgl"),
+            entries: &[
+                bgl_uniform_entry(0),
+                bgl_storage_entry(1, true),
+                bgl_storage_entry(2, true),
+                bgl_storage_entry(3, true),
+                bgl_storage_entry(4, true),
+                bgl_storage_entry(5, true),
+                bgl_storage_entry(6, true),
+                bgl_storage_entry(7, true),
+                bgl_storage_entry(8, false),
+            ],
+        });
+        let pipeline =
+            compute_pipeline(device, "energy", include_str!("shaders/energy.wgsl"), &bgl);
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("bg_energy"),
+            layout: &bgl,
+            entries: &[
+                bg_entry(0, params.as_entire_binding()),
+                bg_entry(1, fields[0].as_entire_binding()),
+                bg_entry(2, fields[1].as_entire_binding()),
+                bg_entry(3, fields[2].as_entire_binding()),
+                bg_entry(4, fields[3].as_entire_binding()),
+                bg_entry(5, fields[4].as_entire_binding()),
+                bg_entry(6, fields[5].as_entire_binding()),
+                bg_entry(7, weights.as_entire_binding()),
+                bg_entry(8, partials.as_entire_binding()),
+            ],
+        });
+        EnergyPass {
+            pipeline,
+            bind_group,
+            partials,
+            staging,
+            groups,
+            slots,
+            pending: Vec::with_capacity(slots),
+        }
+    }
+
+    /// Reduce the fields after step `n` into the partial sums and copy them
+    /// to the next free slot.
+    pub fn encode(&mut self, encoder: &mut wgpu::CommandEncoder, n: u32) {
+        assert!(!self.full(), "energy slots are read before they fill");
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("energy"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &self.bind_group, &[]);
+            pass.dispatch_workgroups(self.groups[0], self.groups[1], 1);
+        }
+        let size = self.partials.size();
+        let offset = self.pending.len() as u64 * size;
+        encoder.copy_buffer_to_buffer(&self.partials, 0, &self.staging, offset, size);
+        self.pending.push(n);
+    }
+
+    /// Whether every slot holds a reduction not yet read.
+    pub fn full(&self) -> bool {
+        self.pending.len() == self.slots
+    }
+
+    /// Steps and total energies (J) of the reductions encoded since the
+    /// last read, once their submission is done.
+    pub fn read(&mut self, device: &wgpu::Device) -> Result<Vec<(u32, f64)>, FdtdError> {
+        if self.pending.is_empty() {
+            return Ok(Vec::new());
+        }
+        let size = self.partials.size();
+        let slice = self.staging.slice(..size * self.pending.len() as u64);
+        gpu::map_read(device, &[slice])?;
+        let totals = {
+            let mapped = slice.get_mapped_range();
+            let partials = bytemuck::cast_slice::<u8, f32>(&mapped);
+            let rows = partials.chunks(size as usize / 4);
+            let totals = rows.map(|row| row.iter().map(|&x| x as f64).sum::<f64>());
+            self.pending.drain(..).zip(totals).collect()
+        };
+        self.staging.unmap();
+        Ok(totals)
+    }
+}
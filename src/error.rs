@@ -0,0 +1,159 @@
+//! Failures of a run an embedding application can act on.
+//!
+//! Setting up a [`Simulation`](crate::Simulation), stepping it, reading
+//! fields back and writing its results return [`FdtdError`] instead of
+//! aborting, so a caller can, say, retry on a smaller grid after
+//! [`FdtdError::BufferTooLarge`] or pick another adapter after
+//! [`FdtdError::NoAdapter`].  Broken invariants of the solver itself still
+//! panic.
+
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::config::ConfigError;
+use crate::divergence::Divergence;
+use crate::progress::format_duration;
+
+/// Why a run could not go on.
+#[derive(Debug)]
+pub enum FdtdError {
+    /// No GPU adapter, or none whose name contains `requested`.
+    NoAdapter {
+        requested: Option<String>,
+        found: Vec<String>,
+    },
+    /// The adapter grants less of `limit` than the solver asks for.
+    DeviceLimits {
+        limit: &'static str,
+        requested: u64,
+        allowed: u64,
+    },
+    /// The device could not be opened.
+    Device(String),
+    /// The device rejected a call (a validation or out-of-memory error)
+    /// `during` a stage of the run.
+    Gpu { during: String, message: String },
+    /// The device was lost, to a driver reset or a hung GPU;
+    /// [`crate::run_scene`] reconnects (see
+    /// [`Config::checkpoint_every`](crate::Config::checkpoint_every)).
+    DeviceLost(String),
+    /// The configuration fails [`Config::validate`](crate::Config::validate).
+    InvalidConfig(ConfigError),
+    /// A GPU buffer of `size` bytes over the device's `limit`; `fits` is
+    /// the largest grid of the same shape within it, when the buffer grows
+    /// with the grid.
+    BufferTooLarge {
+        buffer: &'static str,
+        size: u64,
+        limit: u64,
+        fits: Option<[u32; 3]>,
+    },
+    /// A copy back from the GPU failed.
+    MapFailed(String),
+    /// `found` values for a field component of `expected`, one per cell (a
+    /// single one while another precision runs alone).
+    FieldLength { expected: usize, found: usize },
+    /// A cell or plane, as described, past the last of the grid.
+    OutsideGrid(String),
+    /// Writing an output or reading an input failed.
+    Io(io::Error),
+    /// Stopped by [`crate::interrupt`] after `steps` steps, the state saved
+    /// to `checkpoint`.
+    Interrupted { steps: u32, checkpoint: PathBuf },
+    /// The wall time estimated after the calibration steps is over the
+    /// configured budget.
+    OverBudget {
+        estimate: Duration,
+        budget: Duration,
+    },
+    /// The fields blew up (see [`crate::divergence`]).
+    Diverged(Box<Divergence>),
+}
+
+impl fmt::Display for FdtdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FdtdError::NoAdapter {
+                requested: None, ..
+            } => write!(f, "no suitable GPU adapter found"),
+            FdtdError::NoAdapter {
+                requested: Some(name),
+                found,
+            } => write!(f, "no GPU adapter matches {name:?} (found {found:?})"),
+            FdtdError::DeviceLimits {
+                limit,
+                requested,
+                allowed,
+            } => write!(
+                f,
+                "the adapter allows {limit} = {allowed}, the solver needs {requested}"
+            ),
+            FdtdError::Device(message) => write!(f, "GPU device: {message}"),
+            FdtdError::Gpu { during, message } => write!(f, "GPU error while {during}: {message}"),
+            FdtdError::DeviceLost(reason) => write!(f, "the GPU device was lost: {reason}"),
+            FdtdError::InvalidConfig(e) => e.fmt(f),
+            FdtdError::BufferTooLarge {
+                buffer,
+                size,
+                limit,
+                fits,
+            } => {
+                write!(
+                    f,
+                    "a buffer of {buffer} takes {} MiB, over the device's {} MiB",
+                    size >> 20,
+                    limit >> 20
+                )?;
+                match fits {
+                    Some([nx, ny, nz]) => write!(
+                        f,
+                        ": the largest grid of this shape that fits is {nx}×{ny}×{nz}"
+                    ),
+                    None => write!(f, ": it does not grow with the grid, record less in it"),
+                }
+            }
+            FdtdError::MapFailed(message) => write!(f, "GPU readback failed: {message}"),
+            FdtdError::FieldLength { expected, found } => {
+                write!(f, "{found} values for a field of {expected}")
+            }
+            FdtdError::OutsideGrid(what) => write!(f, "{what} is outside the grid"),
+            FdtdError::Io(e) => e.fmt(f),
+            FdtdError::Interrupted { steps, checkpoint } => write!(
+                f,
+                "interrupted after {steps} steps, checkpoint written to {}",
+                checkpoint.display()
+            ),
+            FdtdError::OverBudget { estimate, budget } => write!(
+                f,
+                "the run would take about {}, over its budget of {}",
+                format_duration(*estimate),
+                format_duration(*budget)
+            ),
+            FdtdError::Diverged(d) => d.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for FdtdError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FdtdError::InvalidConfig(e) => Some(e),
+            FdtdError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ConfigError> for FdtdError {
+    fn from(e: ConfigError) -> Self {
+        FdtdError::InvalidConfig(e)
+    }
+}
+
+impl From<io::Error> for FdtdError {
+    fn from(e: io::Error) -> Self {
+        FdtdError::Io(e)
+    }
+}
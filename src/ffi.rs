@@ -0,0 +1,474 @@
+//! C interface for embedding the solver in C, C++ or Fortran codes.
+//!
+//! A run lives behind an opaque `FdtdSim` handle: create it from a grid or
+//! a scene file, configure it, run steps, read components back and destroy
+//! it.  Every call returns an [`FdtdStatus`]; after a failure
+//! `fdtd_last_error` gives the message (valid until the next call on the
+//! same thread).  Panics of the solver are caught and reported as
+//! [`FdtdStatus::Panic`] instead of unwinding into the caller.
+//!
+//! ```c
+//! FdtdSim *sim;
+//! if (fdtd_create(64, 64, 64, 1e-3, 1e-12, &sim) != FDTD_STATUS_OK) {
+//!     fprintf(stderr, "%s\n", fdtd_last_error());
+//! }
+//! fdtd_add_sphere(sim, (double[3]){0.032, 0.032, 0.032}, 0.01, 4.0, 0.0);
+//! fdtd_run(sim, 300);
+//! float *ez = malloc(64 * 64 * 64 * sizeof(float));
+//! fdtd_read_field(sim, FDTD_FIELD_EZ, ez, 64 * 64 * 64);
+//! fdtd_destroy(sim);
+//! ```
+//!
+//! The header is `include/fdtd_3d.h`, generated from this file by
+//! `cbindgen --config cbindgen.toml --output include/fdtd_3d.h`.  The
+//! configuration is validated and the device opened by the first
+//! `fdtd_run`, whose step count it takes; afterwards the configuring calls
+//! fail with [`FdtdStatus::Running`].  Probe names are kept, like those of
+//! a scene file, until the process exits: one copy of each distinct name.
+
+// Pointers come from the C caller, who keeps them valid; each one is checked
+// for null before use.
+#![allow(clippy::not_unsafe_ptr_arg_deref)]
+
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::ptr;
+use std::sync::{Mutex, PoisonError};
+
+use crate::backend::ComputeBackend;
+use crate::builder::SimulationBuilder;
+use crate::config::Boundary;
+use crate::error::FdtdError;
+use crate::geometry::Shape;
+use crate::grid::{Axis, Field, Grid};
+use crate::materials::Material;
+use crate::probes::{Location, Probe, Quantity};
+use crate::scene_file;
+use crate::simulation::Simulation;
+use crate::sources::Waveform;
+
+/// Result of every call.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FdtdStatus {
+    Ok = 0,
+    /// A handle, array or string argument was null.
+    NullPointer = 1,
+    /// An index, size or string was out of range or not UTF-8.
+    InvalidArgument = 2,
+    /// The configuration or scene file was rejected.
+    InvalidConfiguration = 3,
+    /// The run has started and cannot be configured any more.
+    Running = 4,
+    /// The solver panicked (a broken invariant).
+    Panic = 5,
+    /// No GPU adapter, or a device short of the solver's limits.
+    NoDevice = 6,
+    /// The grid's buffers exceed what the device allows.
+    TooLarge = 7,
+    /// A copy back from the GPU failed.
+    ReadbackFailed = 8,
+    /// Writing an output file failed.
+    Io = 9,
+    /// The run was interrupted (see the `interrupt` module).
+    Interrupted = 10,
+    /// The estimated wall time is over the configured budget.
+    OverBudget = 11,
+    /// The fields diverged (see the `divergence` module).
+    Diverged = 12,
+    /// The device rejected a call (a validation or out-of-memory error).
+    GpuError = 13,
+    /// The device was lost.
+    DeviceLost = 14,
+}
+
+/// Field components as `fdtd_read_field` takes them, passed as their
+/// `uint32_t` value so that any other number is rejected instead of read
+/// as an enum.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FdtdField {
+    Ex = 0,
+    Ey = 1,
+    Ez = 2,
+    Hx = 3,
+    Hy = 4,
+    Hz = 5,
+}
+
+/// Opaque run handle.
+pub struct FdtdSim {
+    /// The configuration until the first step.
+    builder: Option<SimulationBuilder>,
+    simulation: Option<Simulation>,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+fn fail(status: FdtdStatus, message: impl Into<String>) -> FdtdStatus {
+    let message = message.into().replace('\0', " ");
+    LAST_ERROR.with(|e| *e.borrow_mut() = CString::new(message).unwrap_or_default());
+    status
+}
+
+/// The status of a failed set-up or run, with its message.
+fn fail_with(e: FdtdError) -> FdtdStatus {
+    let status = match e {
+        FdtdError::NoAdapter { .. } | FdtdError::DeviceLimits { .. } | FdtdError::Device(_) => {
+            FdtdStatus::NoDevice
+        }
+        FdtdError::InvalidConfig(_) => FdtdStatus::InvalidConfiguration,
+        FdtdError::BufferTooLarge { .. } => FdtdStatus::TooLarge,
+        FdtdError::MapFailed(_) => FdtdStatus::ReadbackFailed,
+        FdtdError::FieldLength { .. } | FdtdError::OutsideGrid(_) => FdtdStatus::InvalidArgument,
+        FdtdError::Io(_) => FdtdStatus::Io,
+        FdtdError::Interrupted { .. } => FdtdStatus::Interrupted,
+        FdtdError::OverBudget { .. } => FdtdStatus::OverBudget,
+        FdtdError::Diverged(_) => FdtdStatus::Diverged,
+        FdtdError::Gpu { .. } => FdtdStatus::GpuError,
+        FdtdError::DeviceLost(_) => FdtdStatus::DeviceLost,
+    };
+    fail(status, e.to_string())
+}
+
+/// Run `f`, turning a panic into [`FdtdStatus::Panic`].
+fn guard(f: impl FnOnce() -> FdtdStatus) -> FdtdStatus {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(status) => status,
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<String>()
+                .map(String::as_str)
+                .or_else(|| payload.downcast_ref::<&str>().copied())
+                .unwrap_or("panic");
+            fail(FdtdStatus::Panic, message)
+        }
+    }
+}
+
+/// The builder of a handle still being configured.
+fn configure(
+    sim: *mut FdtdSim,
+    f: impl FnOnce(SimulationBuilder) -> SimulationBuilder,
+) -> FdtdStatus {
+    guard(|| {
+        let Some(sim) = (unsafe { sim.as_mut() }) else {
+            return fail(FdtdStatus::NullPointer, "null handle");
+        };
+        match sim.builder.take() {
+            Some(builder) => {
+                sim.builder = Some(f(builder));
+                FdtdStatus::Ok
+            }
+            None => fail(FdtdStatus::Running, "the run has started"),
+        }
+    })
+}
+
+/// A C string, borrowed for the call.
+fn string<'a>(s: *const c_char) -> Result<&'a str, FdtdStatus> {
+    if s.is_null() {
+        return Err(fail(FdtdStatus::NullPointer, "null string"));
+    }
+    unsafe { CStr::from_ptr(s) }
+        .to_str()
+        .map_err(|_| fail(FdtdStatus::InvalidArgument, "string is not UTF-8"))
+}
+
+/// `name` as the configuration keeps names, for the life of the process.
+/// Each distinct name is copied once, so a caller creating and destroying
+/// handles with the same probes does not allocate more each time.
+fn intern(name: &str) -> &'static str {
+    static NAMES: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+    let mut names = NAMES.lock().unwrap_or_else(PoisonError::into_inner);
+    match names.get(name) {
+        Some(&name) => name,
+        None => {
+            let name = Box::leak(name.to_string().into_boxed_str());
+            names.insert(name);
+            name
+        }
+    }
+}
+
+fn point(p: *const f64) -> Result<[f64; 3], FdtdStatus> {
+    if p.is_null() {
+        return Err(fail(FdtdStatus::NullPointer, "null point"));
+    }
+    Ok(unsafe { *p.cast::<[f64; 3]>() })
+}
+
+fn axis(axis: u32) -> Result<Axis, FdtdStatus> {
+    match axis {
+        0 => Ok(Axis::X),
+        1 => Ok(Axis::Y),
+        2 => Ok(Axis::Z),
+        _ => Err(fail(FdtdStatus::InvalidArgument, format!("no axis {axis}"))),
+    }
+}
+
+/// The component of an [`FdtdField`] value.
+fn field(field: u32) -> Result<Field, FdtdStatus> {
+    match Field::ALL.get(field as usize) {
+        Some(&field) => Ok(field),
+        None => Err(fail(
+            FdtdStatus::InvalidArgument,
+            format!("no field {field}"),
+        )),
+    }
+}
+
+fn into_handle(builder: SimulationBuilder, out: *mut *mut FdtdSim) -> FdtdStatus {
+    let sim = Box::new(FdtdSim {
+        builder: Some(builder),
+        simulation: None,
+    });
+    unsafe { *out = Box::into_raw(sim) };
+    FdtdStatus::Ok
+}
+
+/// The message of the last failed call on this thread, or "".
+#[no_mangle]
+pub extern "C" fn fdtd_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ptr())
+}
+
+/// A vacuum run on `nx`×`ny`×`nz` cubic cells of `spacing` (m) with time
+/// step `dt` (s), pulsed at the centre.
+#[no_mangle]
+pub extern "C" fn fdtd_create(
+    nx: u32,
+    ny: u32,
+    nz: u32,
+    spacing: f64,
+    dt: f64,
+    out: *mut *mut FdtdSim,
+) -> FdtdStatus {
+    guard(|| {
+        if out.is_null() {
+            return fail(FdtdStatus::NullPointer, "null output handle");
+        }
+        let grid = Grid::uniform([nx, ny, nz], spacing, dt);
+        into_handle(SimulationBuilder::new().grid(grid), out)
+    })
+}
+
+/// A run described by a TOML or JSON scene file.
+#[no_mangle]
+pub extern "C" fn fdtd_create_from_scene(
+    path: *const c_char,
+    out: *mut *mut FdtdSim,
+) -> FdtdStatus {
+    guard(|| {
+        if out.is_null() {
+            return fail(FdtdStatus::NullPointer, "null output handle");
+        }
+        let path = match string(path) {
+            Ok(path) => path,
+            Err(status) => return status,
+        };
+        match scene_file::load(Path::new(path), &[]) {
+            Ok(config) => {
+                let builder = SimulationBuilder::new()
+                    .grid(config.grid)
+                    .source(config.source, config.waveform)
+                    .configure(|c| *c = config);
+                into_handle(builder, out)
+            }
+            Err(e) => fail(FdtdStatus::InvalidConfiguration, e.to_string()),
+        }
+    })
+}
+
+/// Replace the source by a Gaussian pulse of `width` and `delay` steps at
+/// cell (i, j, k).
+#[no_mangle]
+pub extern "C" fn fdtd_set_source(
+    sim: *mut FdtdSim,
+    i: u32,
+    j: u32,
+    k: u32,
+    width: f64,
+    delay: f64,
+) -> FdtdStatus {
+    configure(sim, |b| {
+        b.source([i, j, k], Waveform::Gaussian { width, delay })
+    })
+}
+
+/// Make `axis` (0 = x, 1 = y, 2 = z) periodic.
+#[no_mangle]
+pub extern "C" fn fdtd_set_periodic(sim: *mut FdtdSim, axis: u32) -> FdtdStatus {
+    match self::axis(axis) {
+        Ok(axis) => configure(sim, |b| b.boundary(axis, Boundary::Periodic)),
+        Err(status) => status,
+    }
+}
+
+fn add_shape(sim: *mut FdtdSim, shape: Shape, eps_r: f64, sigma: f64) -> FdtdStatus {
+    let material = Material {
+        eps_r,
+        sigma,
+        ..Material::VACUUM
+    };
+    configure(sim, |b| b.material(shape, material))
+}
+
+/// Paint the box between corners `min` and `max` (m) with ε_r and σ (S/m).
+#[no_mangle]
+pub extern "C" fn fdtd_add_box(
+    sim: *mut FdtdSim,
+    min: *const f64,
+    max: *const f64,
+    eps_r: f64,
+    sigma: f64,
+) -> FdtdStatus {
+    match (point(min), point(max)) {
+        (Ok(min), Ok(max)) => add_shape(sim, Shape::Box { min, max }, eps_r, sigma),
+        (Err(status), _) | (_, Err(status)) => status,
+    }
+}
+
+/// Paint the sphere around `center` (m) with ε_r and σ (S/m).
+#[no_mangle]
+pub extern "C" fn fdtd_add_sphere(
+    sim: *mut FdtdSim,
+    center: *const f64,
+    radius: f64,
+    eps_r: f64,
+    sigma: f64,
+) -> FdtdStatus {
+    match point(center) {
+        Ok(center) => add_shape(sim, Shape::Sphere { center, radius }, eps_r, sigma),
+        Err(status) => status,
+    }
+}
+
+/// Record Ez at cell (i, j, k) under `name` in the probe outputs.
+#[no_mangle]
+pub extern "C" fn fdtd_add_probe(
+    sim: *mut FdtdSim,
+    name: *const c_char,
+    i: u32,
+    j: u32,
+    k: u32,
+) -> FdtdStatus {
+    let name = match string(name) {
+        Ok(name) => intern(name),
+        Err(status) => return status,
+    };
+    let probe = Probe {
+        name,
+        at: Location::Cell([i, j, k]),
+        quantity: Quantity::Component(Field::E(Axis::Z)),
+    };
+    configure(sim, |b| b.probe(probe))
+}
+
+/// Take `steps` steps, setting the run up on the first call.
+#[no_mangle]
+pub extern "C" fn fdtd_run(sim: *mut FdtdSim, steps: u32) -> FdtdStatus {
+    guard(|| {
+        let Some(sim) = (unsafe { sim.as_mut() }) else {
+            return fail(FdtdStatus::NullPointer, "null handle");
+        };
+        // The configuration stays in place until the set-up succeeds
+        if let Some(builder) = &sim.builder {
+            match builder.clone().steps(steps.max(1)).build() {
+                Ok(simulation) => {
+                    sim.builder = None;
+                    sim.simulation = Some(simulation);
+                }
+                Err(e) => return fail_with(e),
+            }
+        }
+        if let Some(simulation) = &mut sim.simulation {
+            if let Err(e) = simulation.run(steps) {
+                return fail_with(e);
+            }
+        }
+        FdtdStatus::Ok
+    })
+}
+
+/// Steps taken so far, or 0 for a null handle.
+#[no_mangle]
+pub extern "C" fn fdtd_steps_taken(sim: *const FdtdSim) -> u32 {
+    let sim = unsafe { sim.as_ref() };
+    sim.and_then(|s| s.simulation.as_ref())
+        .map_or(0, Simulation::steps_taken)
+}
+
+/// Copy `field`, an [`FdtdField`], into `out`, which holds `len` =
+/// nx·ny·nz values, x fastest.
+#[no_mangle]
+pub extern "C" fn fdtd_read_field(
+    sim: *const FdtdSim,
+    field: u32,
+    out: *mut f32,
+    len: usize,
+) -> FdtdStatus {
+    guard(|| {
+        let Some(sim) = (unsafe { sim.as_ref() }) else {
+            return fail(FdtdStatus::NullPointer, "null handle");
+        };
+        if out.is_null() {
+            return fail(FdtdStatus::NullPointer, "null output array");
+        }
+        let field = match self::field(field) {
+            Ok(field) => field,
+            Err(status) => return status,
+        };
+        let Some(simulation) = &sim.simulation else {
+            return fail(FdtdStatus::InvalidArgument, "no step taken yet");
+        };
+        let total = simulation.grid().total();
+        if len != total {
+            return fail(
+                FdtdStatus::InvalidArgument,
+                format!("the field has {total} values, not {len}"),
+            );
+        }
+        let values = match simulation.field(field) {
+            Ok(values) => values,
+            Err(e) => return fail_with(e),
+        };
+        unsafe { ptr::copy_nonoverlapping(values.as_ptr(), out, total) };
+        FdtdStatus::Ok
+    })
+}
+
+/// Free a handle; null is ignored.
+#[no_mangle]
+pub extern "C" fn fdtd_destroy(sim: *mut FdtdSim) {
+    if !sim.is_null() {
+        drop(unsafe { Box::from_raw(sim) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_fields_are_rejected_and_names_copied_once() {
+        let mut sim = ptr::null_mut();
+        assert_eq!(fdtd_create(8, 8, 8, 1e-3, 1e-12, &mut sim), FdtdStatus::Ok);
+        let mut out = [0.0_f32; 512];
+        let status = fdtd_read_field(sim, 6, out.as_mut_ptr(), out.len());
+        assert_eq!(status, FdtdStatus::InvalidArgument);
+        let message = unsafe { CStr::from_ptr(fdtd_last_error()) };
+        assert_eq!(message.to_str().unwrap(), "no field 6");
+        fdtd_destroy(sim);
+
+        let name = CString::new("port").unwrap();
+        let once = intern(string(name.as_ptr()).unwrap());
+        let again = intern(&String::from("port"));
+        assert!(ptr::eq(once, again));
+    }
+}
@@ -0,0 +1,554 @@
+//! Poynting-flux monitors through axis-aligned rectangles.
+//!
+//! A rectangle lies on the E plane `index` normal to `normal` and spans the
+//! cells `u.0..u.1` and `v.0..v.1` of the tangential axes (u, v) of
+//! [`Axis::tangential`].  The flux counts positive along +normal:
+//!
+//!   P = ∫ (E_u·H_v − E_v·H_u) dA
+//!
+//! with H averaged over the planes half a cell either side.  In the time
+//! domain P is summed on the GPU every step (E at (n + 1)·Δt, H half a step
+//! earlier, as stored) and the per-workgroup partial sums are read back in
+//! batches.  With frequencies set, the rectangle's tangential fields also
+//! go to a running-DFT monitor of the same name and the spectrum
+//! P(f) = ½·Re ∫ (E_u·H_v* − E_v·H_u*) dA comes from the time-aligned
+//! transforms.
+//!
+//! A [`FluxBox`] is the closed surface of six such rectangles around a box
+//! of cells, whose outward-signed sum is the net power leaving the box —
+//! the radiated power of a source inside it, or minus the absorbed power of
+//! a lossy scatterer in an incident field.
+
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use bytemuck::{Pod, Zeroable};
+use rustfft::num_complex::Complex64;
+use serde::Serialize;
+use wgpu::util::DeviceExt;
+
+use crate::config::ConfigError;
+use crate::dft::{DftMonitor, Region, Spectrum};
+use crate::error::FdtdError;
+use crate::gpu::{
+    self, bg_entry, bgl_storage_entry, bgl_uniform_entry, compute_pipeline, groups_1d, Uploads,
+};
+use crate::grid::{Axis, Field, Grid};
+
+/// A named flux rectangle.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct FluxMonitor {
+    pub name: &'static str,
+    pub normal: Axis,
+    /// E plane along the normal; must be at least 1.
+    pub index: u32,
+    /// Cell ranges along the first and second tangential axes.
+    pub u: (u32, u32),
+    pub v: (u32, u32),
+    /// Frequencies (Hz) of the flux spectrum; empty for time domain only.
+    pub frequencies: &'static [f64],
+}
+
+impl FluxMonitor {
+    /// Fails unless the rectangle is non-empty, inside `grid` and off its
+    /// lower face, with positive frequencies.
+    pub fn check(&self, grid: &Grid) -> Result<(), ConfigError> {
+        let fail = |problem: String| {
+            Err(ConfigError(format!(
+                "flux monitor {}: {problem}",
+                self.name
+            )))
+        };
+        let n = grid.cells(self.normal);
+        if self.index < 1 || self.index >= n {
+            return fail(format!("plane {} outside 1..{n}", self.index));
+        }
+        let (u, v) = self.normal.tangential();
+        for (range, axis) in [(self.u, u), (self.v, v)] {
+            let n = grid.cells(axis);
+            if range.0 >= range.1 || range.1 > n {
+                return fail(format!(
+                    "{range:?} along {axis:?} is empty or outside 0..{n}"
+                ));
+            }
+        }
+        if self.frequencies.iter().any(|&f| f <= 0.0) {
+            return fail("frequencies must be positive".into());
+        }
+        Ok(())
+    }
+
+    /// The tangential components (E_u, E_v, H_u, H_v).
+    fn components(&self) -> &'static [Field; 4] {
+        match self.normal {
+            Axis::X => &[
+                Field::E(Axis::Y),
+                Field::E(Axis::Z),
+                Field::H(Axis::Y),
+                Field::H(Axis::Z),
+            ],
+            Axis::Y => &[
+                Field::E(Axis::Z),
+                Field::E(Axis::X),
+                Field::H(Axis::Z),
+                Field::H(Axis::X),
+            ],
+            Axis::Z => &[
+                Field::E(Axis::X),
+                Field::E(Axis::Y),
+                Field::H(Axis::X),
+                Field::H(Axis::Y),
+            ],
+        }
+    }
+
+    /// The box of cells holding the rectangle's samples: the E plane and
+    /// the H plane below it.
+    fn region(&self) -> Region {
+        let (u, v) = self.normal.tangential();
+        let (mut lo, mut hi) = ([0; 3], [0; 3]);
+        (lo[u.lane()], hi[u.lane()]) = self.u;
+        (lo[v.lane()], hi[v.lane()]) = self.v;
+        (lo[self.normal.lane()], hi[self.normal.lane()]) = (self.index - 1, self.index + 1);
+        Region::Box { lo, hi }
+    }
+
+    /// The DFT monitor feeding the flux spectrum, if any frequencies are
+    /// set.
+    pub fn dft_monitor(&self) -> Option<DftMonitor> {
+        (!self.frequencies.is_empty()).then(|| DftMonitor {
+            name: self.name,
+            fields: self.components(),
+            region: self.region(),
+            frequencies: self.frequencies,
+        })
+    }
+
+    /// Areas (dA_u, dA_v) of the E_u and E_v samples, u fastest.
+    fn areas(&self, grid: &Grid) -> Vec<[f64; 2]> {
+        let (u, v) = self.normal.tangential();
+        let mut areas = Vec::new();
+        for b in self.v.0..self.v.1 {
+            for a in self.u.0..self.u.1 {
+                areas.push([
+                    grid.width(u, a) * grid.dual_width(v, b),
+                    grid.dual_width(u, a) * grid.width(v, b),
+                ]);
+            }
+        }
+        areas
+    }
+
+    /// Time-aligned tangential samples at frequency `f` from the spectrum
+    /// of [`dft_monitor`](Self::dft_monitor), u fastest: per cell the E_u
+    /// edge with H_v and the E_v edge with H_u, H averaged onto the E plane.
+    pub fn samples(&self, grid: &Grid, spectrum: &Spectrum, f: usize) -> Vec<[Sample; 2]> {
+        let [eu, ev, hu, hv] = *self.components();
+        let (u, v) = self.normal.tangential();
+        let nu = (self.u.1 - self.u.0) as usize;
+        let w = grid.node(self.normal, self.index);
+        // Index in the region of sample (a, b) on normal layer `layer`
+        let cell = |a: usize, b: usize, layer: usize| {
+            let mut q = [0; 3];
+            (q[u.lane()], q[v.lane()], q[self.normal.lane()]) = (a, b, layer);
+            let [sx, sy, _] = spectrum.size.map(|s| s as usize);
+            q[0] + sx * (q[1] + sy * q[2])
+        };
+        let complex = |field: Field, c: usize| {
+            let [re, im] = spectrum.value(field, f, c).unwrap();
+            Complex64::new(re as f64, im as f64)
+        };
+        let point = |pu: f64, pv: f64| {
+            let mut at = [0.0; 3];
+            (at[u.lane()], at[v.lane()], at[self.normal.lane()]) = (pu, pv, w);
+            at
+        };
+        self.areas(grid)
+            .iter()
+            .enumerate()
+            .map(|(s, &[da_u, da_v])| {
+                let (a, b) = (s % nu, s / nu);
+                let (e, below) = (cell(a, b, 1), cell(a, b, 0));
+                let average = |field: Field| 0.5 * (complex(field, e) + complex(field, below));
+                let (i, j) = (self.u.0 + a as u32, self.v.0 + b as u32);
+                [
+                    Sample {
+                        e: complex(eu, e),
+                        h: average(hv),
+                        at: point(grid.node(u, i) + 0.5 * grid.width(u, i), grid.node(v, j)),
+                        area: da_u,
+                    },
+                    Sample {
+                        e: complex(ev, e),
+                        h: average(hu),
+                        at: point(grid.node(u, i), grid.node(v, j) + 0.5 * grid.width(v, j)),
+                        area: da_v,
+                    },
+                ]
+            })
+            .collect()
+    }
+
+    /// P(f) in W from the spectrum of [`dft_monitor`](Self::dft_monitor).
+    pub fn spectrum(&self, grid: &Grid, spectrum: &Spectrum) -> Vec<f64> {
+        (0..spectrum.frequencies.len())
+            .map(|f| {
+                let sum: f64 = self
+                    .samples(grid, spectrum, f)
+                    .iter()
+                    .map(|[pu, pv]| {
+                        (pu.e * pu.h.conj()).re * pu.area - (pv.e * pv.h.conj()).re * pv.area
+                    })
+                    .sum();
+                0.5 * sum
+            })
+            .collect()
+    }
+}
+
+/// One tangential E sample of a flux rectangle and the crossed H component
+/// at the same point, at one frequency.
+#[derive(Copy, Clone, Debug)]
+pub struct Sample {
+    pub e: Complex64,
+    pub h: Complex64,
+    /// Position (m) and area (m²) of the E edge.
+    pub at: [f64; 3],
+    pub area: f64,
+}
+
+/// A named closed flux surface around the cells `lo..hi`.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct FluxBox {
+    pub name: &'static str,
+    /// Lower cell on each axis; must be at least 1.
+    pub lo: [u32; 3],
+    pub hi: [u32; 3],
+    /// Frequencies (Hz) of the net power spectrum; empty for time domain only.
+    pub frequencies: &'static [f64],
+}
+
+impl FluxBox {
+    /// Outward sign of each face of [`faces`](Self::faces).
+    pub const SIGNS: [f64; 6] = [-1.0, 1.0, -1.0, 1.0, -1.0, 1.0];
+
+    /// Fails unless the box is non-empty with every face inside `grid` and
+    /// off its lower faces, with positive frequencies.
+    pub fn check(&self, grid: &Grid) -> Result<(), ConfigError> {
+        let cells = [grid.nx, grid.ny, grid.nz];
+        let (lo, hi) = (self.lo, self.hi);
+        if !(0..3).all(|a| 1 <= lo[a] && lo[a] < hi[a] && hi[a] < cells[a]) {
+            return Err(ConfigError(format!(
+                "box {}: {lo:?}..{hi:?} is empty or its faces outside 1..{cells:?}",
+                self.name
+            )));
+        }
+        if self.frequencies.iter().any(|&f| f <= 0.0) {
+            let message = format!("box {}: frequencies must be positive", self.name);
+            return Err(ConfigError(message));
+        }
+        Ok(())
+    }
+
+    /// The faces normal to x, y and z, lower then upper, each measuring
+    /// flux along +normal.
+    pub fn faces(&self) -> [FluxMonitor; 6] {
+        let face = |f: usize| {
+            let normal = [Axis::X, Axis::Y, Axis::Z][f / 2];
+            let (u, v) = normal.tangential();
+            let n = normal.lane();
+            FluxMonitor {
+                name: self.name,
+                normal,
+                index: if f.is_multiple_of(2) {
+                    self.lo[n]
+                } else {
+                    self.hi[n]
+                },
+                u: (self.lo[u.lane()], self.hi[u.lane()]),
+                v: (self.lo[v.lane()], self.hi[v.lane()]),
+                frequencies: self.frequencies,
+            }
+        };
+        [0, 1, 2, 3, 4, 5].map(face)
+    }
+
+    /// Net outgoing power from the six face powers in [`faces`](Self::faces)
+    /// order.
+    pub fn outgoing(face_power: &[f64]) -> f64 {
+        face_power.iter().zip(Self::SIGNS).map(|(p, s)| p * s).sum()
+    }
+}
+
+/// Reduction parameters (must match WGSL `FluxParams`).
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct FluxParams {
+    nu: u32,
+    count: u32,
+    base: u32,
+    stride_u: u32,
+    stride_v: u32,
+    stride_n: u32,
+    out: u32,
+    _pad: u32,
+}
+
+/// GPU state of one rectangle.
+struct Surface {
+    params: FluxParams,
+    buf_params: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    groups: u32,
+    /// First partial of this surface within a history row.
+    offset: u32,
+}
+
+/// Time-domain flux of every monitor, read back in batches.
+pub struct FluxPass {
+    pipeline: wgpu::ComputePipeline,
+    surfaces: Vec<Surface>,
+    history: wgpu::Buffer,
+    staging: wgpu::Buffer,
+    /// Partial sums per history row (all surfaces).
+    row: u32,
+    batch: u32,
+    filled: u32,
+    pending: u32,
+}
+
+impl FluxPass {
+    /// `fields` are the six field buffers in (Ex, Ey, Ez, Hx, Hy, Hz) order.
+    pub fn new(
+        device: &wgpu::Device,
+        grid: &Grid,
+        monitors: &[FluxMonitor],
+        batch: u32,
+        fields: [&wgpu::Buffer; 6],
+    ) -> Self {
+        let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("flux_bgl"),
+            entries: &[
+                bgl_uniform_entry(0),
+                bgl_storage_entry(1, true),
+                bgl_storage_entry(2, true),
+                bgl_storage_entry(3, true),
+                bgl_storage_entry(4, true),
+                bgl_storage_entry(5, true),
+                bgl_storage_entry(6, false),
+            ],
+        });
+        let pipeline = compute_pipeline(device, "flux", include_str!("shaders/flux.wgsl"), &bgl);
+        let stride = |axis: Axis| match axis {
+            Axis::X => 1,
+            Axis::Y => grid.nx,
+            Axis::Z => grid.nx * grid.ny,
+        };
+        let mut row = 0;
+        let layouts: Vec<_> = monitors
+            .iter()
+            .map(|m| {
+                let (u, v) = m.normal.tangential();
+                let mut lo = [0; 3];
+                (lo[u.lane()], lo[v.lane()], lo[m.normal.lane()]) = (m.u.0, m.v.0, m.index);
+                let nu = m.u.1 - m.u.0;
+                let count = nu * (m.v.1 - m.v.0);
+                let params = FluxParams {
+                    nu,
+                    count,
+                    base: grid.idx(lo[0], lo[1], lo[2]) as u32,
+                    stride_u: stride(u),
+                    stride_v: stride(v),
+                    stride_n: stride(m.normal),
+                    out: 0,
+                    _pad: 0,
+                };
+                let groups = groups_1d(count, 64);
+                let offset = row;
+                row += groups;
+                (m, params, groups, offset)
+            })
+            .collect();
+
+        let size = (batch * row) as u64 * 4;
+        let history = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("flux_history"),
+            size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let staging = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("flux_staging"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let surfaces = layouts
+            .into_iter()
+            .map(|(m, params, groups, offset)| {
+                let areas: Vec<[f32; 2]> =
+                    m.areas(grid).iter().map(|a| a.map(|x| x as f32)).collect();
+                let area = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("flux_area"),
+                    contents: bytemuck::cast_slice(&areas),
+                    usage: wgpu::BufferUsages::STORAGE,
+                });
+                let buf_params = device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("flux_params"),
+                    size: std::mem::size_of::<FluxParams>() as u64,
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                });
+                let [eu, ev, hu, hv] = m.components().map(|c| fields[c.index()]);
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("bg_flux"),
+                    layout: &bgl,
+                    entries: &[
+                        bg_entry(0, buf_params.as_entire_binding()),
+                        bg_entry(1, eu.as_entire_binding()),
+                        bg_entry(2, ev.as_entire_binding()),
+                        bg_entry(3, hu.as_entire_binding()),
+                        bg_entry(4, hv.as_entire_binding()),
+                        bg_entry(5, area.as_entire_binding()),
+                        bg_entry(6, history.as_entire_binding()),
+                    ],
+                });
+                Surface {
+                    params,
+                    buf_params,
+                    bind_group,
+                    groups,
+                    offset,
+                }
+            })
+            .collect();
+        FluxPass {
+            pipeline,
+            surfaces,
+            history,
+            staging,
+            row,
+            batch,
+            filled: 0,
+            pending: 0,
+        }
+    }
+
+    /// Sum the flux of every surface into the next history row and, when
+    /// the batch is full or `last` is set, copy the rows to the staging
+    /// buffer.
+    pub fn encode(
+        &mut self,
+        uploads: &mut Uploads,
+        encoder: &mut wgpu::CommandEncoder,
+        last: bool,
+    ) {
+        for s in &mut self.surfaces {
+            s.params.out = self.filled * self.row + s.offset;
+            uploads.write(encoder, &s.buf_params, 0, bytemuck::bytes_of(&s.params));
+        }
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("flux"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            for s in &self.surfaces {
+                pass.set_bind_group(0, &s.bind_group, &[]);
+                pass.dispatch_workgroups(s.groups, 1, 1);
+            }
+        }
+        self.filled += 1;
+        if self.filled == self.batch || last {
+            let size = (self.filled * self.row) as u64 * 4;
+            encoder.copy_buffer_to_buffer(&self.history, 0, &self.staging, 0, size);
+            self.pending = self.filled;
+            self.filled = 0;
+        }
+    }
+
+    /// Whether steps are summed but not yet copied (mid-batch).
+    pub fn mid_batch(&self) -> bool {
+        self.filled > 0
+    }
+
+    /// Whether rows are copied for [`take`](Self::take) to read back.
+    pub fn copied(&self) -> bool {
+        self.pending > 0
+    }
+
+    /// Flux per surface (W) of the steps copied by the last submitted
+    /// [`encode`](Self::encode), oldest first; empty mid-batch.
+    pub fn take(&mut self, device: &wgpu::Device) -> Result<Vec<Vec<f64>>, FdtdError> {
+        if self.pending == 0 {
+            return Ok(Vec::new());
+        }
+        let size = (self.pending * self.row) as u64 * 4;
+        let slice = self.staging.slice(..size);
+        gpu::map_read(device, &[slice])?;
+        let rows = bytemuck::cast_slice::<u8, f32>(&slice.get_mapped_range())
+            .chunks(self.row as usize)
+            .map(|row| {
+                self.surfaces
+                    .iter()
+                    .map(|s| {
+                        let partials = &row[s.offset as usize..(s.offset + s.groups) as usize];
+                        partials.iter().map(|&x| x as f64).sum()
+                    })
+                    .collect()
+            })
+            .collect();
+        self.staging.unmap();
+        self.pending = 0;
+        Ok(rows)
+    }
+}
+
+/// Write `<name>_spectrum.csv` (frequency in Hz, power in W) for every
+/// monitor and box with frequencies, from the spectra of their rectangles
+/// in [`FluxBox::faces`] order after the monitors'.  Returns the net
+/// outgoing power spectrum of each box.
+pub fn write_spectra(
+    dir: &Path,
+    grid: &Grid,
+    monitors: &[FluxMonitor],
+    boxes: &[FluxBox],
+    spectra: &[Spectrum],
+) -> io::Result<Vec<Vec<f64>>> {
+    let mut spectra = spectra.iter();
+    let mut power = |m: &FluxMonitor| {
+        if m.frequencies.is_empty() {
+            Vec::new()
+        } else {
+            m.spectrum(grid, spectra.next().unwrap())
+        }
+    };
+    let mut rows: Vec<(&str, &[f64], Vec<f64>)> = Vec::new();
+    for m in monitors {
+        rows.push((m.name, m.frequencies, power(m)));
+    }
+    let mut nets = Vec::new();
+    for b in boxes {
+        let faces = b.faces().map(|face| power(&face));
+        let net: Vec<f64> = (0..b.frequencies.len())
+            .map(|f| FluxBox::outgoing(&faces.iter().map(|p| p[f]).collect::<Vec<_>>()))
+            .collect();
+        rows.push((b.name, b.frequencies, net.clone()));
+        nets.push(net);
+    }
+
+    for (name, frequencies, power) in rows {
+        if frequencies.is_empty() {
+            continue;
+        }
+        let path = dir.join(format!("{name}_spectrum.csv"));
+        let mut file = BufWriter::new(fs::File::create(path)?);
+        writeln!(file, "frequency,power")?;
+        for (f, p) in frequencies.iter().zip(&power) {
+            writeln!(file, "{f:e},{p:e}")?;
+        }
+        file.flush()?;
+    }
+    Ok(nets)
+}
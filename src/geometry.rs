@@ -0,0 +1,357 @@
+//! Geometric primitives used to place objects in the grid.
+//!
+//! Coordinates are physical (metres) with the origin at the lower corner of
+//! cell (0, 0, 0); a cell belongs to a shape when its centre does.
+//!
+//! [`Shape::transformed`] places a primitive with an affine [`Transform`]
+//! (tilted plates, rotated waveguides, off-axis cylinders); the cell
+//! centres are mapped back into the primitive's own frame when rasterized.
+//! [`Shape::union`], [`Shape::intersect`] and [`Shape::minus`] combine
+//! shapes (a box minus a cylinder makes a coax aperture); the booleans are
+//! evaluated per cell centre, or per sample with smoothing, when painted.
+//! [`Shape::sdf`] takes any region as a signed distance function (gyroids,
+//! lenses, blended solids), which smoothing turns into fill fractions and
+//! normals without sampling.
+
+use std::fmt;
+
+use glam::{DAffine3, DVec3};
+use serde::{Serialize, Serializer};
+
+use crate::grid::{Axis, Grid};
+use crate::materials::{Coefficients, Material};
+use crate::smoothing::{self, Smoothing};
+
+/// Solid primitive.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub enum Shape {
+    /// Axis-aligned box between two corners.
+    Box {
+        min: [f64; 3],
+        max: [f64; 3],
+    },
+    Sphere {
+        center: [f64; 3],
+        radius: f64,
+    },
+    /// Finite circular cylinder along `axis`.
+    Cylinder {
+        center: [f64; 3],
+        radius: f64,
+        height: f64,
+        axis: Axis,
+    },
+    /// `shape` moved by `transform`.
+    Transformed {
+        shape: &'static Shape,
+        transform: Transform,
+    },
+    /// Points in either shape.
+    Union(&'static Shape, &'static Shape),
+    /// Points in both shapes.
+    Intersection(&'static Shape, &'static Shape),
+    /// Points in the first shape but not in the second.
+    Difference(&'static Shape, &'static Shape),
+    /// Points of a box where a distance function is not positive.
+    Sdf(Sdf),
+}
+
+/// A shape leaked to the `'static` lifetime of constant shapes.
+fn leak(shape: Shape) -> &'static Shape {
+    Box::leak(Box::new(shape))
+}
+
+impl Shape {
+    /// This shape moved by `transform`.  The primitive is leaked to give
+    /// the shape a `'static` lifetime, like constant shapes have, and so
+    /// are the operands of the booleans below.
+    pub fn transformed(self, transform: Transform) -> Shape {
+        Shape::Transformed {
+            shape: leak(self),
+            transform,
+        }
+    }
+
+    pub fn union(self, other: Shape) -> Shape {
+        Shape::Union(leak(self), leak(other))
+    }
+
+    pub fn intersect(self, other: Shape) -> Shape {
+        Shape::Intersection(leak(self), leak(other))
+    }
+
+    /// This shape with `other` cut out.
+    pub fn minus(self, other: Shape) -> Shape {
+        Shape::Difference(leak(self), leak(other))
+    }
+
+    /// The points between `min` and `max` where `distance` (m, negative
+    /// inside) is at most zero.  The closure is leaked like the operands
+    /// above.
+    pub fn sdf(
+        min: [f64; 3],
+        max: [f64; 3],
+        distance: impl Fn([f64; 3]) -> f64 + Send + Sync + 'static,
+    ) -> Shape {
+        Shape::Sdf(Sdf {
+            distance: Box::leak(Box::new(distance)),
+            min,
+            max,
+        })
+    }
+
+    /// Axis-aligned bounding box as (min, max) corners.
+    pub fn bounds(&self) -> ([f64; 3], [f64; 3]) {
+        match *self {
+            Shape::Box { min, max } => (min, max),
+            Shape::Sphere { center, radius } => {
+                (center.map(|c| c - radius), center.map(|c| c + radius))
+            }
+            Shape::Cylinder {
+                center,
+                radius,
+                height,
+                axis,
+            } => {
+                let mut ext = [radius; 3];
+                ext[axis.lane()] = height / 2.0;
+                (
+                    std::array::from_fn(|d| center[d] - ext[d]),
+                    std::array::from_fn(|d| center[d] + ext[d]),
+                )
+            }
+            Shape::Transformed { shape, transform } => {
+                // Box around the moved corners of the inner box
+                let (lo, hi) = shape.bounds();
+                let mut min = [f64::INFINITY; 3];
+                let mut max = [f64::NEG_INFINITY; 3];
+                for corner in 0..8 {
+                    let c =
+                        std::array::from_fn(|d| if corner >> d & 1 == 0 { lo[d] } else { hi[d] });
+                    let p = transform.apply(c);
+                    for d in 0..3 {
+                        min[d] = min[d].min(p[d]);
+                        max[d] = max[d].max(p[d]);
+                    }
+                }
+                (min, max)
+            }
+            Shape::Union(a, b) => {
+                let ((alo, ahi), (blo, bhi)) = (a.bounds(), b.bounds());
+                (
+                    std::array::from_fn(|d| alo[d].min(blo[d])),
+                    std::array::from_fn(|d| ahi[d].max(bhi[d])),
+                )
+            }
+            Shape::Intersection(a, b) => {
+                let ((alo, ahi), (blo, bhi)) = (a.bounds(), b.bounds());
+                (
+                    std::array::from_fn(|d| alo[d].max(blo[d])),
+                    std::array::from_fn(|d| ahi[d].min(bhi[d])),
+                )
+            }
+            Shape::Difference(a, _) => a.bounds(),
+            Shape::Sdf(sdf) => (sdf.min, sdf.max),
+        }
+    }
+
+    pub fn contains(&self, p: [f64; 3]) -> bool {
+        match *self {
+            Shape::Box { min, max } => (0..3).all(|d| p[d] >= min[d] && p[d] <= max[d]),
+            Shape::Sphere { center, radius } => {
+                let d2: f64 = (0..3).map(|d| (p[d] - center[d]).powi(2)).sum();
+                d2 <= radius * radius
+            }
+            Shape::Cylinder {
+                center,
+                radius,
+                height,
+                axis,
+            } => {
+                let a = axis.lane();
+                let r2: f64 = (0..3)
+                    .filter(|&d| d != a)
+                    .map(|d| (p[d] - center[d]).powi(2))
+                    .sum();
+                r2 <= radius * radius && (p[a] - center[a]).abs() <= height / 2.0
+            }
+            Shape::Transformed { shape, transform } => shape.contains(transform.invert(p)),
+            Shape::Union(a, b) => a.contains(p) || b.contains(p),
+            Shape::Intersection(a, b) => a.contains(p) && b.contains(p),
+            Shape::Difference(a, b) => a.contains(p) && !b.contains(p),
+            Shape::Sdf(sdf) => sdf.bounded(p) && sdf.distance(p) <= 0.0,
+        }
+    }
+}
+
+/// A signed distance function within a bounding box; see [`Shape::sdf`].
+#[derive(Copy, Clone, Serialize)]
+pub struct Sdf {
+    #[serde(skip)]
+    distance: &'static (dyn Fn([f64; 3]) -> f64 + Send + Sync),
+    pub min: [f64; 3],
+    pub max: [f64; 3],
+}
+
+impl fmt::Debug for Sdf {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Sdf")
+            .field("min", &self.min)
+            .field("max", &self.max)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Sdf {
+    pub fn distance(&self, p: [f64; 3]) -> f64 {
+        (self.distance)(p)
+    }
+
+    /// Whether `p` lies within the bounding box.
+    pub fn bounded(&self, p: [f64; 3]) -> bool {
+        (0..3).all(|d| p[d] >= self.min[d] && p[d] <= self.max[d])
+    }
+
+    /// Outward unit normal at `p` by central differences of step `h`, or
+    /// None where the function is flat.
+    pub fn normal(&self, p: [f64; 3], h: f64) -> Option<[f64; 3]> {
+        let grad: [f64; 3] = std::array::from_fn(|d| {
+            let (mut a, mut b) = (p, p);
+            a[d] += h;
+            b[d] -= h;
+            (self.distance(a) - self.distance(b)) / (2.0 * h)
+        });
+        let norm = grad.iter().map(|g| g * g).sum::<f64>().sqrt();
+        (norm > 0.0 && norm.is_finite()).then(|| grad.map(|g| g / norm))
+    }
+}
+
+/// Affine map of positions (m): rotations, scalings and translations,
+/// chained with [`Transform::then`] in the order they apply.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize)]
+pub struct Transform {
+    #[serde(serialize_with = "affine_rows")]
+    forward: DAffine3,
+    /// Kept so that containment tests do not invert per cell.
+    #[serde(skip)]
+    inverse: DAffine3,
+}
+
+/// The rows [m₀, m₁, m₂, t] of the 3×4 matrix of `map`.
+fn affine_rows<S: Serializer>(map: &DAffine3, serializer: S) -> Result<S::Ok, S::Error> {
+    let (m, t) = (map.matrix3.transpose(), map.translation);
+    let rows = [m.x_axis, m.y_axis, m.z_axis]
+        .into_iter()
+        .zip(t.to_array())
+        .map(|(row, t)| [row.x, row.y, row.z, t]);
+    serializer.collect_seq(rows)
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform {
+        forward: DAffine3::IDENTITY,
+        inverse: DAffine3::IDENTITY,
+    };
+
+    fn new(forward: DAffine3) -> Transform {
+        assert!(
+            forward.matrix3.determinant() != 0.0,
+            "a transform must be invertible"
+        );
+        Transform {
+            forward,
+            inverse: forward.inverse(),
+        }
+    }
+
+    pub fn translate(offset: [f64; 3]) -> Transform {
+        Transform::new(DAffine3::from_translation(DVec3::from(offset)))
+    }
+
+    /// Rotation by `angle` (rad, right-handed) about `axis` through the
+    /// origin.
+    pub fn rotate(axis: [f64; 3], angle: f64) -> Transform {
+        let axis = DVec3::from(axis);
+        assert!(axis.length() > 0.0, "a rotation needs a non-zero axis");
+        Transform::new(DAffine3::from_axis_angle(axis.normalize(), angle))
+    }
+
+    /// Rotation by `angle` (rad) about `axis` through `point`.
+    pub fn rotate_about(axis: [f64; 3], angle: f64, point: [f64; 3]) -> Transform {
+        let back = point.map(|x| -x);
+        Transform::translate(back)
+            .then(Transform::rotate(axis, angle))
+            .then(Transform::translate(point))
+    }
+
+    /// Scaling by `factors` along x, y and z about the origin.
+    pub fn scale(factors: [f64; 3]) -> Transform {
+        Transform::new(DAffine3::from_scale(DVec3::from(factors)))
+    }
+
+    /// This transform followed by `next`.
+    pub fn then(self, next: Transform) -> Transform {
+        Transform {
+            forward: next.forward * self.forward,
+            inverse: self.inverse * next.inverse,
+        }
+    }
+
+    pub fn apply(&self, p: [f64; 3]) -> [f64; 3] {
+        self.forward.transform_point3(DVec3::from(p)).into()
+    }
+
+    pub fn invert(&self, p: [f64; 3]) -> [f64; 3] {
+        self.inverse.transform_point3(DVec3::from(p)).into()
+    }
+}
+
+/// A shape filled with a homogeneous material.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct Object {
+    pub shape: Shape,
+    pub material: Material,
+}
+
+impl Object {
+    /// Rasterize the object.  Without smoothing every cell whose centre is
+    /// inside the shape takes the object's material (staircasing); with
+    /// smoothing, edges straddling the surface get an effective medium.
+    pub fn apply(&self, grid: &Grid, coeffs: &mut Coefficients, smoothing: Smoothing) {
+        match smoothing {
+            Smoothing::None => {
+                for (id, inside) in grid.mask(&self.shape).into_iter().enumerate() {
+                    if inside {
+                        coeffs.set_cell(id, &self.material, grid.dt);
+                    }
+                }
+            }
+            _ => smoothing::rasterize(grid, coeffs, &self.shape, &self.material, smoothing),
+        }
+    }
+}
+
+impl Grid {
+    /// Physical position of the centre of cell (i, j, k).
+    pub fn cell_center(&self, i: u32, j: u32, k: u32) -> [f64; 3] {
+        let c = self.corner(i, j, k);
+        [
+            c[0] + self.width(Axis::X, i) / 2.0,
+            c[1] + self.width(Axis::Y, j) / 2.0,
+            c[2] + self.width(Axis::Z, k) / 2.0,
+        ]
+    }
+
+    /// Per-cell inside/outside mask of `shape`.
+    pub fn mask(&self, shape: &Shape) -> Vec<bool> {
+        let mut mask = vec![false; self.total()];
+        for k in 0..self.nz {
+            for j in 0..self.ny {
+                for i in 0..self.nx {
+                    mask[self.idx(i, j, k)] = shape.contains(self.cell_center(i, j, k));
+                }
+            }
+        }
+        mask
+    }
+}
@@ -0,0 +1,479 @@
+//! Import of gprMax input files.
+//!
+//! A `.in` file of the gprMax GPR simulator is read into a [`SceneFile`]
+//! and from there into a [`Config`], so existing models run on this solver
+//! unchanged.  The subset understood:
+//!
+//! ```text
+//! #domain: 1.0 0.5 0.002                 extent (m)
+//! #dx_dy_dz: 0.002 0.002 0.002           cell size (m)
+//! #time_window: 12e-9                    seconds, or iterations as an integer
+//! #time_step_stability_factor: 0.9       Δt below the Courant limit (0.99)
+//! #material: 6 0 1 0 half_space          eps_r sigma mu_r sigma_m id
+//! #box: 0 0 0 1.0 0.35 0.002 half_space  x1 y1 z1 x2 y2 z2 material [n]
+//! #waveform: ricker 1 1.5e9 my_ricker    type amplitude frequency id
+//! #hertzian_dipole: z 0.1 0.37 0 my_ricker
+//! #rx: 0.14 0.37 0                       [id Ex Ey Ez Hx Hy Hz]
+//! ```
+//!
+//! Lines not starting with `#` are comments.  `free_space` and `pec` are
+//! predefined; `pec` boxes become [`ConformalPec`] objects, which the
+//! solver applies after the dielectric ones, so a later box does not fill
+//! them.  Waveforms are `gaussian`, `ricker` and `contsine` with gprMax's
+//! definitions (`contsine` ramps up over four periods, raised-cosine here
+//! instead of linearly); amplitudes are dropped, every source being unit
+//! (the fields scale with it).  The single `z`-polarised dipole is the hard
+//! Ez source of the solver, which sets the field rather than adding a
+//! current as gprMax's does.  Each receiver records the components listed,
+//! all six by default, as probes named `<id>_<component>`, the id being
+//! `Rx1`, `Rx2`, … when not given.
+//!
+//! The solver has no absorbing boundaries: `#pml_cells` is read but the
+//! domain is bounded by PEC walls, so pad it where reflections matter.
+//! `#title`, `#messages` and `#num_threads` are ignored; any other command,
+//! and Python blocks, are reported with their line numbers.
+
+use std::collections::BTreeMap;
+use std::f64::consts::PI;
+
+use crate::config::{Config, ConfigError};
+use crate::conformal::ConformalPec;
+use crate::geometry::Shape;
+use crate::scene_file::{
+    GridSpec, MaterialSpec, ObjectSpec, ProbeSpec, QuantityName, SceneFile, ShapeSpec, SourceSpec,
+    Spacing, WaveformSpec,
+};
+use crate::C0;
+
+/// Commands accepted without effect.
+const IGNORED: [&str; 4] = ["title", "messages", "num_threads", "pml_cells"];
+
+/// A gprMax model: the scene and its PEC boxes.
+pub struct GprMaxModel {
+    pub scene: SceneFile,
+    pub pec: Vec<Shape>,
+}
+
+/// A waveform as gprMax defines it, before Δt is known.
+#[derive(Copy, Clone)]
+enum Pulse {
+    Gaussian(f64),
+    Ricker(f64),
+    ContSine(f64),
+}
+
+impl Pulse {
+    /// In time steps of `dt`.
+    fn spec(self, dt: f64) -> WaveformSpec {
+        match self {
+            // exp(−ζ(t − χ)²), ζ = 2π²f², χ = 1/f
+            Pulse::Gaussian(f) => WaveformSpec::Gaussian {
+                width: 1.0 / (PI * 2f64.sqrt() * f * dt),
+                delay: 1.0 / (f * dt),
+            },
+            // χ = √2/f
+            Pulse::Ricker(f) => WaveformSpec::Ricker {
+                freq: f,
+                delay: 2f64.sqrt() / (f * dt),
+            },
+            Pulse::ContSine(f) => WaveformSpec::Sine {
+                freq: f,
+                ramp: 4.0 / (f * dt),
+            },
+        }
+    }
+}
+
+impl GprMaxModel {
+    /// Read the commands of a `.in` file; every problem is reported, one
+    /// per line, at its line number.
+    pub fn parse(text: &str) -> Result<GprMaxModel, ConfigError> {
+        let mut issues = Vec::new();
+        let (mut domain, mut spacing, mut window, mut stability) = (None, None, None, None);
+        let mut materials = BTreeMap::new();
+        let mut boxes = Vec::new();
+        let mut waveforms = BTreeMap::new();
+        let mut dipoles = Vec::new();
+        let mut receivers = Vec::new();
+
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            let Some(command) = line.strip_prefix('#') else {
+                continue;
+            };
+            let at = n + 1;
+            let Some((name, args)) = command.split_once(':') else {
+                issues.push(format!("line {at}: {line:?} is not a command"));
+                continue;
+            };
+            let args: Vec<&str> = args.split_whitespace().collect();
+            let numbers = |args: &[&str]| -> Result<Vec<f64>, String> {
+                args.iter()
+                    .map(|a| a.parse().map_err(|_| format!("{a:?} is not a number")))
+                    .collect()
+            };
+            let count = |expected: &[usize]| -> Result<(), String> {
+                if expected.contains(&args.len()) {
+                    Ok(())
+                } else {
+                    let expected: Vec<String> = expected.iter().map(usize::to_string).collect();
+                    Err(format!(
+                        "expected {} values, found {}",
+                        expected.join(" or "),
+                        args.len()
+                    ))
+                }
+            };
+            let point = |args: &[&str]| -> Result<[f64; 3], String> {
+                numbers(args).map(|v| [v[0], v[1], v[2]])
+            };
+
+            let parsed = match name {
+                "domain" => count(&[3]).and_then(|_| point(&args)).map(|p| {
+                    domain = Some(p);
+                }),
+                "dx_dy_dz" => count(&[3]).and_then(|_| point(&args)).map(|p| {
+                    spacing = Some(p);
+                }),
+                "time_window" => count(&[1]).and_then(|_| {
+                    let text = args[0];
+                    window = Some(match text.parse::<u32>() {
+                        Ok(steps) => Window::Steps(steps),
+                        Err(_) => Window::Seconds(numbers(&args)?[0]),
+                    });
+                    Ok(())
+                }),
+                "time_step_stability_factor" => count(&[1]).and_then(|_| {
+                    let factor = numbers(&args)?[0];
+                    if !(factor > 0.0 && factor <= 1.0) {
+                        return Err(format!("{factor} is not in (0, 1]"));
+                    }
+                    stability = Some(factor);
+                    Ok(())
+                }),
+                "material" => count(&[5]).and_then(|_| {
+                    let v = numbers(&args[..4])?;
+                    let spec = MaterialSpec {
+                        eps_r: Some(v[0]),
+                        sigma: Some(v[1]),
+                        mu_r: Some(v[2]),
+                        sigma_m: Some(v[3]),
+                    };
+                    match materials.insert(args[4].to_string(), spec) {
+                        Some(_) => Err(format!("material {:?} defined twice", args[4])),
+                        None => Ok(()),
+                    }
+                }),
+                "box" => count(&[7, 8]).and_then(|_| {
+                    let v = numbers(&args[..6])?;
+                    let (min, max) = ([v[0], v[1], v[2]], [v[3], v[4], v[5]]);
+                    if (0..3).any(|a| min[a] >= max[a]) {
+                        return Err("the upper corner must lie above the lower one".into());
+                    }
+                    boxes.push((at, min, max, args[6].to_string()));
+                    Ok(())
+                }),
+                "waveform" => count(&[4]).and_then(|_| {
+                    let f = numbers(&args[1..3])?[1];
+                    if f <= 0.0 {
+                        return Err(format!("frequency {f} is not positive"));
+                    }
+                    let pulse = match args[0] {
+                        "gaussian" => Pulse::Gaussian(f),
+                        "ricker" => Pulse::Ricker(f),
+                        "contsine" => Pulse::ContSine(f),
+                        other => {
+                            return Err(format!(
+                                "waveform {other:?} is not supported (gaussian, ricker, contsine)"
+                            ))
+                        }
+                    };
+                    match waveforms.insert(args[3].to_string(), pulse) {
+                        Some(_) => Err(format!("waveform {:?} defined twice", args[3])),
+                        None => Ok(()),
+                    }
+                }),
+                "hertzian_dipole" => count(&[5]).and_then(|_| {
+                    if args[0] != "z" {
+                        return Err("only z-polarised dipoles are supported".into());
+                    }
+                    dipoles.push((at, point(&args[1..4])?, args[4].to_string()));
+                    Ok(())
+                }),
+                "rx" => {
+                    if args.len() == 3 || args.len() >= 5 {
+                        point(&args[..3]).and_then(|p| {
+                            let id = args.get(3).map(|id| id.to_string());
+                            let outputs = args.get(4..).unwrap_or(&[]);
+                            let outputs = outputs
+                                .iter()
+                                .map(|o| component(o))
+                                .collect::<Result<Vec<_>, _>>()?;
+                            receivers.push((p, id, outputs));
+                            Ok(())
+                        })
+                    } else {
+                        Err(format!(
+                            "expected 3 values, or an id and outputs, found {}",
+                            args.len()
+                        ))
+                    }
+                }
+                "python" | "end_python" => Err("Python blocks are not supported".into()),
+                _ if IGNORED.contains(&name) => Ok(()),
+                _ => Err("not supported".into()),
+            };
+            if let Err(message) = parsed {
+                issues.push(format!("line {at}: #{name}: {message}"));
+            }
+        }
+
+        for (name, value) in [
+            ("#domain", domain.is_some()),
+            ("#dx_dy_dz", spacing.is_some()),
+            ("#time_window", window.is_some()),
+        ] {
+            if !value {
+                issues.push(format!("{name} is missing"));
+            }
+        }
+        let dipole = match &dipoles[..] {
+            [dipole] => Some(dipole),
+            [] => {
+                issues.push("no #hertzian_dipole".into());
+                None
+            }
+            [_, (at, ..), ..] => {
+                issues.push(format!("line {at}: the solver has a single source"));
+                None
+            }
+        };
+        if let Some((at, _, id)) = dipole {
+            if !waveforms.contains_key(id) {
+                issues.push(format!("line {at}: unknown waveform {id:?}"));
+            }
+        }
+        for (at, _, _, material) in &boxes {
+            if !(materials.contains_key(material) || material == "free_space" || material == "pec")
+            {
+                issues.push(format!("line {at}: unknown material {material:?}"));
+            }
+        }
+        if !issues.is_empty() {
+            return Err(ConfigError(issues.join("\n")));
+        }
+        let (Some(domain), Some(d), Some(window), Some(dipole)) = (domain, spacing, window, dipole)
+        else {
+            unreachable!("reported above");
+        };
+
+        // gprMax's Δt: the Courant limit, times the stability factor
+        let limit = 1.0 / (C0 * d.iter().map(|d| 1.0 / (d * d)).sum::<f64>().sqrt());
+        let dt = stability.unwrap_or(0.99) * limit;
+        let steps = match window {
+            Window::Steps(steps) => steps,
+            Window::Seconds(t) => (t / dt).ceil() as u32 + 1,
+        };
+        let cell = |p: [f64; 3]| [0, 1, 2].map(|a| (p[a] / d[a]).round().max(0.0) as u32);
+
+        let (_, at, waveform) = dipole;
+        let mut probes = Vec::new();
+        for (n, (p, id, outputs)) in receivers.into_iter().enumerate() {
+            let id = id.unwrap_or_else(|| format!("Rx{}", n + 1));
+            let outputs = if outputs.is_empty() {
+                COMPONENTS.to_vec()
+            } else {
+                outputs
+            };
+            for (label, quantity) in outputs {
+                probes.push(ProbeSpec {
+                    name: format!("{id}_{label}"),
+                    cell: Some(cell(p)),
+                    point: None,
+                    quantity: Some(quantity),
+                });
+            }
+        }
+
+        let mut objects = Vec::new();
+        let mut pec = Vec::new();
+        for (_, min, max, material) in boxes {
+            match material.as_str() {
+                "pec" => pec.push(Shape::Box { min, max }),
+                _ => objects.push(ObjectSpec {
+                    material: match material.as_str() {
+                        "free_space" => "vacuum".into(),
+                        _ => material,
+                    },
+                    shape: ShapeSpec::Box { min, max },
+                    transform: Vec::new(),
+                }),
+            }
+        }
+
+        let scene = SceneFile {
+            steps,
+            grid: GridSpec {
+                cells: [0, 1, 2].map(|a| (domain[a] / d[a]).round() as u32),
+                spacing: Spacing::PerAxis(d),
+                dt: Some(dt),
+                courant: None,
+                origin: None,
+            },
+            script: None,
+            parameters: BTreeMap::new(),
+            boundaries: None,
+            materials,
+            objects,
+            source: Some(SourceSpec {
+                cell: Some(cell(*at)),
+                waveform: Some(waveforms[waveform].spec(dt)),
+            }),
+            probes,
+            monitors: Vec::new(),
+            dft_monitors: Vec::new(),
+            flux_monitors: Vec::new(),
+            lumped: Vec::new(),
+            ports: Vec::new(),
+            output: None,
+            sweep: None,
+        };
+        Ok(GprMaxModel { scene, pec })
+    }
+
+    /// Check the scene and map it, PEC boxes included, onto a validated
+    /// configuration.
+    pub fn config(self) -> Result<Config, ConfigError> {
+        self.scene.check()?;
+        let pec = self.pec;
+        self.scene
+            .builder()?
+            .configure(|config| {
+                config
+                    .conformal_pec
+                    .extend(pec.into_iter().map(|shape| ConformalPec {
+                        shape,
+                        min_fraction: 0.05,
+                    }))
+            })
+            .build_config()
+    }
+}
+
+/// `#time_window` in either unit.
+#[derive(Copy, Clone)]
+enum Window {
+    Seconds(f64),
+    Steps(u32),
+}
+
+const COMPONENTS: [(&str, QuantityName); 6] = [
+    ("Ex", QuantityName::Ex),
+    ("Ey", QuantityName::Ey),
+    ("Ez", QuantityName::Ez),
+    ("Hx", QuantityName::Hx),
+    ("Hy", QuantityName::Hy),
+    ("Hz", QuantityName::Hz),
+];
+
+/// A receiver output by its gprMax name.
+fn component(name: &str) -> Result<(&'static str, QuantityName), String> {
+    COMPONENTS
+        .into_iter()
+        .find(|(label, _)| *label == name)
+        .ok_or_else(|| format!("output {name:?} is not supported (Ex … Hz)"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HALF_SPACE: &str = "\
+#title: a cylinder under a half space
+#domain: 0.2 0.1 0.02
+#dx_dy_dz: 0.002 0.002 0.002
+#time_window: 3e-9
+#material: 6 0.01 1 0 half_space
+#box: 0 0 0 0.2 0.05 0.02 half_space
+#box: 0.08 0.02 0 0.12 0.04 0.02 pec
+#waveform: ricker 1 1.5e9 my_ricker
+#hertzian_dipole: z 0.1 0.07 0.01 my_ricker
+#rx: 0.14 0.07 0.01
+#rx: 0.06 0.07 0.01 left Ez Hy
+";
+
+    #[test]
+    fn boxes_materials_source_and_receivers_map_onto_a_scene() {
+        let model = GprMaxModel::parse(HALF_SPACE).unwrap();
+        let scene = &model.scene;
+        let dt = 0.99 * 0.002 / (C0 * 3f64.sqrt());
+        assert_eq!(scene.grid.cells, [100, 50, 10]);
+        assert!((scene.grid.dt.unwrap() / dt - 1.0).abs() < 1e-12);
+        assert_eq!(scene.steps, (3e-9 / dt).ceil() as u32 + 1);
+
+        let half_space = scene.materials["half_space"];
+        assert_eq!(
+            (half_space.eps_r, half_space.sigma),
+            (Some(6.0), Some(0.01))
+        );
+        assert_eq!(scene.objects.len(), 1);
+        assert_eq!(scene.objects[0].material, "half_space");
+        assert!(matches!(
+            scene.objects[0].shape,
+            ShapeSpec::Box {
+                max: [0.2, 0.05, 0.02],
+                ..
+            }
+        ));
+        assert_eq!(model.pec.len(), 1);
+
+        let source = scene.source.as_ref().unwrap();
+        assert_eq!(source.cell, Some([50, 35, 5]));
+        match source.waveform {
+            Some(WaveformSpec::Ricker { freq, delay }) => {
+                assert_eq!(freq, 1.5e9);
+                assert!((delay * freq * dt - 2f64.sqrt()).abs() < 1e-12);
+            }
+            other => panic!("expected a Ricker wavelet, got {other:?}"),
+        }
+
+        let names: Vec<&str> = scene.probes.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["Rx1_Ex", "Rx1_Ey", "Rx1_Ez", "Rx1_Hx", "Rx1_Hy", "Rx1_Hz", "left_Ez", "left_Hy"]
+        );
+        assert_eq!(scene.probes[0].cell, Some([70, 35, 5]));
+        assert_eq!(scene.probes[7].quantity, Some(QuantityName::Hy));
+
+        let config = model.config().unwrap();
+        assert_eq!(config.conformal_pec.len(), 1);
+        assert_eq!(config.probes.len(), 8);
+    }
+
+    #[test]
+    fn every_problem_is_reported_at_its_line() {
+        let text = "\
+#domain: 0.1 0.1 0.1
+#dx_dy_dz: 0.01 0.01
+#box: 0 0 0 0.05 0.05 0.05 granite
+#hertzian_dipole: x 0.05 0.05 0.05 pulse
+#python:
+#fractal_box: 0 0 0 1 1 1
+";
+        let e = GprMaxModel::parse(text).err().unwrap().0;
+        let lines: Vec<&str> = e.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "line 2: #dx_dy_dz: expected 3 values, found 2",
+                "line 4: #hertzian_dipole: only z-polarised dipoles are supported",
+                "line 5: #python: Python blocks are not supported",
+                "line 6: #fractal_box: not supported",
+                "#dx_dy_dz is missing",
+                "#time_window is missing",
+                "no #hertzian_dipole",
+                "line 3: unknown material \"granite\"",
+            ]
+        );
+    }
+}
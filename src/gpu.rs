@@ -0,0 +1,423 @@
+//! Tiny helpers for bind-group / layout construction, plus the uniform
+//! data shared by the update kernels, the readbacks, the buffer writes of
+//! a submission and the error scopes and device-lost handling of a run.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::error::FdtdError;
+use crate::grid::{Axis, Grid};
+
+/// Grid dimensions (must match WGSL `Params`).
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+pub struct GpuParams {
+    pub nx: u32,
+    pub ny: u32,
+    pub nz: u32,
+    /// Bit per axis (x = 1, y = 2, z = 4) wrapping around periodically in
+    /// `update_e.wgsl` / `update_h.wgsl`; padding to the other shaders.
+    pub periodic: u32,
+}
+
+impl GpuParams {
+    pub fn new(grid: &Grid) -> Self {
+        GpuParams {
+            nx: grid.nx,
+            ny: grid.ny,
+            nz: grid.nz,
+            periodic: 0,
+        }
+    }
+
+    /// The same with periodic boundaries on `axes` instead of PEC walls.
+    pub fn with_periodic(self, axes: &[Axis]) -> Self {
+        GpuParams {
+            periodic: axes.iter().map(|a| 1 << a.lane()).fold(0, |m, b| m | b),
+            ..self
+        }
+    }
+}
+
+/// A staging buffer holding `size` bytes at byte `offset` of `buffer` once
+/// the submitted copy completes.
+fn stage(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    buffer: &wgpu::Buffer,
+    offset: u64,
+    size: u64,
+) -> wgpu::Buffer {
+    let staging = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("field_staging"),
+        size,
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let mut encoder = device.create_command_encoder(&Default::default());
+    encoder.copy_buffer_to_buffer(buffer, offset, &staging, 0, size);
+    queue.submit(Some(encoder.finish()));
+    staging
+}
+
+fn unstage(staging: wgpu::Buffer) -> Vec<f32> {
+    let values = bytemuck::cast_slice(&staging.slice(..).get_mapped_range()).to_vec();
+    staging.unmap();
+    values
+}
+
+/// Copy `size` bytes of f32 values at byte `offset` of `buffer` back from
+/// the device, waiting for it.
+pub fn read_f32(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    buffer: &wgpu::Buffer,
+    offset: u64,
+    size: u64,
+) -> Result<Vec<f32>, FdtdError> {
+    let staging = stage(device, queue, buffer, offset, size);
+    let mapped = Mapped::default();
+    let done = mapped.clone();
+    staging
+        .slice(..)
+        .map_async(wgpu::MapMode::Read, move |result| done.complete(result));
+    device.poll(wgpu::Maintain::Wait);
+    mapped.result().map_err(map_failed)?;
+    Ok(unstage(staging))
+}
+
+/// [`read_f32`] without blocking, for browsers, whose event loop completes
+/// the mapping (natively the device is still waited for).
+pub async fn read_f32_async(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    buffer: &wgpu::Buffer,
+    offset: u64,
+    size: u64,
+) -> Result<Vec<f32>, FdtdError> {
+    let staging = stage(device, queue, buffer, offset, size);
+    let mapped = Mapped::default();
+    let done = mapped.clone();
+    staging
+        .slice(..)
+        .map_async(wgpu::MapMode::Read, move |result| done.complete(result));
+    #[cfg(not(target_arch = "wasm32"))]
+    device.poll(wgpu::Maintain::Wait);
+    mapped.await.map_err(map_failed)?;
+    Ok(unstage(staging))
+}
+
+/// Map `slices` for reading and wait for the device; they stay mapped
+/// until their buffers are unmapped.
+pub fn map_read(device: &wgpu::Device, slices: &[wgpu::BufferSlice]) -> Result<(), FdtdError> {
+    let mapped: Vec<Mapped> = slices
+        .iter()
+        .map(|slice| {
+            let mapped = Mapped::default();
+            let done = mapped.clone();
+            slice.map_async(wgpu::MapMode::Read, move |result| done.complete(result));
+            mapped
+        })
+        .collect();
+    device.poll(wgpu::Maintain::Wait);
+    mapped
+        .iter()
+        .try_for_each(|m| m.result().map_err(map_failed))
+}
+
+fn map_failed(e: wgpu::BufferAsyncError) -> FdtdError {
+    FdtdError::MapFailed(e.to_string())
+}
+
+/// Completion of a `map_async`, as a future.
+#[derive(Clone, Default)]
+struct Mapped(Arc<Mutex<MapState>>);
+
+#[derive(Default)]
+struct MapState {
+    result: Option<Result<(), wgpu::BufferAsyncError>>,
+    waker: Option<Waker>,
+}
+
+impl Mapped {
+    /// The outcome once the device has been waited for.
+    fn result(&self) -> Result<(), wgpu::BufferAsyncError> {
+        let outcome = self.0.lock().unwrap().result.take();
+        outcome.unwrap_or(Err(wgpu::BufferAsyncError))
+    }
+
+    fn complete(&self, result: Result<(), wgpu::BufferAsyncError>) {
+        let mut state = self.0.lock().unwrap();
+        state.result = Some(result);
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl Future for Mapped {
+    type Output = Result<(), wgpu::BufferAsyncError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut state = self.0.lock().unwrap();
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Catch the validation and out-of-memory errors of the device calls that
+/// follow, until [`pop_scopes`].  The browser leaves them to the
+/// uncaptured-error handler of [`DeviceWatch`] instead.
+pub fn push_scopes(device: &wgpu::Device) {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        device.push_error_scope(wgpu::ErrorFilter::OutOfMemory);
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+    }
+    #[cfg(target_arch = "wasm32")]
+    let _ = device;
+}
+
+/// The first error caught since [`push_scopes`], as [`FdtdError::Gpu`]
+/// saying what the run was `doing`.
+pub fn pop_scopes(device: &wgpu::Device, doing: impl FnOnce() -> String) -> Result<(), FdtdError> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let validation = pollster::block_on(device.pop_error_scope());
+        let memory = pollster::block_on(device.pop_error_scope());
+        if let Some(e) = validation.or(memory) {
+            return Err(FdtdError::Gpu {
+                during: doing(),
+                message: e.to_string(),
+            });
+        }
+    }
+    #[cfg(target_arch = "wasm32")]
+    let _ = (device, doing);
+    Ok(())
+}
+
+/// What a device reported outside the error scopes: the first uncaptured
+/// error, and why it was lost.  Installing it replaces wgpu's default
+/// handler, which panics.
+#[derive(Clone, Default)]
+pub struct DeviceWatch(Arc<Mutex<Watched>>);
+
+#[derive(Default)]
+struct Watched {
+    error: Option<String>,
+    lost: Option<String>,
+}
+
+impl DeviceWatch {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let watch = DeviceWatch::default();
+        let errors = watch.clone();
+        device.on_uncaptured_error(Box::new(move |e| {
+            let mut watched = errors.0.lock().unwrap();
+            watched.error.get_or_insert_with(|| e.to_string());
+        }));
+        // Dropping the device destroys it, which is not a loss
+        let lost = watch.clone();
+        device.set_device_lost_callback(move |reason, message| {
+            if reason != wgpu::DeviceLostReason::Destroyed {
+                lost.0.lock().unwrap().lost.get_or_insert(message);
+            }
+        });
+        watch
+    }
+
+    /// Why the device was lost, if it was.
+    pub fn lost(&self) -> Option<String> {
+        self.0.lock().unwrap().lost.clone()
+    }
+
+    /// [`FdtdError::DeviceLost`] once the device is lost, else
+    /// [`FdtdError::Gpu`] for an uncaptured error, while `doing`.
+    pub fn check(&self, doing: impl FnOnce() -> String) -> Result<(), FdtdError> {
+        let mut watched = self.0.lock().unwrap();
+        if let Some(reason) = &watched.lost {
+            return Err(FdtdError::DeviceLost(reason.clone()));
+        }
+        match watched.error.take() {
+            Some(message) => Err(FdtdError::Gpu {
+                during: doing(),
+                message,
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Bytes of an upload buffer of [`Uploads`], unless a write needs more.
+const UPLOAD_CHUNK: u64 = 64 * 1024;
+
+/// Buffer writes in order with the passes of a command buffer.
+/// `queue.write_buffer` lands before the whole of the next submission,
+/// which is wrong for the per-step values (lumped drives, parameters, DFT
+/// factors) of a submission of several steps: here the bytes are gathered
+/// into upload buffers written once by [`submit`](Uploads::submit), and
+/// each write is a copy out of them encoded where it was asked for.
+pub struct Uploads {
+    device: wgpu::Device,
+    /// Upload buffers with the bytes gathered for each, reused across
+    /// submissions.
+    chunks: Vec<(wgpu::Buffer, Vec<u8>)>,
+    /// The chunk being filled.
+    current: usize,
+}
+
+impl Uploads {
+    pub fn new(device: &wgpu::Device) -> Self {
+        Uploads {
+            device: device.clone(),
+            chunks: Vec::new(),
+            current: 0,
+        }
+    }
+
+    /// Write `data` at byte `offset` of `buffer` at this point of
+    /// `encoder`; both are multiples of four bytes.
+    pub fn write(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        buffer: &wgpu::Buffer,
+        offset: u64,
+        data: &[u8],
+    ) {
+        if data.is_empty() {
+            return;
+        }
+        let size = data.len() as u64;
+        let fits =
+            |(chunk, bytes): &(wgpu::Buffer, Vec<u8>)| bytes.len() as u64 + size <= chunk.size();
+        while self.chunks.get(self.current).is_some_and(|c| !fits(c)) {
+            self.current += 1;
+        }
+        if self.current == self.chunks.len() {
+            let chunk = self.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("uploads"),
+                size: size.max(UPLOAD_CHUNK),
+                usage: wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            self.chunks.push((chunk, Vec::new()));
+        }
+        let (chunk, bytes) = &mut self.chunks[self.current];
+        encoder.copy_buffer_to_buffer(chunk, bytes.len() as u64, buffer, offset, size);
+        bytes.extend_from_slice(data);
+    }
+
+    /// Upload the bytes of the writes encoded since the last call; before
+    /// the command buffers holding them are submitted.
+    pub fn submit(&mut self, queue: &wgpu::Queue) {
+        for (chunk, bytes) in &mut self.chunks {
+            if !bytes.is_empty() {
+                queue.write_buffer(chunk, 0, bytes);
+                bytes.clear();
+            }
+        }
+        self.current = 0;
+    }
+}
+
+/// Entries per axis of the WGSL `Spacing` tables.
+pub const MAX_CELLS: usize = 1024;
+
+/// Inverse primary and dual cell widths per index as uploaded to the WGSL
+/// `Spacing` uniform: `MAX_CELLS` entries each, x/y/z lanes.
+pub fn spacing_table(grid: &Grid) -> Vec<[f32; 4]> {
+    let mut table = vec![[0.0_f32; 4]; 2 * MAX_CELLS];
+    for axis in [Axis::X, Axis::Y, Axis::Z] {
+        assert!(
+            grid.cells(axis) as usize <= MAX_CELLS,
+            "at most {MAX_CELLS} cells per axis"
+        );
+        for i in 0..grid.cells(axis) {
+            table[i as usize][axis.lane()] = (1.0 / grid.width(axis, i)) as f32;
+            table[MAX_CELLS + i as usize][axis.lane()] = (1.0 / grid.dual_width(axis, i)) as f32;
+        }
+    }
+    table
+}
+
+pub fn bgl_uniform_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+pub fn bgl_storage_entry(binding: u32, read_only: bool) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+pub fn bg_entry(binding: u32, resource: wgpu::BindingResource<'_>) -> wgpu::BindGroupEntry<'_> {
+    wgpu::BindGroupEntry { binding, resource }
+}
+
+/// Compile a WGSL module and build a single-bind-group compute pipeline.
+pub fn compute_pipeline(
+    device: &wgpu::Device,
+    label: &str,
+    source: &str,
+    bgl: &wgpu::BindGroupLayout,
+) -> wgpu::ComputePipeline {
+    compute_pipeline_entry(device, label, source, bgl, "main")
+}
+
+/// [`compute_pipeline`] for a module with several entry points.
+pub fn compute_pipeline_entry(
+    device: &wgpu::Device,
+    label: &str,
+    source: &str,
+    bgl: &wgpu::BindGroupLayout,
+    entry: &str,
+) -> wgpu::ComputePipeline {
+    let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some(label),
+        source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(source)),
+    });
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some(label),
+        bind_group_layouts: &[bgl],
+        push_constant_ranges: &[],
+    });
+    device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some(label),
+        layout: Some(&layout),
+        module: &module,
+        entry_point: Some(entry),
+        compilation_options: Default::default(),
+        cache: None,
+    })
+}
+
+/// Number of 1D workgroups of `size` threads needed to cover `n` items.
+pub fn groups_1d(n: u32, size: u32) -> u32 {
+    n.div_ceil(size)
+}
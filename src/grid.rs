@@ -0,0 +1,235 @@
+//! Yee-grid geometry: dimensions, cell spacing and the time step.
+//!
+//! Spacing is uniform unless an axis carries a table of graded cell widths;
+//! the update kernels then use per-cell inverse spacings (primary widths for
+//! curl E, dual widths between cell centres for curl H).  Generators that
+//! sample on a regular lattice (random media, rough surfaces, thin layers)
+//! assume the uniform spacing.
+
+use serde::Serialize;
+
+use crate::error::FdtdError;
+
+/// Field component selector used by sub-cell models and probes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+impl Axis {
+    /// Lane of the component in a packed `vec4` coefficient (x=0, y=1, z=2).
+    pub const fn lane(self) -> usize {
+        match self {
+            Axis::X => 0,
+            Axis::Y => 1,
+            Axis::Z => 2,
+        }
+    }
+
+    /// The two axes tangential to a plane whose normal is `self`.
+    pub const fn tangential(self) -> (Axis, Axis) {
+        match self {
+            Axis::X => (Axis::Y, Axis::Z),
+            Axis::Y => (Axis::Z, Axis::X),
+            Axis::Z => (Axis::X, Axis::Y),
+        }
+    }
+}
+
+/// One of the six Yee field components.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum Field {
+    E(Axis),
+    H(Axis),
+}
+
+impl Field {
+    /// All components in buffer order.
+    pub const ALL: [Field; 6] = [
+        Field::E(Axis::X),
+        Field::E(Axis::Y),
+        Field::E(Axis::Z),
+        Field::H(Axis::X),
+        Field::H(Axis::Y),
+        Field::H(Axis::Z),
+    ];
+
+    /// Position in the (Ex, Ey, Ez, Hx, Hy, Hz) buffer order.
+    pub const fn index(self) -> usize {
+        match self {
+            Field::E(axis) => axis.lane(),
+            Field::H(axis) => 3 + axis.lane(),
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        ["Ex", "Ey", "Ez", "Hx", "Hy", "Hz"][self.index()]
+    }
+}
+
+/// Cartesian Yee grid, uniform or graded per axis.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct Grid {
+    pub nx: u32,
+    pub ny: u32,
+    pub nz: u32,
+    /// Uniform spacing, used on axes without a graded table.  With grading
+    /// this should be the finest cell so `dt` respects the Courant limit.
+    pub dx: f64,
+    pub dy: f64,
+    pub dz: f64,
+    pub dt: f64,
+    /// Graded cell widths per axis (one per cell), or `None` for uniform.
+    pub graded: [Option<&'static [f64]>; 3],
+    /// Physical position of the lower corner of cell (0, 0, 0); non-zero
+    /// for grids nested in another one.
+    pub origin: [f64; 3],
+}
+
+impl Grid {
+    /// `cells` cubic cells of side `spacing` along x, y and z, stepped by
+    /// `dt`, with the lower corner at the origin.
+    pub const fn uniform(cells: [u32; 3], spacing: f64, dt: f64) -> Grid {
+        Grid {
+            nx: cells[0],
+            ny: cells[1],
+            nz: cells[2],
+            dx: spacing,
+            dy: spacing,
+            dz: spacing,
+            dt,
+            graded: [None; 3],
+            origin: [0.0; 3],
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        (self.nx * self.ny * self.nz) as usize
+    }
+
+    pub fn idx(&self, i: u32, j: u32, k: u32) -> usize {
+        (i + self.nx * (j + self.ny * k)) as usize
+    }
+
+    /// [`Grid::idx`] of `cell`, which a caller passed in and may lie
+    /// outside.
+    pub fn cell_idx(&self, cell: [u32; 3]) -> Result<usize, FdtdError> {
+        let [i, j, k] = cell;
+        if i < self.nx && j < self.ny && k < self.nz {
+            Ok(self.idx(i, j, k))
+        } else {
+            Err(FdtdError::OutsideGrid(format!("cell {cell:?}")))
+        }
+    }
+
+    /// Check that a caller's `values` hold a whole component, one per cell.
+    pub fn check_field(&self, values: &[f32]) -> Result<(), FdtdError> {
+        if values.len() == self.total() {
+            Ok(())
+        } else {
+            Err(FdtdError::FieldLength {
+                expected: self.total(),
+                found: values.len(),
+            })
+        }
+    }
+
+    /// Uniform (nominal) cell spacing along `axis`.
+    pub fn spacing(&self, axis: Axis) -> f64 {
+        match axis {
+            Axis::X => self.dx,
+            Axis::Y => self.dy,
+            Axis::Z => self.dz,
+        }
+    }
+
+    /// Number of cells along `axis`.
+    pub fn cells(&self, axis: Axis) -> u32 {
+        match axis {
+            Axis::X => self.nx,
+            Axis::Y => self.ny,
+            Axis::Z => self.nz,
+        }
+    }
+
+    /// Width of cell `i` along `axis`.
+    pub fn width(&self, axis: Axis, i: u32) -> f64 {
+        match self.graded[axis.lane()] {
+            Some(w) => w[i as usize],
+            None => self.spacing(axis),
+        }
+    }
+
+    /// Distance between the centres of cells `i − 1` and `i` along `axis`
+    /// (the dual-cell width; the width of cell 0 at the lower boundary).
+    pub fn dual_width(&self, axis: Axis, i: u32) -> f64 {
+        if i == 0 {
+            self.width(axis, 0)
+        } else {
+            (self.width(axis, i - 1) + self.width(axis, i)) / 2.0
+        }
+    }
+
+    /// Position of the lower face of cell `i` along `axis` (`i` may equal
+    /// the cell count for the upper boundary).
+    pub fn node(&self, axis: Axis, i: u32) -> f64 {
+        self.origin[axis.lane()]
+            + match self.graded[axis.lane()] {
+                Some(w) => w[..i as usize].iter().sum(),
+                None => i as f64 * self.spacing(axis),
+            }
+    }
+
+    /// Lower corner of cell (i, j, k).
+    pub fn corner(&self, i: u32, j: u32, k: u32) -> [f64; 3] {
+        [
+            self.node(Axis::X, i),
+            self.node(Axis::Y, j),
+            self.node(Axis::Z, k),
+        ]
+    }
+
+    /// Half-open range of cells along `axis` overlapping [`lo`, `hi`],
+    /// padded by one cell on either side.
+    pub fn cell_range(&self, axis: Axis, lo: f64, hi: f64) -> std::ops::Range<u32> {
+        let n = self.cells(axis);
+        let first = (0..n).find(|&i| self.node(axis, i + 1) >= lo).unwrap_or(n);
+        let last = (first..n).find(|&i| self.node(axis, i) > hi).unwrap_or(n);
+        first.saturating_sub(1)..(last + 1).min(n)
+    }
+}
+
+/// `N` cell widths graded from `coarse` down to `fine` on cells
+/// [`lo`, `hi`), growing by `ratio` per cell on either side of the fine
+/// region (a ratio ≤ 1.3 keeps the dispersion error of the transition low).
+pub const fn graded_widths<const N: usize>(
+    coarse: f64,
+    fine: f64,
+    lo: usize,
+    hi: usize,
+    ratio: f64,
+) -> [f64; N] {
+    let mut w = [coarse; N];
+    let mut i = 0;
+    while i < N {
+        // Distance in cells from the fine region.
+        let d = if i < lo {
+            lo - i
+        } else if i >= hi {
+            i + 1 - hi
+        } else {
+            0
+        };
+        let mut width = fine;
+        let mut n = 0;
+        while n < d && width < coarse {
+            width *= ratio;
+            n += 1;
+        }
+        w[i] = if width < coarse { width } else { coarse };
+        i += 1;
+    }
+    w
+}
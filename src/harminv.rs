@@ -0,0 +1,515 @@
+//! Resonance extraction from probe time signals by harmonic inversion.
+//!
+//! A ringing signal is modelled as a sum of decaying exponentials
+//!
+//!   y(t) = Σ a_k·e^{(−α_k + jω_k)t} + c.c.,
+//!
+//! whose complex frequencies give each mode's frequency f = ω/2π and quality
+//! factor Q = ω/2α far more accurately than the peaks of an FFT of the same,
+//! usually short, record.  The band [f_min, f_max] is isolated by a windowed
+//! sinc low-pass and decimation; the decimated samples are fitted with the
+//! matrix-pencil method (Hua & Sarkar, 1990): the singular values of their
+//! Hankel matrix above `tolerance` × the largest set the number of modes,
+//! and the eigenvalues z = e^{(−α + jω)Δ} of the reduced pencil their
+//! complex frequencies.  Amplitudes and phases come from a least-squares fit
+//! of the modes to the samples, referred to t = 0 although the first sample
+//! is taken at Δt.  Modes outside the band, growing in time, ringing for
+//! less than [`MIN_Q`] or below the fit residual (a relative error above
+//! [`MAX_ERROR`]) are discarded as spurious.
+
+use std::f64::consts::PI;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use rustfft::num_complex::Complex64;
+use serde::Serialize;
+
+/// Band and model-order threshold of the analysis.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct HarmonicInversion {
+    /// Frequency band (Hz).
+    pub f_min: f64,
+    pub f_max: f64,
+    /// Relative singular value below which components count as noise,
+    /// e.g. 1e-4.
+    pub tolerance: f64,
+}
+
+/// One extracted mode.
+#[derive(Copy, Clone, Debug)]
+pub struct Resonance {
+    /// Frequency (Hz).
+    pub frequency: f64,
+    /// Amplitude decay rate α (1/s).
+    pub decay: f64,
+    pub q: f64,
+    /// Real amplitude and phase (rad) of the mode at t = 0.
+    pub amplitude: f64,
+    pub phase: f64,
+    /// Fit residual, or the noise floor set by the tolerance if larger,
+    /// relative to the mode's own part of the signal.
+    pub error: f64,
+}
+
+/// Largest number of decimated samples in a Hankel row.
+const MAX_PENCIL: usize = 150;
+
+/// Lowest Q of a mode: anything more damped is not a resonance.
+pub const MIN_Q: f64 = 1.0;
+
+/// Largest relative fit error of a mode.
+pub const MAX_ERROR: f64 = 0.1;
+
+/// Low-pass `signal` below `cutoff` (in units of the sample rate) with a
+/// Hann-windowed sinc and keep every `factor`-th sample whose filter window
+/// lies inside the signal; returns the index of the first kept sample and
+/// the samples.
+fn decimate(signal: &[f64], cutoff: f64, factor: usize) -> (usize, Vec<f64>) {
+    if factor == 1 {
+        return (0, signal.to_vec());
+    }
+    let half = 4 * factor as isize;
+    let taps: Vec<f64> = (-half..=half)
+        .map(|k| {
+            let x = k as f64;
+            let sinc = if k == 0 {
+                2.0 * cutoff
+            } else {
+                (2.0 * PI * cutoff * x).sin() / (PI * x)
+            };
+            sinc * 0.5 * (1.0 + (PI * x / (half + 1) as f64).cos())
+        })
+        .collect();
+    let (first, end) = (half as usize, signal.len().saturating_sub(half as usize));
+    let samples = (first..end)
+        .step_by(factor)
+        .map(|n| {
+            let window = &signal[n - first..=n + first];
+            taps.iter().zip(window).map(|(w, x)| w * x).sum()
+        })
+        .collect();
+    (first, samples)
+}
+
+/// Right singular vectors (as columns of an n×n row-major matrix) and
+/// singular values, largest first, of the m×n row-major matrix `a` by
+/// one-sided Jacobi rotations.
+fn svd(a: &[f64], m: usize, n: usize) -> (Vec<f64>, Vec<f64>) {
+    let mut a = a.to_vec();
+    let mut v: Vec<f64> = (0..n * n)
+        .map(|ij| if ij / n == ij % n { 1.0 } else { 0.0 })
+        .collect();
+    for _ in 0..60 {
+        let mut rotated = false;
+        for p in 0..n {
+            for q in p + 1..n {
+                let (mut alpha, mut beta, mut gamma) = (0.0, 0.0, 0.0);
+                for i in 0..m {
+                    let (x, y) = (a[i * n + p], a[i * n + q]);
+                    alpha += x * x;
+                    beta += y * y;
+                    gamma += x * y;
+                }
+                if gamma.abs() <= 1e-15 * (alpha * beta).sqrt() || gamma == 0.0 {
+                    continue;
+                }
+                rotated = true;
+                let zeta = (beta - alpha) / (2.0 * gamma);
+                let t = zeta.signum() / (zeta.abs() + (1.0 + zeta * zeta).sqrt());
+                let c = 1.0 / (1.0 + t * t).sqrt();
+                let s = c * t;
+                for (rows, stride) in [(m, &mut a), (n, &mut v)] {
+                    for i in 0..rows {
+                        let (x, y) = (stride[i * n + p], stride[i * n + q]);
+                        stride[i * n + p] = c * x - s * y;
+                        stride[i * n + q] = s * x + c * y;
+                    }
+                }
+            }
+        }
+        if !rotated {
+            break;
+        }
+    }
+    let norms: Vec<f64> = (0..n)
+        .map(|j| (0..m).map(|i| a[i * n + j].powi(2)).sum::<f64>().sqrt())
+        .collect();
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by(|&p, &q| norms[q].total_cmp(&norms[p]));
+    let sorted_v = (0..n * n).map(|ij| v[ij / n * n + order[ij % n]]).collect();
+    (sorted_v, order.iter().map(|&j| norms[j]).collect())
+}
+
+/// Solve the n×n row-major system `a`·x = `b` (each of the `k` columns of
+/// the n×k `b`) by Gaussian elimination with partial pivoting.
+fn solve(mut a: Vec<Complex64>, mut b: Vec<Complex64>, n: usize, k: usize) -> Vec<Complex64> {
+    for col in 0..n {
+        let pivot = (col..n)
+            .max_by(|&p, &q| a[p * n + col].norm().total_cmp(&a[q * n + col].norm()))
+            .unwrap();
+        for j in 0..n {
+            a.swap(col * n + j, pivot * n + j);
+        }
+        for j in 0..k {
+            b.swap(col * k + j, pivot * k + j);
+        }
+        let d = a[col * n + col];
+        if d.norm() == 0.0 {
+            continue;
+        }
+        for row in col + 1..n {
+            let factor = a[row * n + col] / d;
+            for j in col..n {
+                let x = a[col * n + j];
+                a[row * n + j] -= factor * x;
+            }
+            for j in 0..k {
+                let x = b[col * k + j];
+                b[row * k + j] -= factor * x;
+            }
+        }
+    }
+    for row in (0..n).rev() {
+        for j in 0..k {
+            let mut x = b[row * k + j];
+            for c in row + 1..n {
+                x -= a[row * n + c] * b[c * k + j];
+            }
+            b[row * k + j] = x / a[row * n + row];
+        }
+    }
+    b
+}
+
+/// Eigenvalues of the n×n row-major complex matrix `a`: Householder
+/// reduction to Hessenberg form, then shifted QR with deflation.
+fn eigenvalues(mut a: Vec<Complex64>, n: usize) -> Vec<Complex64> {
+    let zero = Complex64::new(0.0, 0.0);
+    for k in 0..n.saturating_sub(2) {
+        let mut v: Vec<Complex64> = (k + 1..n).map(|i| a[i * n + k]).collect();
+        let norm = v.iter().map(|x| x.norm_sqr()).sum::<f64>().sqrt();
+        if norm == 0.0 {
+            continue;
+        }
+        let phase = if v[0].norm() > 0.0 {
+            v[0] / v[0].norm()
+        } else {
+            Complex64::new(1.0, 0.0)
+        };
+        v[0] += phase * norm;
+        let vnorm = v.iter().map(|x| x.norm_sqr()).sum::<f64>().sqrt();
+        v.iter_mut().for_each(|x| *x /= vnorm);
+        // A ← (I − 2vvᴴ) A (I − 2vvᴴ) on rows and columns k+1..n
+        for j in 0..n {
+            let dot: Complex64 = (k + 1..n).map(|i| v[i - k - 1].conj() * a[i * n + j]).sum();
+            for i in k + 1..n {
+                a[i * n + j] -= 2.0 * v[i - k - 1] * dot;
+            }
+        }
+        for i in 0..n {
+            let dot: Complex64 = (k + 1..n).map(|j| a[i * n + j] * v[j - k - 1]).sum();
+            for j in k + 1..n {
+                a[i * n + j] -= 2.0 * dot * v[j - k - 1].conj();
+            }
+        }
+    }
+
+    let mut values = Vec::new();
+    let mut hi = n;
+    let mut iterations = 0;
+    while hi > 0 {
+        // Deflate at the lowest negligible subdiagonal of the active block
+        let mut lo = hi - 1;
+        while lo > 0 {
+            let scale = a[lo * n + lo].norm() + a[(lo - 1) * n + lo - 1].norm();
+            if a[lo * n + lo - 1].norm() <= 1e-14 * scale.max(f64::MIN_POSITIVE) {
+                a[lo * n + lo - 1] = zero;
+                break;
+            }
+            lo -= 1;
+        }
+        if lo == hi - 1 || iterations > 100 * n {
+            values.push(a[(hi - 1) * n + hi - 1]);
+            hi -= 1;
+            iterations = 0;
+            continue;
+        }
+        iterations += 1;
+
+        // Wilkinson shift from the trailing 2×2, exceptional every 11th step
+        let (p, q, r, s) = (
+            a[(hi - 2) * n + hi - 2],
+            a[(hi - 2) * n + hi - 1],
+            a[(hi - 1) * n + hi - 2],
+            a[(hi - 1) * n + hi - 1],
+        );
+        let half = 0.5 * (p - s);
+        let root = (half * half + q * r).sqrt();
+        let mut mu = if (half + root).norm() >= (half - root).norm() {
+            s - q * r / (half + root)
+        } else {
+            s - q * r / (half - root)
+        };
+        if !mu.is_finite() {
+            mu = s;
+        }
+        if iterations % 11 == 0 {
+            mu = s + a[(hi - 1) * n + hi - 2].norm();
+        }
+
+        for i in lo..hi {
+            a[i * n + i] -= mu;
+        }
+        let mut rotations = Vec::new();
+        for k in lo..hi - 1 {
+            let (x, y) = (a[k * n + k], a[(k + 1) * n + k]);
+            let norm = (x.norm_sqr() + y.norm_sqr()).sqrt();
+            let (c, s) = if norm == 0.0 {
+                (Complex64::new(1.0, 0.0), zero)
+            } else {
+                (x / norm, y / norm)
+            };
+            for j in k..hi {
+                let (u, w) = (a[k * n + j], a[(k + 1) * n + j]);
+                a[k * n + j] = c.conj() * u + s.conj() * w;
+                a[(k + 1) * n + j] = -s * u + c * w;
+            }
+            rotations.push((c, s));
+        }
+        for (k, (c, s)) in (lo..).zip(rotations) {
+            for i in lo..(k + 2).min(hi) {
+                let (u, w) = (a[i * n + k], a[i * n + k + 1]);
+                a[i * n + k] = u * c + w * s;
+                a[i * n + k + 1] = -u * s.conj() + w * c.conj();
+            }
+        }
+        for i in lo..hi {
+            a[i * n + i] += mu;
+        }
+    }
+    values
+}
+
+/// Modes of `signal`, sampled every `dt` from t = Δt (as the probes are),
+/// in the band of `spec`, by increasing frequency.
+pub fn analyze(signal: &[f64], dt: f64, spec: &HarmonicInversion) -> Vec<Resonance> {
+    // Decimate to about 2.5 samples per period of the band edge
+    let factor = ((1.0 / (2.5 * spec.f_max * dt)).floor() as usize).max(1);
+    let cutoff = (1.2 * spec.f_max * dt).min(0.5);
+    let (first, y) = decimate(signal, cutoff, factor);
+    let step = factor as f64 * dt;
+    let count = y.len();
+    let pencil = (count / 3).min(MAX_PENCIL);
+    if pencil < 2 {
+        return Vec::new();
+    }
+
+    // Hankel matrix of the samples, (count − pencil) × (pencil + 1)
+    let (rows, cols) = (count - pencil, pencil + 1);
+    let hankel: Vec<f64> = (0..rows * cols)
+        .map(|ij| y[ij / cols + ij % cols])
+        .collect();
+    let (v, sigma) = svd(&hankel, rows, cols);
+    let order = sigma
+        .iter()
+        .take_while(|&&s| s > spec.tolerance * sigma[0])
+        .count()
+        .min(pencil);
+    if order == 0 {
+        return Vec::new();
+    }
+
+    // Reduced pencil: V₁ᴴV₁·X = V₁ᴴV₂ with V₁, V₂ the first and last
+    // `pencil` rows of the leading right singular vectors
+    let c = |x: f64| Complex64::new(x, 0.0);
+    let at = |row: usize, k: usize| v[row * cols + k];
+    let gram = (0..order * order)
+        .map(|ij| {
+            c((0..pencil)
+                .map(|r| at(r, ij / order) * at(r, ij % order))
+                .sum())
+        })
+        .collect();
+    let cross = (0..order * order)
+        .map(|ij| {
+            c((0..pencil)
+                .map(|r| at(r, ij / order) * at(r + 1, ij % order))
+                .sum())
+        })
+        .collect();
+    let poles: Vec<Complex64> = eigenvalues(solve(gram, cross, order, order), order)
+        .into_iter()
+        .filter(|z| z.is_finite() && z.norm() < 1.0)
+        .collect();
+    let order = poles.len();
+
+    // Amplitudes by least squares on the decimated samples, taken at
+    // t = n·step + (first + 1)·Δt, with the (spurious) growing poles left
+    // out as they would swamp the fit
+    let offset = (first + 1) as f64 * dt / step;
+    let basis = |n: usize, k: usize| (poles[k].ln() * (n as f64 + offset)).exp();
+    let normal = (0..order * order)
+        .map(|ij| {
+            (0..count)
+                .map(|n| basis(n, ij / order).conj() * basis(n, ij % order))
+                .sum()
+        })
+        .collect();
+    let rhs = (0..order)
+        .map(|k| (0..count).map(|n| basis(n, k).conj() * y[n]).sum())
+        .collect();
+    let amplitudes = solve(normal, rhs, order, 1);
+
+    // The residual of the whole fit, floored at the noise the tolerance
+    // stands for, against each mode's own energy
+    let residual: f64 = (0..count)
+        .map(|n| {
+            let fit: Complex64 = (0..order).map(|k| amplitudes[k] * basis(n, k)).sum();
+            (y[n] - fit.re).powi(2)
+        })
+        .sum();
+    let floor = spec.tolerance.powi(2) * y.iter().map(|x| x * x).sum::<f64>();
+    let energy = |k: usize| -> f64 {
+        (0..count)
+            .map(|n| (amplitudes[k] * basis(n, k)).norm_sqr())
+            .sum()
+    };
+
+    let mut modes: Vec<Resonance> = poles
+        .iter()
+        .zip(&amplitudes)
+        .enumerate()
+        .filter_map(|(k, (z, a))| {
+            let omega = z.arg() / step;
+            let decay = -z.norm().ln() / step;
+            let frequency = omega / (2.0 * PI);
+            let q = omega / (2.0 * decay);
+            let error = (residual.max(floor) / energy(k)).sqrt();
+            let inside = frequency >= spec.f_min && frequency <= spec.f_max;
+            let resonant = decay > 0.0 && q >= MIN_Q && error <= MAX_ERROR;
+            (inside && resonant && a.is_finite()).then(|| Resonance {
+                frequency,
+                decay,
+                q,
+                // The conjugate pole carries the other half of the cosine
+                amplitude: 2.0 * a.norm(),
+                phase: a.arg(),
+                error,
+            })
+        })
+        .collect();
+    modes.sort_by(|p, q| p.frequency.total_cmp(&q.frequency));
+    modes
+}
+
+/// Write `<name>_resonances.csv` with the modes of one signal.
+pub fn write_resonances(dir: &Path, name: &str, modes: &[Resonance]) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!("{name}_resonances.csv"));
+    let mut file = BufWriter::new(fs::File::create(path)?);
+    writeln!(file, "frequency,decay,q,amplitude,phase,error")?;
+    for m in modes {
+        writeln!(
+            file,
+            "{:e},{:e},{:e},{:e},{:.6},{:e}",
+            m.frequency, m.decay, m.q, m.amplitude, m.phase, m.error
+        )?;
+    }
+    file.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DT: f64 = 1e-12;
+
+    /// Σ a·e^{−αt}·cos(2πft + φ) at t = (n + 1)Δt, as a probe samples it.
+    fn ringing(modes: &[(f64, f64, f64, f64)], steps: usize) -> Vec<f64> {
+        (0..steps)
+            .map(|n| {
+                let t = (n + 1) as f64 * DT;
+                modes
+                    .iter()
+                    .map(|&(f, q, a, phase)| {
+                        let decay = PI * f / q;
+                        a * (-decay * t).exp() * (2.0 * PI * f * t + phase).cos()
+                    })
+                    .sum()
+            })
+            .collect()
+    }
+
+    fn band(f_min: f64, f_max: f64) -> HarmonicInversion {
+        HarmonicInversion {
+            f_min,
+            f_max,
+            tolerance: 1e-4,
+        }
+    }
+
+    #[test]
+    fn recovers_frequency_q_and_phase_at_t_zero() {
+        let signal = ringing(&[(20e9, 200.0, 1.0, 0.7), (31e9, 80.0, 0.5, -1.2)], 2000);
+        let modes = analyze(&signal, DT, &band(10e9, 60e9));
+        assert_eq!(modes.len(), 2, "{modes:?}");
+        let expected = [(20e9, 200.0, 1.0, 0.7), (31e9, 80.0, 0.5, -1.2)];
+        for (m, (f, q, a, phase)) in modes.iter().zip(expected) {
+            assert!((m.frequency / f - 1.0).abs() < 1e-6, "{m:?}");
+            assert!((m.q / q - 1.0).abs() < 1e-3, "{m:?}");
+            // within the ripple of the decimating low-pass
+            assert!((m.amplitude / a - 1.0).abs() < 1e-2, "{m:?}");
+            assert!((m.phase - phase).abs() < 1e-3, "{m:?}");
+            assert!(m.error < 1e-2, "{m:?}");
+        }
+    }
+
+    #[test]
+    fn modes_outside_the_band_are_left_out() {
+        let signal = ringing(&[(20e9, 200.0, 1.0, 0.0), (31e9, 80.0, 1.0, 0.0)], 2000);
+        let modes = analyze(&signal, DT, &band(25e9, 40e9));
+        assert_eq!(modes.len(), 1, "{modes:?}");
+        assert!((modes[0].frequency / 31e9 - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn noise_gives_no_spurious_modes() {
+        // A deterministic pseudo-random noise at 1e-3 of the mode
+        let mut state = 12345_u64;
+        let mut noise = || {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+            (state >> 11) as f64 / (1u64 << 53) as f64 - 0.5
+        };
+        let signal: Vec<f64> = ringing(&[(20e9, 100.0, 1.0, 0.3)], 2000)
+            .into_iter()
+            .map(|x| x + 2e-3 * noise())
+            .collect();
+        let spec = HarmonicInversion {
+            tolerance: 1e-6,
+            ..band(5e9, 45e9)
+        };
+        let modes = analyze(&signal, DT, &spec);
+        assert_eq!(modes.len(), 1, "{modes:?}");
+        assert!((modes[0].frequency / 20e9 - 1.0).abs() < 1e-4);
+        assert!(modes[0].q > MIN_Q && modes[0].error <= MAX_ERROR);
+    }
+
+    #[test]
+    fn a_heavily_damped_pulse_is_not_a_resonance() {
+        let signal = ringing(&[(20e9, 0.4, 1.0, 0.0)], 2000);
+        assert!(analyze(&signal, DT, &band(5e9, 45e9)).is_empty());
+    }
+
+    #[test]
+    fn the_csv_lists_every_mode() {
+        let dir = tempfile::tempdir().unwrap();
+        let signal = ringing(&[(20e9, 200.0, 1.0, 0.0)], 2000);
+        let modes = analyze(&signal, DT, &band(10e9, 40e9));
+        write_resonances(dir.path(), "cavity", &modes).unwrap();
+        let csv = fs::read_to_string(dir.path().join("cavity_resonances.csv")).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "frequency,decay,q,amplitude,phase,error");
+        assert_eq!(lines.len(), 1 + modes.len());
+    }
+}
@@ -0,0 +1,137 @@
+//! Hybrid implicit–explicit (HIE) FDTD for thin layers.
+//!
+//! One axis — normally the one a coating, sheet or 2D material is resolved
+//! along — is treated implicitly and the other two explicitly (after Chen &
+//! Wang, 2008).  The two field components along the implicit axis d never
+//! see ∂_d and keep the explicit leapfrog at the half step; each transverse
+//! E component forms a Crank–Nicolson pair with its partner H through ∂_d,
+//! solved as one tridiagonal system per line along d.  The Courant limit
+//! then only involves the cell widths of the explicit axes, so a few very
+//! thin cells along d no longer dictate Δt for the whole grid.
+//!
+//! Like ADI, the pass replaces the explicit kernels and supports plain
+//! objects only.
+
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+
+use crate::adi::LineSolver;
+use crate::gpu::{bg_entry, bgl_storage_entry, bgl_uniform_entry, compute_pipeline_entry};
+use crate::grid::{Axis, Grid};
+use crate::materials::Coefficients;
+
+/// Explicit-update parameters (must match WGSL `HieParams`).
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct HieParams {
+    nx: u32,
+    ny: u32,
+    nz: u32,
+    d: u32,
+}
+
+/// GPU resources of the HIE update.
+pub struct HiePass {
+    lines: LineSolver,
+    update_hd: wgpu::ComputePipeline,
+    update_ed: wgpu::ComputePipeline,
+    explicit: wgpu::BindGroup,
+    /// Line-solve bind groups of the two transverse E components.
+    groups: [(usize, wgpu::BindGroup); 2],
+    axis: usize,
+    dims: [u32; 3],
+}
+
+impl HiePass {
+    /// `coeffs` holds the scene's coefficients on `grid` (full Δt).
+    pub fn new(
+        device: &wgpu::Device,
+        grid: &Grid,
+        coeffs: &Coefficients,
+        axis: Axis,
+        e: [&wgpu::Buffer; 3],
+        h: [&wgpu::Buffer; 3],
+        spacing: &wgpu::Buffer,
+    ) -> Self {
+        let d = axis.lane();
+        let (next, prev) = ((d + 1) % 3, (d + 2) % 3);
+        let lines = LineSolver::new(device, grid, coeffs, e);
+        let groups = [next, prev].map(|c| (c, lines.bind_group(device, (c, d), 0.5, h, spacing)));
+
+        let params = HieParams {
+            nx: grid.nx,
+            ny: grid.ny,
+            nz: grid.nz,
+            d: d as u32,
+        };
+        let params = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("hie_params"),
+            contents: bytemuck::bytes_of(&params),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("hie_bgl"),
+            entries: &[
+                bgl_uniform_entry(0),
+                bgl_storage_entry(1, false),
+                bgl_storage_entry(2, true),
+                bgl_storage_entry(3, true),
+                bgl_storage_entry(4, false),
+                bgl_storage_entry(5, true),
+                bgl_storage_entry(6, true),
+                bgl_storage_entry(7, true),
+                bgl_uniform_entry(9),
+            ],
+        });
+        let explicit = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("bg_hie"),
+            layout: &bgl,
+            entries: &[
+                bg_entry(0, params.as_entire_binding()),
+                bg_entry(1, e[d].as_entire_binding()),
+                bg_entry(2, e[next].as_entire_binding()),
+                bg_entry(3, e[prev].as_entire_binding()),
+                bg_entry(4, h[d].as_entire_binding()),
+                bg_entry(5, h[next].as_entire_binding()),
+                bg_entry(6, h[prev].as_entire_binding()),
+                bg_entry(7, lines.coef.as_entire_binding()),
+                bg_entry(9, spacing.as_entire_binding()),
+            ],
+        });
+        let source = include_str!("shaders/hie.wgsl");
+        let update_hd = compute_pipeline_entry(device, "hie_update_hd", source, &bgl, "update_hd");
+        let update_ed = compute_pipeline_entry(device, "hie_update_ed", source, &bgl, "update_ed");
+
+        HiePass {
+            lines,
+            update_hd,
+            update_ed,
+            explicit,
+            groups,
+            axis: d,
+            dims: [grid.nx, grid.ny, grid.nz],
+        }
+    }
+
+    /// One HIE step: explicit E_d and H_d, then the two implicit pairs.
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder) {
+        let [cx, cy, cz] = self.dims.map(|n| n.div_ceil(4));
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("HIE explicit"),
+                timestamp_writes: None,
+            });
+            pass.set_bind_group(0, &self.explicit, &[]);
+            pass.set_pipeline(&self.update_hd);
+            pass.dispatch_workgroups(cx, cy, cz);
+            pass.set_pipeline(&self.update_ed);
+            pass.dispatch_workgroups(cx, cy, cz);
+        }
+        let lines: Vec<_> = self
+            .groups
+            .iter()
+            .map(|(c, group)| (*c, self.axis, group))
+            .collect();
+        self.lines.encode(encoder, &lines);
+    }
+}
@@ -0,0 +1,157 @@
+//! Per-step user callbacks.
+//!
+//! [`Simulation::on_step`](crate::ComputeBackend::on_step) registers a closure
+//! that is called after every `every`-th step with a [`StepState`]: the
+//! steps taken and the time, the grid, reads of the f32 field components
+//! (whole or one cell, copied back on demand) and writes into them, which
+//! the device applies before the next step.  Custom diagnostics, adaptive
+//! sources or the coupling to another solver go here instead of into a fork
+//! of the time loop.
+//!
+//! After step n the E components hold E at (n + 1)·Δt and the H components
+//! H at (n + ½)·Δt.  Setting a cell overrides what the update wrote there
+//! (a hard source); adding to it superposes (a soft source).  On the GPU
+//! reads wait for the device, so a callback that reads every step runs at
+//! readback speed, and its time is not counted as stepping; a failed copy
+//! back is a [`FdtdError::MapFailed`].  On the CPU (see [`crate::cpu`])
+//! they are plain copies of the solver's fields.
+
+use ndarray::{Array3, ShapeBuilder};
+
+use crate::cpu::CpuSolver;
+use crate::error::FdtdError;
+use crate::gpu;
+use crate::grid::{Field, Grid};
+
+/// A callback of [`Simulation::on_step`](crate::ComputeBackend::on_step).
+pub type StepCallback = Box<dyn FnMut(&mut StepState) + Send>;
+
+/// The fields between two steps.
+pub struct StepState<'a> {
+    pub(crate) fields: StepFields<'a>,
+    pub(crate) grid: &'a Grid,
+    pub(crate) steps: u32,
+    pub(crate) stop: bool,
+}
+
+/// Where the fields of a [`StepState`] are.
+pub(crate) enum StepFields<'a> {
+    /// The f32 field buffers of a GPU run.
+    Device {
+        device: &'a wgpu::Device,
+        queue: &'a wgpu::Queue,
+        buffers: &'a [wgpu::Buffer; 6],
+    },
+    /// The solver of a CPU run.
+    Host(&'a mut CpuSolver),
+}
+
+impl StepState<'_> {
+    /// Steps taken so far.
+    pub fn steps(&self) -> u32 {
+        self.steps
+    }
+
+    /// Time of the E fields, n·Δt after n steps.
+    pub fn time(&self) -> f64 {
+        self.steps as f64 * self.grid.dt
+    }
+
+    /// The grid as stepped (Δt as selected).
+    pub fn grid(&self) -> &Grid {
+        self.grid
+    }
+
+    /// A copy of one component, x fastest.
+    pub fn field(&self, field: Field) -> Result<Vec<f32>, FdtdError> {
+        match &self.fields {
+            StepFields::Device {
+                device,
+                queue,
+                buffers,
+            } => {
+                let buffer = f32_buffer(self.grid, buffers, field)?;
+                gpu::read_f32(device, queue, buffer, 0, buffer.size())
+            }
+            StepFields::Host(solver) => Ok(solver.field(field).to_vec()),
+        }
+    }
+
+    /// [`StepState::field`] as an array indexed `[i, j, k]`.
+    pub fn read_field(&self, field: Field) -> Result<Array3<f32>, FdtdError> {
+        let g = self.grid;
+        let shape = (g.nx as usize, g.ny as usize, g.nz as usize);
+        Ok(Array3::from_shape_vec(shape.f(), self.field(field)?).expect("a full f32 field"))
+    }
+
+    /// One component at one cell.
+    pub fn value(&self, field: Field, cell: [u32; 3]) -> Result<f32, FdtdError> {
+        let id = self.grid.cell_idx(cell)?;
+        match &self.fields {
+            StepFields::Device {
+                device,
+                queue,
+                buffers,
+            } => {
+                let buffer = f32_buffer(self.grid, buffers, field)?;
+                Ok(gpu::read_f32(device, queue, buffer, 4 * id as u64, 4)?[0])
+            }
+            StepFields::Host(solver) => Ok(solver.field(field)[id]),
+        }
+    }
+
+    /// Overwrite one component at one cell.
+    pub fn set(&mut self, field: Field, cell: [u32; 3], value: f32) -> Result<(), FdtdError> {
+        let id = self.grid.cell_idx(cell)?;
+        match &mut self.fields {
+            StepFields::Device { queue, buffers, .. } => {
+                let buffer = f32_buffer(self.grid, buffers, field)?;
+                queue.write_buffer(buffer, 4 * id as u64, bytemuck::bytes_of(&value));
+            }
+            StepFields::Host(solver) => solver.set(field, id, value),
+        }
+        Ok(())
+    }
+
+    /// Add to one component at one cell.
+    pub fn add(&mut self, field: Field, cell: [u32; 3], value: f32) -> Result<(), FdtdError> {
+        let old = self.value(field, cell)?;
+        self.set(field, cell, old + value)
+    }
+
+    /// Overwrite a whole component, x fastest.
+    pub fn set_field(&mut self, field: Field, values: &[f32]) -> Result<(), FdtdError> {
+        self.grid.check_field(values)?;
+        match &mut self.fields {
+            StepFields::Device { queue, buffers, .. } => {
+                let buffer = f32_buffer(self.grid, buffers, field)?;
+                queue.write_buffer(buffer, 0, bytemuck::cast_slice(values));
+            }
+            StepFields::Host(solver) => solver.field_mut(field).copy_from_slice(values),
+        }
+        Ok(())
+    }
+
+    /// End [`Simulation::run`](crate::ComputeBackend::run) after this step.
+    pub fn stop(&mut self) {
+        self.stop = true;
+    }
+}
+
+/// The buffer of `field`, unless another precision runs alone and it holds
+/// a single value.
+fn f32_buffer<'a>(
+    grid: &Grid,
+    buffers: &'a [wgpu::Buffer; 6],
+    field: Field,
+) -> Result<&'a wgpu::Buffer, FdtdError> {
+    let buffer = &buffers[field.index()];
+    if buffer.size() == 4 * grid.total() as u64 {
+        Ok(buffer)
+    } else {
+        Err(FdtdError::FieldLength {
+            expected: grid.total(),
+            found: buffer.size() as usize / 4,
+        })
+    }
+}
@@ -0,0 +1,36 @@
+//! Stopping a run cleanly on Ctrl-C.
+//!
+//! [`request`] raises a process-wide flag that
+//! [`Simulation::run`](crate::ComputeBackend::run) checks before each step: the
+//! step under way completes and the loop returns early.  [`run_scene`]
+//! then writes a checkpoint (see [`crate::checkpoint`]) and the analyses of
+//! the steps taken, flushes the output sinks and fails with
+//! [`FdtdError::Interrupted`], so an hours-long run is not lost to a stray
+//! key press.
+//!
+//! The `fdtd_3d` binary calls [`request`] on SIGINT and SIGTERM; a second
+//! signal exits at once.  An embedding application can install its own
+//! handler, or call [`request`] from another thread.
+//!
+//! [`run_scene`]: crate::run_scene
+//! [`FdtdError::Interrupted`]: crate::FdtdError::Interrupted
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Ask the running simulation to stop after its current step.  Returns
+/// whether a stop had been requested already.
+pub fn request() -> bool {
+    REQUESTED.swap(true, Ordering::SeqCst)
+}
+
+/// Whether a stop has been requested.
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Forget a request, before another run in the same process.
+pub fn clear() {
+    REQUESTED.store(false, Ordering::SeqCst);
+}
@@ -0,0 +1,356 @@
+//! Spatial spectra of field planes, transformed on the GPU.
+//!
+//! A k-space monitor takes one component on a plane of cells every `every`
+//! steps, zero-pads it to powers of two nu × nv and transforms it with a
+//! radix-2 FFT in WGSL (`shaders/fft.wgsl`), adding the power |F(k_u, k_v)|²
+//! of every frame into an accumulator on the device.  Only the accumulated
+//! spectrum is read back, once at the end of the run, so the planes never
+//! leave the GPU.
+//!
+//! The result is the time-integrated angular spectrum of the plane: a
+//! plane wave e^{j(k_u·u + k_v·v)} shows up at (k_u, k_v), propagating
+//! waves inside the circle |k| ≤ ω/c and evanescent ones outside it.
+//! `<name>_kspace.csv` lists k_u, k_v (rad/m, centred on zero) and the
+//! power; `<name>_kspace.png` shows the power on a 40 dB logarithmic scale,
+//! k_u to the right and k_v upwards.  The k axes assume the uniform
+//! spacing of the plane's axes.
+
+use std::f64::consts::PI;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use bytemuck::{Pod, Zeroable};
+use serde::Serialize;
+use wgpu::util::DeviceExt;
+
+use crate::config::ConfigError;
+use crate::error::FdtdError;
+use crate::gpu::{
+    self, bg_entry, bgl_storage_entry, bgl_uniform_entry, compute_pipeline_entry, groups_1d,
+};
+use crate::grid::{Axis, Field, Grid};
+use crate::png;
+use crate::slices::diverging;
+
+/// Power spectrum of `field` on the plane `index` normal to `normal`.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct KSpaceMonitor {
+    pub name: &'static str,
+    pub field: Field,
+    pub normal: Axis,
+    pub index: u32,
+    /// Steps between transformed frames (the first at step 0).
+    pub every: u32,
+}
+
+impl KSpaceMonitor {
+    /// Fails unless the plane is inside `grid`, small enough for one
+    /// dispatch once padded, and frames are transformed.
+    pub fn check(&self, grid: &Grid) -> Result<(), ConfigError> {
+        let (u, v) = self.normal.tangential();
+        let len = [u, v]
+            .map(|a| grid.cells(a).next_power_of_two().max(2) as u64)
+            .iter()
+            .product::<u64>();
+        let problem = if self.index >= grid.cells(self.normal) {
+            format!("plane {} outside the grid", self.index)
+        } else if len > 64 * 65535 {
+            "plane too large".to_string()
+        } else if self.every == 0 {
+            "every must be at least 1".to_string()
+        } else {
+            return Ok(());
+        };
+        Err(ConfigError(format!(
+            "k-space monitor {}: {problem}",
+            self.name
+        )))
+    }
+}
+
+/// Transform parameters (must match WGSL `FftParams`).
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct FftParams {
+    size: [u32; 4],
+    plane: [u32; 4],
+    stage: [u32; 4],
+}
+
+/// GPU state of one monitor: a bind group per dispatch, in order.
+struct Transform {
+    spec: KSpaceMonitor,
+    size: [u32; 2],
+    /// Gather, the butterfly stages along u then v, and the accumulation.
+    stages: Vec<wgpu::BindGroup>,
+    power: wgpu::Buffer,
+    frames: u32,
+}
+
+/// Accumulated spatial spectra of the k-space monitors.
+pub struct KSpacePass {
+    gather: wgpu::ComputePipeline,
+    butterfly: wgpu::ComputePipeline,
+    accumulate: wgpu::ComputePipeline,
+    transforms: Vec<Transform>,
+}
+
+/// Accumulated power of one monitor, nu × nv with u fastest, unshifted.
+pub struct KSpectrum {
+    pub size: [u32; 2],
+    /// Spacing of the k grid along u and v (rad/m).
+    pub dk: [f64; 2],
+    pub power: Vec<f32>,
+    pub frames: u32,
+}
+
+impl KSpacePass {
+    /// `fields` are the six field buffers in (Ex, Ey, Ez, Hx, Hy, Hz) order.
+    pub fn new(
+        device: &wgpu::Device,
+        grid: &Grid,
+        monitors: &[KSpaceMonitor],
+        fields: [&wgpu::Buffer; 6],
+    ) -> Self {
+        let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("fft_bgl"),
+            entries: &[
+                bgl_uniform_entry(0),
+                bgl_storage_entry(1, true),
+                bgl_storage_entry(2, true),
+                bgl_storage_entry(3, false),
+                bgl_storage_entry(4, false),
+            ],
+        });
+        let source = include_str!("shaders/fft.wgsl");
+        let pipeline = |entry| compute_pipeline_entry(device, "fft", source, &bgl, entry);
+        let (gather, butterfly, accumulate) = (
+            pipeline("gather"),
+            pipeline("butterfly"),
+            pipeline("accumulate"),
+        );
+
+        let transforms = monitors
+            .iter()
+            .map(|spec| {
+                let (u, v) = spec.normal.tangential();
+                let cells = [grid.cells(u), grid.cells(v)];
+                let size = cells.map(|c| c.next_power_of_two().max(2));
+                let len = (size[0] * size[1]) as u64;
+                let mut corner = [0; 3];
+                corner[spec.normal.lane()] = spec.index;
+                let step = |axis: Axis| {
+                    let mut cell = [0; 3];
+                    cell[axis.lane()] = 1;
+                    grid.idx(cell[0], cell[1], cell[2]) as u32
+                };
+                let plane = [
+                    grid.idx(corner[0], corner[1], corner[2]) as u32,
+                    step(u),
+                    step(v),
+                    0,
+                ];
+                let complex = |label| {
+                    device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some(label),
+                        size: len * 8,
+                        usage: wgpu::BufferUsages::STORAGE,
+                        mapped_at_creation: false,
+                    })
+                };
+                let buffers = [complex("fft_a"), complex("fft_b")];
+                let power = device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("fft_power"),
+                    size: len * 4,
+                    usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+                    mapped_at_creation: false,
+                });
+                let field = fields[spec.field.index()];
+                // (span, axis) per stage; `from` is the buffer read
+                let mut stages = Vec::new();
+                let mut bind = |stage: [u32; 4], from: usize| {
+                    let params = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                        label: Some("fft_params"),
+                        contents: bytemuck::bytes_of(&FftParams {
+                            size: [size[0], size[1], cells[0], cells[1]],
+                            plane,
+                            stage,
+                        }),
+                        usage: wgpu::BufferUsages::UNIFORM,
+                    });
+                    stages.push(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                        label: Some("bg_fft"),
+                        layout: &bgl,
+                        entries: &[
+                            bg_entry(0, params.as_entire_binding()),
+                            bg_entry(1, field.as_entire_binding()),
+                            bg_entry(2, buffers[from].as_entire_binding()),
+                            bg_entry(3, buffers[1 - from].as_entire_binding()),
+                            bg_entry(4, power.as_entire_binding()),
+                        ],
+                    }));
+                };
+                // The gather writes buffer 0, each stage flips the buffers
+                bind([0; 4], 1);
+                let mut from = 0;
+                for (axis, n) in size.iter().enumerate() {
+                    let mut span = 1;
+                    while span < *n {
+                        bind([span, axis as u32, 0, 0], from);
+                        from = 1 - from;
+                        span *= 2;
+                    }
+                }
+                bind([0; 4], from);
+                Transform {
+                    spec: *spec,
+                    size,
+                    stages,
+                    power,
+                    frames: 0,
+                }
+            })
+            .collect();
+        KSpacePass {
+            gather,
+            butterfly,
+            accumulate,
+            transforms,
+        }
+    }
+
+    /// Transform and accumulate the planes due at step `n`.
+    pub fn encode(&mut self, encoder: &mut wgpu::CommandEncoder, n: u32) {
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("k-space"),
+            timestamp_writes: None,
+        });
+        for t in self.transforms.iter_mut() {
+            if !n.is_multiple_of(t.spec.every.max(1)) {
+                continue;
+            }
+            let len = t.size[0] * t.size[1];
+            let last = t.stages.len() - 1;
+            for (s, bind_group) in t.stages.iter().enumerate() {
+                let (pipeline, count) = match s {
+                    0 => (&self.gather, len),
+                    s if s == last => (&self.accumulate, len),
+                    _ => (&self.butterfly, len / 2),
+                };
+                pass.set_pipeline(pipeline);
+                pass.set_bind_group(0, bind_group, &[]);
+                pass.dispatch_workgroups(groups_1d(count, 64), 1, 1);
+            }
+            t.frames += 1;
+        }
+    }
+
+    /// Read the accumulated spectra back.
+    pub fn read(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        grid: &Grid,
+    ) -> Result<Vec<KSpectrum>, FdtdError> {
+        self.transforms
+            .iter()
+            .map(|t| {
+                let size = t.power.size();
+                let staging = device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("fft_staging"),
+                    size,
+                    usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                });
+                let mut encoder = device.create_command_encoder(&Default::default());
+                encoder.copy_buffer_to_buffer(&t.power, 0, &staging, 0, size);
+                queue.submit(Some(encoder.finish()));
+                let slice = staging.slice(..);
+                gpu::map_read(device, &[slice])?;
+                let power = bytemuck::cast_slice(&slice.get_mapped_range()).to_vec();
+                staging.unmap();
+                let (u, v) = t.spec.normal.tangential();
+                let dk = [(u, t.size[0]), (v, t.size[1])]
+                    .map(|(axis, n)| 2.0 * PI / (n as f64 * grid.spacing(axis)));
+                Ok(KSpectrum {
+                    size: t.size,
+                    dk,
+                    power,
+                    frames: t.frames,
+                })
+            })
+            .collect()
+    }
+}
+
+impl KSpectrum {
+    /// Signed k index of FFT bin `m` of `n`.
+    fn signed(m: u32, n: u32) -> i64 {
+        if m < n / 2 {
+            m as i64
+        } else {
+            m as i64 - n as i64
+        }
+    }
+
+    /// (k_u, k_v) of the strongest bin.
+    pub fn peak(&self) -> [f64; 2] {
+        let best = (0..self.power.len())
+            .max_by(|&a, &b| self.power[a].total_cmp(&self.power[b]))
+            .unwrap_or(0) as u32;
+        let [nu, nv] = self.size;
+        [
+            Self::signed(best % nu, nu) as f64 * self.dk[0],
+            Self::signed(best / nu, nv) as f64 * self.dk[1],
+        ]
+    }
+}
+
+/// Write `<name>_kspace.csv` and `<name>_kspace.png` for every monitor.
+pub fn write_results(
+    dir: &Path,
+    monitors: &[KSpaceMonitor],
+    spectra: &[KSpectrum],
+) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    for (spec, s) in monitors.iter().zip(spectra) {
+        let [nu, nv] = s.size;
+        // Shifted so that k = 0 sits in the middle
+        let bin = |x: u32, y: u32| {
+            let m = (x + nu / 2) % nu;
+            let n = (y + nv / 2) % nv;
+            (m, n, s.power[(m + nu * n) as usize])
+        };
+        let path = dir.join(format!("{}_kspace.csv", spec.name));
+        let mut file = BufWriter::new(fs::File::create(path)?);
+        writeln!(file, "ku,kv,power")?;
+        for y in 0..nv {
+            for x in 0..nu {
+                let (m, n, p) = bin(x, y);
+                let ku = KSpectrum::signed(m, nu) as f64 * s.dk[0];
+                let kv = KSpectrum::signed(n, nv) as f64 * s.dk[1];
+                writeln!(file, "{ku:e},{kv:e},{p:e}")?;
+            }
+        }
+        file.flush()?;
+
+        let max = s.power.iter().fold(0.0_f32, |a, &b| a.max(b));
+        let mut rgb = vec![0u8; (nu * nv * 3) as usize];
+        for y in 0..nv {
+            for x in 0..nu {
+                let (_, _, p) = bin(x, y);
+                let t = if max > 0.0 && p > 0.0 {
+                    1.0 + (p / max).log10() / 4.0
+                } else {
+                    0.0
+                };
+                let row = nv - 1 - y;
+                let i = 3 * (row * nu + x) as usize;
+                rgb[i..i + 3].copy_from_slice(&diverging(t.max(0.0)));
+            }
+        }
+        png::write_rgb(&dir.join(format!("{}_kspace.png", spec.name)), nu, nv, &rgb)?;
+    }
+    Ok(())
+}
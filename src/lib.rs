@@ -0,0 +1,144 @@
+//! 3D CNN-FDTD electromagnetic simulation — GPU-accelerated via wgpu.
+//!
+//! Implements the framework from the paper:
+//!   - **Shift & Add layer**  → finite-difference spatial derivatives
+//!   - **Hadamard Product layer** → element-wise multiply with CA/CB/CP/CQ
+//!   - **Summation layer** → leapfrog field update
+//!
+//! Two compute-shader dispatches per time step (H-update, E-update), plus
+//! sparse correction passes when sub-cell models are present.
+//!
+//! A run is described by a [`Config`] and stepped by a [`Simulation`]:
+//! `Simulation::new(config, Scene::Structure)` sets up the GPU passes (or
+//! [`Simulation::builder`], validating the scene first), and the methods of
+//! [`ComputeBackend`] step it: `step()` / `run(n)` advance the fields,
+//! `field(..)` reads a component back and `finish()` writes the configured
+//! analyses; [`on_step`](ComputeBackend::on_step) runs user code between
+//! steps (see [`hooks`]) and [`add_sink`](ComputeBackend::add_sink)
+//! receives its outputs as events (see [`sinks`]); [`notebook`] covers
+//! stepping one interactively from evcxr.  [`run`] is the whole program of
+//! the `fdtd_3d` binary, reference run and results.json included, and
+//! [`dry_run`] its setup alone, reported before committing GPU time.  On
+//! wasm32 the `web` module drives a run on the browser's WebGPU from
+//! JavaScript; [`cpu`] steps the Yee core of 3D scenes with probes only on
+//! every core, as a reference or without a capable GPU, behind the same
+//! [`Simulation`], and [`distributed`] over several processes in z slabs;
+//! [`run_scene`] steps both through the same trait (see [`backend`]).
+//!
+//! Setup diagnostics, warnings and timings are [`tracing`] events (the
+//! binary logs them to stderr); results are printed to stdout.  Setting
+//! up, stepping, reading back and writing outputs return [`FdtdError`]
+//! (see [`error`]) instead of aborting the embedding program, and
+//! [`interrupt`] stops a run early with a checkpoint, which
+//! [`resume`](ComputeBackend::resume) goes on from (see [`checkpoint`]);
+//! [`divergence`] stops one whose fields have blown up.
+
+/// Speed of light (m/s) of the configured time steps.
+pub const C0: f64 = 3.0e8;
+
+// Setup and stepping
+pub mod backend;
+pub mod bench;
+pub mod builder;
+pub mod checkpoint;
+pub mod config;
+pub mod divergence;
+pub mod dry_run;
+pub mod error;
+pub mod gprmax;
+pub mod hooks;
+pub mod interrupt;
+pub mod progress;
+pub mod repl;
+pub mod scene_file;
+pub mod script;
+pub mod simulation;
+pub mod sinks;
+pub mod stability;
+pub mod sweep;
+
+// Grid, meshing and GPU plumbing
+pub mod adapters;
+pub mod cpu;
+pub mod distributed;
+pub mod gpu;
+pub mod grid;
+pub mod memory;
+pub mod meshing;
+pub mod precision;
+pub mod reduced;
+
+// Materials and geometry
+pub mod conformal;
+pub mod geometry;
+pub mod materials;
+pub mod modulation;
+pub mod phantom;
+pub mod random_media;
+pub mod rough_surface;
+pub mod smoothing;
+pub mod tissues;
+
+// Sub-cell models and lumped circuits
+pub mod ade;
+pub mod circuit;
+pub mod corrections;
+pub mod cosim;
+pub mod lumped;
+pub mod sheets;
+pub mod sibc;
+pub mod wires;
+
+// Sources
+pub mod sources;
+pub mod unit_cell;
+
+// Boundaries and alternative update schemes
+pub mod adi;
+pub mod bands;
+pub mod hie;
+pub mod moving_window;
+pub mod subgrid;
+
+// Outputs and analyses
+pub mod compare;
+pub mod dft;
+pub mod energy;
+pub mod ffi;
+pub mod flux;
+pub mod harminv;
+pub mod kspace;
+pub mod manifest;
+pub mod mat;
+pub mod modes;
+pub mod monitors;
+pub mod netcdf;
+pub mod notebook;
+pub mod ntff;
+pub mod pattern;
+pub mod png;
+pub mod ports;
+pub mod probes;
+pub mod purcell;
+pub mod rcs;
+pub mod reflectance;
+pub mod sar;
+pub mod shielding;
+pub mod slices;
+pub mod snapshots;
+pub mod spectra;
+pub mod tdr;
+pub mod thermal;
+pub mod touchstone;
+pub mod vtk;
+#[cfg(target_arch = "wasm32")]
+pub mod web;
+pub mod zarr;
+
+pub use backend::ComputeBackend;
+pub use builder::SimulationBuilder;
+pub use config::{Boundary, Config, ConfigError, Scene};
+pub use error::FdtdError;
+pub use hooks::StepState;
+pub use simulation::{run, run_scene, Simulation};
+pub use sinks::{FileSink, JsonLinesSink, MatSink, OutputEvent, OutputSink, StdoutSink};
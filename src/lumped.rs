@@ -0,0 +1,95 @@
+//! Lumped circuit elements on single E edges.
+//!
+//! An element spans one edge of length Δa with cross-section A = Δb·Δc and
+//! enters Ampère's law as a current density J = I/A (Piket-May, Taflove &
+//! Baron):
+//!   - resistor R        → extra conductivity σ = Δa/(R·A)
+//!   - capacitor C       → extra permittivity ε = C·Δa/A
+//!   - inductor L        → ADE current dJ/dt = Δa/(L·A) · E
+//!   - voltage source Vs → resistor R plus a drive term CB·Vs/(R·A)
+//!
+//! The source convention is J = (E·Δa − Vs)/(R·A) along +a, so an
+//! unloaded source settles at E·Δa = Vs.
+
+use serde::Serialize;
+
+use crate::ade::AdeEdge;
+use crate::grid::{Axis, Grid};
+use crate::materials::Coefficients;
+use crate::sources::Waveform;
+
+/// Kind and value of a lumped element.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub enum LumpedKind {
+    Resistor {
+        r: f64,
+    },
+    Capacitor {
+        c: f64,
+    },
+    Inductor {
+        l: f64,
+    },
+    /// Voltage source with internal resistance `r` (Ω) and amplitude `v` (V).
+    VoltageSource {
+        r: f64,
+        v: f64,
+        waveform: Waveform,
+    },
+}
+
+/// A lumped element on the `axis`-directed E edge of `cell`.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct LumpedElement {
+    pub axis: Axis,
+    pub cell: [u32; 3],
+    pub kind: LumpedKind,
+}
+
+impl LumpedElement {
+    /// Fold the element into `coeffs`.  Elements that need a per-edge ADE
+    /// current or a drive return an [`AdeEdge`]; voltage sources append
+    /// their (amplitude-scaled) waveform to `drives` and reference that slot.
+    pub fn apply(
+        &self,
+        grid: &Grid,
+        coeffs: &mut Coefficients,
+        drives: &mut Vec<(f64, Waveform)>,
+    ) -> Option<AdeEdge> {
+        let a = self.axis;
+        let (b, c) = a.tangential();
+        let cell = |axis: Axis| self.cell[axis.lane()];
+        let len = grid.width(a, cell(a));
+        let area = grid.dual_width(b, cell(b)) * grid.dual_width(c, cell(c));
+        let id = grid.idx(self.cell[0], self.cell[1], self.cell[2]);
+
+        match self.kind {
+            LumpedKind::Resistor { r } => {
+                coeffs.add_e_conductivity(id, a, len / (r * area), grid.dt);
+                None
+            }
+            LumpedKind::Capacitor { c } => {
+                coeffs.add_e_permittivity(id, a, c * len / area, grid.dt);
+                None
+            }
+            LumpedKind::Inductor { l } => Some(AdeEdge::integrator(
+                coeffs,
+                id,
+                a,
+                len / (l * area),
+                grid.dt,
+            )),
+            LumpedKind::VoltageSource { r, v, waveform } => {
+                coeffs.add_e_conductivity(id, a, len / (r * area), grid.dt);
+                let cb = coeffs.cb[id][a.lane()] as f64;
+                drives.push((v, waveform));
+                Some(AdeEdge::driven(
+                    id,
+                    a,
+                    (drives.len() - 1) as u32,
+                    cb / (r * area),
+                ))
+            }
+        }
+    }
+}
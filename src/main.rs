@@ -1,374 +1,690 @@
-//! 3D CNN-FDTD electromagnetic simulation — GPU-accelerated via wgpu.
+//! The `fdtd_3d` program: a scene file (see scene_file.rs) run by the
+//! library (see lib.rs), or without one the built-in scene of
+//! scenes/default.toml.  Every setting of the library, those scene files do
+//! not cover included, is set in Rust in examples/scene.rs.
 //!
-//! Implements the framework from the paper:
-//!   - **Shift & Add layer**  → finite-difference spatial derivatives
-//!   - **Hadamard Product layer** → element-wise multiply with CA/CB/CP/CQ
-//!   - **Summation layer** → leapfrog field update
+//! The command line picks what to do with the scene, takes it from a TOML
+//! or JSON description (or a gprMax model) and overrides its size, update
+//! scheme, precision and dimensionality:
 //!
-//! Two compute-shader dispatches per time step (H-update, E-update).
-
-use bytemuck::{Pod, Zeroable};
-use ndarray::Array3;
-use std::borrow::Cow;
-use wgpu::util::DeviceExt;
-
-// ── simulation parameters ────────────────────────────────────────────
-
-const NX: u32 = 64;
-const NY: u32 = 64;
-const NZ: u32 = 64;
-const TOTAL: usize = (NX * NY * NZ) as usize;
-const MAX_TIME: u32 = 300;
-
-// Physical constants
-const C0: f64 = 3.0e8;             // speed of light  (m/s)
-const EPS0: f64 = 8.854187817e-12;  // vacuum permittivity
-const MU0: f64 = 1.2566370614e-6;   // vacuum permeability
-
-// Grid spacing  (uniform cubic cells)
-const DX: f64 = 1e-3; // 1 mm
-const DY: f64 = DX;
-const DZ: f64 = DX;
-
-// Time step (Courant condition: Sc = c·Δt/Δ ≤ 1/√3 for 3D)
-const SC: f64 = 0.5; // Courant number
-const DT: f64 = SC * DX / C0;
-
-// Source (Gaussian pulse at grid centre)
-const SRC_I: u32 = NX / 2;
-const SRC_J: u32 = NY / 2;
-const SRC_K: u32 = NZ / 2;
-const PULSE_WIDTH: f64 = 20.0;
-const PULSE_DELAY: f64 = 40.0;
-
-// Probe location (slightly offset from source)
-const PROBE_I: u32 = NX / 2 + 10;
-const PROBE_J: u32 = NY / 2;
-const PROBE_K: u32 = NZ / 2;
-
-// ── GPU uniform struct (must match WGSL `Params`) ────────────────────
-
-#[repr(C)]
-#[derive(Copy, Clone, Pod, Zeroable)]
-struct GpuParams {
-    nx: u32,
-    ny: u32,
-    nz: u32,
-    _pad: u32,
-    inv_dx: f32,
-    inv_dy: f32,
-    inv_dz: f32,
-    _pad2: f32,
+//! ```text
+//! fdtd_3d [run]      [-c scene.toml] [--set NAME=VALUE]… [--grid 128x128x64]
+//!                    [--steps N] [-o DIR] [--adapter INDEX|NAME] [--backend vulkan]
+//!                    [--power-preference low] [--resume CHECKPOINT]
+//!                    [--time-budget 12h] [--checkpoint-every N] [-v | -q] [--log-json]
+//!                    [--output-format json] [--rank R --hosts HOST:PORT,…]
+//!                    [--scheme yee24] [--precision f64] [--mode tmz]
+//! fdtd_3d sweep      -c scene.toml [--all-adapters] [--cpu-workers N] [run options]
+//! fdtd_3d validate   set the scene up without running it and print a report
+//! fdtd_3d bench      time the update on an empty grid (128³ by default)
+//!                    [--duration 30s] [--peak-bandwidth GB/s]
+//! fdtd_3d schema     print the JSON Schema of scene files
+//! fdtd_3d compare    RUN REFERENCE [--transpose] [--resample] [--tolerance L2]
+//! fdtd_3d repl       step the scene from typed commands (see repl.rs)
+//! fdtd_3d --list-adapters [--backend vulkan]   the GPUs --adapter picks from
+//! ```
+//!
+//! Ctrl-C (or SIGTERM) during a run finishes the step under way, writes the
+//! outputs so far and `MONITOR_DIR/checkpoint.bin`, and exits with status
+//! 130; a second Ctrl-C exits at once.  `--resume MONITOR_DIR/checkpoint.bin`
+//! goes on from there, with a larger `--steps` to continue a finished run.
+//!
+//! A run logs its estimated wall time after its first ten steps;
+//! `--time-budget` stops it there when the estimate is longer.
+//!
+//! `--backend cpu` runs the Yee core of 3D scenes (objects, material maps,
+//! lumped elements, sheets, wires) on the CPU with probes only, as a
+//! reference for the GPU or without one for a run asking for nothing else.
+//! With `--rank R --hosts HOST:PORT,…` it is one of a run split in z slabs
+//! over as many processes, one per host and each with its own rank, which
+//! hand each other the planes at their boundaries over TCP; rank 0 writes
+//! the outputs (see distributed.rs).
+//!
+//! A lost GPU device (a driver reset, a hung GPU) does not end the run: a
+//! new device is opened and the run goes on from the last checkpoint
+//! written every `--checkpoint-every` steps, or starts over without them.
+//!
+//! `fdtd_3d sweep` runs the cases of the scene file's `[sweep]` table (see
+//! sweep.rs) under `case_000`, `case_001`, … of the output directory, with
+//! the run options above applied to each, and writes `sweep.csv`, a row per
+//! case; it fails when a case did.  The cases run one after the other on
+//! the configured device, or several at once with `--all-adapters` (one on
+//! each GPU) and `--cpu-workers N` (N on the CPU backend, sharing its
+//! `--threads`).
+//!
+//! `--output-format json` replaces the lines a run prints on stdout by one
+//! JSON object per step, probe sample and result (see `JsonLinesSink`), for
+//! wrapper scripts and job schedulers; the log stays on stderr.
+
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+use std::{env, fs};
+
+use clap::{Args, Parser, Subcommand};
+use tracing::{warn, Level};
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::prelude::*;
+use fdtd_3d::*;
+
+use adapters::{AdapterChoice, Backend, PowerPreference};
+use config::{OutputFormat, Verbosity};
+use distributed::Cluster;
+use grid::{Axis, Grid};
+use precision::Precision;
+use probes::Location;
+use reduced::Mode;
+use scene_file::Parameter;
+use stability::Scheme;
+
+/// The scene run without -c.
+const SCENE: &str = include_str!("../scenes/default.toml");
+
+// Steps of `fdtd_3d bench` unless --steps says otherwise.
+const BENCH_STEPS: u32 = 200;
+
+/// GPU FDTD solver of a scene file, or of the built-in scene without -c.
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// List the GPU adapters (of --backend) with their index, and exit.
+    #[arg(long)]
+    list_adapters: bool,
+    #[command(flatten)]
+    options: Options,
 }
 
-// ── helpers ──────────────────────────────────────────────────────────
+#[derive(Subcommand)]
+enum Command {
+    /// Run the scene (the default).
+    Run,
+    /// Run every case of the scene file's [sweep] table, each under its
+    /// own directory, and write sweep.csv.
+    Sweep(SweepArgs),
+    /// Set the scene up without running it: geometry, stability, memory
+    /// and device limits, and a report of its materials, sources and
+    /// monitors.
+    Validate,
+    /// Time the update on an empty grid, without outputs: throughput,
+    /// kernel timings and memory bandwidth.
+    Bench(BenchArgs),
+    /// Print the JSON Schema of scene files.
+    Schema,
+    /// Compare an output of a run with reference data.
+    Compare(CompareArgs),
+    /// Step the scene interactively from typed commands.
+    Repl,
+}
 
-fn idx(i: u32, j: u32, k: u32) -> usize {
-    (i + NX * (j + NY * k)) as usize
+#[derive(Args)]
+struct SweepArgs {
+    /// Run cases at once, one on each GPU adapter (of --backend).
+    #[arg(long)]
+    all_adapters: bool,
+    /// Run this many cases at once on the CPU backend, sharing its cores
+    /// (or --threads).
+    #[arg(long, default_value_t = 0)]
+    cpu_workers: usize,
 }
 
-/// Build material coefficient maps (CA, CB, CP, CQ).
-/// For free space:  σ = σ_m = 0  →  CA = CP = 1,  CB = Δt/ε₀,  CQ = Δt/μ₀.
-fn build_coefficients() -> (Vec<f32>, Vec<f32>, Vec<f32>, Vec<f32>) {
-    let ca_val = 1.0_f32;                     // (1 - 0)/(1 + 0)
-    let cb_val = (DT / EPS0) as f32;          // Δt/ε₀
-    let cp_val = 1.0_f32;
-    let cq_val = (DT / MU0) as f32;           // Δt/μ₀
+#[derive(Args)]
+struct BenchArgs {
+    /// Step for this wall time instead of --steps, e.g. 30s or 2m.
+    #[arg(long, value_parser = parse_duration, conflicts_with = "steps")]
+    duration: Option<Duration>,
+    /// Peak memory bandwidth of the GPU in GB/s, to report the share of it
+    /// the update takes.
+    #[arg(long)]
+    peak_bandwidth: Option<f64>,
+}
 
-    let ca = vec![ca_val; TOTAL];
-    let cb = vec![cb_val; TOTAL];
-    let cp = vec![cp_val; TOTAL];
-    let cq = vec![cq_val; TOTAL];
+#[derive(Args)]
+struct CompareArgs {
+    /// Run output, FILE or FILE:NAME (.csv column, .h5/.mat dataset, .bin).
+    run: String,
+    /// Reference data in the same forms, e.g. a Meep .h5 file.
+    reference: String,
+    /// Reverse the axis order of the reference (Meep's x slowest arrays).
+    #[arg(long)]
+    transpose: bool,
+    /// Resample a reference series of another length to the run's.
+    #[arg(long)]
+    resample: bool,
+    /// Fail above this relative L2 error.
+    #[arg(long)]
+    tolerance: Option<f64>,
+}
 
-    (ca, cb, cp, cq)
+#[derive(Args)]
+struct Options {
+    /// TOML or JSON scene description to run instead of the built-in
+    /// scene.
+    #[arg(short, long, global = true)]
+    config: Option<PathBuf>,
+    /// Script parameter of the scene description, e.g. --set radius=2e-3.
+    #[arg(long = "set", global = true, value_parser = parse_parameter)]
+    parameters: Vec<(String, Parameter)>,
+    /// Cells along x, y and z, e.g. 128x128x64.  The source and the probe
+    /// cells move with the grid, at the same fraction of it; other cells and
+    /// the points in metres stay where the scene puts them.
+    #[arg(long, global = true, value_parser = parse_cells)]
+    grid: Option<[u32; 3]>,
+    /// Time steps.
+    #[arg(long, global = true)]
+    steps: Option<u32>,
+    /// Directory the outputs are written under, created if missing.
+    #[arg(short, long, global = true)]
+    output: Option<PathBuf>,
+    /// GPU adapter by index in --list-adapters, or by name or part of it
+    /// in any case.
+    #[arg(long, global = true, value_parser = parse_adapter)]
+    adapter: Option<AdapterChoice>,
+    /// Look for adapters on one graphics API: vulkan, metal, dx12 or gl; or
+    /// run the Yee core on the CPU, with probes only, with cpu.
+    #[arg(long, global = true, value_parser = parse_backend)]
+    backend: Option<Backend>,
+    /// GPU wgpu prefers when no adapter is named: high (discrete) or low
+    /// (integrated).
+    #[arg(long, global = true, value_parser = parse_power_preference, default_value = "high")]
+    power_preference: PowerPreference,
+    /// Update scheme: yee, yee24 (fourth order in space), hie:AXIS (implicit
+    /// along a thin layer's normal) or adi:MULTIPLE (implicit, Δt a
+    /// multiple of the Yee limit).
+    #[arg(long, global = true, value_parser = parse_scheme)]
+    scheme: Option<Scheme>,
+    /// Field precision of the 3D update: f32, f16, f64 or double-single.
+    #[arg(long, global = true, value_parser = parse_precision)]
+    precision: Option<Precision>,
+    /// Dimensionality: 3d, 1d, tmz, tez or bor:M (axisymmetric, azimuthal
+    /// order M), the reduced ones on the line or plane through the source.
+    #[arg(long, global = true, value_parser = parse_mode)]
+    mode: Option<Mode>,
+    /// Worker threads of --backend cpu, one per core by default.
+    #[arg(long, global = true)]
+    threads: Option<usize>,
+    /// Rank of this process in a run split over --hosts.
+    #[arg(long, global = true, requires = "hosts")]
+    rank: Option<usize>,
+    /// host:port each rank of a distributed run listens on, in rank order,
+    /// e.g. node0:7000,node1:7000.
+    #[arg(long, global = true, value_delimiter = ',', requires = "rank")]
+    hosts: Vec<String>,
+    /// Encode up to N steps into one GPU submission, waited for together.
+    #[arg(long, global = true)]
+    steps_per_submit: Option<u32>,
+    /// Read the probe and flux samples back every N steps, rounded up to
+    /// whole submissions.
+    #[arg(long, global = true)]
+    probe_batch: Option<u32>,
+    /// Go on from a checkpoint of the same scene, to --steps in all.
+    #[arg(long, global = true)]
+    resume: Option<PathBuf>,
+    /// Stop after the first steps when the run would take longer, e.g. 90m
+    /// or 12h (s, m and h, seconds by default).
+    #[arg(long, global = true, value_parser = parse_duration)]
+    time_budget: Option<Duration>,
+    /// Write MONITOR_DIR/checkpoint.bin every N steps, and go back to it
+    /// when the GPU device is lost.
+    #[arg(long, global = true)]
+    checkpoint_every: Option<u32>,
+    /// Also print the probe values of every step, the adapter limits and the
+    /// run timings.
+    #[arg(short, long, global = true, conflicts_with = "quiet")]
+    verbose: bool,
+    /// Leave out the progress bar and the setup log, keeping warnings.
+    #[arg(short, long, global = true)]
+    quiet: bool,
+    /// Log as JSON lines on stderr, for collection by other tools.
+    #[arg(long, global = true)]
+    log_json: bool,
+    /// What a run prints on stdout: text, or JSON lines of its steps, probe
+    /// samples and results.
+    #[arg(long, global = true, value_parser = parse_output_format, default_value = "text")]
+    output_format: OutputFormat,
 }
 
-/// Gaussian pulse source value at time step `n`.
-fn gaussian_source(n: u32) -> f32 {
-    let t = n as f64 - PULSE_DELAY;
-    (-(t * t) / (PULSE_WIDTH * PULSE_WIDTH)).exp() as f32
+fn parse_cells(text: &str) -> Result<[u32; 3], String> {
+    let cells: Vec<u32> = text
+        .split('x')
+        .map(|n| n.trim().parse().map_err(|_| format!("bad cell count {n:?}")))
+        .collect::<Result<_, _>>()?;
+    cells.try_into().map_err(|_| "expected NXxNYxNZ".to_string())
 }
 
-// ── main ─────────────────────────────────────────────────────────────
+fn parse_duration(text: &str) -> Result<Duration, String> {
+    let text = text.trim();
+    let (number, unit) = match text.strip_suffix(['s', 'm', 'h']) {
+        Some(number) => (number, &text[number.len()..]),
+        None => (text, "s"),
+    };
+    let seconds: f64 = number.trim().parse().map_err(|_| format!("bad duration {text:?}"))?;
+    let scale = match unit {
+        "h" => 3600.0,
+        "m" => 60.0,
+        _ => 1.0,
+    };
+    Duration::try_from_secs_f64(seconds * scale).map_err(|e| format!("{text:?}: {e}"))
+}
 
-fn main() {
-    pollster::block_on(run());
+fn parse_adapter(text: &str) -> Result<AdapterChoice, String> {
+    Ok(match text.trim().parse() {
+        Ok(index) => AdapterChoice::Index(index),
+        Err(_) => AdapterChoice::Name(text.to_string()),
+    })
 }
 
-async fn run() {
-    // ── 1. wgpu device setup ─────────────────────────────────────────
+fn parse_backend(text: &str) -> Result<Backend, String> {
+    match text.to_lowercase().as_str() {
+        "vulkan" => Ok(Backend::Vulkan),
+        "metal" => Ok(Backend::Metal),
+        "dx12" => Ok(Backend::Dx12),
+        "gl" => Ok(Backend::Gl),
+        "cpu" => Ok(Backend::Cpu),
+        _ => Err(format!("expected vulkan, metal, dx12, gl or cpu, not {text:?}")),
+    }
+}
 
-    let instance = wgpu::Instance::default();
-    let adapter = instance
-        .request_adapter(&wgpu::RequestAdapterOptions {
-            power_preference: wgpu::PowerPreference::HighPerformance,
-            ..Default::default()
-        })
-        .await
-        .expect("No suitable GPU adapter found");
+fn parse_axis(text: &str) -> Result<Axis, String> {
+    match text {
+        "x" => Ok(Axis::X),
+        "y" => Ok(Axis::Y),
+        "z" => Ok(Axis::Z),
+        _ => Err(format!("expected x, y or z, not {text:?}")),
+    }
+}
 
-    let (device, queue) = adapter
-        .request_device(&wgpu::DeviceDescriptor {
-            label: Some("FDTD device"),
-            required_features: wgpu::Features::empty(),
-            required_limits: wgpu::Limits {
-                max_storage_buffer_binding_size: 256 * 1024 * 1024,
-                max_buffer_size: 256 * 1024 * 1024,
-                ..Default::default()
-            },
-            memory_hints: wgpu::MemoryHints::Performance,
-        }, None)
-        .await
-        .expect("Failed to create device");
+fn parse_scheme(text: &str) -> Result<Scheme, String> {
+    let text = text.to_lowercase();
+    match text.split_once(':') {
+        None if text == "yee" => Ok(Scheme::Yee),
+        None if text == "yee24" => Ok(Scheme::Yee24),
+        Some(("hie", axis)) => Ok(Scheme::Hie { axis: parse_axis(axis)? }),
+        Some(("adi", multiple)) => match multiple.parse() {
+            Ok(multiple) => Ok(Scheme::Adi { multiple }),
+            Err(_) => Err(format!("bad Δt multiple {multiple:?}")),
+        },
+        _ => Err(format!("expected yee, yee24, hie:AXIS or adi:MULTIPLE, not {text:?}")),
+    }
+}
 
-    println!(
-        "GPU: {}  (backend {:?})",
-        adapter.get_info().name,
-        adapter.get_info().backend
-    );
-    println!("Grid: {}×{}×{}  ({} cells)", NX, NY, NZ, TOTAL);
-    println!("Time steps: {}", MAX_TIME);
-    println!("Courant number: {}", SC);
-    println!();
+fn parse_precision(text: &str) -> Result<Precision, String> {
+    match text.to_lowercase().as_str() {
+        "f16" => Ok(Precision::F16),
+        "f32" => Ok(Precision::F32),
+        "f64" => Ok(Precision::F64),
+        "double-single" => Ok(Precision::DoubleSingle),
+        _ => Err(format!("expected f16, f32, f64 or double-single, not {text:?}")),
+    }
+}
 
-    // ── 2. Build coefficient maps on CPU ─────────────────────────────
+fn parse_mode(text: &str) -> Result<Mode, String> {
+    let text = text.to_lowercase();
+    match text.split_once(':') {
+        None if text == "3d" => Ok(Mode::ThreeD),
+        None if text == "1d" => Ok(Mode::OneD),
+        None if text == "tmz" => Ok(Mode::TMz),
+        None if text == "tez" => Ok(Mode::TEz),
+        Some(("bor", m)) => match m.parse() {
+            Ok(m) => Ok(Mode::Bor { m }),
+            Err(_) => Err(format!("bad azimuthal order {m:?}")),
+        },
+        _ => Err(format!("expected 3d, 1d, tmz, tez or bor:M, not {text:?}")),
+    }
+}
 
-    let (ca, cb, cp, cq) = build_coefficients();
-    let zeros = vec![0.0_f32; TOTAL];
+fn parse_power_preference(text: &str) -> Result<PowerPreference, String> {
+    match text {
+        "high" => Ok(PowerPreference::HighPerformance),
+        "low" => Ok(PowerPreference::LowPower),
+        _ => Err(format!("expected high or low, not {text:?}")),
+    }
+}
 
-    // ── 3. Create GPU buffers ────────────────────────────────────────
+fn parse_output_format(text: &str) -> Result<OutputFormat, String> {
+    match text {
+        "text" => Ok(OutputFormat::Text),
+        "json" => Ok(OutputFormat::Json),
+        _ => Err(format!("expected text or json, not {text:?}")),
+    }
+}
 
-    let usage_rw = wgpu::BufferUsages::STORAGE
-        | wgpu::BufferUsages::COPY_DST
-        | wgpu::BufferUsages::COPY_SRC;
-    let usage_ro = wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST;
+fn parse_parameter(text: &str) -> Result<(String, Parameter), String> {
+    let (name, value) = text.split_once('=').ok_or("expected NAME=VALUE")?;
+    Ok((name.trim().to_string(), value.trim().parse()?))
+}
 
-    let make_buf = |label: &str, data: &[f32], usage: wgpu::BufferUsages| {
-        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some(label),
-            contents: bytemuck::cast_slice(data),
-            usage,
-        })
+/// Send the library's log to stderr: warnings with -q, the setup at
+/// first, the buffer sizes and per-phase timings with -v.  Of wgpu and the
+/// other crates only errors are shown.
+fn init_logging(options: &Options) {
+    let level = match (options.quiet, options.verbose) {
+        (true, _) => Level::WARN,
+        (_, true) => Level::DEBUG,
+        _ => Level::INFO,
     };
+    let filter = Targets::new()
+        .with_default(Level::ERROR)
+        .with_target("fdtd_3d", level);
+    let log = tracing_subscriber::fmt::layer()
+        .with_writer(io::stderr)
+        .with_target(false)
+        .with_span_events(FmtSpan::CLOSE);
+    if options.log_json {
+        let log = log.json().with_filter(filter);
+        tracing_subscriber::registry().with(log).init();
+    } else {
+        let log = log.without_time().with_filter(filter);
+        tracing_subscriber::registry().with(log).init();
+    }
+}
 
-    // Field buffers (read-write — updated by shaders)
-    let buf_ex = make_buf("ex", &zeros, usage_rw);
-    let buf_ey = make_buf("ey", &zeros, usage_rw);
-    let buf_ez = make_buf("ez", &zeros, usage_rw);
-    let buf_hx = make_buf("hx", &zeros, usage_rw);
-    let buf_hy = make_buf("hy", &zeros, usage_rw);
-    let buf_hz = make_buf("hz", &zeros, usage_rw);
-
-    // Coefficient buffers (read-only — uploaded once)
-    let buf_ca = make_buf("ca", &ca, usage_ro);
-    let buf_cb = make_buf("cb", &cb, usage_ro);
-    let buf_cp = make_buf("cp", &cp, usage_ro);
-    let buf_cq = make_buf("cq", &cq, usage_ro);
+/// `fdtd_3d --list-adapters`: the adapters a run can take, by the index
+/// --adapter takes.
+fn list_adapters(backend: Option<Backend>) {
+    let adapters = adapters::list(backend);
+    if adapters.is_empty() {
+        println!("no GPU adapter found");
+    }
+    for (index, info) in adapters.iter().enumerate() {
+        let driver = format!("{} {}", info.driver, info.driver_info);
+        println!(
+            "{index}: {} ({:?}, {:?}, {})",
+            info.name,
+            info.backend,
+            info.device_type,
+            driver.trim()
+        );
+    }
+}
 
-    // Uniform buffer
-    let params = GpuParams {
-        nx: NX,
-        ny: NY,
-        nz: NZ,
-        _pad: 0,
-        inv_dx: (1.0 / DX) as f32,
-        inv_dy: (1.0 / DY) as f32,
-        inv_dz: (1.0 / DZ) as f32,
-        _pad2: 0.0,
+/// Stop the run after the step under way on SIGINT or SIGTERM, writing a
+/// checkpoint and the outputs so far; a second signal exits at once.
+fn trap_interrupts() {
+    let handler = || {
+        if interrupt::request() {
+            std::process::exit(130);
+        }
+        warn!("interrupted: finishing the step and writing a checkpoint (again to abort)");
     };
-    let buf_params = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-        label: Some("params"),
-        contents: bytemuck::bytes_of(&params),
-        usage: wgpu::BufferUsages::UNIFORM,
-    });
-
-    // Readback staging buffer (single f32 for probe)
-    let buf_readback = device.create_buffer(&wgpu::BufferDescriptor {
-        label: Some("readback"),
-        size: 4,
-        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
-        mapped_at_creation: false,
-    });
-
-    // ── 4. Load shaders & create pipelines ───────────────────────────
-
-    let shader_h = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-        label: Some("update_h"),
-        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("shaders/update_h.wgsl"))),
-    });
-    let shader_e = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-        label: Some("update_e"),
-        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("shaders/update_e.wgsl"))),
-    });
-
-    // Bind-group layout (shared structure: params + 6 fields + 2 coeffs)
-    let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-        label: Some("fdtd_bgl"),
-        entries: &[
-            // @binding(0) uniform Params
-            wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::COMPUTE,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
-            },
-            // @binding(1..3) read-only storage  (source fields)
-            bgl_storage_entry(1, true),
-            bgl_storage_entry(2, true),
-            bgl_storage_entry(3, true),
-            // @binding(4..6) read-write storage (target fields)
-            bgl_storage_entry(4, false),
-            bgl_storage_entry(5, false),
-            bgl_storage_entry(6, false),
-            // @binding(7..8) read-only storage  (coefficients)
-            bgl_storage_entry(7, true),
-            bgl_storage_entry(8, true),
-        ],
-    });
-
-    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-        label: Some("fdtd_pl"),
-        bind_group_layouts: &[&bgl],
-        push_constant_ranges: &[],
-    });
-
-    let pipeline_h = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-        label: Some("pipeline_h"),
-        layout: Some(&pipeline_layout),
-        module: &shader_h,
-        entry_point: Some("main"),
-        compilation_options: Default::default(),
-        cache: None,
-    });
-    let pipeline_e = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-        label: Some("pipeline_e"),
-        layout: Some(&pipeline_layout),
-        module: &shader_e,
-        entry_point: Some("main"),
-        compilation_options: Default::default(),
-        cache: None,
-    });
+    if let Err(e) = ctrlc::set_handler(handler) {
+        warn!("cannot trap Ctrl-C: {e}");
+    }
+}
 
-    // Bind groups:
-    //   H-update reads E, writes H, uses CP/CQ
-    //   E-update reads H, writes E, uses CA/CB
-    let bg_h = device.create_bind_group(&wgpu::BindGroupDescriptor {
-        label: Some("bg_h"),
-        layout: &bgl,
-        entries: &[
-            bg_entry(0, buf_params.as_entire_binding()),
-            bg_entry(1, buf_ex.as_entire_binding()),
-            bg_entry(2, buf_ey.as_entire_binding()),
-            bg_entry(3, buf_ez.as_entire_binding()),
-            bg_entry(4, buf_hx.as_entire_binding()),
-            bg_entry(5, buf_hy.as_entire_binding()),
-            bg_entry(6, buf_hz.as_entire_binding()),
-            bg_entry(7, buf_cp.as_entire_binding()),
-            bg_entry(8, buf_cq.as_entire_binding()),
-        ],
-    });
-    let bg_e = device.create_bind_group(&wgpu::BindGroupDescriptor {
-        label: Some("bg_e"),
-        layout: &bgl,
-        entries: &[
-            bg_entry(0, buf_params.as_entire_binding()),
-            bg_entry(1, buf_hx.as_entire_binding()),
-            bg_entry(2, buf_hy.as_entire_binding()),
-            bg_entry(3, buf_hz.as_entire_binding()),
-            bg_entry(4, buf_ex.as_entire_binding()),
-            bg_entry(5, buf_ey.as_entire_binding()),
-            bg_entry(6, buf_ez.as_entire_binding()),
-            bg_entry(7, buf_ca.as_entire_binding()),
-            bg_entry(8, buf_cb.as_entire_binding()),
-        ],
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let options = &cli.options;
+    init_logging(options);
+    if cli.list_adapters {
+        list_adapters(options.backend);
+        return ExitCode::SUCCESS;
+    }
+    let mut config = match &options.config {
+        Some(path) => match scene_file::load(path, &options.parameters) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("{}: {e}", path.display());
+                return ExitCode::FAILURE;
+            }
+        },
+        None => match scene_file::load_toml(SCENE) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("the built-in scene: {e}");
+                return ExitCode::FAILURE;
+            }
+        },
+    };
+    apply_options(&mut config, options);
+    // A sweep reads the scene file again for each case, from the output
+    // directory
+    let scene_path = options.config.as_ref().map(|path| {
+        fs::canonicalize(path).unwrap_or_else(|_| path.clone())
     });
-
-    // Workgroup counts  (workgroup_size = 4×4×4)
-    let wg_x = (NX + 3) / 4;
-    let wg_y = (NY + 3) / 4;
-    let wg_z = (NZ + 3) / 4;
-
-    // ── 5. Time-stepping loop ────────────────────────────────────────
-
-    let probe_byte_offset = (idx(PROBE_I, PROBE_J, PROBE_K) * 4) as u64;
-    let src_byte_offset = (idx(SRC_I, SRC_J, SRC_K) * 4) as u64;
-
-    for n in 0..MAX_TIME {
-        // Source injection: write Gaussian pulse into Ez at source point
-        let src_val = gaussian_source(n);
-        queue.write_buffer(&buf_ez, src_byte_offset, bytemuck::bytes_of(&src_val));
-
-        // Encode both dispatches into a single command buffer
-        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-            label: Some("fdtd_step"),
-        });
-
-        // H-field update  (Shift&Add → Hadamard CP/CQ → Sum)
-        {
-            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                label: Some("H update"),
-                timestamp_writes: None,
-            });
-            pass.set_pipeline(&pipeline_h);
-            pass.set_bind_group(0, &bg_h, &[]);
-            pass.dispatch_workgroups(wg_x, wg_y, wg_z);
+    if let Some(dir) = &options.output {
+        if let Err(e) = fs::create_dir_all(dir).and_then(|()| env::set_current_dir(dir)) {
+            eprintln!("cannot write under {}: {e}", dir.display());
+            return ExitCode::FAILURE;
         }
+    }
 
-        // E-field update  (Shift&Add → Hadamard CA/CB → Sum)
-        {
-            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                label: Some("E update"),
-                timestamp_writes: None,
-            });
-            pass.set_pipeline(&pipeline_e);
-            pass.set_bind_group(0, &bg_e, &[]);
-            pass.dispatch_workgroups(wg_x, wg_y, wg_z);
+    match cli.command.unwrap_or(Command::Run) {
+        Command::Run => {
+            trap_interrupts();
+            match run(&config) {
+                Ok(_) => {}
+                Err(e @ FdtdError::Interrupted { .. }) => {
+                    eprintln!("{e}");
+                    return ExitCode::from(130);
+                }
+                Err(e) => {
+                    eprintln!("{e}");
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+        Command::Sweep(args) => return run_sweep(scene_path.as_deref(), &config, &args, options),
+        Command::Validate => match dry_run::dry_run(&config) {
+            Ok(report) => {
+                println!("{report}");
+                if !report.ok() {
+                    return ExitCode::FAILURE;
+                }
+            }
+            Err(e) => {
+                eprintln!("{e}");
+                return ExitCode::FAILURE;
+            }
+        },
+        Command::Schema => print!("{}", scene_file::SCHEMA),
+        Command::Compare(args) => return compare_outputs(&args),
+        Command::Repl => {
+            if let Err(e) = config.validate() {
+                eprintln!("{e}");
+                return ExitCode::FAILURE;
+            }
+            if config.mode != Mode::ThreeD || config.bands.is_some() {
+                eprintln!("the REPL steps the 3D solver, not reduced modes or band diagrams");
+                return ExitCode::FAILURE;
+            }
+            // The prompt takes the place of the progress bar
+            if config.verbosity == Verbosity::Normal {
+                config.verbosity = Verbosity::Quiet;
+            }
+            let mut simulation = match Simulation::new(config, Scene::Structure) {
+                Ok(simulation) => simulation,
+                Err(e) => {
+                    eprintln!("{e}");
+                    return ExitCode::FAILURE;
+                }
+            };
+            let prompt = if io::stdin().is_terminal() { "fdtd> " } else { "" };
+            let (input, mut output) = (io::stdin().lock(), io::stdout());
+            if let Err(e) = repl::repl(&mut simulation, input, &mut output, prompt) {
+                eprintln!("{e}");
+                return ExitCode::FAILURE;
+            }
+            if let Err(e) = simulation.finish() {
+                eprintln!("{e}");
+                return ExitCode::FAILURE;
+            }
+        }
+        Command::Bench(args) => {
+            let cells = options.grid.unwrap_or([128; 3]);
+            let grid = &config.grid;
+            let mut bench = Config::new(Grid::uniform(cells, grid.dx, grid.dt), 1);
+            bench.adapter = config.adapter;
+            bench.backend = config.backend;
+            bench.power_preference = config.power_preference;
+            bench.cpu_threads = config.cpu_threads;
+            bench.steps_per_submit = config.steps_per_submit;
+            bench.verbosity = config.verbosity;
+            bench.output_format = config.output_format;
+            if let Err(e) = bench.validate() {
+                eprintln!("{e}");
+                return ExitCode::FAILURE;
+            }
+            let length = match args.duration {
+                Some(duration) => {
+                    // The bar cannot tell how far along a timed benchmark is
+                    if bench.verbosity == Verbosity::Normal {
+                        bench.verbosity = Verbosity::Quiet;
+                    }
+                    bench::BenchLength::Wall(duration)
+                }
+                None => bench::BenchLength::Steps(options.steps.unwrap_or(BENCH_STEPS)),
+            };
+            let mut report = match bench::bench(bench, length) {
+                Ok(report) => report,
+                Err(e) => {
+                    eprintln!("{e}");
+                    return ExitCode::FAILURE;
+                }
+            };
+            report.peak_bandwidth = args.peak_bandwidth.map(|gb_s| gb_s * 1e9);
+            match config.output_format {
+                OutputFormat::Text => println!("{report}"),
+                OutputFormat::Json => println!("{}", report.json()),
+            }
         }
-
-        // Copy probe value to staging buffer
-        encoder.copy_buffer_to_buffer(&buf_ez, probe_byte_offset, &buf_readback, 0, 4);
-
-        queue.submit(Some(encoder.finish()));
-
-        // Read back probe value
-        let slice = buf_readback.slice(..);
-        let (tx, rx) = std::sync::mpsc::channel();
-        slice.map_async(wgpu::MapMode::Read, move |result| {
-            tx.send(result).unwrap();
-        });
-        device.poll(wgpu::Maintain::Wait);
-        rx.recv().unwrap().unwrap();
-
-        let data = slice.get_mapped_range();
-        let value: f32 = *bytemuck::from_bytes(&data);
-        drop(data);
-        buf_readback.unmap();
-
-        println!("t={:4}  Ez[probe] = {:.6e}", n, value);
     }
+    ExitCode::SUCCESS
+}
 
-    println!("\nSimulation complete.");
+/// The command line's settings of a run over those of its scene.
+fn apply_options(config: &mut Config, options: &Options) {
+    if let Some(cells) = options.grid {
+        let g = config.grid;
+        let scale = |cell: [u32; 3]| rescale(cell, [g.nx, g.ny, g.nz], cells);
+        config.source = scale(config.source);
+        for probe in &mut config.probes {
+            if let Location::Cell(cell) = probe.at {
+                probe.at = Location::Cell(scale(cell));
+            }
+        }
+        let [nx, ny, nz] = cells;
+        config.grid = Grid { nx, ny, nz, ..config.grid };
+    }
+    if let Some(steps) = options.steps {
+        config.steps = steps;
+    }
+    config.adapter = options.adapter.clone();
+    config.backend = options.backend;
+    config.power_preference = options.power_preference;
+    if let Some(scheme) = options.scheme {
+        config.scheme = scheme;
+    }
+    if let Some(precision) = options.precision {
+        config.precision = precision;
+    }
+    if let Some(mode) = options.mode {
+        config.mode = mode;
+    }
+    config.cpu_threads = options.threads;
+    config.cluster = options.rank.map(|rank| Cluster {
+        rank,
+        hosts: options.hosts.clone(),
+    });
+    if let Some(steps) = options.steps_per_submit {
+        config.steps_per_submit = steps;
+    }
+    if let Some(steps) = options.probe_batch {
+        config.probe_batch = steps;
+    }
+    config.resume = options.resume.clone();
+    config.time_budget = options.time_budget;
+    config.checkpoint_every = options.checkpoint_every;
+    config.verbosity = match (options.quiet, options.verbose) {
+        (true, _) => Verbosity::Quiet,
+        (_, true) => Verbosity::Verbose,
+        _ => Verbosity::Normal,
+    };
+    config.output_format = options.output_format;
 }
 
-// ── tiny helpers for bind-group / layout construction ────────────────
+/// `cell` of a grid of `from` cells at the same fraction of one of `to`.
+fn rescale(cell: [u32; 3], from: [u32; 3], to: [u32; 3]) -> [u32; 3] {
+    std::array::from_fn(|a| {
+        let at = u64::from(cell[a]) * u64::from(to[a]) / u64::from(from[a].max(1));
+        (at as u32).min(to[a].saturating_sub(1))
+    })
+}
 
-fn bgl_storage_entry(binding: u32, read_only: bool) -> wgpu::BindGroupLayoutEntry {
-    wgpu::BindGroupLayoutEntry {
-        binding,
-        visibility: wgpu::ShaderStages::COMPUTE,
-        ty: wgpu::BindingType::Buffer {
-            ty: wgpu::BufferBindingType::Storage { read_only },
-            has_dynamic_offset: false,
-            min_binding_size: None,
-        },
-        count: None,
+/// `fdtd_3d sweep`: every case of the scene file's sweep, failing when one
+/// of them did.
+fn run_sweep(
+    path: Option<&Path>,
+    config: &Config,
+    args: &SweepArgs,
+    options: &Options,
+) -> ExitCode {
+    let Some(path) = path else {
+        eprintln!("a sweep runs the cases of a scene file: pass it with -c");
+        return ExitCode::FAILURE;
+    };
+    let workers = match sweep::workers(config, args.all_adapters, args.cpu_workers) {
+        Ok(workers) => workers,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    trap_interrupts();
+    let prepare = |config: &mut Config| apply_options(config, options);
+    let dir = Path::new(".");
+    let outcomes = match sweep::run(path, dir, &options.parameters, &workers, prepare) {
+        Ok(outcomes) => outcomes,
+        Err(e) => {
+            eprintln!("{}: {e}", path.display());
+            return ExitCode::FAILURE;
+        }
+    };
+    let failed = |o: &&sweep::Outcome| o.result.is_err();
+    match outcomes.iter().find(failed).map(|o| &o.result) {
+        Some(Err(FdtdError::Interrupted { .. })) => ExitCode::from(130),
+        Some(_) => ExitCode::FAILURE,
+        None => ExitCode::SUCCESS,
     }
 }
 
-fn bg_entry(binding: u32, resource: wgpu::BindingResource<'_>) -> wgpu::BindGroupEntry<'_> {
-    wgpu::BindGroupEntry { binding, resource }
+/// `fdtd_3d compare`: the error metrics of a run output against reference
+/// data, failing above the tolerance.
+fn compare_outputs(args: &CompareArgs) -> ExitCode {
+    let (run, reference) = match (compare::load(&args.run), compare::load(&args.reference)) {
+        (Ok(run), Ok(reference)) => (run, reference),
+        (Err(e), _) | (_, Err(e)) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut reference = if args.transpose { reference.transposed() } else { reference };
+    if args.resample && run.shape().len() <= 1 && reference.shape().len() <= 1 {
+        reference = compare::Data::vector(compare::resample(&reference.values, run.values.len()));
+    }
+    // a vector (a CSV column) is compared with any array of its length
+    let vector = run.shape().len() <= 1 || reference.shape().len() <= 1;
+    let same_length = run.values.len() == reference.values.len();
+    if run.shape() != reference.shape() && !(vector && same_length) {
+        let reversed: Vec<usize> = reference.shape().into_iter().rev().collect();
+        eprintln!(
+            "shapes differ: {:?} against {:?} of the reference{}",
+            run.dims,
+            reference.dims,
+            if run.shape() == reversed && !args.transpose { " (try --transpose)" } else { "" }
+        );
+        return ExitCode::FAILURE;
+    }
+    let comparison = compare::Comparison::of(&run.values, &reference.values);
+    println!("{comparison}");
+    match args.tolerance {
+        Some(tolerance) if comparison.l2 > tolerance || comparison.l2.is_nan() => {
+            eprintln!("relative L2 error above the tolerance {tolerance:e}");
+            ExitCode::FAILURE
+        }
+        _ => ExitCode::SUCCESS,
+    }
 }
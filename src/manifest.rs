@@ -0,0 +1,117 @@
+//! Machine-readable summary of a program run.
+//!
+//! At the end, `MONITOR_DIR/results.json` records what ran and what it
+//! produced, so that pipelines can pick up a run without parsing the
+//! console output:
+//!
+//! - `program`, `version`, `started` (Unix seconds) and `wall_seconds`;
+//! - `config`, the resolved configuration as built by the caller, e.g.
+//!   [`Config::to_json`](crate::Config::to_json);
+//! - `runs`, one entry per solver run (the reference run of normalised
+//!   spectra first) with the adapter, precision, grid, Δt, step count,
+//!   setup and stepping times and the throughput in cell updates per
+//!   second of stepping;
+//! - `artifacts`, every file under the output directories written since
+//!   the program started, sorted.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Value};
+
+/// One solver run.
+#[derive(Clone, Debug)]
+pub struct RunInfo {
+    pub scene: String,
+    pub adapter: String,
+    pub backend: String,
+    pub precision: String,
+    /// Cells along x, y and z of the grid actually stepped.
+    pub size: [u32; 3],
+    pub dt: f64,
+    /// Time steps taken, over all k-points of a band diagram.
+    pub steps: u32,
+    /// Device, coefficient and pipeline setup.
+    pub setup: Duration,
+    /// Time stepping up to the last step's completion on the device.
+    pub stepping: Duration,
+}
+
+impl RunInfo {
+    /// Cell updates per second of stepping.
+    pub fn throughput(&self) -> f64 {
+        let cells = self.size.iter().map(|&n| n as f64).product::<f64>();
+        cells * self.steps as f64 / self.stepping.as_secs_f64()
+    }
+
+    pub(crate) fn json(&self) -> Value {
+        json!({
+            "scene": self.scene,
+            "adapter": self.adapter,
+            "backend": self.backend,
+            "precision": self.precision,
+            "grid": self.size,
+            "dt": self.dt,
+            "steps": self.steps,
+            "setup_seconds": self.setup.as_secs_f64(),
+            "stepping_seconds": self.stepping.as_secs_f64(),
+            "cell_updates_per_second": self.throughput(),
+        })
+    }
+}
+
+/// Files under `dirs` (recursively) modified at or after `since`, sorted;
+/// missing directories are skipped.
+pub fn artifacts(dirs: &[&Path], since: SystemTime) -> io::Result<Vec<PathBuf>> {
+    fn walk(dir: &Path, since: SystemTime, out: &mut Vec<PathBuf>) -> io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let meta = entry.metadata()?;
+            if meta.is_dir() {
+                walk(&entry.path(), since, out)?;
+            } else if meta.modified()? >= since {
+                out.push(entry.path());
+            }
+        }
+        Ok(())
+    }
+    let mut out = Vec::new();
+    for dir in dirs {
+        if dir.is_dir() {
+            walk(dir, since, &mut out)?;
+        }
+    }
+    out.sort();
+    out.dedup();
+    Ok(out)
+}
+
+/// Write `dir/results.json` and return its path.
+pub fn write(
+    dir: &Path,
+    config: Value,
+    runs: &[RunInfo],
+    started: SystemTime,
+    wall: Duration,
+    outputs: &[&Path],
+) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let files = artifacts(outputs, started)?;
+    let unix = started
+        .duration_since(UNIX_EPOCH)
+        .map_or(0.0, |d| d.as_secs_f64());
+    let manifest = json!({
+        "program": env!("CARGO_PKG_NAME"),
+        "version": env!("CARGO_PKG_VERSION"),
+        "started": unix,
+        "wall_seconds": wall.as_secs_f64(),
+        "config": config,
+        "runs": runs.iter().map(RunInfo::json).collect::<Vec<_>>(),
+        "artifacts": files.iter().map(|p| p.display().to_string()).collect::<Vec<_>>(),
+    });
+    let path = dir.join("results.json");
+    fs::write(&path, serde_json::to_string_pretty(&manifest)? + "\n")?;
+    Ok(path)
+}
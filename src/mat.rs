@@ -0,0 +1,601 @@
+//! MATLAB v7.3 `.mat` output.
+//!
+//! A v7.3 MAT-file is an HDF5 file behind a 512-byte MATLAB header (the
+//! HDF5 user block), read by MATLAB's `load`/`matfile` and by h5py.
+//! [`MatFile`] collects numeric variables and writes them flat under the
+//! root group with the oldest HDF5 structures every reader supports:
+//! superblock version 0, a symbol-table root group (local heap, one B-tree
+//! leaf, one symbol-table node), version 1 object headers and contiguous
+//! little-endian datasets, each tagged with its `MATLAB_class` attribute.
+//! Complex values are the `{real, imag}` compound MATLAB writes.
+//!
+//! MATLAB is column-major, so a variable of MATLAB size `[n1, n2, …]` is an
+//! HDF5 dataset of shape `[…, n2, n1]` over the same values, first index
+//! fastest: an x-fastest field volume is `nx×ny×nz` in MATLAB.  Vectors are
+//! columns, `[n, 1]`.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+const SIGNATURE: &[u8; 8] = b"\x89HDF\r\n\x1a\n";
+const USER_BLOCK: usize = 512;
+const UNDEFINED: u64 = u64::MAX;
+/// Group B-tree node K (HDF5's default); the root group has one leaf.
+const TREE_K: usize = 16;
+const SUPERBLOCK_SIZE: usize = 96;
+const ENTRY_SIZE: usize = 40;
+
+const MSG_DATASPACE: u16 = 0x0001;
+const MSG_DATATYPE: u16 = 0x0003;
+const MSG_FILL_VALUE: u16 = 0x0005;
+const MSG_LAYOUT: u16 = 0x0008;
+const MSG_ATTRIBUTE: u16 = 0x000C;
+const MSG_SYMBOL_TABLE: u16 = 0x0011;
+
+/// Element type of a variable.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Class {
+    Double,
+    Single,
+    ComplexSingle,
+}
+
+impl Class {
+    fn matlab(self) -> &'static str {
+        match self {
+            Class::Double => "double",
+            Class::Single | Class::ComplexSingle => "single",
+        }
+    }
+
+    /// HDF5 datatype message body.
+    fn datatype(self) -> Vec<u8> {
+        let mut b = Bytes::default();
+        match self {
+            Class::Double => float(&mut b, 8),
+            Class::Single => float(&mut b, 4),
+            Class::ComplexSingle => {
+                // compound, version 1, two members
+                b.u8(0x16);
+                b.u8(2);
+                b.u16(0);
+                b.u32(8);
+                for (name, offset) in [("real", 0), ("imag", 4)] {
+                    // NUL-terminated, padded to eight bytes from its start
+                    let start = b.0.len();
+                    b.bytes(name.as_bytes());
+                    b.0.resize(start + (name.len() + 8) / 8 * 8, 0);
+                    b.u32(offset);
+                    b.u8(0); // scalar member
+                    b.0.extend([0; 3]);
+                    b.u32(0); // dimension permutation
+                    b.u32(0);
+                    b.0.extend([0; 16]); // dimension sizes
+                    float(&mut b, 4);
+                }
+            }
+        }
+        b.0
+    }
+}
+
+/// IEEE little-endian float datatype of `size` bytes.
+fn float(b: &mut Bytes, size: u32) {
+    let (sign, mantissa, exponent, bias) = match size {
+        4 => (31, 23, 8, 127),
+        _ => (63, 52, 11, 1023),
+    };
+    b.u8(0x11); // version 1, floating point
+    b.u8(0x20); // little-endian, implied leading mantissa bit
+    b.u8(sign);
+    b.u8(0);
+    b.u32(size);
+    b.u16(0); // bit offset
+    b.u16(8 * size as u16);
+    b.u8(mantissa); // exponent location
+    b.u8(exponent);
+    b.u8(0); // mantissa location
+    b.u8(mantissa);
+    b.u32(bias);
+}
+
+/// Little-endian writer.
+#[derive(Default)]
+struct Bytes(Vec<u8>);
+
+impl Bytes {
+    fn u8(&mut self, v: u8) {
+        self.0.push(v);
+    }
+
+    fn u16(&mut self, v: u16) {
+        self.0.extend(v.to_le_bytes());
+    }
+
+    fn u32(&mut self, v: u32) {
+        self.0.extend(v.to_le_bytes());
+    }
+
+    fn u64(&mut self, v: u64) {
+        self.0.extend(v.to_le_bytes());
+    }
+
+    fn bytes(&mut self, b: &[u8]) {
+        self.0.extend(b);
+    }
+
+    /// Zeros up to a multiple of eight bytes.
+    fn pad8(&mut self) {
+        self.0.resize(self.0.len().next_multiple_of(8), 0);
+    }
+
+    /// A version 1 object header holding `messages` (type, flags, body).
+    fn object_header(&mut self, messages: &[(u16, u8, Vec<u8>)]) {
+        let size: usize = messages
+            .iter()
+            .map(|(_, _, body)| 8 + body.len().next_multiple_of(8))
+            .sum();
+        self.u8(1);
+        self.u8(0);
+        self.u16(messages.len() as u16);
+        self.u32(1); // reference count
+        self.u32(size as u32);
+        self.u32(0); // alignment of the first message
+        for (kind, flags, body) in messages {
+            self.u16(*kind);
+            self.u16(body.len().next_multiple_of(8) as u16);
+            self.u8(*flags);
+            self.0.extend([0; 3]);
+            self.bytes(body);
+            self.pad8();
+        }
+    }
+}
+
+/// One variable: MATLAB size and raw little-endian values.
+struct Variable {
+    name: String,
+    size: Vec<usize>,
+    class: Class,
+    data: Vec<u8>,
+}
+
+impl Variable {
+    fn header(&self, address: u64) -> Vec<u8> {
+        let mut space = Bytes::default();
+        space.u8(1);
+        space.u8(self.size.len() as u8);
+        space.u8(0); // no maximum dimensions
+        space.0.extend([0; 5]);
+        for &n in self.size.iter().rev() {
+            space.u64(n as u64);
+        }
+
+        // allocated late, filled if set, no fill value
+        let fill = vec![2, 2, 2, 0];
+
+        let mut layout = Bytes::default();
+        layout.u8(3);
+        layout.u8(1); // contiguous
+        layout.u64(address);
+        layout.u64(self.data.len() as u64);
+
+        let mut header = Bytes::default();
+        header.object_header(&[
+            (MSG_DATASPACE, 0, space.0),
+            (MSG_DATATYPE, 1, self.class.datatype()),
+            (MSG_FILL_VALUE, 1, fill),
+            (MSG_LAYOUT, 0, layout.0),
+            (
+                MSG_ATTRIBUTE,
+                0,
+                text_attribute("MATLAB_class", self.class.matlab()),
+            ),
+        ]);
+        header.0
+    }
+}
+
+/// Attribute message of a scalar ASCII string.
+fn text_attribute(name: &str, value: &str) -> Vec<u8> {
+    let mut b = Bytes::default();
+    b.u8(1);
+    b.u8(0);
+    b.u16(name.len() as u16 + 1);
+    b.u16(8); // datatype size
+    b.u16(8); // dataspace size
+    b.bytes(name.as_bytes());
+    b.u8(0);
+    b.pad8();
+    b.u8(0x13); // version 1, string
+    b.u8(0); // null-terminated ASCII
+    b.u16(0);
+    b.u32(value.len() as u32);
+    b.u8(1); // scalar dataspace: version 1, rank 0
+    b.0.extend([0; 7]);
+    b.bytes(value.as_bytes());
+    b.0
+}
+
+/// A valid MATLAB variable name for `name`: letters, digits and
+/// underscores, starting with a letter, at most 63 characters.
+pub fn variable_name(name: &str) -> String {
+    let mut out: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if !out.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        out.insert_str(0, "v_");
+    }
+    out.truncate(63);
+    out
+}
+
+/// Variables for one `.mat` file.
+#[derive(Default)]
+pub struct MatFile {
+    variables: Vec<Variable>,
+}
+
+impl MatFile {
+    pub fn new() -> Self {
+        MatFile::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.variables.is_empty()
+    }
+
+    fn add(&mut self, name: &str, size: &[usize], len: usize, class: Class, data: Vec<u8>) {
+        assert!(size.len() >= 2, "MATLAB arrays have two dimensions or more");
+        assert_eq!(
+            size.iter().product::<usize>(),
+            len,
+            "one value per element of {name}"
+        );
+        self.variables.push(Variable {
+            name: variable_name(name),
+            size: size.to_vec(),
+            class,
+            data,
+        });
+    }
+
+    /// A double array of MATLAB size `size`, first index fastest; `name`
+    /// goes through [`variable_name`].
+    pub fn add_f64(&mut self, name: &str, size: &[usize], values: &[f64]) {
+        let data = bytemuck::cast_slice(values).to_vec();
+        self.add(name, size, values.len(), Class::Double, data);
+    }
+
+    /// A single array, as [`MatFile::add_f64`].
+    pub fn add_f32(&mut self, name: &str, size: &[usize], values: &[f32]) {
+        let data = bytemuck::cast_slice(values).to_vec();
+        self.add(name, size, values.len(), Class::Single, data);
+    }
+
+    /// A complex single array of (re, im) values, as [`MatFile::add_f64`].
+    pub fn add_complex32(&mut self, name: &str, size: &[usize], values: &[[f32; 2]]) {
+        let data = bytemuck::cast_slice(values).to_vec();
+        self.add(name, size, values.len(), Class::ComplexSingle, data);
+    }
+
+    /// Write the file; names must be unique once made valid.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let mut order: Vec<&Variable> = self.variables.iter().collect();
+        // symbol-table nodes list their members in strcmp order
+        order.sort_by(|a, b| a.name.as_bytes().cmp(b.name.as_bytes()));
+        let mut seen = BTreeSet::new();
+        if let Some(v) = order.iter().find(|v| !seen.insert(&v.name)) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("MAT variable {} given twice", v.name),
+            ));
+        }
+
+        // Local heap of the member names, the empty name first
+        let mut names = Bytes::default();
+        names.0.extend([0; 8]);
+        let offsets: Vec<u64> = order
+            .iter()
+            .map(|v| {
+                let offset = names.0.len() as u64;
+                names.bytes(v.name.as_bytes());
+                names.u8(0);
+                names.pad8();
+                offset
+            })
+            .collect();
+
+        // One symbol-table node holds every member: leaf K ≥ n / 2
+        let leaf_k = order.len().div_ceil(2).max(4);
+        if leaf_k > u16::MAX as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "too many MAT variables",
+            ));
+        }
+        let root = SUPERBLOCK_SIZE as u64;
+        let heap = root + 40;
+        let heap_data = heap + 32;
+        let tree = heap_data + names.0.len() as u64;
+        let node = tree + (24 + (2 * TREE_K + 1) * 8 + 2 * TREE_K * 8) as u64;
+        let mut next = node + (8 + 2 * leaf_k * ENTRY_SIZE) as u64;
+
+        // Dataset headers, each followed by its values
+        let mut headers = Vec::with_capacity(order.len());
+        for v in &order {
+            let size = v.header(0).len() as u64;
+            headers.push((next, v.header(next + size)));
+            next += size + v.data.len().next_multiple_of(8) as u64;
+        }
+        let end = next;
+
+        let mut b = Bytes::default();
+        // Superblock, version 0
+        b.bytes(SIGNATURE);
+        b.0.extend([0, 0, 0, 0, 0]); // versions of its parts
+        b.u8(8); // size of offsets
+        b.u8(8); // size of lengths
+        b.u8(0);
+        b.u16(leaf_k as u16);
+        b.u16(TREE_K as u16);
+        b.u32(0); // consistency flags
+        b.u64(USER_BLOCK as u64); // base address: the superblock
+        b.u64(UNDEFINED); // free space
+        b.u64(end);
+        b.u64(UNDEFINED); // driver information
+        b.u64(0); // root entry: name
+        b.u64(root);
+        b.u32(1); // cached symbol table
+        b.u32(0);
+        b.u64(tree);
+        b.u64(heap);
+
+        // Root group object header
+        let mut table = Bytes::default();
+        table.u64(tree);
+        table.u64(heap);
+        b.object_header(&[(MSG_SYMBOL_TABLE, 0, table.0)]);
+
+        // Local heap
+        b.bytes(b"HEAP");
+        b.0.extend([0; 4]); // version 0
+        b.u64(names.0.len() as u64);
+        b.u64(1); // no free block
+        b.u64(heap_data);
+        b.bytes(&names.0);
+
+        // Group B-tree: one leaf pointing at the symbol-table node, keyed
+        // by the empty name and the last name
+        b.bytes(b"TREE");
+        b.u8(0); // group node
+        b.u8(0); // leaf
+        b.u16(1);
+        b.u64(UNDEFINED);
+        b.u64(UNDEFINED);
+        b.u64(0);
+        b.u64(node);
+        b.u64(offsets.last().copied().unwrap_or(0));
+        b.0.resize(node as usize, 0);
+
+        // Symbol-table node
+        b.bytes(b"SNOD");
+        b.u8(1);
+        b.u8(0);
+        b.u16(order.len() as u16);
+        for (offset, (address, _)) in offsets.iter().zip(&headers) {
+            b.u64(*offset);
+            b.u64(*address);
+            b.u32(0); // nothing cached
+            b.u32(0);
+            b.0.extend([0; 16]);
+        }
+        b.0.resize(b.0.len() + (2 * leaf_k - order.len()) * ENTRY_SIZE, 0);
+
+        for (v, (_, header)) in order.iter().zip(&headers) {
+            b.bytes(header);
+            b.bytes(&v.data);
+            b.pad8();
+        }
+        debug_assert_eq!(b.0.len() as u64, end);
+
+        let mut file = io::BufWriter::new(fs::File::create(path)?);
+        file.write_all(&mat_header())?;
+        file.write_all(&b.0)?;
+        file.flush()
+    }
+}
+
+/// The MATLAB user block: text, no subsystem data, version 0x0200,
+/// little-endian ("IM").
+fn mat_header() -> [u8; USER_BLOCK] {
+    let text = format!(
+        "MATLAB 7.3 MAT-file, Platform: {}, Created by: fdtd_3d {} HDF5 schema 1.00 .",
+        std::env::consts::OS,
+        env!("CARGO_PKG_VERSION")
+    );
+    let mut header = [0; USER_BLOCK];
+    header[..116].fill(b' ');
+    let len = text.len().min(116);
+    header[..len].copy_from_slice(&text.as_bytes()[..len]);
+    header[124..128].copy_from_slice(&[0x00, 0x02, b'I', b'M']);
+    header
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A variable as read back: MATLAB size, `MATLAB_class`, element size
+    /// and raw values.
+    #[derive(Debug, PartialEq)]
+    struct Read {
+        size: Vec<usize>,
+        class: String,
+        element: usize,
+        data: Vec<u8>,
+    }
+
+    fn u16_at(b: &[u8], at: usize) -> usize {
+        u16::from_le_bytes(b[at..at + 2].try_into().unwrap()) as usize
+    }
+
+    fn u32_at(b: &[u8], at: usize) -> usize {
+        u32::from_le_bytes(b[at..at + 4].try_into().unwrap()) as usize
+    }
+
+    fn u64_at(b: &[u8], at: usize) -> usize {
+        u64::from_le_bytes(b[at..at + 8].try_into().unwrap()) as usize
+    }
+
+    fn c_string(b: &[u8], at: usize) -> String {
+        let end = b[at..].iter().position(|&c| c == 0).unwrap();
+        String::from_utf8(b[at..at + end].to_vec()).unwrap()
+    }
+
+    /// The messages (type, body) of the version 1 object header at `at`.
+    fn messages(b: &[u8], at: usize) -> Vec<(u16, &[u8])> {
+        assert_eq!(b[at], 1, "object header version");
+        let count = u16_at(b, at + 2);
+        let mut p = at + 16;
+        (0..count)
+            .map(|_| {
+                let (kind, size) = (u16_at(b, p) as u16, u16_at(b, p + 2));
+                let body = &b[p + 8..p + 8 + size];
+                p += 8 + size;
+                (kind, body)
+            })
+            .collect()
+    }
+
+    /// The variables of the root group of the HDF5 file `b`, read from the
+    /// format specification: the superblock at a power of two from 512,
+    /// the root's symbol table message, its local heap, a leaf B-tree and
+    /// its symbol-table nodes, and the dataspace, datatype, layout and
+    /// attribute messages of each dataset.
+    fn read_hdf5(b: &[u8]) -> Vec<(String, Read)> {
+        let base = [0, 512, 1024, 2048]
+            .into_iter()
+            .find(|&at| b[at..].starts_with(b"\x89HDF\r\n\x1a\n"))
+            .expect("an HDF5 signature");
+        assert_eq!(b[base + 8], 0, "superblock version");
+        assert_eq!((b[base + 13], b[base + 14]), (8, 8), "offset sizes");
+        assert_eq!(u64_at(b, base + 24), base, "base address");
+        assert_eq!(u64_at(b, base + 40) + base, b.len(), "end of file");
+        // the root group's symbol table entry follows the four addresses
+        let root = base + u64_at(b, base + 56 + 8);
+        let (_, table) = messages(b, root)
+            .into_iter()
+            .find(|&(kind, _)| kind == 0x11)
+            .expect("a symbol table message");
+        let (tree, heap) = (base + u64_at(table, 0), base + u64_at(table, 8));
+
+        assert_eq!(&b[heap..heap + 4], b"HEAP");
+        let names = base + u64_at(b, heap + 24);
+        assert_eq!(&b[tree..tree + 4], b"TREE");
+        assert_eq!((b[tree + 4], b[tree + 5]), (0, 0), "a group leaf");
+        let children = u16_at(b, tree + 6);
+        let mut variables = Vec::new();
+        for child in 0..children {
+            // keys and children alternate after the sibling addresses
+            let node = base + u64_at(b, tree + 24 + 8 + 16 * child);
+            assert_eq!(&b[node..node + 4], b"SNOD");
+            for entry in 0..u16_at(b, node + 6) {
+                let e = node + 8 + 40 * entry;
+                let name = c_string(b, names + u64_at(b, e));
+                variables.push((name, dataset(b, base, base + u64_at(b, e + 8))));
+            }
+        }
+        variables
+    }
+
+    fn dataset(b: &[u8], base: usize, header: usize) -> Read {
+        let (mut size, mut class, mut element, mut data) =
+            (Vec::new(), String::new(), 0, Vec::new());
+        for (kind, body) in messages(b, header) {
+            match kind {
+                0x0001 => {
+                    assert_eq!(body[0], 1, "dataspace version");
+                    let dims = (0..body[1] as usize).map(|d| u64_at(body, 8 + 8 * d));
+                    size = dims.rev().collect();
+                }
+                0x0003 => element = u32_at(body, 4),
+                0x0008 => {
+                    assert_eq!((body[0], body[1]), (3, 1), "a contiguous layout");
+                    let (at, len) = (base + u64_at(body, 2), u64_at(body, 10));
+                    data = b[at..at + len].to_vec();
+                }
+                0x000C => {
+                    let name = c_string(body, 8);
+                    let (type_size, space_size) = (u16_at(body, 4), u16_at(body, 6));
+                    let at = 8 + (u16_at(body, 2)).next_multiple_of(8);
+                    let value = at + type_size.next_multiple_of(8) + space_size.next_multiple_of(8);
+                    let len = u32_at(body, at + 4);
+                    assert_eq!(name, "MATLAB_class");
+                    class = String::from_utf8(body[value..value + len].to_vec()).unwrap();
+                }
+                _ => {}
+            }
+        }
+        Read {
+            size,
+            class,
+            element,
+            data,
+        }
+    }
+
+    fn bytes<T: bytemuck::Pod>(values: &[T]) -> Vec<u8> {
+        bytemuck::cast_slice(values).to_vec()
+    }
+
+    #[test]
+    fn variables_read_back_as_written() {
+        let trace = [1.0, -2.5, 1e-300];
+        let field: Vec<f32> = (0..12).map(|n| n as f32 * 0.5).collect();
+        let spectrum = [[1.0_f32, -1.0], [0.25, 4.0]];
+        let mut mat = MatFile::new();
+        mat.add_f64("trace", &[3, 1], &trace);
+        mat.add_f32("Ez", &[2, 3, 2], &field);
+        mat.add_complex32("1st spectrum", &[2, 1], &spectrum);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("results.mat");
+        mat.write(&path).unwrap();
+
+        let file = fs::read(&path).unwrap();
+        assert!(file.starts_with(b"MATLAB 7.3 MAT-file"));
+        assert_eq!(&file[126..128], b"IM");
+        let read = |size: &[usize], class: &str, element, data| Read {
+            size: size.to_vec(),
+            class: class.into(),
+            element,
+            data,
+        };
+        // in strcmp order
+        assert_eq!(
+            read_hdf5(&file),
+            [
+                ("Ez".into(), read(&[2, 3, 2], "single", 4, bytes(&field))),
+                ("trace".into(), read(&[3, 1], "double", 8, bytes(&trace))),
+                (
+                    "v_1st_spectrum".into(),
+                    read(&[2, 1], "single", 8, bytes(&spectrum))
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn names_are_unique_once_made_valid() {
+        let mut mat = MatFile::new();
+        mat.add_f64("a-b", &[1, 1], &[1.0]);
+        mat.add_f64("a_b", &[1, 1], &[2.0]);
+        let dir = tempfile::tempdir().unwrap();
+        let error = mat.write(&dir.path().join("twice.mat")).unwrap_err();
+        assert!(error.to_string().contains("a_b given twice"));
+        assert_eq!(variable_name(&"x".repeat(70)).len(), 63);
+    }
+}
@@ -0,0 +1,326 @@
+//! Material description and conversion to per-component update coefficients.
+//!
+//! The E-update is `E = CA·E + CB·curl(H)` and the H-update is
+//! `H = CP·H − CQ·curl(E)`.
+//! Each coefficient is stored per cell as a packed `vec4` whose x/y/z lanes
+//! belong to the Ex/Ey/Ez (or Hx/Hy/Hz) edge of that cell, so sub-cell models
+//! can modify a single edge without touching the other two components.
+//!
+//! Scenes with few distinct materials can instead upload the maps as a
+//! packed 8/16-bit material index per cell plus a small lookup table
+//! ([`IndexedCoefficients`]), which cuts coefficient memory from 32 bytes
+//! per cell and field to one or two.
+//!
+//! A [`MaterialMap`] sets every cell at once from an `ndarray` volume, e.g.
+//! a permittivity distribution computed or loaded elsewhere.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use ndarray::Array3;
+use serde::{Serialize, Serializer};
+
+use crate::grid::{Axis, Grid};
+
+pub const EPS0: f64 = 8.854187817e-12;
+pub const MU0: f64 = 1.2566370614e-6;
+
+/// Isotropic, non-dispersive linear material.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize)]
+pub struct Material {
+    /// Relative permittivity ε_r.
+    pub eps_r: f64,
+    /// Electric conductivity σ (S/m).
+    pub sigma: f64,
+    /// Relative permeability μ_r.
+    pub mu_r: f64,
+    /// Magnetic conductivity σ_m (Ω/m).
+    pub sigma_m: f64,
+}
+
+impl Material {
+    pub const VACUUM: Material = Material {
+        eps_r: 1.0,
+        sigma: 0.0,
+        mu_r: 1.0,
+        sigma_m: 0.0,
+    };
+
+    /// Electric coefficients (CA, CB) for time step `dt`.
+    pub fn e_coefficients(&self, dt: f64) -> (f32, f32) {
+        e_coefficients(self.eps_r * EPS0, self.sigma, dt)
+    }
+
+    /// Magnetic coefficients (CP, CQ) for time step `dt`.
+    pub fn h_coefficients(&self, dt: f64) -> (f32, f32) {
+        e_coefficients(self.mu_r * MU0, self.sigma_m, dt)
+    }
+}
+
+/// Semi-implicit lossy update coefficients:
+///   CA = (1 − σΔt/2ε)/(1 + σΔt/2ε),  CB = (Δt/ε)/(1 + σΔt/2ε).
+/// The same form gives CP/CQ with (μ, σ_m).
+pub fn e_coefficients(eps: f64, sigma: f64, dt: f64) -> (f32, f32) {
+    let (ca, cb) = e_coefficients_f64(eps, sigma, dt);
+    (ca as f32, cb as f32)
+}
+
+/// [`e_coefficients`] before rounding to f32.
+pub fn e_coefficients_f64(eps: f64, sigma: f64, dt: f64) -> (f64, f64) {
+    let loss = sigma * dt / (2.0 * eps);
+    ((1.0 - loss) / (1.0 + loss), (dt / eps) / (1.0 + loss))
+}
+
+/// Materials of every cell, indexed `[i, j, k]` with the grid's shape;
+/// serialised as that shape, not the cells.
+#[derive(Clone, Debug, Serialize)]
+pub enum MaterialMap {
+    /// Relative permittivity of lossless, non-magnetic cells.
+    Permittivity(#[serde(serialize_with = "array_shape")] Array3<f32>),
+    Materials(#[serde(serialize_with = "array_shape")] Array3<Material>),
+}
+
+fn array_shape<T, S: Serializer>(cells: &Array3<T>, serializer: S) -> Result<S::Ok, S::Error> {
+    cells.shape().serialize(serializer)
+}
+
+impl From<Array3<f32>> for MaterialMap {
+    fn from(eps_r: Array3<f32>) -> Self {
+        MaterialMap::Permittivity(eps_r)
+    }
+}
+
+impl From<Array3<Material>> for MaterialMap {
+    fn from(materials: Array3<Material>) -> Self {
+        MaterialMap::Materials(materials)
+    }
+}
+
+impl MaterialMap {
+    /// Cells along x, y and z.
+    pub fn shape(&self) -> [usize; 3] {
+        let dim = match self {
+            MaterialMap::Permittivity(eps_r) => eps_r.dim(),
+            MaterialMap::Materials(materials) => materials.dim(),
+        };
+        [dim.0, dim.1, dim.2]
+    }
+
+    pub fn material(&self, i: usize, j: usize, k: usize) -> Material {
+        match self {
+            MaterialMap::Permittivity(eps_r) => Material {
+                eps_r: eps_r[[i, j, k]] as f64,
+                ..Material::VACUUM
+            },
+            MaterialMap::Materials(materials) => materials[[i, j, k]],
+        }
+    }
+
+    /// Set every cell of `grid`, whose shape the map must have.
+    pub fn apply(&self, grid: &Grid, coeffs: &mut Coefficients) {
+        for k in 0..grid.nz {
+            for j in 0..grid.ny {
+                for i in 0..grid.nx {
+                    let material = self.material(i as usize, j as usize, k as usize);
+                    coeffs.set_cell(grid.idx(i, j, k), &material, grid.dt);
+                }
+            }
+        }
+    }
+}
+
+/// Per-cell, per-component coefficient maps uploaded to the GPU.
+pub struct Coefficients {
+    pub ca: Vec<[f32; 4]>,
+    pub cb: Vec<[f32; 4]>,
+    pub cp: Vec<[f32; 4]>,
+    pub cq: Vec<[f32; 4]>,
+}
+
+impl Coefficients {
+    /// Fill the whole grid with a single material.
+    pub fn uniform(grid: &Grid, material: &Material) -> Self {
+        let (ca, cb) = material.e_coefficients(grid.dt);
+        let (cp, cq) = material.h_coefficients(grid.dt);
+        let n = grid.total();
+        Coefficients {
+            ca: vec![[ca, ca, ca, 0.0]; n],
+            cb: vec![[cb, cb, cb, 0.0]; n],
+            cp: vec![[cp, cp, cp, 0.0]; n],
+            cq: vec![[cq, cq, cq, 0.0]; n],
+        }
+    }
+
+    /// The maps of the z planes `planes` of `grid`, as the grid of those
+    /// planes alone.
+    pub fn planes(&self, grid: &Grid, planes: Range<u32>) -> Coefficients {
+        let plane = (grid.nx * grid.ny) as usize;
+        let cells = planes.start as usize * plane..planes.end as usize * plane;
+        Coefficients {
+            ca: self.ca[cells.clone()].to_vec(),
+            cb: self.cb[cells.clone()].to_vec(),
+            cp: self.cp[cells.clone()].to_vec(),
+            cq: self.cq[cells].to_vec(),
+        }
+    }
+
+    /// Fill all six edges of cell `id` with `material`.
+    pub fn set_cell(&mut self, id: usize, material: &Material, dt: f64) {
+        let (ca, cb) = material.e_coefficients(dt);
+        let (cp, cq) = material.h_coefficients(dt);
+        self.ca[id] = [ca, ca, ca, 0.0];
+        self.cb[id] = [cb, cb, cb, 0.0];
+        self.cp[id] = [cp, cp, cp, 0.0];
+        self.cq[id] = [cq, cq, cq, 0.0];
+    }
+
+    /// Overwrite the E-coefficients of one edge.
+    pub fn set_e(&mut self, id: usize, axis: Axis, ca: f32, cb: f32) {
+        self.ca[id][axis.lane()] = ca;
+        self.cb[id][axis.lane()] = cb;
+    }
+
+    /// Recover the (ε, σ) of one E edge from its current (CA, CB).
+    /// Returns `None` for PEC edges (CB = 0).
+    pub fn e_material(&self, id: usize, axis: Axis, dt: f64) -> Option<(f64, f64)> {
+        let l = axis.lane();
+        let (ca, cb) = (self.ca[id][l] as f64, self.cb[id][l] as f64);
+        if cb == 0.0 {
+            return None;
+        }
+        let loss = (1.0 - ca) / (1.0 + ca);
+        let eps = dt / (cb * (1.0 + loss));
+        Some((eps, 2.0 * eps * loss / dt))
+    }
+
+    /// Set one E edge from an absolute permittivity and conductivity.
+    pub fn set_e_material(&mut self, id: usize, axis: Axis, eps: f64, sigma: f64, dt: f64) {
+        let (ca, cb) = e_coefficients(eps, sigma, dt);
+        self.set_e(id, axis, ca, cb);
+    }
+
+    /// Add conductivity `dsigma` to one E edge, keeping its permittivity.
+    ///
+    /// The edge's (ε, σ) pair is recovered from its current (CA, CB), so this
+    /// composes with whatever material was rasterized there before.  PEC
+    /// edges (CB = 0) are left untouched.
+    pub fn add_e_conductivity(&mut self, id: usize, axis: Axis, dsigma: f64, dt: f64) {
+        if let Some((eps, sigma)) = self.e_material(id, axis, dt) {
+            self.set_e_material(id, axis, eps, sigma + dsigma, dt);
+        }
+    }
+
+    /// Add permittivity `deps` (F/m) to one E edge, keeping its conductivity.
+    pub fn add_e_permittivity(&mut self, id: usize, axis: Axis, deps: f64, dt: f64) {
+        if let Some((eps, sigma)) = self.e_material(id, axis, dt) {
+            self.set_e_material(id, axis, eps + deps, sigma, dt);
+        }
+    }
+
+    /// Recover the (μ, σ_m) of one H edge from its current (CP, CQ).
+    /// Returns `None` for edges with CQ = 0.
+    pub fn h_material(&self, id: usize, axis: Axis, dt: f64) -> Option<(f64, f64)> {
+        let l = axis.lane();
+        let (cp, cq) = (self.cp[id][l] as f64, self.cq[id][l] as f64);
+        if cq == 0.0 {
+            return None;
+        }
+        let loss = (1.0 - cp) / (1.0 + cp);
+        let mu = dt / (cq * (1.0 + loss));
+        Some((mu, 2.0 * mu * loss / dt))
+    }
+
+    /// Set one H edge from an absolute permeability and magnetic conductivity.
+    pub fn set_h_material(&mut self, id: usize, axis: Axis, mu: f64, sigma_m: f64, dt: f64) {
+        let (cp, cq) = e_coefficients(mu, sigma_m, dt);
+        self.set_h(id, axis, cp, cq);
+    }
+
+    /// Overwrite the H-coefficients of one edge.
+    pub fn set_h(&mut self, id: usize, axis: Axis, cp: f32, cq: f32) {
+        self.cp[id][axis.lane()] = cp;
+        self.cq[id][axis.lane()] = cq;
+    }
+}
+
+/// How the coefficient maps are stored on the GPU.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum CoefficientStorage {
+    /// One `vec4` per cell and coefficient.
+    Dense,
+    /// Material index per cell into a lookup table; falls back to
+    /// [`Dense`](CoefficientStorage::Dense) with more than 65536 distinct
+    /// coefficient sets.
+    Indexed,
+}
+
+/// Coefficient maps as packed per-cell indices into lookup tables.
+///
+/// Each table entry is a pair of `vec4`s, (CA, CB) for E and (CP, CQ) for H,
+/// so entry `m` occupies `lut[2m]` and `lut[2m + 1]`.  Indices are `bits`
+/// wide and packed little-end first into `u32` words.
+pub struct IndexedCoefficients {
+    /// Index width, 8 or 16.
+    pub bits: u32,
+    pub e_index: Vec<u32>,
+    pub e_lut: Vec<[f32; 4]>,
+    pub h_index: Vec<u32>,
+    pub h_lut: Vec<[f32; 4]>,
+}
+
+impl Coefficients {
+    /// Deduplicate the maps into an [`IndexedCoefficients`], or `None` when
+    /// there are too many distinct coefficient sets for a 16-bit index.
+    pub fn to_indexed(&self) -> Option<IndexedCoefficients> {
+        let (e_ids, e_lut) = lookup_table(&self.ca, &self.cb);
+        let (h_ids, h_lut) = lookup_table(&self.cp, &self.cq);
+        let entries = (e_lut.len() / 2).max(h_lut.len() / 2);
+        let bits = match entries {
+            0..=256 => 8,
+            257..=65536 => 16,
+            _ => return None,
+        };
+        Some(IndexedCoefficients {
+            bits,
+            e_index: pack(&e_ids, bits),
+            e_lut,
+            h_index: pack(&h_ids, bits),
+            h_lut,
+        })
+    }
+}
+
+/// Per-cell table index and the table of distinct (`a`, `b`) pairs.
+fn lookup_table(a: &[[f32; 4]], b: &[[f32; 4]]) -> (Vec<u32>, Vec<[f32; 4]>) {
+    let mut seen: HashMap<[u32; 8], u32> = HashMap::new();
+    let mut lut = Vec::new();
+    let ids = a
+        .iter()
+        .zip(b)
+        .map(|(va, vb)| {
+            let key = std::array::from_fn(|n| {
+                if n < 4 {
+                    va[n].to_bits()
+                } else {
+                    vb[n - 4].to_bits()
+                }
+            });
+            *seen.entry(key).or_insert_with(|| {
+                lut.push(*va);
+                lut.push(*vb);
+                (lut.len() / 2 - 1) as u32
+            })
+        })
+        .collect();
+    (ids, lut)
+}
+
+/// Pack `bits`-wide indices into `u32` words (at least one word).
+fn pack(ids: &[u32], bits: u32) -> Vec<u32> {
+    let per_word = (32 / bits) as usize;
+    let mut words = vec![0u32; ids.len().div_ceil(per_word).max(1)];
+    for (n, &id) in ids.iter().enumerate() {
+        words[n / per_word] |= id << ((n % per_word) as u32 * bits);
+    }
+    words
+}
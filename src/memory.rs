@@ -0,0 +1,213 @@
+//! GPU memory of a run, estimated before anything is allocated.
+//!
+//! A [`MemoryEstimate`] lists the buffers a run creates, grouped by what
+//! they hold: the fields and coefficient maps, the state of the implicit
+//! schemes and sub-cell models, the monitor accumulators and the readback
+//! staging.  [`Simulation::new`](crate::Simulation::new) checks every
+//! buffer against the adapter's `max_buffer_size` and
+//! `max_storage_buffer_binding_size` before requesting the device, and
+//! fails with the largest grid of the same shape that would fit; the device
+//! is then asked for limits as large as the largest buffer.  `fdtd_3d
+//! validate` prints the table.  wgpu does not tell how much memory
+//! the device has, so the total is only reported.
+
+use std::fmt;
+
+use crate::error::FdtdError;
+
+/// Buffers of one kind.
+#[derive(Clone, Debug)]
+pub struct Allocation {
+    pub name: &'static str,
+    /// Number of buffers.
+    pub count: u64,
+    /// Bytes of the largest one.
+    pub largest: u64,
+    /// Bytes of them all.
+    pub bytes: u64,
+    /// Bytes per grid cell of each, for buffers that grow with the grid.
+    pub per_cell: Option<u64>,
+}
+
+/// The buffers of a run on a grid of `size` cells.
+#[derive(Clone, Debug)]
+pub struct MemoryEstimate {
+    pub size: [u32; 3],
+    pub allocations: Vec<Allocation>,
+}
+
+impl MemoryEstimate {
+    pub fn new(size: [u32; 3]) -> Self {
+        MemoryEstimate {
+            size,
+            allocations: Vec::new(),
+        }
+    }
+
+    fn cells(&self) -> u64 {
+        self.size.iter().map(|&n| n as u64).product()
+    }
+
+    /// `count` buffers of `per_cell` bytes for each cell of the grid.
+    pub fn per_cell(&mut self, name: &'static str, count: u64, per_cell: u64) {
+        if count > 0 {
+            let largest = per_cell * self.cells();
+            self.allocations.push(Allocation {
+                name,
+                count,
+                largest,
+                bytes: count * largest,
+                per_cell: Some(per_cell),
+            });
+        }
+    }
+
+    /// Buffers of `sizes` bytes, whatever the grid.
+    pub fn fixed(&mut self, name: &'static str, sizes: impl IntoIterator<Item = u64>) {
+        let sizes: Vec<u64> = sizes.into_iter().collect();
+        if !sizes.is_empty() {
+            self.allocations.push(Allocation {
+                name,
+                count: sizes.len() as u64,
+                largest: sizes.iter().copied().max().unwrap_or(0),
+                bytes: sizes.iter().sum(),
+                per_cell: None,
+            });
+        }
+    }
+
+    /// Bytes of the largest buffer.
+    pub fn largest(&self) -> u64 {
+        self.allocations
+            .iter()
+            .map(|a| a.largest)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Bytes of all the buffers.
+    pub fn total(&self) -> u64 {
+        self.allocations.iter().map(|a| a.bytes).sum()
+    }
+
+    /// Check each buffer against the smaller of the device's buffer and
+    /// storage binding limits.
+    pub fn check(&self, limits: &wgpu::Limits) -> Result<(), FdtdError> {
+        let limit = limits
+            .max_buffer_size
+            .min(limits.max_storage_buffer_binding_size as u64);
+        match self.allocations.iter().find(|a| a.largest > limit) {
+            Some(a) => Err(FdtdError::BufferTooLarge {
+                buffer: a.name,
+                size: a.largest,
+                limit,
+                fits: a.per_cell.map(|_| self.largest_grid(limit)),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// The largest grid of the same proportions whose buffers are all
+    /// within `limit` bytes.
+    pub fn largest_grid(&self, limit: u64) -> [u32; 3] {
+        let per_cell = self
+            .allocations
+            .iter()
+            .filter_map(|a| a.per_cell)
+            .max()
+            .unwrap_or(1);
+        let max_cells = limit / per_cell;
+        let scale = (max_cells as f64 / self.cells() as f64).cbrt().min(1.0);
+        let mut size = self.size.map(|n| ((n as f64 * scale) as u32).max(1));
+        // Rounding may still leave it a few cells over
+        while size.iter().map(|&n| n as u64).product::<u64>() > max_cells {
+            let axis = (0..3).max_by_key(|&a| size[a]).unwrap();
+            if size[axis] == 1 {
+                break;
+            }
+            size[axis] -= 1;
+        }
+        size
+    }
+}
+
+impl fmt::Display for MemoryEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mib = |bytes: u64| bytes as f64 / (1 << 20) as f64;
+        writeln!(
+            f,
+            "{:<22} {:>7} {:>12} {:>12}",
+            "GPU buffers", "count", "largest MiB", "total MiB"
+        )?;
+        for a in &self.allocations {
+            writeln!(
+                f,
+                "  {:<20} {:>7} {:>12.2} {:>12.2}",
+                a.name,
+                a.count,
+                mib(a.largest),
+                mib(a.bytes)
+            )?;
+        }
+        write!(
+            f,
+            "  {:<20} {:>7} {:>12} {:>12.2}",
+            "total",
+            "",
+            "",
+            mib(self.total())
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn estimate() -> MemoryEstimate {
+        let mut memory = MemoryEstimate::new([64, 64, 32]);
+        memory.per_cell("fields", 6, 4);
+        memory.per_cell("coefficients", 4, 16);
+        memory.fixed("probe history", [4096, 4096]);
+        memory
+    }
+
+    #[test]
+    fn largest_buffer_sets_the_limit() {
+        let memory = estimate();
+        assert_eq!(memory.largest(), 64 * 64 * 32 * 16);
+        assert_eq!(memory.total(), 64 * 64 * 32 * (6 * 4 + 4 * 16) + 8192);
+        let limits = wgpu::Limits {
+            max_buffer_size: memory.largest(),
+            max_storage_buffer_binding_size: memory.largest() as u32,
+            ..Default::default()
+        };
+        assert!(memory.check(&limits).is_ok());
+    }
+
+    #[test]
+    fn too_large_a_grid_reports_one_that_fits() {
+        let memory = estimate();
+        let limits = wgpu::Limits {
+            max_buffer_size: 1 << 30,
+            max_storage_buffer_binding_size: 1 << 20,
+            ..Default::default()
+        };
+        match memory.check(&limits) {
+            Err(FdtdError::BufferTooLarge {
+                buffer,
+                limit,
+                fits: Some(fits),
+                ..
+            }) => {
+                assert_eq!((buffer, limit), ("coefficients", 1 << 20));
+                let cells: u64 = fits.iter().map(|&n| n as u64).product();
+                assert!(cells * 16 <= 1 << 20);
+                // Still about the proportions of the grid, and not far under
+                assert_eq!(fits[0], fits[1]);
+                assert!(fits[0] > fits[2] && cells * 16 > (1 << 20) * 9 / 10);
+            }
+            other => panic!("expected a buffer too large, got {other:?}"),
+        }
+    }
+}
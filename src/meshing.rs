@@ -0,0 +1,128 @@
+//! Wavelength-driven mesh selection.
+//!
+//! The cell size follows from the shortest wavelength in the scene, λ_min =
+//! c / (f_max · n_max), with n_max the highest refractive index √(ε_r μ_r)
+//! among the objects; the grid covers the objects' bounding box plus a
+//! vacuum margin, and Δt follows from the Courant number as for the
+//! hard-coded grid.
+
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::geometry::Object;
+use crate::grid::Grid;
+use crate::materials::{EPS0, MU0};
+
+/// GPU bytes per cell: six f32 fields plus four vec4 coefficient maps.
+pub const BYTES_PER_CELL: u64 = 6 * 4 + 4 * 16;
+
+/// Mesh requirements.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct MeshSpec {
+    /// Highest frequency to resolve (Hz).
+    pub f_max: f64,
+    /// Cells per shortest wavelength (10–20 is usual).
+    pub cells_per_wavelength: f64,
+    /// Vacuum margin around the objects, in free-space wavelengths at
+    /// `f_max`.
+    pub padding: f64,
+    /// Courant number c·Δt/Δ.
+    pub courant: f64,
+    /// Simulated time (s).
+    pub duration: f64,
+}
+
+/// Mesh chosen for a scene.
+#[derive(Copy, Clone, Debug)]
+pub struct MeshPlan {
+    pub n_max: f64,
+    /// Cubic cell size (m).
+    pub spacing: f64,
+    pub dims: [u32; 3],
+    /// Lower corner of cell (0, 0, 0) (m).
+    pub origin: [f64; 3],
+    pub dt: f64,
+    pub steps: u32,
+}
+
+impl MeshSpec {
+    pub fn plan(&self, objects: &[Object]) -> MeshPlan {
+        let c0 = 1.0 / (EPS0 * MU0).sqrt();
+        let n_max = objects
+            .iter()
+            .map(|o| (o.material.eps_r * o.material.mu_r).sqrt())
+            .fold(1.0, f64::max);
+        let spacing = c0 / (self.f_max * n_max) / self.cells_per_wavelength;
+        let pad = self.padding * c0 / self.f_max;
+
+        let (mut lo, mut hi) = ([0.0; 3], [0.0; 3]);
+        for (n, object) in objects.iter().enumerate() {
+            let (min, max) = object.shape.bounds();
+            for d in 0..3 {
+                lo[d] = if n == 0 { min[d] } else { lo[d].min(min[d]) };
+                hi[d] = if n == 0 { max[d] } else { hi[d].max(max[d]) };
+            }
+        }
+        let dims =
+            std::array::from_fn(|d| ((hi[d] - lo[d] + 2.0 * pad) / spacing).ceil().max(1.0) as u32);
+        let dt = self.courant * spacing / c0;
+
+        MeshPlan {
+            n_max,
+            spacing,
+            dims,
+            origin: lo.map(|l| l - pad),
+            dt,
+            steps: (self.duration / dt).ceil() as u32,
+        }
+    }
+}
+
+impl MeshPlan {
+    pub fn cells(&self) -> u64 {
+        self.dims.iter().map(|&n| n as u64).product()
+    }
+
+    /// Field and coefficient storage on the GPU (bytes).
+    pub fn memory(&self) -> u64 {
+        self.cells() * BYTES_PER_CELL
+    }
+
+    pub fn grid(&self) -> Grid {
+        Grid {
+            nx: self.dims[0],
+            ny: self.dims[1],
+            nz: self.dims[2],
+            dx: self.spacing,
+            dy: self.spacing,
+            dz: self.spacing,
+            dt: self.dt,
+            graded: [None; 3],
+            origin: self.origin,
+        }
+    }
+}
+
+impl fmt::Display for MeshPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [nx, ny, nz] = self.dims;
+        writeln!(
+            f,
+            "n_max {:.3}, Δ = {:.4e} m, Δt = {:.4e} s",
+            self.n_max, self.spacing, self.dt
+        )?;
+        writeln!(
+            f,
+            "grid {nx}×{ny}×{nz} ({} cells) from {:?} m",
+            self.cells(),
+            self.origin
+        )?;
+        write!(
+            f,
+            "{} steps, {:.1} MiB on the GPU",
+            self.steps,
+            self.memory() as f64 / (1024.0 * 1024.0)
+        )
+    }
+}
@@ -0,0 +1,295 @@
+//! Waveguide mode-overlap monitors.
+//!
+//! A mode monitor is a flux rectangle across a waveguide whose DFT fields
+//! are projected on mode profiles (E_m, H_m).  With the overlaps
+//!
+//!   C₁ = ∫ (E × H_m*)·n̂ dA,   C₂ = ∫ (E_m* × H)·n̂ dA,
+//!   N = ∫ (E_m × H_m*)·n̂ dA
+//!
+//! the complex amplitudes of the mode travelling along +normal and −normal
+//! are
+//!
+//!   a± = (C₁ ± C₂) / 2N
+//!
+//! (exact for a field a₊(E_m, H_m) + a₋(E_m, −H_m) and a real N), and they
+//! carry the powers |a±|²·Re N / 2.  Per monitor, `<name>_modes.csv` lists
+//! both amplitudes and powers of every mode and frequency next to the total
+//! flux through the plane, so the ratios between monitors give per-mode
+//! transmission and modal crosstalk.
+//!
+//! Profiles are either the TE_mn / TM_mn modes of a rectangular metal guide
+//! whose walls are the edges of the rectangle, filled with a uniform
+//! dielectric, or stored: the DFT files of an earlier run's monitor on the
+//! same rectangle and frequencies (e.g. one port of a straight guide fed
+//! with a single mode, see [`crate::dft`]).  Only propagating modes have a
+//! real N; the amplitudes of modes below cutoff are not meaningful.
+
+use std::f64::consts::PI;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use rustfft::num_complex::Complex64;
+use serde::Serialize;
+
+use crate::config::ConfigError;
+use crate::dft::{self, DftMonitor, Spectrum};
+use crate::flux::{FluxMonitor, Sample};
+use crate::grid::{Axis, Grid};
+use crate::materials::{EPS0, MU0};
+
+/// A mode profile to project on.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub enum ModeProfile {
+    /// TE_mn of the rectangular guide (m, n not both 0).
+    Te { m: u32, n: u32 },
+    /// TM_mn of the rectangular guide (m, n ≥ 1).
+    Tm { m: u32, n: u32 },
+    /// The spectra `<dir>/<name>_<component>.bin` of an earlier run.
+    Stored {
+        dir: &'static str,
+        name: &'static str,
+    },
+}
+
+impl ModeProfile {
+    /// Name of the mode in output, e.g. `TE10`.
+    pub fn label(&self) -> String {
+        match self {
+            ModeProfile::Te { m, n } => format!("TE{m}{n}"),
+            ModeProfile::Tm { m, n } => format!("TM{m}{n}"),
+            ModeProfile::Stored { name, .. } => name.to_string(),
+        }
+    }
+}
+
+/// A mode-overlap monitor on the E plane `index` normal to `normal`.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct ModeMonitor {
+    pub name: &'static str,
+    pub normal: Axis,
+    /// E plane along the normal; must be at least 1.
+    pub index: u32,
+    /// Cell ranges along the first and second tangential axes.
+    pub u: (u32, u32),
+    pub v: (u32, u32),
+    /// Frequencies in Hz.
+    pub frequencies: &'static [f64],
+    /// Relative permittivity filling the guide of the TE/TM profiles.
+    pub eps_r: f64,
+    pub modes: &'static [ModeProfile],
+}
+
+/// Amplitudes and powers of one mode at one frequency.
+#[derive(Copy, Clone, Debug)]
+pub struct ModalAmplitude {
+    pub forward: Complex64,
+    pub backward: Complex64,
+    /// Powers (W) of the two directions.
+    pub forward_power: f64,
+    pub backward_power: f64,
+}
+
+impl ModeMonitor {
+    /// Fails unless the plane is inside `grid` and the guide has
+    /// frequencies and modes that exist.
+    pub fn check(&self, grid: &Grid) -> Result<(), ConfigError> {
+        self.plane().check(grid)?;
+        let missing = self.modes.iter().find(|mode| match mode {
+            ModeProfile::Te { m, n } => m + n == 0,
+            ModeProfile::Tm { m, n } => m * n == 0,
+            ModeProfile::Stored { .. } => false,
+        });
+        let problem = if self.frequencies.is_empty() {
+            "no frequencies".to_string()
+        } else if let Some(mode) = missing {
+            format!("no {} mode", mode.label())
+        } else {
+            return Ok(());
+        };
+        Err(ConfigError(format!(
+            "mode monitor {}: {problem}",
+            self.name
+        )))
+    }
+
+    /// The rectangle as a flux monitor.
+    pub fn plane(&self) -> FluxMonitor {
+        FluxMonitor {
+            name: self.name,
+            normal: self.normal,
+            index: self.index,
+            u: self.u,
+            v: self.v,
+            frequencies: self.frequencies,
+        }
+    }
+
+    /// The DFT monitor of the plane (at the frequencies
+    /// [`check`](Self::check) asks for).
+    pub fn dft_monitor(&self) -> DftMonitor {
+        self.plane()
+            .dft_monitor()
+            .expect("mode monitors are checked for frequencies")
+    }
+
+    /// The TE/TM profile at the points of `samples` at frequency
+    /// `frequency`, with unit peak transverse E scale.
+    fn analytic(
+        &self,
+        grid: &Grid,
+        mode: ModeProfile,
+        frequency: f64,
+        samples: &[[Sample; 2]],
+    ) -> Vec<[Sample; 2]> {
+        let (u, v) = self.normal.tangential();
+        let (u0, v0) = (grid.node(u, self.u.0), grid.node(v, self.v.0));
+        let a = grid.node(u, self.u.1) - u0;
+        let b = grid.node(v, self.v.1) - v0;
+        let (te, m, n) = match mode {
+            ModeProfile::Te { m, n } => (true, m as f64, n as f64),
+            ModeProfile::Tm { m, n } => (false, m as f64, n as f64),
+            ModeProfile::Stored { .. } => unreachable!(),
+        };
+        let (kx, ky) = (m * PI / a, n * PI / b);
+        let omega = 2.0 * PI * frequency;
+        let k2 = omega * omega * MU0 * EPS0 * self.eps_r;
+        let beta = Complex64::new(k2 - kx * kx - ky * ky, 0.0).sqrt();
+        // Wave impedance E_t / H_t of the mode
+        let z = if te {
+            omega * MU0 / beta
+        } else {
+            beta / (omega * EPS0 * self.eps_r)
+        };
+        // Transverse E of TE ∝ n̂ × ∇H_z and of TM ∝ ∇E_z
+        let field = |at: [f64; 3]| {
+            let (x, y) = (at[u.lane()] - u0, at[v.lane()] - v0);
+            let (cx, sx) = ((kx * x).cos(), (kx * x).sin());
+            let (cy, sy) = ((ky * y).cos(), (ky * y).sin());
+            if te {
+                [ky * cx * sy, -kx * sx * cy]
+            } else {
+                [kx * cx * sy, ky * sx * cy]
+            }
+        };
+        let scale = 1.0 / (kx * kx + ky * ky).sqrt();
+        samples
+            .iter()
+            .map(|[pu, pv]| {
+                let e_u = Complex64::new(scale * field(pu.at)[0], 0.0);
+                let e_v = Complex64::new(scale * field(pv.at)[1], 0.0);
+                // H_t = n̂ × E_t / Z: H_v = E_u / Z, H_u = −E_v / Z
+                [
+                    Sample {
+                        e: e_u,
+                        h: e_u / z,
+                        ..*pu
+                    },
+                    Sample {
+                        e: e_v,
+                        h: -e_v / z,
+                        ..*pv
+                    },
+                ]
+            })
+            .collect()
+    }
+
+    /// Amplitudes of every mode at every frequency from the plane's
+    /// spectrum, stored profiles read from disk.
+    pub fn amplitudes(
+        &self,
+        grid: &Grid,
+        spectrum: &Spectrum,
+    ) -> io::Result<Vec<Vec<ModalAmplitude>>> {
+        let monitor = self.dft_monitor();
+        let mut stored = Vec::new();
+        for mode in self.modes {
+            stored.push(match *mode {
+                ModeProfile::Stored { dir, name } => {
+                    Some(dft::read_spectrum(dir.as_ref(), name, &monitor, grid)?)
+                }
+                _ => None,
+            });
+        }
+        let plane = self.plane();
+        Ok((0..self.frequencies.len())
+            .map(|f| {
+                let samples = plane.samples(grid, spectrum, f);
+                self.modes
+                    .iter()
+                    .zip(&stored)
+                    .map(|(&mode, stored)| {
+                        let profile = match stored {
+                            Some(s) => plane.samples(grid, s, f),
+                            None => self.analytic(grid, mode, self.frequencies[f], &samples),
+                        };
+                        overlap(&samples, &profile)
+                    })
+                    .collect()
+            })
+            .collect())
+    }
+}
+
+/// a± of the field `samples` on the mode `profile` at the same points.
+fn overlap(samples: &[[Sample; 2]], profile: &[[Sample; 2]]) -> ModalAmplitude {
+    let zero = Complex64::new(0.0, 0.0);
+    let (mut c1, mut c2, mut norm) = (zero, zero, zero);
+    for ([pu, pv], [mu, mv]) in samples.iter().zip(profile) {
+        // (A × B)·n̂ = A_u·B_v − A_v·B_u
+        c1 += (pu.e * mu.h.conj()) * pu.area - (pv.e * mv.h.conj()) * pv.area;
+        c2 += (mu.e.conj() * pu.h) * pu.area - (mv.e.conj() * pv.h) * pv.area;
+        norm += (mu.e * mu.h.conj()) * mu.area - (mv.e * mv.h.conj()) * mv.area;
+    }
+    let forward = (c1 + c2) / (2.0 * norm);
+    let backward = (c1 - c2) / (2.0 * norm);
+    ModalAmplitude {
+        forward,
+        backward,
+        forward_power: 0.5 * forward.norm_sqr() * norm.re,
+        backward_power: 0.5 * backward.norm_sqr() * norm.re,
+    }
+}
+
+/// Write `<name>_modes.csv` for every monitor, from the spectra of each in
+/// turn.  Returns the amplitudes per frequency and mode of each.
+pub fn write_results(
+    dir: &Path,
+    grid: &Grid,
+    monitors: &[ModeMonitor],
+    spectra: &[Spectrum],
+) -> io::Result<Vec<Vec<Vec<ModalAmplitude>>>> {
+    let mut results = Vec::new();
+    for (monitor, spectrum) in monitors.iter().zip(spectra) {
+        let amplitudes = monitor.amplitudes(grid, spectrum)?;
+        let total = monitor.plane().spectrum(grid, spectrum);
+
+        fs::create_dir_all(dir)?;
+        let path = dir.join(format!("{}_modes.csv", monitor.name));
+        let mut file = BufWriter::new(fs::File::create(path)?);
+        writeln!(
+            file,
+            "frequency,mode,forward_re,forward_im,backward_re,backward_im,\
+             forward_power,backward_power,plane_power"
+        )?;
+        for ((frequency, modes), p) in monitor.frequencies.iter().zip(&amplitudes).zip(&total) {
+            for (mode, a) in monitor.modes.iter().zip(modes) {
+                writeln!(
+                    file,
+                    "{frequency:e},{},{:e},{:e},{:e},{:e},{:e},{:e},{p:e}",
+                    mode.label(),
+                    a.forward.re,
+                    a.forward.im,
+                    a.backward.re,
+                    a.backward.im,
+                    a.forward_power,
+                    a.backward_power
+                )?;
+            }
+        }
+        file.flush()?;
+        results.push(amplitudes);
+    }
+    Ok(results)
+}
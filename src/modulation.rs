@@ -0,0 +1,207 @@
+//! Time-varying materials (switched photoconductors, time-modulated media).
+//!
+//! A modulated region keeps its magnetic properties but has ε_r(t) and σ(t)
+//! given by a [`Modulation`] schedule.  The host evaluates the schedule once
+//! per region per step and, only when the coefficients actually change,
+//! uploads the new (CA, CB) pair; `shaders/modulate.wgsl` scatters it into
+//! the region's cells.  The update uses the instantaneous ε in the E-form of
+//! Ampère's law, which is adequate for modulation slow compared with Δt.
+
+use bytemuck::{Pod, Zeroable};
+use serde::Serialize;
+use wgpu::util::DeviceExt;
+
+use crate::geometry::Shape;
+use crate::gpu::{bg_entry, bgl_storage_entry, compute_pipeline, groups_1d, Uploads};
+use crate::grid::Grid;
+use crate::materials::{e_coefficients, Coefficients, Material, EPS0};
+
+/// Time dependence of a modulated region.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub enum Modulation {
+    /// Switch from `base` to `to` at step `on`, and back at step `off`.
+    Switch { to: Material, on: u32, off: u32 },
+    /// ε_r(t) = ε_r + Δε_r·sin(2πft), σ(t) = σ + Δσ·sin(2πft).
+    Sinusoidal {
+        delta_eps_r: f64,
+        delta_sigma: f64,
+        freq: f64,
+    },
+}
+
+/// A region whose E coefficients follow a schedule.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct ModulatedRegion {
+    pub shape: Shape,
+    pub base: Material,
+    pub modulation: Modulation,
+}
+
+impl ModulatedRegion {
+    /// Paint the base material; the schedule takes over during the run.
+    pub fn apply(&self, grid: &Grid, coeffs: &mut Coefficients) {
+        for (id, inside) in grid.mask(&self.shape).into_iter().enumerate() {
+            if inside {
+                coeffs.set_cell(id, &self.base, grid.dt);
+            }
+        }
+    }
+
+    /// (ε, σ) at time step `n`.
+    pub fn material_at(&self, n: u32, dt: f64) -> (f64, f64) {
+        match self.modulation {
+            Modulation::Switch { to, on, off } if (on..off).contains(&n) => {
+                (to.eps_r * EPS0, to.sigma)
+            }
+            Modulation::Switch { .. } => (self.base.eps_r * EPS0, self.base.sigma),
+            Modulation::Sinusoidal {
+                delta_eps_r,
+                delta_sigma,
+                freq,
+            } => {
+                let s = (2.0 * std::f64::consts::PI * freq * n as f64 * dt).sin();
+                (
+                    (self.base.eps_r + delta_eps_r * s) * EPS0,
+                    self.base.sigma + delta_sigma * s,
+                )
+            }
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct Entry {
+    cell: u32,
+    region: u32,
+}
+
+/// GPU resources and host-side schedule state for all modulated regions.
+pub struct ModulationPass {
+    regions: Vec<ModulatedRegion>,
+    current: Vec<[f32; 2]>,
+    pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    buf_values: wgpu::Buffer,
+    count: u32,
+}
+
+impl ModulationPass {
+    /// Collect the regions' cells and bind the CA/CB buffers.
+    /// Returns `None` when there are no modulated cells.
+    pub fn new(
+        device: &wgpu::Device,
+        grid: &Grid,
+        regions: &[ModulatedRegion],
+        ca: &wgpu::Buffer,
+        cb: &wgpu::Buffer,
+    ) -> Option<Self> {
+        let mut entries = Vec::new();
+        for (r, region) in regions.iter().enumerate() {
+            for (id, inside) in grid.mask(&region.shape).into_iter().enumerate() {
+                if inside {
+                    entries.push(Entry {
+                        cell: id as u32,
+                        region: r as u32,
+                    });
+                }
+            }
+        }
+        if entries.is_empty() {
+            return None;
+        }
+
+        let buf_entries = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("modulation_entries"),
+            contents: bytemuck::cast_slice(&entries),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let buf_values = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("modulation_values"),
+            size: (regions.len() * 8) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("modulate_bgl"),
+            entries: &[
+                bgl_storage_entry(0, true),
+                bgl_storage_entry(1, true),
+                bgl_storage_entry(2, false),
+                bgl_storage_entry(3, false),
+            ],
+        });
+        let pipeline = compute_pipeline(
+            device,
+            "modulate",
+            include_str!("shaders/modulate.wgsl"),
+            &bgl,
+        );
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("bg_modulate"),
+            layout: &bgl,
+            entries: &[
+                bg_entry(0, buf_entries.as_entire_binding()),
+                bg_entry(1, buf_values.as_entire_binding()),
+                bg_entry(2, ca.as_entire_binding()),
+                bg_entry(3, cb.as_entire_binding()),
+            ],
+        });
+
+        let current = regions
+            .iter()
+            .map(|r| {
+                let (ca, cb) = r.base.e_coefficients(grid.dt);
+                [ca, cb]
+            })
+            .collect();
+
+        Some(ModulationPass {
+            regions: regions.to_vec(),
+            current,
+            pipeline,
+            bind_group,
+            buf_values,
+            count: entries.len() as u32,
+        })
+    }
+
+    /// Evaluate the schedules for step `n` and, if any region changed,
+    /// upload the new coefficients and encode the scatter pass.
+    pub fn update(
+        &mut self,
+        n: u32,
+        dt: f64,
+        uploads: &mut Uploads,
+        encoder: &mut wgpu::CommandEncoder,
+    ) {
+        let next: Vec<[f32; 2]> = self
+            .regions
+            .iter()
+            .map(|r| {
+                let (eps, sigma) = r.material_at(n, dt);
+                let (ca, cb) = e_coefficients(eps, sigma, dt);
+                [ca, cb]
+            })
+            .collect();
+        if next == self.current {
+            return;
+        }
+        self.current = next;
+        uploads.write(
+            encoder,
+            &self.buf_values,
+            0,
+            bytemuck::cast_slice(&self.current),
+        );
+
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("modulate"),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.dispatch_workgroups(groups_1d(self.count, 64), 1, 1);
+    }
+}
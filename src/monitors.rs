@@ -0,0 +1,340 @@
+//! Line and plane field monitors.
+//!
+//! A monitor records every cell of one component on an axis-aligned line or
+//! plane every `every` steps.  Frames are gathered on the GPU into a history
+//! of `batch` frames and copied back a batch at a time, then written to
+//! `dir`:
+//!
+//! * `<name>.bin` — all frames, little-endian: magic `FDTDMON1`, `u32` nu,
+//!   nv and `every`, then nu·nv `f32` per frame, u fastest.  Frame f is
+//!   step f·every, with E at time (f·every + 1)·Δt.
+//! * lines: `<name>.png`, the space-time diagram written at the end of the
+//!   run, position to the right and time downwards;
+//! * planes: `<name>_<step:06>.png`, one movie frame per record.
+//!
+//! Images use the diverging map of [`crate::slices`], scaled to the largest
+//! magnitude of the diagram or of each frame; planes are oriented as slice
+//! images (first tangential axis to the right, second upwards).
+
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use bytemuck::{Pod, Zeroable};
+use serde::Serialize;
+
+use crate::config::ConfigError;
+use crate::error::FdtdError;
+use crate::gpu::{
+    self, bg_entry, bgl_storage_entry, bgl_uniform_entry, compute_pipeline, groups_1d, Uploads,
+};
+use crate::grid::{Axis, Field, Grid};
+use crate::png;
+use crate::slices::diverging;
+
+/// Cells covered by a monitor.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub enum Span {
+    /// Every cell along `axis` through cell `through` (whose coordinate
+    /// along `axis` is ignored).
+    Line { axis: Axis, through: [u32; 3] },
+    /// Every cell of the plane `index` normal to `normal`.
+    Plane { normal: Axis, index: u32 },
+}
+
+/// A named line or plane monitor of one component.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct Monitor {
+    pub name: &'static str,
+    pub field: Field,
+    pub span: Span,
+    /// Steps between frames (the first is recorded at step 0).
+    pub every: u32,
+    /// Frames gathered on the GPU per readback.
+    pub batch: u32,
+}
+
+impl Monitor {
+    /// Fails unless the line or plane is inside `grid` and frames are
+    /// recorded.
+    pub fn check(&self, grid: &Grid) -> Result<(), ConfigError> {
+        let inside = match self.span {
+            Span::Line { axis, through } => {
+                let mut start = through;
+                start[axis.lane()] = 0;
+                let [i, j, k] = start;
+                i < grid.nx && j < grid.ny && k < grid.nz
+            }
+            Span::Plane { normal, index } => index < grid.cells(normal),
+        };
+        let problem = match (inside, self.every > 0 && self.batch > 0) {
+            (false, _) => format!("{:?} outside the grid", self.span),
+            (true, false) => "every and batch must be at least 1".to_string(),
+            (true, true) => return Ok(()),
+        };
+        Err(ConfigError(format!("monitor {}: {problem}", self.name)))
+    }
+}
+
+/// Gather parameters (must match WGSL `MonitorParams`).
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct MonitorParams {
+    count: u32,
+    slot: u32,
+    base: u32,
+    nu: u32,
+    stride_u: u32,
+    stride_v: u32,
+    _pad: [u32; 2],
+}
+
+/// GPU and file state of one monitor.
+struct Recorder {
+    spec: Monitor,
+    params: MonitorParams,
+    buf_params: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    history: wgpu::Buffer,
+    staging: wgpu::Buffer,
+    nv: u32,
+    /// Steps of the frames in the history since the last copy.
+    filled: Vec<u32>,
+    /// Steps of the frames copied to the staging buffer and not yet taken.
+    pending: Vec<u32>,
+    file: BufWriter<fs::File>,
+    /// All frames of a line monitor, for the space-time diagram.
+    diagram: Vec<f32>,
+}
+
+/// All monitors of a run.
+pub struct MonitorPass {
+    pipeline: wgpu::ComputePipeline,
+    recorders: Vec<Recorder>,
+    dir: PathBuf,
+}
+
+impl MonitorPass {
+    /// `fields` are the six field buffers in (Ex, Ey, Ez, Hx, Hy, Hz) order.
+    pub fn new(
+        device: &wgpu::Device,
+        grid: &Grid,
+        monitors: &[Monitor],
+        dir: &str,
+        fields: [&wgpu::Buffer; 6],
+    ) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let dir = PathBuf::from(dir);
+        let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("monitors_bgl"),
+            entries: &[
+                bgl_uniform_entry(0),
+                bgl_storage_entry(1, true),
+                bgl_storage_entry(2, false),
+            ],
+        });
+        let pipeline = compute_pipeline(
+            device,
+            "monitors",
+            include_str!("shaders/monitors.wgsl"),
+            &bgl,
+        );
+
+        let stride = |axis: Axis| match axis {
+            Axis::X => 1,
+            Axis::Y => grid.nx,
+            Axis::Z => grid.nx * grid.ny,
+        };
+        let mut recorders = Vec::with_capacity(monitors.len());
+        for &spec in monitors {
+            // (base, u axis, v axis or none)
+            let (base, u, v) = match spec.span {
+                Span::Line { axis, through } => {
+                    let mut start = through;
+                    start[axis.lane()] = 0;
+                    let [i, j, k] = start;
+                    (grid.idx(i, j, k) as u32, axis, None)
+                }
+                Span::Plane { normal, index } => {
+                    let (u, v) = normal.tangential();
+                    (index * stride(normal), u, Some(v))
+                }
+            };
+            let (nu, nv) = (grid.cells(u), v.map_or(1, |v| grid.cells(v)));
+            let params = MonitorParams {
+                count: nu * nv,
+                slot: 0,
+                base,
+                nu,
+                stride_u: stride(u),
+                stride_v: v.map_or(0, stride),
+                _pad: [0; 2],
+            };
+            let buf_params = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("monitor_params"),
+                size: std::mem::size_of::<MonitorParams>() as u64,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            let size = (spec.batch * nu * nv) as u64 * 4;
+            let history = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("monitor_history"),
+                size,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            });
+            let staging = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("monitor_staging"),
+                size,
+                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("bg_monitor"),
+                layout: &bgl,
+                entries: &[
+                    bg_entry(0, buf_params.as_entire_binding()),
+                    bg_entry(1, fields[spec.field.index()].as_entire_binding()),
+                    bg_entry(2, history.as_entire_binding()),
+                ],
+            });
+
+            let path = dir.join(format!("{}.bin", spec.name));
+            let mut file = BufWriter::new(fs::File::create(path)?);
+            file.write_all(b"FDTDMON1")?;
+            for v in [nu, nv, spec.every] {
+                file.write_all(&v.to_le_bytes())?;
+            }
+            recorders.push(Recorder {
+                spec,
+                params,
+                buf_params,
+                bind_group,
+                history,
+                staging,
+                nv,
+                filled: Vec::new(),
+                pending: Vec::new(),
+                file,
+                diagram: Vec::new(),
+            });
+        }
+        Ok(MonitorPass {
+            pipeline,
+            recorders,
+            dir,
+        })
+    }
+
+    /// Record the frames due at step `n` and copy full batches (every
+    /// batch on the `last` step) to the staging buffers.
+    pub fn encode(
+        &mut self,
+        uploads: &mut Uploads,
+        encoder: &mut wgpu::CommandEncoder,
+        n: u32,
+        last: bool,
+    ) {
+        for r in &mut self.recorders {
+            if n.is_multiple_of(r.spec.every) {
+                r.params.slot = r.filled.len() as u32;
+                uploads.write(encoder, &r.buf_params, 0, bytemuck::bytes_of(&r.params));
+                let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some("monitor"),
+                    timestamp_writes: None,
+                });
+                pass.set_pipeline(&self.pipeline);
+                pass.set_bind_group(0, &r.bind_group, &[]);
+                pass.dispatch_workgroups(groups_1d(r.params.count, 64), 1, 1);
+                r.filled.push(n);
+            }
+            let full = r.filled.len() as u32 == r.spec.batch;
+            if full || (last && !r.filled.is_empty()) {
+                let size = (r.filled.len() as u32 * r.params.count) as u64 * 4;
+                encoder.copy_buffer_to_buffer(&r.history, 0, &r.staging, 0, size);
+                r.pending = std::mem::take(&mut r.filled);
+            }
+        }
+    }
+
+    /// Whether frames are copied for [`take`](Self::take) to write.
+    pub fn copied(&self) -> bool {
+        self.recorders.iter().any(|r| !r.pending.is_empty())
+    }
+
+    /// Write the frames copied by the last submitted
+    /// [`encode`](Self::encode).
+    pub fn take(&mut self, device: &wgpu::Device) -> Result<(), FdtdError> {
+        let ready: Vec<_> = self
+            .recorders
+            .iter_mut()
+            .filter(|r| !r.pending.is_empty())
+            .collect();
+        if ready.is_empty() {
+            return Ok(());
+        }
+        let slices: Vec<_> = ready
+            .iter()
+            .map(|r| {
+                let size = (r.pending.len() as u32 * r.params.count) as u64 * 4;
+                r.staging.slice(..size)
+            })
+            .collect();
+        gpu::map_read(device, &slices)?;
+
+        for r in ready {
+            let size = (r.pending.len() as u32 * r.params.count) as u64 * 4;
+            {
+                let data = r.staging.slice(..size).get_mapped_range();
+                r.file.write_all(&data)?;
+                let values: &[f32] = bytemuck::cast_slice(&data);
+                let frames = values.chunks(r.params.count as usize);
+                match r.spec.span {
+                    Span::Line { .. } => r.diagram.extend_from_slice(values),
+                    Span::Plane { .. } => {
+                        for (&n, frame) in r.pending.iter().zip(frames) {
+                            let path = self.dir.join(format!("{}_{n:06}.png", r.spec.name));
+                            write_image(&path, r.params.nu, r.nv, frame, true)?;
+                        }
+                    }
+                }
+            }
+            r.staging.unmap();
+            r.file.flush()?;
+            r.pending.clear();
+        }
+        Ok(())
+    }
+
+    /// Write the space-time diagrams of the line monitors.
+    pub fn finish(&self) -> io::Result<()> {
+        for r in &self.recorders {
+            if let Span::Line { .. } = r.spec.span {
+                let frames = r.diagram.len() as u32 / r.params.count;
+                if frames > 0 {
+                    let path = self.dir.join(format!("{}.png", r.spec.name));
+                    write_image(&path, r.params.nu, frames, &r.diagram, false)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Colour-map `values` (`width` per row) to a PNG scaled to their largest
+/// magnitude, with the first row at the bottom when `flip` is set.
+fn write_image(path: &Path, width: u32, height: u32, values: &[f32], flip: bool) -> io::Result<()> {
+    let scale = values
+        .iter()
+        .fold(0.0_f32, |m, x| m.max(x.abs()))
+        .max(f32::MIN_POSITIVE);
+    let mut rows: Vec<&[f32]> = values.chunks(width as usize).collect();
+    if flip {
+        rows.reverse();
+    }
+    let rgb: Vec<u8> = rows
+        .iter()
+        .flat_map(|row| row.iter().flat_map(|&x| diverging(x / scale)))
+        .collect();
+    png::write_rgb(path, width, height, &rgb)
+}
@@ -0,0 +1,199 @@
+//! Moving computational window along +x.
+//!
+//! Once the pulse has entered the grid the window follows it: every time
+//! the accumulated travel reaches one cell, all fields and coefficient maps
+//! are shifted one cell towards −x and the new leading face is filled with
+//! zero fields and the coefficients of the scene at its lab-frame position.
+//! A long propagation path then only needs a grid as long as the pulse and
+//! its near surroundings.
+//!
+//! The window carries dense coefficient maps only, so it applies to scenes
+//! built from objects: sub-cell models, modulated regions and subgrids are
+//! tied to fixed cells and are not moved.
+
+use bytemuck::{Pod, Zeroable};
+use serde::Serialize;
+use wgpu::util::DeviceExt;
+
+use crate::gpu::{bg_entry, bgl_storage_entry, bgl_uniform_entry, compute_pipeline};
+use crate::grid::{Axis, Grid};
+use crate::materials::Coefficients;
+
+/// Window motion along +x.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct MovingWindow {
+    /// Step at which the window starts moving.
+    pub start: u32,
+    /// Window speed (m/s), normally the pulse group velocity.
+    pub velocity: f64,
+}
+
+impl MovingWindow {
+    /// Cells the window has travelled by the end of step `n`.
+    pub fn shift_at(&self, grid: &Grid, n: u32) -> u32 {
+        if n < self.start {
+            return 0;
+        }
+        ((n - self.start) as f64 * self.velocity * grid.dt / grid.dx).floor() as u32
+    }
+}
+
+/// Shift parameters (must match WGSL `WindowParams`).
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct WindowParams {
+    nx: u32,
+    ny: u32,
+    nz: u32,
+    width: u32,
+}
+
+/// GPU shift of the six field and four coefficient buffers.
+pub struct MovingWindowPass {
+    window: MovingWindow,
+    pipeline: wgpu::ComputePipeline,
+    /// (bind group, target buffer, floats per cell) per shifted buffer.
+    targets: Vec<(wgpu::BindGroup, wgpu::Buffer, u32)>,
+    scratch: wgpu::Buffer,
+    /// Leading-face coefficients (CA, CB, CP, CQ), refilled before a shift.
+    planes: [wgpu::Buffer; 4],
+    cells: u32,
+    /// Cells travelled so far.
+    pub offset: u32,
+}
+
+impl MovingWindowPass {
+    pub fn new(
+        device: &wgpu::Device,
+        grid: &Grid,
+        window: MovingWindow,
+        fields: [&wgpu::Buffer; 6],
+        coeffs: [&wgpu::Buffer; 4],
+    ) -> Self {
+        let cells = grid.total() as u32;
+        let face = (grid.ny * grid.nz) as usize;
+
+        let uniform = |width: u32| {
+            let params = WindowParams {
+                nx: grid.nx,
+                ny: grid.ny,
+                nz: grid.nz,
+                width,
+            };
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("window_params"),
+                contents: bytemuck::bytes_of(&params),
+                usage: wgpu::BufferUsages::UNIFORM,
+            })
+        };
+        let (params_field, params_coeff) = (uniform(1), uniform(4));
+        let plane = |label: &str| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents: bytemuck::cast_slice(&vec![[0.0_f32; 4]; face]),
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            })
+        };
+        let zero_plane = plane("window_zero_face");
+        let planes = ["window_ca", "window_cb", "window_cp", "window_cq"].map(plane);
+        let scratch = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("window_scratch"),
+            size: grid.total() as u64 * 16,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("window_bgl"),
+            entries: &[
+                bgl_uniform_entry(0),
+                bgl_storage_entry(1, true),
+                bgl_storage_entry(2, false),
+                bgl_storage_entry(3, true),
+            ],
+        });
+        let pipeline = compute_pipeline(
+            device,
+            "moving_window",
+            include_str!("shaders/moving_window.wgsl"),
+            &bgl,
+        );
+
+        let group = |params: &wgpu::Buffer, src: &wgpu::Buffer, face: &wgpu::Buffer| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("bg_window"),
+                layout: &bgl,
+                entries: &[
+                    bg_entry(0, params.as_entire_binding()),
+                    bg_entry(1, src.as_entire_binding()),
+                    bg_entry(2, scratch.as_entire_binding()),
+                    bg_entry(3, face.as_entire_binding()),
+                ],
+            })
+        };
+        let mut targets: Vec<_> = fields
+            .iter()
+            .map(|&f| (group(&params_field, f, &zero_plane), f.clone(), 1))
+            .collect();
+        targets.extend(
+            coeffs
+                .iter()
+                .zip(&planes)
+                .map(|(&c, p)| (group(&params_coeff, c, p), c.clone(), 4)),
+        );
+
+        MovingWindowPass {
+            window,
+            pipeline,
+            targets,
+            scratch,
+            planes,
+            cells,
+            offset: 0,
+        }
+    }
+
+    /// Number of one-cell shifts due at step `n`.
+    pub fn pending(&self, grid: &Grid, n: u32) -> u32 {
+        self.window.shift_at(grid, n).saturating_sub(self.offset)
+    }
+
+    /// Shift everything by one cell in its own submission; `face` holds the
+    /// coefficients of the new leading face, painted on [`Self::face_grid`].
+    pub fn shift(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, face: &Coefficients) {
+        for (buf, data) in self
+            .planes
+            .iter()
+            .zip([&face.ca, &face.cb, &face.cp, &face.cq])
+        {
+            queue.write_buffer(buf, 0, bytemuck::cast_slice(data));
+        }
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("moving_window"),
+        });
+        for (bind_group, target, width) in &self.targets {
+            let groups = (self.cells * width).div_ceil(64);
+            {
+                let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some("moving window"),
+                    timestamp_writes: None,
+                });
+                pass.set_pipeline(&self.pipeline);
+                pass.set_bind_group(0, bind_group, &[]);
+                pass.dispatch_workgroups(groups.min(65535), groups.div_ceil(65535), 1);
+            }
+            let size = (self.cells * width) as u64 * 4;
+            encoder.copy_buffer_to_buffer(&self.scratch, 0, target, 0, size);
+        }
+        queue.submit(Some(encoder.finish()));
+        self.offset += 1;
+    }
+
+    /// The one-cell grid of the face entering at the next shift.
+    pub fn face_grid(&self, grid: &Grid) -> Grid {
+        let mut face = *grid;
+        face.nx = 1;
+        face.origin[0] = grid.node(Axis::X, grid.nx - 1) + (self.offset + 1) as f64 * grid.dx;
+        face
+    }
+}
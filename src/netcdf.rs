@@ -0,0 +1,426 @@
+//! NetCDF output following the CF conventions.
+//!
+//! Writes the classic 64-bit-offset format (CDF-2), which every NetCDF
+//! reader understands, with the unlimited record dimension `t` so steps are
+//! appended as the run goes.  The file holds the coordinate variables `x`,
+//! `y`, `z` (lower cell faces, m) and `t` (E times, s) and one
+//! `float(t, z, y, x)` variable per selected component, with CF `units`,
+//! `axis`, `standard_name` and `long_name` attributes.  The coordinates name
+//! the cell, not the staggered Yee position of each component, which the
+//! variables' `comment` attribute spells out.
+
+use std::fs;
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::grid::{Axis, Field, Grid};
+
+const NC_DIMENSION: u32 = 0x0A;
+const NC_VARIABLE: u32 = 0x0B;
+const NC_ATTRIBUTE: u32 = 0x0C;
+const NC_CHAR: u32 = 2;
+const NC_FLOAT: u32 = 5;
+const NC_DOUBLE: u32 = 6;
+
+/// Big-endian header writer.
+#[derive(Default)]
+struct Header(Vec<u8>);
+
+impl Header {
+    fn u32(&mut self, v: u32) {
+        self.0.extend(v.to_be_bytes());
+    }
+
+    fn u64(&mut self, v: u64) {
+        self.0.extend(v.to_be_bytes());
+    }
+
+    /// Byte string padded to four bytes with zeros.
+    fn bytes(&mut self, b: &[u8]) {
+        self.0.extend(b);
+        self.0.resize(self.0.len().next_multiple_of(4), 0);
+    }
+
+    fn name(&mut self, name: &str) {
+        self.u32(name.len() as u32);
+        self.bytes(name.as_bytes());
+    }
+
+    /// Text attributes.
+    fn attributes(&mut self, attributes: &[(&str, String)]) {
+        if attributes.is_empty() {
+            self.u64(0);
+            return;
+        }
+        self.u32(NC_ATTRIBUTE);
+        self.u32(attributes.len() as u32);
+        for (name, value) in attributes {
+            self.name(name);
+            self.u32(NC_CHAR);
+            self.u32(value.len() as u32);
+            self.bytes(value.as_bytes());
+        }
+    }
+}
+
+/// One variable of the file.
+struct Variable {
+    name: String,
+    dims: Vec<u32>,
+    attributes: Vec<(&'static str, String)>,
+    nc_type: u32,
+    /// Bytes per record (record variables) or in total.
+    size: u64,
+}
+
+fn coordinate(name: &str, dim: u32, axis: &str, len: u64) -> Variable {
+    let (units, standard_name, long_name) = match axis {
+        "T" => ("s", "time", "time of E".to_string()),
+        _ => ("m", "", format!("{name} of the lower cell face")),
+    };
+    let mut attributes = vec![
+        ("units", units.to_string()),
+        ("axis", axis.to_string()),
+        ("long_name", long_name),
+    ];
+    if !standard_name.is_empty() {
+        attributes.push(("standard_name", standard_name.to_string()));
+    }
+    Variable {
+        name: name.to_string(),
+        dims: vec![dim],
+        attributes,
+        nc_type: NC_DOUBLE,
+        size: 8 * len,
+    }
+}
+
+/// Where within cell (i, j, k) the Yee grid samples `field`.
+fn yee_position(field: Field) -> String {
+    match field {
+        Field::E(axis) => {
+            let name = ["x", "y", "z"][axis.lane()];
+            format!("sampled at the centre of the cell's lower-corner edge along {name}, at time t")
+        }
+        Field::H(axis) => {
+            let name = ["x", "y", "z"][axis.lane()];
+            format!("sampled at the centre of the cell's lower face normal to {name}, at t - dt/2")
+        }
+    }
+}
+
+/// Appendable CF NetCDF file of selected field components.
+pub struct NetCdfFile {
+    file: fs::File,
+    records: u32,
+}
+
+impl NetCdfFile {
+    /// Create `path` with the grid's coordinates and no records yet.
+    pub fn create(path: &Path, grid: &Grid, fields: &[Field]) -> io::Result<Self> {
+        let dims = [("t", 0), ("z", grid.nz), ("y", grid.ny), ("x", grid.nx)];
+        let volume = grid.total() as u64 * 4;
+        let mut variables = vec![
+            coordinate("t", 0, "T", 1),
+            coordinate("z", 1, "Z", grid.nz as u64),
+            coordinate("y", 2, "Y", grid.ny as u64),
+            coordinate("x", 3, "X", grid.nx as u64),
+        ];
+        for &field in fields {
+            let (units, kind) = match field {
+                Field::E(_) => ("V m-1", "electric field"),
+                Field::H(_) => ("A m-1", "magnetic field"),
+            };
+            variables.push(Variable {
+                name: field.name().to_string(),
+                dims: vec![0, 1, 2, 3],
+                attributes: vec![
+                    ("units", units.to_string()),
+                    (
+                        "long_name",
+                        format!("{} component of the {kind}", field.name()),
+                    ),
+                    ("comment", yee_position(field)),
+                ],
+                nc_type: NC_FLOAT,
+                size: volume,
+            });
+        }
+
+        // The header goes first, then the fixed-size x, y, z data, then the
+        // records (t and every component per step).
+        let header = |begins: &[u64]| {
+            let mut h = Header::default();
+            h.bytes(b"CDF\x02");
+            h.u32(0);
+            h.u32(NC_DIMENSION);
+            h.u32(dims.len() as u32);
+            for (name, len) in dims {
+                h.name(name);
+                h.u32(len);
+            }
+            h.attributes(&[
+                ("Conventions", "CF-1.8".to_string()),
+                ("title", "FDTD field snapshots".to_string()),
+                ("source", "fdtd_3d".to_string()),
+                ("time_step", format!("{:e} s", grid.dt)),
+            ]);
+            h.u32(NC_VARIABLE);
+            h.u32(variables.len() as u32);
+            for (v, begin) in variables.iter().zip(begins) {
+                h.name(&v.name);
+                h.u32(v.dims.len() as u32);
+                for &d in &v.dims {
+                    h.u32(d);
+                }
+                h.attributes(&v.attributes);
+                h.u32(v.nc_type);
+                h.u32(v.size.min(u32::MAX as u64) as u32);
+                h.u64(*begin);
+            }
+            h.0
+        };
+        let mut begins = vec![0; variables.len()];
+        let mut offset = header(&begins).len() as u64;
+        for i in [1, 2, 3, 0].into_iter().chain(4..variables.len()) {
+            begins[i] = offset;
+            offset += variables[i].size;
+        }
+
+        let mut file = fs::File::create(path)?;
+        file.write_all(&header(&begins))?;
+        for axis in [Axis::Z, Axis::Y, Axis::X] {
+            let nodes: Vec<u8> = (0..grid.cells(axis))
+                .flat_map(|i| grid.node(axis, i).to_be_bytes())
+                .collect();
+            file.write_all(&nodes)?;
+        }
+        Ok(NetCdfFile { file, records: 0 })
+    }
+
+    /// Append one record at time `t`; `values` holds one volume per
+    /// selected component, x fastest.
+    pub fn append(&mut self, t: f64, values: &[&[f32]]) -> io::Result<()> {
+        self.file.seek(SeekFrom::End(0))?;
+        let mut record = t.to_be_bytes().to_vec();
+        for volume in values {
+            record.extend(volume.iter().flat_map(|v| v.to_be_bytes()));
+        }
+        self.file.write_all(&record)?;
+        self.records += 1;
+        self.file.seek(SeekFrom::Start(4))?;
+        self.file.write_all(&self.records.to_be_bytes())?;
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Big-endian reader of the header, written from the CDF-2 spec.
+    struct Reader<'a> {
+        bytes: &'a [u8],
+        at: usize,
+    }
+
+    impl Reader<'_> {
+        fn take(&mut self, n: usize) -> &[u8] {
+            let b = &self.bytes[self.at..self.at + n];
+            self.at += n.next_multiple_of(4);
+            b
+        }
+
+        fn u32(&mut self) -> u32 {
+            u32::from_be_bytes(self.take(4).try_into().unwrap())
+        }
+
+        fn u64(&mut self) -> u64 {
+            u64::from_be_bytes(self.take(8).try_into().unwrap())
+        }
+
+        fn name(&mut self) -> String {
+            let n = self.u32() as usize;
+            String::from_utf8(self.take(n).to_vec()).unwrap()
+        }
+
+        /// A tagged list, or ABSENT (two zero words).
+        fn list(&mut self, tag: u32) -> u32 {
+            match self.u32() {
+                0 => {
+                    assert_eq!(self.u32(), 0);
+                    0
+                }
+                t => {
+                    assert_eq!(t, tag);
+                    self.u32()
+                }
+            }
+        }
+
+        fn attributes(&mut self) -> Vec<(String, String)> {
+            (0..self.list(NC_ATTRIBUTE))
+                .map(|_| {
+                    let name = self.name();
+                    assert_eq!(self.u32(), NC_CHAR);
+                    (name, self.name())
+                })
+                .collect()
+        }
+    }
+
+    struct Var {
+        name: String,
+        dims: Vec<u32>,
+        attributes: Vec<(String, String)>,
+        nc_type: u32,
+        size: u32,
+        begin: u64,
+    }
+
+    struct File {
+        records: u32,
+        dims: Vec<(String, u32)>,
+        attributes: Vec<(String, String)>,
+        vars: Vec<Var>,
+        bytes: Vec<u8>,
+    }
+
+    fn read(path: &Path) -> File {
+        let bytes = fs::read(path).unwrap();
+        let mut r = Reader {
+            bytes: &bytes,
+            at: 0,
+        };
+        assert_eq!(r.take(4), b"CDF\x02");
+        let records = r.u32();
+        let dims = (0..r.list(NC_DIMENSION))
+            .map(|_| (r.name(), r.u32()))
+            .collect();
+        let attributes = r.attributes();
+        let vars = (0..r.list(NC_VARIABLE))
+            .map(|_| {
+                let name = r.name();
+                let dims = (0..r.u32()).map(|_| r.u32()).collect();
+                Var {
+                    name,
+                    dims,
+                    attributes: r.attributes(),
+                    nc_type: r.u32(),
+                    size: r.u32(),
+                    begin: r.u64(),
+                }
+            })
+            .collect();
+        File {
+            records,
+            dims,
+            attributes,
+            vars,
+            bytes,
+        }
+    }
+
+    impl File {
+        fn var(&self, name: &str) -> &Var {
+            self.vars.iter().find(|v| v.name == name).unwrap()
+        }
+
+        /// Bytes per record: the record variables' sizes added up.
+        fn record_size(&self) -> u64 {
+            let vars = self.vars.iter().filter(|v| v.dims.first() == Some(&0));
+            vars.map(|v| v.size as u64).sum()
+        }
+
+        /// `count` values of `var` from record `record` (or the fixed data).
+        fn values<const N: usize>(&self, var: &str, record: u64, count: usize) -> Vec<[u8; N]> {
+            let at = (self.var(var).begin + record * self.record_size()) as usize;
+            self.bytes[at..at + N * count]
+                .chunks_exact(N)
+                .map(|b| b.try_into().unwrap())
+                .collect()
+        }
+
+        fn doubles(&self, var: &str, record: u64, count: usize) -> Vec<f64> {
+            let values = self.values::<8>(var, record, count);
+            values.into_iter().map(f64::from_be_bytes).collect()
+        }
+
+        fn floats(&self, var: &str, record: u64, count: usize) -> Vec<f32> {
+            let values = self.values::<4>(var, record, count);
+            values.into_iter().map(f32::from_be_bytes).collect()
+        }
+    }
+
+    fn attribute<'a>(attributes: &'a [(String, String)], name: &str) -> &'a str {
+        &attributes.iter().find(|(n, _)| n == name).unwrap().1
+    }
+
+    #[test]
+    fn the_header_declares_cf_dimensions_and_variables() {
+        let grid = Grid::uniform([4, 3, 2], 1e-3, 2e-12);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fields.nc");
+        NetCdfFile::create(&path, &grid, &[Field::E(Axis::Y), Field::H(Axis::Z)]).unwrap();
+        let file = read(&path);
+        assert_eq!(file.records, 0);
+        let dims: Vec<_> = file.dims.iter().map(|(n, l)| (n.as_str(), *l)).collect();
+        assert_eq!(dims, [("t", 0), ("z", 2), ("y", 3), ("x", 4)]);
+        assert_eq!(attribute(&file.attributes, "Conventions"), "CF-1.8");
+
+        let names: Vec<_> = file.vars.iter().map(|v| v.name.as_str()).collect();
+        assert_eq!(names, ["t", "z", "y", "x", "Ey", "Hz"]);
+        let t = file.var("t");
+        assert_eq!(
+            (t.dims.as_slice(), t.nc_type, t.size),
+            (&[0][..], NC_DOUBLE, 8)
+        );
+        assert_eq!(attribute(&t.attributes, "standard_name"), "time");
+        let x = file.var("x");
+        assert_eq!((x.dims.as_slice(), x.size), (&[3][..], 32));
+        assert_eq!(attribute(&x.attributes, "axis"), "X");
+        let hz = file.var("Hz");
+        assert_eq!(hz.dims, [0, 1, 2, 3]);
+        assert_eq!((hz.nc_type, hz.size), (NC_FLOAT, 4 * 24));
+        assert_eq!(attribute(&hz.attributes, "units"), "A m-1");
+
+        // the fixed data follows the header, the records follow that
+        assert_eq!(file.var("z").begin, file.bytes.len() as u64 - 8 * 9);
+        assert_eq!(file.var("t").begin, file.bytes.len() as u64);
+        assert_eq!(file.doubles("x", 0, 4), [0.0, 1e-3, 2e-3, 3e-3]);
+        assert_eq!(file.doubles("z", 0, 2), [0.0, 1e-3]);
+    }
+
+    #[test]
+    fn records_hold_the_appended_steps() {
+        let grid = Grid::uniform([3, 2, 2], 1e-3, 2e-12);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fields.nc");
+        let fields = [Field::E(Axis::X), Field::H(Axis::Y)];
+        let mut file = NetCdfFile::create(&path, &grid, &fields).unwrap();
+        let volume = |step: usize, f: usize| -> Vec<f32> {
+            (0..grid.total())
+                .map(|i| (100 * step + 10 * f) as f32 + i as f32 / 16.0)
+                .collect()
+        };
+        for step in 0..3 {
+            let (ex, hy) = (volume(step, 0), volume(step, 1));
+            file.append(step as f64 * 2e-12, &[&ex, &hy]).unwrap();
+        }
+        drop(file);
+
+        let file = read(&path);
+        assert_eq!(file.records, 3);
+        assert_eq!(file.record_size(), 8 + 2 * 4 * 12);
+        assert_eq!(
+            file.bytes.len() as u64,
+            file.var("t").begin + 3 * file.record_size()
+        );
+        for step in 0..3 {
+            let record = step as u64;
+            assert_eq!(file.doubles("t", record, 1), [step as f64 * 2e-12]);
+            assert_eq!(file.floats("Ex", record, 12), volume(step, 0));
+            assert_eq!(file.floats("Hy", record, 12), volume(step, 1));
+        }
+    }
+}
@@ -0,0 +1,98 @@
+//! Interactive use from a notebook (evcxr / Jupyter).
+//!
+//! One [`Simulation`](crate::Simulation) keeps its device and buffers for
+//! the whole session, so a cell can step it a little further, look at the
+//! fields, change the source and step on:
+//!
+//! ```text
+//! :dep fdtd_3d = { path = "." }
+//! use fdtd_3d::{grid::{Axis, Field, Grid}, sources::Waveform, ComputeBackend, Simulation};
+//! let mut sim = Simulation::builder()
+//!     .grid(Grid::uniform([96; 3], 1e-3, 1.5e-12))
+//!     .steps(400)
+//!     .build()?;
+//! sim.advance(100)?;
+//! sim.slice(Field::Ez, Axis::Z, 48)?           // shown as an image
+//! sim.set_waveform(Waveform::Sine { freq: 5e9, ramp: 20.0 });
+//! sim.advance(200)?.slice(Field::Ez, Axis::Z, 48)?.array()
+//! ```
+//!
+//! A [`Slice`] is the values of one plane; evcxr displays it with the
+//! colour map of [`crate::slices`], and [`Slice::array`] hands the numbers
+//! on to plotting or analysis.
+
+use ndarray::{Array2, ShapeBuilder};
+
+use crate::grid::{Axis, Field};
+use crate::png;
+use crate::slices;
+
+/// One plane of a field component, read back by
+/// [`Simulation::slice`](crate::ComputeBackend::slice).
+#[derive(Clone, Debug)]
+pub struct Slice {
+    pub field: Field,
+    /// Plane normal and cell index along it.
+    pub normal: Axis,
+    pub index: u32,
+    /// Steps taken when the plane was read.
+    pub steps: u32,
+    /// Cells along the two tangential axes, in [`Axis::tangential`] order.
+    pub width: u32,
+    pub height: u32,
+    /// Values, first tangential axis fastest.
+    pub values: Vec<f32>,
+}
+
+impl Slice {
+    /// The values indexed `[a, b]` along the two tangential axes.
+    pub fn array(&self) -> Array2<f32> {
+        let shape = (self.width as usize, self.height as usize);
+        Array2::from_shape_vec(shape.f(), self.values.clone()).expect("a full plane")
+    }
+
+    /// Largest magnitude.
+    pub fn max_abs(&self) -> f32 {
+        self.values.iter().fold(0.0, |m, x| m.max(x.abs()))
+    }
+
+    /// PNG image on the blue–white–red scale, `scale` mapping to full colour
+    /// (the largest magnitude when `None`), second axis upwards.
+    pub fn png(&self, scale: Option<f32>) -> Vec<u8> {
+        let rgb = slices::colour_map(&self.values, self.width, scale);
+        png::encode_rgb(self.width, self.height, &rgb)
+    }
+
+    /// Shown by evcxr as the cell's output: the image and its scale.
+    pub fn evcxr_display(&self) {
+        let axis = ["x", "y", "z"][self.normal.lane()];
+        println!(
+            "EVCXR_BEGIN_CONTENT text/html\n<figure><img src=\"data:image/png;base64,{}\" \
+             style=\"image-rendering: pixelated; min-width: 256px\"/><figcaption>{} at \
+             {axis} = {} after {} steps, ±{:.3e}</figcaption></figure>\nEVCXR_END_CONTENT",
+            base64(&self.png(None)),
+            self.field.name(),
+            self.index,
+            self.steps,
+            self.max_abs()
+        );
+    }
+}
+
+/// Standard base64 with padding.
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [0, 1, 2].map(|i| chunk.get(i).copied().unwrap_or(0) as u32);
+        let word = b[0] << 16 | b[1] << 8 | b[2];
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(word >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,137 @@
+//! Near-to-far-field (NTFF) transformation.
+//!
+//! The time-aligned DFT of the tangential fields on the six faces of a
+//! [`FluxBox`] gives the equivalent surface currents J = n̂ × H and
+//! M = −n̂ × E, and their radiation vectors
+//!
+//!   N = ∮ J e^{jk r̂·r'} dS',   L = ∮ M e^{jk r̂·r'} dS'
+//!
+//! (e^{jωt} phasors, r' from the box centre) the far field
+//!
+//!   r·e^{jkr}·E_θ = −jk/4π · (L_φ + η₀N_θ)
+//!   r·e^{jkr}·E_φ =  jk/4π · (L_θ − η₀N_φ)
+//!
+//! (Balanis, *Advanced Engineering Electromagnetics*, §6.8).  The box must
+//! lie in vacuum and enclose every source and scatterer; a field that
+//! passes through it without a source inside (an incident plane wave)
+//! radiates nothing outside, so the transform of the total field is the
+//! scattered or radiated field alone.  Samples on the box edges are shared
+//! by two faces and counted on both, a small error for boxes many cells
+//! wide.
+
+use std::f64::consts::PI;
+
+use rustfft::num_complex::Complex64;
+
+use crate::dft::Spectrum;
+use crate::flux::FluxBox;
+use crate::grid::{Axis, Grid};
+use crate::materials::{EPS0, MU0};
+
+/// Free-space wave impedance η₀ (Ω).
+pub fn eta0() -> f64 {
+    (MU0 / EPS0).sqrt()
+}
+
+/// Unit vector of direction (θ, φ) in radians, θ from +z and φ from +x.
+pub fn direction(theta: f64, phi: f64) -> [f64; 3] {
+    [
+        theta.sin() * phi.cos(),
+        theta.sin() * phi.sin(),
+        theta.cos(),
+    ]
+}
+
+/// Area-weighted surface currents of one sample.
+struct Element {
+    at: [f64; 3],
+    j: [Complex64; 3],
+    m: [Complex64; 3],
+}
+
+/// Equivalent currents of a closed surface at one frequency.
+pub struct Currents {
+    /// Free-space wavenumber (rad/m).
+    k: f64,
+    elements: Vec<Element>,
+}
+
+impl Currents {
+    /// Currents at frequency index `f` from the spectra of the six
+    /// [`FluxBox::faces`] of `surface`, in that order.
+    pub fn new(grid: &Grid, surface: &FluxBox, faces: &[Spectrum], f: usize) -> Self {
+        let centre = [Axis::X, Axis::Y, Axis::Z].map(|axis| {
+            let a = axis.lane();
+            0.5 * (grid.node(axis, surface.lo[a]) + grid.node(axis, surface.hi[a]))
+        });
+        let mut elements = Vec::new();
+        for ((face, spectrum), sign) in surface.faces().iter().zip(faces).zip(FluxBox::SIGNS) {
+            let (u, v) = face.normal.tangential();
+            for [pu, pv] in face.samples(grid, spectrum, f) {
+                // J_u = −s·H_v, M_v = −s·E_u at the E_u edge;
+                // J_v = s·H_u, M_u = s·E_v at the E_v edge
+                let zero = Complex64::new(0.0, 0.0);
+                let (mut j, mut m) = ([zero; 3], [zero; 3]);
+                j[u.lane()] = -sign * pu.h * pu.area;
+                m[v.lane()] = -sign * pu.e * pu.area;
+                elements.push(Element {
+                    at: [0, 1, 2].map(|a| pu.at[a] - centre[a]),
+                    j,
+                    m,
+                });
+                let (mut j, mut m) = ([zero; 3], [zero; 3]);
+                j[v.lane()] = sign * pv.h * pv.area;
+                m[u.lane()] = sign * pv.e * pv.area;
+                elements.push(Element {
+                    at: [0, 1, 2].map(|a| pv.at[a] - centre[a]),
+                    j,
+                    m,
+                });
+            }
+        }
+        Currents {
+            k: 2.0 * PI * faces[0].frequencies[f] * (MU0 * EPS0).sqrt(),
+            elements,
+        }
+    }
+
+    /// Radiation vectors (N, L) in direction `r` (unit vector).
+    fn radiation(&self, r: [f64; 3]) -> ([Complex64; 3], [Complex64; 3]) {
+        let zero = Complex64::new(0.0, 0.0);
+        let (mut n, mut l) = ([zero; 3], [zero; 3]);
+        for e in &self.elements {
+            let phase = Complex64::from_polar(
+                1.0,
+                self.k * (r[0] * e.at[0] + r[1] * e.at[1] + r[2] * e.at[2]),
+            );
+            for a in 0..3 {
+                n[a] += e.j[a] * phase;
+                l[a] += e.m[a] * phase;
+            }
+        }
+        (n, l)
+    }
+
+    /// Far field r·e^{jkr}·(E_θ, E_φ) in V in direction (θ, φ) (radians).
+    pub fn far_field(&self, theta: f64, phi: f64) -> [Complex64; 2] {
+        let (n, l) = self.radiation(direction(theta, phi));
+        let (ct, st, cp, sp) = (theta.cos(), theta.sin(), phi.cos(), phi.sin());
+        let along_theta = |w: [Complex64; 3]| w[0] * ct * cp + w[1] * ct * sp - w[2] * st;
+        let along_phi = |w: [Complex64; 3]| -w[0] * sp + w[1] * cp;
+        let jk = Complex64::new(0.0, self.k / (4.0 * PI));
+        let eta = eta0();
+        [
+            -jk * (along_phi(l) + eta * along_theta(n)),
+            jk * (along_theta(l) - eta * along_phi(n)),
+        ]
+    }
+
+    /// Radiation intensity U = r²|E|²/2η₀ (W/sr) in direction `r` (unit
+    /// vector).
+    pub fn intensity(&self, r: [f64; 3]) -> f64 {
+        let theta = r[2].clamp(-1.0, 1.0).acos();
+        let phi = r[1].atan2(r[0]);
+        let [e_theta, e_phi] = self.far_field(theta, phi);
+        (e_theta.norm_sqr() + e_phi.norm_sqr()) / (2.0 * eta0())
+    }
+}
@@ -0,0 +1,224 @@
+//! Principal-plane far-field pattern cuts of an antenna.
+//!
+//! The [`crate::ntff`] transform of a box around the antenna gives the
+//! radiation intensity U in any direction, and the box's net outgoing flux
+//! the radiated power P_rad, so the gain referred to the radiated power is
+//!
+//!   G = 4π·U / P_rad
+//!
+//! (the directivity; equal to the gain of a lossless, matched antenna).
+//! The E-plane cut contains the boresight and the polarisation axis, the
+//! H-plane cut the boresight and the third axis.  Angles run from −180° to
+//! 180° from the boresight, positive towards +polarisation (E plane) or
+//! +(boresight × polarisation) (H plane).
+//!
+//! Per pattern, `<name>_cuts.csv` holds the gain in dBi of both cuts at
+//! every frequency in 1° steps, and `<name>_cuts_<f>.png` (f the frequency
+//! index) a polar plot over the 40 dB below the peak, boresight up, E plane
+//! in red and H plane in blue.
+
+use std::f64::consts::PI;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::config::ConfigError;
+use crate::dft::{DftMonitor, Spectrum};
+use crate::flux::FluxBox;
+use crate::grid::{Axis, Grid};
+use crate::ntff::Currents;
+use crate::png;
+
+/// Dynamic range of the polar plots (dB).
+const RANGE_DB: f64 = 40.0;
+
+/// Side of the polar plots (pixels).
+const SIZE: u32 = 401;
+
+/// Pattern cuts from the box of cells `lo..hi` around an antenna.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct PatternCuts {
+    pub name: &'static str,
+    /// Lower cell on each axis; must be at least 1.
+    pub lo: [u32; 3],
+    pub hi: [u32; 3],
+    /// Frequencies in Hz.
+    pub frequencies: &'static [f64],
+    /// Main-beam direction (+axis).
+    pub boresight: Axis,
+    /// Axis of the main E polarisation; must differ from the boresight.
+    pub polarization: Axis,
+}
+
+impl PatternCuts {
+    /// Fails unless the box lies inside `grid`, off its lower faces, and
+    /// the polarisation is across the boresight.
+    pub fn check(&self, grid: &Grid) -> Result<(), ConfigError> {
+        if self.boresight == self.polarization {
+            let message = format!(
+                "pattern {}: the polarisation must be across the boresight",
+                self.name
+            );
+            return Err(ConfigError(message));
+        }
+        self.surface().check(grid)
+    }
+
+    /// The box as a closed flux surface.
+    pub fn surface(&self) -> FluxBox {
+        FluxBox {
+            name: self.name,
+            lo: self.lo,
+            hi: self.hi,
+            frequencies: self.frequencies,
+        }
+    }
+
+    /// DFT monitors of the six faces, in [`FluxBox::faces`] order.
+    pub fn dft_monitors(&self) -> Vec<DftMonitor> {
+        self.surface()
+            .faces()
+            .iter()
+            .filter_map(|face| face.dft_monitor())
+            .collect()
+    }
+
+    /// Unit vectors of the boresight and the E- and H-plane directions.
+    fn frame(&self) -> [[f64; 3]; 3] {
+        let unit = |axis: Axis| {
+            let mut e = [0.0; 3];
+            e[axis.lane()] = 1.0;
+            e
+        };
+        let (b, p) = (unit(self.boresight), unit(self.polarization));
+        let h = [
+            b[1] * p[2] - b[2] * p[1],
+            b[2] * p[0] - b[0] * p[2],
+            b[0] * p[1] - b[1] * p[0],
+        ];
+        [b, p, h]
+    }
+}
+
+/// Cut angles in degrees.
+fn angles() -> impl Iterator<Item = i32> {
+    -180..=180
+}
+
+/// Gain (dBi) along the cut towards `across` from the boresight `b`.
+fn cut(currents: &Currents, p_rad: f64, b: [f64; 3], across: [f64; 3]) -> Vec<f64> {
+    angles()
+        .map(|a| {
+            let (s, c) = (a as f64).to_radians().sin_cos();
+            let r = [0, 1, 2].map(|i| c * b[i] + s * across[i]);
+            10.0 * (4.0 * PI * currents.intensity(r) / p_rad).log10()
+        })
+        .collect()
+}
+
+/// Largest gain of the two cuts.
+fn peak(e_plane: &[f64], h_plane: &[f64]) -> f64 {
+    e_plane
+        .iter()
+        .chain(h_plane)
+        .fold(f64::NEG_INFINITY, |m, &g| m.max(g))
+}
+
+/// Polar plot of the two cuts (dBi per degree of [`angles`]).
+fn plot(path: &Path, e_plane: &[f64], h_plane: &[f64]) -> io::Result<()> {
+    let mut rgb = vec![255u8; (SIZE * SIZE * 3) as usize];
+    let centre = (SIZE / 2) as f64;
+    let radius = centre - 10.0;
+    let mut dot = |x: f64, y: f64, colour: [u8; 3]| {
+        let (x, y) = (x.round(), y.round());
+        if x >= 0.0 && y >= 0.0 && x < SIZE as f64 && y < SIZE as f64 {
+            let i = 3 * (y as usize * SIZE as usize + x as usize);
+            rgb[i..i + 3].copy_from_slice(&colour);
+        }
+    };
+    // Point at angle `a` (degrees from up, clockwise) and radius `r` pixels
+    let at = |a: f64, r: f64| {
+        let (s, c) = a.to_radians().sin_cos();
+        (centre + r * s, centre - r * c)
+    };
+    let grey = [200, 200, 200];
+    for ring in 1..=(RANGE_DB / 10.0) as u32 {
+        let r = radius * ring as f64 * 10.0 / RANGE_DB;
+        for step in 0..1440 {
+            let (x, y) = at(step as f64 / 4.0, r);
+            dot(x, y, grey);
+        }
+    }
+    for spoke in (0..360).step_by(30) {
+        for r in 0..radius as u32 {
+            let (x, y) = at(spoke as f64, r as f64);
+            dot(x, y, grey);
+        }
+    }
+
+    let peak = peak(e_plane, h_plane);
+    let pixels = |g: f64| {
+        let r = radius * (1.0 - (peak - g) / RANGE_DB);
+        if r.is_finite() {
+            r.max(0.0)
+        } else {
+            0.0
+        }
+    };
+    for (gains, colour) in [(e_plane, [220, 30, 30]), (h_plane, [30, 30, 220])] {
+        for (w, pair) in gains.windows(2).enumerate() {
+            // Sub-steps between the 1° samples keep the curve connected
+            for sub in 0..16 {
+                let t = sub as f64 / 16.0;
+                let a = w as f64 - 180.0 + t;
+                let (x, y) = at(a, pixels(pair[0] + t * (pair[1] - pair[0])));
+                dot(x, y, colour);
+            }
+        }
+    }
+    png::write_rgb(path, SIZE, SIZE, &rgb)
+}
+
+/// Write the cuts of every pattern, from the face spectra of each in
+/// [`PatternCuts::dft_monitors`] order after the previous one's.  Returns
+/// the peak gain (dBi) over both cuts per frequency of each.
+pub fn write_cuts(
+    dir: &Path,
+    grid: &Grid,
+    patterns: &[PatternCuts],
+    spectra: &[Spectrum],
+) -> io::Result<Vec<Vec<f64>>> {
+    let mut peaks = Vec::new();
+    for (pattern, faces) in patterns.iter().zip(spectra.chunks(6)) {
+        let [b, p, h] = pattern.frame();
+        let surface = pattern.surface();
+        let face_power: Vec<Vec<f64>> = surface
+            .faces()
+            .iter()
+            .zip(faces)
+            .map(|(face, spectrum)| face.spectrum(grid, spectrum))
+            .collect();
+
+        let path = dir.join(format!("{}_cuts.csv", pattern.name));
+        let mut file = BufWriter::new(fs::File::create(path)?);
+        writeln!(file, "frequency,angle,e_plane_dbi,h_plane_dbi")?;
+        let mut maxima = Vec::new();
+        for (f, frequency) in pattern.frequencies.iter().enumerate() {
+            let p_rad = FluxBox::outgoing(&face_power.iter().map(|w| w[f]).collect::<Vec<_>>());
+            let currents = Currents::new(grid, &surface, faces, f);
+            let e_plane = cut(&currents, p_rad, b, p);
+            let h_plane = cut(&currents, p_rad, b, h);
+            for ((a, e), h) in angles().zip(&e_plane).zip(&h_plane) {
+                writeln!(file, "{frequency:e},{a},{e:.4},{h:.4}")?;
+            }
+            let path = dir.join(format!("{}_cuts_{f}.png", pattern.name));
+            plot(&path, &e_plane, &h_plane)?;
+            maxima.push(peak(&e_plane, &h_plane));
+        }
+        file.flush()?;
+        peaks.push(maxima);
+    }
+    Ok(peaks)
+}
@@ -0,0 +1,554 @@
+//! Labeled voxel volumes (anatomical phantoms, CT/MRI segmentations).
+//!
+//! A volume stores one integer label per voxel; a [`Phantom`] maps labels to
+//! materials and resamples the volume onto the simulation grid by nearest
+//! neighbour at cell centres, matching the staircased object rasterization.
+//!
+//! Supported files:
+//! - headerless raw data with known dimensions, voxel type and spacing,
+//! - NRRD (`.nrrd` / `.nhdr`) with `raw` encoding, attached or detached data,
+//!   and the units of its spacing stated,
+//! - single-file NIfTI-1 (`.nii`).
+//!
+//! Compressed data (gzip NRRD, `.nii.gz`) is rejected; decompress it first.
+//! Voxel axes are taken as the grid axes; orientation matrices are ignored.
+//!
+//! Labels can also name tissues of the [`crate::tissues`] database, either
+//! at a single frequency or as fitted Debye media for broadband runs.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::ade::AdeEdge;
+use crate::grid::{Axis, Grid};
+use crate::materials::{Coefficients, Material};
+use crate::tissues::{DebyeModel, Tissue, TissueModel};
+
+/// Scalar type of the stored labels.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum VoxelType {
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+    /// Float labels are rounded to the nearest integer.
+    F32,
+}
+
+impl VoxelType {
+    pub fn size(self) -> usize {
+        match self {
+            VoxelType::U8 | VoxelType::I8 => 1,
+            VoxelType::U16 | VoxelType::I16 => 2,
+            VoxelType::U32 | VoxelType::I32 | VoxelType::F32 => 4,
+        }
+    }
+
+    /// Decode one voxel; negative labels map to `u32::MAX`.
+    fn decode(self, b: &[u8], big_endian: bool) -> u32 {
+        let mut w = [0u8; 4];
+        w[..b.len()].copy_from_slice(b);
+        if big_endian {
+            w[..b.len()].reverse();
+        }
+        let bits = u32::from_le_bytes(w);
+        let signed = match self {
+            VoxelType::U8 | VoxelType::U16 | VoxelType::U32 => return bits,
+            VoxelType::I8 => bits as u8 as i8 as i64,
+            VoxelType::I16 => bits as u16 as i16 as i64,
+            VoxelType::I32 => bits as i32 as i64,
+            VoxelType::F32 => f32::from_bits(bits).round() as i64,
+        };
+        u32::try_from(signed).unwrap_or(u32::MAX)
+    }
+}
+
+/// Where a phantom's voxels come from.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub enum VoxelSource {
+    /// NRRD or NIfTI file, chosen by extension.
+    File(&'static str),
+    /// Headerless raw file, x fastest.
+    Raw {
+        path: &'static str,
+        dims: [usize; 3],
+        /// Voxel size (m).
+        spacing: [f64; 3],
+        voxel: VoxelType,
+        big_endian: bool,
+    },
+}
+
+/// A labeled volume in memory, x fastest.
+pub struct VoxelVolume {
+    pub dims: [usize; 3],
+    /// Voxel size (m).
+    pub spacing: [f64; 3],
+    pub labels: Vec<u32>,
+}
+
+impl VoxelVolume {
+    pub fn load(source: &VoxelSource) -> io::Result<VoxelVolume> {
+        match *source {
+            VoxelSource::File(path) => {
+                let lower = path.to_ascii_lowercase();
+                if lower.ends_with(".nrrd") || lower.ends_with(".nhdr") {
+                    VoxelVolume::from_nrrd(Path::new(path))
+                } else if lower.ends_with(".nii") {
+                    VoxelVolume::from_nifti(Path::new(path))
+                } else {
+                    Err(invalid(format!("{path}: unknown voxel file format")))
+                }
+            }
+            VoxelSource::Raw {
+                path,
+                dims,
+                spacing,
+                voxel,
+                big_endian,
+            } => {
+                let bytes = fs::read(path)?;
+                VoxelVolume::from_bytes(&bytes, dims, spacing, voxel, big_endian)
+            }
+        }
+    }
+
+    fn from_bytes(
+        bytes: &[u8],
+        dims: [usize; 3],
+        spacing: [f64; 3],
+        voxel: VoxelType,
+        big_endian: bool,
+    ) -> io::Result<VoxelVolume> {
+        if dims.contains(&0) {
+            return Err(invalid(format!(
+                "voxel dimensions must be positive, not {dims:?}"
+            )));
+        }
+        let needed = dims
+            .iter()
+            .try_fold(voxel.size(), |n, &d| n.checked_mul(d))
+            .ok_or_else(|| invalid(format!("{dims:?} voxels do not fit in memory")))?;
+        if bytes.len() < needed {
+            return Err(invalid(format!(
+                "voxel data has {} bytes, {needed} expected",
+                bytes.len()
+            )));
+        }
+        let labels = bytes[..needed]
+            .chunks_exact(voxel.size())
+            .map(|b| voxel.decode(b, big_endian))
+            .collect();
+        Ok(VoxelVolume {
+            dims,
+            spacing,
+            labels,
+        })
+    }
+
+    /// NRRD with `raw` encoding.  Spacings are read from `spacings` or the
+    /// lengths of `space directions`, in the units of `units` or `space
+    /// units`, which the file must state.
+    pub fn from_nrrd(path: &Path) -> io::Result<VoxelVolume> {
+        let file = fs::read(path)?;
+        if !file.starts_with(b"NRRD") {
+            return Err(invalid(format!("{}: not an NRRD file", path.display())));
+        }
+
+        // The header ends at the first empty line.
+        let mut pos = 0;
+        let mut header = Vec::new();
+        while pos < file.len() {
+            let end = file[pos..]
+                .iter()
+                .position(|&b| b == b'\n')
+                .map_or(file.len(), |n| pos + n);
+            let line = String::from_utf8_lossy(&file[pos..end])
+                .trim_end_matches('\r')
+                .to_string();
+            pos = end + 1;
+            if line.is_empty() {
+                break;
+            }
+            if !line.starts_with('#') {
+                header.push(line);
+            }
+        }
+        let field = |key: &str| {
+            header.iter().skip(1).find_map(|l| {
+                let (k, v) = l.split_once(':')?;
+                (k.trim() == key).then(|| v.trim_start_matches('=').trim().to_string())
+            })
+        };
+        let require = |key: &str| {
+            field(key)
+                .ok_or_else(|| invalid(format!("{}: NRRD field '{key}' missing", path.display())))
+        };
+
+        let voxel = match require("type")?.as_str() {
+            "uchar" | "unsigned char" | "uint8" | "uint8_t" => VoxelType::U8,
+            "signed char" | "int8" | "int8_t" => VoxelType::I8,
+            "ushort" | "unsigned short" | "unsigned short int" | "uint16" | "uint16_t" => {
+                VoxelType::U16
+            }
+            "short" | "short int" | "signed short" | "signed short int" | "int16" | "int16_t" => {
+                VoxelType::I16
+            }
+            "uint" | "unsigned int" | "uint32" | "uint32_t" => VoxelType::U32,
+            "int" | "signed int" | "int32" | "int32_t" => VoxelType::I32,
+            "float" => VoxelType::F32,
+            other => return Err(invalid(format!("NRRD type '{other}' not supported"))),
+        };
+        if require("dimension")? != "3" {
+            return Err(invalid("only 3-D NRRD volumes are supported".into()));
+        }
+        let encoding = field("encoding").unwrap_or_else(|| "raw".into());
+        if encoding != "raw" {
+            return Err(invalid(format!(
+                "NRRD encoding '{encoding}' not supported (only raw)"
+            )));
+        }
+
+        let sizes: Option<Vec<usize>> = require("sizes")?
+            .split_whitespace()
+            .map(|n| n.parse().ok().filter(|&n| n > 0))
+            .collect();
+        let dims: [usize; 3] = sizes
+            .and_then(|sizes| sizes.try_into().ok())
+            .ok_or_else(|| invalid("NRRD 'sizes' must be three positive integers".into()))?;
+        let lengths = if let Some(s) = field("spacings") {
+            numbers(&s)
+        } else if let Some(s) = field("space directions") {
+            s.split(')')
+                .filter(|v| v.contains('('))
+                .map(|v| numbers(v).iter().map(|c| c * c).sum::<f64>().sqrt())
+                .collect()
+        } else {
+            return Err(invalid(format!(
+                "{}: NRRD gives no 'spacings' or 'space directions'",
+                path.display()
+            )));
+        };
+        // Unit names are quoted, one per axis: "mm" "mm" "mm".
+        let units: Option<Vec<f64>> = field("space units")
+            .or_else(|| field("units"))
+            .ok_or_else(|| {
+                invalid(format!(
+                    "{}: NRRD states no 'space units' for its spacing",
+                    path.display()
+                ))
+            })?
+            .split('"')
+            .skip(1)
+            .step_by(2)
+            .map(metres_per_unit)
+            .collect();
+        let units = units.ok_or_else(|| {
+            invalid(format!(
+                "{}: NRRD units must be m, cm, mm or um",
+                path.display()
+            ))
+        })?;
+        if lengths.len() != 3 || units.len() != 3 {
+            return Err(invalid("NRRD spacing must have three entries".into()));
+        }
+        let spacing = [0, 1, 2].map(|a| lengths[a] * units[a]);
+        let big_endian = field("endian").is_some_and(|e| e == "big");
+
+        match field("data file").or_else(|| field("datafile")) {
+            Some(name) => {
+                let data_path: PathBuf = path.parent().unwrap_or(Path::new(".")).join(name);
+                let bytes = fs::read(data_path)?;
+                VoxelVolume::from_bytes(&bytes, dims, spacing, voxel, big_endian)
+            }
+            None => VoxelVolume::from_bytes(
+                &file[pos.min(file.len())..],
+                dims,
+                spacing,
+                voxel,
+                big_endian,
+            ),
+        }
+    }
+
+    /// Single-file NIfTI-1; spacing units follow `xyzt_units`.
+    pub fn from_nifti(path: &Path) -> io::Result<VoxelVolume> {
+        let file = fs::read(path)?;
+        if file.len() < 352 {
+            return Err(invalid(format!(
+                "{}: truncated NIfTI header",
+                path.display()
+            )));
+        }
+        let big_endian = match i32::from_le_bytes(file[0..4].try_into().unwrap()) {
+            348 => false,
+            _ if i32::from_be_bytes(file[0..4].try_into().unwrap()) == 348 => true,
+            _ => return Err(invalid(format!("{}: not a NIfTI-1 file", path.display()))),
+        };
+        if &file[344..347] != b"n+1" {
+            return Err(invalid(format!(
+                "{}: only single-file NIfTI-1 (n+1) is supported",
+                path.display()
+            )));
+        }
+        let i16_at = |o: usize| {
+            let b = [file[o], file[o + 1]];
+            if big_endian {
+                i16::from_be_bytes(b)
+            } else {
+                i16::from_le_bytes(b)
+            }
+        };
+        let f32_at = |o: usize| {
+            let b = file[o..o + 4].try_into().unwrap();
+            if big_endian {
+                f32::from_be_bytes(b)
+            } else {
+                f32::from_le_bytes(b)
+            }
+        };
+
+        let rank = i16_at(40);
+        if !(3..=7).contains(&rank) || (4..=rank).any(|d| i16_at(40 + 2 * d as usize) > 1) {
+            return Err(invalid("only 3-D NIfTI volumes are supported".into()));
+        }
+        let dims = [42, 44, 46].map(|o| usize::try_from(i16_at(o)).unwrap_or(0));
+        let voxel = match i16_at(70) {
+            2 => VoxelType::U8,
+            4 => VoxelType::I16,
+            8 => VoxelType::I32,
+            16 => VoxelType::F32,
+            256 => VoxelType::I8,
+            512 => VoxelType::U16,
+            768 => VoxelType::U32,
+            code => return Err(invalid(format!("NIfTI datatype {code} not supported"))),
+        };
+        let unit = match file[123] & 0x07 {
+            1 => 1.0,
+            3 => 1e-6,
+            _ => 1e-3, // millimetres, also when unspecified
+        };
+        let spacing = [
+            f32_at(80).abs() as f64 * unit,
+            f32_at(84).abs() as f64 * unit,
+            f32_at(88).abs() as f64 * unit,
+        ];
+        let offset = (f32_at(108) as usize).max(352);
+        VoxelVolume::from_bytes(
+            file.get(offset..).unwrap_or(&[]),
+            dims,
+            spacing,
+            voxel,
+            big_endian,
+        )
+    }
+
+    /// Label of the voxel containing `p` (metres from the volume corner).
+    pub fn label_at(&self, p: [f64; 3]) -> Option<u32> {
+        let mut id = 0;
+        for d in (0..3).rev() {
+            let v = (p[d] / self.spacing[d]).floor();
+            if v < 0.0 || v >= self.dims[d] as f64 {
+                return None;
+            }
+            id = id * self.dims[d] + v as usize;
+        }
+        Some(self.labels[id])
+    }
+}
+
+/// A voxel volume placed in the grid with label → material maps.
+///
+/// `origin` is the physical position of the volume's lower corner.  Labels
+/// in `tissues` take their properties from the tissue database according
+/// to `tissue_model`; labels in neither list (typically 0, the background)
+/// leave the grid untouched.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct Phantom {
+    pub source: VoxelSource,
+    pub origin: [f64; 3],
+    pub materials: &'static [(u32, Material)],
+    pub tissues: &'static [(u32, Tissue)],
+    pub tissue_model: TissueModel,
+}
+
+impl Phantom {
+    /// Paint the phantom, returning the ADE currents of dispersive tissues.
+    pub fn apply(&self, grid: &Grid, coeffs: &mut Coefficients) -> io::Result<Vec<AdeEdge>> {
+        let volume = VoxelVolume::load(&self.source)?;
+        let debye: Vec<(u32, DebyeModel)> = match self.tissue_model {
+            TissueModel::SingleFrequency(_) => Vec::new(),
+            TissueModel::Debye {
+                f_min,
+                f_max,
+                poles,
+            } => self
+                .tissues
+                .iter()
+                .map(|&(label, t)| (label, t.cole_cole().debye_fit(f_min, f_max, poles)))
+                .collect(),
+        };
+        let mut edges = Vec::new();
+
+        for k in 0..grid.nz {
+            for j in 0..grid.ny {
+                for i in 0..grid.nx {
+                    let c = grid.cell_center(i, j, k);
+                    let p = [
+                        c[0] - self.origin[0],
+                        c[1] - self.origin[1],
+                        c[2] - self.origin[2],
+                    ];
+                    let Some(label) = volume.label_at(p) else {
+                        continue;
+                    };
+                    let id = grid.idx(i, j, k);
+                    if let Some((_, material)) = self.materials.iter().find(|(l, _)| *l == label) {
+                        coeffs.set_cell(id, material, grid.dt);
+                    } else if let Some(&(_, tissue)) =
+                        self.tissues.iter().find(|(l, _)| *l == label)
+                    {
+                        match self.tissue_model {
+                            TissueModel::SingleFrequency(f) => {
+                                coeffs.set_cell(id, &tissue.material(f), grid.dt);
+                            }
+                            TissueModel::Debye { .. } => {
+                                let (_, model) = debye.iter().find(|(l, _)| *l == label).unwrap();
+                                coeffs.set_cell(id, &Material::VACUUM, grid.dt);
+                                for axis in [Axis::X, Axis::Y, Axis::Z] {
+                                    edges.extend(model.apply_edge(coeffs, id, axis, grid.dt));
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Ok(edges)
+    }
+
+    /// Label of every cell in `lo..hi` (x fastest), `None` outside the
+    /// volume.
+    pub fn labels(&self, grid: &Grid, lo: [u32; 3], hi: [u32; 3]) -> io::Result<Vec<Option<u32>>> {
+        let volume = VoxelVolume::load(&self.source)?;
+        let mut labels = Vec::new();
+        for k in lo[2]..hi[2] {
+            for j in lo[1]..hi[1] {
+                for i in lo[0]..hi[0] {
+                    let c = grid.cell_center(i, j, k);
+                    let p = [0, 1, 2].map(|a| c[a] - self.origin[a]);
+                    labels.push(volume.label_at(p));
+                }
+            }
+        }
+        Ok(labels)
+    }
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Metres in an NRRD unit.
+fn metres_per_unit(unit: &str) -> Option<f64> {
+    match unit {
+        "m" => Some(1.0),
+        "cm" => Some(1e-2),
+        "mm" => Some(1e-3),
+        "um" | "µm" | "micron" => Some(1e-6),
+        _ => None,
+    }
+}
+
+/// All numbers in a header value such as `"1 2 3"` or `"(0.5,0,0)"`.
+fn numbers(s: &str) -> Vec<f64> {
+    s.split(|c: char| c.is_whitespace() || c == ',' || c == '(' || c == ')')
+        .filter_map(|t| t.parse().ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nrrd(dir: &Path, header: &str, data: &[u8]) -> PathBuf {
+        let path = dir.join("labels.nrrd");
+        fs::write(&path, [header.as_bytes(), b"\n", data].concat()).unwrap();
+        path
+    }
+
+    /// A NIfTI-1 file of `dims` u8 voxels of 2 mm, data at byte 352.
+    fn nifti(dir: &Path, dims: [i16; 3], data: &[u8]) -> PathBuf {
+        let mut file = vec![0u8; 352];
+        file[0..4].copy_from_slice(&348_i32.to_le_bytes());
+        file[40..42].copy_from_slice(&3_i16.to_le_bytes());
+        for (a, d) in dims.iter().enumerate() {
+            file[42 + 2 * a..44 + 2 * a].copy_from_slice(&d.to_le_bytes());
+            file[80 + 4 * a..84 + 4 * a].copy_from_slice(&2.0_f32.to_le_bytes());
+        }
+        file[70..72].copy_from_slice(&2_i16.to_le_bytes());
+        file[108..112].copy_from_slice(&352.0_f32.to_le_bytes());
+        file[123] = 2; // millimetres
+        file[344..348].copy_from_slice(b"n+1\0");
+        file.extend(data);
+        let path = dir.join("labels.nii");
+        fs::write(&path, file).unwrap();
+        path
+    }
+
+    #[test]
+    fn nrrd_spacings_follow_their_units() {
+        let dir = tempfile::tempdir().unwrap();
+        let header = "NRRD0004\ntype: uchar\ndimension: 3\nsizes: 2 3 1\n\
+                      spacings: 0.5 1 2\nunits: \"mm\" \"cm\" \"um\"\nencoding: raw\n";
+        let volume =
+            VoxelVolume::from_nrrd(&nrrd(dir.path(), header, &[0, 1, 2, 3, 4, 5])).unwrap();
+        assert_eq!(volume.dims, [2, 3, 1]);
+        assert_eq!(volume.spacing, [0.5e-3, 1e-2, 2e-6]);
+        assert_eq!(volume.labels, [0, 1, 2, 3, 4, 5]);
+        assert_eq!(volume.label_at([0.6e-3, 1.5e-2, 1e-6]), Some(3));
+        assert_eq!(volume.label_at([1.1e-3, 0.0, 0.0]), None);
+    }
+
+    #[test]
+    fn nrrd_without_units_or_with_bad_sizes_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let unitless = "NRRD0004\ntype: uchar\ndimension: 3\nsizes: 1 1 1\nspacings: 1 1 1\n";
+        let e = VoxelVolume::from_nrrd(&nrrd(dir.path(), unitless, &[0]))
+            .err()
+            .unwrap();
+        assert!(e.to_string().contains("space units"), "{e}");
+        for sizes in ["-1 1 1", "0 1 1", "1.5 1 1", "1 1"] {
+            let header = format!(
+                "NRRD0004\ntype: uchar\ndimension: 3\nsizes: {sizes}\nspacings: 1 1 1\n\
+                 space units: \"mm\" \"mm\" \"mm\"\n"
+            );
+            assert!(VoxelVolume::from_nrrd(&nrrd(dir.path(), &header, &[0])).is_err());
+        }
+    }
+
+    #[test]
+    fn nifti_reads_dims_spacing_and_labels() {
+        let dir = tempfile::tempdir().unwrap();
+        let volume =
+            VoxelVolume::from_nifti(&nifti(dir.path(), [2, 2, 1], &[7, 8, 9, 10])).unwrap();
+        assert_eq!(volume.dims, [2, 2, 1]);
+        assert_eq!(volume.spacing, [2e-3; 3]);
+        assert_eq!(volume.label_at([3e-3, 3e-3, 0.0]), Some(10));
+    }
+
+    #[test]
+    fn non_positive_dims_and_short_data_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(VoxelVolume::from_nifti(&nifti(dir.path(), [-2, 2, 1], &[0; 4])).is_err());
+        assert!(VoxelVolume::from_nifti(&nifti(dir.path(), [0, 2, 1], &[0; 4])).is_err());
+        assert!(VoxelVolume::from_nifti(&nifti(dir.path(), [2, 2, 2], &[0; 4])).is_err());
+        let huge = [usize::MAX / 2, 3, 1];
+        assert!(VoxelVolume::from_bytes(&[0; 8], huge, [1e-3; 3], VoxelType::U8, false).is_err());
+    }
+}
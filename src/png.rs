@@ -0,0 +1,176 @@
+//! Minimal PNG encoder for 8-bit RGB images.
+//!
+//! The image data goes into a zlib stream of stored (uncompressed) deflate
+//! blocks, which every decoder accepts; the files are larger than
+//! compressed ones but need no compression library.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// CRC-32 (IEEE) as used by PNG chunks.
+fn crc32(chunks: &[&[u8]]) -> u32 {
+    let mut table = [0u32; 256];
+    for (n, entry) in table.iter_mut().enumerate() {
+        let mut c = n as u32;
+        for _ in 0..8 {
+            c = if c & 1 == 1 {
+                0xedb8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+        }
+        *entry = c;
+    }
+    let mut crc = !0u32;
+    for &byte in chunks.iter().flat_map(|c| c.iter()) {
+        crc = table[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+fn chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend((data.len() as u32).to_be_bytes());
+    out.extend(kind);
+    out.extend(data);
+    out.extend(crc32(&[kind, data]).to_be_bytes());
+}
+
+/// Encode `rgb` (row-major from the top row, 3 bytes per pixel).
+pub fn encode_rgb(width: u32, height: u32, rgb: &[u8]) -> Vec<u8> {
+    assert_eq!(rgb.len(), (width * height * 3) as usize);
+    // Scanlines, each behind filter type 0
+    let mut raw = Vec::with_capacity(rgb.len() + height as usize);
+    for row in rgb.chunks(width as usize * 3) {
+        raw.push(0);
+        raw.extend(row);
+    }
+
+    let mut zlib = vec![0x78, 0x01];
+    let blocks: Vec<&[u8]> = raw.chunks(65535).collect();
+    for (n, block) in blocks.iter().enumerate() {
+        zlib.push((n + 1 == blocks.len()) as u8);
+        let len = block.len() as u16;
+        zlib.extend(len.to_le_bytes());
+        zlib.extend((!len).to_le_bytes());
+        zlib.extend(*block);
+    }
+    zlib.extend(adler32(&raw).to_be_bytes());
+
+    let mut header = Vec::with_capacity(13);
+    header.extend(width.to_be_bytes());
+    header.extend(height.to_be_bytes());
+    // Bit depth 8, colour type 2 (RGB), deflate, adaptive filters, no interlace
+    header.extend([8, 2, 0, 0, 0]);
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    chunk(&mut png, b"IHDR", &header);
+    chunk(&mut png, b"IDAT", &zlib);
+    chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+pub fn write_rgb(path: &Path, width: u32, height: u32, rgb: &[u8]) -> io::Result<()> {
+    fs::File::create(path)?.write_all(&encode_rgb(width, height, rgb))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The chunks of `png` as (kind, data), checking each CRC.
+    fn chunks(png: &[u8]) -> Vec<([u8; 4], &[u8])> {
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        let mut chunks = Vec::new();
+        let mut at = 8;
+        while at < png.len() {
+            let len = u32::from_be_bytes(png[at..at + 4].try_into().unwrap()) as usize;
+            let kind: [u8; 4] = png[at + 4..at + 8].try_into().unwrap();
+            let data = &png[at + 8..at + 8 + len];
+            let crc = u32::from_be_bytes(png[at + 8 + len..at + 12 + len].try_into().unwrap());
+            assert_eq!(crc, crc32(&[&kind, data]));
+            chunks.push((kind, data));
+            at += 12 + len;
+        }
+        chunks
+    }
+
+    /// Inflate a zlib stream made of stored blocks only.
+    fn inflate_stored(zlib: &[u8]) -> Vec<u8> {
+        assert_eq!(u16::from_be_bytes([zlib[0], zlib[1]]) % 31, 0);
+        let mut out = Vec::new();
+        let mut at = 2;
+        loop {
+            let last = zlib[at] & 1 == 1;
+            assert_eq!(zlib[at] >> 1, 0, "not a stored block");
+            let len = u16::from_le_bytes([zlib[at + 1], zlib[at + 2]]);
+            let nlen = u16::from_le_bytes([zlib[at + 3], zlib[at + 4]]);
+            assert_eq!(nlen, !len);
+            out.extend(&zlib[at + 5..at + 5 + len as usize]);
+            at += 5 + len as usize;
+            if last {
+                break;
+            }
+        }
+        let adler = u32::from_be_bytes(zlib[at..at + 4].try_into().unwrap());
+        assert_eq!(adler, adler32(&out));
+        assert_eq!(at + 4, zlib.len());
+        out
+    }
+
+    #[test]
+    fn checksums_match_their_check_values() {
+        assert_eq!(crc32(&[b"123456789"]), 0xcbf4_3926);
+        assert_eq!(crc32(&[b"1234", b"56789"]), 0xcbf4_3926);
+        assert_eq!(crc32(&[b"IEND"]), 0xae42_6082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+
+    #[test]
+    fn scanlines_decode_to_the_pixels() {
+        let (width, height) = (3, 2);
+        let rgb: Vec<u8> = (0..18).collect();
+        let png = encode_rgb(width, height, &rgb);
+        let chunks = chunks(&png);
+        let kinds: Vec<_> = chunks.iter().map(|(k, _)| k).collect();
+        assert_eq!(kinds, [b"IHDR", b"IDAT", b"IEND"]);
+        assert_eq!(chunks[0].1, [0, 0, 0, 3, 0, 0, 0, 2, 8, 2, 0, 0, 0]);
+        let raw = inflate_stored(chunks[1].1);
+        assert_eq!(raw.len(), 2 * (1 + 9));
+        assert_eq!(raw[0], 0);
+        assert_eq!(raw[1..10], rgb[..9]);
+        assert_eq!(raw[10], 0);
+        assert_eq!(raw[11..], rgb[9..]);
+    }
+
+    #[test]
+    fn large_images_span_several_stored_blocks() {
+        let (width, height) = (200, 150);
+        let rgb: Vec<u8> = (0..width * height * 3).map(|i| (i % 251) as u8).collect();
+        let png = encode_rgb(width, height, &rgb);
+        let chunks = chunks(&png);
+        let zlib = chunks[1].1;
+        let raw = inflate_stored(zlib);
+        // 150 scanlines of 601 bytes need two blocks
+        assert_eq!(raw.len(), 150 * 601);
+        assert_eq!(zlib.len(), 2 + 2 * 5 + raw.len() + 4);
+        let pixels: Vec<u8> = raw
+            .chunks(601)
+            .flat_map(|line| {
+                assert_eq!(line[0], 0);
+                &line[1..]
+            })
+            .copied()
+            .collect();
+        assert_eq!(pixels, rgb);
+    }
+}
@@ -0,0 +1,311 @@
+//! Feed ports: input impedance and return loss of a lumped feed.
+//!
+//! A port sits on the E edge of a lumped source (see [`crate::lumped`]).
+//! Every step it samples the edge's E and the four H components around it,
+//! giving the gap voltage and the current through the gap from Ampère's law
+//! on the loop around the edge,
+//!
+//!   V = −E_a·Δa,   I = ΔH_c·Δc' − ΔH_b·Δb'
+//!
+//! ((b, c) the tangential axes of the edge, Δ' the dual widths), i.e. the
+//! potential of the upper node over the lower one and the current the
+//! source drives into the upper node.  At the end of the run both traces are
+//! Fourier transformed (zero-padded, I shifted by the half step it lags V)
+//! and
+//!
+//!   Z_in(f) = V(f) / I(f),   S11(f) = (Z_in − Z₀) / (Z_in + Z₀)
+//!
+//! go to `<name>_impedance.csv` up to `f_max`, next to the raw traces in
+//! `<name>_vi.csv`.  I includes the displacement current of the gap cell,
+//! so Z_in is that of the antenna in parallel with the gap capacitance.
+//!
+//! Multi-port devices get their scattering matrix from the power waves
+//! a = (V + Z₀I)/2√Z₀ into and b = (V − Z₀I)/2√Z₀ out of each port: a run
+//! driving port j (the one with the most incident energy; the others keep
+//! a resistive termination of their Z₀, e.g. a source with zero amplitude)
+//! gives column S_ij = b_i/a_j in `sparams_col<j>.csv`, up to the f_max of
+//! port j.  Once runs driving every port have left their columns in the
+//! directory, the full matrix is written to `sparams.csv`.  Ports are
+//! lumped; waveguide (modal) ports are not available.
+
+use std::f64::consts::PI;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use rustfft::num_complex::Complex64;
+use rustfft::FftPlanner;
+use serde::Serialize;
+
+use crate::config::ConfigError;
+use crate::grid::{Axis, Field, Grid};
+use crate::probes::{Location, Probe, Quantity};
+
+/// A feed port on the `axis`-directed E edge of `cell`.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct FeedPort {
+    pub name: &'static str,
+    pub axis: Axis,
+    /// Cell of the feed edge; at least 1 on the tangential axes.
+    pub cell: [u32; 3],
+    /// Reference impedance of S11 (Ω), normally the source resistance.
+    pub z0: f64,
+    /// Highest frequency written (Hz).
+    pub f_max: f64,
+}
+
+/// Impedance and match at the frequency of the deepest S11.
+#[derive(Copy, Clone, Debug)]
+pub struct BestMatch {
+    pub frequency: f64,
+    pub z: Complex64,
+    pub s11_db: f64,
+}
+
+impl FeedPort {
+    /// Fails unless the edge is inside `grid`, off its lower faces across
+    /// the edge, and the impedance and frequency are positive.
+    pub fn check(&self, grid: &Grid) -> Result<(), ConfigError> {
+        let (b, c) = self.axis.tangential();
+        let [i, j, k] = self.cell;
+        let inside = i < grid.nx && j < grid.ny && k < grid.nz;
+        let problem = if !inside || self.cell[b.lane()] < 1 || self.cell[c.lane()] < 1 {
+            format!("{:?} outside the grid or on its lower faces", self.cell)
+        } else if !(self.z0 > 0.0 && self.f_max > 0.0) {
+            "z0 and f_max must be positive".to_string()
+        } else {
+            return Ok(());
+        };
+        Err(ConfigError(format!("port {}: {problem}", self.name)))
+    }
+
+    /// The edge's E, then H_c at the cell and the cell below along b, then
+    /// H_b at the cell and the cell below along c.
+    pub fn probes(&self) -> [Probe; 5] {
+        let (b, c) = self.axis.tangential();
+        let below = |axis: Axis| {
+            let mut cell = self.cell;
+            cell[axis.lane()] -= 1;
+            cell
+        };
+        let probe = |cell: [u32; 3], field: Field| Probe {
+            name: self.name,
+            at: Location::Cell(cell),
+            quantity: Quantity::Component(field),
+        };
+        [
+            probe(self.cell, Field::E(self.axis)),
+            probe(self.cell, Field::H(c)),
+            probe(below(b), Field::H(c)),
+            probe(self.cell, Field::H(b)),
+            probe(below(c), Field::H(b)),
+        ]
+    }
+
+    /// (V, I) from the samples of [`probes`](Self::probes).
+    pub fn voltage_current(&self, grid: &Grid, samples: &[f64]) -> [f64; 2] {
+        let (b, c) = self.axis.tangential();
+        let cell = |axis: Axis| self.cell[axis.lane()];
+        let voltage = -samples[0] * grid.width(self.axis, cell(self.axis));
+        let current = (samples[1] - samples[2]) * grid.dual_width(c, cell(c))
+            - (samples[3] - samples[4]) * grid.dual_width(b, cell(b));
+        [voltage, current]
+    }
+}
+
+/// Zero-padded spectrum of `trace` sampled every `dt`, the first sample at
+/// `t0`, for bins `0..bins`.
+fn spectrum(trace: &[f64], len: usize, dt: f64, t0: f64, bins: usize) -> Vec<Complex64> {
+    let mut data: Vec<Complex64> = trace.iter().map(|&x| Complex64::new(x, 0.0)).collect();
+    data.resize(len, Complex64::new(0.0, 0.0));
+    FftPlanner::new().plan_fft_forward(len).process(&mut data);
+    (0..bins)
+        .map(|k| {
+            let omega = 2.0 * PI * k as f64 / (len as f64 * dt);
+            data[k] * dt * Complex64::from_polar(1.0, -omega * t0)
+        })
+        .collect()
+}
+
+/// Scattering matrix of the ports on a common frequency grid.
+pub struct SMatrix {
+    pub ports: usize,
+    pub frequencies: Vec<f64>,
+    /// Per frequency, S_ij at `i * ports + j`.
+    pub s: Vec<Vec<Complex64>>,
+}
+
+/// Power waves (a, b) = (V ± Z₀·I) / 2√Z₀ entering and leaving a port.
+fn waves(v: Complex64, i: Complex64, z0: f64) -> (Complex64, Complex64) {
+    let scale = 0.5 / z0.sqrt();
+    ((v + z0 * i) * scale, (v - z0 * i) * scale)
+}
+
+/// Path of the S-matrix column of port `j` (0-based).
+fn column_path(dir: &Path, j: usize) -> PathBuf {
+    dir.join(format!("sparams_col{}.csv", j + 1))
+}
+
+/// Write the traces and impedance spectra of every port, and the S-matrix
+/// column of the excited port; `traces` holds (V, I) per step, V at
+/// (n + 1)·Δt and I half a step earlier.  Returns the best match of each
+/// port and, once the columns of every port are on disk, the full matrix.
+pub fn write_results(
+    dir: &Path,
+    dt: f64,
+    ports: &[FeedPort],
+    traces: &[Vec<[f64; 2]>],
+) -> io::Result<(Vec<Option<BestMatch>>, Option<SMatrix>)> {
+    if ports.is_empty() {
+        return Ok((Vec::new(), None));
+    }
+    fs::create_dir_all(dir)?;
+    // Four-fold zero padding interpolates the spectra between bins
+    let steps = traces[0].len();
+    let len = (4 * steps).next_power_of_two().max(2);
+    let df = 1.0 / (len as f64 * dt);
+    let bins = |f_max: f64| ((f_max / df) as usize + 1).min(len / 2);
+    let all = ports.iter().map(|p| bins(p.f_max)).max().unwrap();
+
+    let mut best = Vec::new();
+    let mut spectra = Vec::new();
+    for (port, trace) in ports.iter().zip(traces) {
+        let path = dir.join(format!("{}_vi.csv", port.name));
+        let mut file = BufWriter::new(fs::File::create(path)?);
+        writeln!(file, "step,time,voltage,current")?;
+        for (n, [v, i]) in trace.iter().enumerate() {
+            writeln!(file, "{n},{:e},{v:e},{i:e}", (n + 1) as f64 * dt)?;
+        }
+        file.flush()?;
+
+        let split = |k: usize| trace.iter().map(|s| s[k]).collect::<Vec<_>>();
+        let voltage = spectrum(&split(0), len, dt, dt, all);
+        let current = spectrum(&split(1), len, dt, 0.5 * dt, all);
+
+        let path = dir.join(format!("{}_impedance.csv", port.name));
+        let mut file = BufWriter::new(fs::File::create(path)?);
+        writeln!(file, "frequency,z_re,z_im,s11_re,s11_im,s11_db")?;
+        let mut deepest: Option<BestMatch> = None;
+        for k in 1..bins(port.f_max) {
+            let z = voltage[k] / current[k];
+            let s11 = (z - port.z0) / (z + port.z0);
+            let s11_db = 20.0 * s11.norm().log10();
+            let frequency = k as f64 * df;
+            writeln!(
+                file,
+                "{frequency:e},{:e},{:e},{:e},{:e},{s11_db:.4}",
+                z.re, z.im, s11.re, s11.im
+            )?;
+            if deepest.is_none_or(|d| s11_db < d.s11_db) {
+                deepest = Some(BestMatch {
+                    frequency,
+                    z,
+                    s11_db,
+                });
+            }
+        }
+        file.flush()?;
+        best.push(deepest);
+        spectra.push((voltage, current));
+    }
+
+    // The excited port carries the most incident energy
+    let incident: Vec<Vec<Complex64>> = ports
+        .iter()
+        .zip(&spectra)
+        .map(|(port, (v, i))| (0..all).map(|k| waves(v[k], i[k], port.z0).0).collect())
+        .collect();
+    let energy = |a: &Vec<Complex64>| a.iter().map(|x| x.norm_sqr()).sum::<f64>();
+    let j = (0..ports.len())
+        .max_by(|&p, &q| energy(&incident[p]).total_cmp(&energy(&incident[q])))
+        .unwrap();
+    let mut file = BufWriter::new(fs::File::create(column_path(dir, j))?);
+    write!(file, "frequency")?;
+    for i in 1..=ports.len() {
+        write!(file, ",s{i}{}_re,s{i}{}_im", j + 1, j + 1)?;
+    }
+    writeln!(file)?;
+    for k in 1..bins(ports[j].f_max) {
+        write!(file, "{:e}", k as f64 * df)?;
+        for (port, (v, i)) in ports.iter().zip(&spectra) {
+            let s = waves(v[k], i[k], port.z0).1 / incident[j][k];
+            write!(file, ",{:e},{:e}", s.re, s.im)?;
+        }
+        writeln!(file)?;
+    }
+    file.flush()?;
+
+    let matrix = assemble(dir, ports.len())?;
+    if let Some(m) = &matrix {
+        write_matrix(&dir.join("sparams.csv"), m)?;
+    }
+    Ok((best, matrix))
+}
+
+/// The S-matrix from the column files of earlier and current runs, if every
+/// column is present on the same frequency grid.
+fn assemble(dir: &Path, ports: usize) -> io::Result<Option<SMatrix>> {
+    let mut columns = Vec::new();
+    for j in 0..ports {
+        let path = column_path(dir, j);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let rows: Vec<Vec<f64>> = fs::read_to_string(path)?
+            .lines()
+            .skip(1)
+            .map(|line| line.split(',').filter_map(|x| x.parse().ok()).collect())
+            .collect();
+        if rows.iter().any(|row| row.len() != 1 + 2 * ports) {
+            return Ok(None);
+        }
+        columns.push(rows);
+    }
+    let frequencies: Vec<f64> = columns[0].iter().map(|row| row[0]).collect();
+    let same_grid = columns.iter().all(|rows| {
+        rows.len() == frequencies.len()
+            && rows
+                .iter()
+                .zip(&frequencies)
+                .all(|(row, f)| (row[0] - f).abs() <= 1e-9 * f)
+    });
+    if !same_grid {
+        return Ok(None);
+    }
+    let s = (0..frequencies.len())
+        .map(|k| {
+            (0..ports * ports)
+                .map(|ij| {
+                    let row = &columns[ij % ports][k];
+                    let i = ij / ports;
+                    Complex64::new(row[1 + 2 * i], row[2 + 2 * i])
+                })
+                .collect()
+        })
+        .collect();
+    Ok(Some(SMatrix {
+        ports,
+        frequencies,
+        s,
+    }))
+}
+
+/// `sparams.csv`: frequency, then re/im of S_ij row by row.
+fn write_matrix(path: &Path, m: &SMatrix) -> io::Result<()> {
+    let mut file = BufWriter::new(fs::File::create(path)?);
+    write!(file, "frequency")?;
+    for i in 1..=m.ports {
+        for j in 1..=m.ports {
+            write!(file, ",s{i}{j}_re,s{i}{j}_im")?;
+        }
+    }
+    writeln!(file)?;
+    for (f, s) in m.frequencies.iter().zip(&m.s) {
+        write!(file, "{f:e}")?;
+        for x in s {
+            write!(file, ",{:e},{:e}", x.re, x.im)?;
+        }
+        writeln!(file)?;
+    }
+    file.flush()
+}
@@ -0,0 +1,326 @@
+//! Field precision other than f32.
+//!
+//! The regular kernels keep fields and coefficients in f32, whose round-off
+//! accumulates over long runs and limits late-time accuracy and the Q that
+//! can be extracted from a slowly decaying resonance.  The wide precisions
+//! run the same Yee update on values stored in double precision: native
+//! f64 where the adapter offers `SHADER_F64`, otherwise an emulated
+//! double-single (hi + lo f32 pair, ≈ 46 significant bits) that works on
+//! any adapter.  The coefficients are rebuilt in f64 from the (ε, σ) and
+//! (μ, σ_m) of each edge recovered from the f32 maps, so material values
+//! carry f32 rounding but nothing in the time stepping does.
+//!
+//! In the other direction, F16 stores the fields as packed half-precision
+//! pairs and does all arithmetic in f32, halving field memory and traffic
+//! for very large grids at ~3 significant digits.  The pairs go through
+//! `pack2x16float`, which is core WGSL: the WGSL front end of the wgpu
+//! release in use does not accept `enable f16` yet, so `SHADER_F16` would
+//! buy nothing.
+//!
+//! Like ADI, the pass replaces the explicit kernels and supports plain
+//! objects only.
+
+use bytemuck::{Pod, Zeroable};
+use serde::Serialize;
+use wgpu::util::DeviceExt;
+
+use crate::gpu::{
+    bg_entry, bgl_storage_entry, bgl_uniform_entry, compute_pipeline_entry, MAX_CELLS,
+};
+use crate::grid::{Axis, Grid};
+use crate::materials::{e_coefficients_f64, Coefficients};
+
+/// Storage and arithmetic of the field update.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum Precision {
+    /// f16 storage, f32 arithmetic.
+    F16,
+    F32,
+    /// Native f64, or double-single where `SHADER_F64` is missing.
+    F64,
+    /// Emulated double-single on any adapter.
+    DoubleSingle,
+}
+
+impl Precision {
+    /// The precision actually run on an adapter with `features`.
+    pub fn resolve(self, features: wgpu::Features) -> Self {
+        match self {
+            Precision::F64 if !features.contains(wgpu::Features::SHADER_F64) => {
+                Precision::DoubleSingle
+            }
+            other => other,
+        }
+    }
+
+    /// Device features the precision needs.
+    pub fn features(self) -> wgpu::Features {
+        match self {
+            Precision::F64 => wgpu::Features::SHADER_F64,
+            _ => wgpu::Features::empty(),
+        }
+    }
+
+    /// Bytes per stored field value.
+    pub fn size(self) -> u64 {
+        match self {
+            Precision::F16 => 2,
+            Precision::F32 => 4,
+            Precision::F64 | Precision::DoubleSingle => 8,
+        }
+    }
+
+    /// GPU representation of a coefficient or width (f32 for F16).
+    fn encode(self, v: f64) -> Vec<u8> {
+        match self {
+            Precision::F16 | Precision::F32 => (v as f32).to_le_bytes().to_vec(),
+            Precision::F64 => v.to_le_bytes().to_vec(),
+            Precision::DoubleSingle => {
+                let hi = v as f32;
+                let lo = (v - hi as f64) as f32;
+                bytemuck::cast_slice(&[hi, lo]).to_vec()
+            }
+        }
+    }
+
+    /// Field value from its stored bytes.
+    pub fn decode(self, bytes: &[u8]) -> f64 {
+        match self {
+            Precision::F16 => f16_value(u16::from_le_bytes([bytes[0], bytes[1]])),
+            Precision::F32 => f32::from_le_bytes(bytes[..4].try_into().unwrap()) as f64,
+            Precision::F64 => f64::from_le_bytes(bytes[..8].try_into().unwrap()),
+            Precision::DoubleSingle => {
+                let [hi, lo]: [f32; 2] = bytemuck::pod_read_unaligned(&bytes[..8]);
+                hi as f64 + lo as f64
+            }
+        }
+    }
+
+    fn encode_all(self, values: impl Iterator<Item = f64>) -> Vec<u8> {
+        values.flat_map(|v| self.encode(v)).collect()
+    }
+}
+
+/// Value of IEEE half-precision bits.
+fn f16_value(bits: u16) -> f64 {
+    let sign = if bits >> 15 == 1 { -1.0 } else { 1.0 };
+    let exp = ((bits >> 10) & 0x1f) as i32;
+    let frac = (bits & 0x3ff) as f64;
+    sign * match exp {
+        0 => frac * 2f64.powi(-24),
+        31 if frac == 0.0 => f64::INFINITY,
+        31 => f64::NAN,
+        _ => (1.0 + frac / 1024.0) * 2f64.powi(exp - 15),
+    }
+}
+
+/// Grid dimensions and the hard source (must match WGSL `Params` of
+/// update_wide.wgsl and update_f16.wgsl).
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct PrecisionParams {
+    nx: u32,
+    ny: u32,
+    nz: u32,
+    /// Source cell, or `u32::MAX` for none.
+    src: u32,
+    /// Source value as an f32 pair hi + lo.
+    hi: f32,
+    lo: f32,
+    _pad: [u32; 2],
+}
+
+/// f64 coefficients of one cell: CA, CB, CP, CQ with x/y/z lanes each.
+fn wide_coefficients(coeffs: &Coefficients, id: usize, dt: f64) -> [f64; 12] {
+    let mut out = [0.0; 12];
+    for axis in [Axis::X, Axis::Y, Axis::Z] {
+        let l = axis.lane();
+        (out[l], out[3 + l]) = match coeffs.e_material(id, axis, dt) {
+            Some((eps, sigma)) => e_coefficients_f64(eps, sigma, dt),
+            None => (coeffs.ca[id][l] as f64, 0.0),
+        };
+        (out[6 + l], out[9 + l]) = match coeffs.h_material(id, axis, dt) {
+            Some((mu, sigma_m)) => e_coefficients_f64(mu, sigma_m, dt),
+            None => (coeffs.cp[id][l] as f64, 0.0),
+        };
+    }
+    out
+}
+
+/// Fields in a non-f32 precision and the kernels advancing them.
+pub struct PrecisionPass {
+    precision: Precision,
+    update_h: wgpu::ComputePipeline,
+    update_e: wgpu::ComputePipeline,
+    source: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    params: wgpu::Buffer,
+    /// Ex, Ey, Ez, Hx, Hy, Hz.
+    fields: [wgpu::Buffer; 6],
+    dims: [u32; 3],
+}
+
+impl PrecisionPass {
+    /// `precision` is already resolved for the device.
+    pub fn new(
+        device: &wgpu::Device,
+        grid: &Grid,
+        precision: Precision,
+        coeffs: &Coefficients,
+    ) -> Self {
+        let source = match precision {
+            Precision::F16 => include_str!("shaders/update_f16.wgsl").to_string(),
+            Precision::F64 => include_str!("shaders/real_f64.wgsl").to_string(),
+            Precision::DoubleSingle => include_str!("shaders/real_ds.wgsl").to_string(),
+            Precision::F32 => panic!("the f32 path uses the regular kernels"),
+        };
+        let source = match precision {
+            Precision::F16 => source,
+            _ => source + include_str!("shaders/update_wide.wgsl"),
+        };
+        let cells = grid.total();
+
+        // Whole words, so packed f16 pairs never end in half a word
+        let bytes = (cells as u64 * precision.size()).next_multiple_of(4) as usize;
+        let fields = ["ex", "ey", "ez", "hx", "hy", "hz"].map(|label| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents: &vec![0; bytes],
+                usage: wgpu::BufferUsages::STORAGE
+                    | wgpu::BufferUsages::COPY_DST
+                    | wgpu::BufferUsages::COPY_SRC,
+            })
+        });
+        let coef =
+            precision.encode_all((0..cells).flat_map(|id| wide_coefficients(coeffs, id, grid.dt)));
+        let coef = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("precision_coeffs"),
+            contents: &coef,
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let mut table = vec![0.0; 8 * MAX_CELLS];
+        for axis in [Axis::X, Axis::Y, Axis::Z] {
+            for i in 0..grid.cells(axis) as usize {
+                table[4 * i + axis.lane()] = 1.0 / grid.width(axis, i as u32);
+                table[4 * (MAX_CELLS + i) + axis.lane()] = 1.0 / grid.dual_width(axis, i as u32);
+            }
+        }
+        let spacing = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("precision_spacing"),
+            contents: &precision.encode_all(table.into_iter()),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let params = PrecisionParams {
+            nx: grid.nx,
+            ny: grid.ny,
+            nz: grid.nz,
+            src: u32::MAX,
+            hi: 0.0,
+            lo: 0.0,
+            _pad: [0; 2],
+        };
+        let params = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("precision_params"),
+            contents: bytemuck::bytes_of(&params),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("precision_bgl"),
+            entries: &[
+                bgl_uniform_entry(0),
+                bgl_storage_entry(1, false),
+                bgl_storage_entry(2, false),
+                bgl_storage_entry(3, false),
+                bgl_storage_entry(4, false),
+                bgl_storage_entry(5, false),
+                bgl_storage_entry(6, false),
+                bgl_storage_entry(7, true),
+                bgl_storage_entry(8, true),
+            ],
+        });
+        let mut entries = vec![bg_entry(0, params.as_entire_binding())];
+        entries.extend(
+            (1..)
+                .zip(&fields)
+                .map(|(b, f)| bg_entry(b, f.as_entire_binding())),
+        );
+        entries.push(bg_entry(7, coef.as_entire_binding()));
+        entries.push(bg_entry(8, spacing.as_entire_binding()));
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("bg_precision"),
+            layout: &bgl,
+            entries: &entries,
+        });
+
+        let pipeline = |entry: &str| compute_pipeline_entry(device, entry, &source, &bgl, entry);
+        PrecisionPass {
+            precision,
+            update_h: pipeline("update_h"),
+            update_e: pipeline("update_e"),
+            source: pipeline("source"),
+            bind_group,
+            params,
+            fields,
+            dims: [grid.nx, grid.ny, grid.nz],
+        }
+    }
+
+    /// One step: the hard source, then H and E.
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder) {
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("precision update"),
+            timestamp_writes: None,
+        });
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_pipeline(&self.source);
+        pass.dispatch_workgroups(1, 1, 1);
+        // F16 threads own one packed word each, 2D-folded past 65535 groups
+        let groups = match self.precision {
+            Precision::F16 => {
+                let words = (self.dims.iter().product::<u32>()).div_ceil(2).div_ceil(64);
+                [words.min(65535), words.div_ceil(65535), 1]
+            }
+            _ => self.dims.map(|n| n.div_ceil(4)),
+        };
+        for pipeline in [&self.update_h, &self.update_e] {
+            pass.set_pipeline(pipeline);
+            pass.dispatch_workgroups(groups[0], groups[1], groups[2]);
+        }
+    }
+
+    /// Ex … Hz in the pass's format, for checkpoints.
+    pub fn fields(&self) -> &[wgpu::Buffer; 6] {
+        &self.fields
+    }
+
+    /// Set Ez at cell `id` (hard source) at the start of the next step.
+    pub fn write_ez(&self, queue: &wgpu::Queue, id: usize, value: f64) {
+        let hi = value as f32;
+        let params = PrecisionParams {
+            nx: self.dims[0],
+            ny: self.dims[1],
+            nz: self.dims[2],
+            src: id as u32,
+            hi,
+            lo: (value - hi as f64) as f32,
+            _pad: [0; 2],
+        };
+        queue.write_buffer(&self.params, 0, bytemuck::bytes_of(&params));
+    }
+
+    /// Copy the aligned word(s) holding Ez at cell `id` to the start of
+    /// `readback`; returns the byte offset of the value within them.
+    pub fn copy_ez(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        id: usize,
+        readback: &wgpu::Buffer,
+    ) -> usize {
+        let offset = id as u64 * self.precision.size();
+        let aligned = offset & !3;
+        let size = self.precision.size().max(4);
+        encoder.copy_buffer_to_buffer(&self.fields[2], aligned, readback, 0, size);
+        (offset - aligned) as usize
+    }
+}
@@ -0,0 +1,542 @@
+//! Point probes and their time series on disk.
+//!
+//! [`ProbeSet`] samples every probe on the GPU each step into a history
+//! buffer of `batch` rows, which is copied back in one transfer per batch
+//! instead of one per probe and step.  The copies go round a ring of
+//! [`STAGING_BUFFERS`] staging buffers mapped behind the GPU: a batch is
+//! taken once the device gets to it, and the stepping only waits for the
+//! oldest when every buffer is still in flight, so the rows trail the steps
+//! by a few batches until the last one flushes them.  Probes sit on a cell, where they
+//! read its raw Yee samples, or at a physical point, where each component
+//! is interpolated trilinearly between its own staggered samples before the
+//! value (or |E|, |H|) is formed.  [`sample`] forms the same value from
+//! fields on the host, for the CPU solver (see [`crate::cpu`]).
+//!
+//! Each probe streams to its own file in `dir`, one row per step with the
+//! step n, the time of the sample in seconds and the value, either as CSV
+//! (`<name>.csv`, with a header row) or as JSON lines (`<name>.jsonl`).
+//! Rows are flushed as they are written, so a long run can be followed with
+//! `tail -f` and a killed run keeps every step it finished.
+
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::mpsc;
+
+use bytemuck::{Pod, Zeroable};
+use serde::Serialize;
+use wgpu::util::DeviceExt;
+
+use crate::config::ConfigError;
+use crate::error::FdtdError;
+use crate::gpu::{
+    bg_entry, bgl_storage_entry, bgl_uniform_entry, compute_pipeline, groups_1d, Uploads,
+};
+use crate::grid::{Axis, Field, Grid};
+
+/// Staging buffers of [`ProbeSet`], the batches read back at once.
+pub const STAGING_BUFFERS: usize = 4;
+
+/// What a probe records.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum Quantity {
+    Component(Field),
+    /// |E| of the three E components at the probe.
+    AbsE,
+    /// |H| of the three H components at the probe.
+    AbsH,
+}
+
+impl Quantity {
+    /// Index of the quantity in probes.wgsl.
+    fn code(self) -> u32 {
+        match self {
+            Quantity::Component(field) => field.index() as u32,
+            Quantity::AbsE => 6,
+            Quantity::AbsH => 7,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Quantity::Component(field) => field.name(),
+            Quantity::AbsE => "|E|",
+            Quantity::AbsH => "|H|",
+        }
+    }
+}
+
+/// Where a probe samples.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize)]
+pub enum Location {
+    /// The raw samples of cell (i, j, k).
+    Cell([u32; 3]),
+    /// A physical position (m), in the same frame as the grid origin.
+    Point([f64; 3]),
+}
+
+/// A named probe of `quantity` at `at`.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct Probe {
+    pub name: &'static str,
+    pub at: Location,
+    pub quantity: Quantity,
+}
+
+impl Probe {
+    /// Fails unless the cell or point is inside `grid`.
+    pub fn check(&self, grid: &Grid) -> Result<(), ConfigError> {
+        let inside = match self.at {
+            Location::Cell([i, j, k]) => i < grid.nx && j < grid.ny && k < grid.nz,
+            Location::Point(point) => [Axis::X, Axis::Y, Axis::Z].iter().all(|&a| {
+                let x = point[a.lane()];
+                grid.node(a, 0) <= x && x <= grid.node(a, grid.cells(a))
+            }),
+        };
+        match inside {
+            true => Ok(()),
+            false => Err(ConfigError(format!(
+                "probe {}: {:?} outside the grid",
+                self.name, self.at
+            ))),
+        }
+    }
+}
+
+/// Lower sample and fractions of one component's interpolation (must match
+/// WGSL `Stencil`).
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct Stencil {
+    base: [u32; 4],
+    frac: [f32; 4],
+}
+
+/// Quantity and per-component stencils of one probe (must match WGSL
+/// `Target`).
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct Target {
+    quantity: u32,
+    _pad: [u32; 3],
+    stencils: [Stencil; 6],
+}
+
+/// Gather parameters (must match WGSL `ProbeParams`).
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct ProbeParams {
+    count: u32,
+    slot: u32,
+    nx: u32,
+    ny: u32,
+}
+
+/// Interpolation stencil of `field` at `at`.
+fn stencil(grid: &Grid, at: Location, field: Field) -> Stencil {
+    let mut s = Stencil {
+        base: [0; 4],
+        frac: [0.0; 4],
+    };
+    match at {
+        Location::Cell(cell) => s.base[..3].copy_from_slice(&cell),
+        Location::Point(point) => {
+            for axis in [Axis::X, Axis::Y, Axis::Z] {
+                // E sits half a cell up its own axis, H half a cell up the
+                // other two.
+                let half = match field {
+                    Field::E(a) => a == axis,
+                    Field::H(a) => a != axis,
+                };
+                let sample = |i: u32| {
+                    let shift = if half { grid.width(axis, i) / 2.0 } else { 0.0 };
+                    grid.node(axis, i) + shift
+                };
+                let (l, x) = (axis.lane(), point[axis.lane()]);
+                let mut i = 0;
+                while i + 2 < grid.cells(axis) && sample(i + 1) <= x {
+                    i += 1;
+                }
+                let f = (x - sample(i)) / (sample(i + 1) - sample(i));
+                s.base[l] = i;
+                s.frac[l] = f.clamp(0.0, 1.0) as f32;
+            }
+        }
+    }
+    s
+}
+
+/// The value of `probe` from the six field arrays in (Ex, …, Hz) order,
+/// formed as probes.wgsl forms it.
+pub fn sample(grid: &Grid, probe: &Probe, fields: [&[f32]; 6]) -> f64 {
+    let parts = components(grid, probe, 0..grid.nz, |field, id| {
+        fields[field.index()][id]
+    });
+    combine(probe, &parts)
+}
+
+/// The interpolated components `probe` is formed from (one, or the three of
+/// |E| or |H|), summed over the corners of their stencils in the z planes
+/// `planes` only, `value(field, id)` reading `field` at cell `id` of
+/// `grid`.  A distributed run adds up those of the planes of every rank.
+pub fn components(
+    grid: &Grid,
+    probe: &Probe,
+    planes: Range<u32>,
+    value: impl Fn(Field, usize) -> f32,
+) -> Vec<f32> {
+    let component = |field: Field| {
+        let s = stencil(grid, probe.at, field);
+        let mut sum = 0.0_f32;
+        for corner in 0..8 {
+            let o = [corner & 1, (corner >> 1) & 1, corner >> 2];
+            let w: f32 = (0..3)
+                .map(|l| [1.0 - s.frac[l], s.frac[l]][o[l] as usize])
+                .product();
+            let k = s.base[2] + o[2];
+            if w != 0.0 && planes.contains(&k) {
+                let id = grid.idx(s.base[0] + o[0], s.base[1] + o[1], k);
+                sum += w * value(field, id);
+            }
+        }
+        sum
+    };
+    let axes = [Axis::X, Axis::Y, Axis::Z];
+    match probe.quantity {
+        Quantity::Component(field) => vec![component(field)],
+        Quantity::AbsE => axes.map(|axis| component(Field::E(axis))).to_vec(),
+        Quantity::AbsH => axes.map(|axis| component(Field::H(axis))).to_vec(),
+    }
+}
+
+/// The value of `probe` from its [`components`].
+pub fn combine(probe: &Probe, components: &[f32]) -> f64 {
+    let value = match probe.quantity {
+        Quantity::Component(_) => components[0],
+        Quantity::AbsE | Quantity::AbsH => components.iter().map(|c| c * c).sum::<f32>().sqrt(),
+    };
+    value as f64
+}
+
+/// GPU sampling and batched readback of a list of probes.
+pub struct ProbeSet {
+    pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    params: wgpu::Buffer,
+    history: wgpu::Buffer,
+    staging: Vec<wgpu::Buffer>,
+    count: u32,
+    batch: u32,
+    dims: [u32; 2],
+    /// Rows of the history written since the last copy.
+    filled: u32,
+    /// Staging buffer the next batch is copied to.
+    next: usize,
+    /// Buffer and rows of the copy encoded and not yet submitted.
+    copied: Option<(usize, u32)>,
+    /// Whether the last step encoded ends the run.
+    flush: bool,
+    /// Copies submitted and being mapped, oldest first.
+    in_flight: VecDeque<Staged>,
+}
+
+/// A batch of rows copied to a staging buffer and being mapped.
+struct Staged {
+    buffer: usize,
+    rows: u32,
+    submission: wgpu::SubmissionIndex,
+    mapped: mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>,
+}
+
+impl ProbeSet {
+    /// Sample `probes` (inside `grid`, see [`Probe::check`]), reading back
+    /// every `batch` steps;
+    /// `fields` are the six field buffers in (Ex, Ey, Ez, Hx, Hy, Hz) order.
+    pub fn new(
+        device: &wgpu::Device,
+        grid: &Grid,
+        probes: &[Probe],
+        batch: u32,
+        fields: [&wgpu::Buffer; 6],
+    ) -> Self {
+        let targets: Vec<Target> = probes
+            .iter()
+            .map(|probe| Target {
+                quantity: probe.quantity.code(),
+                _pad: [0; 3],
+                stencils: Field::ALL.map(|field| stencil(grid, probe.at, field)),
+            })
+            .collect();
+        let count = targets.len() as u32;
+        let targets = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("probe_targets"),
+            contents: bytemuck::cast_slice(&targets),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let params = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("probe_params"),
+            size: std::mem::size_of::<ProbeParams>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let size = (batch * count) as u64 * 4;
+        let dims = [grid.nx, grid.ny];
+        let history = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("probe_history"),
+            size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let staging = (0..STAGING_BUFFERS)
+            .map(|_| {
+                device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("probe_staging"),
+                    size,
+                    usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                })
+            })
+            .collect();
+
+        let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("probes_bgl"),
+            entries: &[
+                bgl_uniform_entry(0),
+                bgl_storage_entry(1, true),
+                bgl_storage_entry(2, true),
+                bgl_storage_entry(3, true),
+                bgl_storage_entry(4, true),
+                bgl_storage_entry(5, true),
+                bgl_storage_entry(6, true),
+                bgl_storage_entry(7, true),
+                bgl_storage_entry(8, false),
+            ],
+        });
+        let pipeline =
+            compute_pipeline(device, "probes", include_str!("shaders/probes.wgsl"), &bgl);
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("bg_probes"),
+            layout: &bgl,
+            entries: &[
+                bg_entry(0, params.as_entire_binding()),
+                bg_entry(1, fields[0].as_entire_binding()),
+                bg_entry(2, fields[1].as_entire_binding()),
+                bg_entry(3, fields[2].as_entire_binding()),
+                bg_entry(4, fields[3].as_entire_binding()),
+                bg_entry(5, fields[4].as_entire_binding()),
+                bg_entry(6, fields[5].as_entire_binding()),
+                bg_entry(7, targets.as_entire_binding()),
+                bg_entry(8, history.as_entire_binding()),
+            ],
+        });
+        ProbeSet {
+            pipeline,
+            bind_group,
+            params,
+            history,
+            staging,
+            count,
+            batch,
+            dims,
+            filled: 0,
+            next: 0,
+            copied: None,
+            flush: false,
+            in_flight: VecDeque::new(),
+        }
+    }
+
+    /// Sample every probe into the next history row and, when the batch is
+    /// full or `last` is set, copy the rows to the next staging buffer.
+    pub fn encode(
+        &mut self,
+        uploads: &mut Uploads,
+        encoder: &mut wgpu::CommandEncoder,
+        last: bool,
+    ) {
+        let params = ProbeParams {
+            count: self.count,
+            slot: self.filled,
+            nx: self.dims[0],
+            ny: self.dims[1],
+        };
+        uploads.write(encoder, &self.params, 0, bytemuck::bytes_of(&params));
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("probes"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &self.bind_group, &[]);
+            pass.dispatch_workgroups(groups_1d(self.count, 64), 1, 1);
+        }
+        self.filled += 1;
+        if self.filled == self.batch || last {
+            let size = (self.filled * self.count) as u64 * 4;
+            let staging = &self.staging[self.next];
+            encoder.copy_buffer_to_buffer(&self.history, 0, staging, 0, size);
+            self.copied = Some((self.next, self.filled));
+            self.next = (self.next + 1) % self.staging.len();
+            self.filled = 0;
+        }
+        self.flush = last;
+    }
+
+    /// Whether rows are sampled but not yet copied (mid-batch) or copied
+    /// but not yet taken.
+    pub fn mid_batch(&self) -> bool {
+        self.filled > 0 || !self.in_flight.is_empty()
+    }
+
+    /// Whether an [`encode`](Self::encode) copied rows for
+    /// [`take`](Self::take) to read back.
+    pub fn copied(&self) -> bool {
+        self.copied.is_some()
+    }
+
+    /// Start reading back the rows copied by the [`encode`](Self::encode)
+    /// just submitted as `submission`, and return the rows (one value per
+    /// probe) of the earlier copies the device is done with, oldest first.
+    /// Waits for the oldest copy when the next one has no free buffer, and
+    /// for all of them after the `last` step.
+    pub fn take(
+        &mut self,
+        device: &wgpu::Device,
+        submission: wgpu::SubmissionIndex,
+    ) -> Result<Vec<Vec<f32>>, FdtdError> {
+        if let Some((buffer, rows)) = self.copied.take() {
+            let (tx, rx) = mpsc::channel();
+            let size = (rows * self.count) as u64 * 4;
+            self.staging[buffer]
+                .slice(..size)
+                .map_async(wgpu::MapMode::Read, move |result| {
+                    let _ = tx.send(result);
+                });
+            self.in_flight.push_back(Staged {
+                buffer,
+                rows,
+                submission,
+                mapped: rx,
+            });
+        }
+        let maintain = match self.in_flight.front() {
+            _ if self.flush => wgpu::Maintain::Wait,
+            Some(oldest) if self.in_flight.len() == self.staging.len() => {
+                wgpu::Maintain::WaitForSubmissionIndex(oldest.submission.clone())
+            }
+            _ => wgpu::Maintain::Poll,
+        };
+        device.poll(maintain);
+        self.mapped_rows()
+    }
+
+    /// Wait for every copy still in flight and return its rows, as
+    /// [`take`](Self::take) does.
+    pub fn drain(&mut self, device: &wgpu::Device) -> Result<Vec<Vec<f32>>, FdtdError> {
+        if self.in_flight.is_empty() {
+            return Ok(Vec::new());
+        }
+        device.poll(wgpu::Maintain::Wait);
+        self.mapped_rows()
+    }
+
+    /// The rows of the copies mapped so far, in order, unmapping them.
+    fn mapped_rows(&mut self) -> Result<Vec<Vec<f32>>, FdtdError> {
+        let mut rows = Vec::new();
+        while let Some(staged) = self.in_flight.front() {
+            let Ok(result) = staged.mapped.try_recv() else {
+                break;
+            };
+            result.map_err(|e| FdtdError::MapFailed(e.to_string()))?;
+            let staging = &self.staging[staged.buffer];
+            let size = (staged.rows * self.count) as u64 * 4;
+            rows.extend(
+                bytemuck::cast_slice::<u8, f32>(&staging.slice(..size).get_mapped_range())
+                    .chunks(self.count as usize)
+                    .map(<[f32]>::to_vec),
+            );
+            staging.unmap();
+            self.in_flight.pop_front();
+        }
+        Ok(rows)
+    }
+}
+
+/// File format of [`ProbeOutput`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum ProbeFormat {
+    Csv,
+    JsonLines,
+}
+
+/// Where and how probe traces are written.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct ProbeOutput {
+    /// Output directory, created if missing.
+    pub dir: &'static str,
+    pub format: ProbeFormat,
+}
+
+/// Open per-probe files of a [`ProbeOutput`].
+pub struct ProbeWriter {
+    format: ProbeFormat,
+    files: Vec<BufWriter<fs::File>>,
+}
+
+impl ProbeWriter {
+    /// Create one file per probe name, truncating old ones.
+    pub fn new(spec: ProbeOutput, names: &[&str]) -> io::Result<Self> {
+        ProbeWriter::open(spec, names, false)
+    }
+
+    /// Add to the files of a run resumed from a checkpoint, creating the
+    /// missing ones.
+    pub fn append(spec: ProbeOutput, names: &[&str]) -> io::Result<Self> {
+        ProbeWriter::open(spec, names, true)
+    }
+
+    fn open(spec: ProbeOutput, names: &[&str], append: bool) -> io::Result<Self> {
+        fs::create_dir_all(spec.dir)?;
+        let extension = match spec.format {
+            ProbeFormat::Csv => "csv",
+            ProbeFormat::JsonLines => "jsonl",
+        };
+        let mut files = Vec::with_capacity(names.len());
+        for name in names {
+            let path = PathBuf::from(spec.dir).join(format!("{name}.{extension}"));
+            let existing = append && path.exists();
+            let file = match append {
+                true => fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)?,
+                false => fs::File::create(path)?,
+            };
+            let mut file = BufWriter::new(file);
+            if spec.format == ProbeFormat::Csv && !existing {
+                writeln!(file, "step,time,value")?;
+                file.flush()?;
+            }
+            files.push(file);
+        }
+        Ok(ProbeWriter {
+            format: spec.format,
+            files,
+        })
+    }
+
+    /// Append the sample of probe `probe` at step `n` and time `t` (s).
+    pub fn record(&mut self, probe: usize, n: u32, t: f64, value: f64) -> io::Result<()> {
+        let file = &mut self.files[probe];
+        match self.format {
+            ProbeFormat::Csv => writeln!(file, "{n},{t:e},{value:e}")?,
+            ProbeFormat::JsonLines => writeln!(
+                file,
+                "{{\"step\": {n}, \"time\": {t:e}, \"value\": {value:e}}}"
+            )?,
+        }
+        file.flush()
+    }
+}
@@ -0,0 +1,127 @@
+//! Console progress of the time loop.
+//!
+//! At [`Verbosity::Normal`] a run shows one progress bar on stderr instead
+//! of a line per step: steps taken, the simulated time, the throughput in
+//! MCells/s and the time left.  The probe values go to the probe files
+//! (`[output] probes` of a scene file, `PROBE_OUTPUT` of
+//! examples/scene.rs); [`Verbosity::Verbose`] prints the per-step lines
+//! above the bar as well.  The bar stays hidden when stderr
+//! is not a terminal, so logs and pipes get no control characters.
+//!
+//! The cases of a sweep running at once (see [`crate::sweep`]) show a bar
+//! each, labelled with [`labelled`]; whatever a run prints goes through
+//! [`suspend`] so that it lands above them.
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{Duration, Instant};
+#[cfg(target_arch = "wasm32")]
+use web_time::{Duration, Instant};
+
+use std::cell::RefCell;
+use std::sync::LazyLock;
+
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+
+use crate::config::Verbosity;
+
+/// Time between refreshes of the bar's message.
+const REFRESH: Duration = Duration::from_millis(100);
+
+/// The bars of every run of the process.
+static BARS: LazyLock<MultiProgress> = LazyLock::new(MultiProgress::new);
+
+thread_local! {
+    /// Shown before the bars of the runs on this thread.
+    static LABEL: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// The bar of one run.
+pub struct Progress {
+    bar: ProgressBar,
+    /// Cells updated per step.
+    cells: f64,
+    dt: f64,
+    refreshed: Instant,
+}
+
+impl Progress {
+    /// A bar over `steps` steps of `cells` cells and `dt` each, or `None`
+    /// when `verbosity` asks for silence.
+    pub fn new(steps: u32, cells: usize, dt: f64, verbosity: Verbosity) -> Option<Self> {
+        if verbosity == Verbosity::Quiet {
+            return None;
+        }
+        let bar = BARS.add(ProgressBar::new(steps as u64));
+        let style = ProgressStyle::with_template(
+            "{prefix}{bar:30.cyan/blue} {pos}/{len} steps  {msg}  ETA {eta}",
+        )
+        .expect("a valid template")
+        .progress_chars("=> ");
+        bar.set_style(style);
+        if let Some(label) = LABEL.with_borrow(Clone::clone) {
+            bar.set_prefix(format!("{label}  "));
+        }
+        Some(Progress {
+            bar,
+            cells: cells as f64,
+            dt,
+            refreshed: Instant::now(),
+        })
+    }
+
+    /// Record `steps` steps taken, past the nominal end as well.
+    pub fn update(&mut self, steps: u32) {
+        if steps as u64 > self.bar.length().unwrap_or(0) {
+            self.bar.set_length(steps as u64);
+        }
+        self.bar.set_position(steps as u64);
+        if self.refreshed.elapsed() >= REFRESH {
+            self.refreshed = Instant::now();
+            self.bar.set_message(format!(
+                "t = {:.3e} s  {:.1} MCells/s",
+                steps as f64 * self.dt,
+                self.bar.per_sec() * self.cells / 1e6
+            ));
+        }
+    }
+
+    /// Print `line` above the bar.
+    pub fn println(&self, line: &str) {
+        if self.bar.is_hidden() {
+            println!("{line}");
+        } else {
+            self.bar.println(line);
+        }
+    }
+
+    /// Remove the bar.
+    pub fn finish(&self) {
+        self.bar.finish_and_clear();
+        BARS.remove(&self.bar);
+    }
+}
+
+/// Run `f` with `label` before the bars of the runs it makes on this
+/// thread.
+pub fn labelled<R>(label: String, f: impl FnOnce() -> R) -> R {
+    let outer = LABEL.replace(Some(label));
+    let result = f();
+    LABEL.set(outer);
+    result
+}
+
+/// Run `f`, which prints on the console, with the bars out of its way.
+pub fn suspend<R>(f: impl FnOnce() -> R) -> R {
+    BARS.suspend(f)
+}
+
+/// `duration` to the second, in hours and minutes when that long: `2 h 05
+/// min`, `7 min 30 s`, `12.4 s`.
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    match seconds {
+        0..60 => format!("{:.1} s", duration.as_secs_f64()),
+        60..3600 => format!("{} min {:02} s", seconds / 60, seconds % 60),
+        _ => format!("{} h {:02} min", seconds / 3600, seconds % 3600 / 60),
+    }
+}
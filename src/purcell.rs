@@ -0,0 +1,203 @@
+//! Local density of states and Purcell factor of a point dipole.
+//!
+//! A current dipole of moment p(t) = `moment`·waveform(t) (A·m) drives one
+//! E edge as the current density J = p/V of its dual cell and replaces the
+//! point source.  Its spectra give two powers per frequency,
+//!
+//!   P_src(f) = −½·Re(E(f)·P*(f))          (work done by the source, from
+//!                                          the DFT of E on the edge)
+//!   P_rad(f) = ∮ ½·Re(E × H*)·dA          (flux through a box around it)
+//!
+//! normalised by the analytic power of the same dipole in vacuum,
+//!
+//!   P₀(f) = η₀·k²·|P(f)|² / 12π.
+//!
+//! P_src/P₀ is the Purcell factor, the LDOS at the dipole relative to
+//! vacuum; P_rad/P₀ is the part that leaves the box, the rest being
+//! absorbed inside it.  `<name>_purcell.csv` holds the powers and both
+//! ratios.  The grid's numerical dispersion makes a vacuum run read a few
+//! percent off 1 at coarse resolution, and with PEC walls the run has to
+//! end before wall reflections return to the dipole.
+
+use std::f64::consts::PI;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use rustfft::num_complex::Complex64;
+use serde::Serialize;
+
+use crate::ade::AdeEdge;
+use crate::config::ConfigError;
+use crate::dft::{DftMonitor, Region, Spectrum};
+use crate::flux::FluxBox;
+use crate::grid::{Axis, Field, Grid};
+use crate::materials::{Coefficients, EPS0, MU0};
+use crate::ntff::eta0;
+use crate::sources::Waveform;
+
+/// A point dipole along the `axis`-directed E edge of `cell`.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct PurcellDipole {
+    pub name: &'static str,
+    pub axis: Axis,
+    pub cell: [u32; 3],
+    /// Dipole moment amplitude (A·m) and waveform.
+    pub moment: f64,
+    pub waveform: Waveform,
+    /// Cells between the dipole's cell and the flux box on every side.
+    pub margin: u32,
+    /// Frequencies in Hz.
+    pub frequencies: &'static [f64],
+}
+
+/// Powers and enhancements at one frequency.
+#[derive(Copy, Clone, Debug)]
+pub struct Enhancement {
+    /// Source, box and vacuum powers (W).
+    pub source: f64,
+    pub radiated: f64,
+    pub vacuum: f64,
+    /// P_src/P₀ and P_rad/P₀.
+    pub purcell: f64,
+    pub radiative: f64,
+}
+
+impl PurcellDipole {
+    /// Fails unless the dipole has frequencies and its flux box lies inside
+    /// `grid`, off its lower faces.
+    pub fn check(&self, grid: &Grid) -> Result<(), ConfigError> {
+        if self.frequencies.is_empty() {
+            let message = format!("Purcell dipole {}: no frequencies", self.name);
+            return Err(ConfigError(message));
+        }
+        if self.cell.iter().any(|&c| c <= self.margin) {
+            let message = format!(
+                "Purcell dipole {}: the flux box must stay off the lower walls",
+                self.name
+            );
+            return Err(ConfigError(message));
+        }
+        self.flux_box().check(grid)
+    }
+
+    /// The driven edge of the dipole, appending its drive to `drives`.
+    /// E picks up −CB·J = −CB·p/V per step.
+    pub fn source_edge(
+        &self,
+        grid: &Grid,
+        coeffs: &Coefficients,
+        drives: &mut Vec<(f64, Waveform)>,
+    ) -> AdeEdge {
+        let a = self.axis;
+        let (b, c) = a.tangential();
+        let cell = |axis: Axis| self.cell[axis.lane()];
+        let volume =
+            grid.width(a, cell(a)) * grid.dual_width(b, cell(b)) * grid.dual_width(c, cell(c));
+        let id = grid.idx(self.cell[0], self.cell[1], self.cell[2]);
+        let cb = coeffs.cb[id][a.lane()] as f64;
+        drives.push((self.moment, self.waveform));
+        AdeEdge::driven(id, a, (drives.len() - 1) as u32, -cb / volume)
+    }
+
+    /// The flux box `margin` cells around the dipole.
+    pub fn flux_box(&self) -> FluxBox {
+        FluxBox {
+            name: self.name,
+            lo: self.cell.map(|c| c - self.margin),
+            hi: self.cell.map(|c| c + self.margin + 1),
+            frequencies: self.frequencies,
+        }
+    }
+
+    /// DFT monitors: E along the dipole at its edge, then the six faces of
+    /// the [`flux_box`](Self::flux_box).
+    pub fn dft_monitors(&self) -> Vec<DftMonitor> {
+        let fields: &'static [Field] = match self.axis {
+            Axis::X => &[Field::E(Axis::X)],
+            Axis::Y => &[Field::E(Axis::Y)],
+            Axis::Z => &[Field::E(Axis::Z)],
+        };
+        let mut monitors = vec![DftMonitor {
+            name: self.name,
+            fields,
+            region: Region::Point(self.cell),
+            frequencies: self.frequencies,
+        }];
+        monitors.extend(
+            self.flux_box()
+                .faces()
+                .iter()
+                .filter_map(|f| f.dft_monitor()),
+        );
+        monitors
+    }
+
+    /// Moment spectrum Δt·Σ p(n + ½)·e^{−j2πf(n+½)Δt} over `steps` steps,
+    /// the drive sampled at the E-update midpoints.
+    fn moment_spectrum(&self, frequency: f64, dt: f64, steps: u32) -> Complex64 {
+        (0..steps)
+            .map(|n| {
+                let t = n as f64 + 0.5;
+                let phase = -2.0 * PI * frequency * t * dt;
+                self.moment * self.waveform.value(t, dt) * dt * Complex64::from_polar(1.0, phase)
+            })
+            .sum()
+    }
+
+    /// Powers and enhancements per frequency from the
+    /// [`dft_monitors`](Self::dft_monitors) spectra of a `steps`-step run.
+    pub fn enhancement(&self, grid: &Grid, steps: u32, spectra: &[Spectrum]) -> Vec<Enhancement> {
+        let faces = self.flux_box().faces();
+        let face_power: Vec<Vec<f64>> = faces
+            .iter()
+            .zip(&spectra[1..])
+            .map(|(face, s)| face.spectrum(grid, s))
+            .collect();
+        let c0 = 1.0 / (MU0 * EPS0).sqrt();
+        self.frequencies
+            .iter()
+            .enumerate()
+            .map(|(f, &frequency)| {
+                let [re, im] = spectra[0].value(Field::E(self.axis), f, 0).unwrap();
+                let e = Complex64::new(re as f64, im as f64);
+                let p = self.moment_spectrum(frequency, grid.dt, steps);
+                let k = 2.0 * PI * frequency / c0;
+                let source = -0.5 * (e * p.conj()).re;
+                let per_face: Vec<f64> = face_power.iter().map(|power| power[f]).collect();
+                let radiated = FluxBox::outgoing(&per_face);
+                let vacuum = eta0() * k * k * p.norm_sqr() / (12.0 * PI);
+                Enhancement {
+                    source,
+                    radiated,
+                    vacuum,
+                    purcell: source / vacuum,
+                    radiative: radiated / vacuum,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Write `<name>_purcell.csv` from the dipole's enhancements.
+pub fn write_results(
+    dir: &Path,
+    dipole: &PurcellDipole,
+    enhancement: &[Enhancement],
+) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!("{}_purcell.csv", dipole.name));
+    let mut file = BufWriter::new(fs::File::create(path)?);
+    writeln!(
+        file,
+        "frequency,source_power,radiated_power,vacuum_power,purcell,radiative_purcell"
+    )?;
+    for (frequency, e) in dipole.frequencies.iter().zip(enhancement) {
+        writeln!(
+            file,
+            "{frequency:e},{:e},{:e},{:e},{:.6},{:.6}",
+            e.source, e.radiated, e.vacuum, e.purcell, e.radiative
+        )?;
+    }
+    file.flush()
+}
@@ -0,0 +1,303 @@
+//! Seedable generators for random / heterogeneous media.
+//!
+//! Each generator fills the cells of a region (a [`Shape`]) with a
+//! spatially varying material.  The same seed always produces the same
+//! medium, so studies of disordered media are reproducible.
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_distr::StandardNormal;
+use serde::Serialize;
+
+use crate::geometry::Shape;
+use crate::grid::Grid;
+use crate::materials::{Coefficients, Material};
+
+/// Statistical model of the medium.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub enum RandomMedium {
+    /// ε_r = mean.eps_r + eps_std·g(r), with g a unit-variance Gaussian
+    /// random field of correlation ⟨g(r)g(r')⟩ = exp(−|r − r'|²/ℓ²).
+    /// ε_r is clipped at 1 to stay physical and within the Courant limit.
+    GaussianCorrelated {
+        mean: Material,
+        eps_std: f64,
+        corr_len: f64,
+    },
+    /// Non-overlapping spheres of `radius` placed by random sequential
+    /// addition until `fill_fraction` of the region volume is reached.
+    SpherePacking {
+        background: Material,
+        inclusion: Material,
+        radius: f64,
+        fill_fraction: f64,
+    },
+    /// Two-phase mixture thresholded from fractal Perlin noise with feature
+    /// size `scale` (m); cells with noise below `threshold` take `a`.
+    PerlinMixture {
+        a: Material,
+        b: Material,
+        scale: f64,
+        octaves: u32,
+        threshold: f64,
+    },
+}
+
+/// A region filled with a random medium.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct RandomRegion {
+    pub shape: Shape,
+    pub medium: RandomMedium,
+    pub seed: u64,
+}
+
+impl RandomRegion {
+    pub fn apply(&self, grid: &Grid, coeffs: &mut Coefficients) {
+        let mask = grid.mask(&self.shape);
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let materials = match self.medium {
+            RandomMedium::GaussianCorrelated {
+                mean,
+                eps_std,
+                corr_len,
+            } => {
+                let field = gaussian_field(grid, &mask, corr_len, &mut rng);
+                field
+                    .iter()
+                    .map(|g| Material {
+                        eps_r: (mean.eps_r + eps_std * g).max(1.0),
+                        ..mean
+                    })
+                    .collect()
+            }
+            RandomMedium::SpherePacking {
+                background,
+                inclusion,
+                radius,
+                fill_fraction,
+            } => {
+                let inside =
+                    sphere_packing(grid, &self.shape, &mask, radius, fill_fraction, &mut rng);
+                inside
+                    .iter()
+                    .map(|&s| if s { inclusion } else { background })
+                    .collect()
+            }
+            RandomMedium::PerlinMixture {
+                a,
+                b,
+                scale,
+                octaves,
+                threshold,
+            } => {
+                let noise = Perlin::new(&mut rng);
+                let mut out = vec![a; grid.total()];
+                for k in 0..grid.nz {
+                    for j in 0..grid.ny {
+                        for i in 0..grid.nx {
+                            let id = grid.idx(i, j, k);
+                            if mask[id] {
+                                let p = grid.cell_center(i, j, k).map(|x| x / scale);
+                                if noise.fractal(p, octaves) >= threshold {
+                                    out[id] = b;
+                                }
+                            }
+                        }
+                    }
+                }
+                out
+            }
+        };
+
+        for (id, material) in materials.iter().enumerate() {
+            if mask[id] {
+                coeffs.set_cell(id, material, grid.dt);
+            }
+        }
+    }
+}
+
+/// Unit-variance Gaussian random field over the masked cells: white noise
+/// smoothed by a separable Gaussian kernel of width ℓ/2 (whose
+/// autocorrelation has 1/e length ℓ).
+fn gaussian_field(grid: &Grid, mask: &[bool], corr_len: f64, rng: &mut StdRng) -> Vec<f64> {
+    let mut field: Vec<f64> = (0..grid.total())
+        .map(|_| rng.sample(StandardNormal))
+        .collect();
+    let dims = [grid.nx as usize, grid.ny as usize, grid.nz as usize];
+    let strides = [1, dims[0], dims[0] * dims[1]];
+    let spacing = [grid.dx, grid.dy, grid.dz];
+
+    for axis in 0..3 {
+        let s = corr_len / 2.0 / spacing[axis];
+        let half = (3.0 * s).ceil() as isize;
+        let kernel: Vec<f64> = (-half..=half)
+            .map(|m| (-(m * m) as f64 / (2.0 * s * s)).exp())
+            .collect();
+        let src = field.clone();
+        for (id, out) in field.iter_mut().enumerate() {
+            let pos = (id / strides[axis] % dims[axis]) as isize;
+            *out = kernel
+                .iter()
+                .zip(-half..=half)
+                .filter(|(_, m)| (0..dims[axis] as isize).contains(&(pos + m)))
+                .map(|(w, m)| w * src[(id as isize + m * strides[axis] as isize) as usize])
+                .sum();
+        }
+    }
+
+    // Normalize to zero mean and unit variance over the region.
+    let values: Vec<f64> = field
+        .iter()
+        .zip(mask)
+        .filter(|(_, &m)| m)
+        .map(|(v, _)| *v)
+        .collect();
+    let n = values.len().max(1) as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let std = (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n)
+        .sqrt()
+        .max(f64::MIN_POSITIVE);
+    field.iter().map(|v| (v - mean) / std).collect()
+}
+
+/// Random sequential addition of equal spheres inside `shape`; returns the
+/// per-cell "inside a sphere" flags.
+fn sphere_packing(
+    grid: &Grid,
+    shape: &Shape,
+    mask: &[bool],
+    radius: f64,
+    fill_fraction: f64,
+    rng: &mut StdRng,
+) -> Vec<bool> {
+    const MAX_ATTEMPTS: usize = 200_000;
+
+    let (lo, hi) = shape.bounds();
+    let cell_volume = grid.dx * grid.dy * grid.dz;
+    let region_volume = mask.iter().filter(|&&m| m).count() as f64 * cell_volume;
+    let sphere_volume = 4.0 / 3.0 * std::f64::consts::PI * radius.powi(3);
+    let target = (fill_fraction * region_volume / sphere_volume).round() as usize;
+
+    let mut centers: Vec<[f64; 3]> = Vec::new();
+    for _ in 0..MAX_ATTEMPTS {
+        if centers.len() >= target {
+            break;
+        }
+        let c: [f64; 3] = std::array::from_fn(|d| rng.gen_range(lo[d]..=hi[d]));
+        if !shape.contains(c) {
+            continue;
+        }
+        let overlaps = centers
+            .iter()
+            .any(|o| (0..3).map(|d| (o[d] - c[d]).powi(2)).sum::<f64>() < 4.0 * radius * radius);
+        if !overlaps {
+            centers.push(c);
+        }
+    }
+
+    let mut inside = vec![false; grid.total()];
+    for c in &centers {
+        let sphere = Shape::Sphere { center: *c, radius };
+        let (slo, shi) = sphere.bounds();
+        let range = |d: usize, delta: f64, n: u32| {
+            let a = ((slo[d] / delta).floor().max(0.0)) as u32;
+            let b = ((shi[d] / delta).ceil() as u32).min(n);
+            a..b
+        };
+        for k in range(2, grid.dz, grid.nz) {
+            for j in range(1, grid.dy, grid.ny) {
+                for i in range(0, grid.dx, grid.nx) {
+                    if sphere.contains(grid.cell_center(i, j, k)) {
+                        inside[grid.idx(i, j, k)] = true;
+                    }
+                }
+            }
+        }
+    }
+    inside
+}
+
+/// Ken Perlin's improved gradient noise with a seeded permutation table.
+struct Perlin {
+    perm: [u8; 512],
+}
+
+impl Perlin {
+    fn new(rng: &mut StdRng) -> Self {
+        let mut p: Vec<u8> = (0..=255).collect();
+        p.shuffle(rng);
+        let mut perm = [0u8; 512];
+        for (i, v) in perm.iter_mut().enumerate() {
+            *v = p[i & 255];
+        }
+        Perlin { perm }
+    }
+
+    /// Sum of `octaves` noise layers, each at twice the frequency and half
+    /// the amplitude of the previous one.
+    fn fractal(&self, p: [f64; 3], octaves: u32) -> f64 {
+        let (mut sum, mut amp, mut freq) = (0.0, 1.0, 1.0);
+        for _ in 0..octaves.max(1) {
+            sum += amp * self.noise(p.map(|x| x * freq));
+            amp *= 0.5;
+            freq *= 2.0;
+        }
+        sum
+    }
+
+    fn noise(&self, p: [f64; 3]) -> f64 {
+        let cell = p.map(|x| x.floor());
+        let [x, y, z] = std::array::from_fn(|d| p[d] - cell[d]);
+        let [xi, yi, zi] = cell.map(|c| (c as i64 & 255) as usize);
+        let fade = |t: f64| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+        let lerp = |t: f64, a: f64, b: f64| a + t * (b - a);
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+
+        let h = |i: usize| self.perm[i] as usize;
+        let a = h(xi) + yi;
+        let (aa, ab) = (h(a) + zi, h(a + 1) + zi);
+        let b = h(xi + 1) + yi;
+        let (ba, bb) = (h(b) + zi, h(b + 1) + zi);
+
+        lerp(
+            w,
+            lerp(
+                v,
+                lerp(u, grad(h(aa), x, y, z), grad(h(ba), x - 1.0, y, z)),
+                lerp(
+                    u,
+                    grad(h(ab), x, y - 1.0, z),
+                    grad(h(bb), x - 1.0, y - 1.0, z),
+                ),
+            ),
+            lerp(
+                v,
+                lerp(
+                    u,
+                    grad(h(aa + 1), x, y, z - 1.0),
+                    grad(h(ba + 1), x - 1.0, y, z - 1.0),
+                ),
+                lerp(
+                    u,
+                    grad(h(ab + 1), x, y - 1.0, z - 1.0),
+                    grad(h(bb + 1), x - 1.0, y - 1.0, z - 1.0),
+                ),
+            ),
+        )
+    }
+}
+
+fn grad(hash: usize, x: f64, y: f64, z: f64) -> f64 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 {
+        y
+    } else if h == 12 || h == 14 {
+        x
+    } else {
+        z
+    };
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
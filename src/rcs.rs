@@ -0,0 +1,167 @@
+//! Radar cross section (RCS).
+//!
+//! The scattered far field of a scatterer inside a closed box comes from
+//! the [`crate::ntff`] transform of the box's DFT spectra, and
+//!
+//!   σ(θ, φ, f) = 4π·|r·E_s(θ, φ, f)|² / |E_inc(f)|²,
+//!
+//! i.e. 4π times the scattered intensity over the incident power density
+//! |E_inc|²/2η₀.  The incident spectrum E_inc(f) is the DFT of the plane
+//! wave's E at the box centre, given as a waveform in steps with the same
+//! time alignment as the monitors.  The monostatic σ is taken back towards
+//! the direction the wave arrives from, the bistatic σ on a (θ, φ) grid.
+//!
+//! The results are the free-space RCS only when absorbing walls keep
+//! reflections out of the box and a plane wave of the given waveform lights
+//! the scatterer, so [`Rcs::check`] rejects runs without both.  No boundary
+//! absorbs yet, so every RCS run is rejected for now.
+
+use std::f64::consts::PI;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use rustfft::num_complex::Complex64;
+use serde::Serialize;
+
+use crate::config::ConfigError;
+use crate::dft::{DftMonitor, Spectrum};
+use crate::flux::FluxBox;
+use crate::grid::Grid;
+use crate::ntff::Currents;
+use crate::sources::Waveform;
+
+/// An RCS calculation on the box of cells `lo..hi`.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct Rcs {
+    pub name: &'static str,
+    /// Lower cell on each axis; must be at least 1.
+    pub lo: [u32; 3],
+    pub hi: [u32; 3],
+    /// Frequencies in Hz.
+    pub frequencies: &'static [f64],
+    /// Direction (θ, φ) in degrees the incident wave arrives from.
+    pub incidence: (f64, f64),
+    /// Incident E at the box centre per time step.
+    pub incident: Waveform,
+    /// Angular step (degrees) of the bistatic grid; 0 for monostatic only.
+    pub step: f64,
+}
+
+impl Rcs {
+    /// Fails unless the box lies inside `grid`, off its lower faces, the
+    /// walls are `absorbing` and an incident plane wave is `lit`.
+    pub fn check(&self, grid: &Grid, absorbing: bool, lit: bool) -> Result<(), ConfigError> {
+        self.surface().check(grid)?;
+        let problem = if !absorbing {
+            "needs absorbing boundaries, and the walls are PEC or periodic"
+        } else if !lit {
+            "needs an incident plane wave lighting the scatterer"
+        } else {
+            return Ok(());
+        };
+        Err(ConfigError(format!("RCS {}: {problem}", self.name)))
+    }
+
+    /// The box as a closed flux surface.
+    pub fn surface(&self) -> FluxBox {
+        FluxBox {
+            name: self.name,
+            lo: self.lo,
+            hi: self.hi,
+            frequencies: self.frequencies,
+        }
+    }
+
+    /// DFT monitors of the six faces, in [`FluxBox::faces`] order.
+    pub fn dft_monitors(&self) -> Vec<DftMonitor> {
+        self.surface()
+            .faces()
+            .iter()
+            .filter_map(|face| face.dft_monitor())
+            .collect()
+    }
+
+    /// Incident spectrum Δt·Σ E_inc(n)·e^{−j2πf(n+1)Δt} over `steps` steps.
+    fn incident_spectrum(&self, frequency: f64, dt: f64, steps: u32) -> Complex64 {
+        (0..steps)
+            .map(|n| {
+                let phase = -2.0 * PI * frequency * (n + 1) as f64 * dt;
+                self.incident.value(n as f64, dt) * dt * Complex64::from_polar(1.0, phase)
+            })
+            .sum()
+    }
+
+    /// σ (m²) per frequency in direction (θ, φ) in degrees.
+    fn sigma(&self, currents: &[Currents], incident: &[f64], theta: f64, phi: f64) -> Vec<f64> {
+        let (theta, phi) = (theta.to_radians(), phi.to_radians());
+        currents
+            .iter()
+            .zip(incident)
+            .map(|(c, e0)| {
+                let [e_theta, e_phi] = c.far_field(theta, phi);
+                4.0 * PI * (e_theta.norm_sqr() + e_phi.norm_sqr()) / e0
+            })
+            .collect()
+    }
+}
+
+/// σ in dBsm.
+fn dbsm(sigma: f64) -> f64 {
+    10.0 * sigma.log10()
+}
+
+/// Write `<name>_rcs_monostatic.csv` and, with a bistatic step,
+/// `<name>_rcs_bistatic.csv` for every calculation, from the face spectra
+/// of each in [`Rcs::dft_monitors`] order after the previous one's.  Returns
+/// the monostatic σ per frequency of each.
+pub fn write_results(
+    dir: &Path,
+    grid: &Grid,
+    steps: u32,
+    calculations: &[Rcs],
+    spectra: &[Spectrum],
+) -> io::Result<Vec<Vec<f64>>> {
+    let mut monostatic = Vec::new();
+    for (rcs, faces) in calculations.iter().zip(spectra.chunks(6)) {
+        let surface = rcs.surface();
+        let currents: Vec<Currents> = (0..rcs.frequencies.len())
+            .map(|f| Currents::new(grid, &surface, faces, f))
+            .collect();
+        let incident: Vec<f64> = rcs
+            .frequencies
+            .iter()
+            .map(|&f| rcs.incident_spectrum(f, grid.dt, steps).norm_sqr())
+            .collect();
+
+        let (theta, phi) = rcs.incidence;
+        let sigma = rcs.sigma(&currents, &incident, theta, phi);
+        let path = dir.join(format!("{}_rcs_monostatic.csv", rcs.name));
+        let mut file = BufWriter::new(fs::File::create(path)?);
+        writeln!(file, "frequency,sigma,sigma_dbsm")?;
+        for (f, s) in rcs.frequencies.iter().zip(&sigma) {
+            writeln!(file, "{f:e},{s:e},{:.4}", dbsm(*s))?;
+        }
+        file.flush()?;
+
+        if rcs.step > 0.0 {
+            let path = dir.join(format!("{}_rcs_bistatic.csv", rcs.name));
+            let mut file = BufWriter::new(fs::File::create(path)?);
+            writeln!(file, "frequency,theta,phi,sigma,sigma_dbsm")?;
+            let thetas = (180.0 / rcs.step).round() as u32;
+            let phis = (360.0 / rcs.step).round() as u32;
+            for t in 0..=thetas {
+                for p in 0..phis {
+                    let (theta, phi) = (t as f64 * rcs.step, p as f64 * rcs.step);
+                    let sigma = rcs.sigma(&currents, &incident, theta, phi);
+                    for (f, s) in rcs.frequencies.iter().zip(&sigma) {
+                        writeln!(file, "{f:e},{theta},{phi},{s:e},{:.4}", dbsm(*s))?;
+                    }
+                }
+            }
+            file.flush()?;
+        }
+        monostatic.push(sigma);
+    }
+    Ok(monostatic)
+}
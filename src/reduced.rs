@@ -0,0 +1,311 @@
+//! Reduced-dimension solvers: 1D (Ez, Hy along x), 2D TMz / TEz in the
+//! xy plane, and body-of-revolution (BOR) in (r, z).
+//!
+//! The reduced grid is the line or plane of the 3D grid through a chosen
+//! point, so objects and materials are painted exactly as in 3D and the
+//! coefficient maps keep their per-component layout.  Each mode has its
+//! own compact kernels over the field components it carries; the outer
+//! boundary is PEC, as in 3D, and the spacing must be uniform.
+//!
+//! BOR runs on the half plane y = y_axis, x ≥ x_axis of the 3D grid, with
+//! the symmetry axis parallel to z; the lanes x/y/z of the coefficients
+//! become r/φ/z.  One azimuthal order m is solved per run, so a rotationally
+//! symmetric structure costs a 2D simulation per order.
+
+use bytemuck::{Pod, Zeroable};
+use serde::Serialize;
+use wgpu::util::DeviceExt;
+
+use crate::error::FdtdError;
+use crate::gpu::{self, bg_entry, bgl_storage_entry, bgl_uniform_entry, compute_pipeline_entry};
+use crate::grid::{Axis, Field, Grid};
+use crate::materials::Coefficients;
+use crate::sources::Waveform;
+
+/// Dimensionality of a run.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum Mode {
+    ThreeD,
+    /// Ez and Hy along x: a normally incident plane wave.
+    OneD,
+    /// Ez, Hx, Hy in the xy plane.
+    TMz,
+    /// Hz, Ex, Ey in the xy plane.
+    TEz,
+    /// Axisymmetric (r, z) solver for azimuthal order `m`: Er, Ez, Hφ vary
+    /// as cos(mφ), Eφ, Hr, Hz as sin(mφ).
+    Bor {
+        m: u32,
+    },
+}
+
+impl Mode {
+    /// The line (1D) or plane (2D) of `grid` through node `at`; for BOR the
+    /// half plane whose edge, the symmetry axis, passes through `at`.
+    pub fn grid(self, grid: &Grid, at: [u32; 3]) -> Grid {
+        let mut reduced = *grid;
+        match self {
+            Mode::ThreeD => {}
+            Mode::Bor { .. } => {
+                reduced.nx = grid.nx - at[0];
+                reduced.ny = 1;
+                reduced.origin[0] = grid.node(Axis::X, at[0]);
+                reduced.origin[1] = grid.node(Axis::Y, at[1]);
+            }
+            _ => {
+                reduced.nz = 1;
+                reduced.origin[2] = grid.node(Axis::Z, at[2]);
+                if self == Mode::OneD {
+                    reduced.ny = 1;
+                    reduced.origin[1] = grid.node(Axis::Y, at[1]);
+                }
+            }
+        }
+        reduced
+    }
+
+    /// Index of 3D node `p` on the reduced grid built by [`Mode::grid`]
+    /// through `at`, as (first, second) in-plane coordinates.
+    pub fn node(self, at: [u32; 3], p: [u32; 3]) -> (u32, u32) {
+        match self {
+            Mode::ThreeD | Mode::TMz | Mode::TEz => (p[0], p[1]),
+            Mode::OneD => (p[0], 0),
+            Mode::Bor { .. } => {
+                assert!(p[0] >= at[0], "BOR points must not lie at r < 0");
+                (p[0] - at[0], p[2])
+            }
+        }
+    }
+
+    /// Largest stable Courant number c·Δt/Δ on a uniform mesh.  BOR orders
+    /// m ≥ 1 are limited by the m/r terms next to the axis.
+    pub fn courant_limit(self) -> f64 {
+        match self {
+            Mode::ThreeD => 1.0 / 3f64.sqrt(),
+            Mode::OneD => 1.0,
+            Mode::TMz | Mode::TEz | Mode::Bor { m: 0 } => 1.0 / 2f64.sqrt(),
+            Mode::Bor { m } => 1.0 / (m + 1) as f64,
+        }
+    }
+
+    /// Name of the out-of-plane field that sources drive and probes read.
+    pub fn field_name(self) -> &'static str {
+        self.field().name()
+    }
+
+    /// The out-of-plane field itself.
+    pub fn field(self) -> Field {
+        match self {
+            Mode::TEz => Field::H(Axis::Z),
+            _ => Field::E(Axis::Z),
+        }
+    }
+}
+
+/// Grid size and inverse spacing of the two in-plane axes (must match
+/// WGSL `Params`; only the BOR kernels read `m` and `dx`).
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct ReducedParams {
+    nx: u32,
+    ny: u32,
+    inv_dx: f32,
+    inv_dy: f32,
+    m: u32,
+    dx: f32,
+    _pad: [u32; 2],
+}
+
+/// GPU state of a 1D, 2D or BOR run.
+pub struct ReducedSolver {
+    update_h: wgpu::ComputePipeline,
+    update_e: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    /// Out-of-plane field (Ez or Hz), then the two in-plane components;
+    /// BOR packs E and H into the first two as vec4 (r, φ, z).
+    fields: [wgpu::Buffer; 3],
+    groups: [u32; 2],
+    nx: u32,
+    /// Floats per node and lane of the probed component in `fields[0]`.
+    stride: u32,
+    lane: u32,
+    dt: f64,
+}
+
+impl ReducedSolver {
+    pub fn new(device: &wgpu::Device, mode: Mode, grid: &Grid, coeffs: &Coefficients) -> Self {
+        assert!(
+            mode != Mode::ThreeD,
+            "reduced solver needs a 1D, 2D or BOR mode"
+        );
+
+        let storage = |label: &str, contents: &[u8], usage: wgpu::BufferUsages| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents,
+                usage: wgpu::BufferUsages::STORAGE | usage,
+            })
+        };
+        let (stride, lane, second, d_second, m) = match mode {
+            Mode::Bor { m } => (4, 2, grid.nz, grid.dz, m),
+            _ => (1, 0, grid.ny, grid.dy, 0),
+        };
+        let zeros = vec![0.0_f32; grid.total() * stride as usize];
+        let field_usage = wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC;
+        let fields = ["f0", "f1", "f2"]
+            .map(|label| storage(label, bytemuck::cast_slice(&zeros), field_usage));
+        let [ca, cb, cp, cq] = [&coeffs.ca, &coeffs.cb, &coeffs.cp, &coeffs.cq].map(|c| {
+            storage(
+                "reduced_coeffs",
+                bytemuck::cast_slice(c),
+                wgpu::BufferUsages::empty(),
+            )
+        });
+        let params = ReducedParams {
+            nx: grid.nx,
+            ny: second,
+            inv_dx: (1.0 / grid.dx) as f32,
+            inv_dy: (1.0 / d_second) as f32,
+            m,
+            dx: grid.dx as f32,
+            _pad: [0; 2],
+        };
+        let params = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("reduced_params"),
+            contents: bytemuck::bytes_of(&params),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("reduced_bgl"),
+            entries: &[
+                bgl_uniform_entry(0),
+                bgl_storage_entry(1, false),
+                bgl_storage_entry(2, false),
+                bgl_storage_entry(3, false),
+                bgl_storage_entry(4, true),
+                bgl_storage_entry(5, true),
+                bgl_storage_entry(6, true),
+                bgl_storage_entry(7, true),
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("bg_reduced"),
+            layout: &bgl,
+            entries: &[
+                bg_entry(0, params.as_entire_binding()),
+                bg_entry(1, fields[0].as_entire_binding()),
+                bg_entry(2, fields[1].as_entire_binding()),
+                bg_entry(3, fields[2].as_entire_binding()),
+                bg_entry(4, ca.as_entire_binding()),
+                bg_entry(5, cb.as_entire_binding()),
+                bg_entry(6, cp.as_entire_binding()),
+                bg_entry(7, cq.as_entire_binding()),
+            ],
+        });
+
+        let (source, entries, groups) = match mode {
+            Mode::OneD => (
+                include_str!("shaders/update_1d.wgsl"),
+                ("update_h", "update_e"),
+                [grid.nx.div_ceil(64), 1],
+            ),
+            Mode::TMz => (
+                include_str!("shaders/update_2d.wgsl"),
+                ("tm_h", "tm_e"),
+                [grid.nx.div_ceil(8), grid.ny.div_ceil(8)],
+            ),
+            Mode::TEz => (
+                include_str!("shaders/update_2d.wgsl"),
+                ("te_h", "te_e"),
+                [grid.nx.div_ceil(8), grid.ny.div_ceil(8)],
+            ),
+            _ => (
+                include_str!("shaders/update_bor.wgsl"),
+                ("update_h", "update_e"),
+                [grid.nx.div_ceil(8), grid.nz.div_ceil(8)],
+            ),
+        };
+        let update_h = compute_pipeline_entry(device, "reduced_h", source, &bgl, entries.0);
+        let update_e = compute_pipeline_entry(device, "reduced_e", source, &bgl, entries.1);
+
+        ReducedSolver {
+            update_h,
+            update_e,
+            bind_group,
+            fields,
+            groups,
+            nx: grid.nx,
+            stride,
+            lane,
+            dt: grid.dt,
+        }
+    }
+
+    /// Run `steps` steps with a hard source on the out-of-plane field at
+    /// node (`source.0`, `source.1`) and return that field at `probe` after
+    /// every step.  The trace stays on the GPU until the end of the run.
+    pub fn run(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        source: (u32, u32),
+        probe: (u32, u32),
+        waveform: &Waveform,
+        steps: u32,
+    ) -> Result<Vec<f32>, FdtdError> {
+        let offset = |(i, j): (u32, u32)| ((i + self.nx * j) * self.stride + self.lane) as u64 * 4;
+        let trace = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("reduced_trace"),
+            size: steps as u64 * 4,
+            usage: wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        for n in 0..steps {
+            let value = waveform.value(n as f64, self.dt) as f32;
+            queue.write_buffer(&self.fields[0], offset(source), bytemuck::bytes_of(&value));
+
+            let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("reduced_step"),
+            });
+            {
+                let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some("reduced update"),
+                    timestamp_writes: None,
+                });
+                pass.set_bind_group(0, &self.bind_group, &[]);
+                pass.set_pipeline(&self.update_h);
+                pass.dispatch_workgroups(self.groups[0], self.groups[1], 1);
+                pass.set_pipeline(&self.update_e);
+                pass.dispatch_workgroups(self.groups[0], self.groups[1], 1);
+            }
+            encoder.copy_buffer_to_buffer(&self.fields[0], offset(probe), &trace, n as u64 * 4, 4);
+            queue.submit(Some(encoder.finish()));
+        }
+
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("reduced_readback"),
+            size: steps as u64 * 4,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&Default::default());
+        encoder.copy_buffer_to_buffer(&trace, 0, &readback, 0, steps as u64 * 4);
+        queue.submit(Some(encoder.finish()));
+
+        let slice = readback.slice(..);
+        gpu::map_read(device, &[slice])?;
+        let values = bytemuck::cast_slice(&slice.get_mapped_range()).to_vec();
+        readback.unmap();
+        Ok(values)
+    }
+}
+
+/// Analytic 1D hard-source response: the source waveform delayed by the
+/// travel time over `distance` (m) at speed `c`, plus the one step the
+/// update takes to propagate the injected value.  Valid until the first
+/// reflection from the PEC ends reaches the probe.
+pub fn hard_source_1d(waveform: &Waveform, distance: f64, c: f64, dt: f64, n: u32) -> f64 {
+    waveform.value(n as f64 + 1.0 - distance.abs() / (c * dt), dt)
+}
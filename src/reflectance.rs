@@ -0,0 +1,148 @@
+//! Reflection and transmission spectra normalised by a reference run.
+//!
+//! A structure (slab, grating, metasurface) sits between two flux planes
+//! across the path of the incident wave: `front` between the source and the
+//! structure, `back` behind it.  The program then runs twice.  A reference
+//! run without the structure gives the incident power P_inc(f) through the
+//! front plane, and the incident fields there; in the structure run the
+//! front plane, with the incident fields subtracted, sees the reflected
+//! power and the back plane the transmitted power:
+//!
+//!   R(f) = −P_front[E − E_inc, H − H_inc] / P_inc,   T(f) = P_back / P_inc
+//!
+//! (flux counted from front to back), and A = 1 − R − T is the absorbed
+//! fraction.  `<name>_rt.csv` holds the three per frequency.
+//!
+//! The reference run keeps only the lumped elements (the sources) of the
+//! scene, on the Δt of the structure so that both runs sample the same
+//! times; its other outputs are overwritten by the structure run's.  With
+//! PEC walls the run has to end before wall reflections reach the planes,
+//! or the planes must span a waveguide-like cross section of the domain,
+//! for R and T to be those of the structure alone.
+
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::dft::{DftMonitor, Spectrum};
+use crate::flux::FluxMonitor;
+use crate::grid::{Axis, Grid};
+
+/// Reflection and transmission between the E planes `front` and `back`
+/// normal to `normal`.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct ReflectionTransmission {
+    pub name: &'static str,
+    pub normal: Axis,
+    /// Plane between the source and the structure, and plane behind it;
+    /// both at least 1.
+    pub front: u32,
+    pub back: u32,
+    /// Cell ranges of the planes along the first and second tangential axes.
+    pub u: (u32, u32),
+    pub v: (u32, u32),
+    /// Frequencies in Hz.
+    pub frequencies: &'static [f64],
+}
+
+impl ReflectionTransmission {
+    /// The front and back planes as flux rectangles.
+    pub fn planes(&self) -> [FluxMonitor; 2] {
+        [self.front, self.back].map(|index| FluxMonitor {
+            name: self.name,
+            normal: self.normal,
+            index,
+            u: self.u,
+            v: self.v,
+            frequencies: self.frequencies,
+        })
+    }
+
+    /// DFT monitors of the front and back planes.
+    pub fn dft_monitors(&self) -> Vec<DftMonitor> {
+        self.planes()
+            .iter()
+            .filter_map(FluxMonitor::dft_monitor)
+            .collect()
+    }
+
+    /// +1 when the wave travels along +normal, −1 otherwise.
+    fn sign(&self) -> f64 {
+        if self.back > self.front {
+            1.0
+        } else {
+            -1.0
+        }
+    }
+}
+
+/// `structure` − `reference`, the scattered fields of the same monitor.
+fn difference(structure: &Spectrum, reference: &Spectrum) -> Spectrum {
+    let fields = structure
+        .fields
+        .iter()
+        .zip(&reference.fields)
+        .map(|((field, s), (_, r))| {
+            let values = s
+                .iter()
+                .zip(r)
+                .map(|(s, r)| [s[0] - r[0], s[1] - r[1]])
+                .collect();
+            (*field, values)
+        })
+        .collect();
+    Spectrum {
+        name: structure.name,
+        frequencies: structure.frequencies.clone(),
+        lo: structure.lo,
+        size: structure.size,
+        fields,
+    }
+}
+
+/// Write `<name>_rt.csv` for every calculation from the plane spectra of
+/// the reference and structure runs, each in
+/// [`ReflectionTransmission::dft_monitors`] order after the previous one's.
+/// Returns (R, T) per frequency of each.
+pub fn write_results(
+    dir: &Path,
+    grid: &Grid,
+    calculations: &[ReflectionTransmission],
+    reference: &[Spectrum],
+    structure: &[Spectrum],
+) -> io::Result<Vec<Vec<[f64; 2]>>> {
+    let mut results = Vec::new();
+    let planes = reference.chunks(2).zip(structure.chunks(2));
+    for (rt, (reference, structure)) in calculations.iter().zip(planes) {
+        let [front, back] = rt.planes();
+        let sign = rt.sign();
+        let incident = front.spectrum(grid, &reference[0]);
+        let reflected = front.spectrum(grid, &difference(&structure[0], &reference[0]));
+        let transmitted = back.spectrum(grid, &structure[1]);
+
+        fs::create_dir_all(dir)?;
+        let path = dir.join(format!("{}_rt.csv", rt.name));
+        let mut file = BufWriter::new(fs::File::create(path)?);
+        writeln!(
+            file,
+            "frequency,incident_power,reflectance,transmittance,absorptance"
+        )?;
+        let mut spectrum = Vec::new();
+        for (f, frequency) in rt.frequencies.iter().enumerate() {
+            let p_inc = sign * incident[f];
+            let r = -sign * reflected[f] / p_inc;
+            let t = sign * transmitted[f] / p_inc;
+            writeln!(
+                file,
+                "{frequency:e},{p_inc:e},{r:.6},{t:.6},{:.6}",
+                1.0 - r - t
+            )?;
+            spectrum.push([r, t]);
+        }
+        file.flush()?;
+        results.push(spectrum);
+    }
+    Ok(results)
+}
@@ -0,0 +1,206 @@
+//! Interactive stepping of a paused simulation (`fdtd_3d repl`).
+//!
+//! [`repl`] reads one command per line and answers on `output`, so a setup
+//! can be poked at without a config-edit-rerun cycle:
+//!
+//! ```text
+//! step [N]                       take N steps (1 by default)
+//! probe I J K [FIELD]            the six components, or one, at a cell
+//! snapshot [FIELD]…              raw snapshot files of the components (Ez)
+//! set-source amplitude A         scale the source waveform
+//! set-source cell I J K          move the source
+//! status                         steps taken and time
+//! help                           this list
+//! quit                           leave (also at the end of the input)
+//! ```
+//!
+//! Snapshots are written to the working directory in the raw format of
+//! [`crate::snapshots`] (`Ez_000120.bin` after 121 steps), which
+//! `fdtd_3d compare` reads.  A mistyped command is reported and the
+//! session goes on.
+
+use std::fs;
+use std::io::{self, BufRead, Write};
+
+use crate::backend::ComputeBackend;
+use crate::error::FdtdError;
+use crate::grid::{Axis, Field};
+use crate::precision::Precision;
+use crate::simulation::Simulation;
+use crate::snapshots;
+
+const HELP: &str = "\
+step [N]                  take N steps (1 by default)
+probe I J K [FIELD]       the six components, or one, at a cell
+snapshot [FIELD]...       raw snapshot files of the components (Ez)
+set-source amplitude A    scale the source waveform
+set-source cell I J K     move the source
+status                    steps taken and time
+help                      this list
+quit                      leave";
+
+/// One line of the session.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReplCommand {
+    Step(u32),
+    Probe([u32; 3], Option<Field>),
+    Snapshot(Vec<Field>),
+    SourceAmplitude(f64),
+    SourceCell([u32; 3]),
+    Status,
+    Help,
+    Quit,
+}
+
+impl ReplCommand {
+    /// Parse a command line; `None` for a blank one.
+    pub fn parse(line: &str) -> Result<Option<ReplCommand>, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let Some((&command, args)) = words.split_first() else {
+            return Ok(None);
+        };
+        let command = match (command, args) {
+            ("step", []) => ReplCommand::Step(1),
+            ("step", [n]) => ReplCommand::Step(number(n)?),
+            ("probe", [i, j, k]) => ReplCommand::Probe(cell(i, j, k)?, None),
+            ("probe", [i, j, k, field]) => {
+                ReplCommand::Probe(cell(i, j, k)?, Some(parse_field(field)?))
+            }
+            ("snapshot", []) => ReplCommand::Snapshot(vec![Field::E(Axis::Z)]),
+            ("snapshot", fields) => ReplCommand::Snapshot(
+                fields
+                    .iter()
+                    .map(|f| parse_field(f))
+                    .collect::<Result<_, _>>()?,
+            ),
+            ("set-source", ["amplitude", a]) => ReplCommand::SourceAmplitude(number(a)?),
+            ("set-source", ["cell", i, j, k]) => ReplCommand::SourceCell(cell(i, j, k)?),
+            ("status", []) => ReplCommand::Status,
+            ("help", []) => ReplCommand::Help,
+            ("quit" | "exit", []) => ReplCommand::Quit,
+            ("step" | "probe" | "set-source" | "status" | "help" | "quit" | "exit", _) => {
+                return Err(format!("usage of {command}: see help"))
+            }
+            _ => return Err(format!("unknown command {command:?} (help lists them)")),
+        };
+        Ok(Some(command))
+    }
+}
+
+fn number<T: std::str::FromStr>(word: &str) -> Result<T, String> {
+    word.parse().map_err(|_| format!("bad number {word:?}"))
+}
+
+fn cell(i: &str, j: &str, k: &str) -> Result<[u32; 3], String> {
+    Ok([number(i)?, number(j)?, number(k)?])
+}
+
+fn parse_field(name: &str) -> Result<Field, String> {
+    Field::ALL
+        .into_iter()
+        .find(|f| f.name().eq_ignore_ascii_case(name))
+        .ok_or_else(|| format!("unknown field {name:?} (Ex … Hz)"))
+}
+
+/// Run commands from `input` on `simulation` until `quit` or the end of the
+/// input, writing `prompt` before each line when it is not empty.
+pub fn repl(
+    simulation: &mut Simulation,
+    input: impl BufRead,
+    output: &mut impl Write,
+    prompt: &str,
+) -> io::Result<()> {
+    let config = simulation.config();
+    if config.precision != Precision::F32 && !config.compare_f32 {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "the session reads the f32 fields, which another precision running alone \
+             does not step",
+        ));
+    }
+    write!(output, "{prompt}")?;
+    output.flush()?;
+    for line in input.lines() {
+        match ReplCommand::parse(&line?) {
+            Ok(Some(ReplCommand::Quit)) => return Ok(()),
+            Ok(Some(command)) => {
+                if let Err(e) = execute(simulation, command, output) {
+                    writeln!(output, "error: {e}")?;
+                }
+            }
+            Ok(None) => {}
+            Err(e) => writeln!(output, "error: {e}")?,
+        }
+        write!(output, "{prompt}")?;
+        output.flush()?;
+    }
+    writeln!(output)
+}
+
+fn execute(
+    simulation: &mut Simulation,
+    command: ReplCommand,
+    output: &mut impl Write,
+) -> Result<(), String> {
+    let grid = *simulation.grid();
+    let inside = |[i, j, k]: [u32; 3]| {
+        if i < grid.nx && j < grid.ny && k < grid.nz {
+            Ok(())
+        } else {
+            Err(format!(
+                "cell [{i}, {j}, {k}] outside the {}×{}×{} grid",
+                grid.nx, grid.ny, grid.nz
+            ))
+        }
+    };
+    let write_error = |e: io::Error| e.to_string();
+    let run_error = |e: FdtdError| e.to_string();
+    match command {
+        ReplCommand::Step(steps) => {
+            simulation.run(steps).map_err(run_error)?;
+            if simulation.stopped() {
+                writeln!(output, "stopped by a hook").map_err(write_error)?;
+            }
+            status(simulation, output).map_err(write_error)?;
+        }
+        ReplCommand::Probe(cell, field) => {
+            inside(cell)?;
+            let fields = field.map_or(Field::ALL.to_vec(), |f| vec![f]);
+            let values = fields
+                .iter()
+                .map(|&f| Ok(format!("{} = {:e}", f.name(), simulation.value(f, cell)?)))
+                .collect::<Result<Vec<_>, FdtdError>>()
+                .map_err(run_error)?;
+            writeln!(output, "{}", values.join(", ")).map_err(write_error)?;
+        }
+        ReplCommand::Snapshot(fields) => {
+            let n = simulation.steps_taken().saturating_sub(1);
+            for field in fields {
+                let path = format!("{}_{n:06}.bin", field.name());
+                let mut bytes = snapshots::raw_header(&grid, field, n);
+                let values = simulation.field(field).map_err(run_error)?;
+                bytes.extend(bytemuck::cast_slice(&values));
+                fs::write(&path, bytes).map_err(|e| format!("{path}: {e}"))?;
+                writeln!(output, "wrote {path}").map_err(write_error)?;
+            }
+        }
+        ReplCommand::SourceAmplitude(amplitude) => simulation.set_source_amplitude(amplitude),
+        ReplCommand::SourceCell(cell) => {
+            inside(cell)?;
+            simulation.set_source(cell).map_err(run_error)?;
+        }
+        ReplCommand::Status => status(simulation, output).map_err(write_error)?,
+        ReplCommand::Help => writeln!(output, "{HELP}").map_err(write_error)?,
+        ReplCommand::Quit => {}
+    }
+    Ok(())
+}
+
+fn status(simulation: &Simulation, output: &mut impl Write) -> io::Result<()> {
+    writeln!(
+        output,
+        "{} steps, t = {:.4e} s",
+        simulation.steps_taken(),
+        simulation.time()
+    )
+}
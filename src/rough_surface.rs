@@ -0,0 +1,131 @@
+//! Random rough interfaces for surface-scattering studies.
+//!
+//! A height profile ζ(u, v) with prescribed RMS height and correlation
+//! length is synthesised spectrally: complex white noise is shaped by the
+//! square root of the roughness power spectrum, transformed back with an
+//! FFT and rescaled to the exact RMS height.  Cells below the surface take
+//! one material, cells above the other (sea/air, ground/air, …).
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rand_distr::StandardNormal;
+use rustfft::num_complex::Complex64;
+use rustfft::FftPlanner;
+use serde::Serialize;
+
+use crate::grid::{Axis, Grid};
+use crate::materials::{Coefficients, Material};
+
+/// Roughness power spectrum family.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub enum Spectrum {
+    /// W(k) ∝ exp(−k²ℓ²/4): smooth, rolling surfaces.
+    Gaussian,
+    /// W(k) ∝ (1 + k²ℓ²)^(−3/2): rougher at small scales (natural terrain).
+    Exponential,
+}
+
+impl Spectrum {
+    fn density(&self, k: f64, corr_len: f64) -> f64 {
+        let kl2 = (k * corr_len).powi(2);
+        match self {
+            Spectrum::Gaussian => (-kl2 / 4.0).exp(),
+            Spectrum::Exponential => (1.0 + kl2).powf(-1.5),
+        }
+    }
+}
+
+/// Two-material interface at `mean_height` (m) along `normal`.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct RoughSurface {
+    pub normal: Axis,
+    pub mean_height: f64,
+    /// RMS height (m).
+    pub rms: f64,
+    /// Correlation length ℓ (m).
+    pub corr_len: f64,
+    pub spectrum: Spectrum,
+    /// Material on the low side of the interface.
+    pub below: Material,
+    /// Material on the high side of the interface.
+    pub above: Material,
+    pub seed: u64,
+}
+
+impl RoughSurface {
+    pub fn apply(&self, grid: &Grid, coeffs: &mut Coefficients) {
+        let (ta, tb) = self.normal.tangential();
+        let (nu, nv) = (grid.cells(ta) as usize, grid.cells(tb) as usize);
+        let zeta = self.heights(nu, nv, grid.spacing(ta), grid.spacing(tb));
+        let dn = grid.spacing(self.normal);
+
+        for k in 0..grid.nz {
+            for j in 0..grid.ny {
+                for i in 0..grid.nx {
+                    let p = [i, j, k];
+                    let (u, v) = (p[ta.lane()] as usize, p[tb.lane()] as usize);
+                    let h = (p[self.normal.lane()] as f64 + 0.5) * dn;
+                    let material = if h < self.mean_height + zeta[u + nu * v] {
+                        &self.below
+                    } else {
+                        &self.above
+                    };
+                    coeffs.set_cell(grid.idx(i, j, k), material, grid.dt);
+                }
+            }
+        }
+    }
+
+    /// Height profile ζ on an `nu`×`nv` grid (row-major in u), zero mean and
+    /// RMS exactly `self.rms`.
+    pub fn heights(&self, nu: usize, nv: usize, du: f64, dv: f64) -> Vec<f64> {
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let two_pi = 2.0 * std::f64::consts::PI;
+        let wavenumber = |m: usize, n: usize, d: f64| {
+            let m = if m <= n / 2 {
+                m as f64
+            } else {
+                m as f64 - n as f64
+            };
+            two_pi * m / (n as f64 * d)
+        };
+
+        let mut data: Vec<Complex64> = (0..nu * nv)
+            .map(|id| {
+                let (ku, kv) = (wavenumber(id % nu, nu, du), wavenumber(id / nu, nv, dv));
+                let amp = self
+                    .spectrum
+                    .density((ku * ku + kv * kv).sqrt(), self.corr_len)
+                    .sqrt();
+                Complex64::new(rng.sample(StandardNormal), rng.sample(StandardNormal)) * amp
+            })
+            .collect();
+        data[0] = Complex64::new(0.0, 0.0);
+
+        // 2D inverse FFT: rows, then columns.
+        let mut planner = FftPlanner::new();
+        let row = planner.plan_fft_inverse(nu);
+        for chunk in data.chunks_mut(nu) {
+            row.process(chunk);
+        }
+        let col = planner.plan_fft_inverse(nv);
+        let mut column = vec![Complex64::new(0.0, 0.0); nv];
+        for u in 0..nu {
+            for v in 0..nv {
+                column[v] = data[u + nu * v];
+            }
+            col.process(&mut column);
+            for v in 0..nv {
+                data[u + nu * v] = column[v];
+            }
+        }
+
+        let mut zeta: Vec<f64> = data.iter().map(|c| c.re).collect();
+        let n = zeta.len() as f64;
+        let mean = zeta.iter().sum::<f64>() / n;
+        let rms = (zeta.iter().map(|z| (z - mean).powi(2)).sum::<f64>() / n).sqrt();
+        let scale = if rms > 0.0 { self.rms / rms } else { 0.0 };
+        zeta.iter_mut().for_each(|z| *z = (*z - mean) * scale);
+        zeta
+    }
+}
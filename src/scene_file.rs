@@ -0,0 +1,1286 @@
+//! Declarative run descriptions in TOML or JSON.
+//!
+//! A scene file names everything a run needs as text, so that a simulation
+//! is a reproducible artifact rather than an edit of Rust code.  In TOML:
+//!
+//! ```toml
+//! steps = 300
+//! boundaries = ["pec", "pec", "periodic"]   # x, y, z; PEC when left out
+//! script = "row.rhai"     # more materials and objects, see script.rs
+//!
+//! [parameters]            # constants of the script, overridden by --set
+//! count = 4
+//! radius = 1e-3
+//!
+//! [grid]
+//! cells = [64, 64, 64]
+//! spacing = 1e-3          # m, cubic cells; or [dx, dy, dz]
+//! courant = 0.5           # Δt = courant·dx/c; or dt = 1.6e-12
+//!
+//! [materials.glass]       # ε_r, σ, μ_r and σ_m, vacuum values by default
+//! eps_r = 4.0
+//!
+//! [[objects]]             # painted in order; "vacuum" is always defined
+//! material = "glass"
+//! shape = { kind = "sphere", center = [0.032, 0.032, 0.032], radius = 0.01 }
+//!
+//! [[objects]]             # a tilted plate: transforms apply in order
+//! material = "glass"
+//! shape = { kind = "box", min = [-0.01, -0.01, -0.001], max = [0.01, 0.01, 0.001] }
+//! transform = [
+//!     { kind = "rotate", axis = [1.0, 0.0, 0.0], degrees = 30.0 },
+//!     { kind = "translate", offset = [0.032, 0.032, 0.016] },
+//! ]                       # also scale = { factors }, rotate about = [x, y, z]
+//!
+//! [[objects]]             # a coax aperture: a box minus a cylinder
+//! material = "vacuum"
+//! [objects.shape]         # or union / intersection of shapes = [...]
+//! kind = "difference"
+//! shape = { kind = "box", min = [0.0, 0.0, 0.05], max = [0.064, 0.064, 0.052] }
+//! [[objects.shape.minus]]
+//! kind = "cylinder"
+//! center = [0.032, 0.032, 0.051]
+//! radius = 0.004
+//! height = 0.002
+//! axis = "z"
+//!
+//! [source]                # hard Ez point source, a centred Gaussian by default
+//! cell = [32, 32, 32]
+//! waveform = { kind = "gaussian", width = 20.0, delay = 40.0 }
+//!
+//! [[probes]]              # cell = [i, j, k] or point = [x, y, z] (m)
+//! name = "probe"
+//! cell = [42, 32, 32]
+//! quantity = "ez"         # ex … hz, abs_e or abs_h; ez by default
+//!
+//! [[monitors]]            # line = { axis, through } or plane = { normal, index }
+//! name = "xy"
+//! field = "ez"
+//! plane = { normal = "z", index = 32 }
+//! every = 5
+//!
+//! [[dft_monitors]]        # point = [i, j, k], plane = { … } or box = { lo, hi }
+//! name = "centre"
+//! fields = ["ez"]
+//! point = [32, 32, 32]
+//! frequencies = [5e9, 1e10]
+//!
+//! [[flux_monitors]]
+//! name = "top"
+//! normal = "z"
+//! index = 48
+//! u = [8, 56]
+//! v = [8, 56]
+//!
+//! [[lumped]]
+//! axis = "z"
+//! cell = [16, 16, 32]
+//! [lumped.element]        # resistor r, capacitor c, inductor l or voltage_source
+//! kind = "voltage_source"
+//! r = 50.0
+//! v = 1.0
+//! waveform = { kind = "gaussian", width = 20.0, delay = 40.0 }
+//!
+//! [[ports]]               # on the edge of a lumped element
+//! name = "feed"
+//! axis = "z"
+//! cell = [16, 16, 32]
+//! z0 = 50.0
+//! f_max = 2e10
+//!
+//! [output]
+//! dir = "monitors"
+//! probes = { dir = "probes", format = "csv" }   # or "json_lines"
+//! probe_batch = 16        # steps between probe and flux readbacks
+//! energy_every = 10
+//! mat = "monitors/results.mat"                  # MATLAB v7.3
+//!
+//! [sweep]                 # `fdtd_3d sweep` runs every combination, see sweep.rs
+//! warm_start = true       # each case from the fields of the one before
+//!
+//! [sweep.values]
+//! radius = [1e-3, 2e-3, 4e-3]
+//! count = { from = 2, to = 8, count = 4 }      # evenly spaced, ends included
+//!
+//! [[sweep.cases]]         # or the cases one by one instead
+//! radius = 1e-3
+//! count = 2
+//! ```
+//!
+//! The same description in JSON (`.json` files) follows [`SCHEMA`], the
+//! JSON Schema published as `schema/scene.schema.json`; gprMax models
+//! (`.in` files) are read through [`crate::gprmax`].
+//!
+//! [`load`] checks a description before any GPU work and reports every
+//! problem at once, each at its path in the file: syntax and type errors
+//! (`objects[1].shape.radius: invalid type …`), then references and extents
+//! (`objects[2].material: unknown material "glas"`, `probes[0].cell: [70, 3,
+//! 3] outside the 64×64×64 grid`, `ports[1]: overlaps ports[0]`).  The
+//! description maps onto a [`SimulationBuilder`] and comes out as a
+//! validated [`Config`]; the settings it does not cover keep their
+//! [`Config::new`] defaults.  Names and lists are leaked into the `'static`
+//! data the configuration holds, once per loaded file.
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::builder::SimulationBuilder;
+use crate::config::{Boundary, Config, ConfigError};
+use crate::dft::{DftMonitor, Region};
+use crate::flux::FluxMonitor;
+use crate::geometry::{Shape, Transform};
+use crate::gprmax::GprMaxModel;
+use crate::gpu::MAX_CELLS;
+use crate::grid::{self, Grid};
+use crate::lumped::{LumpedElement, LumpedKind};
+use crate::materials::Material;
+use crate::monitors::{Monitor, Span};
+use crate::ports::FeedPort;
+use crate::probes::{Location, Probe, ProbeFormat, ProbeOutput, Quantity};
+use crate::script;
+use crate::sources::Waveform;
+use crate::C0;
+
+/// JSON Schema of the JSON form of a scene file.
+pub const SCHEMA: &str = include_str!("../schema/scene.schema.json");
+
+/// A whole run description; see the module documentation.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SceneFile {
+    pub steps: u32,
+    pub grid: GridSpec,
+    /// Setup script, relative to the file.
+    #[serde(default)]
+    pub script: Option<String>,
+    #[serde(default)]
+    pub parameters: BTreeMap<String, Parameter>,
+    #[serde(default)]
+    pub boundaries: Option<[BoundaryName; 3]>,
+    #[serde(default)]
+    pub materials: BTreeMap<String, MaterialSpec>,
+    #[serde(default)]
+    pub objects: Vec<ObjectSpec>,
+    #[serde(default)]
+    pub source: Option<SourceSpec>,
+    #[serde(default)]
+    pub probes: Vec<ProbeSpec>,
+    #[serde(default)]
+    pub monitors: Vec<MonitorSpec>,
+    #[serde(default)]
+    pub dft_monitors: Vec<DftSpec>,
+    #[serde(default)]
+    pub flux_monitors: Vec<FluxSpec>,
+    #[serde(default)]
+    pub lumped: Vec<LumpedSpec>,
+    #[serde(default)]
+    pub ports: Vec<PortSpec>,
+    #[serde(default)]
+    pub output: Option<OutputSpec>,
+    /// Values of the parameters for `fdtd_3d sweep`; a single run ignores
+    /// it.
+    #[serde(default)]
+    pub sweep: Option<SweepSpec>,
+}
+
+/// A script constant, kept an integer when written as one.
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Parameter {
+    Integer(i64),
+    Real(f64),
+}
+
+impl Parameter {
+    /// The value as a real.
+    pub fn value(self) -> f64 {
+        match self {
+            Parameter::Integer(n) => n as f64,
+            Parameter::Real(x) => x,
+        }
+    }
+}
+
+impl Display for Parameter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Parameter::Integer(n) => n.fmt(f),
+            Parameter::Real(x) => x.fmt(f),
+        }
+    }
+}
+
+impl std::str::FromStr for Parameter {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Parameter, String> {
+        text.parse()
+            .map(Parameter::Integer)
+            .or_else(|_| text.parse().map(Parameter::Real))
+            .map_err(|_| format!("{text:?} is not a number"))
+    }
+}
+
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Spacing {
+    Cubic(f64),
+    PerAxis([f64; 3]),
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GridSpec {
+    pub cells: [u32; 3],
+    pub spacing: Spacing,
+    /// Δt in seconds, or as a Courant number c·Δt/dx.
+    #[serde(default)]
+    pub dt: Option<f64>,
+    #[serde(default)]
+    pub courant: Option<f64>,
+    #[serde(default)]
+    pub origin: Option<[f64; 3]>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BoundaryName {
+    Pec,
+    Periodic,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AxisName {
+    X,
+    Y,
+    Z,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldName {
+    Ex,
+    Ey,
+    Ez,
+    Hx,
+    Hy,
+    Hz,
+}
+
+#[derive(Copy, Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MaterialSpec {
+    #[serde(default)]
+    pub eps_r: Option<f64>,
+    #[serde(default)]
+    pub sigma: Option<f64>,
+    #[serde(default)]
+    pub mu_r: Option<f64>,
+    #[serde(default)]
+    pub sigma_m: Option<f64>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ObjectSpec {
+    pub material: String,
+    pub shape: ShapeSpec,
+    #[serde(default)]
+    pub transform: Vec<TransformSpec>,
+}
+
+/// One step of an object's placement; angles in degrees, about the origin
+/// unless `about` is given.
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum TransformSpec {
+    Translate {
+        offset: [f64; 3],
+    },
+    Rotate {
+        axis: [f64; 3],
+        degrees: f64,
+        #[serde(default)]
+        about: Option<[f64; 3]>,
+    },
+    Scale {
+        factors: [f64; 3],
+    },
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ShapeSpec {
+    Box {
+        min: [f64; 3],
+        max: [f64; 3],
+    },
+    Sphere {
+        center: [f64; 3],
+        radius: f64,
+    },
+    Cylinder {
+        center: [f64; 3],
+        radius: f64,
+        height: f64,
+        axis: AxisName,
+    },
+    Union {
+        shapes: Vec<ShapeSpec>,
+    },
+    Intersection {
+        shapes: Vec<ShapeSpec>,
+    },
+    /// `shape` with every shape of `minus` cut out.
+    Difference {
+        shape: Box<ShapeSpec>,
+        minus: Vec<ShapeSpec>,
+    },
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SourceSpec {
+    #[serde(default)]
+    pub cell: Option<[u32; 3]>,
+    #[serde(default)]
+    pub waveform: Option<WaveformSpec>,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum WaveformSpec {
+    Gaussian { width: f64, delay: f64 },
+    ModulatedGaussian { freq: f64, width: f64, delay: f64 },
+    Sine { freq: f64, ramp: f64 },
+    Step { rise: f64, delay: f64 },
+    Ricker { freq: f64, delay: f64 },
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuantityName {
+    Ex,
+    Ey,
+    Ez,
+    Hx,
+    Hy,
+    Hz,
+    AbsE,
+    AbsH,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProbeSpec {
+    pub name: String,
+    #[serde(default)]
+    pub cell: Option<[u32; 3]>,
+    #[serde(default)]
+    pub point: Option<[f64; 3]>,
+    #[serde(default)]
+    pub quantity: Option<QuantityName>,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PlaneSpec {
+    pub normal: AxisName,
+    pub index: u32,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LineSpec {
+    pub axis: AxisName,
+    pub through: [u32; 3],
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MonitorSpec {
+    pub name: String,
+    pub field: FieldName,
+    #[serde(default)]
+    pub line: Option<LineSpec>,
+    #[serde(default)]
+    pub plane: Option<PlaneSpec>,
+    #[serde(default)]
+    pub every: Option<u32>,
+    #[serde(default)]
+    pub batch: Option<u32>,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BoxSpec {
+    pub lo: [u32; 3],
+    pub hi: [u32; 3],
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DftSpec {
+    pub name: String,
+    pub fields: Vec<FieldName>,
+    #[serde(default)]
+    pub point: Option<[u32; 3]>,
+    #[serde(default)]
+    pub plane: Option<PlaneSpec>,
+    #[serde(default, rename = "box")]
+    pub cells: Option<BoxSpec>,
+    pub frequencies: Vec<f64>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FluxSpec {
+    pub name: String,
+    pub normal: AxisName,
+    pub index: u32,
+    pub u: (u32, u32),
+    pub v: (u32, u32),
+    #[serde(default)]
+    pub frequencies: Vec<f64>,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LumpedSpec {
+    pub axis: AxisName,
+    pub cell: [u32; 3],
+    pub element: ElementSpec,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ElementSpec {
+    Resistor {
+        r: f64,
+    },
+    Capacitor {
+        c: f64,
+    },
+    Inductor {
+        l: f64,
+    },
+    VoltageSource {
+        r: f64,
+        v: f64,
+        waveform: WaveformSpec,
+    },
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PortSpec {
+    pub name: String,
+    pub axis: AxisName,
+    pub cell: [u32; 3],
+    pub z0: f64,
+    pub f_max: f64,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OutputSpec {
+    #[serde(default)]
+    pub dir: Option<String>,
+    #[serde(default)]
+    pub probes: Option<ProbeOutputSpec>,
+    /// Steps between probe and flux readbacks.
+    #[serde(default)]
+    pub probe_batch: Option<u32>,
+    #[serde(default)]
+    pub energy_every: Option<u32>,
+    /// MATLAB v7.3 file of the probes, snapshots and spectra.
+    #[serde(default)]
+    pub mat: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FormatName {
+    Csv,
+    JsonLines,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProbeOutputSpec {
+    pub dir: String,
+    pub format: FormatName,
+}
+
+/// The cases of a parameter sweep (see [`crate::sweep`]): every
+/// combination of `values`, or `cases` as listed.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SweepSpec {
+    #[serde(default)]
+    pub values: BTreeMap<String, ValuesSpec>,
+    #[serde(default)]
+    pub cases: Vec<BTreeMap<String, Parameter>>,
+    /// Start each case from the fields the one before ended with.
+    #[serde(default)]
+    pub warm_start: bool,
+}
+
+/// Values of one swept parameter.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ValuesSpec {
+    List(Vec<Parameter>),
+    /// `count` reals from `from` to `to`, both included.
+    Range {
+        from: f64,
+        to: f64,
+        count: u32,
+    },
+}
+
+impl ValuesSpec {
+    /// The values in order.
+    pub fn values(&self) -> Vec<Parameter> {
+        match *self {
+            ValuesSpec::List(ref values) => values.clone(),
+            ValuesSpec::Range { from, count: 1, .. } => vec![Parameter::Real(from)],
+            ValuesSpec::Range { from, to, count } => (0..count)
+                .map(|i| Parameter::Real(from + (to - from) * i as f64 / (count - 1) as f64))
+                .collect(),
+        }
+    }
+}
+
+fn leak(text: String) -> &'static str {
+    Box::leak(text.into_boxed_str())
+}
+
+/// A parse error with the path of the value it concerns.
+fn at_path<E: Display>(error: serde_path_to_error::Error<E>) -> ConfigError {
+    let path = error.path().to_string();
+    let inner = error.into_inner();
+    ConfigError(if path == "." {
+        inner.to_string()
+    } else {
+        format!("{path}: {inner}")
+    })
+}
+
+/// Problems found in a description, each at its path.
+#[derive(Default)]
+struct Issues(Vec<String>);
+
+impl Issues {
+    fn at(&mut self, path: impl Display, message: impl Display) {
+        self.0.push(format!("{path}: {message}"));
+    }
+}
+
+impl AxisName {
+    fn axis(self) -> grid::Axis {
+        match self {
+            AxisName::X => grid::Axis::X,
+            AxisName::Y => grid::Axis::Y,
+            AxisName::Z => grid::Axis::Z,
+        }
+    }
+}
+
+impl FieldName {
+    fn field(self) -> grid::Field {
+        use grid::{Axis, Field};
+        match self {
+            FieldName::Ex => Field::E(Axis::X),
+            FieldName::Ey => Field::E(Axis::Y),
+            FieldName::Ez => Field::E(Axis::Z),
+            FieldName::Hx => Field::H(Axis::X),
+            FieldName::Hy => Field::H(Axis::Y),
+            FieldName::Hz => Field::H(Axis::Z),
+        }
+    }
+}
+
+impl QuantityName {
+    fn quantity(self) -> Quantity {
+        let field = |name: FieldName| Quantity::Component(name.field());
+        match self {
+            QuantityName::Ex => field(FieldName::Ex),
+            QuantityName::Ey => field(FieldName::Ey),
+            QuantityName::Ez => field(FieldName::Ez),
+            QuantityName::Hx => field(FieldName::Hx),
+            QuantityName::Hy => field(FieldName::Hy),
+            QuantityName::Hz => field(FieldName::Hz),
+            QuantityName::AbsE => Quantity::AbsE,
+            QuantityName::AbsH => Quantity::AbsH,
+        }
+    }
+}
+
+impl TransformSpec {
+    /// Why the step cannot be inverted, if it cannot.
+    fn problem(self) -> Option<&'static str> {
+        match self {
+            TransformSpec::Rotate { axis, .. } if axis == [0.0; 3] => {
+                Some("a rotation needs a non-zero axis")
+            }
+            TransformSpec::Scale { factors } if factors.contains(&0.0) => {
+                Some("scale factors must be non-zero")
+            }
+            _ => None,
+        }
+    }
+
+    fn transform(self) -> Transform {
+        match self {
+            TransformSpec::Translate { offset } => Transform::translate(offset),
+            TransformSpec::Rotate {
+                axis,
+                degrees,
+                about,
+            } => Transform::rotate_about(axis, degrees.to_radians(), about.unwrap_or([0.0; 3])),
+            TransformSpec::Scale { factors } => Transform::scale(factors),
+        }
+    }
+}
+
+impl ObjectSpec {
+    /// The placed shape.
+    fn shape(&self) -> Shape {
+        let shape = self.shape.shape();
+        if self.transform.is_empty() {
+            return shape;
+        }
+        let transform = self
+            .transform
+            .iter()
+            .fold(Transform::IDENTITY, |t, step| t.then(step.transform()));
+        shape.transformed(transform)
+    }
+}
+
+impl ShapeSpec {
+    /// Whether the shape has no points (an operand list may be empty).
+    fn empty(&self) -> bool {
+        match self {
+            ShapeSpec::Box { min, max } => (0..3).any(|d| min[d] >= max[d]),
+            ShapeSpec::Sphere { radius, .. } => *radius <= 0.0,
+            ShapeSpec::Cylinder { radius, height, .. } => *radius <= 0.0 || *height <= 0.0,
+            ShapeSpec::Union { shapes } => shapes.iter().all(ShapeSpec::empty),
+            ShapeSpec::Intersection { shapes } => {
+                shapes.is_empty() || shapes.iter().any(ShapeSpec::empty)
+            }
+            ShapeSpec::Difference { shape, .. } => shape.empty(),
+        }
+    }
+
+    fn shape(&self) -> Shape {
+        let combine = |shapes: &[ShapeSpec], op: fn(Shape, Shape) -> Shape| {
+            let mut shapes = shapes.iter().map(ShapeSpec::shape);
+            let first = shapes.next().expect("a boolean of at least one shape");
+            shapes.fold(first, op)
+        };
+        match *self {
+            ShapeSpec::Box { min, max } => Shape::Box { min, max },
+            ShapeSpec::Sphere { center, radius } => Shape::Sphere { center, radius },
+            ShapeSpec::Cylinder {
+                center,
+                radius,
+                height,
+                axis,
+            } => Shape::Cylinder {
+                center,
+                radius,
+                height,
+                axis: axis.axis(),
+            },
+            ShapeSpec::Union { ref shapes } => combine(shapes, Shape::union),
+            ShapeSpec::Intersection { ref shapes } => combine(shapes, Shape::intersect),
+            ShapeSpec::Difference {
+                ref shape,
+                ref minus,
+            } => minus.iter().fold(shape.shape(), |s, m| s.minus(m.shape())),
+        }
+    }
+}
+
+impl WaveformSpec {
+    fn waveform(self) -> Waveform {
+        match self {
+            WaveformSpec::Gaussian { width, delay } => Waveform::Gaussian { width, delay },
+            WaveformSpec::ModulatedGaussian { freq, width, delay } => {
+                Waveform::ModulatedGaussian { freq, width, delay }
+            }
+            WaveformSpec::Sine { freq, ramp } => Waveform::Sine { freq, ramp },
+            WaveformSpec::Step { rise, delay } => Waveform::Step { rise, delay },
+            WaveformSpec::Ricker { freq, delay } => Waveform::Ricker { freq, delay },
+        }
+    }
+}
+
+impl MaterialSpec {
+    fn material(self) -> Material {
+        let vacuum = Material::VACUUM;
+        Material {
+            eps_r: self.eps_r.unwrap_or(vacuum.eps_r),
+            sigma: self.sigma.unwrap_or(vacuum.sigma),
+            mu_r: self.mu_r.unwrap_or(vacuum.mu_r),
+            sigma_m: self.sigma_m.unwrap_or(vacuum.sigma_m),
+        }
+    }
+}
+
+impl ElementSpec {
+    fn kind(self) -> LumpedKind {
+        match self {
+            ElementSpec::Resistor { r } => LumpedKind::Resistor { r },
+            ElementSpec::Capacitor { c } => LumpedKind::Capacitor { c },
+            ElementSpec::Inductor { l } => LumpedKind::Inductor { l },
+            ElementSpec::VoltageSource { r, v, waveform } => LumpedKind::VoltageSource {
+                r,
+                v,
+                waveform: waveform.waveform(),
+            },
+        }
+    }
+}
+
+impl SceneFile {
+    /// Parse a TOML description.
+    pub fn from_toml(text: &str) -> Result<SceneFile, ConfigError> {
+        serde_path_to_error::deserialize(toml::Deserializer::new(text)).map_err(at_path)
+    }
+
+    /// Parse a JSON description.
+    pub fn from_json(text: &str) -> Result<SceneFile, ConfigError> {
+        let mut json = serde_json::Deserializer::from_str(text);
+        serde_path_to_error::deserialize(&mut json).map_err(at_path)
+    }
+
+    /// Run a setup script: its materials join the file's, its objects are
+    /// painted after the file's and its source replaces the file's.
+    pub fn apply_script(&mut self, source: &str) -> Result<(), ConfigError> {
+        let placed = script::evaluate(source, &self.grid, &self.parameters)?;
+        self.materials.extend(placed.materials);
+        self.objects.extend(placed.objects);
+        if placed.source.is_some() {
+            self.source = placed.source;
+        }
+        Ok(())
+    }
+
+    /// Check references and names without building anything; every
+    /// problem is reported, one per line, at its path in the file.  The
+    /// extents of monitors and ports are checked by [`Config::validate`]
+    /// once mapped.
+    pub fn check(&self) -> Result<(), ConfigError> {
+        let mut issues = Issues::default();
+        let cells = self.grid.cells;
+        let size = format!("{}×{}×{}", cells[0], cells[1], cells[2]);
+        let inside = |cell: [u32; 3]| cell.iter().zip(cells).all(|(&i, n)| i < n);
+
+        if cells.iter().any(|&n| n == 0 || n as usize > MAX_CELLS) {
+            issues.at("grid.cells", format!("1 to {MAX_CELLS} cells per axis"));
+        }
+        let spacing = match self.grid.spacing {
+            Spacing::Cubic(d) => [d; 3],
+            Spacing::PerAxis(d) => d,
+        };
+        if spacing.iter().any(|&d| d <= 0.0) {
+            issues.at("grid.spacing", "cell sizes must be positive");
+        }
+        match (self.grid.dt, self.grid.courant) {
+            (Some(dt), None) if dt <= 0.0 => issues.at("grid.dt", "must be positive"),
+            (None, Some(c)) if c <= 0.0 => issues.at("grid.courant", "must be positive"),
+            (Some(_), None) | (None, Some(_)) => {}
+            _ => issues.at("grid", "give exactly one of dt and courant"),
+        }
+        if self.steps == 0 {
+            issues.at("steps", "the run needs at least one step");
+        }
+
+        for (name, spec) in &self.materials {
+            let values = [spec.eps_r, spec.mu_r].into_iter().flatten();
+            if values.into_iter().any(|v| v <= 0.0) {
+                issues.at(format!("materials.{name}"), "ε_r and μ_r must be positive");
+            }
+            let losses = [spec.sigma, spec.sigma_m].into_iter().flatten();
+            if losses.into_iter().any(|v| v < 0.0) {
+                issues.at(
+                    format!("materials.{name}"),
+                    "σ and σ_m must not be negative",
+                );
+            }
+        }
+        for (n, object) in self.objects.iter().enumerate() {
+            let name = object.material.as_str();
+            if name != "vacuum" && !self.materials.contains_key(name) {
+                issues.at(
+                    format!("objects[{n}].material"),
+                    format!("unknown material {name:?}"),
+                );
+            }
+            if object.shape.empty() {
+                issues.at(format!("objects[{n}].shape"), "the shape is empty");
+            }
+            for (m, step) in object.transform.iter().enumerate() {
+                if let Some(problem) = step.problem() {
+                    issues.at(format!("objects[{n}].transform[{m}]"), problem);
+                }
+            }
+        }
+        if let Some(cell) = self.source.as_ref().and_then(|s| s.cell) {
+            if !inside(cell) {
+                issues.at("source.cell", format!("{cell:?} outside the {size} grid"));
+            }
+        }
+
+        // First path of each name, per kind (DFT and flux monitors share
+        // their output files)
+        let mut names: BTreeMap<(&str, String), String> = BTreeMap::new();
+        let mut unique = |issues: &mut Issues, kind: &'static str, name: &str, path: String| {
+            let key = (kind, name.to_string());
+            if let Some(first) = names.get(&key) {
+                issues.at(path, format!("{name:?} is already used by {first}"));
+            } else {
+                names.insert(key, path);
+            }
+        };
+        for (n, probe) in self.probes.iter().enumerate() {
+            let path = format!("probes[{n}]");
+            unique(&mut issues, "probe", &probe.name, path.clone());
+            if probe.cell.is_some() == probe.point.is_some() {
+                issues.at(path, "give one of cell and point");
+            }
+        }
+        for (n, monitor) in self.monitors.iter().enumerate() {
+            let path = format!("monitors[{n}]");
+            unique(&mut issues, "monitor", &monitor.name, path.clone());
+            if monitor.line.is_some() == monitor.plane.is_some() {
+                issues.at(path, "give one of line and plane");
+            }
+        }
+        for (n, monitor) in self.dft_monitors.iter().enumerate() {
+            let path = format!("dft_monitors[{n}]");
+            unique(&mut issues, "dft", &monitor.name, path.clone());
+            let given = [
+                monitor.point.is_some(),
+                monitor.plane.is_some(),
+                monitor.cells.is_some(),
+            ];
+            if given.iter().filter(|&&g| g).count() != 1 {
+                issues.at(path, "give one of point, plane and box");
+            }
+        }
+        for (n, monitor) in self.flux_monitors.iter().enumerate() {
+            unique(
+                &mut issues,
+                "dft",
+                &monitor.name,
+                format!("flux_monitors[{n}]"),
+            );
+        }
+        for (n, port) in self.ports.iter().enumerate() {
+            unique(&mut issues, "port", &port.name, format!("ports[{n}]"));
+        }
+
+        if let Some(sweep) = &self.sweep {
+            let declared = |name: &str| self.parameters.contains_key(name);
+            if !sweep.values.is_empty() && !sweep.cases.is_empty() {
+                issues.at("sweep", "either values or cases, not both");
+            }
+            for (name, values) in &sweep.values {
+                let path = format!("sweep.values.{name}");
+                if !declared(name) {
+                    issues.at(&path, "not one of the [parameters]");
+                }
+                match values {
+                    ValuesSpec::List(list) if list.is_empty() => issues.at(path, "no values"),
+                    ValuesSpec::Range { count: 0, .. } => issues.at(path, "count must be ≥ 1"),
+                    _ => {}
+                }
+            }
+            for (n, case) in sweep.cases.iter().enumerate() {
+                for name in case.keys().filter(|name| !declared(name)) {
+                    issues.at(
+                        format!("sweep.cases[{n}].{name}"),
+                        "not one of the [parameters]",
+                    );
+                }
+            }
+        }
+
+        if issues.0.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError(issues.0.join("\n")))
+        }
+    }
+
+    /// The builder of the described run, unvalidated.
+    pub fn builder(self) -> Result<SimulationBuilder, ConfigError> {
+        let fail = |message: String| Err(ConfigError(message));
+        let g = &self.grid;
+        let [dx, dy, dz] = match g.spacing {
+            Spacing::Cubic(d) => [d; 3],
+            Spacing::PerAxis(d) => d,
+        };
+        let dt = match (g.dt, g.courant) {
+            (Some(dt), None) => dt,
+            (None, Some(courant)) => courant * dx / C0,
+            _ => return fail("grid: give exactly one of dt and courant".into()),
+        };
+        let grid = Grid {
+            dx,
+            dy,
+            dz,
+            origin: g.origin.unwrap_or([0.0; 3]),
+            ..Grid::uniform(g.cells, dx, dt)
+        };
+        let mut builder = SimulationBuilder::new().grid(grid).steps(self.steps);
+
+        if let Some(boundaries) = self.boundaries {
+            for (axis, name) in [AxisName::X, AxisName::Y, AxisName::Z]
+                .iter()
+                .zip(boundaries)
+            {
+                let boundary = match name {
+                    BoundaryName::Pec => Boundary::Pec,
+                    BoundaryName::Periodic => Boundary::Periodic,
+                };
+                builder = builder.boundary(axis.axis(), boundary);
+            }
+        }
+        for (n, object) in self.objects.into_iter().enumerate() {
+            let material = match (
+                object.material.as_str(),
+                self.materials.get(&object.material),
+            ) {
+                (_, Some(spec)) => spec.material(),
+                ("vacuum", None) => Material::VACUUM,
+                (name, None) => {
+                    return fail(format!("objects[{n}].material: unknown material {name:?}"))
+                }
+            };
+            if object.shape.empty() {
+                return fail(format!("objects[{n}].shape: the shape is empty"));
+            }
+            let steps = object.transform.iter().enumerate();
+            if let Some((m, problem)) = steps.filter_map(|(m, t)| Some((m, t.problem()?))).next() {
+                return fail(format!("objects[{n}].transform[{m}]: {problem}"));
+            }
+            builder = builder.material(object.shape(), material);
+        }
+        if let Some(source) = self.source {
+            let centre = g.cells.map(|n| n / 2);
+            let waveform = source.waveform.map_or(
+                Waveform::Gaussian {
+                    width: 20.0,
+                    delay: 40.0,
+                },
+                WaveformSpec::waveform,
+            );
+            builder = builder.source(source.cell.unwrap_or(centre), waveform);
+        }
+        for (n, probe) in self.probes.into_iter().enumerate() {
+            let at = match (probe.cell, probe.point) {
+                (Some(cell), None) => Location::Cell(cell),
+                (None, Some(point)) => Location::Point(point),
+                _ => return fail(format!("probes[{n}]: give one of cell and point")),
+            };
+            builder = builder.probe(Probe {
+                name: leak(probe.name),
+                at,
+                quantity: probe.quantity.unwrap_or(QuantityName::Ez).quantity(),
+            });
+        }
+        for (n, monitor) in self.monitors.into_iter().enumerate() {
+            let span = match (monitor.line, monitor.plane) {
+                (Some(line), None) => Span::Line {
+                    axis: line.axis.axis(),
+                    through: line.through,
+                },
+                (None, Some(plane)) => Span::Plane {
+                    normal: plane.normal.axis(),
+                    index: plane.index,
+                },
+                _ => return fail(format!("monitors[{n}]: give one of line and plane")),
+            };
+            builder = builder.monitor(Monitor {
+                name: leak(monitor.name),
+                field: monitor.field.field(),
+                span,
+                every: monitor.every.unwrap_or(1),
+                batch: monitor.batch.unwrap_or(1),
+            });
+        }
+        for (n, monitor) in self.dft_monitors.into_iter().enumerate() {
+            let region = match (monitor.point, monitor.plane, monitor.cells) {
+                (Some(cell), None, None) => Region::Point(cell),
+                (None, Some(plane), None) => Region::Plane {
+                    normal: plane.normal.axis(),
+                    index: plane.index,
+                },
+                (None, None, Some(cells)) => Region::Box {
+                    lo: cells.lo,
+                    hi: cells.hi,
+                },
+                _ => {
+                    return fail(format!(
+                        "dft_monitors[{n}]: give one of point, plane and box"
+                    ))
+                }
+            };
+            let fields = monitor.fields.iter().map(|f| f.field()).collect::<Vec<_>>();
+            builder = builder.dft_monitor(DftMonitor {
+                name: leak(monitor.name),
+                fields: fields.leak(),
+                region,
+                frequencies: monitor.frequencies.leak(),
+            });
+        }
+        for monitor in self.flux_monitors {
+            builder = builder.flux_monitor(FluxMonitor {
+                name: leak(monitor.name),
+                normal: monitor.normal.axis(),
+                index: monitor.index,
+                u: monitor.u,
+                v: monitor.v,
+                frequencies: monitor.frequencies.leak(),
+            });
+        }
+        for element in self.lumped {
+            builder = builder.lumped(LumpedElement {
+                axis: element.axis.axis(),
+                cell: element.cell,
+                kind: element.element.kind(),
+            });
+        }
+        for port in self.ports {
+            builder = builder.port(FeedPort {
+                name: leak(port.name),
+                axis: port.axis.axis(),
+                cell: port.cell,
+                z0: port.z0,
+                f_max: port.f_max,
+            });
+        }
+        if let Some(output) = self.output {
+            if let Some(dir) = output.dir {
+                builder = builder.output_dir(leak(dir));
+            }
+            let probes = output.probes.map(|spec| ProbeOutput {
+                dir: leak(spec.dir),
+                format: match spec.format {
+                    FormatName::Csv => ProbeFormat::Csv,
+                    FormatName::JsonLines => ProbeFormat::JsonLines,
+                },
+            });
+            let mat = output.mat.map(leak);
+            builder = builder.configure(|config| {
+                config.probe_output = probes;
+                if let Some(steps) = output.probe_batch {
+                    config.probe_batch = steps;
+                }
+                config.energy_every = output.energy_every;
+                config.mat_file = mat;
+            });
+        }
+        Ok(builder)
+    }
+}
+
+/// Read, check, map and validate the description at `path`: JSON for
+/// `.json` files, a gprMax model for `.in` files (see [`crate::gprmax`]),
+/// TOML otherwise, with `parameters` overriding the file's before its
+/// script runs.
+pub fn load(path: &Path, parameters: &[(String, Parameter)]) -> Result<Config, ConfigError> {
+    if path.extension().is_some_and(|e| e == "in") {
+        return GprMaxModel::parse(&read_text(path)?)?.config();
+    }
+    let mut scene = read(path)?;
+    scene.parameters.extend(parameters.iter().cloned());
+    if let Some(script) = &scene.script {
+        let script = path.parent().unwrap_or(Path::new(".")).join(script);
+        let source = fs::read_to_string(&script)
+            .map_err(|e| ConfigError(format!("cannot read {}: {e}", script.display())))?;
+        scene.apply_script(&source)?;
+    }
+    scene.check()?;
+    scene.builder()?.build_config()
+}
+
+/// Check, map and validate the TOML description `text`, which has no
+/// file for a script to be found next to, as the binary's built-in scene.
+pub fn load_toml(text: &str) -> Result<Config, ConfigError> {
+    let scene = SceneFile::from_toml(text)?;
+    if scene.script.is_some() {
+        let message = "script: a description without a file runs no script";
+        return Err(ConfigError(message.into()));
+    }
+    scene.check()?;
+    scene.builder()?.build_config()
+}
+
+/// Parse the description at `path`, JSON for `.json` files and TOML
+/// otherwise, without running its script or checking it.
+pub fn read(path: &Path) -> Result<SceneFile, ConfigError> {
+    let text = read_text(path)?;
+    if path.extension().is_some_and(|e| e == "json") {
+        SceneFile::from_json(&text)
+    } else {
+        SceneFile::from_toml(&text)
+    }
+}
+
+fn read_text(path: &Path) -> Result<String, ConfigError> {
+    fs::read_to_string(path)
+        .map_err(|e| ConfigError(format!("cannot read {}: {e}", path.display())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::{Axis, Field};
+
+    const SCENE: &str = r#"
+        steps = 50
+        boundaries = ["pec", "pec", "periodic"]
+
+        [grid]
+        cells = [32, 24, 16]
+        spacing = [1e-3, 1e-3, 2e-3]
+        dt = 1e-12
+
+        [materials.glass]
+        eps_r = 4.0
+
+        [[objects]]
+        material = "glass"
+        shape = { kind = "sphere", center = [0.016, 0.012, 0.016], radius = 0.004 }
+        transform = [{ kind = "translate", offset = [0.001, 0.0, 0.0] }]
+
+        [source]
+        cell = [8, 12, 8]
+        waveform = { kind = "ricker", freq = 1e10, delay = 30.0 }
+
+        [[probes]]
+        name = "near"
+        point = [0.010, 0.012, 0.016]
+        quantity = "abs_e"
+
+        [[lumped]]
+        axis = "z"
+        cell = [20, 12, 8]
+        element = { kind = "resistor", r = 50.0 }
+
+        [[dft_monitors]]
+        name = "mid"
+        fields = ["ez", "hx"]
+        plane = { normal = "z", index = 8 }
+        frequencies = [5e9]
+
+        [output]
+        dir = "out"
+        probe_batch = 4
+    "#;
+
+    fn problems(text: &str) -> String {
+        SceneFile::from_toml(text)
+            .and_then(|scene| scene.check())
+            .expect_err("the description is invalid")
+            .0
+    }
+
+    #[test]
+    fn the_built_in_scene_loads() {
+        let c = load_toml(include_str!("../scenes/default.toml")).unwrap();
+        assert_eq!([c.grid.nx, c.grid.ny, c.grid.nz], [64; 3]);
+        assert_eq!(c.steps, 300);
+        assert!((c.grid.dt - 0.5e-3 / C0).abs() < 1e-24);
+        assert_eq!(c.source, [32; 3]);
+        assert_eq!(c.probes[0].at, Location::Cell([42, 32, 32]));
+        assert_eq!(c.probe_output.map(|p| p.dir), Some("probes"));
+    }
+
+    #[test]
+    fn a_description_maps_onto_the_config() {
+        let c = load_toml(SCENE).unwrap();
+        assert_eq!([c.grid.nx, c.grid.ny, c.grid.nz], [32, 24, 16]);
+        assert_eq!([c.grid.dx, c.grid.dz, c.grid.dt], [1e-3, 2e-3, 1e-12]);
+        assert_eq!(c.periodic_axes(), [Axis::Z]);
+        assert_eq!(c.objects.len(), 1);
+        assert_eq!(c.objects[0].material.eps_r, 4.0);
+        assert_eq!(c.source, [8, 12, 8]);
+        assert!(matches!(c.waveform, Waveform::Ricker { freq, .. } if freq == 1e10));
+        assert_eq!(c.probes[0].quantity, Quantity::AbsE);
+        assert!(matches!(c.lumped[0].kind, LumpedKind::Resistor { r } if r == 50.0));
+        assert_eq!(
+            c.dft_monitors[0].fields,
+            [Field::E(Axis::Z), Field::H(Axis::X)]
+        );
+        assert_eq!((c.monitor_dir, c.probe_batch), ("out", 4));
+    }
+
+    #[test]
+    fn json_describes_the_same_run() {
+        let toml: toml::Value = toml::from_str(SCENE).unwrap();
+        let json = serde_json::to_string(&toml).unwrap();
+        let from_json = SceneFile::from_json(&json).unwrap().builder().unwrap();
+        let from_toml = SceneFile::from_toml(SCENE).unwrap().builder().unwrap();
+        assert_eq!(
+            from_json.build_config().unwrap().to_json(),
+            from_toml.build_config().unwrap().to_json()
+        );
+    }
+
+    #[test]
+    fn type_errors_name_their_path() {
+        let text = SCENE.replace("radius = 0.004", "radius = \"big\"");
+        let error = SceneFile::from_toml(&text).unwrap_err().0;
+        assert!(error.starts_with("objects[0].shape"), "{error}");
+        let text = SCENE.replace("[output]", "[output]\ncolour = \"red\"");
+        let error = SceneFile::from_toml(&text).unwrap_err().0;
+        assert!(error.contains("colour"), "{error}");
+    }
+
+    #[test]
+    fn every_problem_is_reported_at_its_path() {
+        let text = SCENE
+            .replace("material = \"glass\"", "material = \"glas\"")
+            .replace("cell = [8, 12, 8]", "cell = [8, 12, 16]")
+            .replace("dt = 1e-12", "dt = 1e-12\ncourant = 0.5")
+            .replace(
+                "[[lumped]]",
+                "[[probes]]\nname = \"near\"\ncell = [1, 1, 1]\n\n[[lumped]]",
+            );
+        let found = problems(&text);
+        let lines: Vec<&str> = found.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "grid: give exactly one of dt and courant",
+                "objects[0].material: unknown material \"glas\"",
+                "source.cell: [8, 12, 16] outside the 32×24×16 grid",
+                "probes[1]: \"near\" is already used by probes[0]",
+            ]
+        );
+    }
+
+    #[test]
+    fn sweeps_vary_declared_parameters() {
+        let text =
+            format!("{SCENE}\n[parameters]\nradius = 1e-3\n\n[sweep.values]\nsize = [1, 2]\n");
+        assert_eq!(
+            problems(&text),
+            "sweep.values.size: not one of the [parameters]"
+        );
+        let text = text.replace("size = [1, 2]", "radius = []");
+        assert_eq!(problems(&text), "sweep.values.radius: no values");
+    }
+}
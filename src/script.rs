@@ -0,0 +1,206 @@
+//! Setup-time scripts of scene files.
+//!
+//! A scene file may name a [Rhai](https://rhai.rs) script that places
+//! materials, objects and the source with loops and arithmetic instead of
+//! listing them, e.g. a row of spheres whose count and radius come from the
+//! file's `[parameters]` (or from `--set radius=2e-3` on the command line):
+//!
+//! ```text
+//! material("glass", #{ eps_r: 4.0 });
+//! for i in 0..count {
+//!     sphere("glass", [0.008 + i * pitch, 0.032, 0.032], radius);
+//! }
+//! source([8, ny / 2, nz / 2], #{ kind: "gaussian", width: 20.0, delay: 40.0 });
+//! ```
+//!
+//! The script sees the grid as `nx`, `ny`, `nz` (cells) and `dx`, `dy`, `dz`
+//! (m) and every parameter as a constant (an integer when written as one),
+//! and has
+//!
+//! - `material(name, #{ eps_r, sigma, mu_r, sigma_m })`;
+//! - `object(material, shape)` with a shape map as in the scene file
+//!   (`#{ kind: "box", min: [..], max: [..] }` …), or `object(material,
+//!   shape, transform)` with an array of transform maps (`#{ kind:
+//!   "rotate", axis: [0, 0, 1], degrees: 45 }` …), and the shorthands
+//!   `block(material, min, max)`, `sphere(material, center, radius)` and
+//!   `cylinder(material, center, radius, height, axis)`;
+//! - `source(cell, waveform)` with a waveform map as in the scene file.
+//!
+//! Objects are painted after those listed in the file, in call order.
+//! Maps go through the same definitions as the file, so integers stand in
+//! for reals and the checks of [`crate::scene_file`] apply to the result.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Map, Scope, FLOAT, INT};
+use serde::de::DeserializeOwned;
+
+use crate::config::ConfigError;
+use crate::scene_file::{
+    GridSpec, MaterialSpec, ObjectSpec, Parameter, ShapeSpec, SourceSpec, Spacing,
+};
+
+/// What a script placed, in call order.
+#[derive(Clone, Debug, Default)]
+pub struct Placement {
+    pub materials: Vec<(String, MaterialSpec)>,
+    pub objects: Vec<ObjectSpec>,
+    pub source: Option<SourceSpec>,
+}
+
+type Fallible<T> = Result<T, Box<EvalAltResult>>;
+
+/// Integers as reals, recursively, so that `[0, 1, 2]` reads as a point.
+fn reals(value: Dynamic) -> Dynamic {
+    if let Some(n) = value.clone().try_cast::<INT>() {
+        return Dynamic::from_float(n as FLOAT);
+    }
+    if value.is_array() {
+        let items: Array = value.cast::<Array>().into_iter().map(reals).collect();
+        return items.into();
+    }
+    if value.is_map() {
+        let map = value.cast::<Map>();
+        let map: Map = map.into_iter().map(|(k, v)| (k, reals(v))).collect();
+        return map.into();
+    }
+    value
+}
+
+/// A scene-file definition from a script value.
+fn decode<T: DeserializeOwned>(value: Dynamic, what: &str) -> Fallible<T> {
+    rhai::serde::from_dynamic(&value).map_err(|e| format!("bad {what}: {e}").into())
+}
+
+/// Reals except where the definition wants cells.
+fn decode_real<T: DeserializeOwned>(value: Dynamic, what: &str) -> Fallible<T> {
+    decode(reals(value), what)
+}
+
+/// Run `source` against the grid and parameters of a scene file.
+pub fn evaluate(
+    source: &str,
+    grid: &GridSpec,
+    parameters: &BTreeMap<String, Parameter>,
+) -> Result<Placement, ConfigError> {
+    let placed = Rc::new(RefCell::new(Placement::default()));
+    let mut engine = Engine::new();
+    engine.set_max_operations(100_000_000);
+
+    let out = placed.clone();
+    engine.register_fn(
+        "material",
+        move |name: &str, properties: Map| -> Fallible<()> {
+            let spec = decode_real(properties.into(), "material")?;
+            out.borrow_mut().materials.push((name.to_string(), spec));
+            Ok(())
+        },
+    );
+    let out = placed.clone();
+    let place = move |material: &str, shape: Dynamic, transform: Dynamic| -> Fallible<()> {
+        let shape: ShapeSpec = decode_real(shape, "shape")?;
+        let transform = decode_real(transform, "transform")?;
+        out.borrow_mut().objects.push(ObjectSpec {
+            material: material.to_string(),
+            shape,
+            transform,
+        });
+        Ok(())
+    };
+    let shape = |pairs: Vec<(&str, Dynamic)>| -> Dynamic {
+        let map: Map = pairs.into_iter().map(|(k, v)| (k.into(), v)).collect();
+        map.into()
+    };
+    let object = place.clone();
+    engine.register_fn("object", move |material: &str, shape: Map| {
+        object(material, shape.into(), Array::new().into())
+    });
+    let object = place.clone();
+    engine.register_fn(
+        "object",
+        move |material: &str, shape: Map, transform: Array| {
+            object(material, shape.into(), transform.into())
+        },
+    );
+    let object = place.clone();
+    engine.register_fn("block", move |material: &str, min: Array, max: Array| {
+        object(
+            material,
+            shape(vec![
+                ("kind", "box".into()),
+                ("min", min.into()),
+                ("max", max.into()),
+            ]),
+            Array::new().into(),
+        )
+    });
+    let object = place.clone();
+    engine.register_fn(
+        "sphere",
+        move |material: &str, center: Array, radius: Dynamic| {
+            object(
+                material,
+                shape(vec![
+                    ("kind", "sphere".into()),
+                    ("center", center.into()),
+                    ("radius", radius),
+                ]),
+                Array::new().into(),
+            )
+        },
+    );
+    let object = place;
+    engine.register_fn(
+        "cylinder",
+        move |material: &str, center: Array, radius: Dynamic, height: Dynamic, axis: &str| {
+            object(
+                material,
+                shape(vec![
+                    ("kind", "cylinder".into()),
+                    ("center", center.into()),
+                    ("radius", radius),
+                    ("height", height),
+                    ("axis", axis.into()),
+                ]),
+                Array::new().into(),
+            )
+        },
+    );
+    let out = placed.clone();
+    engine.register_fn(
+        "source",
+        move |cell: Array, waveform: Map| -> Fallible<()> {
+            let cell = decode(cell.into(), "source cell")?;
+            let waveform = decode_real(waveform.into(), "waveform")?;
+            out.borrow_mut().source = Some(SourceSpec {
+                cell: Some(cell),
+                waveform: Some(waveform),
+            });
+            Ok(())
+        },
+    );
+
+    let mut scope = Scope::new();
+    let [dx, dy, dz] = match grid.spacing {
+        Spacing::Cubic(d) => [d; 3],
+        Spacing::PerAxis(d) => d,
+    };
+    for (name, n) in ["nx", "ny", "nz"].into_iter().zip(grid.cells) {
+        scope.push_constant(name, n as INT);
+    }
+    for (name, d) in ["dx", "dy", "dz"].into_iter().zip([dx, dy, dz]) {
+        scope.push_constant(name, d as FLOAT);
+    }
+    for (name, &value) in parameters {
+        match value {
+            Parameter::Integer(n) => scope.push_constant(name.as_str(), n as INT),
+            Parameter::Real(x) => scope.push_constant(name.as_str(), x as FLOAT),
+        };
+    }
+    engine
+        .run_with_scope(&mut scope, source)
+        .map_err(|e| ConfigError(format!("script: {e}")))?;
+    Ok(placed.take())
+}
@@ -0,0 +1,68 @@
+// ------------------------------------------------------------------
+// ade_edges.wgsl  –  Sparse auxiliary-current correction of E edges
+//
+// Runs after the E-update.  Each entry owns one E edge and one
+// auxiliary current J (auxiliary differential equation):
+//
+//   E^{n+1}   = E*  - CJ * J^{n-1/2} + DJ * drive[src]   (E* = CA/CB update)
+//   J^{n+1/2} = KJ * J^{n-1/2} + BJ * E^{n+1} + BP * E^n
+//
+// BP = BJ gives a Drude current, BP = -BJ a Debye polarisation current.
+//
+// state[n] = (J^{n-1/2}, E^n) is carried between steps.  drive[] holds
+// per-step scalars written by the host (lumped voltage sources).
+// ------------------------------------------------------------------
+
+struct AdeEdge {
+    cell: u32,
+    comp: u32,   // 0 = Ex, 1 = Ey, 2 = Ez
+    kj: f32,
+    bj: f32,
+    cj: f32,
+    src: u32,    // drive slot, 0xffffffff = none
+    dj: f32,
+    bp: f32,
+}
+
+@group(0) @binding(0) var<storage, read>       edges: array<AdeEdge>;
+@group(0) @binding(1) var<storage, read_write> state: array<vec2<f32>>;
+@group(0) @binding(2) var<storage, read_write> ex: array<f32>;
+@group(0) @binding(3) var<storage, read_write> ey: array<f32>;
+@group(0) @binding(4) var<storage, read_write> ez: array<f32>;
+@group(0) @binding(5) var<storage, read>       drive: array<f32>;
+
+fn load_e(comp: u32, id: u32) -> f32 {
+    switch comp {
+        case 0u: { return ex[id]; }
+        case 1u: { return ey[id]; }
+        default: { return ez[id]; }
+    }
+}
+
+fn store_e(comp: u32, id: u32, v: f32) {
+    switch comp {
+        case 0u: { ex[id] = v; }
+        case 1u: { ey[id] = v; }
+        default: { ez[id] = v; }
+    }
+}
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let n = gid.x;
+    if (n >= arrayLength(&edges)) {
+        return;
+    }
+
+    let e = edges[n];
+    let s = state[n];
+
+    var e_new = load_e(e.comp, e.cell) - e.cj * s.x;
+    if (e.src != 0xffffffffu) {
+        e_new += e.dj * drive[e.src];
+    }
+    let j_new = e.kj * s.x + e.bj * e_new + e.bp * s.y;
+
+    store_e(e.comp, e.cell, e_new);
+    state[n] = vec2<f32>(j_new, e_new);
+}
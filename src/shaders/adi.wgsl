@@ -0,0 +1,175 @@
+// ------------------------------------------------------------------
+// adi.wgsl  –  Tridiagonal line solve for one E component
+//
+// For E_c with implicit axis d, partner H_p (p the third axis) and sign
+// s (+1 for d = next(c), −1 for d = prev(c)):
+//
+//   E_c' = CA·E_c + CB·( s·∂_d H_p^θ − s·∂_p H_d )
+//   H_p' = CP·H_p + CQ·( s·∂_d E_c^θ − s·∂_c E_d )
+//
+// where F^θ = (1−θ)·F + θ·F'.  θ = 1 is an ADI half step (with half-step
+// coefficients), θ = ½ the Crank–Nicolson pair of the HIE update.
+// Substituting H_p' gives a tridiagonal system along d per line, solved by
+// the Thomas algorithm (one thread per line): `solve` writes E_c' into
+// e_new, then `update_h` advances H_p; the host copies e_new over E_c once
+// every component of the sweep is solved.
+// ------------------------------------------------------------------
+
+struct AdiParams {
+    nx: u32,
+    ny: u32,
+    nz: u32,
+    theta: f32,
+    c: u32,
+    d: u32,
+    p: u32,
+    s: f32,
+}
+
+@group(0) @binding(0) var<uniform> a: AdiParams;
+
+@group(0) @binding(1) var<storage, read>       e_c: array<f32>;
+@group(0) @binding(2) var<storage, read>       e_d: array<f32>;
+@group(0) @binding(3) var<storage, read_write> h_p: array<f32>;
+@group(0) @binding(4) var<storage, read>       h_d: array<f32>;
+@group(0) @binding(5) var<storage, read_write> e_new: array<f32>;
+@group(0) @binding(6) var<storage, read_write> scratch: array<f32>;
+
+// Half-step coefficients, four vec4 per cell: CA, CB, CP, CQ
+@group(0) @binding(7) var<storage, read>       coef: array<vec4<f32>>;
+
+struct Spacing {
+    inv_primary: array<vec4<f32>, 1024>,
+    inv_dual: array<vec4<f32>, 1024>,
+}
+
+@group(0) @binding(9) var<uniform> sp: Spacing;
+
+fn dims() -> vec3<u32> {
+    return vec3<u32>(a.nx, a.ny, a.nz);
+}
+
+fn idx(q: vec3<u32>) -> u32 {
+    return q.x + a.nx * (q.y + a.ny * q.z);
+}
+
+fn unit(axis: u32) -> vec3<u32> {
+    var u = vec3<u32>(0u);
+    u[axis] = 1u;
+    return u;
+}
+
+// H is advanced only one cell inside the upper boundary, as in update_h.
+fn h_active(q: vec3<u32>) -> bool {
+    return all(q + 1u < dims());
+}
+
+// (CP, CQ) of H_p at q; zero where H_p is frozen.
+fn h_coeffs(q: vec3<u32>) -> vec2<f32> {
+    if (!h_active(q)) {
+        return vec2<f32>(0.0);
+    }
+    let id = idx(q);
+    return vec2<f32>(coef[4u * id + 2u][a.p], coef[4u * id + 3u][a.p]);
+}
+
+// ∂_c E_d at H_p(q) (explicit, old E).
+fn x_term(q: vec3<u32>) -> f32 {
+    if (!h_active(q)) {
+        return 0.0;
+    }
+    return (e_d[idx(q + unit(a.c))] - e_d[idx(q)]) * sp.inv_primary[q[a.c]][a.c];
+}
+
+// ∂_d E_c at H_p(q) (old E).
+fn d_term(q: vec3<u32>) -> f32 {
+    if (!h_active(q)) {
+        return 0.0;
+    }
+    return (e_c[idx(q + unit(a.d))] - e_c[idx(q)]) * sp.inv_primary[q[a.d]][a.d];
+}
+
+// ∂_p H_d at E_c(q) (explicit, old H); needs q[p] ≥ 1.
+fn y_term(q: vec3<u32>) -> f32 {
+    return (h_d[idx(q)] - h_d[idx(q - unit(a.p))]) * sp.inv_dual[q[a.p]][a.p];
+}
+
+@compute @workgroup_size(8, 8)
+fn solve(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let n_dims = dims();
+    if (gid.x >= n_dims[a.c] || gid.y >= n_dims[a.p]) {
+        return;
+    }
+    let n_d = n_dims[a.d];
+    var q = vec3<u32>(0u);
+    q[a.c] = gid.x;
+    q[a.p] = gid.y;
+
+    // Lines on the lower faces stay fixed, as in update_e.
+    if (gid.x == 0u || gid.y == 0u) {
+        for (var n = 0u; n < n_d; n++) {
+            q[a.d] = n;
+            e_new[idx(q)] = e_c[idx(q)];
+        }
+        return;
+    }
+
+    // Row 0 is the fixed boundary value.
+    e_new[idx(q)] = e_c[idx(q)];
+    var c_prev = 0.0;
+    var d_prev = e_c[idx(q)];
+
+    // Forward sweep
+    for (var n = 1u; n < n_d; n++) {
+        q[a.d] = n;
+        var qm = q;
+        qm[a.d] = n - 1u;
+        let id = idx(q);
+        let idm = idx(qm);
+
+        let ca = coef[4u * id][a.c];
+        let cb = coef[4u * id + 1u][a.c];
+        let h_n = sp.inv_dual[n][a.d];
+        let g_n = sp.inv_primary[n][a.d];
+        let g_m = sp.inv_primary[n - 1u][a.d];
+        let hc = h_coeffs(q);
+        let hm = h_coeffs(qm);
+
+        let th = a.theta;
+        let k = cb * th * th * h_n;
+        let lower = -k * hm.y * g_m;
+        let upper = -k * hc.y * g_n;
+        let diag = 1.0 + k * (hc.y * g_n + hm.y * g_m);
+        let h_old = (1.0 - th) * (h_p[id] - h_p[idm]) + th * (hc.x * h_p[id] - hm.x * h_p[idm]);
+        let rhs = ca * e_c[id] - a.s * cb * y_term(q)
+            + a.s * cb * h_n * h_old
+            + cb * th * h_n * (hc.y * ((1.0 - th) * d_term(q) - x_term(q))
+                - hm.y * ((1.0 - th) * d_term(qm) - x_term(qm)));
+
+        let m = diag - lower * c_prev;
+        c_prev = upper / m;
+        d_prev = (rhs - lower * d_prev) / m;
+        scratch[id] = c_prev;
+        e_new[id] = d_prev;
+    }
+
+    // Back substitution
+    for (var t = 2u; t < n_d; t++) {
+        q[a.d] = n_d - t;
+        let id = idx(q);
+        e_new[id] = e_new[id] - scratch[id] * e_new[idx(q + unit(a.d))];
+    }
+}
+
+@compute @workgroup_size(4, 4, 4)
+fn update_h(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let q = gid;
+    if (!h_active(q)) {
+        return;
+    }
+    let id = idx(q);
+    let hc = h_coeffs(q);
+    let de_c = (e_new[idx(q + unit(a.d))] - e_new[id]) * sp.inv_primary[q[a.d]][a.d];
+    let de = (1.0 - a.theta) * d_term(q) + a.theta * de_c;
+    h_p[id] = hc.x * h_p[id] + hc.y * a.s * (de - x_term(q));
+}
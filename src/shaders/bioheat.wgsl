@@ -0,0 +1,70 @@
+// ------------------------------------------------------------------
+// bioheat.wgsl  –  Explicit step of the Pennes bioheat equation
+//
+// For the temperature rise θ over the basal state,
+//
+//   ρc ∂θ/∂t = ∇·(k∇θ) + Q − B·θ,
+//
+// forward in time and central in space on the cells of a box.  Faces
+// between two cells conduct with the harmonic mean of their k; faces to
+// cells without tissue (ρc = 0) and the box faces are insulated.  The
+// host packs per cell (Δt/ρc, k, B, Q), with Δt/ρc = 0 for cells that
+// stay at θ = 0.  θ ping-pongs between `t_in` and `t_out`.
+// ------------------------------------------------------------------
+
+struct HeatParams {
+    // box size in cells, -
+    size: vec4<u32>,
+    // 1/dx², 1/dy², 1/dz², -
+    inv_h2: vec4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> p: HeatParams;
+
+@group(0) @binding(1) var<storage, read>       t_in: array<f32>;
+@group(0) @binding(2) var<storage, read_write> t_out: array<f32>;
+@group(0) @binding(3) var<storage, read>       props: array<vec4<f32>>;
+
+fn idx(c: vec3<u32>) -> u32 {
+    return c.x + p.size.x * (c.y + p.size.y * c.z);
+}
+
+// Conducted power density from the neighbour at `c` into a cell of
+// conductivity `k` and rise `t`, over an axis with 1/h² `w`
+fn face(c: vec3<u32>, k: f32, t: f32, w: f32) -> f32 {
+    let id = idx(c);
+    let kn = props[id].y;
+    if (props[id].x == 0.0 || k + kn == 0.0) {
+        return 0.0;
+    }
+    return 2.0 * k * kn / (k + kn) * (t_in[id] - t) * w;
+}
+
+@compute @workgroup_size(4, 4, 4)
+fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let n = p.size.xyz;
+    if (any(gid >= n)) {
+        return;
+    }
+    let id = idx(gid);
+    let q = props[id];
+    let t = t_in[id];
+    if (q.x == 0.0) {
+        t_out[id] = 0.0;
+        return;
+    }
+    var flux = 0.0;
+    for (var a = 0u; a < 3u; a++) {
+        if (gid[a] > 0u) {
+            var c = gid;
+            c[a] -= 1u;
+            flux += face(c, q.y, t, p.inv_h2[a]);
+        }
+        if (gid[a] + 1u < n[a]) {
+            var c = gid;
+            c[a] += 1u;
+            flux += face(c, q.y, t, p.inv_h2[a]);
+        }
+    }
+    t_out[id] = t + q.x * (flux + q.w - q.z * t);
+}
@@ -0,0 +1,142 @@
+// ------------------------------------------------------------------
+// bloch.wgsl  –  Complex 3D Yee update with Bloch-periodic boundaries
+//
+// Real and imaginary parts of E and H are stored as vec4 (x, y, z, -)
+// per cell and updated with the same real coefficients as update_e.wgsl
+// and update_h.wgsl.  Across a periodic axis of length L the fields obey
+//
+//   F(r + L) = e^{jkL} F(r),
+//
+// so the H update of the last cell reads E(0)·e^{jkL} and the E update
+// of index 0 reads H(N−1)·e^{−jkL}.  Non-periodic axes keep the PEC
+// walls of the main solver.  `inject` adds the drive value to the real
+// part of the source components after the E update (soft sources).
+// ------------------------------------------------------------------
+
+struct Params {
+    // nx, ny, nz, periodic bits (x = 1, y = 2, z = 4)
+    size: vec4<u32>,
+    // 1/dx, 1/dy, 1/dz, source drive value
+    inv_d: vec4<f32>,
+    // cos(kL) and sin(kL) per axis
+    phase_cos: vec4<f32>,
+    phase_sin: vec4<f32>,
+    // (cell, E component, active, -) of up to four sources
+    sources: array<vec4<u32>, 4>,
+}
+
+@group(0) @binding(0) var<uniform> p: Params;
+
+@group(0) @binding(1) var<storage, read_write> e_re: array<vec4<f32>>;
+@group(0) @binding(2) var<storage, read_write> e_im: array<vec4<f32>>;
+@group(0) @binding(3) var<storage, read_write> h_re: array<vec4<f32>>;
+@group(0) @binding(4) var<storage, read_write> h_im: array<vec4<f32>>;
+
+// Dense material coefficients (x/y/z lanes per field component)
+@group(0) @binding(5) var<storage, read> ca: array<vec4<f32>>;
+@group(0) @binding(6) var<storage, read> cb: array<vec4<f32>>;
+@group(0) @binding(7) var<storage, read> cp: array<vec4<f32>>;
+@group(0) @binding(8) var<storage, read> cq: array<vec4<f32>>;
+
+struct Complex {
+    re: vec4<f32>,
+    im: vec4<f32>,
+}
+
+fn idx(c: vec3<u32>) -> u32 {
+    return c.x + p.size.x * (c.y + p.size.y * c.z);
+}
+
+fn periodic() -> vec3<bool> {
+    return (vec3<u32>(p.size.w) & vec3<u32>(1u, 2u, 4u)) != vec3<u32>(0u);
+}
+
+// f·e^{±jkL} along `axis`
+fn rotate(f: Complex, axis: u32, sign: f32) -> Complex {
+    let c = p.phase_cos[axis];
+    let s = sign * p.phase_sin[axis];
+    return Complex(f.re * c - f.im * s, f.re * s + f.im * c);
+}
+
+// E of the upper neighbour along `axis`, wrapped with e^{jkL}
+fn e_upper(c: vec3<u32>, axis: u32, wrap: bool) -> Complex {
+    var q = c;
+    q[axis] = select(c[axis] + 1u, 0u, wrap);
+    let id = idx(q);
+    let f = Complex(e_re[id], e_im[id]);
+    if (wrap) {
+        return rotate(f, axis, 1.0);
+    }
+    return f;
+}
+
+// H of the lower neighbour along `axis`, wrapped with e^{−jkL}
+fn h_lower(c: vec3<u32>, axis: u32, wrap: bool) -> Complex {
+    var q = c;
+    q[axis] = select(c[axis] - 1u, p.size[axis] - 1u, wrap);
+    let id = idx(q);
+    let f = Complex(h_re[id], h_im[id]);
+    if (wrap) {
+        return rotate(f, axis, -1.0);
+    }
+    return f;
+}
+
+// (dEy/dz − dEz/dy, dEz/dx − dEx/dz, dEx/dy − dEy/dx) of one part
+fn curl_e(dx: vec4<f32>, dy: vec4<f32>, dz: vec4<f32>) -> vec4<f32> {
+    return vec4<f32>(dz.y - dy.z, dx.z - dz.x, dy.x - dx.y, 0.0);
+}
+
+@compute @workgroup_size(4, 4, 4)
+fn update_h(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let n = p.size.xyz;
+    if (any(gid >= n)) {
+        return;
+    }
+    let last = gid == n - vec3<u32>(1u);
+    if (any(last & !periodic())) {
+        return;
+    }
+    let id = idx(gid);
+    let e = Complex(e_re[id], e_im[id]);
+    let ux = e_upper(gid, 0u, last.x);
+    let uy = e_upper(gid, 1u, last.y);
+    let uz = e_upper(gid, 2u, last.z);
+    let d = p.inv_d;
+    let curl_re = curl_e((ux.re - e.re) * d.x, (uy.re - e.re) * d.y, (uz.re - e.re) * d.z);
+    let curl_im = curl_e((ux.im - e.im) * d.x, (uy.im - e.im) * d.y, (uz.im - e.im) * d.z);
+    h_re[id] = cp[id] * h_re[id] + cq[id] * curl_re;
+    h_im[id] = cp[id] * h_im[id] + cq[id] * curl_im;
+}
+
+@compute @workgroup_size(4, 4, 4)
+fn update_e(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let n = p.size.xyz;
+    if (any(gid >= n)) {
+        return;
+    }
+    let first = gid == vec3<u32>(0u);
+    if (any(first & !periodic())) {
+        return;
+    }
+    let id = idx(gid);
+    let h = Complex(h_re[id], h_im[id]);
+    let lx = h_lower(gid, 0u, first.x);
+    let ly = h_lower(gid, 1u, first.y);
+    let lz = h_lower(gid, 2u, first.z);
+    let d = p.inv_d;
+    // curl H = −curl_e of the backward differences
+    let curl_re = curl_e((h.re - lx.re) * d.x, (h.re - ly.re) * d.y, (h.re - lz.re) * d.z);
+    let curl_im = curl_e((h.im - lx.im) * d.x, (h.im - ly.im) * d.y, (h.im - lz.im) * d.z);
+    e_re[id] = ca[id] * e_re[id] - cb[id] * curl_re;
+    e_im[id] = ca[id] * e_im[id] - cb[id] * curl_im;
+}
+
+@compute @workgroup_size(4)
+fn inject(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let s = p.sources[gid.x];
+    if (s.z == 0u) {
+        return;
+    }
+    e_re[s.x][s.y] += p.inv_d.w;
+}
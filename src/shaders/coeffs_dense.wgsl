@@ -0,0 +1,13 @@
+// ------------------------------------------------------------------
+// coeffs_dense.wgsl  –  Coefficient fetch, one vec4 pair per cell
+//
+// Prepended to update_e.wgsl (CA, CB) or update_h.wgsl (CP, CQ).
+// ------------------------------------------------------------------
+
+// Material coefficients (x/y/z lanes per field component)
+@group(0) @binding(7) var<storage, read>       coef_a: array<vec4<f32>>;
+@group(0) @binding(8) var<storage, read>       coef_b: array<vec4<f32>>;
+
+fn load_coeffs(id: u32) -> array<vec4<f32>, 2> {
+    return array<vec4<f32>, 2>(coef_a[id], coef_b[id]);
+}
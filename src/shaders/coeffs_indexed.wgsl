@@ -0,0 +1,19 @@
+// ------------------------------------------------------------------
+// coeffs_indexed.wgsl  –  Coefficient fetch through a material index
+//
+// Prepended to update_e.wgsl (CA, CB) or update_h.wgsl (CP, CQ).
+// Each cell stores an `index_bits`-wide material index, packed into
+// u32 words; entry m of the table is the pair lut[2m], lut[2m + 1].
+// ------------------------------------------------------------------
+
+override index_bits: u32 = 8u;
+
+@group(0) @binding(7) var<storage, read>       material: array<u32>;
+@group(0) @binding(8) var<storage, read>       lut: array<vec4<f32>>;
+
+fn load_coeffs(id: u32) -> array<vec4<f32>, 2> {
+    let per_word = 32u / index_bits;
+    let word = material[id / per_word];
+    let m = (word >> ((id % per_word) * index_bits)) & ((1u << index_bits) - 1u);
+    return array<vec4<f32>, 2>(lut[2u * m], lut[2u * m + 1u]);
+}
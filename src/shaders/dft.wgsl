@@ -0,0 +1,41 @@
+// ------------------------------------------------------------------
+// dft.wgsl  –  Running DFT of one component over a box of cells
+//
+// Each step adds field · Δt·exp(−jωt) to the complex accumulator of every
+// cell and frequency; the host writes the per-step factors (E factors,
+// then H factors at the half-step-earlier H time) before dispatch.
+// Accumulators are frequency-major: acc[f·count + t].
+// ------------------------------------------------------------------
+
+struct DftParams {
+    count: u32,
+    nf: u32,
+    base: u32,
+    sx: u32,
+    sy: u32,
+    nx: u32,
+    ny: u32,
+    // 0 for E components, nf for H (offset into the factors)
+    phase: u32,
+}
+
+@group(0) @binding(0) var<uniform> p: DftParams;
+
+@group(0) @binding(1) var<storage, read>       field: array<f32>;
+@group(0) @binding(2) var<storage, read>       factors: array<vec2<f32>>;
+@group(0) @binding(3) var<storage, read_write> acc: array<vec2<f32>>;
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let t = gid.x;
+    if (t >= p.count) {
+        return;
+    }
+    let a = t % p.sx;
+    let b = (t / p.sx) % p.sy;
+    let c = t / (p.sx * p.sy);
+    let v = field[p.base + a + p.nx * (b + p.ny * c)];
+    for (var f = 0u; f < p.nf; f++) {
+        acc[f * p.count + t] += v * factors[p.phase + f];
+    }
+}
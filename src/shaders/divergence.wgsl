@@ -0,0 +1,65 @@
+// ------------------------------------------------------------------
+// divergence.wgsl  –  Largest |E| component by parallel reduction
+//
+// Thread t takes the largest of |Ex|, |Ey|, |Ez| at cell t as the bits
+// of its magnitude, which order like the magnitudes themselves and put
+// Inf and NaN above every finite value, with its edge 3·t + lane; each
+// workgroup writes the largest of its threads and the host takes the
+// largest partial.  The dispatch is 2D-folded past 65535 workgroups.
+// ------------------------------------------------------------------
+
+struct DivergenceParams {
+    count: u32,
+    _pad0: u32,
+    _pad1: u32,
+    _pad2: u32,
+}
+
+@group(0) @binding(0) var<uniform> p: DivergenceParams;
+
+@group(0) @binding(1) var<storage, read>       ex: array<f32>;
+@group(0) @binding(2) var<storage, read>       ey: array<f32>;
+@group(0) @binding(3) var<storage, read>       ez: array<f32>;
+
+// (magnitude bits, edge) per workgroup
+@group(0) @binding(4) var<storage, read_write> partials: array<vec2<u32>>;
+
+var<workgroup> peaks: array<vec2<u32>, 64>;
+
+fn larger(a: vec2<u32>, b: vec2<u32>) -> vec2<u32> {
+    if (b.x > a.x) {
+        return b;
+    }
+    return a;
+}
+
+fn magnitude(v: f32, edge: u32) -> vec2<u32> {
+    return vec2<u32>(bitcast<u32>(v) & 0x7fffffffu, edge);
+}
+
+@compute @workgroup_size(64)
+fn main(
+    @builtin(local_invocation_index) lid: u32,
+    @builtin(workgroup_id) wid: vec3<u32>,
+    @builtin(num_workgroups) groups: vec3<u32>,
+) {
+    let g = wid.x + wid.y * groups.x;
+    let t = g * 64u + lid;
+    var m = vec2<u32>(0u, 0u);
+    if (t < p.count) {
+        m = magnitude(ex[t], 3u * t);
+        m = larger(m, magnitude(ey[t], 3u * t + 1u));
+        m = larger(m, magnitude(ez[t], 3u * t + 2u));
+    }
+    peaks[lid] = m;
+    workgroupBarrier();
+    for (var half = 32u; half > 0u; half >>= 1u) {
+        if (lid < half) {
+            peaks[lid] = larger(peaks[lid], peaks[lid + half]);
+        }
+        workgroupBarrier();
+    }
+    if (lid == 0u) {
+        partials[g] = peaks[0];
+    }
+}
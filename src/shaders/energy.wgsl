@@ -0,0 +1,60 @@
+// ------------------------------------------------------------------
+// energy.wgsl  –  Total electromagnetic energy by parallel reduction
+//
+// Thread t adds the energy of cell t's six Yee samples,
+//
+//   w_E · (Ex², Ey², Ez²) + w_H · (Hx², Hy², Hz²),
+//
+// with per-edge weights ε·dV/2 and μ·dV/2 from the host, and each
+// workgroup writes its partial sum; the host adds the partials.  The
+// dispatch is 2D-folded past 65535 workgroups.
+// ------------------------------------------------------------------
+
+struct EnergyParams {
+    count: u32,
+    _pad0: u32,
+    _pad1: u32,
+    _pad2: u32,
+}
+
+@group(0) @binding(0) var<uniform> p: EnergyParams;
+
+@group(0) @binding(1) var<storage, read>       ex: array<f32>;
+@group(0) @binding(2) var<storage, read>       ey: array<f32>;
+@group(0) @binding(3) var<storage, read>       ez: array<f32>;
+@group(0) @binding(4) var<storage, read>       hx: array<f32>;
+@group(0) @binding(5) var<storage, read>       hy: array<f32>;
+@group(0) @binding(6) var<storage, read>       hz: array<f32>;
+
+// Two per cell: E weights, then H weights (x, y, z lanes)
+@group(0) @binding(7) var<storage, read>       weights: array<vec4<f32>>;
+@group(0) @binding(8) var<storage, read_write> partials: array<f32>;
+
+var<workgroup> sums: array<f32, 64>;
+
+@compute @workgroup_size(64)
+fn main(
+    @builtin(local_invocation_index) lid: u32,
+    @builtin(workgroup_id) wid: vec3<u32>,
+    @builtin(num_workgroups) groups: vec3<u32>,
+) {
+    let g = wid.x + wid.y * groups.x;
+    let t = g * 64u + lid;
+    var s = 0.0;
+    if (t < p.count) {
+        let e = vec3<f32>(ex[t], ey[t], ez[t]);
+        let h = vec3<f32>(hx[t], hy[t], hz[t]);
+        s = dot(weights[2u * t].xyz, e * e) + dot(weights[2u * t + 1u].xyz, h * h);
+    }
+    sums[lid] = s;
+    workgroupBarrier();
+    for (var half = 32u; half > 0u; half >>= 1u) {
+        if (lid < half) {
+            sums[lid] += sums[lid + half];
+        }
+        workgroupBarrier();
+    }
+    if (lid == 0u) {
+        partials[g] = sums[0];
+    }
+}
@@ -0,0 +1,85 @@
+// ------------------------------------------------------------------
+// fft.wgsl  –  2D FFT of a field plane and its accumulated power
+//
+// `gather` copies one component on a plane of cells into a complex
+// nu × nv array (u fastest), zero-padded to the power-of-two size.  Each
+// `butterfly` dispatch is one radix-2 Stockham stage along u or v,
+// reading `src` and writing `dst` in natural order after the last stage;
+// the host ping-pongs the two buffers.  `accumulate` adds |F|² of the
+// transformed plane into `power`.
+// ------------------------------------------------------------------
+
+struct FftParams {
+    // transform size nu, nv; plane size in cells mu, mv
+    size: vec4<u32>,
+    // field index of the plane's cell (0, 0), strides along u and v, -
+    plane: vec4<u32>,
+    // butterfly span p, axis (0 = u, 1 = v), -, -
+    stage: vec4<u32>,
+}
+
+const PI: f32 = 3.14159265358979;
+
+@group(0) @binding(0) var<uniform> p: FftParams;
+
+@group(0) @binding(1) var<storage, read>       field: array<f32>;
+@group(0) @binding(2) var<storage, read>       src: array<vec2<f32>>;
+@group(0) @binding(3) var<storage, read_write> dst: array<vec2<f32>>;
+@group(0) @binding(4) var<storage, read_write> power: array<f32>;
+
+@compute @workgroup_size(64)
+fn gather(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let id = gid.x;
+    if (id >= p.size.x * p.size.y) {
+        return;
+    }
+    let a = id % p.size.x;
+    let b = id / p.size.x;
+    var value = 0.0;
+    if (a < p.size.z && b < p.size.w) {
+        value = field[p.plane.x + a * p.plane.y + b * p.plane.z];
+    }
+    dst[id] = vec2<f32>(value, 0.0);
+}
+
+// Array index of element `x` of line `line` along the stage's axis
+fn at(x: u32, line: u32) -> u32 {
+    if (p.stage.y == 0u) {
+        return x + p.size.x * line;
+    }
+    return line + p.size.x * x;
+}
+
+@compute @workgroup_size(64)
+fn butterfly(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let n = select(p.size.y, p.size.x, p.stage.y == 0u);
+    let lines = select(p.size.x, p.size.y, p.stage.y == 0u);
+    let half = n / 2u;
+    if (gid.x >= half * lines) {
+        return;
+    }
+    let line = gid.x / half;
+    let i = gid.x % half;
+    let span = p.stage.x;
+    let k = i & (span - 1u);
+    let u0 = src[at(i, line)];
+    let v = src[at(i + half, line)];
+    // u1 = v · e^{−jπk/p}
+    let angle = -PI * f32(k) / f32(span);
+    let c = cos(angle);
+    let s = sin(angle);
+    let u1 = vec2<f32>(v.x * c - v.y * s, v.x * s + v.y * c);
+    let j = ((i - k) << 1u) + k;
+    dst[at(j, line)] = u0 + u1;
+    dst[at(j + span, line)] = u0 - u1;
+}
+
+@compute @workgroup_size(64)
+fn accumulate(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let id = gid.x;
+    if (id >= p.size.x * p.size.y) {
+        return;
+    }
+    let f = src[id];
+    power[id] += dot(f, f);
+}
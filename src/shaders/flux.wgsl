@@ -0,0 +1,63 @@
+// ------------------------------------------------------------------
+// flux.wgsl  –  Poynting flux through an axis-aligned rectangle
+//
+// With (u, v) the tangential axes of the normal n, thread t takes the
+// sample pair at u = t mod nu, v = t / nu of the plane and adds
+//
+//   (E_u·H_v·dA_u − E_v·H_u·dA_v)
+//
+// to its workgroup's partial sum.  H is averaged over the two H planes
+// half a cell either side of the E plane; dA_u and dA_v are the areas of
+// the E_u and E_v samples.  The host adds the partial sums.
+// ------------------------------------------------------------------
+
+struct FluxParams {
+    nu: u32,
+    count: u32,
+    base: u32,
+    stride_u: u32,
+    stride_v: u32,
+    stride_n: u32,
+    // Index of this workgroup-0 partial in the history
+    out: u32,
+    _pad: u32,
+}
+
+@group(0) @binding(0) var<uniform> p: FluxParams;
+
+@group(0) @binding(1) var<storage, read>       eu: array<f32>;
+@group(0) @binding(2) var<storage, read>       ev: array<f32>;
+@group(0) @binding(3) var<storage, read>       hu: array<f32>;
+@group(0) @binding(4) var<storage, read>       hv: array<f32>;
+@group(0) @binding(5) var<storage, read>       area: array<vec2<f32>>;
+@group(0) @binding(6) var<storage, read_write> partials: array<f32>;
+
+var<workgroup> sums: array<f32, 64>;
+
+@compute @workgroup_size(64)
+fn main(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+    @builtin(local_invocation_index) lid: u32,
+    @builtin(workgroup_id) wid: vec3<u32>,
+) {
+    let t = gid.x;
+    var s = 0.0;
+    if (t < p.count) {
+        let cell = p.base + (t % p.nu) * p.stride_u + (t / p.nu) * p.stride_v;
+        let below = cell - p.stride_n;
+        let h_u = 0.5 * (hu[cell] + hu[below]);
+        let h_v = 0.5 * (hv[cell] + hv[below]);
+        s = eu[cell] * h_v * area[t].x - ev[cell] * h_u * area[t].y;
+    }
+    sums[lid] = s;
+    workgroupBarrier();
+    for (var half = 32u; half > 0u; half >>= 1u) {
+        if (lid < half) {
+            sums[lid] += sums[lid + half];
+        }
+        workgroupBarrier();
+    }
+    if (lid == 0u) {
+        partials[p.out + wid.x] = sums[0];
+    }
+}
@@ -0,0 +1,61 @@
+// ------------------------------------------------------------------
+// h_correct.wgsl  –  Sparse additive corrections to H edges
+//
+// Runs after the H-update.  Sub-cell models whose Faraday loop differs
+// from the regular Yee cell (thin wires, …) express the difference as
+// up to four extra E terms per H edge:
+//
+//   H[h] += Σ_t coef_t · E_{comp_t}[cell_t]
+//
+// One entry per H edge, so there are no write conflicts.
+// ------------------------------------------------------------------
+
+struct HCorrection {
+    h_cell: u32,
+    h_comp: u32,
+    _pad0: u32,
+    _pad1: u32,
+    e_cell: vec4<u32>,
+    e_comp: vec4<u32>,
+    coef: vec4<f32>,
+}
+
+@group(0) @binding(0) var<storage, read>       entries: array<HCorrection>;
+
+@group(0) @binding(1) var<storage, read>       ex: array<f32>;
+@group(0) @binding(2) var<storage, read>       ey: array<f32>;
+@group(0) @binding(3) var<storage, read>       ez: array<f32>;
+
+@group(0) @binding(4) var<storage, read_write> hx: array<f32>;
+@group(0) @binding(5) var<storage, read_write> hy: array<f32>;
+@group(0) @binding(6) var<storage, read_write> hz: array<f32>;
+
+fn load_e(comp: u32, id: u32) -> f32 {
+    switch comp {
+        case 0u: { return ex[id]; }
+        case 1u: { return ey[id]; }
+        default: { return ez[id]; }
+    }
+}
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let n = gid.x;
+    if (n >= arrayLength(&entries)) {
+        return;
+    }
+
+    let c = entries[n];
+    var dh = 0.0;
+    for (var t = 0u; t < 4u; t++) {
+        if (c.coef[t] != 0.0) {
+            dh += c.coef[t] * load_e(c.e_comp[t], c.e_cell[t]);
+        }
+    }
+
+    switch c.h_comp {
+        case 0u: { hx[c.h_cell] += dh; }
+        case 1u: { hy[c.h_cell] += dh; }
+        default: { hz[c.h_cell] += dh; }
+    }
+}
@@ -0,0 +1,82 @@
+// ------------------------------------------------------------------
+// hie.wgsl  –  Explicit half of the HIE-FDTD step
+//
+// With implicit axis d, the components along d never meet ∂_d and are
+// advanced explicitly to the half step (n = next(d), p = prev(d)):
+//
+//   H_d = CP·H_d + CQ·( ∂_p E_n − ∂_n E_p )
+//   E_d = CA·E_d + CB·( ∂_n H_p − ∂_p H_n )
+//
+// The transverse components then go through the θ = ½ line solves of
+// adi.wgsl.
+// ------------------------------------------------------------------
+
+struct HieParams {
+    nx: u32,
+    ny: u32,
+    nz: u32,
+    d: u32,
+}
+
+@group(0) @binding(0) var<uniform> a: HieParams;
+
+@group(0) @binding(1) var<storage, read_write> e_d: array<f32>;
+@group(0) @binding(2) var<storage, read>       e_n: array<f32>;
+@group(0) @binding(3) var<storage, read>       e_p: array<f32>;
+@group(0) @binding(4) var<storage, read_write> h_d: array<f32>;
+@group(0) @binding(5) var<storage, read>       h_n: array<f32>;
+@group(0) @binding(6) var<storage, read>       h_p: array<f32>;
+
+// Coefficients, four vec4 per cell: CA, CB, CP, CQ
+@group(0) @binding(7) var<storage, read>       coef: array<vec4<f32>>;
+
+struct Spacing {
+    inv_primary: array<vec4<f32>, 1024>,
+    inv_dual: array<vec4<f32>, 1024>,
+}
+
+@group(0) @binding(9) var<uniform> sp: Spacing;
+
+fn dims() -> vec3<u32> {
+    return vec3<u32>(a.nx, a.ny, a.nz);
+}
+
+fn idx(q: vec3<u32>) -> u32 {
+    return q.x + a.nx * (q.y + a.ny * q.z);
+}
+
+fn unit(axis: u32) -> vec3<u32> {
+    var u = vec3<u32>(0u);
+    u[axis] = 1u;
+    return u;
+}
+
+@compute @workgroup_size(4, 4, 4)
+fn update_hd(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let q = gid;
+    // Guard: stay one cell inside upper boundary, as in update_h
+    if (any(q + 1u >= dims())) {
+        return;
+    }
+    let n = (a.d + 1u) % 3u;
+    let p = (a.d + 2u) % 3u;
+    let id = idx(q);
+    let de_n = (e_n[idx(q + unit(p))] - e_n[id]) * sp.inv_primary[q[p]][p];
+    let de_p = (e_p[idx(q + unit(n))] - e_p[id]) * sp.inv_primary[q[n]][n];
+    h_d[id] = coef[4u * id + 2u][a.d] * h_d[id] + coef[4u * id + 3u][a.d] * (de_n - de_p);
+}
+
+@compute @workgroup_size(4, 4, 4)
+fn update_ed(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let q = gid;
+    // Guard: skip index 0 on each axis, as in update_e
+    if (any(q == vec3<u32>(0u)) || any(q >= dims())) {
+        return;
+    }
+    let n = (a.d + 1u) % 3u;
+    let p = (a.d + 2u) % 3u;
+    let id = idx(q);
+    let dh_p = (h_p[id] - h_p[idx(q - unit(n))]) * sp.inv_dual[q[n]][n];
+    let dh_n = (h_n[id] - h_n[idx(q - unit(p))]) * sp.inv_dual[q[p]][p];
+    e_d[id] = coef[4u * id][a.d] * e_d[id] + coef[4u * id + 1u][a.d] * (dh_p - dh_n);
+}
@@ -0,0 +1,30 @@
+// ------------------------------------------------------------------
+// modulate.wgsl  –  Scatter time-varying E coefficients into CA/CB
+//
+// Each entry names a cell and the modulated region it belongs to; the
+// host writes the current (CA, CB) of every region before dispatch.
+// Modulated cells are isotropic: all three lanes get the same value.
+// ------------------------------------------------------------------
+
+struct Entry {
+    cell: u32,
+    region: u32,
+}
+
+@group(0) @binding(0) var<storage, read>       entries: array<Entry>;
+@group(0) @binding(1) var<storage, read>       values: array<vec2<f32>>;
+@group(0) @binding(2) var<storage, read_write> ca: array<vec4<f32>>;
+@group(0) @binding(3) var<storage, read_write> cb: array<vec4<f32>>;
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let n = gid.x;
+    if (n >= arrayLength(&entries)) {
+        return;
+    }
+
+    let e = entries[n];
+    let v = values[e.region];
+    ca[e.cell] = vec4<f32>(v.x, v.x, v.x, 0.0);
+    cb[e.cell] = vec4<f32>(v.y, v.y, v.y, 0.0);
+}
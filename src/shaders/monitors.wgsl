@@ -0,0 +1,33 @@
+// ------------------------------------------------------------------
+// monitors.wgsl  –  Gather a line or plane of one component
+//
+// Thread t copies cell base + u·stride_u + v·stride_v, with u = t mod nu
+// and v = t / nu, into row `slot` of the monitor's history; the host reads
+// the rows back a batch at a time.
+// ------------------------------------------------------------------
+
+struct MonitorParams {
+    count: u32,
+    slot: u32,
+    base: u32,
+    nu: u32,
+    stride_u: u32,
+    stride_v: u32,
+    _pad0: u32,
+    _pad1: u32,
+}
+
+@group(0) @binding(0) var<uniform> p: MonitorParams;
+
+@group(0) @binding(1) var<storage, read>       field: array<f32>;
+@group(0) @binding(2) var<storage, read_write> history: array<f32>;
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let t = gid.x;
+    if (t >= p.count) {
+        return;
+    }
+    let cell = p.base + (t % p.nu) * p.stride_u + (t / p.nu) * p.stride_v;
+    history[p.slot * p.count + t] = field[cell];
+}
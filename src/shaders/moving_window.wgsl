@@ -0,0 +1,41 @@
+// ------------------------------------------------------------------
+// moving_window.wgsl  –  Shift a per-cell array one cell towards −x
+//
+// out[i, j, k] = src[i + 1, j, k]        for i < nx − 1
+//              = plane[j, k]             on the leading face i = nx − 1
+//
+// Arrays hold `width` floats per cell (1 for fields, 4 for coefficient
+// vec4s); `out` is a scratch buffer copied back over `src` afterwards.
+// ------------------------------------------------------------------
+
+struct WindowParams {
+    nx: u32,
+    ny: u32,
+    nz: u32,
+    width: u32,
+}
+
+@group(0) @binding(0) var<uniform> p: WindowParams;
+
+@group(0) @binding(1) var<storage, read>       src: array<f32>;
+@group(0) @binding(2) var<storage, read_write> out: array<f32>;
+@group(0) @binding(3) var<storage, read>       plane: array<f32>;
+
+@compute @workgroup_size(64)
+fn main(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+    @builtin(num_workgroups) groups: vec3<u32>,
+) {
+    let n = gid.x + gid.y * groups.x * 64u;
+    if (n >= p.nx * p.ny * p.nz * p.width) {
+        return;
+    }
+    let cell = n / p.width;
+    let lane = n % p.width;
+    let i = cell % p.nx;
+    if (i + 1u < p.nx) {
+        out[n] = src[n + p.width];
+    } else {
+        out[n] = plane[(cell / p.nx) * p.width + lane];
+    }
+}
@@ -0,0 +1,107 @@
+// ------------------------------------------------------------------
+// probes.wgsl  –  Gather probe samples into the history buffer
+//
+// One thread per probe writes its quantity into row `slot` of the history
+// (`count` values per row), which the host reads back a batch of rows at a
+// time.  Quantities 0..5 are the components Ex..Hz, 6 and 7 the
+// magnitudes |E| and |H|.
+//
+// Each component is interpolated trilinearly between its own Yee samples:
+// the probe carries, per component, the lower sample (i, j, k) of the
+// surrounding 2×2×2 block and the fractions along x, y and z.  Probes on a
+// cell have zero fractions and read that cell's raw samples.
+// ------------------------------------------------------------------
+
+struct ProbeParams {
+    count: u32,
+    slot: u32,
+    nx: u32,
+    ny: u32,
+}
+
+@group(0) @binding(0) var<uniform> p: ProbeParams;
+
+struct Stencil {
+    base: vec4<u32>,
+    frac: vec4<f32>,
+}
+
+struct Target {
+    quantity: u32,
+    _pad0: u32,
+    _pad1: u32,
+    _pad2: u32,
+    // One per component, Ex..Hz
+    stencils: array<Stencil, 6>,
+}
+
+@group(0) @binding(1) var<storage, read>       ex: array<f32>;
+@group(0) @binding(2) var<storage, read>       ey: array<f32>;
+@group(0) @binding(3) var<storage, read>       ez: array<f32>;
+@group(0) @binding(4) var<storage, read>       hx: array<f32>;
+@group(0) @binding(5) var<storage, read>       hy: array<f32>;
+@group(0) @binding(6) var<storage, read>       hz: array<f32>;
+@group(0) @binding(7) var<storage, read>       targets: array<Target>;
+@group(0) @binding(8) var<storage, read_write> history: array<f32>;
+
+fn value(c: u32, id: u32) -> f32 {
+    switch c {
+        case 0u: {
+            return ex[id];
+        }
+        case 1u: {
+            return ey[id];
+        }
+        case 2u: {
+            return ez[id];
+        }
+        case 3u: {
+            return hx[id];
+        }
+        case 4u: {
+            return hy[id];
+        }
+        default: {
+            return hz[id];
+        }
+    }
+}
+
+// Component `c` of probe `n` at the probe position
+fn interpolate(n: u32, c: u32) -> f32 {
+    let s = targets[n].stencils[c];
+    var sum = 0.0;
+    for (var corner = 0u; corner < 8u; corner++) {
+        let o = vec3<u32>(corner & 1u, (corner >> 1u) & 1u, corner >> 2u);
+        let w3 = select(1.0 - s.frac.xyz, s.frac.xyz, o == vec3<u32>(1u));
+        let w = w3.x * w3.y * w3.z;
+        // Zero weights also keep on-cell probes at the upper boundary in range
+        if (w != 0.0) {
+            let q = s.base.xyz + o;
+            sum += w * value(c, q.x + p.nx * (q.y + p.ny * q.z));
+        }
+    }
+    return sum;
+}
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let n = gid.x;
+    if (n >= p.count) {
+        return;
+    }
+    let quantity = targets[n].quantity;
+    var result: f32;
+    if (quantity < 6u) {
+        result = interpolate(n, quantity);
+    } else {
+        let first = 3u * (quantity - 6u);
+        let v = vec3<f32>(
+            interpolate(n, first),
+            interpolate(n, first + 1u),
+            interpolate(n, first + 2u),
+        );
+        result = length(v);
+    }
+    history[p.slot * p.count + n] = result;
+}
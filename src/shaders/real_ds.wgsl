@@ -0,0 +1,55 @@
+// ------------------------------------------------------------------
+// real_ds.wgsl  –  Emulated double-single arithmetic
+//
+// Prepended to update_wide.wgsl on adapters without SHADER_F64.  A value
+// is the unevaluated sum hi + lo of two f32 (≈ 48-bit mantissa), combined
+// with Knuth's two-sum and Dekker's split product, which need no fused
+// multiply-add.
+// ------------------------------------------------------------------
+
+alias Real = vec2<f32>;
+
+fn quick_two_sum(a: f32, b: f32) -> vec2<f32> {
+    let s = a + b;
+    return vec2<f32>(s, b - (s - a));
+}
+
+fn two_sum(a: f32, b: f32) -> vec2<f32> {
+    let s = a + b;
+    let v = s - a;
+    return vec2<f32>(s, (a - (s - v)) + (b - v));
+}
+
+// Dekker split into two 12-bit halves
+fn split(a: f32) -> vec2<f32> {
+    let t = 4097.0 * a;
+    let hi = t - (t - a);
+    return vec2<f32>(hi, a - hi);
+}
+
+fn two_prod(a: f32, b: f32) -> vec2<f32> {
+    let p = a * b;
+    let sa = split(a);
+    let sb = split(b);
+    let e = ((sa.x * sb.x - p) + sa.x * sb.y + sa.y * sb.x) + sa.y * sb.y;
+    return vec2<f32>(p, e);
+}
+
+fn r_add(a: Real, b: Real) -> Real {
+    let s = two_sum(a.x, b.x);
+    return quick_two_sum(s.x, s.y + a.y + b.y);
+}
+
+fn r_sub(a: Real, b: Real) -> Real {
+    return r_add(a, -b);
+}
+
+fn r_mul(a: Real, b: Real) -> Real {
+    let p = two_prod(a.x, b.x);
+    return quick_two_sum(p.x, p.y + (a.x * b.y + a.y * b.x));
+}
+
+// Value sent from the host as an f32 pair (hi, lo)
+fn r_pair(hi: f32, lo: f32) -> Real {
+    return vec2<f32>(hi, lo);
+}
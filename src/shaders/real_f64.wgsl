@@ -0,0 +1,24 @@
+// ------------------------------------------------------------------
+// real_f64.wgsl  –  Native double-precision arithmetic (SHADER_F64)
+//
+// Prepended to update_wide.wgsl.
+// ------------------------------------------------------------------
+
+alias Real = f64;
+
+fn r_add(a: Real, b: Real) -> Real {
+    return a + b;
+}
+
+fn r_sub(a: Real, b: Real) -> Real {
+    return a - b;
+}
+
+fn r_mul(a: Real, b: Real) -> Real {
+    return a * b;
+}
+
+// Value sent from the host as an f32 pair (hi, lo)
+fn r_pair(hi: f32, lo: f32) -> Real {
+    return f64(hi) + f64(lo);
+}
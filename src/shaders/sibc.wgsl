@@ -0,0 +1,82 @@
+// ------------------------------------------------------------------
+// sibc.wgsl  –  Surface impedance boundary condition on conductor faces
+//
+// Runs after the E-update and overwrites the tangential E edges lying on
+// a good-conductor surface with the Leontovich condition
+//
+//   E_t = Z_s(ω) · H_t,   Z_s(s) = sqrt(μ/σ) · sqrt(s)
+//
+// sqrt(s) is expanded as Σ w_p · (1 − a_p/(s + a_p)), so the convolution
+// with H becomes NPOLES recursive accumulators per edge:
+//
+//   E^{n+1}   = coef · Σ w_p · e_p · (H − a_p · ψ_p^{n})
+//   ψ_p^{n+1} = e_p · ψ_p^{n} + g_p · H          (e_p = exp(−a_pΔt))
+// ------------------------------------------------------------------
+
+const NPOLES: u32 = 16u;
+
+struct Poles {
+    // (decay e_p, gain g_p, weight w_p, rate a_p)
+    p: array<vec4<f32>, 16>,
+}
+
+struct SibcEdge {
+    e_cell: u32,
+    e_comp: u32,
+    h_cell: u32,
+    h_comp: u32,
+    coef: f32,   // ±sqrt(μ/σ), sign from the outward normal
+    _pad0: u32,
+    _pad1: u32,
+    _pad2: u32,
+}
+
+@group(0) @binding(0) var<uniform>             poles: Poles;
+@group(0) @binding(1) var<storage, read>       edges: array<SibcEdge>;
+@group(0) @binding(2) var<storage, read_write> psi: array<f32>;
+
+@group(0) @binding(3) var<storage, read>       hx: array<f32>;
+@group(0) @binding(4) var<storage, read>       hy: array<f32>;
+@group(0) @binding(5) var<storage, read>       hz: array<f32>;
+
+@group(0) @binding(6) var<storage, read_write> ex: array<f32>;
+@group(0) @binding(7) var<storage, read_write> ey: array<f32>;
+@group(0) @binding(8) var<storage, read_write> ez: array<f32>;
+
+fn load_h(comp: u32, id: u32) -> f32 {
+    switch comp {
+        case 0u: { return hx[id]; }
+        case 1u: { return hy[id]; }
+        default: { return hz[id]; }
+    }
+}
+
+fn store_e(comp: u32, id: u32, v: f32) {
+    switch comp {
+        case 0u: { ex[id] = v; }
+        case 1u: { ey[id] = v; }
+        default: { ez[id] = v; }
+    }
+}
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let n = gid.x;
+    if (n >= arrayLength(&edges)) {
+        return;
+    }
+
+    let e = edges[n];
+    let h = load_h(e.h_comp, e.h_cell);
+
+    var acc = 0.0;
+    for (var p = 0u; p < NPOLES; p++) {
+        let pole = poles.p[p];
+        let slot = n * NPOLES + p;
+        let old = psi[slot];
+        acc += pole.z * pole.x * (h - pole.w * old);
+        psi[slot] = pole.x * old + pole.y * h;
+    }
+
+    store_e(e.e_comp, e.e_cell, e.coef * acc);
+}
@@ -0,0 +1,86 @@
+// ------------------------------------------------------------------
+// source.wgsl  –  Point source written into Ez from a device clock
+//
+// One invocation evaluates the waveform at step n, read from the clock
+// the host sets only on a restore, writes it into the source's Ez cell
+// and advances the clock, so the steps of a submission need no upload.
+// Widths, delays and ramps are in steps, as in sources.rs.  The carrier
+// phase is counted in 2⁻³² turns with wrapping u32 arithmetic, from the
+// cycles per step in 64-bit fixed point, so that it stays exact over long
+// runs where f32(n)·f·Δt would not.
+// ------------------------------------------------------------------
+
+struct Source {
+    kind: u32,           // 0 Gaussian, 1 modulated Gaussian, 2 sine, 3 step, 4 Ricker
+    cell: u32,
+    on: u32,
+    delay_turns: u32,    // f·Δt·delay mod 1, in 2⁻³² turns
+    amplitude: f32,
+    delay: f32,
+    width: f32,
+    ramp: f32,           // sine ramp or step rise
+    cycles: f32,         // f·Δt
+    cycles_hi: u32,      // f·Δt mod 1 in 2⁻⁶⁴ turns, high and low words
+    cycles_lo: u32,
+    _pad: u32,
+}
+
+@group(0) @binding(0) var<uniform>             source: Source;
+@group(0) @binding(1) var<storage, read_write> ez: array<f32>;
+@group(0) @binding(2) var<storage, read_write> clock: array<u32>;
+
+const PI: f32 = 3.14159265358979;
+
+// High word of the 64-bit product a·b
+fn mul_hi(a: u32, b: u32) -> u32 {
+    let a0 = a & 0xffffu;
+    let a1 = a >> 16u;
+    let b0 = b & 0xffffu;
+    let b1 = b >> 16u;
+    let mid0 = a1 * b0;
+    let mid1 = a0 * b1;
+    let carry = (((a0 * b0) >> 16u) + (mid0 & 0xffffu) + (mid1 & 0xffffu)) >> 16u;
+    return a1 * b1 + (mid0 >> 16u) + (mid1 >> 16u) + carry;
+}
+
+// sin of the carrier at step n, delayed by `delay`
+fn carrier(n: u32) -> f32 {
+    let turns = n * source.cycles_hi + mul_hi(n, source.cycles_lo) - source.delay_turns;
+    return sin(2.0 * PI * (f32(turns) / 4294967296.0));
+}
+
+fn waveform(n: u32) -> f32 {
+    let t = f32(n) - source.delay;
+    let envelope = exp(-(t * t) / (source.width * source.width));
+    switch source.kind {
+        case 0u: {
+            return envelope;
+        }
+        case 1u: {
+            return envelope * carrier(n);
+        }
+        case 2u: {
+            let ramp = 0.5 * (1.0 - cos(PI * f32(n) / source.ramp));
+            return select(1.0, ramp, f32(n) < source.ramp) * carrier(n);
+        }
+        case 3u: {
+            if (t <= 0.0) {
+                return 0.0;
+            }
+            return select(1.0, t / source.ramp, t < source.ramp);
+        }
+        default: {
+            let x = PI * source.cycles * t;
+            return (1.0 - 2.0 * x * x) * exp(-x * x);
+        }
+    }
+}
+
+@compute @workgroup_size(1)
+fn main() {
+    let n = clock[0];
+    if (source.on != 0u) {
+        ez[source.cell] = source.amplitude * waveform(n);
+    }
+    clock[0] = n + 1u;
+}
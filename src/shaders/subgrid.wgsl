@@ -0,0 +1,175 @@
+// ------------------------------------------------------------------
+// subgrid.wgsl  –  Coupling of a refined child grid to its parent
+//
+// snapshot:         prev[box] = parent E^n   (before the parent E-update)
+// boundary:         child tangential E on the six outer faces
+//                     = trilinear(parent E) at time n + alpha, with parent
+//                     E linearly interpolated between prev and E^{n+1}
+// restrict_parent:  parent E on edges strictly inside the region
+//                     = mean of the `ratio` child edges along it
+//
+// The box covers parent cells lo .. lo + cells (inclusive of the upper
+// face), the child has ratio·cells + 1 nodes per axis.
+// ------------------------------------------------------------------
+
+struct SubParams {
+    pnx: u32,
+    pny: u32,
+    pnz: u32,
+    ratio: u32,
+    cnx: u32,
+    cny: u32,
+    cnz: u32,
+    _pad: u32,
+    lo: vec3<u32>,
+    alpha: f32,
+}
+
+@group(0) @binding(0) var<uniform> sp: SubParams;
+
+// Parent electric fields
+@group(0) @binding(1) var<storage, read_write> pex: array<f32>;
+@group(0) @binding(2) var<storage, read_write> pey: array<f32>;
+@group(0) @binding(3) var<storage, read_write> pez: array<f32>;
+
+// Parent E^n over the box (xyz lanes)
+@group(0) @binding(4) var<storage, read_write> prev: array<vec4<f32>>;
+
+// Child electric fields
+@group(0) @binding(5) var<storage, read_write> cex: array<f32>;
+@group(0) @binding(6) var<storage, read_write> cey: array<f32>;
+@group(0) @binding(7) var<storage, read_write> cez: array<f32>;
+
+// Box nodes per axis (parent cells + 1).
+fn box_dims() -> vec3<u32> {
+    return vec3<u32>(sp.cnx - 1u, sp.cny - 1u, sp.cnz - 1u) / sp.ratio + 1u;
+}
+
+fn parent_idx(q: vec3<u32>) -> u32 {
+    let g = sp.lo + q;
+    return g.x + sp.pnx * (g.y + sp.pny * g.z);
+}
+
+fn box_idx(q: vec3<u32>) -> u32 {
+    let b = box_dims();
+    return q.x + b.x * (q.y + b.y * q.z);
+}
+
+fn child_idx(i: u32, j: u32, k: u32) -> u32 {
+    return i + sp.cnx * (j + sp.cny * k);
+}
+
+fn load_parent(comp: u32, q: vec3<u32>) -> f32 {
+    let id = parent_idx(q);
+    switch comp {
+        case 0u: { return pex[id]; }
+        case 1u: { return pey[id]; }
+        default: { return pez[id]; }
+    }
+}
+
+// Parent E_comp at box node q, at time n + alpha.
+fn parent_at(comp: u32, q: vec3<u32>) -> f32 {
+    let old = prev[box_idx(q)][comp];
+    return mix(old, load_parent(comp, q), sp.alpha);
+}
+
+// Trilinear interpolation of parent E_comp at box coordinate t (in parent
+// cells, relative to the sample lattice of that component).
+fn interp(comp: u32, t: vec3<f32>) -> f32 {
+    let hi = vec3<f32>(box_dims() - 2u);
+    let base = clamp(floor(t), vec3<f32>(0.0), hi);
+    let f = clamp(t - base, vec3<f32>(0.0), vec3<f32>(1.0));
+    let q = vec3<u32>(base);
+    var acc = 0.0;
+    for (var n = 0u; n < 8u; n++) {
+        let o = vec3<u32>(n & 1u, (n >> 1u) & 1u, (n >> 2u) & 1u);
+        let w = mix(1.0 - f, f, vec3<f32>(o));
+        acc += w.x * w.y * w.z * parent_at(comp, q + o);
+    }
+    return acc;
+}
+
+@compute @workgroup_size(4, 4, 4)
+fn snapshot(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let b = box_dims();
+    if (any(gid >= b)) {
+        return;
+    }
+    let id = parent_idx(gid);
+    prev[box_idx(gid)] = vec4<f32>(pex[id], pey[id], pez[id], 0.0);
+}
+
+@compute @workgroup_size(4, 4, 4)
+fn boundary(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let last = vec3<u32>(sp.cnx, sp.cny, sp.cnz) - 1u;
+    if (any(gid > last)) {
+        return;
+    }
+    let on_face = (gid == vec3<u32>(0u)) | (gid == last);
+    let r = f32(sp.ratio);
+    let id = child_idx(gid.x, gid.y, gid.z);
+
+    for (var c = 0u; c < 3u; c++) {
+        // E_c runs along c: it needs a successor node on c and must lie on
+        // a face normal to one of the other two axes.
+        var tangential = false;
+        for (var d = 0u; d < 3u; d++) {
+            if (d != c && on_face[d]) {
+                tangential = true;
+            }
+        }
+        if (!tangential || gid[c] == last[c]) {
+            continue;
+        }
+        var t = vec3<f32>(gid) / r;
+        t[c] = (f32(gid[c]) + 0.5) / r - 0.5;
+        let v = interp(c, t);
+        switch c {
+            case 0u: { cex[id] = v; }
+            case 1u: { cey[id] = v; }
+            default: { cez[id] = v; }
+        }
+    }
+}
+
+@compute @workgroup_size(4, 4, 4)
+fn restrict_parent(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let cells = box_dims() - 1u;
+    if (any(gid >= cells)) {
+        return;
+    }
+    let pid = parent_idx(gid);
+    let base = gid * sp.ratio;
+
+    for (var c = 0u; c < 3u; c++) {
+        // Only edges strictly inside the region; the faces stay with the
+        // parent, which drives the child boundary.
+        var interior = true;
+        for (var d = 0u; d < 3u; d++) {
+            if (d != c && gid[d] == 0u) {
+                interior = false;
+            }
+        }
+        if (!interior) {
+            continue;
+        }
+        var sum = 0.0;
+        for (var s = 0u; s < sp.ratio; s++) {
+            var q = base;
+            q[c] += s;
+            let cid = child_idx(q.x, q.y, q.z);
+            switch c {
+                case 0u: { sum += cex[cid]; }
+                case 1u: { sum += cey[cid]; }
+                default: { sum += cez[cid]; }
+            }
+        }
+        let v = sum / f32(sp.ratio);
+        switch c {
+            case 0u: { pex[pid] = v; }
+            case 1u: { pey[pid] = v; }
+            default: { pez[pid] = v; }
+        }
+    }
+}
@@ -0,0 +1,42 @@
+// ------------------------------------------------------------------
+// update_1d.wgsl  –  1D FDTD along x  (Ez, Hy)
+//
+// Hy[i] = CP * Hy  +  CQ * dEz/dx
+// Ez[i] = CA * Ez  +  CB * dHy/dx
+// ------------------------------------------------------------------
+
+struct Params {
+    nx: u32,
+    ny: u32,
+    inv_dx: f32,
+    inv_dy: f32,
+}
+
+@group(0) @binding(0) var<uniform> p: Params;
+
+@group(0) @binding(1) var<storage, read_write> ez: array<f32>;
+@group(0) @binding(2) var<storage, read_write> hy: array<f32>;
+
+// Material coefficients (x/y/z lanes per field component)
+@group(0) @binding(4) var<storage, read>       ca: array<vec4<f32>>;
+@group(0) @binding(5) var<storage, read>       cb: array<vec4<f32>>;
+@group(0) @binding(6) var<storage, read>       cp: array<vec4<f32>>;
+@group(0) @binding(7) var<storage, read>       cq: array<vec4<f32>>;
+
+@compute @workgroup_size(64)
+fn update_h(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    if (i >= p.nx - 1u) {
+        return;
+    }
+    hy[i] = cp[i].y * hy[i] + cq[i].y * (ez[i + 1u] - ez[i]) * p.inv_dx;
+}
+
+@compute @workgroup_size(64)
+fn update_e(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    if (i == 0u || i >= p.nx) {
+        return;
+    }
+    ez[i] = ca[i].z * ez[i] + cb[i].z * (hy[i] - hy[i - 1u]) * p.inv_dx;
+}
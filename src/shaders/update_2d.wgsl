@@ -0,0 +1,95 @@
+// ------------------------------------------------------------------
+// update_2d.wgsl  –  2D FDTD in the xy plane
+//
+// TMz (Ez, Hx, Hy):
+//   Hx = CP * Hx  -  CQ * dEz/dy
+//   Hy = CP * Hy  +  CQ * dEz/dx
+//   Ez = CA * Ez  +  CB * ( dHy/dx - dHx/dy )
+//
+// TEz (Hz, Ex, Ey):
+//   Hz = CP * Hz  +  CQ * ( dEx/dy - dEy/dx )
+//   Ex = CA * Ex  +  CB * dHz/dy
+//   Ey = CA * Ey  -  CB * dHz/dx
+// ------------------------------------------------------------------
+
+struct Params {
+    nx: u32,
+    ny: u32,
+    inv_dx: f32,
+    inv_dy: f32,
+}
+
+@group(0) @binding(0) var<uniform> p: Params;
+
+// Out-of-plane field (Ez or Hz) and the two in-plane components
+@group(0) @binding(1) var<storage, read_write> f0: array<f32>;
+@group(0) @binding(2) var<storage, read_write> f1: array<f32>;
+@group(0) @binding(3) var<storage, read_write> f2: array<f32>;
+
+// Material coefficients (x/y/z lanes per field component)
+@group(0) @binding(4) var<storage, read>       ca: array<vec4<f32>>;
+@group(0) @binding(5) var<storage, read>       cb: array<vec4<f32>>;
+@group(0) @binding(6) var<storage, read>       cp: array<vec4<f32>>;
+@group(0) @binding(7) var<storage, read>       cq: array<vec4<f32>>;
+
+fn idx(i: u32, j: u32) -> u32 {
+    return i + p.nx * j;
+}
+
+@compute @workgroup_size(8, 8)
+fn tm_h(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    let j = gid.y;
+    if (i >= p.nx || j >= p.ny) {
+        return;
+    }
+    let id = idx(i, j);
+    if (j + 1u < p.ny) {
+        f1[id] = cp[id].x * f1[id] - cq[id].x * (f0[idx(i, j + 1u)] - f0[id]) * p.inv_dy;
+    }
+    if (i + 1u < p.nx) {
+        f2[id] = cp[id].y * f2[id] + cq[id].y * (f0[idx(i + 1u, j)] - f0[id]) * p.inv_dx;
+    }
+}
+
+@compute @workgroup_size(8, 8)
+fn tm_e(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    let j = gid.y;
+    if (i == 0u || j == 0u || i >= p.nx || j >= p.ny) {
+        return;
+    }
+    let id = idx(i, j);
+    let dhy_dx = (f2[id] - f2[idx(i - 1u, j)]) * p.inv_dx;
+    let dhx_dy = (f1[id] - f1[idx(i, j - 1u)]) * p.inv_dy;
+    f0[id] = ca[id].z * f0[id] + cb[id].z * (dhy_dx - dhx_dy);
+}
+
+@compute @workgroup_size(8, 8)
+fn te_h(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    let j = gid.y;
+    if (i + 1u >= p.nx || j + 1u >= p.ny) {
+        return;
+    }
+    let id = idx(i, j);
+    let dex_dy = (f1[idx(i, j + 1u)] - f1[id]) * p.inv_dy;
+    let dey_dx = (f2[idx(i + 1u, j)] - f2[id]) * p.inv_dx;
+    f0[id] = cp[id].z * f0[id] + cq[id].z * (dex_dy - dey_dx);
+}
+
+@compute @workgroup_size(8, 8)
+fn te_e(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    let j = gid.y;
+    if (i >= p.nx || j >= p.ny) {
+        return;
+    }
+    let id = idx(i, j);
+    if (j > 0u) {
+        f1[id] = ca[id].x * f1[id] + cb[id].x * (f0[id] - f0[idx(i, j - 1u)]) * p.inv_dy;
+    }
+    if (i > 0u) {
+        f2[id] = ca[id].y * f2[id] - cb[id].y * (f0[id] - f0[idx(i - 1u, j)]) * p.inv_dx;
+    }
+}
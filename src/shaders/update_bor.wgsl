@@ -0,0 +1,129 @@
+// ------------------------------------------------------------------
+// update_bor.wgsl  –  Body-of-revolution FDTD in (r, z), azimuthal mode m
+//
+// Fields vary as cos(mφ) (Er, Ez, Hφ) or sin(mφ) (Eφ, Hr, Hz).  Yee
+// layout as in 3D with x → r, y → φ, z → z; node i sits at r = i·dr, so
+// the axis is i = 0.  Packed vec4 fields: x = r, y = φ, z = z lane.
+//
+// Hr = CP·Hr + CQ·( m/r·Ez + dEφ/dz )
+// Hφ = CP·Hφ + CQ·( dEz/dr − dEr/dz )
+// Hz = CP·Hz − CQ·( 1/r·d(r·Eφ)/dr + m/r·Er )
+// Er = CA·Er + CB·( m/r·Hz − dHφ/dz )
+// Eφ = CA·Eφ + CB·( dHr/dz − dHz/dr )
+// Ez = CA·Ez + CB·( 1/r·d(r·Hφ)/dr − m/r·Hr )
+//
+// On the axis only m = 0 (Ez) and m = 1 (Eφ, Hr) fields survive; they
+// use the small-loop limits of the 1/r terms.
+// ------------------------------------------------------------------
+
+struct Params {
+    nr: u32,
+    nz: u32,
+    inv_dr: f32,
+    inv_dz: f32,
+    m: u32,
+    dr: f32,
+}
+
+@group(0) @binding(0) var<uniform> p: Params;
+
+@group(0) @binding(1) var<storage, read_write> e: array<vec4<f32>>;
+@group(0) @binding(2) var<storage, read_write> h: array<vec4<f32>>;
+
+// Material coefficients (x/y/z lanes = r/φ/z components)
+@group(0) @binding(4) var<storage, read>       ca: array<vec4<f32>>;
+@group(0) @binding(5) var<storage, read>       cb: array<vec4<f32>>;
+@group(0) @binding(6) var<storage, read>       cp: array<vec4<f32>>;
+@group(0) @binding(7) var<storage, read>       cq: array<vec4<f32>>;
+
+fn idx(i: u32, k: u32) -> u32 {
+    return i + p.nr * k;
+}
+
+@compute @workgroup_size(8, 8)
+fn update_h(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    let k = gid.y;
+    if (i >= p.nr || k >= p.nz) {
+        return;
+    }
+    let id = idx(i, k);
+    let m = f32(p.m);
+    let r = f32(i) * p.dr;          // node radius
+    let rh = (f32(i) + 0.5) * p.dr; // half-cell radius
+    var hn = h[id];
+
+    // Hr(i, k+½)
+    if (k + 1u < p.nz) {
+        let dephi_dz = (e[idx(i, k + 1u)].y - e[id].y) * p.inv_dz;
+        if (i > 0u) {
+            hn.x = cp[id].x * hn.x + cq[id].x * (m / r * e[id].z + dephi_dz);
+        } else if (p.m == 1u) {
+            // Ez ∝ r near the axis: m/r·Ez → m·Ez(dr)/dr
+            hn.x = cp[id].x * hn.x + cq[id].x * (e[idx(1u, k)].z * p.inv_dr + dephi_dz);
+        } else {
+            hn.x = 0.0;
+        }
+    }
+
+    if (i + 1u < p.nr) {
+        // Hφ(i+½, k+½)
+        if (k + 1u < p.nz) {
+            let dez_dr = (e[idx(i + 1u, k)].z - e[id].z) * p.inv_dr;
+            let der_dz = (e[idx(i, k + 1u)].x - e[id].x) * p.inv_dz;
+            hn.y = cp[id].y * hn.y + cq[id].y * (dez_dr - der_dz);
+        }
+        // Hz(i+½, k)
+        let r1 = r + p.dr;
+        let curl = (r1 * e[idx(i + 1u, k)].y - r * e[id].y) * p.inv_dr / rh + m / rh * e[id].x;
+        hn.z = cp[id].z * hn.z - cq[id].z * curl;
+    }
+    h[id] = hn;
+}
+
+@compute @workgroup_size(8, 8)
+fn update_e(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    let k = gid.y;
+    if (i + 1u >= p.nr || k >= p.nz) {
+        return;
+    }
+    let id = idx(i, k);
+    let m = f32(p.m);
+    let r = f32(i) * p.dr;
+    let rh = (f32(i) + 0.5) * p.dr;
+    var en = e[id];
+
+    // Er(i+½, k)
+    if (k > 0u && k + 1u < p.nz) {
+        let dhphi_dz = (h[id].y - h[idx(i, k - 1u)].y) * p.inv_dz;
+        en.x = ca[id].x * en.x + cb[id].x * (m / rh * h[id].z - dhphi_dz);
+    }
+
+    // Eφ(i, k)
+    if (k > 0u && k + 1u < p.nz) {
+        let dhr_dz = (h[id].x - h[idx(i, k - 1u)].x) * p.inv_dz;
+        if (i > 0u) {
+            let dhz_dr = (h[id].z - h[idx(i - 1u, k)].z) * p.inv_dr;
+            en.y = ca[id].y * en.y + cb[id].y * (dhr_dz - dhz_dr);
+        } else if (p.m == 1u) {
+            // Hz(0) = 0 for m = 1: dHz/dr → Hz(dr/2) / (dr/2)
+            en.y = ca[id].y * en.y + cb[id].y * (dhr_dz - 2.0 * h[id].z * p.inv_dr);
+        } else {
+            en.y = 0.0;
+        }
+    }
+
+    // Ez(i, k+½)
+    if (k + 1u < p.nz) {
+        if (i > 0u) {
+            let rl = r - 0.5 * p.dr;
+            let curl = (rh * h[id].y - rl * h[idx(i - 1u, k)].y) * p.inv_dr / r;
+            en.z = ca[id].z * en.z + cb[id].z * (curl - m / r * h[id].x);
+        } else if (p.m == 0u) {
+            // Ampère around the disc of radius dr/2: 4·Hφ(dr/2)/dr
+            en.z = ca[id].z * en.z + cb[id].z * 4.0 * h[id].y * p.inv_dr;
+        }
+    }
+    e[id] = en;
+}
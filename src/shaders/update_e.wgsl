@@ -4,17 +4,16 @@
 // Ex[i,j,k] = CA * Ex  +  CB * ( dHz/dy - dHy/dz )
 // Ey[i,j,k] = CA * Ey  +  CB * ( dHx/dz - dHz/dx )
 // Ez[i,j,k] = CA * Ez  +  CB * ( dHy/dx - dHx/dy )
+//
+// Index 0 of a periodic axis (bit of p.periodic) takes its lower H
+// neighbour from the last cell instead of staying a PEC wall.
 // ------------------------------------------------------------------
 
 struct Params {
     nx: u32,
     ny: u32,
     nz: u32,
-    _pad: u32,
-    inv_dx: f32,
-    inv_dy: f32,
-    inv_dz: f32,
-    _pad2: f32,
+    periodic: u32,
 }
 
 @group(0) @binding(0) var<uniform> p: Params;
@@ -29,9 +28,17 @@ struct Params {
 @group(0) @binding(5) var<storage, read_write> ey: array<f32>;
 @group(0) @binding(6) var<storage, read_write> ez: array<f32>;
 
-// Material coefficients
-@group(0) @binding(7) var<storage, read>       ca: array<f32>;
-@group(0) @binding(8) var<storage, read>       cb: array<f32>;
+// Inverse cell widths per index along each axis (x/y/z lanes): primary
+// widths for differences of E, dual widths for differences of H.
+struct Spacing {
+    inv_primary: array<vec4<f32>, 1024>,
+    inv_dual: array<vec4<f32>, 1024>,
+}
+
+@group(0) @binding(9) var<uniform> sp: Spacing;
+
+// Material coefficients at bindings 7..8 come from the prepended
+// coeffs_dense.wgsl / coeffs_indexed.wgsl via load_coeffs().
 
 fn idx(i: u32, j: u32, k: u32) -> u32 {
     return i + p.nx * (j + p.ny * k);
@@ -43,31 +50,39 @@ fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
     let j = gid.y;
     let k = gid.z;
 
-    // Guard: skip index 0 on each axis (need i-1, j-1, k-1)
-    if (i == 0u || j == 0u || k == 0u || i >= p.nx || j >= p.ny || k >= p.nz) {
+    // Guard: skip index 0 on each non-periodic axis (need i-1, j-1, k-1)
+    if (i >= p.nx || j >= p.ny || k >= p.nz) {
+        return;
+    }
+    let periodic = (vec3<u32>(p.periodic) & vec3<u32>(1u, 2u, 4u)) != vec3<u32>(0u);
+    if (any(vec3<bool>(i == 0u, j == 0u, k == 0u) & !periodic)) {
         return;
     }
+    let im = select(i - 1u, p.nx - 1u, i == 0u);
+    let jm = select(j - 1u, p.ny - 1u, j == 0u);
+    let km = select(k - 1u, p.nz - 1u, k == 0u);
 
     let id  = idx(i, j, k);
-    let ca_v = ca[id];
-    let cb_v = cb[id];
+    let coeffs = load_coeffs(id);
+    let ca_v = coeffs[0];
+    let cb_v = coeffs[1];
 
     // --- Shift & Add  (finite differences of H) -----------------------
 
     // Ex:  dHz/dy - dHy/dz
-    let dHz_dy = (hz[id] - hz[idx(i, j - 1u, k)]) * p.inv_dy;
-    let dHy_dz = (hy[id] - hy[idx(i, j, k - 1u)]) * p.inv_dz;
+    let dHz_dy = (hz[id] - hz[idx(i, jm, k)]) * sp.inv_dual[j].y;
+    let dHy_dz = (hy[id] - hy[idx(i, j, km)]) * sp.inv_dual[k].z;
 
     // Ey:  dHx/dz - dHz/dx
-    let dHx_dz = (hx[id] - hx[idx(i, j, k - 1u)]) * p.inv_dz;
-    let dHz_dx = (hz[id] - hz[idx(i - 1u, j, k)]) * p.inv_dx;
+    let dHx_dz = (hx[id] - hx[idx(i, j, km)]) * sp.inv_dual[k].z;
+    let dHz_dx = (hz[id] - hz[idx(im, j, k)]) * sp.inv_dual[i].x;
 
     // Ez:  dHy/dx - dHx/dy
-    let dHy_dx = (hy[id] - hy[idx(i - 1u, j, k)]) * p.inv_dx;
-    let dHx_dy = (hx[id] - hx[idx(i, j - 1u, k)]) * p.inv_dy;
+    let dHy_dx = (hy[id] - hy[idx(im, j, k)]) * sp.inv_dual[i].x;
+    let dHx_dy = (hx[id] - hx[idx(i, jm, k)]) * sp.inv_dual[j].y;
 
     // --- Hadamard Product + Summation ---------------------------------
-    ex[id] = ca_v * ex[id] + cb_v * (dHz_dy - dHy_dz);
-    ey[id] = ca_v * ey[id] + cb_v * (dHx_dz - dHz_dx);
-    ez[id] = ca_v * ez[id] + cb_v * (dHy_dx - dHx_dy);
+    ex[id] = ca_v.x * ex[id] + cb_v.x * (dHz_dy - dHy_dz);
+    ey[id] = ca_v.y * ey[id] + cb_v.y * (dHx_dz - dHz_dx);
+    ez[id] = ca_v.z * ez[id] + cb_v.z * (dHy_dx - dHx_dy);
 }
@@ -0,0 +1,107 @@
+// ------------------------------------------------------------------
+// update_e4.wgsl  –  Electric field update, (2,4) stencil
+//
+// Ex[i,j,k] = CA * Ex  +  CB * ( dHz/dy - dHy/dz )
+// Ey[i,j,k] = CA * Ey  +  CB * ( dHx/dz - dHz/dx )
+// Ez[i,j,k] = CA * Ez  +  CB * ( dHy/dx - dHx/dy )
+//
+// Differences of H are fourth order on a uniform mesh,
+//   f(n) − f(n−1)  →  9/8·(f(n) − f(n−1)) − 1/24·(f(n+1) − f(n−2)),
+// falling back to the Yee difference next to the boundary.
+// ------------------------------------------------------------------
+
+struct Params {
+    nx: u32,
+    ny: u32,
+    nz: u32,
+    _pad: u32,
+}
+
+@group(0) @binding(0) var<uniform> p: Params;
+
+// Magnetic fields (read-only this pass)
+@group(0) @binding(1) var<storage, read>       hx: array<f32>;
+@group(0) @binding(2) var<storage, read>       hy: array<f32>;
+@group(0) @binding(3) var<storage, read>       hz: array<f32>;
+
+// Electric fields (read-write)
+@group(0) @binding(4) var<storage, read_write> ex: array<f32>;
+@group(0) @binding(5) var<storage, read_write> ey: array<f32>;
+@group(0) @binding(6) var<storage, read_write> ez: array<f32>;
+
+// Inverse cell widths per index along each axis (x/y/z lanes): primary
+// widths for differences of E, dual widths for differences of H.
+struct Spacing {
+    inv_primary: array<vec4<f32>, 1024>,
+    inv_dual: array<vec4<f32>, 1024>,
+}
+
+@group(0) @binding(9) var<uniform> sp: Spacing;
+
+// Material coefficients at bindings 7..8 come from the prepended
+// coeffs_dense.wgsl / coeffs_indexed.wgsl via load_coeffs().
+
+fn idx(i: u32, j: u32, k: u32) -> u32 {
+    return i + p.nx * (j + p.ny * k);
+}
+
+// b − a, with the (2,4) correction across the outer pair (m, q).
+fn d4(m: f32, a: f32, b: f32, q: f32, inner: bool) -> f32 {
+    if (inner) {
+        return 1.125 * (b - a) - (q - m) / 24.0;
+    }
+    return b - a;
+}
+
+@compute @workgroup_size(4, 4, 4)
+fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    let j = gid.y;
+    let k = gid.z;
+
+    // Guard: skip index 0 on each axis (need i-1, j-1, k-1)
+    if (i == 0u || j == 0u || k == 0u || i >= p.nx || j >= p.ny || k >= p.nz) {
+        return;
+    }
+
+    let id  = idx(i, j, k);
+    let coeffs = load_coeffs(id);
+    let ca_v = coeffs[0];
+    let cb_v = coeffs[1];
+
+    // Outer stencil points n − 2 and n + 1, clamped where they don't exist
+    let im = max(i, 2u) - 2u;
+    let jm = max(j, 2u) - 2u;
+    let km = max(k, 2u) - 2u;
+    let ip = min(i + 1u, p.nx - 1u);
+    let jp = min(j + 1u, p.ny - 1u);
+    let kp = min(k + 1u, p.nz - 1u);
+    let in_x = i >= 2u && i + 1u < p.nx;
+    let in_y = j >= 2u && j + 1u < p.ny;
+    let in_z = k >= 2u && k + 1u < p.nz;
+
+    // --- Shift & Add  (finite differences of H) -----------------------
+
+    // Ex:  dHz/dy - dHy/dz
+    let dHz_dy = d4(hz[idx(i, jm, k)], hz[idx(i, j - 1u, k)], hz[id], hz[idx(i, jp, k)], in_y)
+        * sp.inv_dual[j].y;
+    let dHy_dz = d4(hy[idx(i, j, km)], hy[idx(i, j, k - 1u)], hy[id], hy[idx(i, j, kp)], in_z)
+        * sp.inv_dual[k].z;
+
+    // Ey:  dHx/dz - dHz/dx
+    let dHx_dz = d4(hx[idx(i, j, km)], hx[idx(i, j, k - 1u)], hx[id], hx[idx(i, j, kp)], in_z)
+        * sp.inv_dual[k].z;
+    let dHz_dx = d4(hz[idx(im, j, k)], hz[idx(i - 1u, j, k)], hz[id], hz[idx(ip, j, k)], in_x)
+        * sp.inv_dual[i].x;
+
+    // Ez:  dHy/dx - dHx/dy
+    let dHy_dx = d4(hy[idx(im, j, k)], hy[idx(i - 1u, j, k)], hy[id], hy[idx(ip, j, k)], in_x)
+        * sp.inv_dual[i].x;
+    let dHx_dy = d4(hx[idx(i, jm, k)], hx[idx(i, j - 1u, k)], hx[id], hx[idx(i, jp, k)], in_y)
+        * sp.inv_dual[j].y;
+
+    // --- Hadamard Product + Summation ---------------------------------
+    ex[id] = ca_v.x * ex[id] + cb_v.x * (dHz_dy - dHy_dz);
+    ey[id] = ca_v.y * ey[id] + cb_v.y * (dHx_dz - dHz_dx);
+    ez[id] = ca_v.z * ez[id] + cb_v.z * (dHy_dx - dHx_dy);
+}
@@ -0,0 +1,171 @@
+// ------------------------------------------------------------------
+// update_f16.wgsl  –  Yee H and E updates on half-precision fields
+//
+// Fields are stored as f16 pairs packed into u32 words (cells 2w and
+// 2w + 1 of the flattened grid) and unpacked to f32 for the update, so
+// only storage is rounded to half precision.  Each thread owns one word
+// and updates both of its cells, so no two threads write the same word.
+// Coefficients and inverse widths are f32, laid out as in
+// update_wide.wgsl.
+// ------------------------------------------------------------------
+
+struct Params {
+    nx: u32,
+    ny: u32,
+    nz: u32,
+    src: u32,
+    hi: f32,
+    lo: f32,
+    _pad0: u32,
+    _pad1: u32,
+}
+
+@group(0) @binding(0) var<uniform> p: Params;
+
+@group(0) @binding(1) var<storage, read_write> ex: array<u32>;
+@group(0) @binding(2) var<storage, read_write> ey: array<u32>;
+@group(0) @binding(3) var<storage, read_write> ez: array<u32>;
+@group(0) @binding(4) var<storage, read_write> hx: array<u32>;
+@group(0) @binding(5) var<storage, read_write> hy: array<u32>;
+@group(0) @binding(6) var<storage, read_write> hz: array<u32>;
+
+// Twelve per cell: CA, CB, CP, CQ with x/y/z lanes each
+@group(0) @binding(7) var<storage, read>       coef: array<f32>;
+
+// Inverse primary widths (entries 0..1023) then dual widths
+// (1024..2047), four lanes per entry
+@group(0) @binding(8) var<storage, read>       sp: array<f32>;
+
+fn idx(i: u32, j: u32, k: u32) -> u32 {
+    return i + p.nx * (j + p.ny * k);
+}
+
+fn c(id: u32, which: u32, lane: u32) -> f32 {
+    return coef[12u * id + 3u * which + lane];
+}
+
+fn inv_primary(i: u32, lane: u32) -> f32 {
+    return sp[4u * i + lane];
+}
+
+fn inv_dual(i: u32, lane: u32) -> f32 {
+    return sp[4u * (1024u + i) + lane];
+}
+
+fn half(word: u32, id: u32) -> f32 {
+    return unpack2x16float(word)[id & 1u];
+}
+
+fn ex_at(id: u32) -> f32 {
+    return half(ex[id >> 1u], id);
+}
+
+fn ey_at(id: u32) -> f32 {
+    return half(ey[id >> 1u], id);
+}
+
+fn ez_at(id: u32) -> f32 {
+    return half(ez[id >> 1u], id);
+}
+
+fn hx_at(id: u32) -> f32 {
+    return half(hx[id >> 1u], id);
+}
+
+fn hy_at(id: u32) -> f32 {
+    return half(hy[id >> 1u], id);
+}
+
+fn hz_at(id: u32) -> f32 {
+    return half(hz[id >> 1u], id);
+}
+
+// New (Hx, Hy, Hz) of cell `id`, or the old values outside the update range
+fn h_cell(id: u32) -> vec3<f32> {
+    let old = vec3<f32>(hx_at(id), hy_at(id), hz_at(id));
+    let i = id % p.nx;
+    let j = (id / p.nx) % p.ny;
+    let k = id / (p.nx * p.ny);
+    // Guard: stay one cell inside upper boundary (need i+1, j+1, k+1)
+    if (id >= p.nx * p.ny * p.nz || i >= p.nx - 1u || j >= p.ny - 1u || k >= p.nz - 1u) {
+        return old;
+    }
+    let dEy_dz = (ey_at(idx(i, j, k + 1u)) - ey_at(id)) * inv_primary(k, 2u);
+    let dEz_dy = (ez_at(idx(i, j + 1u, k)) - ez_at(id)) * inv_primary(j, 1u);
+    let dEz_dx = (ez_at(idx(i + 1u, j, k)) - ez_at(id)) * inv_primary(i, 0u);
+    let dEx_dz = (ex_at(idx(i, j, k + 1u)) - ex_at(id)) * inv_primary(k, 2u);
+    let dEx_dy = (ex_at(idx(i, j + 1u, k)) - ex_at(id)) * inv_primary(j, 1u);
+    let dEy_dx = (ey_at(idx(i + 1u, j, k)) - ey_at(id)) * inv_primary(i, 0u);
+    let cp = vec3<f32>(c(id, 2u, 0u), c(id, 2u, 1u), c(id, 2u, 2u));
+    let cq = vec3<f32>(c(id, 3u, 0u), c(id, 3u, 1u), c(id, 3u, 2u));
+    return cp * old + cq * vec3<f32>(dEy_dz - dEz_dy, dEz_dx - dEx_dz, dEx_dy - dEy_dx);
+}
+
+// New (Ex, Ey, Ez) of cell `id`, or the old values outside the update range
+fn e_cell(id: u32) -> vec3<f32> {
+    let old = vec3<f32>(ex_at(id), ey_at(id), ez_at(id));
+    let i = id % p.nx;
+    let j = (id / p.nx) % p.ny;
+    let k = id / (p.nx * p.ny);
+    // Guard: skip index 0 on each axis (need i-1, j-1, k-1)
+    if (id >= p.nx * p.ny * p.nz || i == 0u || j == 0u || k == 0u) {
+        return old;
+    }
+    let dHz_dy = (hz_at(id) - hz_at(idx(i, j - 1u, k))) * inv_dual(j, 1u);
+    let dHy_dz = (hy_at(id) - hy_at(idx(i, j, k - 1u))) * inv_dual(k, 2u);
+    let dHx_dz = (hx_at(id) - hx_at(idx(i, j, k - 1u))) * inv_dual(k, 2u);
+    let dHz_dx = (hz_at(id) - hz_at(idx(i - 1u, j, k))) * inv_dual(i, 0u);
+    let dHy_dx = (hy_at(id) - hy_at(idx(i - 1u, j, k))) * inv_dual(i, 0u);
+    let dHx_dy = (hx_at(id) - hx_at(idx(i, j - 1u, k))) * inv_dual(j, 1u);
+    let ca = vec3<f32>(c(id, 0u, 0u), c(id, 0u, 1u), c(id, 0u, 2u));
+    let cb = vec3<f32>(c(id, 1u, 0u), c(id, 1u, 1u), c(id, 1u, 2u));
+    return ca * old + cb * vec3<f32>(dHz_dy - dHy_dz, dHx_dz - dHz_dx, dHy_dx - dHx_dy);
+}
+
+// Word index of a 2D-folded 1D dispatch
+fn word(gid: vec3<u32>, groups: vec3<u32>) -> u32 {
+    return gid.x + gid.y * groups.x * 64u;
+}
+
+@compute @workgroup_size(64)
+fn update_h(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+    @builtin(num_workgroups) groups: vec3<u32>,
+) {
+    let w = word(gid, groups);
+    if (2u * w >= p.nx * p.ny * p.nz) {
+        return;
+    }
+    let a = h_cell(2u * w);
+    let b = h_cell(2u * w + 1u);
+    hx[w] = pack2x16float(vec2<f32>(a.x, b.x));
+    hy[w] = pack2x16float(vec2<f32>(a.y, b.y));
+    hz[w] = pack2x16float(vec2<f32>(a.z, b.z));
+}
+
+@compute @workgroup_size(64)
+fn update_e(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+    @builtin(num_workgroups) groups: vec3<u32>,
+) {
+    let w = word(gid, groups);
+    if (2u * w >= p.nx * p.ny * p.nz) {
+        return;
+    }
+    let a = e_cell(2u * w);
+    let b = e_cell(2u * w + 1u);
+    ex[w] = pack2x16float(vec2<f32>(a.x, b.x));
+    ey[w] = pack2x16float(vec2<f32>(a.y, b.y));
+    ez[w] = pack2x16float(vec2<f32>(a.z, b.z));
+}
+
+// Hard source: Ez[src] = hi + lo, keeping the other half of the word
+@compute @workgroup_size(1)
+fn source() {
+    if (p.src >= p.nx * p.ny * p.nz) {
+        return;
+    }
+    var pair = unpack2x16float(ez[p.src >> 1u]);
+    pair[p.src & 1u] = p.hi + p.lo;
+    ez[p.src >> 1u] = pack2x16float(pair);
+}
@@ -4,17 +4,16 @@
 // Hx[i,j,k] = CP * Hx  +  CQ * ( dEy/dz - dEz/dy )
 // Hy[i,j,k] = CP * Hy  +  CQ * ( dEz/dx - dEx/dz )
 // Hz[i,j,k] = CP * Hz  +  CQ * ( dEx/dy - dEy/dx )
+//
+// The last cell of a periodic axis (bit of p.periodic) takes its upper E
+// neighbour from index 0 instead of staying outside the update.
 // ------------------------------------------------------------------
 
 struct Params {
     nx: u32,
     ny: u32,
     nz: u32,
-    _pad: u32,
-    inv_dx: f32,
-    inv_dy: f32,
-    inv_dz: f32,
-    _pad2: f32,
+    periodic: u32,
 }
 
 @group(0) @binding(0) var<uniform> p: Params;
@@ -29,9 +28,17 @@ struct Params {
 @group(0) @binding(5) var<storage, read_write> hy: array<f32>;
 @group(0) @binding(6) var<storage, read_write> hz: array<f32>;
 
-// Material coefficients
-@group(0) @binding(7) var<storage, read>       cp: array<f32>;
-@group(0) @binding(8) var<storage, read>       cq: array<f32>;
+// Inverse cell widths per index along each axis (x/y/z lanes): primary
+// widths for differences of E, dual widths for differences of H.
+struct Spacing {
+    inv_primary: array<vec4<f32>, 1024>,
+    inv_dual: array<vec4<f32>, 1024>,
+}
+
+@group(0) @binding(9) var<uniform> sp: Spacing;
+
+// Material coefficients at bindings 7..8 come from the prepended
+// coeffs_dense.wgsl / coeffs_indexed.wgsl via load_coeffs().
 
 fn idx(i: u32, j: u32, k: u32) -> u32 {
     return i + p.nx * (j + p.ny * k);
@@ -43,31 +50,40 @@ fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
     let j = gid.y;
     let k = gid.z;
 
-    // Guard: stay one cell inside upper boundary (need i+1, j+1, k+1)
-    if (i >= p.nx - 1u || j >= p.ny - 1u || k >= p.nz - 1u) {
+    // Guard: stay one cell inside the upper boundary of each non-periodic
+    // axis (need i+1, j+1, k+1)
+    if (i >= p.nx || j >= p.ny || k >= p.nz) {
+        return;
+    }
+    let periodic = (vec3<u32>(p.periodic) & vec3<u32>(1u, 2u, 4u)) != vec3<u32>(0u);
+    if (any(vec3<bool>(i == p.nx - 1u, j == p.ny - 1u, k == p.nz - 1u) & !periodic)) {
         return;
     }
+    let ip = select(i + 1u, 0u, i == p.nx - 1u);
+    let jp = select(j + 1u, 0u, j == p.ny - 1u);
+    let kp = select(k + 1u, 0u, k == p.nz - 1u);
 
     let id  = idx(i, j, k);
-    let cp_v = cp[id];
-    let cq_v = cq[id];
+    let coeffs = load_coeffs(id);
+    let cp_v = coeffs[0];
+    let cq_v = coeffs[1];
 
     // --- Shift & Add  (finite differences of E) -----------------------
 
     // Hx:  dEy/dz - dEz/dy
-    let dEy_dz = (ey[idx(i, j, k + 1u)] - ey[id]) * p.inv_dz;
-    let dEz_dy = (ez[idx(i, j + 1u, k)] - ez[id]) * p.inv_dy;
+    let dEy_dz = (ey[idx(i, j, kp)] - ey[id]) * sp.inv_primary[k].z;
+    let dEz_dy = (ez[idx(i, jp, k)] - ez[id]) * sp.inv_primary[j].y;
 
     // Hy:  dEz/dx - dEx/dz
-    let dEz_dx = (ez[idx(i + 1u, j, k)] - ez[id]) * p.inv_dx;
-    let dEx_dz = (ex[idx(i, j, k + 1u)] - ex[id]) * p.inv_dz;
+    let dEz_dx = (ez[idx(ip, j, k)] - ez[id]) * sp.inv_primary[i].x;
+    let dEx_dz = (ex[idx(i, j, kp)] - ex[id]) * sp.inv_primary[k].z;
 
     // Hz:  dEx/dy - dEy/dx
-    let dEx_dy = (ex[idx(i, j + 1u, k)] - ex[id]) * p.inv_dy;
-    let dEy_dx = (ey[idx(i + 1u, j, k)] - ey[id]) * p.inv_dx;
+    let dEx_dy = (ex[idx(i, jp, k)] - ex[id]) * sp.inv_primary[j].y;
+    let dEy_dx = (ey[idx(ip, j, k)] - ey[id]) * sp.inv_primary[i].x;
 
     // --- Hadamard Product + Summation ---------------------------------
-    hx[id] = cp_v * hx[id] + cq_v * (dEy_dz - dEz_dy);
-    hy[id] = cp_v * hy[id] + cq_v * (dEz_dx - dEx_dz);
-    hz[id] = cp_v * hz[id] + cq_v * (dEx_dy - dEy_dx);
+    hx[id] = cp_v.x * hx[id] + cq_v.x * (dEy_dz - dEz_dy);
+    hy[id] = cp_v.y * hy[id] + cq_v.y * (dEz_dx - dEx_dz);
+    hz[id] = cp_v.z * hz[id] + cq_v.z * (dEx_dy - dEy_dx);
 }
@@ -0,0 +1,107 @@
+// ------------------------------------------------------------------
+// update_h4.wgsl  –  Magnetic field update, (2,4) stencil
+//
+// Hx[i,j,k] = CP * Hx  +  CQ * ( dEy/dz - dEz/dy )
+// Hy[i,j,k] = CP * Hy  +  CQ * ( dEz/dx - dEx/dz )
+// Hz[i,j,k] = CP * Hz  +  CQ * ( dEx/dy - dEy/dx )
+//
+// Differences of E are fourth order on a uniform mesh,
+//   f(n+1) − f(n)  →  9/8·(f(n+1) − f(n)) − 1/24·(f(n+2) − f(n−1)),
+// falling back to the Yee difference next to the boundary.
+// ------------------------------------------------------------------
+
+struct Params {
+    nx: u32,
+    ny: u32,
+    nz: u32,
+    _pad: u32,
+}
+
+@group(0) @binding(0) var<uniform> p: Params;
+
+// Electric fields (read-only this pass)
+@group(0) @binding(1) var<storage, read>       ex: array<f32>;
+@group(0) @binding(2) var<storage, read>       ey: array<f32>;
+@group(0) @binding(3) var<storage, read>       ez: array<f32>;
+
+// Magnetic fields (read-write)
+@group(0) @binding(4) var<storage, read_write> hx: array<f32>;
+@group(0) @binding(5) var<storage, read_write> hy: array<f32>;
+@group(0) @binding(6) var<storage, read_write> hz: array<f32>;
+
+// Inverse cell widths per index along each axis (x/y/z lanes): primary
+// widths for differences of E, dual widths for differences of H.
+struct Spacing {
+    inv_primary: array<vec4<f32>, 1024>,
+    inv_dual: array<vec4<f32>, 1024>,
+}
+
+@group(0) @binding(9) var<uniform> sp: Spacing;
+
+// Material coefficients at bindings 7..8 come from the prepended
+// coeffs_dense.wgsl / coeffs_indexed.wgsl via load_coeffs().
+
+fn idx(i: u32, j: u32, k: u32) -> u32 {
+    return i + p.nx * (j + p.ny * k);
+}
+
+// b − a, with the (2,4) correction across the outer pair (m, q).
+fn d4(m: f32, a: f32, b: f32, q: f32, inner: bool) -> f32 {
+    if (inner) {
+        return 1.125 * (b - a) - (q - m) / 24.0;
+    }
+    return b - a;
+}
+
+@compute @workgroup_size(4, 4, 4)
+fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    let j = gid.y;
+    let k = gid.z;
+
+    // Guard: stay one cell inside upper boundary (need i+1, j+1, k+1)
+    if (i >= p.nx - 1u || j >= p.ny - 1u || k >= p.nz - 1u) {
+        return;
+    }
+
+    let id  = idx(i, j, k);
+    let coeffs = load_coeffs(id);
+    let cp_v = coeffs[0];
+    let cq_v = coeffs[1];
+
+    // Outer stencil points n − 1 and n + 2, clamped where they don't exist
+    let im = max(i, 1u) - 1u;
+    let jm = max(j, 1u) - 1u;
+    let km = max(k, 1u) - 1u;
+    let ip = min(i + 2u, p.nx - 1u);
+    let jp = min(j + 2u, p.ny - 1u);
+    let kp = min(k + 2u, p.nz - 1u);
+    let in_x = i >= 1u && i + 2u < p.nx;
+    let in_y = j >= 1u && j + 2u < p.ny;
+    let in_z = k >= 1u && k + 2u < p.nz;
+
+    // --- Shift & Add  (finite differences of E) -----------------------
+
+    // Hx:  dEy/dz - dEz/dy
+    let dEy_dz = d4(ey[idx(i, j, km)], ey[id], ey[idx(i, j, k + 1u)], ey[idx(i, j, kp)], in_z)
+        * sp.inv_primary[k].z;
+    let dEz_dy = d4(ez[idx(i, jm, k)], ez[id], ez[idx(i, j + 1u, k)], ez[idx(i, jp, k)], in_y)
+        * sp.inv_primary[j].y;
+
+    // Hy:  dEz/dx - dEx/dz
+    let dEz_dx = d4(ez[idx(im, j, k)], ez[id], ez[idx(i + 1u, j, k)], ez[idx(ip, j, k)], in_x)
+        * sp.inv_primary[i].x;
+    let dEx_dz = d4(ex[idx(i, j, km)], ex[id], ex[idx(i, j, k + 1u)], ex[idx(i, j, kp)], in_z)
+        * sp.inv_primary[k].z;
+
+    // Hz:  dEx/dy - dEy/dx
+    let dEx_dy = d4(ex[idx(i, jm, k)], ex[id], ex[idx(i, j + 1u, k)], ex[idx(i, jp, k)], in_y)
+        * sp.inv_primary[j].y;
+    let dEy_dx = d4(ey[idx(im, j, k)], ey[id], ey[idx(i + 1u, j, k)], ey[idx(ip, j, k)], in_x)
+        * sp.inv_primary[i].x;
+
+    // --- Hadamard Product + Summation ---------------------------------
+    hx[id] = cp_v.x * hx[id] + cq_v.x * (dEy_dz - dEz_dy);
+    hy[id] = cp_v.y * hy[id] + cq_v.y * (dEz_dx - dEx_dz);
+    hz[id] = cp_v.z * hz[id] + cq_v.z * (dEx_dy - dEy_dx);
+}
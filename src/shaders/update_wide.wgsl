@@ -0,0 +1,108 @@
+// ------------------------------------------------------------------
+// update_wide.wgsl  –  Yee H and E updates in extended precision
+//
+// Same stencil as update_h.wgsl / update_e.wgsl on `Real` values from
+// the prepended real_f64.wgsl or real_ds.wgsl.  Coefficients and inverse
+// widths are stored as `Real` too, so nothing in the update is rounded to
+// f32.
+// ------------------------------------------------------------------
+
+struct Params {
+    nx: u32,
+    ny: u32,
+    nz: u32,
+    src: u32,
+    hi: f32,
+    lo: f32,
+    _pad0: u32,
+    _pad1: u32,
+}
+
+@group(0) @binding(0) var<uniform> p: Params;
+
+@group(0) @binding(1) var<storage, read_write> ex: array<Real>;
+@group(0) @binding(2) var<storage, read_write> ey: array<Real>;
+@group(0) @binding(3) var<storage, read_write> ez: array<Real>;
+@group(0) @binding(4) var<storage, read_write> hx: array<Real>;
+@group(0) @binding(5) var<storage, read_write> hy: array<Real>;
+@group(0) @binding(6) var<storage, read_write> hz: array<Real>;
+
+// Twelve per cell: CA, CB, CP, CQ with x/y/z lanes each
+@group(0) @binding(7) var<storage, read>       coef: array<Real>;
+
+// Inverse primary widths (entries 0..1023) then dual widths
+// (1024..2047), four lanes per entry as in the f32 Spacing table
+@group(0) @binding(8) var<storage, read>       sp: array<Real>;
+
+fn idx(i: u32, j: u32, k: u32) -> u32 {
+    return i + p.nx * (j + p.ny * k);
+}
+
+// Coefficient `which` (0 CA, 1 CB, 2 CP, 3 CQ) of component `lane`
+fn c(id: u32, which: u32, lane: u32) -> Real {
+    return coef[12u * id + 3u * which + lane];
+}
+
+fn inv_primary(i: u32, lane: u32) -> Real {
+    return sp[4u * i + lane];
+}
+
+fn inv_dual(i: u32, lane: u32) -> Real {
+    return sp[4u * (1024u + i) + lane];
+}
+
+@compute @workgroup_size(4, 4, 4)
+fn update_h(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    let j = gid.y;
+    let k = gid.z;
+
+    // Guard: stay one cell inside upper boundary (need i+1, j+1, k+1)
+    if (i >= p.nx - 1u || j >= p.ny - 1u || k >= p.nz - 1u) {
+        return;
+    }
+    let id = idx(i, j, k);
+
+    let dEy_dz = r_mul(r_sub(ey[idx(i, j, k + 1u)], ey[id]), inv_primary(k, 2u));
+    let dEz_dy = r_mul(r_sub(ez[idx(i, j + 1u, k)], ez[id]), inv_primary(j, 1u));
+    let dEz_dx = r_mul(r_sub(ez[idx(i + 1u, j, k)], ez[id]), inv_primary(i, 0u));
+    let dEx_dz = r_mul(r_sub(ex[idx(i, j, k + 1u)], ex[id]), inv_primary(k, 2u));
+    let dEx_dy = r_mul(r_sub(ex[idx(i, j + 1u, k)], ex[id]), inv_primary(j, 1u));
+    let dEy_dx = r_mul(r_sub(ey[idx(i + 1u, j, k)], ey[id]), inv_primary(i, 0u));
+
+    hx[id] = r_add(r_mul(c(id, 2u, 0u), hx[id]), r_mul(c(id, 3u, 0u), r_sub(dEy_dz, dEz_dy)));
+    hy[id] = r_add(r_mul(c(id, 2u, 1u), hy[id]), r_mul(c(id, 3u, 1u), r_sub(dEz_dx, dEx_dz)));
+    hz[id] = r_add(r_mul(c(id, 2u, 2u), hz[id]), r_mul(c(id, 3u, 2u), r_sub(dEx_dy, dEy_dx)));
+}
+
+@compute @workgroup_size(4, 4, 4)
+fn update_e(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    let j = gid.y;
+    let k = gid.z;
+
+    // Guard: skip index 0 on each axis (need i-1, j-1, k-1)
+    if (i == 0u || j == 0u || k == 0u || i >= p.nx || j >= p.ny || k >= p.nz) {
+        return;
+    }
+    let id = idx(i, j, k);
+
+    let dHz_dy = r_mul(r_sub(hz[id], hz[idx(i, j - 1u, k)]), inv_dual(j, 1u));
+    let dHy_dz = r_mul(r_sub(hy[id], hy[idx(i, j, k - 1u)]), inv_dual(k, 2u));
+    let dHx_dz = r_mul(r_sub(hx[id], hx[idx(i, j, k - 1u)]), inv_dual(k, 2u));
+    let dHz_dx = r_mul(r_sub(hz[id], hz[idx(i - 1u, j, k)]), inv_dual(i, 0u));
+    let dHy_dx = r_mul(r_sub(hy[id], hy[idx(i - 1u, j, k)]), inv_dual(i, 0u));
+    let dHx_dy = r_mul(r_sub(hx[id], hx[idx(i, j - 1u, k)]), inv_dual(j, 1u));
+
+    ex[id] = r_add(r_mul(c(id, 0u, 0u), ex[id]), r_mul(c(id, 1u, 0u), r_sub(dHz_dy, dHy_dz)));
+    ey[id] = r_add(r_mul(c(id, 0u, 1u), ey[id]), r_mul(c(id, 1u, 1u), r_sub(dHx_dz, dHz_dx)));
+    ez[id] = r_add(r_mul(c(id, 0u, 2u), ez[id]), r_mul(c(id, 1u, 2u), r_sub(dHy_dx, dHx_dy)));
+}
+
+// Hard source: Ez[src] = hi + lo
+@compute @workgroup_size(1)
+fn source() {
+    if (p.src < p.nx * p.ny * p.nz) {
+        ez[p.src] = r_pair(p.hi, p.lo);
+    }
+}
@@ -0,0 +1,237 @@
+//! Infinitesimally thin conductive sheets (graphene, thin metal films).
+//!
+//! A sheet lies on a grid plane and only affects the two E components
+//! tangential to it.  Its surface conductivity σ_s (S) is smeared over one
+//! cell as a volume conductivity σ_s/Δn on those edges, so the sheet costs
+//! nothing in resolution.  The dispersive variant adds a Drude current
+//! through the sparse ADE pass.
+//!
+//! Layers with a finite thickness below one cell (radome walls, coatings)
+//! use [`ThinLayer`], which blends the layer into the coefficients of the
+//! edges it crosses: parallel averaging for tangential E, series averaging
+//! for the normal component.
+
+use serde::Serialize;
+
+use crate::ade::AdeEdge;
+use crate::grid::{Axis, Grid};
+use crate::materials::{Coefficients, Material, EPS0};
+
+const Q_E: f64 = 1.602176634e-19; // elementary charge (C)
+const HBAR: f64 = 1.054571817e-34; // reduced Planck constant (J·s)
+const K_B: f64 = 1.380649e-23; // Boltzmann constant (J/K)
+
+/// Surface-conductivity model of a sheet.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub enum SheetModel {
+    /// Frequency-independent surface conductivity σ_s (S).
+    Constant { sigma_s: f64 },
+    /// Drude surface conductivity σ_s(ω) = σ_dc / (1 + jω/γ).
+    Drude { sigma_dc: f64, gamma: f64 },
+}
+
+impl SheetModel {
+    /// Intraband (Kubo) conductivity of graphene as a Drude model.
+    ///
+    /// `mu_c` is the chemical potential (eV), `tau` the relaxation time (s)
+    /// and `temperature` in kelvin.
+    pub fn graphene(mu_c: f64, tau: f64, temperature: f64) -> SheetModel {
+        let kt = K_B * temperature;
+        let mu = mu_c * Q_E;
+        let weight = mu / kt + 2.0 * (1.0 + (-mu / kt).exp()).ln();
+        let d = Q_E * Q_E * kt / (std::f64::consts::PI * HBAR * HBAR) * weight;
+        SheetModel::Drude {
+            sigma_dc: d * tau,
+            gamma: 1.0 / tau,
+        }
+    }
+}
+
+/// A rectangular sheet on the plane `normal = index`.
+///
+/// `u` and `v` are half-open cell ranges along the two tangential axes, in
+/// the order returned by [`Axis::tangential`].
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct ConductiveSheet {
+    pub normal: Axis,
+    pub index: u32,
+    pub u: (u32, u32),
+    pub v: (u32, u32),
+    pub model: SheetModel,
+}
+
+impl ConductiveSheet {
+    /// Rasterize the sheet into `coeffs`, returning any ADE currents it needs.
+    pub fn apply(&self, grid: &Grid, coeffs: &mut Coefficients) -> Vec<AdeEdge> {
+        let (ta, tb) = self.normal.tangential();
+        let thickness = grid.dual_width(self.normal, self.index);
+        let mut edges = Vec::new();
+
+        for a in self.u.0..self.u.1.min(grid.cells(ta)) {
+            for b in self.v.0..self.v.1.min(grid.cells(tb)) {
+                let id = self.cell(grid, a, b);
+                for comp in [ta, tb] {
+                    match self.model {
+                        SheetModel::Constant { sigma_s } => {
+                            coeffs.add_e_conductivity(id, comp, sigma_s / thickness, grid.dt);
+                        }
+                        SheetModel::Drude { sigma_dc, gamma } => edges.push(AdeEdge::drude(
+                            coeffs,
+                            id,
+                            comp,
+                            sigma_dc / thickness,
+                            gamma,
+                            grid.dt,
+                        )),
+                    }
+                }
+            }
+        }
+        edges
+    }
+
+    /// Linear index of the cell at tangential coordinates (`a`, `b`).
+    fn cell(&self, grid: &Grid, a: u32, b: u32) -> usize {
+        plane_cell(grid, self.normal, self.index, a, b)
+    }
+}
+
+/// A dielectric/conductive layer thinner than a cell.
+///
+/// `center` and `thickness` are in metres along `normal`, in the frame of
+/// the grid origin; `u`/`v` are half-open cell ranges as for
+/// [`ConductiveSheet`].  The layer is assumed non-magnetic, so only E
+/// coefficients change.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct ThinLayer {
+    pub normal: Axis,
+    pub center: f64,
+    pub thickness: f64,
+    pub u: (u32, u32),
+    pub v: (u32, u32),
+    pub material: Material,
+}
+
+impl ThinLayer {
+    /// Blend the layer into the E coefficients of every edge it overlaps,
+    /// on the cells of a graded grid as on a uniform one.
+    pub fn apply(&self, grid: &Grid, coeffs: &mut Coefficients) {
+        let (ta, tb) = self.normal.tangential();
+        let (lo, hi) = (
+            self.center - self.thickness / 2.0,
+            self.center + self.thickness / 2.0,
+        );
+        let eps_s = self.material.eps_r * EPS0;
+        let sigma_s = self.material.sigma;
+
+        // Fraction of [a, b] (m) covered by the layer.
+        let fill = |a: f64, b: f64| ((hi.min(b) - lo.max(a)) / (b - a)).max(0.0);
+        let node = |p: u32| grid.node(self.normal, p);
+        // Centre of cell p along the normal.
+        let centre = |p: u32| node(p) + grid.width(self.normal, p) / 2.0;
+
+        for p in grid.cell_range(self.normal, lo, hi) {
+            // Tangential E lives on plane p, dual cell between the centres
+            // of cells p − 1 and p (half a cell below plane 0).
+            let below = match p {
+                0 => node(0) - grid.width(self.normal, 0) / 2.0,
+                _ => centre(p - 1),
+            };
+            let f_t = fill(below, centre(p));
+            // Normal E lives in cell p, between planes p and p + 1.
+            let f_n = fill(node(p), node(p + 1));
+
+            for a in self.u.0..self.u.1.min(grid.cells(ta)) {
+                for b in self.v.0..self.v.1.min(grid.cells(tb)) {
+                    let id = plane_cell(grid, self.normal, p, a, b);
+                    if f_t > 0.0 {
+                        for comp in [ta, tb] {
+                            if let Some((eps, sigma)) = coeffs.e_material(id, comp, grid.dt) {
+                                coeffs.set_e_material(
+                                    id,
+                                    comp,
+                                    f_t * eps_s + (1.0 - f_t) * eps,
+                                    f_t * sigma_s + (1.0 - f_t) * sigma,
+                                    grid.dt,
+                                );
+                            }
+                        }
+                    }
+                    if f_n > 0.0 {
+                        if let Some((eps, sigma)) = coeffs.e_material(id, self.normal, grid.dt) {
+                            // Series (harmonic) mean of ε; σ to first order in
+                            // the loss tangent of 1/(ε − jσ/ω).
+                            let eps_eff = 1.0 / (f_n / eps_s + (1.0 - f_n) / eps);
+                            let sigma_eff = eps_eff
+                                * eps_eff
+                                * (f_n * sigma_s / (eps_s * eps_s)
+                                    + (1.0 - f_n) * sigma / (eps * eps));
+                            coeffs.set_e_material(id, self.normal, eps_eff, sigma_eff, grid.dt);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Linear index of the cell at `index` along `normal` and tangential
+/// coordinates (`a`, `b`) in [`Axis::tangential`] order.
+fn plane_cell(grid: &Grid, normal: Axis, index: u32, a: u32, b: u32) -> usize {
+    match normal {
+        Axis::X => grid.idx(index, a, b),
+        Axis::Y => grid.idx(b, index, a),
+        Axis::Z => grid.idx(a, b, index),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Graded z widths: a fine pair of cells between two coarse ones.
+    static WIDTHS: [f64; 4] = [1e-3, 0.5e-3, 0.5e-3, 1e-3];
+
+    #[test]
+    fn a_thin_layer_blends_by_the_local_cells_of_a_graded_offset_grid() {
+        let grid = Grid {
+            origin: [0.0, 0.0, 0.01],
+            graded: [None, None, Some(&WIDTHS)],
+            ..Grid::uniform([4; 3], 1e-3, 1e-12)
+        };
+        let material = Material {
+            eps_r: 5.0,
+            ..Material::VACUUM
+        };
+        // 0.1 mm on plane 2, centred between the fine cells
+        let layer = ThinLayer {
+            normal: Axis::Z,
+            center: grid.node(Axis::Z, 2),
+            thickness: 0.1e-3,
+            u: (0, 4),
+            v: (0, 4),
+            material,
+        };
+        let mut coeffs = Coefficients::uniform(&grid, &Material::VACUUM);
+        layer.apply(&grid, &mut coeffs);
+        // ε_r at the edges of cell (1, 1, k), through the f32 coefficients
+        let eps = |k: u32, axis: Axis| {
+            let (eps, _) = coeffs.e_material(grid.idx(1, 1, k), axis, grid.dt).unwrap();
+            eps / EPS0
+        };
+
+        // A fifth of the 0.5 mm dual cell of plane 2, none of the others
+        let blended = 0.2 * 5.0 + 0.8;
+        assert!((eps(2, Axis::X) - blended).abs() < 1e-6);
+        assert!((eps(2, Axis::Y) - blended).abs() < 1e-6);
+        for k in [1, 3] {
+            assert!((eps(k, Axis::X) - 1.0).abs() < 1e-6);
+        }
+        // A tenth of either fine cell, in series
+        let series = 1.0 / (0.1 / 5.0 + 0.9);
+        for k in [1, 2] {
+            assert!((eps(k, Axis::Z) - series).abs() < 1e-6);
+        }
+        assert!((eps(0, Axis::Z) - 1.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,163 @@
+//! Shielding effectiveness of an enclosure.
+//!
+//! A uniform current sheet across the grid (as for [`crate::unit_cell`])
+//! lights the enclosure with a plane wave polarised along `polarization`,
+//! and DFT monitors record E and H at the interior `points`.  As for
+//! [`crate::reflectance`], the program runs twice: a reference run with the
+//! sheet alone gives the incident field at the points, and the run with the
+//! enclosure the shielded field, so that per point and frequency
+//!
+//!   SE_E(f) = 20·log₁₀(|E_inc| / |E|),   SE_H(f) = 20·log₁₀(|H_inc| / |H|)
+//!
+//! in dB, with |E| and |H| from the three components on the edges and faces
+//! of the point's cell.  `<name>_shielding.csv` holds both per point.
+//!
+//! The sheet spans the whole cross section, so without periodic axes the
+//! side walls bound the incident wave as a waveguide would; for a plane
+//! wave the run has to end before their reflections reach the enclosure,
+//! or the polarisation must lie along the normal of a pair of PEC walls.
+//! The points should avoid the enclosure's apertures and walls, where the
+//! field varies over a cell.
+
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use rustfft::num_complex::Complex64;
+use serde::Serialize;
+
+use crate::ade::AdeEdge;
+use crate::config::ConfigError;
+use crate::dft::{DftMonitor, Region, Spectrum};
+use crate::grid::{Axis, Field, Grid};
+use crate::materials::Coefficients;
+use crate::sources::Waveform;
+use crate::unit_cell;
+
+/// Plane-wave illumination along +`normal` and the interior field points.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct Shielding {
+    pub name: &'static str,
+    pub normal: Axis,
+    /// E plane of the source sheet, below the enclosure.
+    pub source: u32,
+    /// Direction of the incident E, across the normal.
+    pub polarization: Axis,
+    /// Incident E amplitude (V/m) and waveform.
+    pub amplitude: f64,
+    pub waveform: Waveform,
+    /// Cells inside the enclosure.
+    pub points: &'static [[u32; 3]],
+    /// Frequencies in Hz.
+    pub frequencies: &'static [f64],
+}
+
+impl Shielding {
+    /// Check the polarisation and that the source sheet lies on the grid.
+    pub fn check(&self, grid: &Grid) -> Result<(), ConfigError> {
+        let problem = if self.polarization == self.normal {
+            "the polarisation must be across the normal"
+        } else if self.source >= grid.cells(self.normal) {
+            "the source plane is outside the grid"
+        } else {
+            return Ok(());
+        };
+        Err(ConfigError(format!("shielding {}: {problem}", self.name)))
+    }
+
+    /// Driven E edges of the source sheet, appending its drive to `drives`.
+    pub fn source_edges(
+        &self,
+        grid: &Grid,
+        coeffs: &Coefficients,
+        drives: &mut Vec<(f64, Waveform)>,
+    ) -> Vec<AdeEdge> {
+        drives.push((self.amplitude, self.waveform));
+        let slot = (drives.len() - 1) as u32;
+        unit_cell::sheet_edges(
+            grid,
+            coeffs,
+            self.normal,
+            self.source,
+            self.polarization,
+            slot,
+        )
+    }
+
+    /// One DFT monitor of all six components per point.
+    pub fn dft_monitors(&self) -> Vec<DftMonitor> {
+        self.points
+            .iter()
+            .map(|&cell| DftMonitor {
+                name: self.name,
+                fields: &Field::ALL,
+                region: Region::Point(cell),
+                frequencies: self.frequencies,
+            })
+            .collect()
+    }
+
+    /// SE_E and SE_H (dB) per point and frequency from the
+    /// [`dft_monitors`](Self::dft_monitors) spectra of the reference and
+    /// enclosure runs.
+    pub fn effectiveness(
+        &self,
+        reference: &[Spectrum],
+        structure: &[Spectrum],
+    ) -> Vec<Vec<[f64; 2]>> {
+        let magnitude = |spectrum: &Spectrum, f: usize, field: fn(Axis) -> Field| {
+            [Axis::X, Axis::Y, Axis::Z]
+                .iter()
+                .map(|&a| {
+                    let [re, im] = spectrum.value(field(a), f, 0).unwrap();
+                    Complex64::new(re as f64, im as f64).norm_sqr()
+                })
+                .sum::<f64>()
+                .sqrt()
+        };
+        reference
+            .iter()
+            .zip(structure)
+            .take(self.points.len())
+            .map(|(incident, shielded)| {
+                (0..self.frequencies.len())
+                    .map(|f| {
+                        [Field::E, Field::H].map(|field| {
+                            let ratio =
+                                magnitude(incident, f, field) / magnitude(shielded, f, field);
+                            20.0 * ratio.log10()
+                        })
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// Write `<name>_shielding.csv`; returns the lowest SE_E over the points per
+/// frequency.
+pub fn write_results(dir: &Path, spec: &Shielding, se: &[Vec<[f64; 2]>]) -> io::Result<Vec<f64>> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!("{}_shielding.csv", spec.name));
+    let mut file = BufWriter::new(fs::File::create(path)?);
+    write!(file, "frequency")?;
+    for [i, j, k] in spec.points {
+        write!(file, ",se_e_{i}_{j}_{k},se_h_{i}_{j}_{k}")?;
+    }
+    writeln!(file)?;
+    let mut lowest = Vec::new();
+    for (f, frequency) in spec.frequencies.iter().enumerate() {
+        write!(file, "{frequency:e}")?;
+        for point in se {
+            write!(file, ",{:.3},{:.3}", point[f][0], point[f][1])?;
+        }
+        writeln!(file)?;
+        lowest.push(
+            se.iter()
+                .map(|point| point[f][0])
+                .fold(f64::INFINITY, f64::min),
+        );
+    }
+    file.flush()?;
+    Ok(lowest)
+}
@@ -0,0 +1,218 @@
+//! Surface impedance boundary condition (SIBC) for good conductors.
+//!
+//! Meshing the skin depth of a metal at microwave frequencies is hopeless,
+//! so conductor objects are treated as PEC inside and the tangential E on
+//! their staircased surface is tied to the adjacent tangential H through the
+//! frequency-dependent Leontovich impedance Z_s = sqrt(jωμ/σ).  The time
+//! convolution is evaluated recursively (see `shaders/sibc.wgsl`).
+
+use bytemuck::{Pod, Zeroable};
+use serde::Serialize;
+use wgpu::util::DeviceExt;
+
+use crate::geometry::Shape;
+use crate::gpu::{bg_entry, bgl_storage_entry, bgl_uniform_entry, compute_pipeline, groups_1d};
+use crate::grid::{Axis, Grid};
+use crate::materials::{Coefficients, MU0};
+
+/// Number of exponential terms in the sqrt(s) expansion (matches WGSL).
+pub const NPOLES: usize = 16;
+
+/// A good-conductor object whose losses are modelled by the SIBC.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct SibcObject {
+    pub shape: Shape,
+    /// Bulk conductivity σ (S/m).
+    pub sigma: f64,
+    /// Relative permeability of the conductor.
+    pub mu_r: f64,
+}
+
+/// One surface E edge driven by the impedance condition (must match WGSL).
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct SibcEdge {
+    pub e_cell: u32,
+    pub e_comp: u32,
+    pub h_cell: u32,
+    pub h_comp: u32,
+    pub coef: f32,
+    pub _pad: [u32; 3],
+}
+
+impl SibcObject {
+    /// Make interior edges PEC and collect the surface edges.
+    ///
+    /// An E edge touches four cells; if all are inside the conductor it is
+    /// PEC, if some are it lies on the surface.  The outward normal is taken
+    /// along the transverse axis with the larger inside/outside imbalance and
+    /// selects which H sample (half a cell outside) drives the edge.
+    pub fn apply(&self, grid: &Grid, coeffs: &mut Coefficients) -> Vec<SibcEdge> {
+        let mask = grid.mask(&self.shape);
+        let z0 = (self.mu_r * MU0 / self.sigma).sqrt();
+        let mut edges = Vec::new();
+
+        for k in 1..grid.nz {
+            for j in 1..grid.ny {
+                for i in 1..grid.nx {
+                    let p = [i, j, k];
+                    let id = grid.idx(i, j, k);
+                    for a in [Axis::X, Axis::Y, Axis::Z] {
+                        let (b, c) = a.tangential();
+                        let (b, c) = (b.lane(), c.lane());
+                        // inside(db, dc): cell shifted by −db along b, −dc along c
+                        let inside = |db: u32, dc: u32| -> i32 {
+                            let mut q = p;
+                            q[b] -= db;
+                            q[c] -= dc;
+                            mask[grid.idx(q[0], q[1], q[2])] as i32
+                        };
+                        let cells = [inside(0, 0), inside(1, 0), inside(0, 1), inside(1, 1)];
+                        let count: i32 = cells.iter().sum();
+                        if count == 0 {
+                            continue;
+                        }
+                        if count == 4 {
+                            coeffs.set_e(id, a, 0.0, 0.0);
+                            continue;
+                        }
+
+                        let imb_c = (cells[2] + cells[3]) - (cells[0] + cells[1]);
+                        let imb_b = (cells[1] + cells[3]) - (cells[0] + cells[2]);
+                        let (h_axis, shift, outward, coef) =
+                            if imb_c != 0 && imb_c.abs() >= imb_b.abs() {
+                                // normal ±c, driven by H_b:  E_a = ∓Z H_b
+                                (b, c, imb_c > 0, if imb_c > 0 { -z0 } else { z0 })
+                            } else if imb_b != 0 {
+                                // normal ±b, driven by H_c:  E_a = ±Z H_c
+                                (c, b, imb_b > 0, if imb_b > 0 { z0 } else { -z0 })
+                            } else {
+                                // diagonal contact: no well-defined normal
+                                coeffs.set_e(id, a, 0.0, 0.0);
+                                continue;
+                            };
+
+                        let mut q = p;
+                        if !outward {
+                            q[shift] -= 1;
+                        }
+                        edges.push(SibcEdge {
+                            e_cell: id as u32,
+                            e_comp: a.lane() as u32,
+                            h_cell: grid.idx(q[0], q[1], q[2]) as u32,
+                            h_comp: h_axis as u32,
+                            coef: coef as f32,
+                            _pad: [0; 3],
+                        });
+                    }
+                }
+            }
+        }
+        edges
+    }
+}
+
+/// Quadrature expansion 1/sqrt(s) ≈ Σ w_p/(s + a_p) with log-spaced rates
+/// between `omega_min` and `omega_max`, returned as (e_p, g_p, w_p, a_p).
+pub fn sibc_poles(dt: f64, omega_min: f64, omega_max: f64) -> [[f32; 4]; NPOLES] {
+    let (u0, u1) = (omega_min.ln(), omega_max.ln());
+    let h = (u1 - u0) / (NPOLES - 1) as f64;
+    std::array::from_fn(|p| {
+        let a = (u0 + h * p as f64).exp();
+        let w = h * a.sqrt() / std::f64::consts::PI;
+        let decay = (-a * dt).exp();
+        [decay as f32, ((1.0 - decay) / a) as f32, w as f32, a as f32]
+    })
+}
+
+/// GPU resources for the SIBC pass.
+pub struct SibcPass {
+    pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    /// Recursive-convolution terms of the edges, kept across steps.
+    buf_psi: wgpu::Buffer,
+    count: u32,
+}
+
+impl SibcPass {
+    /// Returns `None` when no conductor surface is present.
+    pub fn new(
+        device: &wgpu::Device,
+        edges: &[SibcEdge],
+        poles: &[[f32; 4]; NPOLES],
+        h_fields: [&wgpu::Buffer; 3],
+        e_fields: [&wgpu::Buffer; 3],
+    ) -> Option<Self> {
+        if edges.is_empty() {
+            return None;
+        }
+
+        let buf_poles = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("sibc_poles"),
+            contents: bytemuck::cast_slice(poles),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let buf_edges = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("sibc_edges"),
+            contents: bytemuck::cast_slice(edges),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let buf_psi = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("sibc_psi"),
+            contents: bytemuck::cast_slice(&vec![0.0_f32; edges.len() * NPOLES]),
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let mut layout = vec![
+            bgl_uniform_entry(0),
+            bgl_storage_entry(1, true),
+            bgl_storage_entry(2, false),
+        ];
+        layout.extend((3..6).map(|b| bgl_storage_entry(b, true)));
+        layout.extend((6..9).map(|b| bgl_storage_entry(b, false)));
+        let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("sibc_bgl"),
+            entries: &layout,
+        });
+        let pipeline = compute_pipeline(device, "sibc", include_str!("shaders/sibc.wgsl"), &bgl);
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("bg_sibc"),
+            layout: &bgl,
+            entries: &[
+                bg_entry(0, buf_poles.as_entire_binding()),
+                bg_entry(1, buf_edges.as_entire_binding()),
+                bg_entry(2, buf_psi.as_entire_binding()),
+                bg_entry(3, h_fields[0].as_entire_binding()),
+                bg_entry(4, h_fields[1].as_entire_binding()),
+                bg_entry(5, h_fields[2].as_entire_binding()),
+                bg_entry(6, e_fields[0].as_entire_binding()),
+                bg_entry(7, e_fields[1].as_entire_binding()),
+                bg_entry(8, e_fields[2].as_entire_binding()),
+            ],
+        });
+
+        Some(SibcPass {
+            pipeline,
+            bind_group,
+            buf_psi,
+            count: edges.len() as u32,
+        })
+    }
+
+    /// The buffer of the edges' state, for checkpoints.
+    pub fn state(&self) -> &wgpu::Buffer {
+        &self.buf_psi
+    }
+
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder) {
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("SIBC"),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.dispatch_workgroups(groups_1d(self.count, 64), 1, 1);
+    }
+}
@@ -0,0 +1,3053 @@
+//! The 3D solver as a steppable value, and the program around it.
+//!
+//! [`Simulation::new`] opens the GPU, builds the coefficient maps and every
+//! pass the [`Config`] asks for, or sets up the CPU solver of
+//! [`crate::cpu`] with [`Backend::Cpu`]; [`Simulation::step`] advances the
+//! fields by one Δt (sources, updates, sub-cell models, probes and
+//! monitors) and [`Simulation::finish`] writes the analyses of the run.  [`run`] is the
+//! whole program of the binary: the reference run of normalised spectra if
+//! needed, the run of the structure, the normalised results and
+//! results.json.
+
+use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+// std's clock panics in the browser
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+
+use tracing::{debug, debug_span, info, warn};
+use wgpu::util::DeviceExt;
+
+use crate::adapters::{self, AdapterChoice, Backend};
+use crate::ade::{AdeEdge, AdePass};
+use crate::adi::{self, AdiPass};
+use crate::backend::{self, ComputeBackend, SourceDrive};
+use crate::bands;
+use crate::bench::KernelTimer;
+use crate::builder::SimulationBuilder;
+use crate::checkpoint::{self, Checkpoint};
+use crate::config::{Config, ConfigError, OutputFormat, Scene, Verbosity};
+use crate::corrections::{HCorrectionPass, HCorrections};
+use crate::cosim::CosimPass;
+use crate::cpu::CpuRun;
+use crate::dft::{self, DftMonitor, DftPass, Spectrum};
+use crate::distributed::DistributedRun;
+use crate::divergence::{self, Cause, Divergence, DivergencePass};
+use crate::energy::EnergyPass;
+use crate::error::FdtdError;
+use crate::flux::{self, FluxBox, FluxMonitor, FluxPass};
+use crate::gpu::{self, DeviceWatch, Uploads, MAX_CELLS};
+use crate::gpu::{bg_entry, bgl_storage_entry, bgl_uniform_entry, spacing_table, GpuParams};
+use crate::grid::{Axis, Field, Grid};
+use crate::harminv;
+use crate::hie::HiePass;
+use crate::hooks::{StepCallback, StepFields, StepState};
+use crate::interrupt;
+use crate::kspace::{self, KSpacePass};
+use crate::manifest::{self, RunInfo};
+use crate::materials::{CoefficientStorage, Coefficients, IndexedCoefficients, Material, EPS0};
+use crate::memory::MemoryEstimate;
+use crate::modes::{self, ModeMonitor};
+use crate::modulation::ModulationPass;
+use crate::monitors::{MonitorPass, Span};
+use crate::moving_window::MovingWindowPass;
+use crate::pattern::{self, PatternCuts};
+use crate::ports::{self, FeedPort};
+use crate::precision::{Precision, PrecisionPass};
+use crate::probes::{self, Location, Probe, ProbeOutput, ProbeSet, ProbeWriter, Quantity};
+use crate::progress::{self, Progress};
+use crate::purcell::{self, PurcellDipole};
+use crate::rcs::{self, Rcs};
+use crate::reduced::{self, Mode, ReducedSolver};
+use crate::reflectance::{self, ReflectionTransmission};
+use crate::sar;
+use crate::shielding::{self, Shielding};
+use crate::sibc::{self, SibcEdge, SibcPass};
+use crate::sinks::{JsonLinesSink, MatSink, OutputEvent, OutputSink};
+use crate::slices::SliceWriter;
+use crate::snapshots::SnapshotWriter;
+use crate::sources::{SourcePass, Waveform};
+use crate::spectra;
+use crate::stability::{Check, Scheme, Stability};
+use crate::subgrid::SubgridPass;
+use crate::tdr;
+use crate::thermal;
+use crate::touchstone;
+use crate::unit_cell::{self, UnitCell};
+use crate::C0;
+
+/// Sparse per-edge data produced by sub-cell models.
+pub(crate) struct Subcell {
+    pub(crate) ade_edges: Vec<AdeEdge>,
+    /// (amplitude, waveform) of each ADE drive slot.
+    pub(crate) drives: Vec<(f64, Waveform)>,
+    /// Drive slots set by the circuit co-simulation instead.
+    pub(crate) circuit_slots: Vec<u32>,
+    pub(crate) sibc_edges: Vec<SibcEdge>,
+    pub(crate) h_corrections: HCorrections,
+}
+
+/// Build material coefficient maps (CA, CB, CP, CQ) plus the sparse edge
+/// lists needed by sub-cell models (ADE currents, SIBC surfaces, …).
+/// For free space:  σ = σ_m = 0  →  CA = CP = 1,  CB = Δt/ε₀,  CQ = Δt/μ₀.
+pub(crate) fn build_coefficients(
+    config: &Config,
+    grid: &Grid,
+    scene: Scene,
+) -> Result<(Coefficients, Subcell), FdtdError> {
+    let mut coeffs = Coefficients::uniform(grid, &Material::VACUUM);
+    let mut sub = Subcell {
+        ade_edges: Vec::new(),
+        drives: Vec::new(),
+        circuit_slots: Vec::new(),
+        sibc_edges: Vec::new(),
+        h_corrections: HCorrections::default(),
+    };
+    if scene == Scene::Reference {
+        for element in &config.lumped {
+            sub.ade_edges
+                .extend(element.apply(grid, &mut coeffs, &mut sub.drives));
+        }
+        if let Some(tdr) = config.tdr {
+            let source = tdr.source(&config.ports[tdr.port]);
+            sub.ade_edges
+                .extend(source.apply(grid, &mut coeffs, &mut sub.drives));
+        }
+        if let Some(cell) = config.unit_cell {
+            sub.ade_edges
+                .extend(cell.source_edges(grid, &coeffs, &mut sub.drives));
+        }
+        if let Some(spec) = config.shielding {
+            sub.ade_edges
+                .extend(spec.source_edges(grid, &coeffs, &mut sub.drives));
+        }
+        if let Some(dipole) = config.purcell {
+            sub.ade_edges
+                .push(dipole.source_edge(grid, &coeffs, &mut sub.drives));
+        }
+        return Ok((coeffs, sub));
+    }
+
+    // Rough interfaces fill the whole grid, so they go down first.
+    for surface in &config.rough_surfaces {
+        surface.apply(grid, &mut coeffs);
+    }
+    for phantom in &config.phantoms {
+        let edges = phantom
+            .apply(grid, &mut coeffs)
+            .map_err(|e| io::Error::new(e.kind(), format!("phantom {:?}: {e}", phantom.source)))?;
+        sub.ade_edges.extend(edges);
+    }
+    if let Some(map) = &config.material_map {
+        map.apply(grid, &mut coeffs);
+    }
+    for object in &config.objects {
+        object.apply(grid, &mut coeffs, config.smoothing);
+    }
+    for region in &config.random_regions {
+        region.apply(grid, &mut coeffs);
+    }
+    for region in &config.modulated {
+        region.apply(grid, &mut coeffs);
+    }
+    for object in &config.sibc_objects {
+        sub.sibc_edges.extend(object.apply(grid, &mut coeffs));
+    }
+    for object in &config.conformal_pec {
+        object.apply(grid, &mut coeffs, &mut sub.h_corrections);
+    }
+    for layer in &config.thin_layers {
+        layer.apply(grid, &mut coeffs);
+    }
+    for sheet in &config.sheets {
+        sub.ade_edges.extend(sheet.apply(grid, &mut coeffs));
+    }
+    for wire in &config.wires {
+        wire.apply(grid, &mut coeffs, &mut sub.h_corrections);
+    }
+    for element in &config.lumped {
+        sub.ade_edges
+            .extend(element.apply(grid, &mut coeffs, &mut sub.drives));
+    }
+    if let Some(tdr) = config.tdr {
+        let source = tdr.source(&config.ports[tdr.port]);
+        sub.ade_edges
+            .extend(source.apply(grid, &mut coeffs, &mut sub.drives));
+    }
+    for port in &config.circuits {
+        let edge = port.apply(grid, &mut coeffs, &mut sub.drives)?;
+        sub.circuit_slots.push(edge.src);
+        sub.ade_edges.push(edge);
+    }
+    if let Some(cell) = config.unit_cell {
+        sub.ade_edges
+            .extend(cell.source_edges(grid, &coeffs, &mut sub.drives));
+    }
+    if let Some(spec) = config.shielding {
+        sub.ade_edges
+            .extend(spec.source_edges(grid, &coeffs, &mut sub.drives));
+    }
+    if let Some(dipole) = config.purcell {
+        sub.ade_edges
+            .push(dipole.source_edge(grid, &coeffs, &mut sub.drives));
+    }
+
+    Ok((coeffs, sub))
+}
+
+/// Coefficients of a refined child grid or a reduced-dimension slice: the
+/// scene's objects painted on that mesh.
+fn object_coefficients(config: &Config, grid: &Grid, scene: Scene) -> Coefficients {
+    let mut coeffs = Coefficients::uniform(grid, &Material::VACUUM);
+    for object in config.objects.iter().filter(|_| scene == Scene::Structure) {
+        object.apply(grid, &mut coeffs, config.smoothing);
+    }
+    coeffs
+}
+
+/// Check `grid.dt` against the material-aware stability limit, or pick it
+/// from `dt_safety`.  Returns true when Δt changed and the coefficients must
+/// be rebuilt, and an invalid configuration past the limit.
+pub(crate) fn select_dt(
+    config: &Config,
+    grid: &mut Grid,
+    coeffs: &Coefficients,
+) -> Result<bool, FdtdError> {
+    let stability = Stability::analyze(grid, config.mode, config.scheme, coeffs);
+    let changed = if let Scheme::Adi { multiple } = config.scheme {
+        grid.dt = multiple * Stability::analyze(grid, config.mode, Scheme::Yee, coeffs).dt_max;
+        true
+    } else if let Some(safety) = config.dt_safety {
+        grid.dt = stability.dt(safety);
+        true
+    } else {
+        false
+    };
+    let (dt, dt_max) = (grid.dt, stability.dt_max);
+    if dt_max.is_finite() {
+        info!(
+            v_max = stability.v_max,
+            dt,
+            dt_max,
+            "Δt at {:.1} % of the stability limit",
+            100.0 * dt / dt_max
+        );
+    } else {
+        info!(dt, "unconditionally stable scheme");
+    }
+    match stability.check(dt) {
+        Check::Stable => {}
+        Check::Marginal => warn!(dt, dt_max, "Δt is within 5 % of the stability limit"),
+        Check::Unstable => {
+            let message = format!("Δt = {dt:.4e} s exceeds the stability limit");
+            return Err(ConfigError(message).into());
+        }
+    }
+    Ok(changed)
+}
+
+/// Cell of the first probe, the one followed by the reduced modes and by
+/// non-f32 precisions ([`Config::validate`] keeps it on a cell for them).
+fn first_probe_cell(probes: &[Probe]) -> [u32; 3] {
+    match probes[0].at {
+        Location::Cell(cell) => cell,
+        Location::Point(_) => unreachable!("validated: the first probe sits on a cell"),
+    }
+}
+
+/// Open the GPU with the features of the configured precision and the
+/// limits of the 3D run's buffers, print the run header and start the run's
+/// summary.
+fn open_device(
+    config: &Config,
+    scene: Scene,
+    clock: Instant,
+) -> Result<(Opened, RunInfo), FdtdError> {
+    let opened = pollster::block_on(request_device(config, scene))?;
+    let info = run_header(config, scene, &opened.adapter, opened.precision, clock);
+    Ok((opened, info))
+}
+
+/// The grid, coefficient maps and buffer estimate of a 3D run, made before
+/// the device is requested so that its limits follow the estimate.
+pub(crate) struct Setup {
+    grid: Grid,
+    coeffs: Coefficients,
+    sub: Subcell,
+    indexed: Option<IndexedCoefficients>,
+    memory: MemoryEstimate,
+}
+
+impl Setup {
+    /// Δt always follows the structure, so that a reference run matches it.
+    fn new(cfg: &Config, scene: Scene, precision: Precision) -> Result<Setup, FdtdError> {
+        if [cfg.grid.nx, cfg.grid.ny, cfg.grid.nz]
+            .iter()
+            .any(|&n| n as usize > MAX_CELLS)
+        {
+            return Err(ConfigError(format!("at most {MAX_CELLS} cells per axis")).into());
+        }
+        let mut grid = cfg.grid;
+        let (mut coeffs, mut sub) = build_coefficients(cfg, &grid, Scene::Structure)?;
+        if select_dt(cfg, &mut grid, &coeffs)? || scene == Scene::Reference {
+            (coeffs, sub) = build_coefficients(cfg, &grid, scene)?;
+        }
+        // Dense coefficients, or indexed ones: CA/CP then hold the packed
+        // material index, CB/CQ the table.
+        let indexed = index_coefficients(cfg, &coeffs);
+        let memory = estimate_memory(cfg, &grid, precision, indexed.as_ref(), &sub);
+        Ok(Setup {
+            grid,
+            coeffs,
+            sub,
+            indexed,
+            memory,
+        })
+    }
+}
+
+/// A device opened for a run, with its 3D setup.
+struct Opened {
+    adapter: wgpu::Adapter,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    precision: Precision,
+    /// None for the reduced and band-diagram runs, set up on the device.
+    setup: Option<Setup>,
+}
+
+/// The flux rectangles of `cfg` (the monitors', then six faces per box) and
+/// the DFT monitors of all its frequency-domain analyses, laid out as
+/// [`DftLayout`] says.
+fn frequency_monitors(cfg: &Config, grid: &Grid) -> (Vec<FluxMonitor>, Vec<DftMonitor>, DftLayout) {
+    let flux_surfaces: Vec<FluxMonitor> = cfg
+        .flux_monitors
+        .iter()
+        .copied()
+        .chain(cfg.flux_boxes.iter().flat_map(FluxBox::faces))
+        .collect();
+    let mut dft_monitors = cfg.dft_monitors.clone();
+    dft_monitors.extend(flux_surfaces.iter().filter_map(FluxMonitor::dft_monitor));
+    let rcs = dft_monitors.len();
+    dft_monitors.extend(cfg.rcs.iter().flat_map(Rcs::dft_monitors));
+    let pattern = dft_monitors.len();
+    dft_monitors.extend(cfg.patterns.iter().flat_map(PatternCuts::dft_monitors));
+    let mode = dft_monitors.len();
+    dft_monitors.extend(cfg.mode_monitors.iter().map(ModeMonitor::dft_monitor));
+    let purcell = dft_monitors.len();
+    dft_monitors.extend(cfg.purcell.iter().flat_map(PurcellDipole::dft_monitors));
+    let sar = dft_monitors.len();
+    dft_monitors.extend(cfg.sar.iter().map(|s| s.dft_monitor(grid)));
+    let rt = dft_monitors.len();
+    dft_monitors.extend(
+        cfg.reflectance
+            .iter()
+            .flat_map(ReflectionTransmission::dft_monitors),
+    );
+    dft_monitors.extend(cfg.unit_cell.iter().flat_map(UnitCell::dft_monitors));
+    dft_monitors.extend(cfg.shielding.iter().flat_map(Shielding::dft_monitors));
+    let layout = DftLayout {
+        rcs,
+        pattern,
+        mode,
+        purcell,
+        sar,
+        rt,
+    };
+    (flux_surfaces, dft_monitors, layout)
+}
+
+/// The GPU buffers of a run of `cfg` on `grid` at `precision`, with the
+/// coefficient maps `indexed` or dense and the sub-cell edges of `sub`.
+/// Small uniform and parameter buffers are left out.
+pub(crate) fn estimate_memory(
+    cfg: &Config,
+    grid: &Grid,
+    precision: Precision,
+    indexed: Option<&IndexedCoefficients>,
+    sub: &Subcell,
+) -> MemoryEstimate {
+    let mut memory = MemoryEstimate::new([grid.nx, grid.ny, grid.nz]);
+    let bytes = |n: usize, size: usize| (n * size) as u64;
+
+    // The f32 fields and maps shrink to one cell while another precision
+    // runs alone
+    if precision == Precision::F32 || cfg.compare_f32 {
+        memory.per_cell("fields", 6, 4);
+        if indexed.is_none() {
+            memory.per_cell("coefficients", 4, 16);
+        }
+        if cfg.divergence.is_some() {
+            let size = divergence::partials_size(grid.total());
+            memory.fixed("divergence check", [size; 2]);
+        }
+    } else {
+        memory.fixed("fields", [4; 6]);
+        if indexed.is_none() {
+            memory.fixed("coefficients", [16; 4]);
+        }
+    }
+    if let Some(table) = indexed {
+        let words = [table.e_index.len(), table.h_index.len()];
+        memory.fixed("material indices", words.map(|n| bytes(n, 4)));
+        let entries = [table.e_lut.len(), table.h_lut.len()];
+        memory.fixed("material tables", entries.map(|n| bytes(n, 16)));
+    }
+    if precision != Precision::F32 {
+        memory.per_cell("wide fields", 6, precision.size());
+        let coefficient = if precision == Precision::F16 { 4 } else { 8 };
+        memory.per_cell("wide coefficients", 1, 12 * coefficient);
+    }
+    if let Scheme::Adi { .. } | Scheme::Hie { .. } = cfg.scheme {
+        memory.per_cell("implicit coefficients", 1, 64);
+        memory.per_cell("implicit scratch", 4, 4);
+    }
+    if cfg.moving_window.is_some() {
+        memory.per_cell("window shift", 1, 16);
+    }
+    if cfg.energy_every.is_some() {
+        memory.per_cell("energy weights", 1, 32);
+    }
+    if let Some(spec) = cfg.snapshots {
+        memory.per_cell("snapshot staging", spec.fields.len() as u64, 4);
+    }
+
+    let subgrids = cfg.subgrids.iter().flat_map(|sg| {
+        let cells = sg.child_grid(grid).total();
+        [bytes(cells, 4); 6]
+            .into_iter()
+            .chain([bytes(cells, 16); 4])
+    });
+    memory.fixed("subgrids", subgrids);
+    if !sub.ade_edges.is_empty() {
+        let edges = sub.ade_edges.len();
+        let drive = bytes(sub.drives.len().max(1), 4);
+        let ade = [
+            bytes(edges, std::mem::size_of::<AdeEdge>()),
+            bytes(edges, 8),
+            drive,
+        ];
+        memory.fixed("ADE edges", ade);
+    }
+    if !sub.sibc_edges.is_empty() {
+        let edges = sub.sibc_edges.len();
+        let sibc = [
+            bytes(edges, std::mem::size_of::<SibcEdge>()),
+            bytes(edges * sibc::NPOLES, 4),
+        ];
+        memory.fixed("SIBC edges", sibc);
+    }
+
+    // Monitor accumulators, and histories read back with the probes
+    let (flux_surfaces, dft_monitors, _) = frequency_monitors(cfg, grid);
+    let dft = dft_monitors.iter().flat_map(|m| {
+        let (_, size) = m.region.bounds(grid);
+        let values = size.iter().product::<u32>() as usize * m.frequencies.len();
+        std::iter::repeat_n(bytes(values, 8), m.fields.len())
+    });
+    memory.fixed("DFT accumulators", dft);
+    let sampled = cfg.probes.len() + cfg.ports.iter().map(|p| p.probes().len()).sum::<usize>();
+    let batch = cfg.readback_batch() as usize;
+    if sampled > 0 {
+        memory.fixed("probe history", [bytes(batch * sampled, 4); 2]);
+    }
+    if !flux_surfaces.is_empty() {
+        let row: u32 = flux_surfaces
+            .iter()
+            .map(|m| gpu::groups_1d((m.u.1 - m.u.0) * (m.v.1 - m.v.0), 64))
+            .sum();
+        memory.fixed("flux history", [bytes(batch * row as usize, 4); 2]);
+    }
+    let recorders = cfg.monitors.iter().flat_map(|m| {
+        let samples = match m.span {
+            Span::Line { axis, .. } => grid.cells(axis),
+            Span::Plane { normal, .. } => {
+                let (u, v) = normal.tangential();
+                grid.cells(u) * grid.cells(v)
+            }
+        };
+        [bytes((m.batch * samples) as usize, 4); 2]
+    });
+    memory.fixed("monitor history", recorders);
+    let transforms = cfg.kspace_monitors.iter().flat_map(|m| {
+        let (u, v) = m.normal.tangential();
+        let len = [u, v]
+            .map(|a| grid.cells(a).next_power_of_two().max(2) as usize)
+            .iter()
+            .product::<usize>();
+        [bytes(len, 8), bytes(len, 8), bytes(len, 4)]
+    });
+    memory.fixed("k-space transforms", transforms);
+    memory
+}
+
+/// The adapter (named, or the first high-performance one) and a device with
+/// the features of the configured precision.  A 3D run of `scene` is set up
+/// first and its buffers checked against the adapter's limits; the device
+/// is asked for what the largest of them needs.
+async fn request_device(config: &Config, scene: Scene) -> Result<Opened, FdtdError> {
+    let adapter = request_adapter(config).await?;
+    let precision = config.precision.resolve(adapter.features());
+    let setup = match config.mode == Mode::ThreeD && config.bands.is_none() {
+        true => Some(Setup::new(config, scene, precision)?),
+        false => None,
+    };
+    let memory = setup.as_ref().map(|setup| &setup.memory);
+    if let Some(memory) = memory {
+        memory.check(&adapter.limits())?;
+    }
+    let required_limits = required_limits(&adapter, memory);
+    check_limits(&required_limits, &adapter)?;
+
+    let timestamps = match config.time_kernels {
+        true => adapter.features() & wgpu::Features::TIMESTAMP_QUERY,
+        false => wgpu::Features::empty(),
+    };
+    let (device, queue) = adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("FDTD device"),
+                required_features: precision.features() | timestamps,
+                required_limits,
+                memory_hints: wgpu::MemoryHints::Performance,
+            },
+            None,
+        )
+        .await
+        .map_err(|e| FdtdError::Device(e.to_string()))?;
+    Ok(Opened {
+        adapter,
+        device,
+        queue,
+        precision,
+        setup,
+    })
+}
+
+/// The adapter chosen in `config` (see [`crate::adapters`]), or the one
+/// wgpu prefers for its power preference on its backend.
+pub(crate) async fn request_adapter(config: &Config) -> Result<wgpu::Adapter, FdtdError> {
+    if config.backend == Some(Backend::Cpu) {
+        let message = "the CPU backend steps without an adapter".to_string();
+        return Err(ConfigError(message).into());
+    }
+    let instance = adapters::instance(config.backend);
+    let adapter = match &config.adapter {
+        #[cfg(not(target_arch = "wasm32"))]
+        Some(choice) => {
+            let adapters = instance.enumerate_adapters(adapters::backends(config.backend));
+            let names: Vec<String> = adapters.iter().map(|a| a.get_info().name).collect();
+            let found = match choice {
+                AdapterChoice::Index(index) => adapters.into_iter().nth(*index),
+                AdapterChoice::Name(name) => {
+                    let wanted = name.to_lowercase();
+                    adapters
+                        .into_iter()
+                        .find(|a| a.get_info().name.to_lowercase().contains(&wanted))
+                }
+            };
+            found.ok_or_else(|| FdtdError::NoAdapter {
+                requested: Some(choice.to_string()),
+                found: names,
+            })?
+        }
+        #[cfg(target_arch = "wasm32")]
+        Some(_) => {
+            let message = "the browser chooses the adapter".to_string();
+            return Err(ConfigError(message).into());
+        }
+        None => instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: config.power_preference.wgpu(),
+                ..Default::default()
+            })
+            .await
+            .ok_or(FdtdError::NoAdapter {
+                requested: config.backend.map(|b| format!("{b:?}")),
+                found: Vec::new(),
+            })?,
+    };
+    Ok(adapter)
+}
+
+/// The limits the solver asks of `adapter`'s device: the defaults natively
+/// (whatever WebGPU grants in the browser), with buffers and storage
+/// bindings as large as the largest buffer of `memory`, or as the adapter
+/// allows for the runs not estimated.
+pub(crate) fn required_limits(
+    adapter: &wgpu::Adapter,
+    memory: Option<&MemoryEstimate>,
+) -> wgpu::Limits {
+    let limits = match cfg!(target_arch = "wasm32") {
+        true => adapter.limits(),
+        false => wgpu::Limits::default(),
+    };
+    let (buffer, binding) = match memory {
+        Some(memory) => {
+            let largest = memory.largest();
+            (largest, largest.min(u32::MAX as u64) as u32)
+        }
+        None => {
+            let allowed = adapter.limits();
+            (
+                allowed.max_buffer_size,
+                allowed.max_storage_buffer_binding_size,
+            )
+        }
+    };
+    wgpu::Limits {
+        max_buffer_size: limits.max_buffer_size.max(buffer),
+        max_storage_buffer_binding_size: limits.max_storage_buffer_binding_size.max(binding),
+        ..limits
+    }
+}
+
+/// The first of `required` that `adapter` falls short of.
+pub(crate) fn check_limits(
+    required: &wgpu::Limits,
+    adapter: &wgpu::Adapter,
+) -> Result<(), FdtdError> {
+    let mut short = None;
+    required.check_limits_with_fail_fn(&adapter.limits(), true, |limit, requested, allowed| {
+        short.get_or_insert(FdtdError::DeviceLimits {
+            limit,
+            requested,
+            allowed,
+        });
+    });
+    short.map_or(Ok(()), Err)
+}
+
+/// The indexed form of `coeffs` when `config` asks for it and no pass
+/// rewrites the maps during the run.
+pub(crate) fn index_coefficients(
+    config: &Config,
+    coeffs: &Coefficients,
+) -> Option<IndexedCoefficients> {
+    match config.coefficient_storage {
+        CoefficientStorage::Indexed
+            if config.modulated.is_empty()
+                && config.subgrids.is_empty()
+                && config.moving_window.is_none() =>
+        {
+            coeffs.to_indexed()
+        }
+        _ => None,
+    }
+}
+
+/// Print the run header and start the run's summary.
+fn run_header(
+    config: &Config,
+    scene: Scene,
+    adapter: &wgpu::Adapter,
+    precision: Precision,
+    clock: Instant,
+) -> RunInfo {
+    if scene == Scene::Reference {
+        info!("reference run: lumped elements only, no structure");
+    }
+    if let Some(spec) = config.auto_mesh {
+        info!(
+            cells_per_wavelength = spec.cells_per_wavelength,
+            f_max = spec.f_max,
+            "auto mesh:\n{}",
+            spec.plan(&config.objects)
+        );
+    }
+
+    let grid = &config.grid;
+    let adapter_info = adapter.get_info();
+    info!(
+        adapter = %adapter_info.name,
+        backend = ?adapter_info.backend,
+        ?precision,
+        "opened the GPU"
+    );
+    let limits = adapter.limits();
+    debug!(
+        max_buffer_mib = limits.max_buffer_size >> 20,
+        max_storage_binding_mib = limits.max_storage_buffer_binding_size >> 20,
+        "adapter limits"
+    );
+    info!(
+        nx = grid.nx,
+        ny = grid.ny,
+        nz = grid.nz,
+        cells = grid.total(),
+        steps = config.steps,
+        courant = C0 * grid.dt / grid.dx,
+        "grid"
+    );
+    RunInfo {
+        scene: format!("{scene:?}"),
+        adapter: adapter.get_info().name,
+        backend: format!("{:?}", adapter.get_info().backend),
+        precision: format!("{precision:?}"),
+        size: [grid.nx, grid.ny, grid.nz],
+        dt: grid.dt,
+        steps: config.steps,
+        setup: clock.elapsed(),
+        stepping: Duration::ZERO,
+    }
+}
+
+/// The sinks every run of `config` starts with: a [`JsonLinesSink`] with
+/// [`OutputFormat::Json`], none otherwise.
+pub(crate) fn console_sinks(config: &Config) -> Vec<Box<dyn OutputSink>> {
+    let mut sinks: Vec<Box<dyn OutputSink>> = Vec::new();
+    if config.output_format == OutputFormat::Json {
+        sinks.push(Box::new(JsonLinesSink));
+    }
+    sinks
+}
+
+/// Hand a result to `sinks`, printing `line` as well unless stdout takes
+/// JSON lines.
+pub(crate) fn report(
+    sinks: &mut [Box<dyn OutputSink>],
+    format: OutputFormat,
+    event: OutputEvent,
+    line: fmt::Arguments,
+) -> io::Result<()> {
+    if format == OutputFormat::Text {
+        progress::suspend(|| println!("{line}"));
+    }
+    for sink in sinks {
+        sink.event(&event)?;
+    }
+    Ok(())
+}
+
+/// 1D / 2D run through the source, or BOR about the central z line,
+/// printing the probe trace (and, in 1D, the analytic hard-source pulse);
+/// returns the grid stepped.
+fn run_reduced(
+    config: &Config,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+) -> Result<Grid, FdtdError> {
+    let (mode, source_cell) = (config.mode, config.source);
+    let at = match mode {
+        Mode::Bor { .. } => [config.grid.nx / 2, config.grid.ny / 2, 0],
+        _ => source_cell,
+    };
+    let mut grid = mode.grid(&config.grid, at);
+    let mut coeffs = object_coefficients(config, &grid, Scene::Structure);
+    if select_dt(config, &mut grid, &coeffs)? {
+        coeffs = object_coefficients(config, &grid, Scene::Structure);
+    }
+    let solver = ReducedSolver::new(device, mode, &grid, &coeffs);
+    let source = mode.node(at, source_cell);
+    let probe_cell = first_probe_cell(&config.probes);
+    let probe = mode.node(at, probe_cell);
+    let trace = solver.run(device, queue, source, probe, &config.waveform, config.steps)?;
+
+    let (name, probe_name) = (mode.field_name(), config.probes[0].name);
+    info!(?mode, cells = grid.total(), "reduced run");
+    let distance = grid.node(Axis::X, probe_cell[0]) - grid.node(Axis::X, source_cell[0]);
+    let mut probe_writer = config
+        .probe_output
+        .map(|spec| ProbeWriter::new(spec, &[probe_name]))
+        .transpose()?;
+    let mut sinks = console_sinks(config);
+    for (n, value) in trace.iter().enumerate() {
+        let t = (n + 1) as f64 * grid.dt;
+        if let Some(writer) = &mut probe_writer {
+            writer.record(0, n as u32, t, *value as f64)?;
+        }
+        let event = OutputEvent::Probe {
+            name: probe_name,
+            quantity: Quantity::Component(mode.field()),
+            step: n as u32,
+            time: t,
+            value: *value as f64,
+        };
+        for sink in &mut sinks {
+            sink.event(&event)?;
+        }
+        if config.verbosity != Verbosity::Verbose || config.output_format == OutputFormat::Json {
+            continue;
+        }
+        if mode == Mode::OneD {
+            let exact = reduced::hard_source_1d(&config.waveform, distance, C0, grid.dt, n as u32);
+            println!(
+                "t={:4}  {}[{}] = {:.6e}  analytic = {:.6e}",
+                n, name, probe_name, value, exact
+            );
+        } else {
+            println!("t={:4}  {}[{}] = {:.6e}", n, name, probe_name, value);
+        }
+    }
+    for sink in &mut sinks {
+        sink.finish()?;
+    }
+    if config.output_format == OutputFormat::Text {
+        progress::suspend(|| println!("\nSimulation complete."));
+    }
+    Ok(grid)
+}
+
+/// Band-diagram run: complex Bloch fields on the structure's coefficients
+/// for every k-point of `bands`; returns the grid and the steps taken.
+fn run_bands(
+    config: &Config,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+) -> Result<(Grid, u32), FdtdError> {
+    let spec = config.bands.unwrap();
+    let mut grid = config.grid;
+    let mut coeffs = object_coefficients(config, &grid, Scene::Structure);
+    if select_dt(config, &mut grid, &coeffs)? {
+        coeffs = object_coefficients(config, &grid, Scene::Structure);
+    }
+    let bands = bands::compute(device, queue, &grid, &coeffs, &spec)?;
+    let mut sinks = console_sinks(config);
+    for (k, modes) in spec.k_points().iter().zip(&bands) {
+        for m in modes {
+            let values = [
+                ("kx", k[0]),
+                ("ky", k[1]),
+                ("kz", k[2]),
+                ("frequency", m.frequency),
+                ("Q", m.q),
+            ];
+            report(
+                &mut sinks,
+                config.output_format,
+                OutputEvent::Result {
+                    analysis: "band",
+                    name: spec.name,
+                    values: &values,
+                },
+                format_args!(
+                    "Band k = ({:.3}, {:.3}, {:.3}): f = {:.6e} Hz  Q = {:.1}",
+                    k[0], k[1], k[2], m.frequency, m.q
+                ),
+            )?;
+        }
+    }
+    bands::write_results(config.monitor_dir.as_ref(), &spec, &bands)?;
+    for sink in &mut sinks {
+        sink.finish()?;
+    }
+    if config.output_format == OutputFormat::Text {
+        progress::suspend(|| println!("\nSimulation complete."));
+    }
+    Ok((grid, spec.steps * bands.len() as u32))
+}
+
+/// Offsets of each analysis' monitors in the DFT pass: the DFT monitors and
+/// flux rectangles first, then six faces per RCS box, six per pattern box,
+/// one plane per mode monitor, the dipole edge and box faces of the Purcell
+/// dipole, the box of each SAR calculation, and from `rt` on the planes of
+/// the normalised spectra (two per reflection/transmission calculation, the
+/// unit cell's front and back and the shielding points).
+struct DftLayout {
+    rcs: usize,
+    pattern: usize,
+    mode: usize,
+    purcell: usize,
+    sar: usize,
+    rt: usize,
+}
+
+/// What the host reads after an encoded step, which then ends its
+/// submission.
+#[derive(Default)]
+struct Reads {
+    /// Energy reductions filling every slot.
+    energy: bool,
+    divergence: bool,
+    /// Batches copied for readback, snapshots, images or hooks due.
+    batches: bool,
+    /// Byte of the non-f32 first probe sample in the readback buffer.
+    probe_at: usize,
+}
+
+impl Reads {
+    fn any(&self) -> bool {
+        self.energy || self.divergence || self.batches
+    }
+}
+
+/// A row of probe values ready for output: the step, the values and the
+/// f32 reference of a non-f32 first probe.
+type ProbeRow = (u32, Vec<f64>, Option<f64>);
+
+/// The 3D solver on the GPU, stepped one Δt at a time.
+pub(crate) struct GpuRun {
+    config: Config,
+    scene: Scene,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    watch: DeviceWatch,
+    /// Per-step buffer writes of the steps encoded for the next submission.
+    uploads: Uploads,
+    /// GPU timestamps of the update kernels, with `config.time_kernels`.
+    kernel_timer: Option<KernelTimer>,
+    precision: Precision,
+    grid: Grid,
+    /// Steps taken.
+    n: u32,
+    info: RunInfo,
+
+    /// The f32 field buffers (Ex, Ey, Ez, Hx, Hy, Hz), one cell each while
+    /// another precision runs alone.
+    fields: [wgpu::Buffer; 6],
+    f32_update: bool,
+    /// Factor on the point source's waveform.
+    source_amplitude: f64,
+    /// The point source of the f32 update, unless another source replaces
+    /// it.
+    source_pass: Option<SourcePass>,
+    drives: Vec<(f64, Waveform)>,
+    buf_readback: wgpu::Buffer,
+    pipeline_h: wgpu::ComputePipeline,
+    pipeline_e: wgpu::ComputePipeline,
+    bg_h: wgpu::BindGroup,
+    bg_e: wgpu::BindGroup,
+    workgroups: [u32; 3],
+
+    modulation_pass: Option<ModulationPass>,
+    ade_pass: Option<AdePass>,
+    h_correction_pass: Option<HCorrectionPass>,
+    sibc_pass: Option<SibcPass>,
+    subgrid_passes: Vec<SubgridPass>,
+    window_pass: Option<MovingWindowPass>,
+    adi_pass: Option<AdiPass>,
+    hie_pass: Option<HiePass>,
+    precision_pass: Option<PrecisionPass>,
+    cosim: Option<CosimPass>,
+    monitor_pass: Option<MonitorPass>,
+    kspace_pass: Option<KSpacePass>,
+    dft_pass: Option<DftPass>,
+    dft_layout: DftLayout,
+    flux_pass: Option<FluxPass>,
+    flux_writer: Option<ProbeWriter>,
+    flux_step: u32,
+    energy_pass: Option<EnergyPass>,
+    divergence_pass: Option<DivergencePass>,
+    energy_writer: Option<ProbeWriter>,
+    /// (step, U) waiting for its probe row.
+    energies: VecDeque<(u32, f64)>,
+    snapshot_writer: Option<SnapshotWriter>,
+    slice_writer: Option<SliceWriter>,
+
+    probe_set: Option<ProbeSet>,
+    probe_writer: Option<ProbeWriter>,
+    /// Non-f32 samples of the first probe waiting for their f32 reference,
+    /// and the steps of f32 probe rows already reported.
+    precise: VecDeque<(u32, f64)>,
+    reported: u32,
+    /// Largest |non-f32 − f32| and |f32| of the first probe.
+    max_diff: f64,
+    max_ref: f64,
+    port_traces: Vec<Vec<[f64; 2]>>,
+    /// Whole probe traces for harmonic inversion and spectra.
+    probe_traces: Vec<Vec<f64>>,
+
+    hooks: Vec<(u32, StepCallback)>,
+    stopped: bool,
+    sinks: Vec<Box<dyn OutputSink>>,
+    progress: Option<Progress>,
+}
+
+impl GpuRun {
+    fn with_device(
+        config: Config,
+        scene: Scene,
+        opened: Opened,
+        mut info: RunInfo,
+        clock: Instant,
+    ) -> Result<GpuRun, FdtdError> {
+        let _setup = debug_span!("setup", ?scene).entered();
+        let cfg = &config;
+        let Opened {
+            device,
+            queue,
+            precision,
+            setup,
+            ..
+        } = opened;
+        // The coefficient maps built on the CPU with the device requested
+        let Setup {
+            grid,
+            coeffs,
+            sub,
+            indexed,
+            memory,
+        } = setup.expect("3D runs are set up with their device");
+        let dt = grid.dt;
+        info.dt = dt;
+        // The f32 fields and maps shrink to one cell while another precision
+        // runs alone.
+        let f32_update = precision == Precision::F32 || cfg.compare_f32;
+        let f32_cells = if f32_update { grid.total() } else { 1 };
+        let zeros = vec![0.0_f32; f32_cells];
+
+        // A checkpoint of this scene to go on from; the outputs written step
+        // by step are then appended to
+        let resumed = match &cfg.resume {
+            Some(path) => Some(checkpoint::read(path)?)
+                .filter(|c| c.section("scene") == Some(format!("{scene:?}").as_bytes())),
+            None => None,
+        };
+        let open_writer: fn(ProbeOutput, &[&str]) -> io::Result<ProbeWriter> = match resumed {
+            Some(_) => ProbeWriter::append,
+            None => ProbeWriter::new,
+        };
+
+        info!(
+            total_mib = memory.total() as f64 / (1 << 20) as f64,
+            "GPU memory estimate"
+        );
+        // Errors of the setup come back with the run rather than panicking
+        let watch = DeviceWatch::new(&device);
+        gpu::push_scopes(&device);
+
+        // ── Create GPU buffers ───────────────────────────────────────
+
+        let usage_rw = wgpu::BufferUsages::STORAGE
+            | wgpu::BufferUsages::COPY_DST
+            | wgpu::BufferUsages::COPY_SRC;
+        let usage_ro = wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST;
+
+        let make_buf = |label: &str, data: &[u8], usage: wgpu::BufferUsages| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents: data,
+                usage,
+            })
+        };
+
+        // Field buffers (read-write — updated by shaders)
+        let buf_ex = make_buf("ex", bytemuck::cast_slice(&zeros), usage_rw);
+        let buf_ey = make_buf("ey", bytemuck::cast_slice(&zeros), usage_rw);
+        let buf_ez = make_buf("ez", bytemuck::cast_slice(&zeros), usage_rw);
+        let buf_hx = make_buf("hx", bytemuck::cast_slice(&zeros), usage_rw);
+        let buf_hy = make_buf("hy", bytemuck::cast_slice(&zeros), usage_rw);
+        let buf_hz = make_buf("hz", bytemuck::cast_slice(&zeros), usage_rw);
+
+        // Coefficient buffers (read-only — uploaded once).  Dense: one vec4 per
+        // cell.  Indexed: CA/CP hold the packed material index, CB/CQ the table.
+        let [buf_ca, buf_cb, buf_cp, buf_cq] = match &indexed {
+            None => [
+                make_buf(
+                    "ca",
+                    bytemuck::cast_slice(&coeffs.ca[..f32_cells]),
+                    usage_ro,
+                ),
+                make_buf(
+                    "cb",
+                    bytemuck::cast_slice(&coeffs.cb[..f32_cells]),
+                    usage_ro,
+                ),
+                make_buf(
+                    "cp",
+                    bytemuck::cast_slice(&coeffs.cp[..f32_cells]),
+                    usage_ro,
+                ),
+                make_buf(
+                    "cq",
+                    bytemuck::cast_slice(&coeffs.cq[..f32_cells]),
+                    usage_ro,
+                ),
+            ],
+            Some(table) => {
+                info!(
+                    e_materials = table.e_lut.len() / 2,
+                    h_materials = table.h_lut.len() / 2,
+                    bits = table.bits,
+                    "indexed coefficients"
+                );
+                [
+                    make_buf("e_index", bytemuck::cast_slice(&table.e_index), usage_ro),
+                    make_buf("e_lut", bytemuck::cast_slice(&table.e_lut), usage_ro),
+                    make_buf("h_index", bytemuck::cast_slice(&table.h_index), usage_ro),
+                    make_buf("h_lut", bytemuck::cast_slice(&table.h_lut), usage_ro),
+                ]
+            }
+        };
+
+        let mib = |buffers: &[&wgpu::Buffer]| {
+            buffers.iter().map(|b| b.size()).sum::<u64>() as f64 / (1 << 20) as f64
+        };
+        debug!(
+            fields_mib = mib(&[&buf_ex, &buf_ey, &buf_ez, &buf_hx, &buf_hy, &buf_hz]),
+            coefficients_mib = mib(&[&buf_ca, &buf_cb, &buf_cp, &buf_cq]),
+            "allocated the field and coefficient buffers"
+        );
+
+        // Uniform buffer, with the periodic boundaries and unit-cell axes
+        let periodic = cfg.periodic_axes();
+        let params = GpuParams::new(&grid).with_periodic(&periodic);
+        let buf_params = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("params"),
+            contents: bytemuck::bytes_of(&params),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let buf_spacing = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("spacing"),
+            contents: bytemuck::cast_slice(&spacing_table(&grid)),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        // Readback staging buffer (the aligned word of the non-f32 probe value)
+        let buf_readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("readback"),
+            size: 8,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        // ── Load shaders & create pipelines ──────────────────────────
+
+        // The update kernels fetch coefficients through a prepended loader.
+        let (loader, constants) = match &indexed {
+            None => (include_str!("shaders/coeffs_dense.wgsl"), HashMap::new()),
+            Some(table) => (
+                include_str!("shaders/coeffs_indexed.wgsl"),
+                HashMap::from([("index_bits".to_string(), table.bits as f64)]),
+            ),
+        };
+        let (update_h, update_e) = match cfg.scheme {
+            Scheme::Yee | Scheme::Hie { .. } | Scheme::Adi { .. } => (
+                include_str!("shaders/update_h.wgsl"),
+                include_str!("shaders/update_e.wgsl"),
+            ),
+            Scheme::Yee24 => (
+                include_str!("shaders/update_h4.wgsl"),
+                include_str!("shaders/update_e4.wgsl"),
+            ),
+        };
+        let shader_h = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("update_h"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Owned(loader.to_string() + update_h)),
+        });
+        let shader_e = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("update_e"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Owned(loader.to_string() + update_e)),
+        });
+
+        // Bind-group layout (shared structure: params + 6 fields + 2 coeffs)
+        let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("fdtd_bgl"),
+            entries: &[
+                // @binding(0) uniform Params
+                bgl_uniform_entry(0),
+                // @binding(1..3) read-only storage  (source fields)
+                bgl_storage_entry(1, true),
+                bgl_storage_entry(2, true),
+                bgl_storage_entry(3, true),
+                // @binding(4..6) read-write storage (target fields)
+                bgl_storage_entry(4, false),
+                bgl_storage_entry(5, false),
+                bgl_storage_entry(6, false),
+                // @binding(7..8) read-only storage  (coefficients)
+                bgl_storage_entry(7, true),
+                bgl_storage_entry(8, true),
+                // @binding(9) uniform Spacing (inverse cell widths)
+                bgl_uniform_entry(9),
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("fdtd_pl"),
+            bind_group_layouts: &[&bgl],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline_h = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("pipeline_h"),
+            layout: Some(&pipeline_layout),
+            module: &shader_h,
+            entry_point: Some("main"),
+            compilation_options: wgpu::PipelineCompilationOptions {
+                constants: &constants,
+                ..Default::default()
+            },
+            cache: None,
+        });
+        let pipeline_e = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("pipeline_e"),
+            layout: Some(&pipeline_layout),
+            module: &shader_e,
+            entry_point: Some("main"),
+            compilation_options: wgpu::PipelineCompilationOptions {
+                constants: &constants,
+                ..Default::default()
+            },
+            cache: None,
+        });
+
+        // Bind groups:
+        //   H-update reads E, writes H, uses CP/CQ
+        //   E-update reads H, writes E, uses CA/CB
+        let bg_h = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("bg_h"),
+            layout: &bgl,
+            entries: &[
+                bg_entry(0, buf_params.as_entire_binding()),
+                bg_entry(1, buf_ex.as_entire_binding()),
+                bg_entry(2, buf_ey.as_entire_binding()),
+                bg_entry(3, buf_ez.as_entire_binding()),
+                bg_entry(4, buf_hx.as_entire_binding()),
+                bg_entry(5, buf_hy.as_entire_binding()),
+                bg_entry(6, buf_hz.as_entire_binding()),
+                bg_entry(7, buf_cp.as_entire_binding()),
+                bg_entry(8, buf_cq.as_entire_binding()),
+                bg_entry(9, buf_spacing.as_entire_binding()),
+            ],
+        });
+        let bg_e = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("bg_e"),
+            layout: &bgl,
+            entries: &[
+                bg_entry(0, buf_params.as_entire_binding()),
+                bg_entry(1, buf_hx.as_entire_binding()),
+                bg_entry(2, buf_hy.as_entire_binding()),
+                bg_entry(3, buf_hz.as_entire_binding()),
+                bg_entry(4, buf_ex.as_entire_binding()),
+                bg_entry(5, buf_ey.as_entire_binding()),
+                bg_entry(6, buf_ez.as_entire_binding()),
+                bg_entry(7, buf_ca.as_entire_binding()),
+                bg_entry(8, buf_cb.as_entire_binding()),
+                bg_entry(9, buf_spacing.as_entire_binding()),
+            ],
+        });
+
+        // Time-varying coefficients (re-uploaded only when a schedule changes)
+        let modulation_pass = ModulationPass::new(&device, &grid, &cfg.modulated, &buf_ca, &buf_cb);
+
+        // Sparse ADE pass for dispersive sub-cell models (sheets, …)
+        let ade_pass = AdePass::new(
+            &device,
+            &sub.ade_edges,
+            sub.drives.len(),
+            [&buf_ex, &buf_ey, &buf_ez],
+        );
+
+        // Sparse H corrections for sub-cell Faraday loops (thin wires, …)
+        let h_correction_pass = HCorrectionPass::new(
+            &device,
+            &sub.h_corrections,
+            [&buf_ex, &buf_ey, &buf_ez],
+            [&buf_hx, &buf_hy, &buf_hz],
+        );
+
+        // SIBC pass on conductor surfaces; the impedance expansion spans ten
+        // times below the lowest resolvable frequency up to Nyquist.
+        let omega_min = 2.0 * std::f64::consts::PI / (cfg.steps as f64 * dt) / 10.0;
+        let sibc_poles = sibc::sibc_poles(dt, omega_min, std::f64::consts::PI / dt);
+        let sibc_pass = SibcPass::new(
+            &device,
+            &sub.sibc_edges,
+            &sibc_poles,
+            [&buf_hx, &buf_hy, &buf_hz],
+            [&buf_ex, &buf_ey, &buf_ez],
+        );
+
+        // Refined child grids, stepped with the same update pipelines
+        let subgrid_passes: Vec<SubgridPass> = cfg
+            .subgrids
+            .iter()
+            .map(|sg| {
+                SubgridPass::new(
+                    &device,
+                    &grid,
+                    sg,
+                    &object_coefficients(cfg, &sg.child_grid(&grid), scene),
+                    (&pipeline_h, &pipeline_e, &bgl),
+                    [&buf_ex, &buf_ey, &buf_ez],
+                )
+            })
+            .collect();
+
+        // Moving window: shifts every buffer, so only plain objects are allowed
+        let window_pass = cfg.moving_window.map(|window| {
+            MovingWindowPass::new(
+                &device,
+                &grid,
+                window,
+                [&buf_ex, &buf_ey, &buf_ez, &buf_hx, &buf_hy, &buf_hz],
+                [&buf_ca, &buf_cb, &buf_cp, &buf_cq],
+            )
+        });
+
+        // Implicit ADI update in place of the explicit kernels
+        let adi_pass = match cfg.scheme {
+            Scheme::Adi { .. } => Some(AdiPass::new(
+                &device,
+                &grid,
+                &build_coefficients(cfg, &adi::half_step_grid(&grid), scene)?.0,
+                [&buf_ex, &buf_ey, &buf_ez],
+                [&buf_hx, &buf_hy, &buf_hz],
+                &buf_spacing,
+            )),
+            _ => None,
+        };
+
+        // Hybrid implicit–explicit update in place of the explicit kernels
+        let hie_pass = match cfg.scheme {
+            Scheme::Hie { axis } => Some(HiePass::new(
+                &device,
+                &grid,
+                &coeffs,
+                axis,
+                [&buf_ex, &buf_ey, &buf_ez],
+                [&buf_hx, &buf_hy, &buf_hz],
+                &buf_spacing,
+            )),
+            _ => None,
+        };
+
+        // Fields in another precision in place of (or next to) the f32 ones
+        let precision_pass = (precision != Precision::F32)
+            .then(|| PrecisionPass::new(&device, &grid, precision, &coeffs));
+
+        // Point probes on the f32 fields, followed by the E and H samples of
+        // the feed ports
+        let sampled: Vec<Probe> = cfg
+            .probes
+            .iter()
+            .copied()
+            .chain(cfg.ports.iter().flat_map(FeedPort::probes))
+            .collect();
+        let fields = [&buf_ex, &buf_ey, &buf_ez, &buf_hx, &buf_hy, &buf_hz];
+        let probe_set = (f32_update && !sampled.is_empty())
+            .then(|| ProbeSet::new(&device, &grid, &sampled, cfg.readback_batch(), fields));
+        let port_traces = vec![Vec::new(); cfg.ports.len()];
+        // and the whole probe traces for harmonic inversion and spectra
+        let whole = cfg.harminv.is_some() || cfg.probe_spectra.is_some();
+        let probe_traces = vec![Vec::new(); if whole { cfg.probes.len() } else { 0 }];
+
+        // Circuit co-simulation (of the f32 fields), on the structure only
+        let cosim = (!sub.circuit_slots.is_empty())
+            .then(|| CosimPass::new(&device, &grid, &cfg.circuits, &sub.circuit_slots))
+            .transpose()?;
+
+        // Line and plane monitors (of the f32 fields)
+        let monitor_pass = (!cfg.monitors.is_empty())
+            .then(|| MonitorPass::new(&device, &grid, &cfg.monitors, cfg.monitor_dir, fields))
+            .transpose()?;
+
+        // Plane spatial spectra (of the f32 fields)
+        let kspace_pass = (!cfg.kspace_monitors.is_empty())
+            .then(|| KSpacePass::new(&device, &grid, &cfg.kspace_monitors, fields));
+
+        // Frequency monitors (of the f32 fields)
+        let (flux_surfaces, dft_monitors, dft_layout) = frequency_monitors(cfg, &grid);
+        let dft_pass =
+            (!dft_monitors.is_empty()).then(|| DftPass::new(&device, &grid, &dft_monitors, fields));
+
+        // Time-domain Poynting flux (of the f32 fields), read back with the probes
+        let flux_pass = (!flux_surfaces.is_empty())
+            .then(|| FluxPass::new(&device, &grid, &flux_surfaces, cfg.readback_batch(), fields));
+        let flux_writer = flux_pass
+            .as_ref()
+            .map(|_| {
+                let spec = ProbeOutput {
+                    dir: cfg.monitor_dir,
+                    format: probes::ProbeFormat::Csv,
+                };
+                let monitors = cfg.flux_monitors.iter().map(|m| m.name);
+                let names: Vec<_> = monitors
+                    .chain(cfg.flux_boxes.iter().map(|b| b.name))
+                    .collect();
+                open_writer(spec, &names)
+            })
+            .transpose()?;
+
+        // Total field energy (of the f32 fields)
+        // with a slot for every reduction a submission can hold
+        let energy_pass = cfg.energy_every.map(|every| {
+            let slots = cfg.steps_per_submit.div_ceil(every) as usize;
+            EnergyPass::new(&device, &grid, &coeffs, fields, slots)
+        });
+
+        // NaN and runaway growth (of the f32 fields)
+        let divergence_pass = match cfg.divergence {
+            Some(spec) if f32_update => Some(DivergencePass::new(&device, &grid, spec, fields)),
+            Some(_) => {
+                debug!("the divergence check reads the f32 fields, skipped");
+                None
+            }
+            None => None,
+        };
+
+        // Full-volume snapshots (of the f32 fields)
+        let snapshot_writer = cfg
+            .snapshots
+            .map(|spec| SnapshotWriter::new(&device, &grid, &coeffs, spec))
+            .transpose()?;
+
+        // Slice images (of the f32 fields)
+        let slice_writer = cfg
+            .slice_images
+            .map(|spec| SliceWriter::new(&device, &grid, spec))
+            .transpose()?;
+
+        // The MATLAB file of the structure run
+        let mut sinks = console_sinks(cfg);
+        if let (Some(path), Scene::Structure) = (cfg.mat_file, scene) {
+            sinks.push(Box::new(MatSink::new(path)));
+        }
+
+        // Workgroup counts  (workgroup_size = 4×4×4)
+        let workgroups = [grid.nx, grid.ny, grid.nz].map(|n| n.div_ceil(4));
+
+        let names: Vec<_> = cfg.probes.iter().map(|probe| probe.name).collect();
+        let probe_writer = cfg
+            .probe_output
+            .map(|spec| open_writer(spec, &names))
+            .transpose()?;
+        let energy_writer = cfg
+            .probe_output
+            .filter(|_| energy_pass.is_some())
+            .map(|spec| open_writer(spec, &["energy"]))
+            .transpose()?;
+
+        let progress = Progress::new(cfg.steps, grid.total(), grid.dt, cfg.verbosity);
+
+        let kernel_timer = cfg
+            .time_kernels
+            .then(|| KernelTimer::new(&device, &queue))
+            .flatten();
+        let uploads = Uploads::new(&device);
+        let source_pass =
+            (f32_update && !cfg.source_replaced()).then(|| SourcePass::new(&device, &buf_ez));
+
+        info.setup = clock.elapsed();
+        let mut simulation = GpuRun {
+            config,
+            scene,
+            device,
+            queue,
+            watch,
+            uploads,
+            kernel_timer,
+            precision,
+            grid,
+            n: 0,
+            info,
+            fields: [buf_ex, buf_ey, buf_ez, buf_hx, buf_hy, buf_hz],
+            f32_update,
+            source_amplitude: 1.0,
+            source_pass,
+            drives: sub.drives,
+            buf_readback,
+            pipeline_h,
+            pipeline_e,
+            bg_h,
+            bg_e,
+            workgroups,
+            modulation_pass,
+            ade_pass,
+            h_correction_pass,
+            sibc_pass,
+            subgrid_passes,
+            window_pass,
+            adi_pass,
+            hie_pass,
+            precision_pass,
+            cosim,
+            monitor_pass,
+            kspace_pass,
+            dft_pass,
+            dft_layout,
+            flux_pass,
+            flux_writer,
+            flux_step: 0,
+            energy_pass,
+            divergence_pass,
+            energy_writer,
+            energies: VecDeque::new(),
+            snapshot_writer,
+            slice_writer,
+            probe_set,
+            probe_writer,
+            precise: VecDeque::new(),
+            reported: 0,
+            max_diff: 0.0,
+            max_ref: 0.0,
+            port_traces,
+            probe_traces,
+            hooks: Vec::new(),
+            stopped: false,
+            sinks,
+            progress,
+        };
+        if let Some(checkpoint) = resumed {
+            simulation.restore(&checkpoint)?;
+        }
+        gpu::pop_scopes(&simulation.device, || "setting up the run".into())?;
+        Ok(simulation)
+    }
+
+    /// Take up to `steps` steps in one command buffer and return the steps
+    /// taken.  The submission ends early on a step whose results the host
+    /// reads (a probe, flux or monitor batch, a divergence check, a
+    /// snapshot, an image or a hook; the energy reductions are read after
+    /// the submission, from a slot each) and after every step of the runs
+    /// driven from the host each step: non-f32 precisions, circuit
+    /// co-simulation and the moving window.
+    fn submit(&mut self, steps: u32) -> Result<u32, FdtdError> {
+        let start = Instant::now();
+        let (first, dt) = (self.n, self.grid.dt);
+        // An interrupt ends the run here, with the batches flushed
+        let stopping = interrupt::requested();
+        let alone =
+            self.precision_pass.is_some() || self.cosim.is_some() || self.window_pass.is_some();
+        gpu::push_scopes(&self.device);
+
+        // Encode the steps into a single command buffer
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("fdtd_step"),
+            });
+        let mut n = first;
+        let reads = loop {
+            let last = n + 1 == self.config.steps || stopping;
+            let reads = self.encode_step(&mut encoder, n, last);
+            if n + 1 - first == steps || last || alone || reads.any() {
+                break reads;
+            }
+            n += 1;
+        };
+
+        self.uploads.submit(&self.queue);
+        let submission = self.queue.submit(Some(encoder.finish()));
+        let doing = || format!("taking step {}", n + 1);
+        gpu::pop_scopes(&self.device, doing)?;
+        self.watch.check(doing)?;
+
+        // Circuit ports take the new gap voltages before the next step
+        if let Some(cosim) = &mut self.cosim {
+            cosim.exchange(&self.device)?;
+        }
+
+        // Rows of probe values ready for output, those of earlier steps
+        // while the last batches are still being read back
+        let mut rows = Vec::new();
+        if self.precision_pass.is_some() {
+            let slice = self.buf_readback.slice(..);
+            gpu::map_read(&self.device, &[slice])?;
+            let value = self
+                .precision
+                .decode(&slice.get_mapped_range()[reads.probe_at..]);
+            self.precise.push_back((n, value));
+            self.buf_readback.unmap();
+        }
+        match &mut self.probe_set {
+            Some(set) => {
+                let samples = set.take(&self.device, submission)?;
+                rows = self.probe_rows(samples);
+            }
+            None => rows.extend(
+                self.precise
+                    .drain(..)
+                    .map(|(m, value)| (m, vec![value], None)),
+            ),
+        }
+
+        if let Some(energy) = &mut self.energy_pass {
+            for (m, u) in energy.read(&self.device)? {
+                if let Some(writer) = &mut self.energy_writer {
+                    writer.record(0, m, (m + 1) as f64 * dt, u)?;
+                }
+                self.energies.push_back((m, u));
+            }
+        }
+        if let (Some(divergence), true) = (&mut self.divergence_pass, reads.divergence) {
+            if let Some((cause, value, edge)) = divergence.read(&self.device)? {
+                return Err(self.diverged(n + 1, cause, value, edge));
+            }
+        }
+        if let Some(monitors) = &mut self.monitor_pass {
+            monitors.take(&self.device)?;
+        }
+        if let (Some(flux), Some(writer)) = (&mut self.flux_pass, &mut self.flux_writer) {
+            for power in flux.take(&self.device)? {
+                let t = (self.flux_step + 1) as f64 * dt;
+                let (monitors, faces) = power.split_at(self.config.flux_monitors.len());
+                let boxes = faces.chunks(6).map(FluxBox::outgoing);
+                for (m, p) in monitors.iter().copied().chain(boxes).enumerate() {
+                    writer.record(m, self.flux_step, t, p)?;
+                }
+                self.flux_step += 1;
+            }
+        }
+
+        let fields = self.fields.each_ref();
+        if let Some(writer) = &mut self.snapshot_writer {
+            writer.capture(&self.device, &self.queue, n, fields, &mut self.sinks)?;
+        }
+        if let Some(writer) = &self.slice_writer {
+            writer.capture(&self.device, &self.queue, n, fields)?;
+        }
+
+        self.record_rows(rows)?;
+
+        self.info.stepping += start.elapsed();
+        for m in first..=n {
+            self.n = m + 1;
+            let event = OutputEvent::Step {
+                step: m,
+                steps: self.config.steps,
+                time: self.n as f64 * dt,
+            };
+            for sink in &mut self.sinks {
+                sink.event(&event)?;
+            }
+        }
+        if let Some(progress) = &mut self.progress {
+            progress.update(self.n);
+        }
+
+        for (every, hook) in &mut self.hooks {
+            if self.n.is_multiple_of(*every) {
+                let mut state = StepState {
+                    fields: StepFields::Device {
+                        device: &self.device,
+                        queue: &self.queue,
+                        buffers: &self.fields,
+                    },
+                    grid: &self.grid,
+                    steps: self.n,
+                    stop: false,
+                };
+                hook(&mut state);
+                self.stopped |= state.stop;
+            }
+        }
+        self.stopped |= stopping;
+        Ok(n + 1 - first)
+    }
+
+    /// Encode step `n` (the `last` flushes the batches) and say what the
+    /// host reads after it.
+    fn encode_step(&mut self, encoder: &mut wgpu::CommandEncoder, n: u32, last: bool) -> Reads {
+        let dt = self.grid.dt;
+        let mut reads = Reads::default();
+        let [buf_ex, buf_ey, buf_ez, ..] = &self.fields;
+
+        // Advance the moving window; the probe travels with it, the source
+        // stays at its lab position until it leaves the window.
+        let mut shift = 0;
+        if let Some(window) = &mut self.window_pass {
+            for _ in 0..window.pending(&self.grid, n) {
+                let face_grid = window.face_grid(&self.grid);
+                let coeffs = object_coefficients(&self.config, &face_grid, self.scene);
+                window.shift(&self.device, &self.queue, &coeffs);
+            }
+            shift = window.offset;
+        }
+
+        // Source injection: write the waveform into Ez at the source point,
+        // unless a plane-wave sheet, a Purcell dipole or a TDR step
+        // replaces it; the f32 fields take it from the device's clock
+        let [si, sj, sk] = self.config.source;
+        let src_id = (shift <= si).then(|| self.grid.idx(si - shift, sj, sk));
+        if let (Some(fields), Some(src_id)) = (&self.precision_pass, src_id) {
+            if !self.config.source_replaced() {
+                let value = self.source_amplitude * self.config.waveform.value(n as f64, dt);
+                fields.write_ez(&self.queue, src_id, value);
+            }
+        }
+        if let Some(source) = &mut self.source_pass {
+            let (waveform, amplitude) = (&self.config.waveform, self.source_amplitude);
+            source.set(&mut self.uploads, encoder, waveform, dt, amplitude, src_id);
+            source.encode(encoder);
+        }
+
+        // Lumped voltage sources, evaluated at the E-update midpoint n + ½
+        if let Some(ade) = &self.ade_pass {
+            let mut drives: Vec<f32> = self
+                .drives
+                .iter()
+                .map(|(v, w)| (v * w.value(n as f64 + 0.5, dt)) as f32)
+                .collect();
+            if let Some(cosim) = &self.cosim {
+                cosim.set_drives(&mut drives);
+            }
+            ade.set_drives(&mut self.uploads, encoder, &drives);
+        }
+
+        // Material modulation for this step
+        if let Some(modulation) = &mut self.modulation_pass {
+            modulation.update(n, dt, &mut self.uploads, encoder);
+        }
+
+        // Parent E^n around refined regions, for time interpolation
+        for subgrid in &self.subgrid_passes {
+            subgrid.encode_snapshot(encoder);
+        }
+
+        if let Some(fields) = &self.precision_pass {
+            // Non-f32 H and E updates
+            fields.encode(encoder);
+        }
+
+        if self.f32_update {
+            let [wg_x, wg_y, wg_z] = self.workgroups;
+            if let Some(adi) = &self.adi_pass {
+                // ADI step  (implicit solves along each axis → explicit H)
+                adi.encode(encoder);
+            } else if let Some(hie) = &self.hie_pass {
+                // HIE step  (explicit E/H along the implicit axis → line solves)
+                hie.encode(encoder);
+            } else {
+                // H-field update  (Shift&Add → Hadamard CP/CQ → Sum)
+                {
+                    let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                        label: Some("H update"),
+                        timestamp_writes: self.kernel_timer.as_ref().map(|t| t.writes(0)),
+                    });
+                    pass.set_pipeline(&self.pipeline_h);
+                    pass.set_bind_group(0, &self.bg_h, &[]);
+                    pass.dispatch_workgroups(wg_x, wg_y, wg_z);
+                }
+
+                // Sub-cell H corrections  (thin wires)
+                if let Some(corr) = &self.h_correction_pass {
+                    corr.encode(encoder);
+                }
+
+                // E-field update  (Shift&Add → Hadamard CA/CB → Sum)
+                {
+                    let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                        label: Some("E update"),
+                        timestamp_writes: self.kernel_timer.as_ref().map(|t| t.writes(1)),
+                    });
+                    pass.set_pipeline(&self.pipeline_e);
+                    pass.set_bind_group(0, &self.bg_e, &[]);
+                    pass.dispatch_workgroups(wg_x, wg_y, wg_z);
+                }
+                if let Some(timer) = &mut self.kernel_timer {
+                    timer.resolve(encoder);
+                }
+            }
+        }
+
+        // Auxiliary currents  (Drude sheets, lumped L and sources)
+        if let Some(ade) = &self.ade_pass {
+            ade.encode(encoder);
+        }
+
+        // Surface impedance on conductor faces
+        if let Some(sibc) = &self.sibc_pass {
+            sibc.encode(encoder);
+        }
+
+        // Child grid sub-steps and restriction back to the parent
+        for subgrid in &self.subgrid_passes {
+            subgrid.encode(encoder);
+        }
+
+        // Probe samples: the non-f32 field at the first probe every step,
+        // the f32 fields at every probe into the batched history
+        if let Some(fields) = &self.precision_pass {
+            let [i, j, k] = first_probe_cell(&self.config.probes);
+            reads.probe_at = fields.copy_ez(encoder, self.grid.idx(i, j, k), &self.buf_readback);
+        }
+        if let Some(set) = &mut self.probe_set {
+            set.encode(&mut self.uploads, encoder, last);
+        }
+        if let Some(monitors) = &mut self.monitor_pass {
+            monitors.encode(&mut self.uploads, encoder, n, last);
+        }
+        if let Some(dft) = &self.dft_pass {
+            dft.encode(&mut self.uploads, encoder, n);
+        }
+        if let Some(kspace) = &mut self.kspace_pass {
+            kspace.encode(encoder, n);
+        }
+        if let Some(flux) = &mut self.flux_pass {
+            flux.encode(&mut self.uploads, encoder, last);
+        }
+        if let (Some(energy), Some(every)) = (&mut self.energy_pass, self.config.energy_every) {
+            if n.is_multiple_of(every) {
+                energy.encode(encoder, n);
+                reads.energy = energy.full();
+            }
+        }
+        reads.divergence = self
+            .divergence_pass
+            .as_ref()
+            .is_some_and(|pass| pass.due(n));
+        if let (Some(divergence), true) = (&self.divergence_pass, reads.divergence) {
+            divergence.encode(encoder);
+        }
+        if let Some(cosim) = &self.cosim {
+            cosim.encode(encoder, [buf_ex, buf_ey, buf_ez]);
+        }
+
+        reads.batches = self.probe_set.as_ref().is_some_and(ProbeSet::copied)
+            || self.flux_pass.as_ref().is_some_and(FluxPass::copied)
+            || self.monitor_pass.as_ref().is_some_and(MonitorPass::copied)
+            || self.snapshot_writer.as_ref().is_some_and(|w| w.due(n))
+            || self.slice_writer.as_ref().is_some_and(|w| w.due(n))
+            || self
+                .hooks
+                .iter()
+                .any(|(every, _)| (n + 1).is_multiple_of(*every));
+        reads
+    }
+
+    /// Rows of the f32 probe `samples` taken from the device, paired with
+    /// the non-f32 samples of their steps.
+    fn probe_rows(&mut self, samples: Vec<Vec<f32>>) -> Vec<ProbeRow> {
+        let mut rows = Vec::with_capacity(samples.len());
+        for samples in samples {
+            let samples: Vec<f64> = samples.iter().map(|&v| v as f64).collect();
+            match self.precise.pop_front() {
+                Some((m, value)) => rows.push((m, vec![value], Some(samples[0]))),
+                None => rows.push((self.reported, samples, None)),
+            }
+            self.reported += 1;
+        }
+        rows
+    }
+
+    /// Output the probe rows still being read back, once the device is
+    /// done with them.
+    fn drain_probes(&mut self) -> Result<(), FdtdError> {
+        let Some(set) = &mut self.probe_set else {
+            return Ok(());
+        };
+        let samples = set.drain(&self.device)?;
+        let rows = self.probe_rows(samples);
+        self.record_rows(rows)
+    }
+
+    /// Trace, write and report probe `rows`.
+    fn record_rows(&mut self, rows: Vec<ProbeRow>) -> Result<(), FdtdError> {
+        let dt = self.grid.dt;
+        let probes = &self.config.probes;
+        for (m, mut values, reference) in rows {
+            let ports = values.split_off(values.len().min(probes.len()));
+            let traces = self.config.ports.iter().zip(&mut self.port_traces);
+            for ((port, trace), samples) in traces.zip(ports.chunks(5)) {
+                trace.push(port.voltage_current(&self.grid, samples));
+            }
+            for (trace, &value) in self.probe_traces.iter_mut().zip(&values) {
+                trace.push(value);
+            }
+            let t = (m + 1) as f64 * dt;
+            if let Some(writer) = &mut self.probe_writer {
+                for (p, &value) in values.iter().enumerate() {
+                    writer.record(p, m, t, value)?;
+                }
+            }
+            for (probe, &value) in probes.iter().zip(&values) {
+                let event = OutputEvent::Probe {
+                    name: probe.name,
+                    quantity: probe.quantity,
+                    step: m,
+                    time: t,
+                    value,
+                };
+                for sink in &mut self.sinks {
+                    sink.event(&event)?;
+                }
+            }
+            if let Some(reference) = reference {
+                self.max_diff = self.max_diff.max((values[0] - reference).abs());
+                self.max_ref = self.max_ref.max(reference.abs());
+            }
+            let energy = match self.energies.front() {
+                Some(&(step, u)) if step == m => {
+                    self.energies.pop_front();
+                    Some(u)
+                }
+                _ => None,
+            };
+            let json = self.config.output_format == OutputFormat::Json;
+            if self.config.verbosity != Verbosity::Verbose || json {
+                continue;
+            }
+            let mut line = format!("t={:4}", m);
+            for (probe, value) in probes.iter().zip(&values) {
+                line += &format!(
+                    "  {}[{}] = {:.6e}",
+                    probe.quantity.label(),
+                    probe.name,
+                    value
+                );
+            }
+            if let Some(reference) = reference {
+                line += &format!("  (f32 {:.6e})", reference);
+            }
+            if let Some(u) = energy {
+                line += &format!("  U = {:.6e} J", u);
+            }
+            match &self.progress {
+                Some(progress) => progress.println(&line),
+                None => println!("{line}"),
+            }
+        }
+        Ok(())
+    }
+
+    /// The failure of a run found diverged after `steps` steps, with the
+    /// largest |E| component `value` on `edge` (3·cell + lane), and its
+    /// fields written if the check asks for them.
+    fn diverged(&self, steps: u32, cause: Cause, value: f32, edge: usize) -> FdtdError {
+        let g = &self.grid;
+        let (id, axis) = (edge / 3, [Axis::X, Axis::Y, Axis::Z][edge % 3]);
+        let (nx, ny) = (g.nx as usize, g.ny as usize);
+        let cell = [id % nx, id / nx % ny, id / (nx * ny)].map(|n| n as u32);
+        let lane = axis.lane() as u32;
+
+        // The coefficients are not kept past the setup
+        let (material, model, stability) = match build_coefficients(&self.config, g, self.scene) {
+            Ok((coeffs, sub)) => {
+                let material = coeffs
+                    .e_material(id, axis, g.dt)
+                    .map(|(eps, sigma)| (eps / EPS0, sigma));
+                let on_edge = |cell: u32, comp: u32| cell as usize == id && comp == lane;
+                let model = if sub.ade_edges.iter().any(|e| on_edge(e.cell, e.comp)) {
+                    Some("dispersive or lumped")
+                } else if sub.sibc_edges.iter().any(|e| on_edge(e.e_cell, e.e_comp)) {
+                    Some("surface-impedance")
+                } else {
+                    None
+                };
+                let stability = match self.config.scheme {
+                    Scheme::Adi { .. } => None,
+                    scheme => {
+                        let limit = Stability::analyze(g, self.config.mode, scheme, &coeffs);
+                        Some(g.dt / limit.dt_max)
+                    }
+                };
+                (material, model, stability)
+            }
+            Err(_) => (None, None, None),
+        };
+
+        let mut snapshot = None;
+        if self.config.divergence.is_some_and(|check| check.snapshot) {
+            let dir = Path::new(self.config.monitor_dir).join("diverged");
+            let written = Field::ALL
+                .iter()
+                .map(|&field| Ok((field, self.field(field)?)))
+                .collect::<Result<Vec<_>, FdtdError>>()
+                .and_then(|fields| Ok(divergence::write_snapshot(&dir, g, steps - 1, &fields)?));
+            match written {
+                Ok(()) => snapshot = Some(dir),
+                Err(e) => warn!("cannot write the diverged fields: {e}"),
+            }
+        }
+
+        FdtdError::Diverged(Box::new(Divergence {
+            steps,
+            cause,
+            value,
+            field: Field::E(axis),
+            cell,
+            material,
+            model,
+            courant: C0 * g.dt / g.dx,
+            stability,
+            snapshot,
+        }))
+    }
+
+    /// [`ComputeBackend::run`] but for a lost device's panic.
+    fn run_batches(&mut self, steps: u32) -> Result<(), FdtdError> {
+        let mut left = steps;
+        while left > 0 && !self.stopped {
+            match self.submit(left.min(self.config.steps_per_submit)) {
+                Ok(taken) => left -= taken,
+                Err(e) => return Err(self.watch.lost().map_or(e, FdtdError::DeviceLost)),
+            }
+        }
+        let start = Instant::now();
+        self.device.poll(wgpu::Maintain::Wait);
+        if let Err(e) = self.drain_probes() {
+            return Err(self.watch.lost().map_or(e, FdtdError::DeviceLost));
+        }
+        self.info.stepping += start.elapsed();
+        Ok(())
+    }
+
+    /// [`Simulation::field`] awaiting the copy, which in the browser
+    /// completes on the event loop instead of a blocking device poll.
+    pub async fn field_async(&self, field: Field) -> Result<Vec<f32>, FdtdError> {
+        let buffer = &self.fields[field.index()];
+        gpu::read_f32_async(&self.device, &self.queue, buffer, 0, buffer.size()).await
+    }
+
+    /// The GPU buffers whose contents carry over from step to step, named
+    /// as in a checkpoint.
+    fn state_buffers(&self) -> Vec<(String, &wgpu::Buffer)> {
+        let mut buffers: Vec<_> = Field::ALL
+            .iter()
+            .zip(&self.fields)
+            .map(|(f, buffer)| (f.name().to_string(), buffer))
+            .collect();
+        if let Some(pass) = &self.precision_pass {
+            let fields = Field::ALL.iter().zip(pass.fields());
+            buffers.extend(fields.map(|(f, buffer)| (format!("p{}", f.name()), buffer)));
+        }
+        if let Some(pass) = &self.ade_pass {
+            buffers.push(("ade".to_string(), pass.state()));
+        }
+        if let Some(pass) = &self.sibc_pass {
+            buffers.push(("sibc".to_string(), pass.state()));
+        }
+        if let Some(pass) = &self.dft_pass {
+            let accumulators = pass.state().enumerate();
+            buffers.extend(accumulators.map(|(i, buffer)| (format!("dft{i}"), buffer)));
+        }
+        buffers
+    }
+
+}
+
+impl ComputeBackend for GpuRun {
+    fn config(&self) -> &Config {
+        &self.config
+    }
+
+    fn scene(&self) -> Scene {
+        self.scene
+    }
+
+    fn grid(&self) -> &Grid {
+        &self.grid
+    }
+
+    fn steps_taken(&self) -> u32 {
+        self.n
+    }
+
+    fn stopped(&self) -> bool {
+        self.stopped
+    }
+
+    /// Advance the fields by one Δt.  The batched readbacks (probes, flux,
+    /// monitors) flush on step `config.steps`, the nominal end of the run;
+    /// until then the probe rows come a few batches behind the steps (see
+    /// [`crate::probes`]), and [`run`](Self::run) catches them up.  Fails
+    /// when a readback or an output write does.
+    fn step(&mut self) -> Result<(), FdtdError> {
+        self.submit(1).map(drop)
+    }
+
+    /// Take `steps` steps, or fewer when a hook stops the run or a stop is
+    /// requested (see [`crate::interrupt`]), waiting for the device to
+    /// finish the last and outputting the probe rows still being read back.
+    /// The steps go to the device `config.steps_per_submit` at a time, in
+    /// one command buffer, unless the host reads something in between.  A
+    /// step failing on a lost device fails with [`FdtdError::DeviceLost`],
+    /// whatever went wrong first, a panic on it (a readback whose mapping
+    /// failed) included.
+    fn run(&mut self, steps: u32) -> Result<(), FdtdError> {
+        let watch = self.watch.clone();
+        match panic::catch_unwind(AssertUnwindSafe(|| self.run_batches(steps))) {
+            Ok(result) => result,
+            Err(payload) => match watch.lost() {
+                Some(reason) => Err(FdtdError::DeviceLost(reason)),
+                None => panic::resume_unwind(payload),
+            },
+        }
+    }
+
+    fn add_hook(&mut self, every: u32, hook: StepCallback) -> Result<(), FdtdError> {
+        self.hooks.push((every, hook));
+        Ok(())
+    }
+
+    fn add_boxed_sink(&mut self, sink: Box<dyn OutputSink>) {
+        self.sinks.push(sink);
+    }
+
+    fn source_mut(&mut self) -> SourceDrive<'_> {
+        SourceDrive {
+            cell: &mut self.config.source,
+            waveform: &mut self.config.waveform,
+            amplitude: &mut self.source_amplitude,
+        }
+    }
+
+    /// A single cell while another precision runs alone.
+    fn field(&self, field: Field) -> Result<Vec<f32>, FdtdError> {
+        let buffer = &self.fields[field.index()];
+        gpu::read_f32(&self.device, &self.queue, buffer, 0, buffer.size())
+    }
+
+    fn set_field(&mut self, field: Field, values: &[f32]) -> Result<(), FdtdError> {
+        let buffer = &self.fields[field.index()];
+        let expected = buffer.size() as usize / 4;
+        if values.len() != expected {
+            return Err(FdtdError::FieldLength {
+                expected,
+                found: values.len(),
+            });
+        }
+        self.queue
+            .write_buffer(buffer, 0, bytemuck::cast_slice(values));
+        Ok(())
+    }
+
+    /// Reads the one cell back.
+    fn value(&self, field: Field, cell: [u32; 3]) -> Result<f32, FdtdError> {
+        let id = self.grid.cell_idx(cell)?;
+        let buffer = &self.fields[field.index()];
+        if 4 * id as u64 >= buffer.size() {
+            // Another precision runs alone; the f32 buffer is a placeholder.
+            return Err(FdtdError::FieldLength {
+                expected: self.grid.total(),
+                found: buffer.size() as usize / 4,
+            });
+        }
+        Ok(gpu::read_f32(&self.device, &self.queue, buffer, 4 * id as u64, 4)?[0])
+    }
+
+    /// The mean time per step of each update kernel (see
+    /// [`crate::bench::KERNELS`]) over the last steps, from GPU timestamps;
+    /// empty without `config.time_kernels` or timestamps on the adapter, or
+    /// when another scheme or precision replaces the kernels.
+    fn kernel_times(&self) -> Result<Vec<(&'static str, Duration)>, FdtdError> {
+        match &self.kernel_timer {
+            Some(timer) => timer.times(&self.device, &self.queue),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Fails in the middle of a readback batch, or after a
+    /// [`step`](Self::step) whose rows are still being read back, when
+    /// samples are still on the device: take it after a [`run`](Self::run)
+    /// of a multiple of the batch.
+    fn save_checkpoint(&self, path: &Path) -> Result<(), FdtdError> {
+        let mid_batch = self.probe_set.as_ref().is_some_and(ProbeSet::mid_batch)
+            || self.flux_pass.as_ref().is_some_and(FluxPass::mid_batch);
+        if mid_batch {
+            let message = format!(
+                "no checkpoint after {} steps, in the middle of a probe batch of {}",
+                self.n,
+                self.config.readback_batch()
+            );
+            return Err(ConfigError(message).into());
+        }
+        let mut sections = vec![(
+            "scene".to_string(),
+            format!("{:?}", self.scene).into_bytes(),
+        )];
+        for (name, buffer) in self.state_buffers() {
+            let values = gpu::read_f32(&self.device, &self.queue, buffer, 0, buffer.size())?;
+            sections.push((name, bytemuck::cast_slice(&values).to_vec()));
+        }
+        for (p, trace) in self.probe_traces.iter().enumerate() {
+            let bytes = trace.iter().flat_map(|v| v.to_le_bytes()).collect();
+            sections.push((format!("probe{p}"), bytes));
+        }
+        for (p, trace) in self.port_traces.iter().enumerate() {
+            let bytes = trace
+                .iter()
+                .flatten()
+                .flat_map(|v| v.to_le_bytes())
+                .collect();
+            sections.push((format!("port{p}"), bytes));
+        }
+        let mut counters = [self.reported, self.flux_step]
+            .map(u32::to_le_bytes)
+            .concat();
+        counters.extend([self.max_diff, self.max_ref].map(f64::to_le_bytes).concat());
+        sections.push(("counters".to_string(), counters));
+
+        let sections: Vec<(&str, &[u8])> = sections
+            .iter()
+            .map(|(name, bytes)| (name.as_str(), &bytes[..]))
+            .collect();
+        checkpoint::write(path, &self.grid, self.n, &sections)?;
+        info!(path = %path.display(), steps = self.n, "wrote a checkpoint");
+        Ok(())
+    }
+
+    fn restore(&mut self, checkpoint: &Checkpoint) -> Result<(), FdtdError> {
+        if let Some(name) = self.config.unresumable() {
+            let message = format!("a run with {name} cannot resume from a checkpoint");
+            return Err(ConfigError(message).into());
+        }
+        let g = &self.grid;
+        let mismatch = if checkpoint.size != [g.nx, g.ny, g.nz] {
+            Some(format!("a {:?} grid", checkpoint.size))
+        } else if (checkpoint.dt - g.dt).abs() > 1e-9 * g.dt {
+            Some(format!("Δt = {:.6e} s", checkpoint.dt))
+        } else {
+            None
+        };
+        if let Some(what) = mismatch {
+            let message = format!("the checkpoint is of {what}, not this run's");
+            return Err(ConfigError(message).into());
+        }
+
+        let section = |name: &str| {
+            checkpoint.section(name).ok_or_else(|| {
+                let message = format!("the checkpoint has no {name} section for this run");
+                FdtdError::from(ConfigError(message))
+            })
+        };
+        for (name, buffer) in self.state_buffers() {
+            let bytes = section(&name)?;
+            if bytes.len() as u64 != buffer.size() {
+                let message = format!("the checkpoint's {name} section is of another size");
+                return Err(ConfigError(message).into());
+            }
+            self.queue.write_buffer(buffer, 0, bytes);
+        }
+        let f64s = |bytes: &[u8]| -> Vec<f64> {
+            bytes
+                .chunks_exact(8)
+                .map(|b| f64::from_le_bytes(b.try_into().unwrap()))
+                .collect()
+        };
+        for p in 0..self.probe_traces.len() {
+            self.probe_traces[p] = f64s(section(&format!("probe{p}"))?);
+        }
+        for p in 0..self.port_traces.len() {
+            let values = f64s(section(&format!("port{p}"))?);
+            self.port_traces[p] = values.chunks_exact(2).map(|v| [v[0], v[1]]).collect();
+        }
+        let counters = section("counters")?;
+        let u32_at = |at: usize| u32::from_le_bytes(counters[at..at + 4].try_into().unwrap());
+        let [max_diff, max_ref] = f64s(&counters[8..])[..] else {
+            return Err(ConfigError("the checkpoint's counters are incomplete".into()).into());
+        };
+        (self.reported, self.flux_step) = (u32_at(0), u32_at(4));
+        (self.max_diff, self.max_ref) = (max_diff, max_ref);
+
+        self.n = checkpoint.steps;
+        if let Some(source) = &self.source_pass {
+            source.set_step(&self.queue, self.n);
+        }
+        if let Some(progress) = &mut self.progress {
+            progress.update(self.n);
+        }
+        info!(steps = self.n, "resumed from a checkpoint");
+        Ok(())
+    }
+
+    /// A new device and the run set up on it again; the lost device goes
+    /// before the new one is opened.
+    fn reopen(self, resume: Option<PathBuf>) -> Result<Self, FdtdError> {
+        let (mut config, scene) = (self.config.clone(), self.scene);
+        let stepping = self.info.stepping;
+        config.resume = resume;
+        drop(self);
+        let clock = Instant::now();
+        let (opened, info) = open_device(&config, scene, clock)?;
+        let mut simulation = GpuRun::with_device(config, scene, opened, info, clock)?;
+        simulation.info.stepping += stepping;
+        Ok(simulation)
+    }
+
+    fn finish(mut self) -> Result<(Vec<Spectrum>, RunInfo), FdtdError> {
+        self.drain_probes()?;
+        if let Some(progress) = &self.progress {
+            progress.finish();
+        }
+        let _finish = debug_span!("finish", scene = ?self.scene).entered();
+        let mut sinks = std::mem::take(&mut self.sinks);
+        let cfg = &self.config;
+        let (device, queue, grid) = (&self.device, &self.queue, &self.grid);
+        let (dt, steps) = (grid.dt, self.n);
+        let dir = Path::new(cfg.monitor_dir);
+        let format = cfg.output_format;
+        let mut info = self.info.clone();
+        info.steps = steps;
+
+        if let Some(monitors) = &self.monitor_pass {
+            monitors.finish()?;
+        }
+        let (matches, s_matrix) = ports::write_results(dir, dt, &cfg.ports, &self.port_traces)?;
+        for (port, best) in cfg.ports.iter().zip(matches) {
+            if let Some(best) = best {
+                let values = [
+                    ("frequency", best.frequency),
+                    ("S11_dB", best.s11_db),
+                    ("Z_in_re", best.z.re),
+                    ("Z_in_im", best.z.im),
+                ];
+                report(
+                    &mut sinks,
+                    format,
+                    OutputEvent::Result {
+                        analysis: "port",
+                        name: port.name,
+                        values: &values,
+                    },
+                    format_args!(
+                        "Port {}: best match at f = {:.4e} Hz  S11 = {:.2} dB  Z_in = {:.2} Ω",
+                        port.name, best.frequency, best.s11_db, best.z
+                    ),
+                )?;
+            }
+        }
+        if let Some(m) = &s_matrix {
+            let values = [
+                ("ports", m.ports as f64),
+                ("frequencies", m.frequencies.len() as f64),
+            ];
+            report(
+                &mut sinks,
+                format,
+                OutputEvent::Result {
+                    analysis: "S-matrix",
+                    name: "",
+                    values: &values,
+                },
+                format_args!(
+                    "S-matrix of {} ports at {} frequencies complete",
+                    m.ports,
+                    m.frequencies.len()
+                ),
+            )?;
+            if let Some(options) = &cfg.touchstone {
+                let z0: Vec<f64> = cfg.ports.iter().map(|port| port.z0).collect();
+                let path = touchstone::write(dir, m, &z0, options)?;
+                if format == OutputFormat::Text {
+                    println!("Touchstone file: {}", path.display());
+                }
+            }
+        }
+        if let Some(cosim) = &self.cosim {
+            let peaks = cosim.write_results(dir, dt)?;
+            for (port, (v, i)) in cfg.circuits.iter().zip(peaks) {
+                report(
+                    &mut sinks,
+                    format,
+                    OutputEvent::Result {
+                        analysis: "circuit",
+                        name: port.name,
+                        values: &[("peak_V", v), ("peak_I", i)],
+                    },
+                    format_args!(
+                        "Circuit {}: peak |V| = {:.4e} V  peak |I| = {:.4e} A",
+                        port.name, v, i
+                    ),
+                )?;
+            }
+        }
+        if let Some(tdr) = &cfg.tdr {
+            let port = &cfg.ports[tdr.port];
+            let profile = tdr.profile(port, dt, &self.port_traces[tdr.port]);
+            let extremes = tdr::write_results(dir, tdr, &profile)?;
+            if let [Some((z_min, d_min)), Some((z_max, d_max))] = extremes {
+                let values = [
+                    ("Z_min", z_min),
+                    ("distance_min", d_min),
+                    ("Z_max", z_max),
+                    ("distance_max", d_max),
+                ];
+                report(
+                    &mut sinks,
+                    format,
+                    OutputEvent::Result {
+                        analysis: "TDR",
+                        name: tdr.name,
+                        values: &values,
+                    },
+                    format_args!(
+                        "TDR {}: Z_min = {:.2} Ω at {:.4e} m  Z_max = {:.2} Ω at {:.4e} m",
+                        tdr.name, z_min, d_min, z_max, d_max
+                    ),
+                )?;
+            }
+        }
+        if let Some(spec) = &cfg.probe_spectra {
+            let spectrum_dir = cfg
+                .probe_output
+                .map_or(cfg.monitor_dir, |output| output.dir);
+            for (probe, trace) in cfg.probes.iter().zip(&self.probe_traces) {
+                let spectrum = spec.analyze(trace, dt);
+                spectra::write_spectrum(spectrum_dir.as_ref(), probe.name, spec, &spectrum)?;
+                if let Some(peak) = spectrum.peak() {
+                    report(
+                        &mut sinks,
+                        format,
+                        OutputEvent::Result {
+                            analysis: "spectrum",
+                            name: probe.name,
+                            values: &[("peak", peak)],
+                        },
+                        format_args!("Spectrum [{}]: peak at f = {:.6e} Hz", probe.name, peak),
+                    )?;
+                }
+            }
+        }
+        if let Some(spec) = &cfg.harminv {
+            for (probe, trace) in cfg.probes.iter().zip(&self.probe_traces) {
+                let modes = harminv::analyze(trace, dt, spec);
+                harminv::write_resonances(dir, probe.name, &modes)?;
+                for m in modes {
+                    report(
+                        &mut sinks,
+                        format,
+                        OutputEvent::Result {
+                            analysis: "resonance",
+                            name: probe.name,
+                            values: &[("frequency", m.frequency), ("Q", m.q)],
+                        },
+                        format_args!(
+                            "Resonance [{}]: f = {:.6e} Hz  Q = {:.1}",
+                            probe.name, m.frequency, m.q
+                        ),
+                    )?;
+                }
+            }
+        }
+        if let Some(kspace) = &self.kspace_pass {
+            let spectra = kspace.read(device, queue, grid)?;
+            kspace::write_results(dir, &cfg.kspace_monitors, &spectra)?;
+            for (m, s) in cfg.kspace_monitors.iter().zip(&spectra) {
+                let [ku, kv] = s.peak();
+                let values = [("frames", s.frames as f64), ("ku", ku), ("kv", kv)];
+                report(
+                    &mut sinks,
+                    format,
+                    OutputEvent::Result {
+                        analysis: "k-space",
+                        name: m.name,
+                        values: &values,
+                    },
+                    format_args!(
+                        "k-space {}: {} frames, peak at k = ({:.4e}, {:.4e}) rad/m",
+                        m.name, s.frames, ku, kv
+                    ),
+                )?;
+            }
+        }
+        let mut rt_spectra = Vec::new();
+        if let Some(dft) = &self.dft_pass {
+            let layout = &self.dft_layout;
+            let mut spectra = dft.read(device, queue)?;
+            dft::write_spectra(dir, &spectra)?;
+            for spectrum in &spectra[..cfg.dft_monitors.len()] {
+                for sink in &mut sinks {
+                    sink.event(&OutputEvent::Spectrum(spectrum))?;
+                }
+            }
+            // Spectra of the flux rectangles follow the DFT monitors' ones
+            let flux_spectra = &spectra[cfg.dft_monitors.len()..];
+            let nets =
+                flux::write_spectra(dir, grid, &cfg.flux_monitors, &cfg.flux_boxes, flux_spectra)?;
+            for (b, net) in cfg.flux_boxes.iter().zip(nets) {
+                for (&frequency, power) in b.frequencies.iter().zip(net) {
+                    report(
+                        &mut sinks,
+                        format,
+                        OutputEvent::Result {
+                            analysis: "flux box",
+                            name: b.name,
+                            values: &[("frequency", frequency), ("P_out", power)],
+                        },
+                        format_args!(
+                            "Flux box {}: f = {:.4e} Hz  P_out = {:.6e} W",
+                            b.name, frequency, power
+                        ),
+                    )?;
+                }
+            }
+            let rcs_spectra = &spectra[layout.rcs..layout.pattern];
+            let monostatic = rcs::write_results(dir, grid, steps, &cfg.rcs, rcs_spectra)?;
+            for (r, sigma) in cfg.rcs.iter().zip(monostatic) {
+                for (&frequency, s) in r.frequencies.iter().zip(sigma) {
+                    report(
+                        &mut sinks,
+                        format,
+                        OutputEvent::Result {
+                            analysis: "RCS",
+                            name: r.name,
+                            values: &[("frequency", frequency), ("sigma", s)],
+                        },
+                        format_args!(
+                            "RCS {}: f = {:.4e} Hz  σ = {:.6e} m² ({:.2} dBsm)",
+                            r.name,
+                            frequency,
+                            s,
+                            10.0 * s.log10()
+                        ),
+                    )?;
+                }
+            }
+            let pattern_spectra = &spectra[layout.pattern..layout.mode];
+            let peaks = pattern::write_cuts(dir, grid, &cfg.patterns, pattern_spectra)?;
+            for (p, peak) in cfg.patterns.iter().zip(peaks) {
+                for (&frequency, g) in p.frequencies.iter().zip(peak) {
+                    report(
+                        &mut sinks,
+                        format,
+                        OutputEvent::Result {
+                            analysis: "pattern",
+                            name: p.name,
+                            values: &[("frequency", frequency), ("peak_gain_dBi", g)],
+                        },
+                        format_args!(
+                            "Pattern {}: f = {:.4e} Hz  peak gain = {:.2} dBi",
+                            p.name, frequency, g
+                        ),
+                    )?;
+                }
+            }
+            let mode_spectra = &spectra[layout.mode..layout.purcell];
+            let amplitudes = modes::write_results(dir, grid, &cfg.mode_monitors, mode_spectra)?;
+            for (m, amplitudes) in cfg.mode_monitors.iter().zip(amplitudes) {
+                for (&frequency, modes) in m.frequencies.iter().zip(amplitudes) {
+                    for (profile, a) in m.modes.iter().zip(modes) {
+                        let name = format!("{} {}", m.name, profile.label());
+                        let values = [
+                            ("frequency", frequency),
+                            ("P_forward", a.forward_power),
+                            ("P_backward", a.backward_power),
+                        ];
+                        report(
+                            &mut sinks,
+                            format,
+                            OutputEvent::Result {
+                                analysis: "mode",
+                                name: &name,
+                                values: &values,
+                            },
+                            format_args!(
+                                "Mode {}: f = {:.4e} Hz  P+ = {:.6e} W  P- = {:.6e} W",
+                                name, frequency, a.forward_power, a.backward_power
+                            ),
+                        )?;
+                    }
+                }
+            }
+            if let Some(dipole) = &cfg.purcell {
+                let purcell_spectra = &spectra[layout.purcell..layout.sar];
+                let enhancement = dipole.enhancement(grid, steps, purcell_spectra);
+                purcell::write_results(dir, dipole, &enhancement)?;
+                for (&frequency, e) in dipole.frequencies.iter().zip(enhancement) {
+                    let values = [
+                        ("frequency", frequency),
+                        ("purcell", e.purcell),
+                        ("radiative", e.radiative),
+                    ];
+                    report(
+                        &mut sinks,
+                        format,
+                        OutputEvent::Result {
+                            analysis: "Purcell",
+                            name: dipole.name,
+                            values: &values,
+                        },
+                        format_args!(
+                            "Purcell {}: f = {:.4e} Hz  P/P0 = {:.4}  P_rad/P0 = {:.4}",
+                            dipole.name, frequency, e.purcell, e.radiative
+                        ),
+                    )?;
+                }
+            }
+            let sar_spectra = &spectra[layout.sar..layout.rt];
+            let peaks = sar::write_results(dir, grid, steps, &cfg.sar, &cfg.phantoms, sar_spectra)?;
+            for (s, peaks) in cfg.sar.iter().zip(peaks) {
+                for (&frequency, p) in s.frequencies.iter().zip(peaks) {
+                    let [local, one, ten] = p.peaks.map(|(value, _)| value);
+                    let values = [
+                        ("frequency", frequency),
+                        ("average", p.average),
+                        ("peak", local),
+                        ("peak_1g", one),
+                        ("peak_10g", ten),
+                    ];
+                    report(
+                        &mut sinks,
+                        format,
+                        OutputEvent::Result {
+                            analysis: "SAR",
+                            name: s.name,
+                            values: &values,
+                        },
+                        format_args!(
+                            "SAR {}: f = {:.4e} Hz  average = {:.4e}  peak = {:.4e}  \
+                             1 g = {:.4e}  10 g = {:.4e} W/kg",
+                            s.name, frequency, p.average, local, one, ten
+                        ),
+                    )?;
+                }
+            }
+            if let Some(spec) = &cfg.thermal {
+                let sar_box = &cfg.sar[spec.sar];
+                let dissipation = sar_box.dissipation(
+                    grid,
+                    steps,
+                    &cfg.phantoms,
+                    &sar_spectra[spec.sar],
+                    spec.frequency,
+                )?;
+                let heated = spec.heated_box(grid, sar_box, &cfg.phantoms, dissipation)?;
+                let records = thermal::run(device, queue, dir, grid, spec, &heated)?;
+                if let Some(r) = records.last() {
+                    let values = [
+                        ("time", r.time),
+                        ("peak_rise", r.peak),
+                        ("mean_rise", r.mean),
+                    ];
+                    report(
+                        &mut sinks,
+                        format,
+                        OutputEvent::Result {
+                            analysis: "thermal",
+                            name: spec.name,
+                            values: &values,
+                        },
+                        format_args!(
+                            "Thermal {}: t = {:.1} s  peak rise = {:.4} K  mean rise = {:.4} K",
+                            spec.name, r.time, r.peak, r.mean
+                        ),
+                    )?;
+                }
+            }
+            rt_spectra = spectra.split_off(layout.rt);
+        }
+        if self.precision_pass.is_some() && cfg.compare_f32 {
+            let relative = self.max_diff / self.max_ref;
+            let name = format!("{:?}", self.precision);
+            report(
+                &mut sinks,
+                format,
+                OutputEvent::Result {
+                    analysis: "precision",
+                    name: &name,
+                    values: &[("max_diff", self.max_diff), ("relative", relative)],
+                },
+                format_args!(
+                    "\n{name} vs f32: max |ΔEz| = {:.3e} ({:.3} % of the f32 peak)",
+                    self.max_diff,
+                    100.0 * relative
+                ),
+            )?;
+        }
+        for sink in &mut sinks {
+            sink.finish()?;
+        }
+        if format == OutputFormat::Text {
+            progress::suspend(|| println!("\nSimulation complete."));
+        }
+        Ok((rt_spectra, info))
+    }
+}
+
+/// The 3D solver, stepped one Δt at a time on the GPU or, with
+/// [`Backend::Cpu`], on the CPU (see [`crate::cpu`]), through the
+/// operations of [`ComputeBackend`].
+pub struct Simulation {
+    run: Run,
+}
+
+/// The run behind a [`Simulation`].
+enum Run {
+    Gpu(Box<GpuRun>),
+    Cpu(Box<CpuRun>),
+}
+
+impl Simulation {
+    /// Fluent setup, validated before the device is opened.
+    pub fn builder() -> SimulationBuilder {
+        SimulationBuilder::new()
+    }
+
+    /// Set up the 3D run of `scene` on the first high-performance adapter,
+    /// or on the CPU when [`Config::backend`] says so.
+    ///
+    /// Fails on a configuration [`Config::validate`] rejects, without an
+    /// adapter, on a device short of the solver's limits, on a grid whose
+    /// buffers do not fit and on a configuration the 3D solver cannot step
+    /// (reduced modes and band diagrams go through [`run_scene`]).
+    pub fn new(config: Config, scene: Scene) -> Result<Simulation, FdtdError> {
+        let clock = Instant::now();
+        config.validate()?;
+        require_3d(&config)?;
+        Simulation::open(config, scene, clock)
+    }
+
+    /// [`Simulation::new`] awaiting the adapter and device instead of
+    /// blocking on them, as the browser build must (the `web` module).
+    pub async fn new_async(config: Config, scene: Scene) -> Result<Simulation, FdtdError> {
+        let clock = Instant::now();
+        config.validate()?;
+        require_3d(&config)?;
+        if config.backend == Some(Backend::Cpu) {
+            return Simulation::open(config, scene, clock);
+        }
+        let opened = request_device(&config, scene).await?;
+        let info = run_header(&config, scene, &opened.adapter, opened.precision, clock);
+        let run = GpuRun::with_device(config, scene, opened, info, clock)?;
+        Ok(Simulation {
+            run: Run::Gpu(Box::new(run)),
+        })
+    }
+
+    /// The run of a validated 3D configuration on its backend.
+    fn open(config: Config, scene: Scene, clock: Instant) -> Result<Simulation, FdtdError> {
+        let run = match config.backend {
+            Some(Backend::Cpu) => Run::Cpu(Box::new(CpuRun::new(config, scene, clock)?)),
+            _ => {
+                let (opened, info) = open_device(&config, scene, clock)?;
+                let run = GpuRun::with_device(config, scene, opened, info, clock)?;
+                Run::Gpu(Box::new(run))
+            }
+        };
+        Ok(Simulation { run })
+    }
+
+    /// [`ComputeBackend::field`] awaiting the copy, which in the browser
+    /// completes on the event loop instead of a blocking device poll.
+    pub async fn field_async(&self, field: Field) -> Result<Vec<f32>, FdtdError> {
+        match &self.run {
+            Run::Gpu(run) => run.field_async(field).await,
+            Run::Cpu(run) => run.field(field),
+        }
+    }
+}
+
+/// Each operation on the backend held.
+impl ComputeBackend for Simulation {
+    fn config(&self) -> &Config {
+        match &self.run {
+            Run::Gpu(run) => run.config(),
+            Run::Cpu(run) => run.config(),
+        }
+    }
+
+    fn scene(&self) -> Scene {
+        match &self.run {
+            Run::Gpu(run) => run.scene(),
+            Run::Cpu(run) => run.scene(),
+        }
+    }
+
+    fn grid(&self) -> &Grid {
+        match &self.run {
+            Run::Gpu(run) => run.grid(),
+            Run::Cpu(run) => run.grid(),
+        }
+    }
+
+    fn steps_taken(&self) -> u32 {
+        match &self.run {
+            Run::Gpu(run) => run.steps_taken(),
+            Run::Cpu(run) => run.steps_taken(),
+        }
+    }
+
+    fn stopped(&self) -> bool {
+        match &self.run {
+            Run::Gpu(run) => run.stopped(),
+            Run::Cpu(run) => run.stopped(),
+        }
+    }
+
+    /// On the GPU the batched readbacks (probes, flux, monitors) flush on
+    /// step `config.steps`, the nominal end of the run; until then the
+    /// probe rows come a few batches behind the steps (see
+    /// [`crate::probes`]), and [`run`](Self::run) catches them up.
+    fn step(&mut self) -> Result<(), FdtdError> {
+        match &mut self.run {
+            Run::Gpu(run) => run.step(),
+            Run::Cpu(run) => run.step(),
+        }
+    }
+
+    /// On the GPU the steps go to the device `config.steps_per_submit` at a
+    /// time, in one command buffer, unless the host reads something in
+    /// between, and the probe rows still being read back are output before
+    /// this returns.
+    fn run(&mut self, steps: u32) -> Result<(), FdtdError> {
+        match &mut self.run {
+            Run::Gpu(run) => run.run(steps),
+            Run::Cpu(run) => run.run(steps),
+        }
+    }
+
+    fn add_hook(&mut self, every: u32, hook: StepCallback) -> Result<(), FdtdError> {
+        match &mut self.run {
+            Run::Gpu(run) => run.add_hook(every, hook),
+            Run::Cpu(run) => run.add_hook(every, hook),
+        }
+    }
+
+    fn add_boxed_sink(&mut self, sink: Box<dyn OutputSink>) {
+        match &mut self.run {
+            Run::Gpu(run) => run.add_boxed_sink(sink),
+            Run::Cpu(run) => run.add_boxed_sink(sink),
+        }
+    }
+
+    fn source_mut(&mut self) -> SourceDrive<'_> {
+        match &mut self.run {
+            Run::Gpu(run) => run.source_mut(),
+            Run::Cpu(run) => run.source_mut(),
+        }
+    }
+
+    /// A single cell while another precision runs alone on the GPU.
+    fn field(&self, field: Field) -> Result<Vec<f32>, FdtdError> {
+        match &self.run {
+            Run::Gpu(run) => run.field(field),
+            Run::Cpu(run) => run.field(field),
+        }
+    }
+
+    fn set_field(&mut self, field: Field, values: &[f32]) -> Result<(), FdtdError> {
+        match &mut self.run {
+            Run::Gpu(run) => run.set_field(field, values),
+            Run::Cpu(run) => run.set_field(field, values),
+        }
+    }
+
+    fn value(&self, field: Field, cell: [u32; 3]) -> Result<f32, FdtdError> {
+        match &self.run {
+            Run::Gpu(run) => run.value(field, cell),
+            Run::Cpu(run) => run.value(field, cell),
+        }
+    }
+
+    /// From GPU timestamps; empty without `config.time_kernels` or
+    /// timestamps on the adapter, when another scheme or precision replaces
+    /// the kernels and on the CPU.
+    fn kernel_times(&self) -> Result<Vec<(&'static str, Duration)>, FdtdError> {
+        match &self.run {
+            Run::Gpu(run) => run.kernel_times(),
+            Run::Cpu(run) => run.kernel_times(),
+        }
+    }
+
+    /// On the GPU this fails in the middle of a readback batch, or after a
+    /// [`step`](Self::step) whose rows are still being read back, when
+    /// samples are still on the device: take it after a [`run`](Self::run)
+    /// of a multiple of the batch.
+    fn save_checkpoint(&self, path: &Path) -> Result<(), FdtdError> {
+        match &self.run {
+            Run::Gpu(run) => run.save_checkpoint(path),
+            Run::Cpu(run) => run.save_checkpoint(path),
+        }
+    }
+
+    fn restore(&mut self, checkpoint: &Checkpoint) -> Result<(), FdtdError> {
+        match &mut self.run {
+            Run::Gpu(run) => run.restore(checkpoint),
+            Run::Cpu(run) => run.restore(checkpoint),
+        }
+    }
+
+    fn reopen(self, resume: Option<PathBuf>) -> Result<Self, FdtdError> {
+        let run = match self.run {
+            Run::Gpu(run) => Run::Gpu(Box::new(run.reopen(resume)?)),
+            Run::Cpu(run) => Run::Cpu(Box::new(run.reopen(resume)?)),
+        };
+        Ok(Simulation { run })
+    }
+
+    fn finish(self) -> Result<(Vec<Spectrum>, RunInfo), FdtdError> {
+        match self.run {
+            Run::Gpu(run) => run.finish(),
+            Run::Cpu(run) => run.finish(),
+        }
+    }
+}
+
+fn require_3d(config: &Config) -> Result<(), FdtdError> {
+    if config.mode != Mode::ThreeD || config.bands.is_some() {
+        let message = "Simulation steps the 3D solver; reduced modes and band diagrams go \
+                       through run_scene";
+        return Err(ConfigError(message.to_string()).into());
+    }
+    if config.cluster.is_some() {
+        let message = "Simulation steps the whole grid; a rank of a distributed run goes \
+                       through run_scene";
+        return Err(ConfigError(message.to_string()).into());
+    }
+    Ok(())
+}
+
+/// One run of `scene`: the 3D solver for the configured steps (on this
+/// rank's slab in a distributed run, see [`crate::distributed`]), or the
+/// reduced or band-diagram run that replaces it.  Returns the
+/// REFLECTANCE and UNIT_CELL plane spectra and the SHIELDING point spectra,
+/// and the run's summary for results.json.
+pub fn run_scene(config: &Config, scene: Scene) -> Result<(Vec<Spectrum>, RunInfo), FdtdError> {
+    config.validate()?;
+    let clock = Instant::now();
+    let reduced = config.mode != Mode::ThreeD;
+    if reduced || config.bands.is_some() {
+        let (opened, mut info) = open_device(config, scene, clock)?;
+        let (device, queue) = (opened.device, opened.queue);
+        let start = Instant::now();
+        if reduced {
+            let grid = run_reduced(config, &device, &queue)?;
+            info.size = [grid.nx, grid.ny, grid.nz];
+            info.dt = grid.dt;
+        } else {
+            let (grid, steps) = run_bands(config, &device, &queue)?;
+            (info.dt, info.steps) = (grid.dt, steps);
+        }
+        info.stepping = start.elapsed();
+        return Ok((Vec::new(), info));
+    }
+    if config.cluster.is_some() {
+        return backend::drive(DistributedRun::new(config.clone(), scene, clock)?);
+    }
+    backend::drive(Simulation::open(config.clone(), scene, clock)?)
+}
+
+/// The whole program: a reference run first when reflection/transmission,
+/// unit-cell or shielding spectra are normalised by one, the run of the
+/// structure, the normalised results and `monitor_dir/results.json`.
+/// Returns the summaries of the runs, as results.json lists them.
+pub fn run(config: &Config) -> Result<Vec<RunInfo>, FdtdError> {
+    config.validate()?;
+    let (started, clock) = (SystemTime::now(), Instant::now());
+    let reference = match config.normalised() {
+        true => Some(run_scene(config, Scene::Reference)?),
+        false => None,
+    };
+    let (spectra, info) = run_scene(config, Scene::Structure)?;
+    let mut runs: Vec<RunInfo> = reference.iter().map(|(_, info)| info.clone()).collect();
+    runs.push(info);
+    for info in &runs {
+        debug!(
+            scene = %info.scene,
+            setup_s = info.setup.as_secs_f64(),
+            stepping_s = info.stepping.as_secs_f64(),
+            mcells_per_s = info.throughput() / 1e6,
+            "run timings"
+        );
+    }
+    // Rank 0 writes the results of a distributed run
+    if config
+        .cluster
+        .as_ref()
+        .is_some_and(|cluster| cluster.rank != 0)
+    {
+        return Ok(runs);
+    }
+    let (mut sinks, format) = (console_sinks(config), config.output_format);
+    if let Some((reference, _)) = reference {
+        let dir = Path::new(config.monitor_dir);
+        let planes = 2 * config.reflectance.len();
+        let rt = reflectance::write_results(
+            dir,
+            &config.grid,
+            &config.reflectance,
+            &reference,
+            &spectra,
+        )?;
+        for (c, spectrum) in config.reflectance.iter().zip(rt) {
+            for (&frequency, [r, t]) in c.frequencies.iter().zip(spectrum) {
+                let values = [
+                    ("frequency", frequency),
+                    ("R", r),
+                    ("T", t),
+                    ("A", 1.0 - r - t),
+                ];
+                report(
+                    &mut sinks,
+                    format,
+                    OutputEvent::Result {
+                        analysis: "R/T",
+                        name: c.name,
+                        values: &values,
+                    },
+                    format_args!(
+                        "R/T {}: f = {:.4e} Hz  R = {:.4}  T = {:.4}  A = {:.4}",
+                        c.name,
+                        frequency,
+                        r,
+                        t,
+                        1.0 - r - t
+                    ),
+                )?;
+            }
+        }
+        if let Some(cell) = &config.unit_cell {
+            let coefficients =
+                cell.coefficients(&config.grid, &reference[planes..], &spectra[planes..]);
+            unit_cell::write_coefficients(dir, cell, &coefficients)?;
+            for (&frequency, c) in cell.frequencies.iter().zip(&coefficients) {
+                let values = [
+                    ("frequency", frequency),
+                    ("t_re", c.t.re),
+                    ("t_im", c.t.im),
+                    ("r_re", c.r.re),
+                    ("r_im", c.r.im),
+                ];
+                report(
+                    &mut sinks,
+                    format,
+                    OutputEvent::Result {
+                        analysis: "unit cell",
+                        name: cell.name,
+                        values: &values,
+                    },
+                    format_args!(
+                        "Unit cell {}: f = {:.4e} Hz  t = {:.4} ∠ {:.1}°  r = {:.4} ∠ {:.1}°",
+                        cell.name,
+                        frequency,
+                        c.t.norm(),
+                        c.t.arg().to_degrees(),
+                        c.r.norm(),
+                        c.r.arg().to_degrees()
+                    ),
+                )?;
+            }
+        }
+        if let Some(spec) = &config.shielding {
+            let points = planes + 2 * config.unit_cell.iter().count();
+            let se = spec.effectiveness(&reference[points..], &spectra[points..]);
+            let lowest = shielding::write_results(dir, spec, &se)?;
+            for (&frequency, se) in spec.frequencies.iter().zip(lowest) {
+                report(
+                    &mut sinks,
+                    format,
+                    OutputEvent::Result {
+                        analysis: "shielding",
+                        name: spec.name,
+                        values: &[("frequency", frequency), ("lowest_SE_E_dB", se)],
+                    },
+                    format_args!(
+                        "Shielding {}: f = {:.4e} Hz  lowest SE_E = {:.2} dB",
+                        spec.name, frequency, se
+                    ),
+                )?;
+            }
+        }
+    }
+
+    let outputs = config.output_dirs();
+    let outputs: Vec<&Path> = outputs.iter().map(Path::new).collect();
+    let path = manifest::write(
+        config.monitor_dir.as_ref(),
+        config.to_json(),
+        &runs,
+        started,
+        clock.elapsed(),
+        &outputs,
+    )?;
+    report(
+        &mut sinks,
+        format,
+        OutputEvent::Summary { path: &path },
+        format_args!("Run summary: {}", path.display()),
+    )?;
+    for sink in &mut sinks {
+        sink.finish()?;
+    }
+    Ok(runs)
+}
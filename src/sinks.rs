@@ -0,0 +1,359 @@
+//! Pluggable outputs of a run.
+//!
+//! Besides the files of the configuration, a [`Simulation`](crate::Simulation)
+//! hands every probe sample, every component of a due snapshot and the
+//! spectra of the DFT monitors to the sinks added with
+//! [`Simulation::add_sink`](crate::ComputeBackend::add_sink), as typed
+//! [`OutputEvent`]s.  A database or message-queue writer is an
+//! [`OutputSink`] of its own; [`FileSink`], [`MatSink`], [`StdoutSink`] and
+//! [`JsonLinesSink`] are built in.
+//!
+//! Snapshots reach the sinks on the schedule of [`Config::snapshots`]
+//! (nothing is copied back without one), steps after each one, spectra and
+//! the results of the analyses once in
+//! [`Simulation::finish`](crate::ComputeBackend::finish), after which every
+//! sink's [`OutputSink::finish`] is called.  With
+//! [`OutputFormat::Json`](crate::config::OutputFormat::Json) a run adds a
+//! [`JsonLinesSink`] itself and prints nothing else on stdout.
+//!
+//! [`Config::snapshots`]: crate::Config::snapshots
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use serde_json::json;
+
+use crate::dft;
+use crate::grid::{Field, Grid};
+use crate::mat::MatFile;
+use crate::probes::Quantity;
+use crate::snapshots;
+
+/// One output of a run; times are those of E after step `step`,
+/// (step + 1)·Δt (s).
+#[derive(Copy, Clone)]
+pub enum OutputEvent<'a> {
+    /// One sample of a probe of the configuration.
+    Probe {
+        name: &'a str,
+        quantity: Quantity,
+        step: u32,
+        time: f64,
+        value: f64,
+    },
+    /// One component of a full-volume snapshot, x fastest.
+    Snapshot {
+        field: Field,
+        step: u32,
+        time: f64,
+        grid: &'a Grid,
+        values: &'a [f32],
+    },
+    /// The spectra of one DFT monitor at the end of the run.
+    Spectrum(&'a dft::Spectrum),
+    /// Step `step` of the `steps` of the run is done.
+    Step { step: u32, steps: u32, time: f64 },
+    /// Figures of one analysis at the end of the run, in SI units, e.g.
+    /// the frequency and R, T and A of a reflectance monitor.
+    Result {
+        analysis: &'a str,
+        name: &'a str,
+        values: &'a [(&'a str, f64)],
+    },
+    /// The run summary (see [`crate::manifest`]) is written at `path`.
+    Summary { path: &'a Path },
+}
+
+/// Receiver of [`OutputEvent`]s; an error stops the run like a failed
+/// write of the configured files.
+pub trait OutputSink: Send {
+    fn event(&mut self, event: &OutputEvent) -> io::Result<()>;
+
+    /// Called once after the last event.
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// One line per probe sample and spectrum, and one per snapshot component
+/// with its extremes; steps and results are left to the run's own lines.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct StdoutSink;
+
+impl OutputSink for StdoutSink {
+    fn event(&mut self, event: &OutputEvent) -> io::Result<()> {
+        let mut out = io::stdout().lock();
+        match *event {
+            OutputEvent::Probe {
+                name,
+                quantity,
+                step,
+                time,
+                value,
+            } => writeln!(
+                out,
+                "probe {name} {} n={step} t={time:e} {value:e}",
+                quantity.label()
+            ),
+            OutputEvent::Snapshot {
+                field,
+                step,
+                time,
+                values,
+                ..
+            } => {
+                let (lo, hi) = extremes(values);
+                writeln!(
+                    out,
+                    "snapshot {} n={step} t={time:e} min={lo:e} max={hi:e}",
+                    field.name()
+                )
+            }
+            OutputEvent::Spectrum(s) => writeln!(
+                out,
+                "spectrum {}: {} frequencies over {} cells",
+                s.name,
+                s.frequencies.len(),
+                s.cells()
+            ),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// One JSON object per line on stdout for every event, its kind under
+/// `"event"`:
+///
+/// ```text
+/// {"event":"step","step":41,"steps":1000,"time":5.6e-12}
+/// {"event":"probe","name":"center","quantity":"Ez","step":41,"time":5.6e-12,"value":0.0013}
+/// {"event":"snapshot","field":"Ez","step":99,"time":1.3e-11,"min":-0.2,"max":0.4}
+/// {"event":"spectrum","name":"plane","frequencies":[1e9,2e9],"cells":4096}
+/// {"event":"result","analysis":"R/T","name":"slab","frequency":1e10,"R":0.31,"T":0.69,"A":0.0}
+/// {"event":"summary","path":"output/results.json"}
+/// ```
+///
+/// Snapshots carry their extremes rather than their values.  Each line is
+/// flushed as it is written, so a reader sees the run's progress live.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct JsonLinesSink;
+
+impl OutputSink for JsonLinesSink {
+    fn event(&mut self, event: &OutputEvent) -> io::Result<()> {
+        let line = match *event {
+            OutputEvent::Probe {
+                name,
+                quantity,
+                step,
+                time,
+                value,
+            } => json!({
+                "event": "probe",
+                "name": name,
+                "quantity": quantity.label(),
+                "step": step,
+                "time": time,
+                "value": value,
+            }),
+            OutputEvent::Snapshot {
+                field,
+                step,
+                time,
+                values,
+                ..
+            } => {
+                let (lo, hi) = extremes(values);
+                json!({
+                    "event": "snapshot",
+                    "field": field.name(),
+                    "step": step,
+                    "time": time,
+                    "min": lo as f64,
+                    "max": hi as f64,
+                })
+            }
+            OutputEvent::Spectrum(s) => json!({
+                "event": "spectrum",
+                "name": s.name,
+                "frequencies": s.frequencies,
+                "cells": s.cells(),
+            }),
+            OutputEvent::Step { step, steps, time } => json!({
+                "event": "step",
+                "step": step,
+                "steps": steps,
+                "time": time,
+            }),
+            OutputEvent::Result {
+                analysis,
+                name,
+                values,
+            } => {
+                let mut line = json!({
+                    "event": "result",
+                    "analysis": analysis,
+                    "name": name,
+                });
+                for &(key, value) in values {
+                    line[key] = value.into();
+                }
+                line
+            }
+            OutputEvent::Summary { path } => json!({
+                "event": "summary",
+                "path": path.display().to_string(),
+            }),
+        };
+        let mut out = io::stdout().lock();
+        writeln!(out, "{line}")?;
+        out.flush()
+    }
+}
+
+/// Smallest and largest of `values`.
+fn extremes(values: &[f32]) -> (f32, f32) {
+    values
+        .iter()
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), &v| {
+            (lo.min(v), hi.max(v))
+        })
+}
+
+/// Files in one directory: `<probe>.csv` traces, raw snapshot files as
+/// [`crate::snapshots`] writes them and DFT spectra as
+/// [`dft::write_spectra`] does.
+pub struct FileSink {
+    dir: PathBuf,
+    probes: BTreeMap<String, BufWriter<fs::File>>,
+}
+
+impl FileSink {
+    /// Create `dir` if missing; probe files are created on their first
+    /// sample.
+    pub fn new(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(FileSink {
+            dir,
+            probes: BTreeMap::new(),
+        })
+    }
+}
+
+impl OutputSink for FileSink {
+    fn event(&mut self, event: &OutputEvent) -> io::Result<()> {
+        match *event {
+            OutputEvent::Probe {
+                name,
+                step,
+                time,
+                value,
+                ..
+            } => {
+                if !self.probes.contains_key(name) {
+                    let path = self.dir.join(format!("{name}.csv"));
+                    let mut file = BufWriter::new(fs::File::create(path)?);
+                    writeln!(file, "step,time,value")?;
+                    self.probes.insert(name.to_string(), file);
+                }
+                let file = self.probes.get_mut(name).unwrap();
+                writeln!(file, "{step},{time:e},{value:e}")
+            }
+            OutputEvent::Snapshot {
+                field,
+                step,
+                grid,
+                values,
+                ..
+            } => {
+                let path = self.dir.join(format!("{}_{step:06}.bin", field.name()));
+                let mut file = BufWriter::new(fs::File::create(path)?);
+                file.write_all(&snapshots::raw_header(grid, field, step))?;
+                file.write_all(bytemuck::cast_slice(values))?;
+                file.flush()
+            }
+            OutputEvent::Spectrum(s) => dft::write_spectra(&self.dir, std::slice::from_ref(s)),
+            _ => Ok(()),
+        }
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        for file in self.probes.values_mut() {
+            file.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// One MATLAB v7.3 file (see [`crate::mat`]) written by
+/// [`OutputSink::finish`], with per probe the columns `<probe>` (values)
+/// and `<probe>_t` (times, s), per snapshot component `<Ez>_<step:06>`
+/// (single, nx×ny×nz) and per DFT monitor `<monitor>_f` (frequencies, Hz)
+/// and `<monitor>_<Ez>` (complex single, sx×sy×sz×frequencies).
+pub struct MatSink {
+    path: PathBuf,
+    probes: BTreeMap<String, (Vec<f64>, Vec<f64>)>,
+    file: MatFile,
+}
+
+impl MatSink {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        MatSink {
+            path: path.into(),
+            probes: BTreeMap::new(),
+            file: MatFile::new(),
+        }
+    }
+}
+
+impl OutputSink for MatSink {
+    fn event(&mut self, event: &OutputEvent) -> io::Result<()> {
+        match *event {
+            OutputEvent::Probe {
+                name, time, value, ..
+            } => {
+                let (times, values) = self.probes.entry(name.to_string()).or_default();
+                times.push(time);
+                values.push(value);
+            }
+            OutputEvent::Snapshot {
+                field,
+                step,
+                grid,
+                values,
+                ..
+            } => {
+                let size = [grid.nx, grid.ny, grid.nz].map(|n| n as usize);
+                let name = format!("{}_{step:06}", field.name());
+                self.file.add_f32(&name, &size, values);
+            }
+            OutputEvent::Spectrum(s) => {
+                let f = &s.frequencies;
+                self.file
+                    .add_f64(&format!("{}_f", s.name), &[f.len(), 1], f);
+                let [sx, sy, sz] = s.size.map(|n| n as usize);
+                for (field, values) in &s.fields {
+                    let name = format!("{}_{}", s.name, field.name());
+                    self.file
+                        .add_complex32(&name, &[sx, sy, sz, f.len()], values);
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        for (name, (times, values)) in std::mem::take(&mut self.probes) {
+            self.file.add_f64(&name, &[values.len(), 1], &values);
+            self.file
+                .add_f64(&format!("{name}_t"), &[times.len(), 1], &times);
+        }
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        self.file.write(&self.path)
+    }
+}
@@ -0,0 +1,160 @@
+//! Colour-mapped slice images of one field component.
+//!
+//! Every `every` steps one axis-aligned plane of the chosen component is
+//! read back and written as a PNG named `<component>_<axis><index>_<step:06>.png`
+//! (e.g. `Ez_z32_000120.png`).  The image spans the two tangential axes of
+//! the plane normal in [`Axis::tangential`] order — (x, y) for a z plane,
+//! (y, z) for x and (z, x) for y — with the first horizontal and the second
+//! increasing upwards.  Values map onto a blue–white–red scale symmetric
+//! about zero, so the sign of the field and its nodes are visible at a
+//! glance.
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::error::FdtdError;
+use crate::gpu;
+use crate::grid::{Axis, Field, Grid};
+use crate::png;
+
+/// Slice image schedule and selection.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct SliceImages {
+    /// Steps between images (the first is written at step 0).
+    pub every: u32,
+    pub field: Field,
+    /// Plane normal and cell index along it.
+    pub normal: Axis,
+    pub index: u32,
+    /// Field value mapped to full colour; `None` scales each image to its
+    /// own largest magnitude.
+    pub scale: Option<f32>,
+    /// Output directory, created if missing.
+    pub dir: &'static str,
+}
+
+/// Diverging colour map: −1 → blue, 0 → white, +1 → red.
+pub fn diverging(t: f32) -> [u8; 3] {
+    let t = t.clamp(-1.0, 1.0);
+    let fade = (255.0 * (1.0 - t.abs())).round() as u8;
+    if t >= 0.0 {
+        [255, fade, fade]
+    } else {
+        [fade, fade, 255]
+    }
+}
+
+/// Plane `index` along `normal` of an x-fastest `volume`, first tangential
+/// axis fastest.
+pub(crate) fn plane(grid: &Grid, volume: &[f32], normal: Axis, index: u32) -> Vec<f32> {
+    let (u, v) = normal.tangential();
+    let mut values = Vec::with_capacity((grid.cells(u) * grid.cells(v)) as usize);
+    for b in 0..grid.cells(v) {
+        for a in 0..grid.cells(u) {
+            let mut ijk = [0; 3];
+            ijk[normal.lane()] = index;
+            ijk[u.lane()] = a;
+            ijk[v.lane()] = b;
+            let [i, j, k] = ijk.map(|x| x as usize);
+            let (nx, ny) = (grid.nx as usize, grid.ny as usize);
+            values.push(volume[i + nx * (j + ny * k)]);
+        }
+    }
+    values
+}
+
+/// RGB pixels of a plane `width` values wide, rows from the top (the
+/// largest second index) down; `scale` maps to full colour, or the largest
+/// magnitude when `None`.
+pub(crate) fn colour_map(values: &[f32], width: u32, scale: Option<f32>) -> Vec<u8> {
+    let scale = scale.unwrap_or_else(|| {
+        values
+            .iter()
+            .fold(0.0_f32, |m, x| m.max(x.abs()))
+            .max(f32::MIN_POSITIVE)
+    });
+    values
+        .chunks(width as usize)
+        .rev()
+        .flat_map(|row| row.iter().flat_map(|&x| diverging(x / scale)))
+        .collect()
+}
+
+/// Host staging and PNG output of [`SliceImages`].
+pub struct SliceWriter {
+    spec: SliceImages,
+    grid: Grid,
+    staging: wgpu::Buffer,
+}
+
+impl SliceWriter {
+    pub fn new(device: &wgpu::Device, grid: &Grid, spec: SliceImages) -> io::Result<Self> {
+        fs::create_dir_all(spec.dir)?;
+        let staging = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("slice_staging"),
+            size: grid.total() as u64 * 4,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Ok(SliceWriter {
+            spec,
+            grid: *grid,
+            staging,
+        })
+    }
+
+    /// Whether an image is due at step `n`.
+    pub fn due(&self, n: u32) -> bool {
+        n.is_multiple_of(self.spec.every)
+    }
+
+    /// Write the image of step `n` if one is due; `fields` are the six
+    /// field buffers in (Ex, Ey, Ez, Hx, Hy, Hz) order.
+    pub fn capture(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        n: u32,
+        fields: [&wgpu::Buffer; 6],
+    ) -> Result<(), FdtdError> {
+        if !self.due(n) {
+            return Ok(());
+        }
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("slice"),
+        });
+        let source = fields[self.spec.field.index()];
+        encoder.copy_buffer_to_buffer(source, 0, &self.staging, 0, self.staging.size());
+        queue.submit(Some(encoder.finish()));
+        let slice = self.staging.slice(..);
+        gpu::map_read(device, &[slice])?;
+        let values = {
+            let data = slice.get_mapped_range();
+            plane(
+                &self.grid,
+                bytemuck::cast_slice(&data),
+                self.spec.normal,
+                self.spec.index,
+            )
+        };
+        self.staging.unmap();
+
+        let (u, v) = self.spec.normal.tangential();
+        let (width, height) = (self.grid.cells(u), self.grid.cells(v));
+        let rgb = colour_map(&values, width, self.spec.scale);
+        Ok(png::write_rgb(&self.path(n), width, height, &rgb)?)
+    }
+
+    fn path(&self, n: u32) -> PathBuf {
+        let axis = ["x", "y", "z"][self.spec.normal.lane()];
+        let name = format!(
+            "{}_{axis}{}_{n:06}.png",
+            self.spec.field.name(),
+            self.spec.index
+        );
+        PathBuf::from(self.spec.dir).join(name)
+    }
+}
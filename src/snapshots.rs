@@ -0,0 +1,300 @@
+//! Periodic full-volume field snapshots.
+//!
+//! Every `every` steps the selected field buffers are copied to the host and
+//! written to `dir`, one file per component and step named
+//! `<component>_<step:06>.bin` (e.g. `Ez_000120.bin`), in this layout (all
+//! little-endian):
+//!
+//! | offset | type             | content                                |
+//! |--------|------------------|----------------------------------------|
+//! | 0      | `[u8; 8]`        | magic `FDTDSNP1`                       |
+//! | 8      | `u32` × 3        | nx, ny, nz                             |
+//! | 20     | `u32`            | step n                                 |
+//! | 24     | `f64`            | time of E after step n, (n + 1)·Δt (s) |
+//! | 32     | `[u8; 8]`        | component name, NUL-padded (`Ez`)      |
+//! | 40     | `f32` × nx·ny·nz | values, x fastest: i + nx·(j + ny·k)   |
+//!
+//! Values are the raw Yee samples of cell (i, j, k): E at edge centres, H
+//! at face centres and half a step behind E.
+//!
+//! With [`SnapshotFormat::Vtk`] each step instead goes to one ParaView file
+//! `fields_<step:06>.vti` (`.vtr` on graded grids, see [`crate::vtk`])
+//! holding every selected component as cell data, and the material maps are
+//! written once to `materials.vti`.  [`SnapshotFormat::Zarr`] appends
+//! every snapshot to one chunked Zarr v3 store at `dir` (see
+//! [`crate::zarr`]) for lazy reading with xarray/dask, and
+//! [`SnapshotFormat::NetCdf`] appends them as records of one CF NetCDF file
+//! `dir/fields.nc` (see [`crate::netcdf`]).
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::error::FdtdError;
+use crate::gpu;
+use crate::grid::{Field, Grid};
+use crate::materials::Coefficients;
+use crate::netcdf::NetCdfFile;
+use crate::sinks::{OutputEvent, OutputSink};
+use crate::vtk;
+use crate::zarr::ZarrStore;
+
+/// File format of [`Snapshots`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum SnapshotFormat {
+    /// One headered `.bin` file per component and step.
+    Raw,
+    /// One VTK XML file per step with all components.
+    Vtk,
+    /// One Zarr store, chunked by (x, y, z) cells within each step.
+    Zarr { chunk: [u32; 3] },
+    /// One NetCDF file with a record per step.
+    NetCdf,
+}
+
+/// Snapshot schedule and selection.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct Snapshots {
+    /// Steps between snapshots (the first is taken at step 0).
+    pub every: u32,
+    pub fields: &'static [Field],
+    /// Output directory, created if missing.
+    pub dir: &'static str,
+    pub format: SnapshotFormat,
+}
+
+impl Snapshots {
+    pub fn due(&self, n: u32) -> bool {
+        n.is_multiple_of(self.every)
+    }
+}
+
+/// Host staging and file output of [`Snapshots`].
+pub struct SnapshotWriter {
+    spec: Snapshots,
+    grid: Grid,
+    zarr: Option<ZarrStore>,
+    netcdf: Option<NetCdfFile>,
+    /// One MAP_READ buffer per selected component.
+    staging: Vec<wgpu::Buffer>,
+}
+
+impl SnapshotWriter {
+    /// `coeffs` supplies the material maps of the VTK format.
+    pub fn new(
+        device: &wgpu::Device,
+        grid: &Grid,
+        coeffs: &Coefficients,
+        spec: Snapshots,
+    ) -> io::Result<Self> {
+        fs::create_dir_all(spec.dir)?;
+        let (mut zarr, mut netcdf) = (None, None);
+        match spec.format {
+            SnapshotFormat::Raw => {}
+            SnapshotFormat::Vtk => {
+                let maps = vtk::material_arrays(grid, coeffs);
+                let arrays: Vec<_> = maps.iter().map(|(name, v)| (*name, &v[..])).collect();
+                vtk::write_cells(spec.dir.as_ref(), "materials", grid, None, &arrays)?;
+            }
+            SnapshotFormat::Zarr { chunk } => {
+                zarr = Some(ZarrStore::create(
+                    spec.dir.as_ref(),
+                    grid,
+                    spec.fields,
+                    chunk,
+                )?);
+            }
+            SnapshotFormat::NetCdf => {
+                let path = PathBuf::from(spec.dir).join("fields.nc");
+                netcdf = Some(NetCdfFile::create(&path, grid, spec.fields)?);
+            }
+        }
+        let staging = spec
+            .fields
+            .iter()
+            .map(|field| {
+                device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some(field.name()),
+                    size: grid.total() as u64 * 4,
+                    usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                })
+            })
+            .collect();
+        Ok(SnapshotWriter {
+            spec,
+            grid: *grid,
+            zarr,
+            netcdf,
+            staging,
+        })
+    }
+
+    /// Whether a snapshot is due at step `n`.
+    pub fn due(&self, n: u32) -> bool {
+        self.spec.due(n)
+    }
+
+    /// Write the snapshot of step `n` if one is due and hand its components
+    /// to `sinks`; `fields` are the six field buffers in (Ex, Ey, Ez, Hx,
+    /// Hy, Hz) order.
+    pub fn capture(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        n: u32,
+        fields: [&wgpu::Buffer; 6],
+        sinks: &mut [Box<dyn OutputSink>],
+    ) -> Result<(), FdtdError> {
+        if !self.due(n) {
+            return Ok(());
+        }
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("snapshot"),
+        });
+        for (field, staging) in self.spec.fields.iter().zip(&self.staging) {
+            encoder.copy_buffer_to_buffer(fields[field.index()], 0, staging, 0, staging.size());
+        }
+        queue.submit(Some(encoder.finish()));
+
+        let slices: Vec<_> = self.staging.iter().map(|s| s.slice(..)).collect();
+        gpu::map_read(device, &slices)?;
+
+        match self.spec.format {
+            SnapshotFormat::Raw => {
+                for (field, staging) in self.spec.fields.iter().zip(&self.staging) {
+                    let path = self.path(*field, n);
+                    let mut file = io::BufWriter::new(fs::File::create(path)?);
+                    file.write_all(&self.header(*field, n))?;
+                    file.write_all(&staging.slice(..).get_mapped_range())?;
+                    file.flush()?;
+                }
+            }
+            SnapshotFormat::Vtk => {
+                let values = self.mapped_values();
+                let arrays: Vec<_> = self
+                    .spec
+                    .fields
+                    .iter()
+                    .zip(&values)
+                    .map(|(field, v)| (field.name(), &v[..]))
+                    .collect();
+                let stem = format!("fields_{n:06}");
+                let time = self.time(n);
+                vtk::write_cells(
+                    self.spec.dir.as_ref(),
+                    &stem,
+                    &self.grid,
+                    Some(time),
+                    &arrays,
+                )?;
+            }
+            SnapshotFormat::Zarr { .. } => {
+                let values = self.mapped_values();
+                let volumes: Vec<&[f32]> = values.iter().map(|v| &v[..]).collect();
+                let time = self.time(n);
+                if let Some(store) = &mut self.zarr {
+                    store.append(time, &volumes)?;
+                }
+            }
+            SnapshotFormat::NetCdf => {
+                let values = self.mapped_values();
+                let volumes: Vec<&[f32]> = values.iter().map(|v| &v[..]).collect();
+                let time = self.time(n);
+                if let Some(file) = &mut self.netcdf {
+                    file.append(time, &volumes)?;
+                }
+            }
+        }
+        for (&field, staging) in self.spec.fields.iter().zip(&self.staging) {
+            if sinks.is_empty() {
+                break;
+            }
+            let mapped = staging.slice(..).get_mapped_range();
+            let event = OutputEvent::Snapshot {
+                field,
+                step: n,
+                time: self.time(n),
+                grid: &self.grid,
+                values: bytemuck::cast_slice(&mapped),
+            };
+            for sink in sinks.iter_mut() {
+                sink.event(&event)?;
+            }
+        }
+        for staging in &self.staging {
+            staging.unmap();
+        }
+        Ok(())
+    }
+
+    /// Copies of the mapped staging buffers.
+    fn mapped_values(&self) -> Vec<Vec<f32>> {
+        self.staging
+            .iter()
+            .map(|s| bytemuck::cast_slice(&s.slice(..).get_mapped_range()).to_vec())
+            .collect()
+    }
+
+    /// Time of E after step n, (n + 1)·Δt.
+    fn time(&self, n: u32) -> f64 {
+        (n + 1) as f64 * self.grid.dt
+    }
+
+    fn path(&self, field: Field, n: u32) -> PathBuf {
+        PathBuf::from(self.spec.dir).join(format!("{}_{n:06}.bin", field.name()))
+    }
+
+    fn header(&self, field: Field, n: u32) -> Vec<u8> {
+        raw_header(&self.grid, field, n)
+    }
+}
+
+/// The 40-byte header of a raw snapshot file.
+pub(crate) fn raw_header(grid: &Grid, field: Field, n: u32) -> Vec<u8> {
+    let mut header = b"FDTDSNP1".to_vec();
+    for v in [grid.nx, grid.ny, grid.nz, n] {
+        header.extend(v.to_le_bytes());
+    }
+    header.extend(((n + 1) as f64 * grid.dt).to_le_bytes());
+    let mut name = [0u8; 8];
+    name[..2].copy_from_slice(field.name().as_bytes());
+    header.extend(name);
+    header
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::Axis;
+
+    #[test]
+    fn raw_headers_follow_the_documented_layout() {
+        let grid = Grid::uniform([3, 4, 5], 1e-3, 2e-12);
+        let header = raw_header(&grid, Field::H(Axis::Y), 120);
+        assert_eq!(header.len(), 40);
+        assert_eq!(&header[..8], b"FDTDSNP1");
+        let u32_at = |o: usize| u32::from_le_bytes(header[o..o + 4].try_into().unwrap());
+        assert_eq!(
+            [u32_at(8), u32_at(12), u32_at(16), u32_at(20)],
+            [3, 4, 5, 120]
+        );
+        let time = f64::from_le_bytes(header[24..32].try_into().unwrap());
+        assert_eq!(time, 121.0 * 2e-12);
+        assert_eq!(&header[32..40], b"Hy\0\0\0\0\0\0");
+    }
+
+    #[test]
+    fn snapshots_are_due_from_step_zero() {
+        let spec = Snapshots {
+            every: 5,
+            fields: &[Field::E(Axis::Z)],
+            dir: "snapshots",
+            format: SnapshotFormat::Raw,
+        };
+        let due: Vec<u32> = (0..16).filter(|&n| spec.due(n)).collect();
+        assert_eq!(due, [0, 5, 10, 15]);
+    }
+}
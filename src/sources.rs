@@ -0,0 +1,209 @@
+//! Source excitation waveforms.
+//!
+//! On the GPU the point source is written into Ez by [`SourcePass`], which
+//! evaluates the waveform from a step counter kept on the device: the steps
+//! of a submission then need no upload from the host, only a change of the
+//! source does.
+
+use std::f64::consts::PI;
+
+use bytemuck::{Pod, Zeroable};
+use serde::Serialize;
+use wgpu::util::DeviceExt;
+
+use crate::gpu::{bg_entry, bgl_storage_entry, bgl_uniform_entry, compute_pipeline, Uploads};
+
+/// Time signature of a source.  Widths and delays are in time steps, as in
+/// the original hard-coded Gaussian pulse.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub enum Waveform {
+    /// exp(−((n − delay)/width)²)
+    Gaussian { width: f64, delay: f64 },
+    /// Gaussian envelope on a sine carrier at `freq` (Hz).
+    ModulatedGaussian { freq: f64, width: f64, delay: f64 },
+    /// Continuous sine at `freq` (Hz), switched on smoothly over `ramp` steps.
+    Sine { freq: f64, ramp: f64 },
+    /// Unit step after `delay`, ramped up linearly over `rise` steps (a hard
+    /// step for `rise` = 0).
+    Step { rise: f64, delay: f64 },
+    /// Ricker wavelet (1 − 2ζτ²)·exp(−ζτ²), ζ = π²·freq², τ = (n − delay)·Δt:
+    /// the GPR pulse, peaking at `freq` (Hz).
+    Ricker { freq: f64, delay: f64 },
+}
+
+impl Waveform {
+    /// Waveform value at (possibly fractional) time step `n`.
+    pub fn value(&self, n: f64, dt: f64) -> f64 {
+        match *self {
+            Waveform::Gaussian { width, delay } => {
+                let t = n - delay;
+                (-(t * t) / (width * width)).exp()
+            }
+            Waveform::ModulatedGaussian { freq, width, delay } => {
+                let t = n - delay;
+                (-(t * t) / (width * width)).exp() * (2.0 * PI * freq * t * dt).sin()
+            }
+            Waveform::Sine { freq, ramp } => {
+                let envelope = if n < ramp {
+                    0.5 * (1.0 - (PI * n / ramp).cos())
+                } else {
+                    1.0
+                };
+                envelope * (2.0 * PI * freq * n * dt).sin()
+            }
+            Waveform::Step { rise, delay } => {
+                let t = n - delay;
+                if t <= 0.0 {
+                    0.0
+                } else if t < rise {
+                    t / rise
+                } else {
+                    1.0
+                }
+            }
+            Waveform::Ricker { freq, delay } => {
+                let t = (n - delay) * dt;
+                let zt2 = (PI * freq * t).powi(2);
+                (1.0 - 2.0 * zt2) * (-zt2).exp()
+            }
+        }
+    }
+}
+
+/// Waveform and cell of the point source (must match WGSL `Source`).
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable, PartialEq)]
+struct SourceParams {
+    kind: u32,
+    cell: u32,
+    on: u32,
+    /// f·Δt·delay mod 1 in 2⁻³² turns.
+    delay_turns: u32,
+    amplitude: f32,
+    delay: f32,
+    width: f32,
+    /// Sine ramp or step rise.
+    ramp: f32,
+    cycles: f32,
+    /// f·Δt mod 1 in 2⁻⁶⁴ turns, high and low words.
+    cycles_hi: u32,
+    cycles_lo: u32,
+    _pad: u32,
+}
+
+impl SourceParams {
+    fn new(waveform: &Waveform, dt: f64, amplitude: f64, cell: Option<usize>) -> Self {
+        let (kind, freq, delay, width, ramp) = match *waveform {
+            Waveform::Gaussian { width, delay } => (0, 0.0, delay, width, 0.0),
+            Waveform::ModulatedGaussian { freq, width, delay } => (1, freq, delay, width, 0.0),
+            Waveform::Sine { freq, ramp } => (2, freq, 0.0, 0.0, ramp),
+            Waveform::Step { rise, delay } => (3, 0.0, delay, 0.0, rise),
+            Waveform::Ricker { freq, delay } => (4, freq, delay, 0.0, 0.0),
+        };
+        let cycles = freq * dt;
+        let turns = (cycles.fract() * 2.0_f64.powi(64)) as u64;
+        SourceParams {
+            kind,
+            cell: cell.unwrap_or(0) as u32,
+            on: cell.is_some() as u32,
+            delay_turns: ((cycles * delay).rem_euclid(1.0) * 2.0_f64.powi(32)) as u64 as u32,
+            amplitude: amplitude as f32,
+            delay: delay as f32,
+            width: width as f32,
+            ramp: ramp as f32,
+            cycles: cycles as f32,
+            cycles_hi: (turns >> 32) as u32,
+            cycles_lo: turns as u32,
+            _pad: 0,
+        }
+    }
+}
+
+/// The point source evaluated and written into Ez on the GPU each step.
+pub struct SourcePass {
+    pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    buf_params: wgpu::Buffer,
+    /// The step the next dispatch evaluates.
+    clock: wgpu::Buffer,
+    /// What `buf_params` holds, None before the first [`set`](Self::set).
+    params: Option<SourceParams>,
+}
+
+impl SourcePass {
+    /// Write into `ez`, counting the steps from 0; no cell until
+    /// [`set`](Self::set).
+    pub fn new(device: &wgpu::Device, ez: &wgpu::Buffer) -> Self {
+        let buf_params = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("source_params"),
+            size: std::mem::size_of::<SourceParams>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let clock = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("source_clock"),
+            contents: bytemuck::bytes_of(&0_u32),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        });
+        let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("source_bgl"),
+            entries: &[
+                bgl_uniform_entry(0),
+                bgl_storage_entry(1, false),
+                bgl_storage_entry(2, false),
+            ],
+        });
+        let pipeline =
+            compute_pipeline(device, "source", include_str!("shaders/source.wgsl"), &bgl);
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("bg_source"),
+            layout: &bgl,
+            entries: &[
+                bg_entry(0, buf_params.as_entire_binding()),
+                bg_entry(1, ez.as_entire_binding()),
+                bg_entry(2, clock.as_entire_binding()),
+            ],
+        });
+        SourcePass {
+            pipeline,
+            bind_group,
+            buf_params,
+            clock,
+            params: None,
+        }
+    }
+
+    /// Drive Ez at `cell` (none: nowhere) with `amplitude` × `waveform`
+    /// from this point of `encoder` on; uploads only a change.
+    pub fn set(
+        &mut self,
+        uploads: &mut Uploads,
+        encoder: &mut wgpu::CommandEncoder,
+        waveform: &Waveform,
+        dt: f64,
+        amplitude: f64,
+        cell: Option<usize>,
+    ) {
+        let params = SourceParams::new(waveform, dt, amplitude, cell);
+        if self.params != Some(params) {
+            uploads.write(encoder, &self.buf_params, 0, bytemuck::bytes_of(&params));
+            self.params = Some(params);
+        }
+    }
+
+    /// Count the steps from `n` on, for a run restored after `n` steps.
+    pub fn set_step(&self, queue: &wgpu::Queue, n: u32) {
+        queue.write_buffer(&self.clock, 0, bytemuck::bytes_of(&n));
+    }
+
+    /// Write the source of the next step and advance the clock.
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder) {
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("source"),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.dispatch_workgroups(1, 1, 1);
+    }
+}
@@ -0,0 +1,332 @@
+//! FFT spectra of probe time signals.
+//!
+//! At the end of a run each probe's whole trace is windowed, zero-padded to
+//! the next power of two of at least `padding` times its length and
+//! transformed, scaled by Δt so that the result approximates the continuous
+//! Fourier transform
+//!
+//!   X(f) = ∫ x(t)·e^{−j2πft} dt,
+//!
+//! with the phase referred to t = 0 (the samples sit at (n + 1)·Δt).
+//! Padding only interpolates between the bins of the unpadded record; the
+//! resolution stays 1/T of the record length T.
+//!
+//! A trace cut off while it still rings leaks into every bin through the
+//! sidelobes of the record's rectangular window.  A tapered window trades
+//! main-lobe width for sidelobe level (Hann −31 dB, Blackman −58 dB, Kaiser
+//! tunable through β) and is divided by its mean, so that the amplitude of
+//! a tone in the record is kept.  With `segments` > 1 the record is split
+//! into that many half-overlapping segments, each windowed and transformed,
+//! and the magnitudes are averaged in power (Welch's method): less variance
+//! for noisy signals at the cost of resolution, and no phase.
+//!
+//! `<probe>_spectrum.csv` holds the magnitude, in dB relative to its peak
+//! with `db`, up to `f_max` (the Nyquist frequency when `None`), and for a
+//! single segment also the real and imaginary parts and the phase in
+//! degrees.
+
+use std::f64::consts::PI;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use rustfft::num_complex::Complex64;
+use rustfft::FftPlanner;
+use serde::Serialize;
+
+/// Taper applied to each record or segment before the transform.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize)]
+pub enum Window {
+    Rectangular,
+    Hann,
+    Blackman,
+    /// Kaiser–Bessel window of shape `beta` (sidelobes near −63 dB at 8.6,
+    /// lower as it grows).
+    Kaiser {
+        beta: f64,
+    },
+}
+
+impl Window {
+    /// Weights of a window of `len` samples.
+    pub fn weights(self, len: usize) -> Vec<f64> {
+        let m = len.max(2) as f64 - 1.0;
+        (0..len)
+            .map(|n| {
+                let x = n as f64 / m;
+                match self {
+                    Window::Rectangular => 1.0,
+                    Window::Hann => 0.5 - 0.5 * (2.0 * PI * x).cos(),
+                    Window::Blackman => {
+                        0.42 - 0.5 * (2.0 * PI * x).cos() + 0.08 * (4.0 * PI * x).cos()
+                    }
+                    Window::Kaiser { beta } => {
+                        let r = 2.0 * x - 1.0;
+                        bessel_i0(beta * (1.0 - r * r).max(0.0).sqrt()) / bessel_i0(beta)
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+/// Modified Bessel function I₀ by its power series.
+fn bessel_i0(x: f64) -> f64 {
+    let (mut sum, mut term) = (1.0, 1.0);
+    let q = 0.25 * x * x;
+    for k in 1..500 {
+        term *= q / (k * k) as f64;
+        sum += term;
+        if term < 1e-16 * sum {
+            break;
+        }
+    }
+    sum
+}
+
+/// Zero padding, window, averaging and band of the probe spectra.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct ProbeSpectra {
+    /// Minimum ratio of the transform length to the record length (≥ 1).
+    pub padding: usize,
+    pub window: Window,
+    /// Number of half-overlapping segments averaged (1 = the whole record).
+    pub segments: usize,
+    /// Magnitude in dB relative to the peak instead of linear.
+    pub db: bool,
+    /// Highest frequency written (Hz).
+    pub f_max: Option<f64>,
+}
+
+/// Spectrum of one trace on a uniform frequency grid from 0 Hz.
+pub struct Spectrum {
+    pub df: f64,
+    /// Complex spectrum of a single segment, `None` when averaged.
+    pub values: Option<Vec<Complex64>>,
+    /// |X|, the root mean square over the segments.
+    pub magnitude: Vec<f64>,
+}
+
+impl Spectrum {
+    /// Frequency of the largest magnitude above 0 Hz.
+    pub fn peak(&self) -> Option<f64> {
+        (1..self.magnitude.len())
+            .max_by(|&a, &b| self.magnitude[a].total_cmp(&self.magnitude[b]))
+            .map(|k| k as f64 * self.df)
+    }
+}
+
+impl ProbeSpectra {
+    /// Spectrum of `trace`, sampled every `dt` from t = `dt` on.
+    pub fn analyze(&self, trace: &[f64], dt: f64) -> Spectrum {
+        let segments = self.segments.max(1);
+        // Segments of `seg` samples starting every seg/2
+        let seg = if segments > 1 {
+            (2 * trace.len() / (segments + 1)).max(2)
+        } else {
+            trace.len()
+        };
+        let len = (self.padding.max(1) * seg).next_power_of_two().max(2);
+        let df = 1.0 / (len as f64 * dt);
+        let nyquist = len / 2 + 1;
+        let bins = self
+            .f_max
+            .map_or(nyquist, |f| ((f / df) as usize + 1).min(nyquist));
+
+        let weights = self.window.weights(seg);
+        let gain = weights.iter().sum::<f64>() / seg.max(1) as f64;
+        let fft = FftPlanner::new().plan_fft_forward(len);
+        let mut power = vec![0.0; bins];
+        let mut values = Vec::new();
+        let mut count = 0;
+        for start in (0..segments).map(|s| s * seg / 2) {
+            let Some(samples) = trace.get(start..start + seg) else {
+                break;
+            };
+            let mut data: Vec<Complex64> = samples
+                .iter()
+                .zip(&weights)
+                .map(|(&x, &w)| Complex64::new(x * w / gain, 0.0))
+                .collect();
+            data.resize(len, Complex64::new(0.0, 0.0));
+            fft.process(&mut data);
+            let t0 = (start + 1) as f64 * dt;
+            values = (0..bins)
+                .map(|k| {
+                    let omega = 2.0 * PI * k as f64 * df;
+                    data[k] * dt * Complex64::from_polar(1.0, -omega * t0)
+                })
+                .collect();
+            for (p, x) in power.iter_mut().zip(&values) {
+                *p += x.norm_sqr();
+            }
+            count += 1;
+        }
+        Spectrum {
+            df,
+            magnitude: power
+                .iter()
+                .map(|p| (p / count.max(1) as f64).sqrt())
+                .collect(),
+            values: (count == 1).then_some(values),
+        }
+    }
+}
+
+/// Write `<name>_spectrum.csv` into `dir`.
+pub fn write_spectrum(
+    dir: &Path,
+    name: &str,
+    spec: &ProbeSpectra,
+    spectrum: &Spectrum,
+) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!("{name}_spectrum.csv"));
+    let mut file = BufWriter::new(fs::File::create(path)?);
+    let peak = spectrum.magnitude.iter().fold(0.0, |a: f64, &b| a.max(b));
+    let magnitude = |m: f64| {
+        if spec.db {
+            20.0 * (m / peak).log10()
+        } else {
+            m
+        }
+    };
+    let unit = if spec.db { "magnitude_db" } else { "magnitude" };
+    match &spectrum.values {
+        Some(_) => writeln!(file, "frequency,re,im,{unit},phase_deg")?,
+        None => writeln!(file, "frequency,{unit}")?,
+    }
+    for (k, &m) in spectrum.magnitude.iter().enumerate() {
+        let f = k as f64 * spectrum.df;
+        match &spectrum.values {
+            Some(values) => {
+                let x = values[k];
+                writeln!(
+                    file,
+                    "{f:e},{:e},{:e},{:e},{:.3}",
+                    x.re,
+                    x.im,
+                    magnitude(m),
+                    x.arg().to_degrees()
+                )?;
+            }
+            None => writeln!(file, "{f:e},{:e}", magnitude(m))?,
+        }
+    }
+    file.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spectra(window: Window, padding: usize, segments: usize) -> ProbeSpectra {
+        ProbeSpectra {
+            padding,
+            window,
+            segments,
+            db: false,
+            f_max: None,
+        }
+    }
+
+    /// Level in dB of the highest sidelobe of `window` relative to its main
+    /// lobe, from the transform of the window alone.
+    fn sidelobe(window: Window) -> f64 {
+        let magnitude = spectra(window, 64, 1).analyze(&[1.0; 64], 1.0).magnitude;
+        let first_null = (1..magnitude.len())
+            .find(|&k| magnitude[k + 1] > magnitude[k])
+            .unwrap();
+        let highest = magnitude[first_null..]
+            .iter()
+            .fold(0.0, |a: f64, &m| a.max(m));
+        20.0 * (highest / magnitude[0]).log10()
+    }
+
+    #[test]
+    fn windows_taper_to_their_ends() {
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-12;
+        assert!(Window::Rectangular.weights(5).iter().all(|&w| w == 1.0));
+        for window in [Window::Hann, Window::Blackman] {
+            let w = window.weights(9);
+            assert!(
+                close(w[0], 0.0) && close(w[8], 0.0) && close(w[4], 1.0),
+                "{window:?}"
+            );
+            assert!((0..9).all(|n| close(w[n], w[8 - n])));
+        }
+        let w = Window::Kaiser { beta: 8.6 }.weights(9);
+        assert!(close(w[0], 1.0 / bessel_i0(8.6)) && close(w[4], 1.0));
+        let flat = Window::Kaiser { beta: 0.0 }.weights(9);
+        assert!(flat.iter().all(|&w| close(w, 1.0)));
+    }
+
+    #[test]
+    fn bessel_i0_matches_tabulated_values() {
+        assert_eq!(bessel_i0(0.0), 1.0);
+        assert!((bessel_i0(1.0) - 1.266_065_877_752_008_4).abs() < 1e-14);
+        assert!((bessel_i0(5.0) / 27.239_871_823_604_44 - 1.0).abs() < 1e-14);
+    }
+
+    #[test]
+    fn sidelobes_fall_as_documented() {
+        assert!((sidelobe(Window::Rectangular) + 13.3).abs() < 0.5);
+        assert!((sidelobe(Window::Hann) + 31.5).abs() < 0.5);
+        assert!((sidelobe(Window::Blackman) + 58.1).abs() < 1.0);
+        assert!((sidelobe(Window::Kaiser { beta: 8.6 }) + 63.0).abs() < 1.0);
+        assert!(sidelobe(Window::Kaiser { beta: 12.0 }) < -85.0);
+    }
+
+    #[test]
+    fn a_tone_keeps_its_amplitude_in_every_window() {
+        // 16 periods over 256 samples: a tone on bin 16 of the record
+        let (dt, n, f0) = (1e-12, 256, 16.0 / (256.0 * 1e-12));
+        let trace: Vec<f64> = (1..=n)
+            .map(|m| 3.0 * (2.0 * PI * f0 * m as f64 * dt).cos())
+            .collect();
+        let windows = [
+            Window::Rectangular,
+            Window::Hann,
+            Window::Blackman,
+            Window::Kaiser { beta: 6.0 },
+        ];
+        for window in windows {
+            let spectrum = spectra(window, 4, 1).analyze(&trace, dt);
+            assert_eq!(spectrum.peak(), Some(f0), "{window:?}");
+            let peak = spectrum.magnitude[(f0 / spectrum.df).round() as usize];
+            // |X(f0)| = A·T/2 for a tone of amplitude A over the record T
+            let expected = 3.0 * n as f64 * dt / 2.0;
+            assert!((peak / expected - 1.0).abs() < 0.02, "{window:?}: {peak:e}");
+        }
+    }
+
+    #[test]
+    fn the_phase_refers_to_t_zero() {
+        // A Gaussian centred on t = 100 Δt: X(f) = |X(f)|·e^{−j2πf·100Δt}
+        let dt = 1e-12;
+        let trace: Vec<f64> = (1..=400)
+            .map(|n| (-((n as f64 - 100.0) / 10.0).powi(2)).exp())
+            .collect();
+        let spectrum = spectra(Window::Rectangular, 2, 1).analyze(&trace, dt);
+        let values = spectrum.values.unwrap();
+        for k in [1, 5, 20] {
+            let f = k as f64 * spectrum.df;
+            let expected = Complex64::from_polar(1.0, -2.0 * PI * f * 100.0 * dt);
+            let error = (values[k] / values[k].norm() - expected).norm();
+            assert!(error < 1e-9, "bin {k}: {error:e}");
+        }
+        // the area under the pulse, 10·√π samples of Δt
+        assert!((values[0].re / (10.0 * PI.sqrt() * dt) - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn welch_averages_drop_the_phase() {
+        let trace: Vec<f64> = (0..300).map(|n| (0.3 * n as f64).sin()).collect();
+        let spectrum = spectra(Window::Hann, 1, 3).analyze(&trace, 1.0);
+        assert!(spectrum.values.is_none());
+        // three segments of 150 samples, padded to 256
+        assert_eq!(spectrum.magnitude.len(), 129);
+        let peak = spectrum.peak().unwrap();
+        assert!((2.0 * PI * peak - 0.3).abs() < 2.0 * PI * spectrum.df);
+    }
+}
@@ -0,0 +1,129 @@
+//! Courant (CFL) stability limit of the explicit update.
+//!
+//! The Yee scheme is stable for
+//!
+//!   Δt ≤ 1 / (v_max · √(Σ 1/Δ²)),
+//!
+//! summed over the axes the mode actually discretizes, with Δ the smallest
+//! cell width on each axis and v_max the fastest phase velocity in the
+//! grid.  Ordinary media (ε_r, μ_r ≥ 1) never exceed c, but permittivities
+//! or permeabilities below the vacuum value (plasma ε∞ < 1, effective
+//! metamaterial parameters) raise v_max and shrink the limit.  Losses do
+//! not tighten it.  BOR orders m ≥ 1 use the axis-limited Courant number of
+//! [`Mode::courant_limit`].  The (2,4) stencil's larger spatial spectral
+//! radius (9/8 + 1/24 = 7/6) lowers the limit by 6/7; HIE drops its
+//! implicit axis from the sum and ADI has no limit at all.
+
+use serde::Serialize;
+
+use crate::grid::{Axis, Grid};
+use crate::materials::{Coefficients, EPS0, MU0};
+use crate::reduced::Mode;
+
+/// Time-stepping scheme.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize)]
+pub enum Scheme {
+    /// Second-order leapfrog on the Yee grid.
+    Yee,
+    /// Second order in time, fourth order in space on a uniform mesh
+    /// (less numerical dispersion on coarse grids).
+    Yee24,
+    /// Hybrid implicit–explicit update, implicit along `axis` (a thin
+    /// layer's normal); the limit ignores the widths along it.
+    Hie { axis: Axis },
+    /// Unconditionally stable ADI update with Δt = `multiple` × the Yee
+    /// limit.
+    Adi { multiple: f64 },
+}
+
+/// How a chosen Δt relates to the limit.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Check {
+    Stable,
+    /// Within 5 % of the limit: round-off in the f32 coefficients can tip
+    /// long runs into late-time growth.
+    Marginal,
+    Unstable,
+}
+
+/// Stability limit of one grid and scene.
+#[derive(Copy, Clone, Debug)]
+pub struct Stability {
+    /// Fastest phase velocity in the grid (m/s).
+    pub v_max: f64,
+    /// Largest stable Δt (s).
+    pub dt_max: f64,
+}
+
+impl Stability {
+    /// Limit for `grid` with the materials in `coeffs` (built with
+    /// `grid.dt`).
+    pub fn analyze(grid: &Grid, mode: Mode, scheme: Scheme, coeffs: &Coefficients) -> Self {
+        let v_max = max_velocity(grid, coeffs);
+        let min_width = |axis: Axis| {
+            (0..grid.cells(axis))
+                .map(|i| grid.width(axis, i))
+                .fold(f64::INFINITY, f64::min)
+        };
+        let axes: &[Axis] = match mode {
+            Mode::ThreeD => &[Axis::X, Axis::Y, Axis::Z],
+            Mode::TMz | Mode::TEz => &[Axis::X, Axis::Y],
+            Mode::OneD => &[Axis::X],
+            Mode::Bor { .. } => &[Axis::X, Axis::Z],
+        };
+        let cfl = |axes: &[Axis]| {
+            let sum: f64 = axes.iter().map(|&a| min_width(a).powi(-2)).sum();
+            1.0 / (v_max * sum.sqrt())
+        };
+        let yee = match mode {
+            Mode::Bor { .. } => {
+                let d = min_width(Axis::X).min(min_width(Axis::Z));
+                mode.courant_limit() * d / v_max
+            }
+            _ => cfl(axes),
+        };
+        let dt_max = match scheme {
+            Scheme::Yee => yee,
+            Scheme::Yee24 => yee * 6.0 / 7.0,
+            Scheme::Hie { axis } => {
+                let explicit: Vec<Axis> = axes.iter().copied().filter(|&a| a != axis).collect();
+                cfl(&explicit)
+            }
+            Scheme::Adi { .. } => f64::INFINITY,
+        };
+        Stability { v_max, dt_max }
+    }
+
+    /// Δt at `safety` (0 < safety ≤ 1) times the limit.
+    pub fn dt(&self, safety: f64) -> f64 {
+        safety * self.dt_max
+    }
+
+    pub fn check(&self, dt: f64) -> Check {
+        let ratio = dt / self.dt_max;
+        if ratio > 1.0 {
+            Check::Unstable
+        } else if ratio > 0.95 {
+            Check::Marginal
+        } else {
+            Check::Stable
+        }
+    }
+}
+
+/// Fastest phase velocity 1/√(εμ) over the grid, taking the smallest ε and
+/// μ found on any non-PEC edge.
+pub fn max_velocity(grid: &Grid, coeffs: &Coefficients) -> f64 {
+    let (mut eps_min, mut mu_min) = (EPS0, MU0);
+    for id in 0..coeffs.ca.len() {
+        for axis in [Axis::X, Axis::Y, Axis::Z] {
+            if let Some((eps, _)) = coeffs.e_material(id, axis, grid.dt) {
+                eps_min = eps_min.min(eps);
+            }
+            if let Some((mu, _)) = coeffs.h_material(id, axis, grid.dt) {
+                mu_min = mu_min.min(mu);
+            }
+        }
+    }
+    1.0 / (eps_min * mu_min).sqrt()
+}
@@ -0,0 +1,293 @@
+//! Local mesh refinement (subgridding).
+//!
+//! A box of parent cells is re-meshed by a child grid with `ratio` (2 or 3)
+//! times finer cells and time step, so the Courant number is unchanged.
+//! Per parent step the child runs `ratio` sub-steps with the regular update
+//! kernels; its tangential E on the outer faces is interpolated from the
+//! parent (trilinear in space, linear in time between E^n and E^{n+1}), and
+//! the parent E inside the box is replaced by averages of the child edges
+//! afterwards.  The scheme is the simple collocated-E interface; it is not
+//! provably stable, so keep refined regions away from strong resonances and
+//! watch long runs for late-time growth.
+//!
+//! Child grids are painted with the scene's objects only; sub-cell models,
+//! sources and probes stay on the parent grid.
+
+use bytemuck::{Pod, Zeroable};
+use serde::Serialize;
+use wgpu::util::DeviceExt;
+
+use crate::config::ConfigError;
+use crate::gpu::{
+    bg_entry, bgl_storage_entry, bgl_uniform_entry, compute_pipeline_entry, spacing_table,
+    GpuParams,
+};
+use crate::grid::{Axis, Grid};
+use crate::materials::Coefficients;
+
+/// Refined region covering parent cells `lo .. hi` on each axis.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct Subgrid {
+    pub lo: [u32; 3],
+    pub hi: [u32; 3],
+    /// Refinement factor in space and time (2 or 3).
+    pub ratio: u32,
+}
+
+impl Subgrid {
+    /// Check the ratio and that the box is non-empty and keeps a parent
+    /// cell to either side.
+    pub fn check(&self, parent: &Grid) -> Result<(), ConfigError> {
+        if !(2..=3).contains(&self.ratio) {
+            return Err(ConfigError(format!(
+                "subgrid ratio must be 2 or 3, not {}",
+                self.ratio
+            )));
+        }
+        let cells = [parent.nx, parent.ny, parent.nz];
+        let inside =
+            (0..3).all(|d| 1 <= self.lo[d] && self.lo[d] < self.hi[d] && self.hi[d] < cells[d]);
+        if !inside {
+            return Err(ConfigError(format!(
+                "subgrid {:?}..{:?} must be a non-empty box inside the grid",
+                self.lo, self.hi
+            )));
+        }
+        Ok(())
+    }
+
+    /// The child grid: `ratio · cells + 1` nodes per axis, so its first and
+    /// last nodes coincide with the box faces.
+    pub fn child_grid(&self, parent: &Grid) -> Grid {
+        let n = |d: usize| (self.hi[d] - self.lo[d]) * self.ratio + 1;
+        let r = self.ratio as f64;
+        Grid {
+            nx: n(0),
+            ny: n(1),
+            nz: n(2),
+            dx: parent.width(Axis::X, self.lo[0]) / r,
+            dy: parent.width(Axis::Y, self.lo[1]) / r,
+            dz: parent.width(Axis::Z, self.lo[2]) / r,
+            dt: parent.dt / r,
+            graded: [None; 3],
+            origin: parent.corner(self.lo[0], self.lo[1], self.lo[2]),
+        }
+    }
+}
+
+/// Coupling parameters (must match WGSL `SubParams`).
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct SubParams {
+    pnx: u32,
+    pny: u32,
+    pnz: u32,
+    ratio: u32,
+    cnx: u32,
+    cny: u32,
+    cnz: u32,
+    _pad: u32,
+    lo: [u32; 3],
+    alpha: f32,
+}
+
+/// GPU resources of one child grid and its coupling to the parent.
+pub struct SubgridPass {
+    update_h: wgpu::ComputePipeline,
+    update_e: wgpu::ComputePipeline,
+    bg_h: wgpu::BindGroup,
+    bg_e: wgpu::BindGroup,
+    snapshot: wgpu::ComputePipeline,
+    boundary: wgpu::ComputePipeline,
+    restrict: wgpu::ComputePipeline,
+    /// One coupling bind group per sub-step (alpha = s / ratio).
+    bg_couple: Vec<wgpu::BindGroup>,
+    child: [u32; 3],
+    cells: [u32; 3],
+}
+
+impl SubgridPass {
+    /// `update` is the parent's (H, E) update pipelines and their layout,
+    /// reused for the child with its own buffers.
+    pub fn new(
+        device: &wgpu::Device,
+        parent: &Grid,
+        subgrid: &Subgrid,
+        coeffs: &Coefficients,
+        update: (
+            &wgpu::ComputePipeline,
+            &wgpu::ComputePipeline,
+            &wgpu::BindGroupLayout,
+        ),
+        parent_e: [&wgpu::Buffer; 3],
+    ) -> Self {
+        let grid = subgrid.child_grid(parent);
+        let (update_h, update_e, update_bgl) = update;
+
+        let storage = |label: &str, contents: &[u8]| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents,
+                usage: wgpu::BufferUsages::STORAGE,
+            })
+        };
+        let uniform = |label: &str, contents: &[u8]| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents,
+                usage: wgpu::BufferUsages::UNIFORM,
+            })
+        };
+
+        let zeros = vec![0.0_f32; grid.total()];
+        let fields: Vec<wgpu::Buffer> =
+            ["sub_ex", "sub_ey", "sub_ez", "sub_hx", "sub_hy", "sub_hz"]
+                .iter()
+                .map(|label| storage(label, bytemuck::cast_slice(&zeros)))
+                .collect();
+        let [ca, cb, cp, cq] = [&coeffs.ca, &coeffs.cb, &coeffs.cp, &coeffs.cq]
+            .map(|c| storage("sub_coeffs", bytemuck::cast_slice(c)));
+        let params = uniform("sub_params", bytemuck::bytes_of(&GpuParams::new(&grid)));
+        let spacing = uniform("sub_spacing", bytemuck::cast_slice(&spacing_table(&grid)));
+
+        // Child update bind groups, laid out like the parent's.
+        let update_group = |label: &str,
+                            src: &[wgpu::Buffer],
+                            dst: &[wgpu::Buffer],
+                            c0: &wgpu::Buffer,
+                            c1: &wgpu::Buffer| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(label),
+                layout: update_bgl,
+                entries: &[
+                    bg_entry(0, params.as_entire_binding()),
+                    bg_entry(1, src[0].as_entire_binding()),
+                    bg_entry(2, src[1].as_entire_binding()),
+                    bg_entry(3, src[2].as_entire_binding()),
+                    bg_entry(4, dst[0].as_entire_binding()),
+                    bg_entry(5, dst[1].as_entire_binding()),
+                    bg_entry(6, dst[2].as_entire_binding()),
+                    bg_entry(7, c0.as_entire_binding()),
+                    bg_entry(8, c1.as_entire_binding()),
+                    bg_entry(9, spacing.as_entire_binding()),
+                ],
+            })
+        };
+        let bg_h = update_group("bg_sub_h", &fields[0..3], &fields[3..6], &cp, &cq);
+        let bg_e = update_group("bg_sub_e", &fields[3..6], &fields[0..3], &ca, &cb);
+
+        // Coupling kernels.
+        let cells = std::array::from_fn(|d| subgrid.hi[d] - subgrid.lo[d]);
+        let box_nodes: usize = cells.iter().map(|&c| c as usize + 1).product();
+        let prev = storage(
+            "sub_prev",
+            bytemuck::cast_slice(&vec![[0.0_f32; 4]; box_nodes]),
+        );
+
+        let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("subgrid_bgl"),
+            entries: &[
+                bgl_uniform_entry(0),
+                bgl_storage_entry(1, false),
+                bgl_storage_entry(2, false),
+                bgl_storage_entry(3, false),
+                bgl_storage_entry(4, false),
+                bgl_storage_entry(5, false),
+                bgl_storage_entry(6, false),
+                bgl_storage_entry(7, false),
+            ],
+        });
+        let source = include_str!("shaders/subgrid.wgsl");
+        let snapshot = compute_pipeline_entry(device, "subgrid_snapshot", source, &bgl, "snapshot");
+        let boundary = compute_pipeline_entry(device, "subgrid_boundary", source, &bgl, "boundary");
+        let restrict =
+            compute_pipeline_entry(device, "subgrid_restrict", source, &bgl, "restrict_parent");
+
+        let bg_couple = (1..=subgrid.ratio)
+            .map(|s| {
+                let p = SubParams {
+                    pnx: parent.nx,
+                    pny: parent.ny,
+                    pnz: parent.nz,
+                    ratio: subgrid.ratio,
+                    cnx: grid.nx,
+                    cny: grid.ny,
+                    cnz: grid.nz,
+                    _pad: 0,
+                    lo: subgrid.lo,
+                    alpha: s as f32 / subgrid.ratio as f32,
+                };
+                let buf = uniform("subgrid_params", bytemuck::bytes_of(&p));
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("bg_subgrid"),
+                    layout: &bgl,
+                    entries: &[
+                        bg_entry(0, buf.as_entire_binding()),
+                        bg_entry(1, parent_e[0].as_entire_binding()),
+                        bg_entry(2, parent_e[1].as_entire_binding()),
+                        bg_entry(3, parent_e[2].as_entire_binding()),
+                        bg_entry(4, prev.as_entire_binding()),
+                        bg_entry(5, fields[0].as_entire_binding()),
+                        bg_entry(6, fields[1].as_entire_binding()),
+                        bg_entry(7, fields[2].as_entire_binding()),
+                    ],
+                })
+            })
+            .collect();
+
+        SubgridPass {
+            update_h: update_h.clone(),
+            update_e: update_e.clone(),
+            bg_h,
+            bg_e,
+            snapshot,
+            boundary,
+            restrict,
+            bg_couple,
+            child: [grid.nx, grid.ny, grid.nz],
+            cells,
+        }
+    }
+
+    /// Save the parent E^n over the box; encode before the parent E-update.
+    pub fn encode_snapshot(&self, encoder: &mut wgpu::CommandEncoder) {
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("subgrid snapshot"),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.snapshot);
+        pass.set_bind_group(0, &self.bg_couple[0], &[]);
+        let [x, y, z] = self.cells.map(|c| (c + 1).div_ceil(4));
+        pass.dispatch_workgroups(x, y, z);
+    }
+
+    /// Advance the child by one parent step and restrict it back; encode
+    /// after the parent E-update.
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder) {
+        let [cx, cy, cz] = self.child.map(|n| n.div_ceil(4));
+        for bg in &self.bg_couple {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("subgrid step"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.update_h);
+            pass.set_bind_group(0, &self.bg_h, &[]);
+            pass.dispatch_workgroups(cx, cy, cz);
+            pass.set_pipeline(&self.update_e);
+            pass.set_bind_group(0, &self.bg_e, &[]);
+            pass.dispatch_workgroups(cx, cy, cz);
+            pass.set_pipeline(&self.boundary);
+            pass.set_bind_group(0, bg, &[]);
+            pass.dispatch_workgroups(cx, cy, cz);
+        }
+
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("subgrid restrict"),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.restrict);
+        pass.set_bind_group(0, &self.bg_couple[0], &[]);
+        let [x, y, z] = self.cells.map(|c| c.div_ceil(4));
+        pass.dispatch_workgroups(x, y, z);
+    }
+}
@@ -0,0 +1,467 @@
+//! Parameter sweeps of a scene file.
+//!
+//! The `[sweep]` table of a scene file gives its script parameters (see
+//! [`crate::script`]) several values: every combination of
+//! `[sweep.values]`, a list or an evenly spaced `{ from, to, count }`
+//! range per parameter, or the `[[sweep.cases]]` as listed.  [`run`] sets
+//! each case up from the file with its values, as `--set` would, runs it
+//! with its outputs under `case_000`, `case_001`, … and writes
+//! [`SUMMARY_FILE`]: a row per case with its values, whether it completed,
+//! where it ran, its steps, wall time and throughput, and its directory.
+//! A case that fails is recorded and the sweep goes on; an interrupt stops
+//! it after the cases under way, whose checkpoints are in their
+//! directories.
+//!
+//! The cases are independent, so the [`Worker`]s of a sweep run them at
+//! once: the configured device alone by default, or every GPU adapter and
+//! some CPU workers sharing the cores (see [`workers`]).  The cases are
+//! dealt round the workers' queues; a worker takes the next case from the
+//! front of its own and, once that is empty, steals from the back of
+//! another's, so a fast GPU is not left waiting on a slow one.  Each run
+//! shows its own progress bar, labelled with its case.
+//!
+//! With `warm_start = true` in `[sweep]` the cases run in order on one
+//! worker, each starting from the fields the last completed one ended with
+//! (`fields.bin` in its monitor directory, see [`crate::checkpoint`])
+//! instead of zero: a continuation study, slowly varying a geometry or a
+//! frequency, then reaches its steady state in fewer steps.  The materials
+//! and the rest of the state are set up afresh for each case.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+
+use tracing::warn;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::adapters;
+use crate::adapters::{AdapterChoice, Backend};
+use crate::checkpoint;
+use crate::config::{Config, ConfigError, OutputFormat};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::cpu;
+use crate::error::FdtdError;
+use crate::interrupt;
+use crate::manifest::RunInfo;
+use crate::progress::{self, format_duration};
+use crate::scene_file::{self, Parameter, SweepSpec};
+use crate::simulation::{self, console_sinks, report};
+use crate::sinks::OutputEvent;
+
+/// File name of the summary table, in the sweep's directory.
+pub const SUMMARY_FILE: &str = "sweep.csv";
+
+/// One run of a sweep.
+#[derive(Clone, Debug)]
+pub struct Case {
+    /// Position in the sweep, from 0.
+    pub index: usize,
+    /// Values of the swept parameters.
+    pub parameters: Vec<(String, Parameter)>,
+}
+
+impl Case {
+    /// Directory of the case's outputs, in the sweep's.
+    pub fn dir(&self) -> PathBuf {
+        PathBuf::from(format!("case_{:03}", self.index))
+    }
+
+    /// `radius = 0.001, count = 2`.
+    fn describe(&self) -> String {
+        let values = self
+            .parameters
+            .iter()
+            .map(|(name, v)| format!("{name} = {v}"));
+        values.collect::<Vec<_>>().join(", ")
+    }
+}
+
+/// What runs the cases of a sweep, one at a time.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Worker {
+    /// The adapter or backend of the configuration.
+    Configured,
+    /// The GPU adapter at `index` in [`adapters::list`] of the configured
+    /// graphics API, named `name`.
+    Adapter { index: usize, name: String },
+    /// The CPU backend on `threads` threads.
+    Cpu { threads: usize },
+}
+
+impl Worker {
+    /// Have `config` run on this worker.
+    fn apply(&self, config: &mut Config) {
+        match self {
+            Worker::Configured => {}
+            Worker::Adapter { index, .. } => {
+                config.adapter = Some(AdapterChoice::Index(*index));
+                if config.backend == Some(Backend::Cpu) {
+                    config.backend = None;
+                }
+            }
+            Worker::Cpu { threads } => {
+                config.backend = Some(Backend::Cpu);
+                config.cpu_threads = Some(*threads);
+            }
+        }
+    }
+}
+
+impl fmt::Display for Worker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Worker::Configured => f.write_str("configured device"),
+            Worker::Adapter { index, name } => write!(f, "#{index} {name}"),
+            Worker::Cpu { threads } => write!(f, "CPU ({threads} threads)"),
+        }
+    }
+}
+
+/// The workers of a sweep of `config`: each GPU adapter of its graphics
+/// API (all of them when None) when `gpus`, once per device, and `cpu` CPU
+/// workers sharing the cores (`config.cpu_threads`, or all of them); the
+/// configured device alone when neither.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn workers(config: &Config, gpus: bool, cpu: usize) -> Result<Vec<Worker>, ConfigError> {
+    let mut workers = Vec::new();
+    if gpus {
+        let listed = adapters::list(config.backend.filter(|&b| b != Backend::Cpu));
+        for (index, info) in listed.iter().enumerate() {
+            // the same GPU seen through Vulkan and GL, say, is run on once
+            let same = |other: &wgpu::AdapterInfo| {
+                (other.vendor, other.device, &other.name) == (info.vendor, info.device, &info.name)
+            };
+            if !listed[..index].iter().any(same) {
+                let name = info.name.clone();
+                workers.push(Worker::Adapter { index, name });
+            }
+        }
+        if workers.is_empty() {
+            return Err(ConfigError("no GPU adapter to run the cases on".into()));
+        }
+    }
+    let cores = config.cpu_threads.unwrap_or_else(cpu::threads);
+    if let Some(threads) = cores.checked_div(cpu) {
+        let threads = threads.max(1);
+        workers.extend((0..cpu).map(|_| Worker::Cpu { threads }));
+    }
+    if workers.is_empty() {
+        workers.push(Worker::Configured);
+    }
+    Ok(workers)
+}
+
+/// The cases no worker has taken yet, a queue per worker: a worker takes
+/// from the front of its own and, once it is empty, from the back of the
+/// next one that is not.
+struct Queues(Vec<Mutex<VecDeque<usize>>>);
+
+impl Queues {
+    /// The indices of `cases` cases dealt round `workers` queues.
+    fn new(cases: usize, workers: usize) -> Self {
+        let mut queues = vec![VecDeque::new(); workers];
+        for case in 0..cases {
+            queues[case % workers].push_back(case);
+        }
+        Queues(queues.into_iter().map(Mutex::new).collect())
+    }
+
+    /// The next case of `worker`, None once every queue is empty.
+    fn next(&self, worker: usize) -> Option<usize> {
+        if let Some(case) = self.0[worker].lock().unwrap().pop_front() {
+            return Some(case);
+        }
+        let n = self.0.len();
+        let mut others = (1..n).map(|k| (worker + k) % n);
+        others.find_map(|other| self.0[other].lock().unwrap().pop_back())
+    }
+}
+
+/// How one case ended.
+#[derive(Debug)]
+pub struct Outcome {
+    pub case: Case,
+    /// Where it ran.
+    pub worker: Worker,
+    /// The summaries of its runs (see [`simulation::run`]), or why it
+    /// failed.
+    pub result: Result<Vec<RunInfo>, FdtdError>,
+    pub wall: Duration,
+}
+
+impl Outcome {
+    fn status(&self) -> &'static str {
+        match &self.result {
+            Ok(_) => "completed",
+            Err(FdtdError::Interrupted { .. }) => "interrupted",
+            Err(_) => "failed",
+        }
+    }
+}
+
+/// The cases of the scene file at `path` in the order they run: the
+/// combinations of `[sweep.values]`, the last parameter by name varying
+/// fastest, or the `[[sweep.cases]]`.
+pub fn cases(path: &Path) -> Result<Vec<Case>, ConfigError> {
+    Ok(cases_of(spec(path)?))
+}
+
+/// The `[sweep]` table of the scene file at `path`.
+fn spec(path: &Path) -> Result<SweepSpec, ConfigError> {
+    let no_sweep = || ConfigError("no [sweep] table".into());
+    if path.extension().is_some_and(|e| e == "in") {
+        return Err(no_sweep());
+    }
+    scene_file::read(path)?.sweep.ok_or_else(no_sweep)
+}
+
+fn cases_of(sweep: SweepSpec) -> Vec<Case> {
+    let parameters: Vec<Vec<(String, Parameter)>> = if sweep.cases.is_empty() {
+        let mut cases = vec![Vec::new()];
+        for (name, values) in &sweep.values {
+            let values = values.values();
+            cases = cases
+                .iter()
+                .flat_map(|case| {
+                    values.iter().map(move |&value| {
+                        let mut case: Vec<(String, Parameter)> = case.clone();
+                        case.push((name.clone(), value));
+                        case
+                    })
+                })
+                .collect();
+        }
+        cases
+    } else {
+        let cases = sweep.cases.into_iter();
+        cases.map(|case| case.into_iter().collect()).collect()
+    };
+    let cases = parameters.into_iter().enumerate();
+    cases
+        .map(|(index, parameters)| Case { index, parameters })
+        .collect()
+}
+
+/// Run every case of the scene file at `path` on `workers`, under `dir`.
+/// `parameters` (from `--set`) apply to every case, below its own values,
+/// and `prepare` adjusts each configuration as loaded (with the command
+/// line's steps, adapter, verbosity, …) before its worker does.  Fails
+/// when the file or its sweep is invalid or the summary cannot be written;
+/// the failures of the cases are in their outcomes, in the order of the
+/// cases.
+pub fn run(
+    path: &Path,
+    dir: &Path,
+    parameters: &[(String, Parameter)],
+    workers: &[Worker],
+    prepare: impl Fn(&mut Config) + Sync,
+) -> Result<Vec<Outcome>, FdtdError> {
+    // The file as a whole is checked before the first case runs
+    let mut base = scene_file::load(path, parameters)?;
+    prepare(&mut base);
+    let sweep = spec(path)?;
+    let warm = sweep.warm_start;
+    if warm && workers.len() > 1 {
+        let message = "a warm-started sweep runs its cases one after the other, on one worker";
+        return Err(ConfigError(message.into()).into());
+    }
+    let cases = cases_of(sweep);
+    let (mut sinks, format) = (console_sinks(&base), base.output_format);
+    let queues = Queues::new(cases.len(), workers.len());
+
+    let mut outcomes: Vec<Outcome> = Vec::with_capacity(cases.len());
+    thread::scope(|scope| -> Result<(), FdtdError> {
+        let (tx, rx) = mpsc::channel();
+        for (w, worker) in workers.iter().enumerate() {
+            let (tx, queues, cases, prepare) = (tx.clone(), &queues, &cases, &prepare);
+            scope.spawn(move || {
+                // the fields the last case completed here left
+                let mut fields: Option<PathBuf> = None;
+                while !interrupt::requested() {
+                    let Some(index) = queues.next(w) else { break };
+                    let case = &cases[index];
+                    if format == OutputFormat::Text {
+                        progress::suspend(|| {
+                            println!(
+                                "\nCase {} of {} ({}){}: {}",
+                                index + 1,
+                                cases.len(),
+                                case.dir().display(),
+                                on(worker),
+                                case.describe()
+                            )
+                        });
+                    }
+                    let start = Instant::now();
+                    let label = case.dir().display().to_string();
+                    let result = progress::labelled(label, || {
+                        let fields = warm.then_some(&mut fields);
+                        run_case(path, dir, parameters, prepare, worker, case, fields)
+                    });
+                    let outcome = Outcome {
+                        case: case.clone(),
+                        worker: worker.clone(),
+                        result,
+                        wall: start.elapsed(),
+                    };
+                    if tx.send(outcome).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(tx);
+        for outcome in rx {
+            let case = &outcome.case;
+            if let Err(e) = &outcome.result {
+                warn!(case = case.index, "{e}");
+            }
+            let mut values: Vec<(&str, f64)> = case
+                .parameters
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.value()))
+                .collect();
+            values.push(("completed", outcome.result.is_ok() as u8 as f64));
+            values.push(("wall_seconds", outcome.wall.as_secs_f64()));
+            let name = case.dir().display().to_string();
+            report(
+                &mut sinks,
+                format,
+                OutputEvent::Result {
+                    analysis: "sweep",
+                    name: &name,
+                    values: &values,
+                },
+                format_args!(
+                    "Case {} {}{} in {}",
+                    case.index + 1,
+                    outcome.status(),
+                    on(&outcome.worker),
+                    format_duration(outcome.wall)
+                ),
+            )?;
+            outcomes.push(outcome);
+        }
+        Ok(())
+    })?;
+    outcomes.sort_by_key(|o| o.case.index);
+
+    let path = dir.join(SUMMARY_FILE);
+    write_summary(&path, &outcomes)?;
+    let completed = outcomes.iter().filter(|o| o.result.is_ok()).count();
+    report(
+        &mut sinks,
+        format,
+        OutputEvent::Summary { path: &path },
+        format_args!(
+            "\nSweep summary: {} ({completed} of {} cases completed)",
+            path.display(),
+            cases.len()
+        ),
+    )?;
+    for sink in &mut sinks {
+        sink.finish()?;
+    }
+    Ok(outcomes)
+}
+
+/// ` on #1 NVIDIA …`, nothing for the configured device.
+fn on(worker: &Worker) -> String {
+    match worker {
+        Worker::Configured => String::new(),
+        worker => format!(" on {worker}"),
+    }
+}
+
+/// Set `case` up with `parameters` below its values and run it on `worker`
+/// under `dir`.  With `fields`, a warm start, it starts from those fields,
+/// if any, and leaves its own there when it completes.
+fn run_case(
+    path: &Path,
+    dir: &Path,
+    parameters: &[(String, Parameter)],
+    prepare: &impl Fn(&mut Config),
+    worker: &Worker,
+    case: &Case,
+    fields: Option<&mut Option<PathBuf>>,
+) -> Result<Vec<RunInfo>, FdtdError> {
+    let mut values = parameters.to_vec();
+    values.extend(case.parameters.iter().cloned());
+    let mut config = scene_file::load(path, &values)?;
+    prepare(&mut config);
+    worker.apply(&mut config);
+    config.output_under(&dir.join(case.dir()));
+    if let Some(fields) = fields.as_deref() {
+        config.initial_fields = fields.clone();
+        config.final_fields = true;
+    }
+    let runs = simulation::run(&config)?;
+    if let Some(fields) = fields {
+        *fields = Some(Path::new(config.monitor_dir).join(checkpoint::FIELDS_FILE));
+    }
+    Ok(runs)
+}
+
+/// Write the summary table of `outcomes` to `path` as CSV: the case, the
+/// value of each swept parameter, the status, the worker, the steps, wall
+/// time and throughput of the structure run, the directory and the error,
+/// if any.
+pub fn write_summary(path: &Path, outcomes: &[Outcome]) -> io::Result<()> {
+    let mut names: Vec<&str> = Vec::new();
+    for (name, _) in outcomes.iter().flat_map(|o| &o.case.parameters) {
+        if !names.contains(&name.as_str()) {
+            names.push(name);
+        }
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = BufWriter::new(fs::File::create(path)?);
+    write!(file, "case")?;
+    for name in &names {
+        write!(file, ",{name}")?;
+    }
+    writeln!(file, ",status,worker,steps,wall_s,mcells_per_s,dir,error")?;
+    for outcome in outcomes {
+        let case = &outcome.case;
+        write!(file, "{}", case.index)?;
+        for name in &names {
+            match case.parameters.iter().find(|(n, _)| n == name) {
+                Some((_, value)) => write!(file, ",{value}")?,
+                None => write!(file, ",")?,
+            }
+        }
+        let wall = outcome.wall.as_secs_f64();
+        write!(file, ",{},{}", outcome.status(), quoted(&outcome.worker))?;
+        match &outcome.result {
+            Ok(runs) => {
+                let info = runs.last().expect("a run of the structure");
+                let mcells = info.throughput() / 1e6;
+                write!(file, ",{},{wall:.3},{mcells:.3}", info.steps)?;
+            }
+            Err(_) => write!(file, ",,{wall:.3},")?,
+        }
+        write!(file, ",{}", case.dir().display())?;
+        match &outcome.result {
+            Ok(_) => writeln!(file, ",")?,
+            Err(e) => writeln!(file, ",{}", quoted(e))?,
+        }
+    }
+    file.flush()
+}
+
+/// `text` as a quoted CSV field, on one line.
+fn quoted(text: impl fmt::Display) -> String {
+    let text = text.to_string().replace('"', "\"\"").replace('\n', "; ");
+    format!("\"{text}\"")
+}
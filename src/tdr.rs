@@ -0,0 +1,142 @@
+//! Time-domain reflectometry at a feed port.
+//!
+//! A matched source (internal resistance Z₀ of the port) launches a ramped
+//! voltage step onto the interconnect at one of the [`crate::ports`] feed
+//! edges, replacing the point source.  From the port's V and I (I moved
+//! onto the V samples by averaging its two neighbours) the step splits into
+//! the incident and reflected voltage waves
+//!
+//!   a = (V + Z₀·I)/2,   b = (V − Z₀·I)/2,
+//!
+//! and with the settled incident height a_∞ (the last sample of a) the
+//! reflection and impedance profile follow as
+//!
+//!   ρ(t) = b(t)/a_∞,   Z(t) = Z₀·(1 + ρ)/(1 − ρ).
+//!
+//! Time is counted from the middle of the ramp and mapped to the distance
+//! d = v·t/2 along the line, v = c₀/√ε_eff.  The ramp sets the resolution:
+//! discontinuities closer than about v·t_rise/2 blur together, and the
+//! ramp should span several steps to keep the grid dispersion out of the
+//! profile.  Beyond the first discontinuity ρ includes multiple
+//! reflections, as a measured TDR trace does.  `<name>_tdr.csv` holds the
+//! whole profile.
+
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::lumped::{LumpedElement, LumpedKind};
+use crate::materials::{EPS0, MU0};
+use crate::ports::FeedPort;
+use crate::sources::Waveform;
+
+/// TDR at feed port `port` (an index into the run's ports).
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct Tdr {
+    pub name: &'static str,
+    pub port: usize,
+    /// Height (V) of the source step and its linear rise (time steps).
+    pub amplitude: f64,
+    pub rise: f64,
+    /// Effective relative permittivity of the line, for the distance axis.
+    pub eps_eff: f64,
+}
+
+/// One sample of the reflection profile.
+#[derive(Copy, Clone, Debug)]
+pub struct TdrSample {
+    /// Time after the middle of the ramp (s) and the distance it maps to (m).
+    pub time: f64,
+    pub distance: f64,
+    pub incident: f64,
+    pub reflected: f64,
+    pub rho: f64,
+    /// Impedance (Ω); infinite at an open circuit.
+    pub z: f64,
+}
+
+impl Tdr {
+    /// The matched step source on the edge of `port`.
+    pub fn source(&self, port: &FeedPort) -> LumpedElement {
+        LumpedElement {
+            axis: port.axis,
+            cell: port.cell,
+            kind: LumpedKind::VoltageSource {
+                r: port.z0,
+                v: self.amplitude,
+                waveform: Waveform::Step {
+                    rise: self.rise,
+                    delay: 0.0,
+                },
+            },
+        }
+    }
+
+    /// Reflection profile from the port's (V, I) trace, V at (n + 1)·Δt and
+    /// I half a step earlier.
+    pub fn profile(&self, port: &FeedPort, dt: f64, trace: &[[f64; 2]]) -> Vec<TdrSample> {
+        let z0 = port.z0;
+        let waves: Vec<(f64, f64)> = (0..trace.len())
+            .map(|n| {
+                let v = trace[n][0];
+                let i = 0.5 * (trace[n][1] + trace.get(n + 1).unwrap_or(&trace[n])[1]);
+                (0.5 * (v + z0 * i), 0.5 * (v - z0 * i))
+            })
+            .collect();
+        let settled = waves.last().map_or(0.0, |w| w.0);
+        let velocity = 1.0 / (MU0 * EPS0 * self.eps_eff).sqrt();
+        let edge = 0.5 * self.rise * dt;
+        waves
+            .iter()
+            .enumerate()
+            .map(|(n, &(incident, reflected))| {
+                let time = (n + 1) as f64 * dt - edge;
+                let rho = if settled != 0.0 {
+                    reflected / settled
+                } else {
+                    0.0
+                };
+                TdrSample {
+                    time,
+                    distance: 0.5 * velocity * time,
+                    incident,
+                    reflected,
+                    rho,
+                    z: if rho < 1.0 {
+                        z0 * (1.0 + rho) / (1.0 - rho)
+                    } else {
+                        f64::INFINITY
+                    },
+                }
+            })
+            .collect()
+    }
+}
+
+/// Write `<name>_tdr.csv` and return the lowest and highest impedance seen
+/// from the middle of the ramp on, as (Z, distance).
+pub fn write_results(
+    dir: &Path,
+    tdr: &Tdr,
+    profile: &[TdrSample],
+) -> io::Result<[Option<(f64, f64)>; 2]> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!("{}_tdr.csv", tdr.name));
+    let mut file = BufWriter::new(fs::File::create(path)?);
+    writeln!(file, "time,distance,incident,reflected,rho,z")?;
+    for s in profile {
+        writeln!(
+            file,
+            "{:e},{:e},{:e},{:e},{:e},{:e}",
+            s.time, s.distance, s.incident, s.reflected, s.rho, s.z
+        )?;
+    }
+    file.flush()?;
+
+    let after = || profile.iter().filter(|s| s.time >= 0.0 && s.z.is_finite());
+    let low = after().min_by(|a, b| a.z.total_cmp(&b.z));
+    let high = after().max_by(|a, b| a.z.total_cmp(&b.z));
+    Ok([low, high].map(|s| s.map(|s| (s.z, s.distance))))
+}
@@ -0,0 +1,329 @@
+//! Bioheat and heat-diffusion stage driven by the EM dissipation.
+//!
+//! After the EM run, the dissipated power density Q = σ|E|²/2 of one SAR
+//! box at one of its frequencies ([`crate::sar::Sar::dissipation`], times
+//! `scale`) heats the phantom cells of that box, and the temperature rise θ
+//! over the basal state follows the Pennes bioheat equation
+//!
+//!   ρc ∂θ/∂t = ∇·(k∇θ) + Q − ρ_b·c_b·ω·θ
+//!
+//! or, with [`ThermalModel::Diffusion`], plain heat conduction without the
+//! perfusion sink.  The EM field settles within nanoseconds while tissue
+//! heats over seconds to minutes, so the stage runs after the EM solve on
+//! its steady-state dissipation, with its own explicit time step
+//! (`bioheat.wgsl`) at 0.9 of the stability limit.  Tissue labels take
+//! their properties from the database ([`crate::tissues::Tissue::thermal`]),
+//! labels of plain materials from `materials`; other cells (air, labels
+//! without properties) are insulating and the box faces adiabatic, which
+//! errs towards higher temperatures.  As the equation is linear in θ the
+//! rise adds to any basal temperature field.
+//!
+//! `<name>_thermal.csv` lists the peak and mean rise over the heated cells
+//! every `record_every` seconds and `<name>_thermal.vti` (or `.vtr`) holds
+//! the final rise on the whole grid.
+
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use bytemuck::{Pod, Zeroable};
+use serde::Serialize;
+use wgpu::util::DeviceExt;
+
+use crate::error::FdtdError;
+use crate::gpu::{self, bg_entry, bgl_storage_entry, bgl_uniform_entry, compute_pipeline};
+use crate::grid::Grid;
+use crate::phantom::Phantom;
+use crate::sar::Sar;
+use crate::tissues::ThermalProperties;
+use crate::vtk;
+
+/// Equation of the thermal stage.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum ThermalModel {
+    /// Conduction with the perfusion heat sink.
+    Pennes,
+    /// Conduction only.
+    Diffusion,
+}
+
+/// Heating of the phantom cells of a SAR box.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct Thermal {
+    pub name: &'static str,
+    /// Index of the SAR box into the configured list, and of the
+    /// frequency into its frequencies.
+    pub sar: usize,
+    pub frequency: usize,
+    /// Factor on the dissipated power, e.g. to rescale the excitation.
+    pub scale: f64,
+    pub model: ThermalModel,
+    /// Properties of phantom labels mapped to plain materials.
+    pub materials: &'static [(u32, ThermalProperties)],
+    /// Heating time and interval between records (s).
+    pub duration: f64,
+    pub record_every: f64,
+}
+
+/// Box size and spacing (must match WGSL `HeatParams`).
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct HeatParams {
+    size: [u32; 4],
+    inv_h2: [f32; 4],
+}
+
+/// Peak and mean temperature rise (K) at one time (s).
+#[derive(Copy, Clone, Debug)]
+pub struct Heating {
+    pub time: f64,
+    pub peak: f64,
+    pub mean: f64,
+}
+
+/// The heated box: thermal properties of its cells (`None` where a cell
+/// does not conduct heat) and their dissipated power density (W/m³).
+pub struct HeatedBox {
+    pub lo: [u32; 3],
+    pub size: [u32; 3],
+    pub properties: Vec<Option<ThermalProperties>>,
+    pub dissipation: Vec<f64>,
+}
+
+impl Thermal {
+    /// The box of `sar` heated by `dissipation` (its cells, x fastest).
+    pub fn heated_box(
+        &self,
+        grid: &Grid,
+        sar: &Sar,
+        phantoms: &[Phantom],
+        dissipation: Vec<f64>,
+    ) -> io::Result<HeatedBox> {
+        let size = [0, 1, 2].map(|a| sar.hi[a] - sar.lo[a]);
+        let cells = size.iter().product::<u32>() as usize;
+        let mut properties = vec![None; cells];
+        for phantom in phantoms {
+            let labels = phantom.labels(grid, sar.lo, sar.hi)?;
+            for (id, label) in labels.into_iter().enumerate() {
+                let Some(label) = label else {
+                    continue;
+                };
+                if let Some(&(_, tissue)) = phantom.tissues.iter().find(|(l, _)| *l == label) {
+                    properties[id] = Some(tissue.thermal());
+                } else if let Some(&(_, p)) = self.materials.iter().find(|(l, _)| *l == label) {
+                    properties[id] = Some(p);
+                }
+            }
+        }
+        Ok(HeatedBox {
+            lo: sar.lo,
+            size,
+            properties,
+            dissipation,
+        })
+    }
+}
+
+/// Run the thermal stage on `heated` and write its outputs.  Returns the
+/// records.
+pub fn run(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    dir: &Path,
+    grid: &Grid,
+    spec: &Thermal,
+    heated: &HeatedBox,
+) -> Result<Vec<Heating>, FdtdError> {
+    let (properties, size) = (&heated.properties, heated.size);
+    let inv_h2 = [grid.dx, grid.dy, grid.dz].map(|h| 1.0 / (h * h));
+    let id = |c: [u32; 3]| (c[0] + size[0] * (c[1] + size[1] * c[2])) as usize;
+
+    // Largest stable step: Δt·(Σ k_face/h² + B)/ρc ≤ 1 in every cell
+    let conductivity = |c: [u32; 3]| properties[id(c)].map_or(0.0, |p| p.conductivity);
+    let perfusion = |p: &ThermalProperties| match spec.model {
+        ThermalModel::Pennes => p.perfusion,
+        ThermalModel::Diffusion => 0.0,
+    };
+    let mut rate: f64 = 0.0;
+    for k in 0..size[2] {
+        for j in 0..size[1] {
+            for i in 0..size[0] {
+                let c = [i, j, k];
+                let Some(p) = properties[id(c)] else {
+                    continue;
+                };
+                let mut sum = perfusion(&p);
+                for a in 0..3 {
+                    for step in [-1_i64, 1] {
+                        let n = c[a] as i64 + step;
+                        if n < 0 || n >= size[a] as i64 {
+                            continue;
+                        }
+                        let mut neighbour = c;
+                        neighbour[a] = n as u32;
+                        let kn = conductivity(neighbour);
+                        if kn + p.conductivity > 0.0 {
+                            let face = 2.0 * p.conductivity * kn / (p.conductivity + kn);
+                            sum += face * inv_h2[a];
+                        }
+                    }
+                }
+                rate = rate.max(sum / (p.density * p.heat_capacity));
+            }
+        }
+    }
+    let steps = if rate > 0.0 {
+        (spec.duration * rate / 0.9).ceil().max(1.0) as u64
+    } else {
+        1
+    };
+    let dt = spec.duration / steps as f64;
+    let every = ((spec.record_every / dt).round() as u64).max(1);
+
+    let props: Vec<[f32; 4]> = properties
+        .iter()
+        .zip(&heated.dissipation)
+        .map(|(p, q)| match p {
+            Some(p) => [
+                (dt / (p.density * p.heat_capacity)) as f32,
+                p.conductivity as f32,
+                perfusion(p) as f32,
+                (spec.scale * q) as f32,
+            ],
+            None => [0.0; 4],
+        })
+        .collect();
+    let params = HeatParams {
+        size: [size[0], size[1], size[2], 0],
+        inv_h2: [inv_h2[0] as f32, inv_h2[1] as f32, inv_h2[2] as f32, 0.0],
+    };
+    let buf_params = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("heat_params"),
+        contents: bytemuck::bytes_of(&params),
+        usage: wgpu::BufferUsages::UNIFORM,
+    });
+    let buf_props = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("heat_props"),
+        contents: bytemuck::cast_slice(&props),
+        usage: wgpu::BufferUsages::STORAGE,
+    });
+    let zeros = vec![0.0_f32; props.len()];
+    let temperature = ["heat_t0", "heat_t1"].map(|label| {
+        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(label),
+            contents: bytemuck::cast_slice(&zeros),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+        })
+    });
+
+    let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("heat_bgl"),
+        entries: &[
+            bgl_uniform_entry(0),
+            bgl_storage_entry(1, true),
+            bgl_storage_entry(2, false),
+            bgl_storage_entry(3, true),
+        ],
+    });
+    // Bind group s reads buffer s and writes the other
+    let bind_groups = [0, 1].map(|s| {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("bg_heat"),
+            layout: &bgl,
+            entries: &[
+                bg_entry(0, buf_params.as_entire_binding()),
+                bg_entry(1, temperature[s].as_entire_binding()),
+                bg_entry(2, temperature[1 - s].as_entire_binding()),
+                bg_entry(3, buf_props.as_entire_binding()),
+            ],
+        })
+    });
+    let pipeline = compute_pipeline(
+        device,
+        "bioheat",
+        include_str!("shaders/bioheat.wgsl"),
+        &bgl,
+    );
+    let groups = size.map(|n| n.div_ceil(4));
+
+    let cells: Vec<usize> = (0..props.len()).filter(|&c| props[c][0] > 0.0).collect();
+    let mut records = Vec::new();
+    let mut rise = zeros;
+    let mut n = 0;
+    while n < steps {
+        let batch = every.min(steps - n);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("heat_steps"),
+        });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("bioheat"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&pipeline);
+            for s in n..n + batch {
+                pass.set_bind_group(0, &bind_groups[(s % 2) as usize], &[]);
+                pass.dispatch_workgroups(groups[0], groups[1], groups[2]);
+            }
+        }
+        queue.submit(Some(encoder.finish()));
+        n += batch;
+        rise = read_back(device, queue, &temperature[(n % 2) as usize], rise.len())?;
+
+        let peak = cells.iter().map(|&c| rise[c] as f64).fold(0.0, f64::max);
+        let mean = cells.iter().map(|&c| rise[c] as f64).sum::<f64>() / cells.len().max(1) as f64;
+        records.push(Heating {
+            time: n as f64 * dt,
+            peak,
+            mean,
+        });
+    }
+
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!("{}_thermal.csv", spec.name));
+    let mut file = BufWriter::new(fs::File::create(path)?);
+    writeln!(file, "time,peak_rise,mean_rise")?;
+    for r in &records {
+        writeln!(file, "{:e},{:e},{:e}", r.time, r.peak, r.mean)?;
+    }
+    file.flush()?;
+
+    let mut volume = vec![0.0_f32; grid.total()];
+    for k in 0..size[2] {
+        for j in 0..size[1] {
+            for i in 0..size[0] {
+                let g = grid.idx(heated.lo[0] + i, heated.lo[1] + j, heated.lo[2] + k);
+                volume[g] = rise[id([i, j, k])];
+            }
+        }
+    }
+    let stem = format!("{}_thermal", spec.name);
+    let time = Some(spec.duration);
+    vtk::write_cells(dir, &stem, grid, time, &[("temperature_rise", &volume)])?;
+    Ok(records)
+}
+
+/// Copy `count` f32 values of `buffer` to the host.
+fn read_back(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    buffer: &wgpu::Buffer,
+    count: usize,
+) -> Result<Vec<f32>, FdtdError> {
+    let size = (count * 4) as u64;
+    let readback = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("heat_readback"),
+        size,
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let mut encoder = device.create_command_encoder(&Default::default());
+    encoder.copy_buffer_to_buffer(buffer, 0, &readback, 0, size);
+    queue.submit(Some(encoder.finish()));
+
+    let slice = readback.slice(..);
+    gpu::map_read(device, &[slice])?;
+    let values = bytemuck::cast_slice(&slice.get_mapped_range()).to_vec();
+    readback.unmap();
+    Ok(values)
+}
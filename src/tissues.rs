@@ -0,0 +1,469 @@
+//! Dielectric properties of human tissues (Gabriel et al., 1996).
+//!
+//! Each tissue is a four-term Cole–Cole model
+//!
+//!   ε(ω) = ε∞ + Σ Δεₙ / (1 + (jωτₙ)^(1−αₙ)) + σᵢ / (jωε₀),
+//!
+//! valid from 10 Hz to 100 GHz.  For a simulation the model is reduced
+//! either to the (ε_r, σ) at one frequency, the usual choice for
+//! narrow-band SAR studies, or to a few Debye poles fitted over a band,
+//! which the ADE pass can integrate in time.
+
+use rustfft::num_complex::Complex64;
+use serde::Serialize;
+
+use crate::ade::AdeEdge;
+use crate::grid::Axis;
+use crate::materials::{Coefficients, Material, EPS0};
+
+/// One Cole–Cole dispersion term.
+#[derive(Copy, Clone, Debug)]
+pub struct ColeColeTerm {
+    pub delta: f64,
+    pub tau: f64,
+    pub alpha: f64,
+}
+
+/// Four-term Cole–Cole permittivity with static ionic conductivity.
+#[derive(Copy, Clone, Debug)]
+pub struct ColeCole {
+    pub eps_inf: f64,
+    /// Ionic conductivity σᵢ (S/m).
+    pub sigma: f64,
+    pub terms: [ColeColeTerm; 4],
+}
+
+/// Thermal properties of a tissue or material, for [`crate::thermal`].
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct ThermalProperties {
+    /// Thermal conductivity k (W/m/K).
+    pub conductivity: f64,
+    /// Specific heat capacity c (J/kg/K).
+    pub heat_capacity: f64,
+    /// Mass density ρ (kg/m³).
+    pub density: f64,
+    /// Perfusion heat-sink coefficient ρ_b·c_b·ω (W/m³/K), zero without
+    /// blood flow.
+    pub perfusion: f64,
+}
+
+/// Tissues of the bundled database.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum Tissue {
+    Blood,
+    BoneCancellous,
+    BoneCortical,
+    BrainGreyMatter,
+    BrainWhiteMatter,
+    CerebroSpinalFluid,
+    Fat,
+    Muscle,
+    SkinDry,
+}
+
+const fn term(delta: f64, tau: f64, alpha: f64) -> ColeColeTerm {
+    ColeColeTerm { delta, tau, alpha }
+}
+
+impl Tissue {
+    pub const ALL: [Tissue; 9] = [
+        Tissue::Blood,
+        Tissue::BoneCancellous,
+        Tissue::BoneCortical,
+        Tissue::BrainGreyMatter,
+        Tissue::BrainWhiteMatter,
+        Tissue::CerebroSpinalFluid,
+        Tissue::Fat,
+        Tissue::Muscle,
+        Tissue::SkinDry,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Tissue::Blood => "blood",
+            Tissue::BoneCancellous => "bone (cancellous)",
+            Tissue::BoneCortical => "bone (cortical)",
+            Tissue::BrainGreyMatter => "brain (grey matter)",
+            Tissue::BrainWhiteMatter => "brain (white matter)",
+            Tissue::CerebroSpinalFluid => "cerebro-spinal fluid",
+            Tissue::Fat => "fat",
+            Tissue::Muscle => "muscle",
+            Tissue::SkinDry => "skin (dry)",
+        }
+    }
+
+    /// Cole–Cole parameters (Gabriel, Lau & Gabriel 1996, part III).
+    pub fn cole_cole(self) -> ColeCole {
+        let (eps_inf, sigma, terms) = match self {
+            Tissue::Blood => (
+                4.0,
+                0.7,
+                [
+                    term(56.0, 8.377e-12, 0.10),
+                    term(5200.0, 132.629e-9, 0.10),
+                    term(0.0, 159.155e-6, 0.20),
+                    term(0.0, 15.915e-3, 0.0),
+                ],
+            ),
+            Tissue::BoneCancellous => (
+                2.5,
+                0.07,
+                [
+                    term(18.0, 13.263e-12, 0.22),
+                    term(300.0, 79.577e-9, 0.25),
+                    term(2.0e4, 159.155e-6, 0.20),
+                    term(2.0e7, 15.915e-3, 0.0),
+                ],
+            ),
+            Tissue::BoneCortical => (
+                2.5,
+                0.02,
+                [
+                    term(10.0, 13.263e-12, 0.20),
+                    term(180.0, 79.577e-9, 0.20),
+                    term(5.0e3, 159.155e-6, 0.20),
+                    term(1.0e5, 15.915e-3, 0.0),
+                ],
+            ),
+            Tissue::BrainGreyMatter => (
+                4.0,
+                0.02,
+                [
+                    term(45.0, 7.958e-12, 0.10),
+                    term(400.0, 15.915e-9, 0.15),
+                    term(2.0e5, 106.103e-6, 0.22),
+                    term(4.5e7, 5.305e-3, 0.0),
+                ],
+            ),
+            Tissue::BrainWhiteMatter => (
+                4.0,
+                0.02,
+                [
+                    term(32.0, 7.958e-12, 0.10),
+                    term(100.0, 7.958e-9, 0.10),
+                    term(4.0e4, 53.052e-6, 0.30),
+                    term(3.5e7, 7.958e-3, 0.02),
+                ],
+            ),
+            Tissue::CerebroSpinalFluid => (
+                4.0,
+                2.0,
+                [
+                    term(65.0, 7.958e-12, 0.10),
+                    term(40.0, 1.592e-9, 0.0),
+                    term(0.0, 159.155e-6, 0.0),
+                    term(0.0, 15.915e-3, 0.0),
+                ],
+            ),
+            Tissue::Fat => (
+                2.5,
+                0.01,
+                [
+                    term(3.0, 7.958e-12, 0.20),
+                    term(15.0, 15.915e-9, 0.10),
+                    term(3.3e4, 159.155e-6, 0.05),
+                    term(1.0e7, 7.958e-3, 0.01),
+                ],
+            ),
+            Tissue::Muscle => (
+                4.0,
+                0.2,
+                [
+                    term(50.0, 7.234e-12, 0.10),
+                    term(7000.0, 353.678e-9, 0.10),
+                    term(1.2e6, 318.310e-6, 0.10),
+                    term(2.5e7, 2.274e-3, 0.0),
+                ],
+            ),
+            Tissue::SkinDry => (
+                4.0,
+                0.0002,
+                [
+                    term(32.0, 7.234e-12, 0.0),
+                    term(1100.0, 32.481e-9, 0.20),
+                    term(0.0, 159.155e-6, 0.20),
+                    term(0.0, 15.915e-3, 0.20),
+                ],
+            ),
+        };
+        ColeCole {
+            eps_inf,
+            sigma,
+            terms,
+        }
+    }
+
+    /// Non-dispersive material with the tissue's ε_r and σ at `freq` (Hz).
+    pub fn material(self, freq: f64) -> Material {
+        self.cole_cole().material(freq)
+    }
+
+    /// Thermal conductivity, heat capacity and blood perfusion (IT'IS
+    /// database v4.1), the perfusion rate in ml/min/kg converted to the
+    /// Pennes coefficient ρ_b·c_b·ω with blood's ρ_b and c_b.
+    pub fn thermal(self) -> ThermalProperties {
+        let (conductivity, heat_capacity, rate) = match self {
+            Tissue::Blood => (0.52, 3617.0, 10000.0),
+            Tissue::BoneCancellous => (0.31, 2274.0, 30.0),
+            Tissue::BoneCortical => (0.32, 1313.0, 10.0),
+            Tissue::BrainGreyMatter => (0.55, 3696.0, 763.0),
+            Tissue::BrainWhiteMatter => (0.48, 3583.0, 212.0),
+            Tissue::CerebroSpinalFluid => (0.57, 4096.0, 0.0),
+            Tissue::Fat => (0.21, 2348.0, 33.0),
+            Tissue::Muscle => (0.49, 3421.0, 37.0),
+            Tissue::SkinDry => (0.37, 3391.0, 106.0),
+        };
+        let density = self.density();
+        // ω (1/s) = rate · ρ / (60 s/min · 10⁶ ml/m³)
+        let omega = rate * density / 60e6;
+        ThermalProperties {
+            conductivity,
+            heat_capacity,
+            density,
+            perfusion: Tissue::Blood.density() * 3617.0 * omega,
+        }
+    }
+
+    /// Mass density (kg/m³, IT'IS database v4.1).
+    pub fn density(self) -> f64 {
+        match self {
+            Tissue::Blood => 1050.0,
+            Tissue::BoneCancellous => 1178.0,
+            Tissue::BoneCortical => 1908.0,
+            Tissue::BrainGreyMatter => 1045.0,
+            Tissue::BrainWhiteMatter => 1041.0,
+            Tissue::CerebroSpinalFluid => 1007.0,
+            Tissue::Fat => 911.0,
+            Tissue::Muscle => 1090.0,
+            Tissue::SkinDry => 1109.0,
+        }
+    }
+}
+
+impl ColeCole {
+    /// Relative complex permittivity at `freq` (Hz), conduction included.
+    pub fn permittivity(&self, freq: f64) -> Complex64 {
+        let omega = 2.0 * std::f64::consts::PI * freq;
+        let jw = Complex64::new(0.0, omega);
+        let mut eps = Complex64::new(self.eps_inf, 0.0);
+        for t in self.terms.iter().filter(|t| t.delta != 0.0) {
+            eps += t.delta / ((jw * t.tau).powf(1.0 - t.alpha) + 1.0);
+        }
+        eps + self.sigma / (jw * EPS0)
+    }
+
+    /// (ε_r, effective σ) at `freq`, folding dielectric loss into σ.
+    pub fn material(&self, freq: f64) -> Material {
+        let eps = self.permittivity(freq);
+        let omega = 2.0 * std::f64::consts::PI * freq;
+        Material {
+            eps_r: eps.re,
+            sigma: -eps.im * omega * EPS0,
+            ..Material::VACUUM
+        }
+    }
+
+    /// Least-squares fit of `poles` Debye terms over [`f_min`, `f_max`].
+    ///
+    /// Relaxation times are fixed on a logarithmic grid spanning the band;
+    /// ε∞, σ and the pole strengths are solved for, and poles with negative
+    /// strength (non-passive) are dropped and the fit repeated.
+    pub fn debye_fit(&self, f_min: f64, f_max: f64, poles: usize) -> DebyeModel {
+        const SAMPLES: usize = 64;
+        let two_pi = 2.0 * std::f64::consts::PI;
+        let taus: Vec<f64> = (0..poles)
+            .map(|n| {
+                let s = if poles > 1 {
+                    n as f64 / (poles - 1) as f64
+                } else {
+                    0.5
+                };
+                1.0 / (two_pi * f_min * (f_max / f_min).powf(s))
+            })
+            .collect();
+        let freqs: Vec<f64> = (0..SAMPLES)
+            .map(|n| f_min * (f_max / f_min).powf(n as f64 / (SAMPLES - 1) as f64))
+            .collect();
+
+        let mut active: Vec<f64> = taus;
+        loop {
+            // Unknowns: ε∞, σ, Δε₁…Δε_N.  Rows: Re and Im at each frequency,
+            // weighted by 1/|ε| so every frequency counts equally.
+            let unknowns = active.len() + 2;
+            let mut rows = Vec::with_capacity(2 * SAMPLES);
+            for &f in &freqs {
+                let omega = two_pi * f;
+                let target = self.permittivity(f);
+                let w = 1.0 / target.norm();
+                let mut re = vec![0.0; unknowns + 1];
+                let mut im = vec![0.0; unknowns + 1];
+                re[0] = w;
+                im[1] = -w / (omega * EPS0);
+                for (n, &tau) in active.iter().enumerate() {
+                    let pole = 1.0 / Complex64::new(1.0, omega * tau);
+                    re[n + 2] = w * pole.re;
+                    im[n + 2] = w * pole.im;
+                }
+                re[unknowns] = w * target.re;
+                im[unknowns] = w * target.im;
+                rows.push(re);
+                rows.push(im);
+            }
+            let x = least_squares(&rows, unknowns);
+            if let Some(worst) = (0..active.len())
+                .filter(|&n| x[n + 2] < 0.0)
+                .min_by(|&a, &b| x[a + 2].total_cmp(&x[b + 2]))
+            {
+                active.remove(worst);
+                continue;
+            }
+            return DebyeModel {
+                eps_inf: x[0].max(1.0),
+                sigma: x[1].max(0.0),
+                poles: active
+                    .iter()
+                    .enumerate()
+                    .map(|(n, &tau)| (x[n + 2], tau))
+                    .collect(),
+            };
+        }
+    }
+}
+
+/// How tissue labels of a phantom are turned into update coefficients.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub enum TissueModel {
+    /// Non-dispersive (ε_r, σ) at one frequency (Hz).
+    SingleFrequency(f64),
+    /// Dispersive: `poles` Debye terms fitted over [`f_min`, `f_max`] (Hz).
+    Debye {
+        f_min: f64,
+        f_max: f64,
+        poles: usize,
+    },
+}
+
+/// Multi-pole Debye medium: ε∞ + Σ Δεₙ/(1 + jωτₙ) with conductivity σ.
+#[derive(Clone, Debug)]
+pub struct DebyeModel {
+    pub eps_inf: f64,
+    pub sigma: f64,
+    /// (Δε, τ) per pole.
+    pub poles: Vec<(f64, f64)>,
+}
+
+impl DebyeModel {
+    /// Set edge (`id`, `axis`) to this medium, returning one ADE current
+    /// per pole.
+    pub fn apply_edge(
+        &self,
+        coeffs: &mut Coefficients,
+        id: usize,
+        axis: Axis,
+        dt: f64,
+    ) -> Vec<AdeEdge> {
+        coeffs.set_e_material(id, axis, self.eps_inf * EPS0, self.sigma, dt);
+        self.poles
+            .iter()
+            .map(|&(delta, tau)| AdeEdge::debye(coeffs, id, axis, delta, tau, dt))
+            .collect()
+    }
+}
+
+/// Solve the overdetermined system `rows` (each `unknowns` coefficients
+/// followed by the right-hand side) through its normal equations.
+fn least_squares(rows: &[Vec<f64>], unknowns: usize) -> Vec<f64> {
+    let n = unknowns;
+    let mut a = vec![vec![0.0; n + 1]; n];
+    for row in rows {
+        for i in 0..n {
+            for j in 0..=n {
+                a[i][j] += row[i] * row[j];
+            }
+        }
+    }
+    // Gaussian elimination with partial pivoting.
+    for c in 0..n {
+        let p = (c..n)
+            .max_by(|&x, &y| a[x][c].abs().total_cmp(&a[y][c].abs()))
+            .unwrap();
+        a.swap(c, p);
+        let pivot_row = a[c].clone();
+        if pivot_row[c].abs() < 1e-300 {
+            continue;
+        }
+        for (r, row) in a.iter_mut().enumerate() {
+            if r != c {
+                let f = row[c] / pivot_row[c];
+                for (x, p) in row[c..].iter_mut().zip(&pivot_row[c..]) {
+                    *x -= f * p;
+                }
+            }
+        }
+    }
+    (0..n)
+        .map(|i| {
+            if a[i][i].abs() < 1e-300 {
+                0.0
+            } else {
+                a[i][n] / a[i][i]
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cole_cole_matches_gabriels_tables() {
+        // (tissue, Hz, ε_r, σ in S/m) as tabulated from the parametric model
+        let table = [
+            (Tissue::Blood, 900e6, 61.36, 1.538),
+            (Tissue::BrainGreyMatter, 900e6, 52.73, 0.942),
+            (Tissue::Fat, 900e6, 5.462, 0.0510),
+            (Tissue::Muscle, 900e6, 55.03, 0.943),
+            (Tissue::SkinDry, 900e6, 41.41, 0.867),
+            (Tissue::Muscle, 2.45e9, 52.73, 1.739),
+            (Tissue::CerebroSpinalFluid, 2.45e9, 66.24, 3.458),
+        ];
+        for (tissue, f, eps_r, sigma) in table {
+            let m = tissue.material(f);
+            let name = tissue.name();
+            assert!(
+                (m.eps_r / eps_r - 1.0).abs() < 2e-3,
+                "{name}: ε_r {}",
+                m.eps_r
+            );
+            assert!(
+                (m.sigma / sigma - 1.0).abs() < 4e-3,
+                "{name}: σ {}",
+                m.sigma
+            );
+        }
+    }
+
+    #[test]
+    fn debye_fits_follow_the_cole_cole_model_over_the_band() {
+        let (f_min, f_max) = (300e6, 3e9);
+        for tissue in Tissue::ALL {
+            let cole_cole = tissue.cole_cole();
+            let fit = cole_cole.debye_fit(f_min, f_max, 3);
+            assert!(fit.eps_inf >= 1.0 && fit.sigma >= 0.0);
+            assert!(fit.poles.iter().all(|&(delta, _)| delta >= 0.0));
+            for n in 0..=10 {
+                let f = f_min * (f_max / f_min).powf(n as f64 / 10.0);
+                let omega = 2.0 * std::f64::consts::PI * f;
+                let conduction = fit.sigma / Complex64::new(0.0, omega * EPS0);
+                let eps = fit.poles.iter().fold(
+                    Complex64::new(fit.eps_inf, 0.0) + conduction,
+                    |eps, &(delta, tau)| eps + delta / Complex64::new(1.0, omega * tau),
+                );
+                let target = cole_cole.permittivity(f);
+                let error = (eps - target).norm() / target.norm();
+                assert!(error < 0.06, "{} at {f:e} Hz: {error}", tissue.name());
+            }
+        }
+    }
+}
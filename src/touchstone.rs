@@ -0,0 +1,319 @@
+//! Touchstone (.sNp) export of port S-matrices.
+//!
+//! Writes Touchstone 1.1 files readable by microwave CAD tools and
+//! scikit-rf: an option line `# <unit> S <format> R <ohms>`, then one
+//! record per frequency.  Two-port data is in the column order N11 N21 N12
+//! N22 the format prescribes; larger matrices go row by row, four pairs per
+//! line.  When the reference impedance differs from the ports' Z₀ the
+//! matrix is renormalized through its impedance matrix,
+//!
+//!   Z = √Z₀ (1 − S)⁻¹ (1 + S) √Z₀,   S' = (Z − R)(Z + R)⁻¹.
+
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use rustfft::num_complex::Complex64;
+use serde::Serialize;
+
+use crate::ports::SMatrix;
+
+/// Frequency unit of the file.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum FrequencyUnit {
+    Hz,
+    KHz,
+    MHz,
+    GHz,
+}
+
+impl FrequencyUnit {
+    fn keyword(self) -> &'static str {
+        match self {
+            FrequencyUnit::Hz => "HZ",
+            FrequencyUnit::KHz => "KHZ",
+            FrequencyUnit::MHz => "MHZ",
+            FrequencyUnit::GHz => "GHZ",
+        }
+    }
+
+    fn scale(self) -> f64 {
+        match self {
+            FrequencyUnit::Hz => 1.0,
+            FrequencyUnit::KHz => 1e3,
+            FrequencyUnit::MHz => 1e6,
+            FrequencyUnit::GHz => 1e9,
+        }
+    }
+}
+
+/// Number pair format of the file.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum DataFormat {
+    /// Real and imaginary part.
+    RealImaginary,
+    /// Magnitude and angle (degrees).
+    MagnitudeAngle,
+    /// Magnitude in dB and angle (degrees).
+    DbAngle,
+}
+
+impl DataFormat {
+    fn keyword(self) -> &'static str {
+        match self {
+            DataFormat::RealImaginary => "RI",
+            DataFormat::MagnitudeAngle => "MA",
+            DataFormat::DbAngle => "DB",
+        }
+    }
+
+    fn pair(self, s: Complex64) -> (f64, f64) {
+        match self {
+            DataFormat::RealImaginary => (s.re, s.im),
+            DataFormat::MagnitudeAngle => (s.norm(), s.arg().to_degrees()),
+            DataFormat::DbAngle => (
+                20.0 * s.norm().max(f64::MIN_POSITIVE).log10(),
+                s.arg().to_degrees(),
+            ),
+        }
+    }
+}
+
+/// Touchstone output options.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct Touchstone {
+    pub unit: FrequencyUnit,
+    pub format: DataFormat,
+    /// Reference impedance of the file (Ω).
+    pub reference: f64,
+}
+
+/// The n×n identity, row-major.
+fn identity(n: usize) -> Vec<Complex64> {
+    (0..n * n)
+        .map(|ij| Complex64::new(if ij / n == ij % n { 1.0 } else { 0.0 }, 0.0))
+        .collect()
+}
+
+/// Inverse of the n×n row-major matrix `m` by Gauss–Jordan elimination with
+/// partial pivoting.
+fn invert(m: &[Complex64], n: usize) -> Vec<Complex64> {
+    let mut a = m.to_vec();
+    let mut inv = identity(n);
+    for col in 0..n {
+        let pivot = (col..n)
+            .max_by(|&p, &q| a[p * n + col].norm().total_cmp(&a[q * n + col].norm()))
+            .unwrap();
+        for k in 0..n {
+            a.swap(col * n + k, pivot * n + k);
+            inv.swap(col * n + k, pivot * n + k);
+        }
+        let d = a[col * n + col];
+        for k in 0..n {
+            a[col * n + k] /= d;
+            inv[col * n + k] /= d;
+        }
+        let (a_col, inv_col) = (a[col * n..][..n].to_vec(), inv[col * n..][..n].to_vec());
+        for row in (0..n).filter(|&r| r != col) {
+            let factor = a[row * n + col];
+            for k in 0..n {
+                a[row * n + k] -= factor * a_col[k];
+                inv[row * n + k] -= factor * inv_col[k];
+            }
+        }
+    }
+    inv
+}
+
+/// Product of two n×n row-major matrices.
+fn multiply(a: &[Complex64], b: &[Complex64], n: usize) -> Vec<Complex64> {
+    (0..n * n)
+        .map(|ij| (0..n).map(|k| a[ij / n * n + k] * b[k * n + ij % n]).sum())
+        .collect()
+}
+
+/// S renormalized from the ports' impedances `z0` to `reference`.
+fn renormalize(s: &[Complex64], z0: &[f64], reference: f64) -> Vec<Complex64> {
+    let n = z0.len();
+    let one = identity(n);
+    let minus: Vec<_> = (0..n * n).map(|ij| one[ij] - s[ij]).collect();
+    let plus: Vec<_> = (0..n * n).map(|ij| one[ij] + s[ij]).collect();
+    let z: Vec<_> = multiply(&invert(&minus, n), &plus, n)
+        .iter()
+        .enumerate()
+        .map(|(ij, x)| x * (z0[ij / n] * z0[ij % n]).sqrt())
+        .collect();
+    let numerator: Vec<_> = (0..n * n).map(|ij| z[ij] - one[ij] * reference).collect();
+    let denominator: Vec<_> = (0..n * n).map(|ij| z[ij] + one[ij] * reference).collect();
+    multiply(&numerator, &invert(&denominator, n), n)
+}
+
+/// Write `<dir>/sparams.s<N>p` from the matrix `m` of ports with impedances
+/// `z0`.
+pub fn write(dir: &Path, m: &SMatrix, z0: &[f64], options: &Touchstone) -> io::Result<PathBuf> {
+    let n = m.ports;
+    let path = dir.join(format!("sparams.s{n}p"));
+    let mut file = BufWriter::new(std::fs::File::create(&path)?);
+    writeln!(file, "! {n}-port S-parameters from the FDTD port runs")?;
+    writeln!(
+        file,
+        "# {} S {} R {}",
+        options.unit.keyword(),
+        options.format.keyword(),
+        options.reference
+    )?;
+    let same = z0.iter().all(|&z| z == options.reference);
+    for (f, s) in m.frequencies.iter().zip(&m.s) {
+        let s = if same {
+            s.clone()
+        } else {
+            renormalize(s, z0, options.reference)
+        };
+        // Two-port files list N11 N21 N12 N22
+        let order: Vec<usize> = if n == 2 {
+            vec![0, 2, 1, 3]
+        } else {
+            (0..n * n).collect()
+        };
+        write!(file, "{:e}", f / options.unit.scale())?;
+        for (k, &ij) in order.iter().enumerate() {
+            let (x, y) = options.format.pair(s[ij]);
+            // Larger matrices start each row on a new line, four pairs a line
+            if n > 2 && k > 0 && (k % n).is_multiple_of(4) {
+                write!(file, "\n ")?;
+            }
+            write!(file, " {x:e} {y:e}")?;
+        }
+        writeln!(file)?;
+    }
+    file.flush()?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn c(re: f64, im: f64) -> Complex64 {
+        Complex64::new(re, im)
+    }
+
+    fn close(a: &[Complex64], b: &[Complex64]) -> bool {
+        a.iter().zip(b).all(|(x, y)| (x - y).norm() < 1e-12)
+    }
+
+    /// A reciprocal, lossy two-port.
+    fn two_port() -> Vec<Complex64> {
+        vec![c(0.2, -0.1), c(0.7, 0.3), c(0.7, 0.3), c(-0.1, 0.25)]
+    }
+
+    /// The numbers after the option line of `path`.
+    fn numbers(path: &Path) -> (String, Vec<f64>) {
+        let text = std::fs::read_to_string(path).unwrap();
+        let option = text.lines().find(|l| l.starts_with('#')).unwrap();
+        let values = text
+            .lines()
+            .filter(|l| !l.starts_with(['!', '#']))
+            .flat_map(str::split_whitespace)
+            .map(|x| x.parse().unwrap())
+            .collect();
+        (option.to_string(), values)
+    }
+
+    #[test]
+    fn inverse_times_matrix_is_the_identity() {
+        let m = vec![
+            c(0.0, 1.0),
+            c(2.0, 0.0),
+            c(1.0, -1.0),
+            c(3.0, 0.5),
+            c(0.0, 0.0),
+            c(1.0, 0.0),
+            c(-1.0, 2.0),
+            c(0.5, 0.5),
+            c(4.0, 0.0),
+        ];
+        assert!(close(&multiply(&invert(&m, 3), &m, 3), &identity(3)));
+        assert!(close(&multiply(&m, &invert(&m, 3), 3), &identity(3)));
+    }
+
+    #[test]
+    fn renormalizing_to_another_reference_and_back_keeps_s() {
+        let s = two_port();
+        assert!(close(&renormalize(&s, &[50.0; 2], 50.0), &s));
+        let at_75 = renormalize(&s, &[50.0; 2], 75.0);
+        assert!(!close(&at_75, &s));
+        assert!(close(&renormalize(&at_75, &[75.0; 2], 50.0), &s));
+        // reciprocity survives
+        assert!((at_75[1] - at_75[2]).norm() < 1e-12);
+    }
+
+    #[test]
+    fn a_matched_load_reflects_at_another_reference() {
+        // 50 Ω seen from 75 Ω: (50 − 75) / (50 + 75)
+        let s = renormalize(&[c(0.0, 0.0)], &[50.0], 75.0);
+        assert!((s[0] - c(-0.2, 0.0)).norm() < 1e-12);
+    }
+
+    #[test]
+    fn two_port_records_list_s21_before_s12() {
+        let s = vec![c(0.1, 0.0), c(0.2, 0.0), c(0.3, 0.0), c(0.4, 0.0)];
+        let m = SMatrix {
+            ports: 2,
+            frequencies: vec![1e9, 2.5e9],
+            s: vec![s.clone(), s],
+        };
+        let options = Touchstone {
+            unit: FrequencyUnit::GHz,
+            format: DataFormat::RealImaginary,
+            reference: 50.0,
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let path = write(dir.path(), &m, &[50.0; 2], &options).unwrap();
+        assert_eq!(path.file_name().unwrap(), "sparams.s2p");
+        let (option, values) = numbers(&path);
+        assert_eq!(option, "# GHZ S RI R 50");
+        let record = [1.0, 0.1, 0.0, 0.3, 0.0, 0.2, 0.0, 0.4, 0.0];
+        assert_eq!(values[..9], record);
+        assert_eq!(values[9], 2.5);
+    }
+
+    #[test]
+    fn larger_matrices_wrap_rows_four_pairs_a_line() {
+        let n = 5;
+        let s: Vec<Complex64> = (0..n * n).map(|ij| c(ij as f64 / 100.0, 0.0)).collect();
+        let m = SMatrix {
+            ports: n,
+            frequencies: vec![3e6],
+            s: vec![s],
+        };
+        let options = Touchstone {
+            unit: FrequencyUnit::MHz,
+            format: DataFormat::MagnitudeAngle,
+            reference: 50.0,
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let path = write(dir.path(), &m, &[50.0; 5], &options).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        // each row of five pairs takes a line of four and one of one
+        let records: Vec<&str> = text.lines().skip(2).collect();
+        assert_eq!(records.len(), 2 * n);
+        assert_eq!(records[0].split_whitespace().count(), 1 + 2 * 4);
+        assert_eq!(records[1].split_whitespace().count(), 2);
+        let (_, values) = numbers(&path);
+        assert_eq!(values[0], 3.0);
+        // row by row: the magnitudes come out in S11, S12, … order
+        let magnitudes: Vec<f64> = values[1..].iter().step_by(2).copied().collect();
+        assert_eq!(
+            magnitudes,
+            (0..n * n).map(|ij| ij as f64 / 100.0).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn db_angle_pairs() {
+        let (db, angle) = DataFormat::DbAngle.pair(c(0.0, -0.5));
+        assert!((db + 6.020_599_913_279_624).abs() < 1e-12);
+        assert!((angle + 90.0).abs() < 1e-12);
+        assert!(DataFormat::DbAngle.pair(c(0.0, 0.0)).0.is_finite());
+    }
+}
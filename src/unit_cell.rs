@@ -0,0 +1,226 @@
+//! Metasurface unit-cell transmission and reflection coefficients.
+//!
+//! The grid is one period of the surface: the two axes across `normal` are
+//! periodic (see [`crate::gpu::GpuParams::with_periodic`]), and a uniform
+//! electric current sheet on the E plane `source` launches a normally
+//! incident plane wave polarised along `polarization` in both directions
+//! along the normal, of E amplitude `amplitude`·waveform.  The cell is
+//! simulated twice, as for [`crate::reflectance`]: a reference run with the
+//! sheet alone and the run with the structure.  From the plane averages of
+//! E_pol (the specular, zeroth diffraction order) on the E planes `front`
+//! and `back` either side of the surface,
+//!
+//!   t(f) = E(back) / E_inc(back),
+//!   r(f) = (E(front) − E_inc(front)) / E_inc(front) · e^{2jk(z_s − z_front)}
+//!
+//! with the phase of r moved from the front plane to the surface plane z_s
+//! (E plane `surface`), where the phase of t refers to as well.
+//! `<name>_unit_cell.csv` holds both as real/imaginary parts, magnitude and
+//! phase in degrees per frequency.  The order is source < front < surface
+//! < back along the normal, all in vacuum.
+//!
+//! With PEC walls along the normal the run has to end before the waves
+//! reflected by the end walls return to the planes; the sheet's backward
+//! wave is one of them, so the source wants a few wavelengths' room below
+//! the front plane.
+
+use std::f64::consts::PI;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use rustfft::num_complex::Complex64;
+use serde::Serialize;
+
+use crate::ade::AdeEdge;
+use crate::config::ConfigError;
+use crate::dft::{DftMonitor, Region, Spectrum};
+use crate::grid::{Axis, Field, Grid};
+use crate::materials::{Coefficients, EPS0, MU0};
+use crate::ntff::eta0;
+use crate::sources::Waveform;
+
+/// A unit cell lit at normal incidence along +`normal`.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct UnitCell {
+    pub name: &'static str,
+    pub normal: Axis,
+    /// E planes of the source sheet, the reflection plane, the surface
+    /// (phase reference) and the transmission plane.
+    pub source: u32,
+    pub front: u32,
+    pub surface: u32,
+    pub back: u32,
+    /// Direction of the incident E, across the normal.
+    pub polarization: Axis,
+    /// Incident E amplitude (V/m) and waveform.
+    pub amplitude: f64,
+    pub waveform: Waveform,
+    /// Frequencies in Hz.
+    pub frequencies: &'static [f64],
+}
+
+/// Driven `polarization` edges of a uniform current sheet filling the E
+/// plane `index` normal to `normal`, launching a plane wave of E = drive
+/// `slot` both ways.  A sheet current K radiates E = −η₀K/2 each way, so
+/// the edges get −CB·K/Δ' = 2·CB/(η₀Δ')·E_inc on top of the update.
+pub fn sheet_edges(
+    grid: &Grid,
+    coeffs: &Coefficients,
+    normal: Axis,
+    index: u32,
+    polarization: Axis,
+    slot: u32,
+) -> Vec<AdeEdge> {
+    let width = grid.dual_width(normal, index);
+    let (u, v) = normal.tangential();
+    let mut edges = Vec::new();
+    for b in 0..grid.cells(v) {
+        for a in 0..grid.cells(u) {
+            let mut cell = [0; 3];
+            (cell[u.lane()], cell[v.lane()]) = (a, b);
+            cell[normal.lane()] = index;
+            let id = grid.idx(cell[0], cell[1], cell[2]);
+            let cb = coeffs.cb[id][polarization.lane()] as f64;
+            let dj = 2.0 * cb / (eta0() * width);
+            edges.push(AdeEdge::driven(id, polarization, slot, dj));
+        }
+    }
+    edges
+}
+
+/// Complex transmission and reflection coefficients at one frequency.
+#[derive(Copy, Clone, Debug)]
+pub struct Coefficient {
+    pub t: Complex64,
+    pub r: Complex64,
+}
+
+impl UnitCell {
+    /// Check the polarisation and the order of the planes; the monitors
+    /// check that the planes lie on the grid.
+    pub fn check(&self) -> Result<(), ConfigError> {
+        let problem = if self.polarization == self.normal {
+            "the polarisation must be across the normal"
+        } else if !(self.source < self.front
+            && self.front < self.surface
+            && self.surface < self.back)
+        {
+            "planes must run source < front < surface < back"
+        } else {
+            return Ok(());
+        };
+        Err(ConfigError(format!("unit cell {}: {problem}", self.name)))
+    }
+
+    /// The periodic axes.
+    pub fn periodic(&self) -> [Axis; 2] {
+        let (u, v) = self.normal.tangential();
+        [u, v]
+    }
+
+    /// Driven E edges of the source sheet, appending its drive to `drives`.
+    pub fn source_edges(
+        &self,
+        grid: &Grid,
+        coeffs: &Coefficients,
+        drives: &mut Vec<(f64, Waveform)>,
+    ) -> Vec<AdeEdge> {
+        drives.push((self.amplitude, self.waveform));
+        let slot = (drives.len() - 1) as u32;
+        sheet_edges(
+            grid,
+            coeffs,
+            self.normal,
+            self.source,
+            self.polarization,
+            slot,
+        )
+    }
+
+    /// DFT monitors of E_pol on the front and back planes.
+    pub fn dft_monitors(&self) -> Vec<DftMonitor> {
+        let fields: &'static [Field] = match self.polarization {
+            Axis::X => &[Field::E(Axis::X)],
+            Axis::Y => &[Field::E(Axis::Y)],
+            Axis::Z => &[Field::E(Axis::Z)],
+        };
+        [self.front, self.back]
+            .map(|index| DftMonitor {
+                name: self.name,
+                fields,
+                region: Region::Plane {
+                    normal: self.normal,
+                    index,
+                },
+                frequencies: self.frequencies,
+            })
+            .to_vec()
+    }
+
+    /// Plane average of E_pol at frequency `f`.
+    fn average(&self, spectrum: &Spectrum, f: usize) -> Complex64 {
+        let cells = spectrum.cells();
+        let sum: Complex64 = (0..cells)
+            .map(|c| {
+                let [re, im] = spectrum.value(Field::E(self.polarization), f, c).unwrap();
+                Complex64::new(re as f64, im as f64)
+            })
+            .sum();
+        sum / cells as f64
+    }
+
+    /// t and r per frequency from the [`dft_monitors`](Self::dft_monitors)
+    /// spectra of the reference and structure runs.
+    pub fn coefficients(
+        &self,
+        grid: &Grid,
+        reference: &[Spectrum],
+        structure: &[Spectrum],
+    ) -> Vec<Coefficient> {
+        let distance = grid.node(self.normal, self.surface) - grid.node(self.normal, self.front);
+        let c0 = 1.0 / (MU0 * EPS0).sqrt();
+        (0..self.frequencies.len())
+            .map(|f| {
+                let k = 2.0 * PI * self.frequencies[f] / c0;
+                let incident = [0, 1].map(|p| self.average(&reference[p], f));
+                let [front, back] = [0, 1].map(|p| self.average(&structure[p], f));
+                Coefficient {
+                    t: back / incident[1],
+                    r: (front - incident[0]) / incident[0]
+                        * Complex64::from_polar(1.0, 2.0 * k * distance),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Write `<name>_unit_cell.csv` from the coefficients of the cell.
+pub fn write_coefficients(
+    dir: &Path,
+    cell: &UnitCell,
+    coefficients: &[Coefficient],
+) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!("{}_unit_cell.csv", cell.name));
+    let mut file = BufWriter::new(fs::File::create(path)?);
+    writeln!(
+        file,
+        "frequency,t_re,t_im,t_abs,t_phase_deg,r_re,r_im,r_abs,r_phase_deg"
+    )?;
+    for (frequency, c) in cell.frequencies.iter().zip(coefficients) {
+        write!(file, "{frequency:e}")?;
+        for x in [c.t, c.r] {
+            write!(
+                file,
+                ",{:e},{:e},{:.6},{:.3}",
+                x.re,
+                x.im,
+                x.norm(),
+                x.arg().to_degrees()
+            )?;
+        }
+        writeln!(file)?;
+    }
+    file.flush()
+}
@@ -0,0 +1,251 @@
+//! VTK XML output for ParaView and VisIt.
+//!
+//! Per-cell arrays are written as `ImageData` (`.vti`) on uniform grids and
+//! as `RectilinearGrid` (`.vtr`, with the graded node coordinates) when any
+//! axis is graded, both with raw appended binary data.  The extent covers
+//! the grid's nodes, so each value is cell data of cell (i, j, k); field
+//! components are the raw Yee samples of that cell, not interpolated to its
+//! centre.  Origin and spacing are in metres.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::grid::{Axis, Grid};
+use crate::materials::{Coefficients, EPS0, MU0};
+
+/// Write per-cell `arrays` (name, one value per cell in grid order) to
+/// `dir/stem.vti` or `dir/stem.vtr`, with `time` as the ParaView time value
+/// when given.  Returns the path written.
+pub fn write_cells(
+    dir: &Path,
+    stem: &str,
+    grid: &Grid,
+    time: Option<f64>,
+    arrays: &[(&str, &[f32])],
+) -> io::Result<PathBuf> {
+    let rectilinear = grid.graded.iter().any(Option::is_some);
+    let (kind, ext) = if rectilinear {
+        ("RectilinearGrid", "vtr")
+    } else {
+        ("ImageData", "vti")
+    };
+    let extent = format!("0 {} 0 {} 0 {}", grid.nx, grid.ny, grid.nz);
+
+    // Appended blocks: UInt64 byte count, then the raw little-endian values
+    let mut blocks: Vec<Vec<u8>> = arrays
+        .iter()
+        .map(|(_, values)| bytemuck::cast_slice(values).to_vec())
+        .collect();
+    let coordinates: Vec<Vec<f64>> = [Axis::X, Axis::Y, Axis::Z]
+        .iter()
+        .map(|&axis| (0..=grid.cells(axis)).map(|i| grid.node(axis, i)).collect())
+        .collect();
+    if rectilinear {
+        blocks.extend(coordinates.iter().map(|c| bytemuck::cast_slice(c).to_vec()));
+    }
+    let mut offsets = Vec::with_capacity(blocks.len());
+    let mut offset = 0;
+    for block in &blocks {
+        offsets.push(offset);
+        offset += 8 + block.len();
+    }
+
+    let mut xml = String::new();
+    writeln!(xml, r#"<?xml version="1.0"?>"#).unwrap();
+    writeln!(
+        xml,
+        r#"<VTKFile type="{kind}" version="1.0" byte_order="LittleEndian" header_type="UInt64">"#
+    )
+    .unwrap();
+    if rectilinear {
+        writeln!(xml, r#"  <{kind} WholeExtent="{extent}">"#).unwrap();
+    } else {
+        let [ox, oy, oz] = grid.origin;
+        let (dx, dy, dz) = (grid.dx, grid.dy, grid.dz);
+        writeln!(
+            xml,
+            r#"  <{kind} WholeExtent="{extent}" Origin="{ox:e} {oy:e} {oz:e}" Spacing="{dx:e} {dy:e} {dz:e}">"#
+        )
+        .unwrap();
+    }
+    if let Some(t) = time {
+        writeln!(xml, "    <FieldData>").unwrap();
+        writeln!(
+            xml,
+            r#"      <DataArray type="Float64" Name="TimeValue" NumberOfTuples="1" format="ascii">{t:e}</DataArray>"#
+        )
+        .unwrap();
+        writeln!(xml, "    </FieldData>").unwrap();
+    }
+    writeln!(xml, r#"    <Piece Extent="{extent}">"#).unwrap();
+    writeln!(xml, "      <CellData>").unwrap();
+    for ((name, _), offset) in arrays.iter().zip(&offsets) {
+        writeln!(
+            xml,
+            r#"        <DataArray type="Float32" Name="{name}" format="appended" offset="{offset}"/>"#
+        )
+        .unwrap();
+    }
+    writeln!(xml, "      </CellData>").unwrap();
+    if rectilinear {
+        writeln!(xml, "      <Coordinates>").unwrap();
+        for (name, offset) in ["x", "y", "z"].iter().zip(&offsets[arrays.len()..]) {
+            writeln!(
+                xml,
+                r#"        <DataArray type="Float64" Name="{name}" format="appended" offset="{offset}"/>"#
+            )
+            .unwrap();
+        }
+        writeln!(xml, "      </Coordinates>").unwrap();
+    }
+    writeln!(xml, "    </Piece>").unwrap();
+    writeln!(xml, "  </{kind}>").unwrap();
+    write!(xml, r#"  <AppendedData encoding="raw">"#).unwrap();
+    write!(xml, "\n   _").unwrap();
+
+    let path = dir.join(format!("{stem}.{ext}"));
+    let mut file = io::BufWriter::new(fs::File::create(&path)?);
+    file.write_all(xml.as_bytes())?;
+    for block in &blocks {
+        file.write_all(&(block.len() as u64).to_le_bytes())?;
+        file.write_all(block)?;
+    }
+    file.write_all(b"\n  </AppendedData>\n</VTKFile>\n")?;
+    file.flush()?;
+    Ok(path)
+}
+
+/// Per-cell material maps recovered from the coefficients: relative
+/// permittivity and permeability and the two conductivities, averaged over
+/// the cell's three edges of each kind, and the number of PEC E edges.
+pub fn material_arrays(grid: &Grid, coeffs: &Coefficients) -> Vec<(&'static str, Vec<f32>)> {
+    let cells: Vec<[f32; 5]> = (0..grid.total())
+        .map(|id| {
+            let (mut e, mut h) = (Vec::new(), Vec::new());
+            for axis in [Axis::X, Axis::Y, Axis::Z] {
+                e.extend(coeffs.e_material(id, axis, grid.dt));
+                h.extend(coeffs.h_material(id, axis, grid.dt));
+            }
+            let mean = |v: &[(f64, f64)], f: fn(&(f64, f64)) -> f64| {
+                if v.is_empty() {
+                    0.0
+                } else {
+                    (v.iter().map(f).sum::<f64>() / v.len() as f64) as f32
+                }
+            };
+            [
+                mean(&e, |m| m.0 / EPS0),
+                mean(&e, |m| m.1),
+                mean(&h, |m| m.0 / MU0),
+                mean(&h, |m| m.1),
+                (3 - e.len()) as f32,
+            ]
+        })
+        .collect();
+    ["eps_r", "sigma", "mu_r", "sigma_m", "pec_edges"]
+        .into_iter()
+        .enumerate()
+        .map(|(q, name)| (name, cells.iter().map(|c| c[q]).collect()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::Material;
+
+    /// The XML header and the appended blocks of a file, each without its
+    /// byte count.
+    fn read(path: &Path) -> (String, Vec<Vec<u8>>) {
+        let file = fs::read(path).unwrap();
+        let marker = b"<AppendedData encoding=\"raw\">\n   _";
+        let data = file
+            .windows(marker.len())
+            .position(|w| w == marker)
+            .unwrap()
+            + marker.len();
+        let header = String::from_utf8(file[..data].to_vec()).unwrap();
+        let mut blocks = Vec::new();
+        let mut at = data;
+        while !file[at..].starts_with(b"\n  </AppendedData>") {
+            let len = u64::from_le_bytes(file[at..at + 8].try_into().unwrap()) as usize;
+            blocks.push(file[at + 8..at + 8 + len].to_vec());
+            at += 8 + len;
+        }
+        (header, blocks)
+    }
+
+    fn f32s(block: &[u8]) -> Vec<f32> {
+        block
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn uniform_grids_are_image_data_with_appended_arrays() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut grid = Grid::uniform([2, 3, 4], 1e-3, 1e-12);
+        grid.origin = [0.5, 0.0, 0.0];
+        let ramp: Vec<f32> = (0..24).map(|n| n as f32).collect();
+        let ones = vec![1.0; 24];
+        let arrays: [(&str, &[f32]); 2] = [("Ez", &ramp), ("ones", &ones)];
+        let path = write_cells(dir.path(), "step", &grid, Some(2e-9), &arrays).unwrap();
+        assert_eq!(path, dir.path().join("step.vti"));
+        let (header, blocks) = read(&path);
+        assert!(header.contains(r#"<ImageData WholeExtent="0 2 0 3 0 4" Origin="5e-1 0e0 0e0" Spacing="1e-3 1e-3 1e-3">"#));
+        assert!(header.contains(r#"Name="TimeValue" NumberOfTuples="1" format="ascii">2e-9<"#));
+        assert!(header.contains(r#"Name="Ez" format="appended" offset="0""#));
+        assert!(header.contains(r#"Name="ones" format="appended" offset="104""#));
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(f32s(&blocks[0]), ramp);
+        assert_eq!(f32s(&blocks[1]), ones);
+    }
+
+    #[test]
+    fn graded_grids_are_rectilinear_with_their_nodes() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut grid = Grid::uniform([2, 1, 1], 1e-3, 1e-12);
+        grid.graded[0] = Some(&[1e-3, 2e-3]);
+        let path = write_cells(dir.path(), "step", &grid, None, &[("Ez", &[3.0, 4.0])]).unwrap();
+        assert_eq!(path, dir.path().join("step.vtr"));
+        let (header, blocks) = read(&path);
+        assert!(header.contains(r#"<RectilinearGrid WholeExtent="0 2 0 1 0 1">"#));
+        assert!(!header.contains("TimeValue"));
+        assert_eq!(blocks.len(), 4);
+        assert_eq!(f32s(&blocks[0]), [3.0, 4.0]);
+        let x: Vec<f64> = blocks[1]
+            .chunks_exact(8)
+            .map(|b| f64::from_le_bytes(b.try_into().unwrap()))
+            .collect();
+        assert_eq!(x, [0.0, 1e-3, 3e-3]);
+    }
+
+    #[test]
+    fn material_maps_recover_the_cell_materials() {
+        let grid = Grid::uniform([2, 2, 2], 1e-3, 1e-12);
+        let material = Material {
+            eps_r: 4.0,
+            sigma: 0.1,
+            mu_r: 2.0,
+            sigma_m: 0.0,
+        };
+        let coeffs = Coefficients::uniform(&grid, &material);
+        for (name, values) in material_arrays(&grid, &coeffs) {
+            let expected = match name {
+                "eps_r" => 4.0,
+                "sigma" => 0.1,
+                "mu_r" => 2.0,
+                _ => 0.0,
+            };
+            for v in values {
+                assert!(
+                    (v - expected).abs() < 1e-3 * expected.max(1.0),
+                    "{name}: {v}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,181 @@
+//! Browser build: the solver on WebGPU, driven from JavaScript.
+//!
+//! Built for `wasm32-unknown-unknown` (e.g. `wasm-pack build --target web`),
+//! the crate exports [`WebSimulation`]: a scene in the JSON form of
+//! [`crate::scene_file`], set up without blocking the page, stepped in
+//! chunks and streamed to callbacks.
+//!
+//! ```js
+//! const sim = await WebSimulation.create(JSON.stringify(scene));
+//! sim.stream("Ez", 10, (step, ez) => draw(ez, sim.size()));
+//! while (sim.steps_taken() < 1000) await sim.advance(50);
+//! ```
+//!
+//! The browser cannot wait for the GPU, so every copy back is awaited
+//! instead of polled.  Only the field reads of this module are; scenes with
+//! outputs read back during the steps (probes, ports, monitors, flux,
+//! energy, snapshots, circuits, non-f32 precisions) or a setup script are
+//! rejected by [`WebSimulation::create`], and the [`crate::divergence`]
+//! check is switched off.  Each `advance` records its steps back to back,
+//! so the page stays responsive between calls, not within one.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use js_sys::{Float32Array, Function, Promise};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::future_to_promise;
+
+use crate::backend::ComputeBackend;
+use crate::config::{Config, Scene};
+use crate::grid::Field;
+use crate::precision::Precision;
+use crate::scene_file::SceneFile;
+use crate::simulation::Simulation;
+
+/// A component every `every` steps to a JS function of (steps, values).
+struct Stream {
+    field: Field,
+    every: u32,
+    callback: Function,
+}
+
+/// A 3D run in the browser.
+#[wasm_bindgen]
+pub struct WebSimulation {
+    simulation: Rc<RefCell<Simulation>>,
+    streams: Rc<RefCell<Vec<Stream>>>,
+}
+
+#[wasm_bindgen]
+impl WebSimulation {
+    /// Check the JSON scene and open the browser's WebGPU device.
+    pub async fn create(scene: String) -> Result<WebSimulation, JsError> {
+        let scene = SceneFile::from_json(&scene)?;
+        if scene.script.is_some() {
+            return Err(JsError::new("setup scripts are not read in the browser"));
+        }
+        scene.check()?;
+        let mut config = scene.builder()?.build_config()?;
+        // The divergence check reads back every so often, which is not
+        // awaited; the scene cannot ask for it, so it is left out
+        config.divergence = None;
+        let blocking = blocking_outputs(&config);
+        if !blocking.is_empty() {
+            return Err(JsError::new(&format!(
+                "read back during the steps, which the browser cannot wait for: {}",
+                blocking.join(", ")
+            )));
+        }
+        let simulation = Simulation::new_async(config, Scene::Structure).await?;
+        Ok(WebSimulation {
+            simulation: Rc::new(RefCell::new(simulation)),
+            streams: Rc::default(),
+        })
+    }
+
+    /// Call `callback(steps, values)` with a copy of `field` ("Ex" … "Hz",
+    /// x fastest) after every `every`-th step of [`WebSimulation::advance`].
+    pub fn stream(&self, field: &str, every: u32, callback: Function) -> Result<(), JsError> {
+        if every == 0 {
+            return Err(JsError::new("stream every one step or more"));
+        }
+        let field = parse_field(field)?;
+        self.streams.borrow_mut().push(Stream {
+            field,
+            every,
+            callback,
+        });
+        Ok(())
+    }
+
+    /// Take `steps` steps, streaming as they come; resolves to the steps
+    /// taken so far.  Rejects while another `advance` is under way.
+    // The borrow held across the reads is what marks the run busy
+    #[allow(clippy::await_holding_refcell_ref)]
+    pub fn advance(&self, steps: u32) -> Promise {
+        let (simulation, streams) = (self.simulation.clone(), self.streams.clone());
+        future_to_promise(async move {
+            let mut simulation = simulation
+                .try_borrow_mut()
+                .map_err(|_| JsError::new("the simulation is busy"))?;
+            for _ in 0..steps {
+                simulation.step().map_err(JsError::from)?;
+                let n = simulation.steps_taken();
+                let due: Vec<(Field, Function)> = streams
+                    .borrow()
+                    .iter()
+                    .filter(|s| n.is_multiple_of(s.every))
+                    .map(|s| (s.field, s.callback.clone()))
+                    .collect();
+                for (field, callback) in due {
+                    let values = simulation.field_async(field).await.map_err(JsError::from)?;
+                    let values = Float32Array::from(&values[..]);
+                    callback.call2(&JsValue::NULL, &n.into(), &values)?;
+                }
+            }
+            Ok(simulation.steps_taken().into())
+        })
+    }
+
+    /// Resolves to a copy of `field` ("Ex" … "Hz"), x fastest.
+    #[allow(clippy::await_holding_refcell_ref)]
+    pub fn read_field(&self, field: &str) -> Result<Promise, JsError> {
+        let field = parse_field(field)?;
+        let simulation = self.simulation.clone();
+        Ok(future_to_promise(async move {
+            let simulation = simulation
+                .try_borrow()
+                .map_err(|_| JsError::new("the simulation is busy"))?;
+            let values = simulation.field_async(field).await.map_err(JsError::from)?;
+            Ok(Float32Array::from(&values[..]).into())
+        }))
+    }
+
+    /// Steps taken so far.
+    pub fn steps_taken(&self) -> u32 {
+        self.simulation.borrow().steps_taken()
+    }
+
+    /// Time of the E fields (s).
+    pub fn time(&self) -> f64 {
+        self.simulation.borrow().time()
+    }
+
+    /// Cells per axis, [nx, ny, nz].
+    pub fn size(&self) -> Vec<u32> {
+        let grid = *self.simulation.borrow().grid();
+        vec![grid.nx, grid.ny, grid.nz]
+    }
+}
+
+fn parse_field(name: &str) -> Result<Field, JsError> {
+    Field::ALL
+        .into_iter()
+        .find(|f| f.name().eq_ignore_ascii_case(name))
+        .ok_or_else(|| JsError::new(&format!("unknown field {name:?} (Ex … Hz)")))
+}
+
+/// The outputs of `config` that would block on a copy back mid-run.
+fn blocking_outputs(config: &Config) -> Vec<&'static str> {
+    [
+        ("probes", !config.probes.is_empty()),
+        ("ports", !config.ports.is_empty()),
+        ("monitors", !config.monitors.is_empty()),
+        (
+            "flux monitors",
+            !config.flux_monitors.is_empty() || !config.flux_boxes.is_empty(),
+        ),
+        ("energy", config.energy_every.is_some()),
+        ("snapshots", config.snapshots.is_some()),
+        ("slice images", config.slice_images.is_some()),
+        ("circuits", !config.circuits.is_empty()),
+        (
+            "precision",
+            config.precision != Precision::F32 || config.compare_f32,
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(name, used)| used.then_some(name))
+    .collect()
+}
@@ -0,0 +1,95 @@
+//! Thin-wire sub-cell model (Umashankar–Taflove).
+//!
+//! A perfectly conducting wire of radius r₀ < Δ runs along a line of E
+//! edges.  Those edges are forced to zero, and the four H edges circling the
+//! wire assume the 1/r near-field variation inside their Faraday loop, which
+//! scales the E-difference across the wire by w = 2/ln(Δ/r₀).  The scaling
+//! is applied as an H correction of (w − 1) times the regular term.
+
+use serde::Serialize;
+
+use crate::config::ConfigError;
+use crate::corrections::HCorrections;
+use crate::grid::{Axis, Grid};
+use crate::materials::Coefficients;
+
+/// Straight thin wire along `axis`.
+///
+/// `at` gives the wire's cell coordinates on the two tangential axes (in
+/// [`Axis::tangential`] order) and `span` the half-open range of E edges it
+/// covers along `axis`.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct ThinWire {
+    pub axis: Axis,
+    pub at: (u32, u32),
+    pub span: (u32, u32),
+    /// Wire radius r₀ (m), smaller than the transverse cell size.
+    pub radius: f64,
+}
+
+impl ThinWire {
+    /// Check the wire lies on the grid and is thinner than its cell.
+    pub fn check(&self, grid: &Grid) -> Result<(), ConfigError> {
+        let a = self.axis;
+        let (b, c) = a.tangential();
+        let (b0, c0) = self.at;
+        let fail = |problem: String| Err(ConfigError(format!("thin wire along {a:?}: {problem}")));
+        if b0 >= grid.cells(b) || c0 >= grid.cells(c) {
+            return fail(format!("{:?} is outside the grid", self.at));
+        }
+        let cell = grid.width(b, b0).min(grid.width(c, c0));
+        if !(self.radius > 0.0 && self.radius < cell) {
+            return fail(format!(
+                "the radius of {} m must lie between 0 and the cell's {cell} m",
+                self.radius
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn apply(&self, grid: &Grid, coeffs: &mut Coefficients, corrections: &mut HCorrections) {
+        let a = self.axis;
+        let (b, c) = a.tangential();
+        let (b0, c0) = self.at;
+        let (db, dc) = (grid.width(b, b0), grid.width(c, c0));
+        let wb = 2.0 / (db / self.radius).ln() - 1.0;
+        let wc = 2.0 / (dc / self.radius).ln() - 1.0;
+
+        let at = |s: u32, pb: u32, pc: u32| -> usize {
+            let mut p = [0; 3];
+            p[a.lane()] = s;
+            p[b.lane()] = pb;
+            p[c.lane()] = pc;
+            grid.idx(p[0], p[1], p[2])
+        };
+
+        for s in self.span.0..self.span.1.min(grid.cells(a)) {
+            // The wire itself: PEC edge.
+            coeffs.set_e(at(s, b0, c0), a, 0.0, 0.0);
+
+            // H_c on either side along b carries +∂E_a/∂b.
+            if b0 + 1 < grid.cells(b) {
+                let h = at(s, b0, c0);
+                let cq = coeffs.cq[h][c.lane()] as f64;
+                corrections.add(h, c, at(s, b0 + 1, c0), a, cq * wb / db);
+            }
+            if b0 > 0 {
+                let h = at(s, b0 - 1, c0);
+                let cq = coeffs.cq[h][c.lane()] as f64;
+                corrections.add(h, c, at(s, b0 - 1, c0), a, -cq * wb / db);
+            }
+
+            // H_b on either side along c carries −∂E_a/∂c.
+            if c0 + 1 < grid.cells(c) {
+                let h = at(s, b0, c0);
+                let cq = coeffs.cq[h][b.lane()] as f64;
+                corrections.add(h, b, at(s, b0, c0 + 1), a, -cq * wc / dc);
+            }
+            if c0 > 0 {
+                let h = at(s, b0, c0 - 1);
+                let cq = coeffs.cq[h][b.lane()] as f64;
+                corrections.add(h, b, at(s, b0, c0 - 1), a, cq * wc / dc);
+            }
+        }
+    }
+}
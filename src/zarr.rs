@@ -0,0 +1,327 @@
+//! Zarr v3 store for time series of full volumes.
+//!
+//! The store is a directory with a root group and one array per field
+//! component of shape (t, z, y, x), chunked as (1, cz, cy, cx), plus the
+//! coordinate arrays `t`, `z`, `y` and `x` (lower cell faces in metres and E
+//! times in seconds).  Every array names its dimensions, so
+//! `xarray.open_zarr(dir)` opens the whole series lazily.  Chunks are
+//! uncompressed little-endian (`bytes` codec) with edge chunks zero-padded,
+//! and each append writes the new step's chunks before rewriting the array
+//! metadata with the longer shape, so a reader never sees a step whose
+//! chunks are missing.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::grid::{Axis, Field, Grid};
+
+/// Entries per chunk of the growing `t` coordinate.
+const TIME_CHUNK: usize = 64;
+
+/// Metadata of one array (`zarr.json`).
+fn array_json(
+    shape: &[usize],
+    chunks: &[usize],
+    data_type: &str,
+    dimensions: &[&str],
+    attributes: &str,
+) -> String {
+    let list = |v: &[usize]| {
+        v.iter()
+            .map(usize::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let names = dimensions
+        .iter()
+        .map(|d| format!("\"{d}\""))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut json = String::new();
+    writeln!(json, "{{").unwrap();
+    writeln!(json, "  \"zarr_format\": 3,").unwrap();
+    writeln!(json, "  \"node_type\": \"array\",").unwrap();
+    writeln!(json, "  \"shape\": [{}],", list(shape)).unwrap();
+    writeln!(json, "  \"data_type\": \"{data_type}\",").unwrap();
+    writeln!(
+        json,
+        "  \"chunk_grid\": {{\"name\": \"regular\", \"configuration\": {{\"chunk_shape\": [{}]}}}},",
+        list(chunks)
+    )
+    .unwrap();
+    writeln!(
+        json,
+        "  \"chunk_key_encoding\": {{\"name\": \"default\", \"configuration\": {{\"separator\": \"/\"}}}},"
+    )
+    .unwrap();
+    writeln!(json, "  \"fill_value\": 0.0,").unwrap();
+    writeln!(
+        json,
+        "  \"codecs\": [{{\"name\": \"bytes\", \"configuration\": {{\"endian\": \"little\"}}}}],"
+    )
+    .unwrap();
+    writeln!(json, "  \"attributes\": {{{attributes}}},").unwrap();
+    writeln!(json, "  \"dimension_names\": [{names}]").unwrap();
+    writeln!(json, "}}").unwrap();
+    json
+}
+
+/// Write `bytes` to `path`, creating its parent directories.
+fn write_file(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, bytes)
+}
+
+/// Appendable Zarr v3 store of selected field components.
+pub struct ZarrStore {
+    root: PathBuf,
+    fields: &'static [Field],
+    /// Grid size as (nz, ny, nx).
+    dims: [usize; 3],
+    /// Chunk size as (cz, cy, cx).
+    chunk: [usize; 3],
+    times: Vec<f64>,
+}
+
+impl ZarrStore {
+    /// Create the store at `root` with the given (x, y, z) chunk size,
+    /// clamped to the grid, and write the spatial coordinates.
+    pub fn create(
+        root: &Path,
+        grid: &Grid,
+        fields: &'static [Field],
+        chunk: [u32; 3],
+    ) -> io::Result<Self> {
+        fs::create_dir_all(root)?;
+        write_file(
+            &root.join("zarr.json"),
+            b"{\"zarr_format\": 3, \"node_type\": \"group\", \"attributes\": {}}\n",
+        )?;
+        for (name, axis) in [("x", Axis::X), ("y", Axis::Y), ("z", Axis::Z)] {
+            let nodes: Vec<f64> = (0..grid.cells(axis)).map(|i| grid.node(axis, i)).collect();
+            let dir = root.join(name);
+            let json = array_json(
+                &[nodes.len()],
+                &[nodes.len()],
+                "float64",
+                &[name],
+                "\"units\": \"m\"",
+            );
+            write_file(&dir.join("zarr.json"), json.as_bytes())?;
+            write_file(&dir.join("c").join("0"), bytemuck::cast_slice(&nodes))?;
+        }
+        let dims = [grid.nz, grid.ny, grid.nx].map(|n| n as usize);
+        let [cx, cy, cz] = chunk.map(|c| c.max(1) as usize);
+        let store = ZarrStore {
+            root: root.to_path_buf(),
+            fields,
+            dims,
+            chunk: [cz.min(dims[0]), cy.min(dims[1]), cx.min(dims[2])],
+            times: Vec::new(),
+        };
+        store.write_metadata()?;
+        Ok(store)
+    }
+
+    /// Append one step at time `t`; `values` holds one volume per selected
+    /// component, x fastest.
+    pub fn append(&mut self, t: f64, values: &[&[f32]]) -> io::Result<()> {
+        let step = self.times.len();
+        let [nz, ny, nx] = self.dims;
+        let [cz, cy, cx] = self.chunk;
+        let mut buffer = vec![0.0_f32; cz * cy * cx];
+        for (field, volume) in self.fields.iter().zip(values) {
+            let dir = self
+                .root
+                .join(field.name())
+                .join("c")
+                .join(step.to_string());
+            for kz in 0..nz.div_ceil(cz) {
+                for ky in 0..ny.div_ceil(cy) {
+                    for kx in 0..nx.div_ceil(cx) {
+                        buffer.fill(0.0);
+                        let x0 = kx * cx;
+                        let width = cx.min(nx - x0);
+                        for z in 0..cz.min(nz - kz * cz) {
+                            for y in 0..cy.min(ny - ky * cy) {
+                                let from = x0 + nx * (ky * cy + y + ny * (kz * cz + z));
+                                let to = cx * (y + cy * z);
+                                buffer[to..to + width].copy_from_slice(&volume[from..from + width]);
+                            }
+                        }
+                        let path = dir
+                            .join(kz.to_string())
+                            .join(ky.to_string())
+                            .join(kx.to_string());
+                        write_file(&path, bytemuck::cast_slice(&buffer))?;
+                    }
+                }
+            }
+        }
+
+        // Rewrite the last chunk of `t`, zero-padded
+        self.times.push(t);
+        let first = step / TIME_CHUNK * TIME_CHUNK;
+        let mut chunk = self.times[first..].to_vec();
+        chunk.resize(TIME_CHUNK, 0.0);
+        let path = self
+            .root
+            .join("t")
+            .join("c")
+            .join((step / TIME_CHUNK).to_string());
+        write_file(&path, bytemuck::cast_slice(&chunk))?;
+        self.write_metadata()
+    }
+
+    /// Write the metadata of `t` and of every component at the current
+    /// number of steps.
+    fn write_metadata(&self) -> io::Result<()> {
+        let steps = self.times.len();
+        let json = array_json(
+            &[steps],
+            &[TIME_CHUNK],
+            "float64",
+            &["t"],
+            "\"units\": \"s\"",
+        );
+        write_file(&self.root.join("t").join("zarr.json"), json.as_bytes())?;
+        let [nz, ny, nx] = self.dims;
+        let [cz, cy, cx] = self.chunk;
+        for field in self.fields {
+            let units = match field {
+                Field::E(_) => "V/m",
+                Field::H(_) => "A/m",
+            };
+            let json = array_json(
+                &[steps, nz, ny, nx],
+                &[1, cz, cy, cx],
+                "float32",
+                &["t", "z", "y", "x"],
+                &format!("\"units\": \"{units}\""),
+            );
+            write_file(
+                &self.root.join(field.name()).join("zarr.json"),
+                json.as_bytes(),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(path: &Path) -> serde_json::Value {
+        serde_json::from_str(&fs::read_to_string(path.join("zarr.json")).unwrap()).unwrap()
+    }
+
+    fn floats(path: &Path) -> Vec<f32> {
+        let bytes = fs::read(path).unwrap();
+        bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+            .collect()
+    }
+
+    fn doubles(path: &Path) -> Vec<f64> {
+        let bytes = fs::read(path).unwrap();
+        bytes
+            .chunks_exact(8)
+            .map(|b| f64::from_le_bytes(b.try_into().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn chunks_reassemble_into_the_appended_volumes() {
+        let grid = Grid::uniform([5, 4, 3], 1e-3, 1e-12);
+        let fields: &[Field] = &[Field::E(Axis::Z), Field::H(Axis::X)];
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = ZarrStore::create(dir.path(), &grid, fields, [2, 3, 8]).unwrap();
+        let volume = |step: usize, f: usize| -> Vec<f32> {
+            (0..grid.total())
+                .map(|i| (1000 * step + 100 * f + i) as f32)
+                .collect()
+        };
+        for step in 0..3 {
+            let ez = volume(step, 0);
+            let hx = volume(step, 1);
+            store.append(step as f64 * 1e-12, &[&ez, &hx]).unwrap();
+        }
+
+        for (f, field) in fields.iter().enumerate() {
+            let array = dir.path().join(field.name());
+            let meta = metadata(&array);
+            assert_eq!(meta["shape"], serde_json::json!([3, 3, 4, 5]));
+            // the z chunk is clamped to the grid
+            let chunk = &meta["chunk_grid"]["configuration"]["chunk_shape"];
+            assert_eq!(*chunk, serde_json::json!([1, 3, 3, 2]));
+            assert_eq!(
+                meta["dimension_names"],
+                serde_json::json!(["t", "z", "y", "x"])
+            );
+            for step in 0..3 {
+                let expected = volume(step, f);
+                for z in 0..3 {
+                    for y in 0..4 {
+                        for x in 0..5 {
+                            let path = array
+                                .join("c")
+                                .join(step.to_string())
+                                .join("0")
+                                .join((y / 3).to_string())
+                                .join((x / 2).to_string());
+                            let chunk = floats(&path);
+                            assert_eq!(chunk.len(), 3 * 3 * 2);
+                            let value = chunk[x % 2 + 2 * (y % 3 + 3 * z)];
+                            assert_eq!(value, expected[grid.idx(x as u32, y as u32, z as u32)]);
+                        }
+                    }
+                }
+            }
+        }
+        // the corner chunk holds only x = 4, y = 3 and is zero-padded past it
+        let edge = floats(&dir.path().join("Ez/c/0/0/1/2"));
+        for (i, &v) in edge.iter().enumerate() {
+            assert_eq!(v == 0.0, i % 6 != 0, "entry {i}");
+        }
+    }
+
+    #[test]
+    fn coordinates_name_the_cell_faces_and_times() {
+        let grid = Grid::uniform([3, 2, 2], 0.5, 0.25);
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = ZarrStore::create(dir.path(), &grid, &[Field::E(Axis::X)], [4; 3]).unwrap();
+        assert_eq!(metadata(dir.path())["node_type"], "group");
+        assert_eq!(
+            metadata(&dir.path().join("t"))["shape"],
+            serde_json::json!([0])
+        );
+
+        let volume = vec![0.0; grid.total()];
+        for step in 0..TIME_CHUNK + 2 {
+            store.append(step as f64 * 0.25, &[&volume]).unwrap();
+        }
+        assert_eq!(doubles(&dir.path().join("x/c/0")), [0.0, 0.5, 1.0]);
+        assert_eq!(doubles(&dir.path().join("z/c/0")), [0.0, 0.5]);
+        let t = dir.path().join("t");
+        assert_eq!(metadata(&t)["shape"], serde_json::json!([TIME_CHUNK + 2]));
+        assert_eq!(metadata(&t)["attributes"]["units"], "s");
+        let first = doubles(&t.join("c/0"));
+        assert_eq!(first.len(), TIME_CHUNK);
+        assert_eq!(first[TIME_CHUNK - 1], (TIME_CHUNK - 1) as f64 * 0.25);
+        let second = doubles(&t.join("c/1"));
+        assert_eq!(
+            second[..3],
+            [
+                TIME_CHUNK as f64 * 0.25,
+                (TIME_CHUNK + 1) as f64 * 0.25,
+                0.0
+            ]
+        );
+    }
+}